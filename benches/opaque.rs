@@ -10,6 +10,7 @@
 extern crate criterion;
 
 use criterion::Criterion;
+use generic_array::typenum::U0;
 use opaque_ke::*;
 use rand::rngs::OsRng;
 
@@ -26,6 +27,7 @@ impl CipherSuite for Default {
     type KeGroup = opaque_ke::Ristretto255;
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
     type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(not(feature = "ristretto255"))]
@@ -34,6 +36,7 @@ impl CipherSuite for Default {
     type KeGroup = p256::NistP256;
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
     type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 fn server_setup(c: &mut Criterion) {
@@ -41,7 +44,7 @@ fn server_setup(c: &mut Criterion) {
 
     c.bench_function(&format!("server setup ({SUFFIX})"), move |b| {
         b.iter(|| {
-            ServerSetup::<Default>::new(&mut rng);
+            ServerSetup::<Default>::new(&mut rng).unwrap();
         })
     });
 }
@@ -61,7 +64,7 @@ fn server_registration_start(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
 
@@ -81,7 +84,7 @@ fn client_registration_finish(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
     let server_registration_start_result = ServerRegistration::<Default>::start(
@@ -114,7 +117,7 @@ fn server_registration_finish(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
     let server_registration_start_result = ServerRegistration::<Default>::start(
@@ -158,7 +161,7 @@ fn server_login_start_real(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
     let server_registration_start_result = ServerRegistration::<Default>::start(
@@ -198,7 +201,7 @@ fn server_login_start_fake(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_login_start_result = ClientLogin::<Default>::start(&mut rng, password).unwrap();
 
     c.bench_function(&format!("server login start (fake) ({SUFFIX})"), move |b| {
@@ -220,7 +223,7 @@ fn client_login_finish(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
     let server_registration_start_result = ServerRegistration::<Default>::start(
@@ -269,7 +272,7 @@ fn server_login_finish(c: &mut Criterion) {
     let mut rng = OsRng;
     let username = b"username";
     let password = b"password";
-    let server_setup = ServerSetup::<Default>::new(&mut rng);
+    let server_setup = ServerSetup::<Default>::new(&mut rng).unwrap();
     let client_registration_start_result =
         ClientRegistration::<Default>::start(&mut rng, password).unwrap();
     let server_registration_start_result = ServerRegistration::<Default>::start(
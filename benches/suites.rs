@@ -0,0 +1,77 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+#[macro_use]
+extern crate criterion;
+
+use criterion::Criterion;
+use generic_array::typenum::U0;
+use opaque_ke::bench::bench_suite;
+use opaque_ke::key_exchange::tripledh::TripleDh;
+use opaque_ke::ksf::Identity;
+use opaque_ke::CipherSuite;
+use rand::rngs::OsRng;
+
+#[cfg(feature = "ristretto255")]
+struct Ristretto255;
+
+#[cfg(feature = "ristretto255")]
+impl CipherSuite for Ristretto255 {
+    type OprfCs = opaque_ke::Ristretto255;
+    type KeGroup = opaque_ke::Ristretto255;
+    type KeyExchange = TripleDh;
+    type Ksf = Identity;
+    type AppDataLen = U0;
+}
+
+struct P256;
+
+impl CipherSuite for P256 {
+    type OprfCs = p256::NistP256;
+    type KeGroup = p256::NistP256;
+    type KeyExchange = TripleDh;
+    type Ksf = Identity;
+    type AppDataLen = U0;
+}
+
+struct P384;
+
+impl CipherSuite for P384 {
+    type OprfCs = p384::NistP384;
+    type KeGroup = p384::NistP384;
+    type KeyExchange = TripleDh;
+    type Ksf = Identity;
+    type AppDataLen = U0;
+}
+
+struct P521;
+
+impl CipherSuite for P521 {
+    type OprfCs = p521::NistP521;
+    type KeGroup = p521::NistP521;
+    type KeyExchange = TripleDh;
+    type Ksf = Identity;
+    type AppDataLen = U0;
+}
+
+fn suites(c: &mut Criterion) {
+    #[cfg(feature = "ristretto255")]
+    bench_suite::<Ristretto255, _>(c, "ristretto255", &mut OsRng);
+    bench_suite::<P256, _>(c, "p256", &mut OsRng);
+    bench_suite::<P384, _>(c, "p384", &mut OsRng);
+    bench_suite::<P521, _>(c, "p521", &mut OsRng);
+    #[cfg(all(feature = "argon2", feature = "ristretto255-voprf"))]
+    bench_suite::<opaque_ke::suites::Ristretto255Sha512Argon2id, _>(
+        c,
+        "ristretto255 argon2id",
+        &mut OsRng,
+    );
+}
+
+criterion_group!(suite_benches, suites);
+criterion_main!(suite_benches);
@@ -30,6 +30,7 @@ use std::process::exit;
 
 use chacha20poly1305::aead::{Aead, KeyInit};
 use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use generic_array::typenum::U0;
 use generic_array::GenericArray;
 use opaque_ke::ciphersuite::CipherSuite;
 use opaque_ke::rand::rngs::OsRng;
@@ -55,6 +56,7 @@ impl CipherSuite for DefaultCipherSuite {
     type KeGroup = opaque_ke::Ristretto255;
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
     type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(not(feature = "ristretto255"))]
@@ -63,6 +65,7 @@ impl CipherSuite for DefaultCipherSuite {
     type KeGroup = p256::NistP256;
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
     type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 struct Locker {
@@ -216,7 +219,7 @@ fn open_locker(
 
 fn main() {
     let mut rng = OsRng;
-    let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng);
+    let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng).unwrap();
 
     let mut rl = Editor::<(), _>::new().unwrap();
     let mut registered_lockers: Vec<Locker> = vec![];
@@ -26,6 +26,7 @@ use std::collections::HashMap;
 use std::process::exit;
 
 use argon2::Argon2;
+use generic_array::typenum::U0;
 use generic_array::GenericArray;
 use opaque_ke::ciphersuite::CipherSuite;
 use opaque_ke::rand::rngs::OsRng;
@@ -51,6 +52,7 @@ impl CipherSuite for DefaultCipherSuite {
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 
     type Ksf = Argon2<'static>;
+    type AppDataLen = U0;
 }
 
 #[cfg(not(feature = "ristretto255"))]
@@ -60,6 +62,7 @@ impl CipherSuite for DefaultCipherSuite {
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 
     type Ksf = Argon2<'static>;
+    type AppDataLen = U0;
 }
 
 // Password-based registration between a client and server
@@ -160,7 +163,7 @@ fn account_login(
 
 fn main() {
     let mut rng = OsRng;
-    let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng);
+    let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng).unwrap();
 
     let mut rl = Editor::<(), _>::new().unwrap();
     let mut registered_users =
@@ -0,0 +1,38 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opaque_ke::fuzzing::*;
+use opaque_ke::CipherSuite;
+
+struct Default;
+
+impl CipherSuite for Default {
+    type OprfCs = opaque_ke::Ristretto255;
+    type KeGroup = opaque_ke::Ristretto255;
+    type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+    type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = generic_array::typenum::U0;
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+
+    match selector % 6 {
+        0 => fuzz_registration_request_roundtrip::<Default>(data),
+        1 => fuzz_registration_response_roundtrip::<Default>(data),
+        2 => fuzz_registration_upload_roundtrip::<Default>(data),
+        3 => fuzz_credential_request_roundtrip::<Default>(data),
+        4 => fuzz_credential_response_roundtrip::<Default>(data),
+        _ => fuzz_credential_finalization_roundtrip::<Default>(data),
+    }
+});
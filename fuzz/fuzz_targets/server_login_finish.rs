@@ -0,0 +1,30 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opaque_ke::fuzzing::fuzz_server_login_finish;
+use opaque_ke::CipherSuite;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+struct Default;
+
+impl CipherSuite for Default {
+    type OprfCs = opaque_ke::Ristretto255;
+    type KeGroup = opaque_ke::Ristretto255;
+    type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+    type Ksf = opaque_ke::ksf::Identity;
+    type AppDataLen = generic_array::typenum::U0;
+}
+
+fuzz_target!(|data: &[u8]| {
+    // A fixed seed keeps every run reproducible from its input alone
+    fuzz_server_login_finish::<Default, _>(&mut StdRng::seed_from_u64(0), data);
+});
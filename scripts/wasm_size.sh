@@ -0,0 +1,22 @@
+#!/bin/sh
+# Builds the client-only WebAssembly module in wasm-client/ and fails if it
+# grew beyond the threshold, e.g. because server code or a large dependency
+# became part of the client build. Raise the threshold deliberately when a
+# client feature requires it.
+
+set -eu
+
+MAX_SIZE=120000
+
+cd "$(dirname "$0")/../wasm-client"
+cargo build --release --target wasm32-unknown-unknown
+
+WASM=target/wasm32-unknown-unknown/release/opaque_ke_wasm_client.wasm
+SIZE=$(wc -c < "$WASM")
+
+echo "$WASM: $SIZE bytes (maximum $MAX_SIZE)"
+
+if [ "$SIZE" -gt "$MAX_SIZE" ]; then
+    echo "error: the client WebAssembly module exceeds $MAX_SIZE bytes" >&2
+    exit 1
+fi
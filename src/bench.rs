@@ -0,0 +1,259 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Benchmarks of each protocol phase for a [`CipherSuite`], available with the
+//! `bench` feature
+//!
+//! [`bench_suite`] registers the benchmarks with [`criterion`], so that
+//! applications can measure their chosen suite and KSF parameters on their
+//! own hardware:
+//!
+//! ```ignore
+//! fn benches(c: &mut criterion::Criterion) {
+//!     opaque_ke::bench::bench_suite::<YourCipherSuite, _>(c, "your suite", &mut OsRng);
+//! }
+//!
+//! criterion::criterion_group!(suite_benches, benches);
+//! criterion::criterion_main!(suite_benches);
+//! ```
+
+use core::iter;
+use core::ops::Add;
+
+use criterion::Criterion;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::{ArrayLength, GenericArray};
+use rand::{CryptoRng, RngCore};
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::envelope::{Envelope, EnvelopeLen};
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{KeyExchange, MacLen, SuiteProvider};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::keypair::{KeyPair, SecretKey};
+use crate::opaque::MaskedResponseLen;
+use crate::provider::Provider;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, EnvelopeMode, Identifiers, ServerLogin,
+    ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+const PASSWORD: &[u8] = b"password";
+const CREDENTIAL_IDENTIFIER: &[u8] = b"credential identifier";
+
+/// Registers benchmarks for the OPRF, the envelope, the key exchange and full
+/// registration and login round trips of `CS` in a group called `name`
+///
+/// The round trips use the default parameters of `CS::Ksf`, so they show the
+/// cost of the configured key stretching function.
+pub fn bench_suite<CS: CipherSuite, R: CryptoRng + RngCore>(
+    c: &mut Criterion,
+    name: &str,
+    rng: &mut R,
+) where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+{
+    let mut group = c.benchmark_group(name);
+
+    // OPRF
+
+    let blind_result = voprf::OprfClient::<CS::OprfCs>::blind(PASSWORD, rng).unwrap();
+    let oprf_server = voprf::OprfServer::<CS::OprfCs>::new(rng).unwrap();
+    let evaluation_element = oprf_server.blind_evaluate(&blind_result.message);
+
+    group.bench_function("oprf blind", |b| {
+        b.iter(|| voprf::OprfClient::<CS::OprfCs>::blind(PASSWORD, rng).unwrap())
+    });
+    group.bench_function("oprf evaluate", |b| {
+        b.iter(|| oprf_server.blind_evaluate(&blind_result.message))
+    });
+    group.bench_function("oprf finalize", |b| {
+        b.iter(|| {
+            blind_result
+                .state
+                .finalize(PASSWORD, &evaluation_element)
+                .unwrap()
+        })
+    });
+
+    // Envelope
+
+    let (_, randomized_pwd_hasher) = SuiteProvider::<CS>::extract(None, &[PASSWORD]);
+    let server_keypair = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(rng).unwrap();
+    let app_data = GenericArray::default();
+    let envelope = Envelope::<CS>::seal(
+        rng,
+        &randomized_pwd_hasher,
+        server_keypair.public(),
+        Identifiers::default(),
+        &app_data,
+        None,
+        None,
+        None,
+    )
+    .unwrap()
+    .0;
+
+    group.bench_function("envelope seal", |b| {
+        b.iter(|| {
+            Envelope::<CS>::seal(
+                rng,
+                &randomized_pwd_hasher,
+                server_keypair.public(),
+                Identifiers::default(),
+                &app_data,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("envelope open", |b| {
+        b.iter(|| {
+            envelope
+                .open(
+                    &randomized_pwd_hasher,
+                    server_keypair.public().clone(),
+                    Identifiers::default(),
+                    EnvelopeMode::Internal,
+                    None,
+                )
+                .unwrap()
+        })
+    });
+
+    // Key exchange
+
+    let client_keypair = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(rng).unwrap();
+    let (ke1_state, ke1_message) = CS::KeyExchange::generate_ke1::<CS::OprfCs, _>(rng).unwrap();
+    let generate_ke2 = |rng: &mut R| {
+        CS::KeyExchange::generate_ke2::<CS::OprfCs, _, _>(
+            rng,
+            iter::empty(),
+            iter::empty(),
+            ke1_message.clone(),
+            Some(client_keypair.public().clone()),
+            server_keypair.private().clone(),
+            iter::empty(),
+            iter::empty(),
+            &[],
+            None,
+        )
+        .unwrap()
+    };
+    let ke2_message = generate_ke2(rng).1;
+    let client_s_dh = client_keypair
+        .private()
+        .diffie_hellman(CS::KeyExchange::client_s_dh_pk(&ke2_message))
+        .unwrap();
+
+    group.bench_function("key exchange ke1", |b| {
+        b.iter(|| CS::KeyExchange::generate_ke1::<CS::OprfCs, _>(rng).unwrap())
+    });
+    group.bench_function("key exchange ke2", |b| b.iter(|| generate_ke2(rng)));
+    group.bench_function("key exchange ke3", |b| {
+        b.iter(|| {
+            CS::KeyExchange::generate_ke3(
+                iter::empty(),
+                ke2_message.clone(),
+                &ke1_state,
+                iter::empty(),
+                server_keypair.public().clone(),
+                Some(client_s_dh.clone()),
+                iter::empty(),
+                iter::empty(),
+                &[],
+            )
+            .unwrap()
+        })
+    });
+
+    // Round trips
+
+    let server_setup = ServerSetup::<CS>::new(rng).unwrap();
+
+    group.bench_function("registration", |b| b.iter(|| register(rng, &server_setup)));
+
+    let password_file = register(rng, &server_setup);
+
+    group.bench_function("login", |b| {
+        b.iter(|| {
+            let client_login_start_result = ClientLogin::<CS>::start(rng, PASSWORD).unwrap();
+            let server_login_start_result = ServerLogin::start(
+                rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                CREDENTIAL_IDENTIFIER,
+                ServerLoginStartParameters::default(),
+            )
+            .unwrap();
+            let client_login_finish_result = client_login_start_result
+                .state
+                .finish(
+                    PASSWORD,
+                    server_login_start_result.message,
+                    ClientLoginFinishParameters::default(),
+                )
+                .unwrap();
+            server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn register<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    server_setup: &ServerSetup<CS>,
+) -> ServerRegistration<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let client_registration_start_result = ClientRegistration::<CS>::start(rng, PASSWORD).unwrap();
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        server_setup,
+        client_registration_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+    )
+    .unwrap();
+    let client_registration_finish_result = client_registration_start_result
+        .state
+        .finish(
+            rng,
+            PASSWORD,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+        .unwrap();
+
+    ServerRegistration::finish(client_registration_finish_result.message)
+}
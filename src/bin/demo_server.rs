@@ -0,0 +1,300 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! A reference credential server, built with the `demo-server` feature
+//!
+//! It serves registration and login with the `Ristretto255Sha512Argon2id`
+//! suite over HTTP at the paths of [`opaque_ke::web::paths`], and stores the
+//! [`ServerSetup`] and the password files in a sled database, so that it can
+//! serve as a peer for interoperability tests of clients, e.g. of the `mobile`
+//! or `node` bindings:
+//!
+//! ```text
+//! cargo run --features demo-server --bin opaque-demo-server -- 127.0.0.1:8080 demo.db
+//! ```
+//!
+//! Every request carries the credential identifier in the
+//! `opaque-credential-identifier` header and the message of the client as
+//! body. It demonstrates
+//!
+//! - dummy credential responses for unregistered credential identifiers, with
+//!   the processing time of logins padded to a [`LatencyTarget`], so that
+//!   responses don't reveal whether an identifier is registered,
+//! - throttling of login attempts with [`ThrottleToken`]s, which are returned
+//!   in the `opaque-throttle-token` header of the credential response and have
+//!   to be echoed with the next credential request of the identifier, and
+//! - versioned password files, which are upgraded as they are read.
+//!
+//! The state of a login is kept in memory between the start and the finish,
+//! under the session returned in the `opaque-session` header. It is not meant
+//! for production use: logins which are never finished are never evicted, and
+//! the server speaks plain HTTP.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use opaque_ke::rand::rngs::OsRng;
+use opaque_ke::rand::RngCore;
+use opaque_ke::suites::Ristretto255Sha512Argon2id;
+use opaque_ke::throttle::ThrottleToken;
+use opaque_ke::timing::LatencyTarget;
+use opaque_ke::web::{paths, Opaque};
+use opaque_ke::{
+    CredentialFinalization, CredentialRequest, RegistrationRequest, RegistrationUpload,
+    ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+type Suite = Ristretto255Sha512Argon2id;
+
+const CREDENTIAL_IDENTIFIER: &str = "opaque-credential-identifier";
+const SESSION: &str = "opaque-session";
+const THROTTLE_TOKEN: &str = "opaque-throttle-token";
+
+const SERVER_SETUP_KEY: &[u8] = b"server_setup";
+const PASSWORD_FILES_TREE: &[u8] = b"password_files";
+
+// Above the processing time of a login start, including the lookup of the
+// password file
+const LOGIN_LATENCY: Duration = Duration::from_millis(50);
+
+// The maximum delay in seconds after repeated failed login attempts
+const MAX_DELAY: u64 = 1 << 10;
+
+struct Server {
+    setup: ServerSetup<Suite>,
+    password_files: sled::Tree,
+    logins: Mutex<HashMap<String, ServerLogin<Suite>>>,
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let addr: SocketAddr = args
+        .next()
+        .as_deref()
+        .unwrap_or("127.0.0.1:8080")
+        .parse()
+        .expect("invalid address");
+    let path = args
+        .next()
+        .unwrap_or_else(|| "opaque-demo-server.db".into());
+
+    let db = sled::open(&path).expect("failed to open the database");
+    let setup = match db.get(SERVER_SETUP_KEY).expect("failed to read the setup") {
+        Some(bytes) => ServerSetup::deserialize(&bytes).expect("invalid setup"),
+        None => {
+            let setup =
+                ServerSetup::<Suite>::new(&mut OsRng).expect("failed to generate the setup");
+            db.insert(SERVER_SETUP_KEY, &setup.serialize()[..])
+                .expect("failed to write the setup");
+            setup
+        }
+    };
+    let server = Arc::new(Server {
+        setup,
+        password_files: db
+            .open_tree(PASSWORD_FILES_TREE)
+            .expect("failed to open the password files"),
+        logins: Mutex::default(),
+    });
+
+    let app = Router::new()
+        .route(paths::REGISTRATION_START, post(registration_start))
+        .route(paths::REGISTRATION_FINISH, post(registration_finish))
+        .route(paths::LOGIN_START, post(login_start))
+        .route(paths::LOGIN_FINISH, post(login_finish))
+        .with_state(server);
+
+    println!("Serving OPAQUE on http://{addr}, storing password files in {path}");
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .expect("failed to bind the address");
+    axum::serve(listener, app).await.expect("server failed");
+}
+
+async fn registration_start(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Opaque(request): Opaque<RegistrationRequest<Suite>>,
+) -> Result<Response, StatusCode> {
+    let credential_identifier = credential_identifier(&headers)?;
+    let result = ServerRegistration::start(&server.setup, request, credential_identifier)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    Ok(Opaque(result.message).into_response())
+}
+
+async fn registration_finish(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Opaque(upload): Opaque<RegistrationUpload<Suite>>,
+) -> Result<StatusCode, StatusCode> {
+    let credential_identifier = credential_identifier(&headers)?;
+    let password_file = ServerRegistration::finish(upload);
+
+    // Existing registrations are not overwritten, a real server would require
+    // the client to be logged in to change its password
+    server
+        .password_files
+        .compare_and_swap(
+            credential_identifier,
+            None::<&[u8]>,
+            Some(&password_file.serialize_versioned()[..]),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::CONFLICT)?;
+
+    Ok(StatusCode::CREATED)
+}
+
+async fn login_start(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Opaque(request): Opaque<CredentialRequest<Suite>>,
+) -> Result<Response, StatusCode> {
+    let credential_identifier = credential_identifier(&headers)?;
+    let now = now();
+
+    // Rejects attempts before the delay of the previous failure has passed
+    let failures = match headers.get(THROTTLE_TOKEN) {
+        Some(token) => {
+            let token = hex_decode(token.as_bytes())
+                .and_then(|token| ThrottleToken::deserialize(&token).ok())
+                .ok_or(StatusCode::BAD_REQUEST)?;
+            server
+                .setup
+                .verify_throttle_token(credential_identifier, &token, now)
+                .map_err(|_| StatusCode::TOO_MANY_REQUESTS)?
+        }
+        None => 0,
+    };
+
+    // Unregistered identifiers are answered with a dummy response, in the same
+    // time as registered ones
+    let target = LatencyTarget::new(LOGIN_LATENCY);
+    let start = Instant::now();
+    let password_file = server
+        .password_files
+        .get(credential_identifier)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(|record| {
+            ServerRegistration::deserialize_versioned(&record, |_| {
+                Err(opaque_ke::errors::ProtocolError::SerializationError)
+            })
+        })
+        .transpose()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(result) = &password_file {
+        if result.upgraded {
+            server
+                .password_files
+                .insert(
+                    credential_identifier,
+                    &result.record.serialize_versioned()[..],
+                )
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+    }
+    let result = ServerLogin::start(
+        &mut OsRng,
+        &server.setup,
+        password_file.map(|result| result.record),
+        request,
+        credential_identifier,
+        ServerLoginStartParameters::default(),
+    )
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+    tokio::time::sleep(target.remaining(start.elapsed())).await;
+
+    // Issued pessimistically, the client drops it after a successful login
+    let delay = (1 << failures.min(10)).min(MAX_DELAY);
+    let token = server
+        .setup
+        .throttle_token(credential_identifier, failures + 1, now + delay)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut session = [0; 16];
+    OsRng.fill_bytes(&mut session);
+    let session = hex_encode(&session);
+    server
+        .logins
+        .lock()
+        .unwrap()
+        .insert(session.clone(), result.state);
+
+    let mut response = Opaque(result.message).into_response();
+    let headers = response.headers_mut();
+    headers.insert(SESSION, header(&session));
+    headers.insert(THROTTLE_TOKEN, header(&hex_encode(&token.serialize())));
+
+    Ok(response)
+}
+
+async fn login_finish(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Opaque(finalization): Opaque<CredentialFinalization<Suite>>,
+) -> Result<StatusCode, StatusCode> {
+    let session = headers
+        .get(SESSION)
+        .and_then(|session| session.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let state = server
+        .logins
+        .lock()
+        .unwrap()
+        .remove(session)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // A real server would now derive a session for the client from the
+    // session key
+    state
+        .finish(finalization)
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|_| StatusCode::UNAUTHORIZED)
+}
+
+fn credential_identifier(headers: &HeaderMap) -> Result<&[u8], StatusCode> {
+    headers
+        .get(CREDENTIAL_IDENTIFIER)
+        .map(HeaderValue::as_bytes)
+        .filter(|credential_identifier| !credential_identifier.is_empty())
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time before the epoch")
+        .as_secs()
+}
+
+fn header(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("hex is a valid header")
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hex_decode(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
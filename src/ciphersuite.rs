@@ -9,14 +9,19 @@
 //! Defines the [`CipherSuite`] trait to specify the underlying primitives for
 //! OPAQUE
 
+use core::marker::PhantomData;
+
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::OutputSizeUser;
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Unsigned, U256};
+use generic_array::ArrayLength;
+use voprf::Group;
 
-use crate::hash::{Hash, ProxyHash};
+use crate::hash::{Hash, OutputSize, ProxyHash};
 use crate::key_exchange::group::KeGroup;
-use crate::key_exchange::traits::KeyExchange;
+use crate::key_exchange::traits::{KeyExchange, MacLen, SessionKeyLen};
 use crate::ksf::Ksf;
+use crate::messages::Sizes;
 
 /// Configures the underlying primitives used in OPAQUE
 /// * `OprfCs`: A VOPRF ciphersuite, see [`voprf::CipherSuite`].
@@ -24,6 +29,48 @@ use crate::ksf::Ksf;
 /// * `KeyExchange`: The key exchange protocol to use in the login step
 /// * `Hash`: The main hashing function to use
 /// * `Ksf`: A key stretching function, typically used for password hashing
+/// * `AppDataLen`: The length of the application data stored in the envelope
+///
+/// The HKDF labels used to derive the masking key and the envelope keys, as
+/// well as the `info` and domain separation tags of the key derivations, can
+/// additionally be overridden to domain-separate credentials of different
+/// deployments. Leave them at their default values to stay compatible with the
+/// specification.
+///
+/// The domain separation tags of the OPRF itself, including the one hashing
+/// the password to the group, are derived by the `voprf` crate from
+/// [`voprf::CipherSuite::ID`]. Deployments which also need to separate them can
+/// set `OprfCs` to their own type implementing [`voprf::CipherSuite`] with a
+/// distinct `ID`, and the `Group` and `Hash` of an existing suite.
+///
+/// Combinations of primitives that the bounds can't rule out, such as an OPRF
+/// hash with less than 32 bytes of output, MACs longer than the hash output,
+/// or application data too long to be masked, fail the build that uses the
+/// suite with a message naming the problem:
+///
+/// ```compile_fail,E0080
+/// # #[cfg(feature = "ristretto255")]
+/// # {
+/// use generic_array::typenum::U16384;
+/// use opaque_ke::{ClientRegistration, CipherSuite};
+/// use rand::rngs::OsRng;
+///
+/// struct Oversized;
+///
+/// impl CipherSuite for Oversized {
+///     type OprfCs = opaque_ke::Ristretto255;
+///     type KeGroup = opaque_ke::Ristretto255;
+///     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+///     type Ksf = opaque_ke::ksf::Identity;
+///     // Too long for the masking pad derived with HKDF-SHA512
+///     type AppDataLen = U16384;
+/// }
+///
+/// let _ = ClientRegistration::<Oversized>::start(&mut OsRng, b"password");
+/// # }
+/// # #[cfg(not(feature = "ristretto255"))]
+/// # compile_error!("requires the ristretto255 feature");
+/// ```
 pub trait CipherSuite
 where
     <OprfHash<Self> as OutputSizeUser>::OutputSize:
@@ -41,7 +88,140 @@ where
     type KeyExchange: KeyExchange<OprfHash<Self>, Self::KeGroup>;
     /// A key stretching function, typically used for password hashing
     type Ksf: Ksf;
+    /// The length in bytes of the application data sealed inside the
+    /// envelope. Use [`U0`](generic_array::typenum::U0) to retain the envelope
+    /// layout of the specification.
+    type AppDataLen: ArrayLength<u8>;
+
+    /// The HKDF label used to derive the masking key from the randomized
+    /// password, defaults to `MaskingKey`.
+    const MASKING_KEY_LABEL: &'static [u8] = b"MaskingKey";
+    /// The HKDF label used to derive the envelope authentication key, defaults
+    /// to `AuthKey`.
+    const AUTH_KEY_LABEL: &'static [u8] = b"AuthKey";
+    /// The HKDF label used to derive the export key, defaults to `ExportKey`.
+    const EXPORT_KEY_LABEL: &'static [u8] = b"ExportKey";
+    /// The HKDF label used to derive the seed of the client's static keypair,
+    /// defaults to `PrivateKey`.
+    const PRIVATE_KEY_LABEL: &'static [u8] = b"PrivateKey";
+    /// The `info` of the derivation of the OPRF key of a credential from the
+    /// OPRF seed, defaults to `OPAQUE-DeriveKeyPair`.
+    const OPRF_KEY_INFO: &'static [u8] = b"OPAQUE-DeriveKeyPair";
+    /// The `info` of the derivation of the client's static keypair, and the
+    /// server's with
+    /// [`KeyPair::derive_from_seed`](crate::keypair::KeyPair::derive_from_seed),
+    /// defaults to `OPAQUE-DeriveDiffieHellmanKeyPair`.
+    const AUTH_KEY_PAIR_INFO: &'static [u8] = b"OPAQUE-DeriveDiffieHellmanKeyPair";
+    /// The domain separation tag of the hash to scalar deriving the keypairs
+    /// of [`AUTH_KEY_PAIR_INFO`](Self::AUTH_KEY_PAIR_INFO). Defaults to
+    /// `None`, which uses `DeriveKeyPair` followed by the context string of
+    /// `OprfCs`.
+    const AUTH_KEY_PAIR_DST: Option<&'static [u8]> = None;
+
+    /// The minimum length of a password in bytes, which
+    /// [`ClientRegistration::start`](crate::ClientRegistration::start) and
+    /// [`ClientLogin::start`](crate::ClientLogin::start) enforce. Defaults to
+    /// 1, which rejects empty passwords.
+    const MIN_PASSWORD_LEN: usize = 1;
+    /// The maximum length of a password in bytes, which
+    /// [`ClientRegistration::start`](crate::ClientRegistration::start) and
+    /// [`ClientLogin::start`](crate::ClientLogin::start) enforce. Defaults to
+    /// 1024, which is far above any passphrase, and can be raised up to
+    /// [`u16::MAX`], the longest input of the OPRF.
+    const MAX_PASSWORD_LEN: usize = 1024;
+
+    /// Whether the client attests the parameters of its key stretching
+    /// function in the registration upload, so that the server can enforce a
+    /// minimum with
+    /// [`ServerRegistration::finish_with_ksf_policy`](crate::ServerRegistration::finish_with_ksf_policy).
+    /// The parameters take the place of the last 12 bytes of the envelope
+    /// nonce, which keeps the layout of the specification, and are covered by
+    /// the envelope MAC. Defaults to `false`, which keeps the nonce fully
+    /// random.
+    const ATTEST_KSF_PARAMETERS: bool = false;
+
+    /// Whether the login authenticates the client with its static keypair.
+    /// Setting this to `false` selects a server-authenticated mode, which is
+    /// not part of the specification: registration doesn't derive a client
+    /// keypair and stores the server's public key in its place, and the key
+    /// exchange leaves out the Diffie-Hellman of the client's static key. The
+    /// client still authenticates the server and obtains the session and
+    /// export keys, but [`ServerLogin::finish`](crate::ServerLogin::finish)
+    /// then only confirms that the client derived the same session key, not
+    /// that it knows the password, and server APIs relying on the client's
+    /// authentication refuse to run. Defaults to `true`.
+    const CLIENT_AUTHENTICATION: bool = true;
 }
 
 pub(crate) type OprfGroup<CS> = <<CS as CipherSuite>::OprfCs as voprf::CipherSuite>::Group;
 pub(crate) type OprfHash<CS> = <<CS as CipherSuite>::OprfCs as voprf::CipherSuite>::Hash;
+
+/// The HKDF expansions can output at most 255 times the hash output length
+const MAX_HKDF_OUTPUT_BLOCKS: usize = 255;
+
+/// Checks of the parameters of a [`CipherSuite`] that its bounds don't express
+///
+/// The entry points of the protocol evaluate [`VALID`](Self::VALID), so that a
+/// suite failing a check is rejected when the code using it is built, instead
+/// of failing or losing security at runtime.
+pub(crate) struct SuiteAssertions<CS: CipherSuite>(PhantomData<CS>);
+
+impl<CS: CipherSuite> SuiteAssertions<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    pub(crate) const VALID: () = {
+        let hash_len = OutputSize::<OprfHash<CS>>::USIZE;
+        let max_hkdf_len = MAX_HKDF_OUTPUT_BLOCKS * hash_len;
+
+        assert!(
+            hash_len >= 32,
+            "invalid CipherSuite: the OPRF hash must have an output of at least 32 bytes"
+        );
+        assert!(
+            MacLen::<CS>::USIZE >= 16,
+            "invalid CipherSuite: the MACs of the key exchange must have at least 16 bytes"
+        );
+        assert!(
+            MacLen::<CS>::USIZE <= hash_len,
+            "invalid CipherSuite: the MACs of the key exchange can't be longer than the output of \
+             the OPRF hash"
+        );
+        assert!(
+            CS::MIN_PASSWORD_LEN <= CS::MAX_PASSWORD_LEN,
+            "invalid CipherSuite: MIN_PASSWORD_LEN can't be greater than MAX_PASSWORD_LEN"
+        );
+        assert!(
+            CS::MAX_PASSWORD_LEN <= u16::MAX as usize,
+            "invalid CipherSuite: MAX_PASSWORD_LEN can't exceed the longest input of the OPRF"
+        );
+        assert!(
+            SessionKeyLen::<CS>::USIZE >= 16,
+            "invalid CipherSuite: the session key must have at least 16 bytes"
+        );
+        assert!(
+            SessionKeyLen::<CS>::USIZE <= max_hkdf_len,
+            "invalid CipherSuite: the OPRF hash can't derive a session key of this length"
+        );
+        assert!(
+            <OprfGroup<CS> as Group>::ScalarLen::USIZE <= max_hkdf_len,
+            "invalid CipherSuite: the OPRF hash can't derive OPRF keys of the scalar length of \
+             the OPRF group"
+        );
+        assert!(
+            <CS::KeGroup as KeGroup>::SkLen::USIZE <= max_hkdf_len,
+            "invalid CipherSuite: the OPRF hash can't derive keypair seeds of the private key \
+             length of the KeGroup"
+        );
+        assert!(
+            Sizes::<CS>::MASKED_RESPONSE_LEN <= max_hkdf_len,
+            "invalid CipherSuite: the OPRF hash can't derive a masking pad of the length of the \
+             masked response, AppDataLen is too long"
+        );
+    };
+}
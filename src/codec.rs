@@ -0,0 +1,199 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! [`tokio_util::codec`] implementations for the protocol messages, available
+//! with the `tokio-codec` feature
+//!
+//! A [`MessageCodec`] decodes every incoming frame into a [`Message`] and
+//! encodes any protocol message, so that a `Framed` transport carries a whole
+//! flow:
+//!
+//! ```ignore
+//! let mut transport = Framed::new(stream, MessageCodec::<DefaultCipherSuite>::new());
+//!
+//! match transport.next().await {
+//!     Some(Ok(Message::CredentialRequest(credential_request))) => {
+//!         let server_login_start_result = ServerLogin::start(/* ... */, credential_request, /* ... */)?;
+//!         transport.send(server_login_start_result.message).await?;
+//!     }
+//!     // ...
+//! }
+//! ```
+//!
+//! Each frame is a four-byte big-endian length, as written by
+//! [`LengthDelimitedCodec`], followed by the frame of the message as written
+//! by [`Message::serialize`]. Frames longer than [`Message::max_len`] are
+//! rejected before they are buffered.
+
+use core::marker::PhantomData;
+use core::ops::Add;
+use std::{io, vec};
+
+use bytes::{Bytes, BytesMut};
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use displaydoc::Display;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::ArrayLength;
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+pub use crate::driver::Message;
+use crate::envelope::EnvelopeLen;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, MacLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::CredentialResponseWithoutKeLen;
+use crate::opaque::MaskedResponseLen;
+use crate::{
+    CredentialRequestLen, CredentialResponseLen, RegistrationResponseLen, RegistrationUploadLen,
+    RetrievalResponseLen,
+};
+
+/// The error of a [`MessageCodec`]
+#[derive(Debug, Display)]
+pub enum CodecError {
+    /// Reading or writing a frame failed: {0}
+    Io(io::Error),
+    /// A frame does not contain a valid message: {0}
+    Protocol(ProtocolError),
+}
+
+impl From<io::Error> for CodecError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ProtocolError> for CodecError {
+    fn from(error: ProtocolError) -> Self {
+        Self::Protocol(error)
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Protocol(error) => Some(error),
+        }
+    }
+}
+
+/// Length-delimited framing of the [`Message`]s of `CS`, see the
+/// [module documentation](self)
+#[derive_where(Debug)]
+pub struct MessageCodec<CS: CipherSuite> {
+    inner: LengthDelimitedCodec,
+    _cs: PhantomData<CS>,
+}
+
+impl<CS: CipherSuite> MessageCodec<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Creates a codec accepting frames of up to [`Message::max_len`] bytes
+    pub fn new() -> Self {
+        Self {
+            inner: LengthDelimitedCodec::builder()
+                .max_frame_length(Message::<CS>::max_len())
+                .new_codec(),
+            _cs: PhantomData,
+        }
+    }
+}
+
+impl<CS: CipherSuite> Default for MessageCodec<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<CS: CipherSuite> Decoder for MessageCodec<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Item = Message<CS>;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src)? {
+            Some(frame) => Ok(Some(Message::deserialize(&frame)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Encodes a [`Message`] or any of the protocol messages it contains
+impl<CS: CipherSuite, M: Into<Message<CS>>> Encoder<M> for MessageCodec<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // RegistrationResponse: KgPk + KePk
+    <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    RegistrationResponseLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+    // CredentialRequest: KgPk + Ke1Message
+    <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+    CredentialRequestLen<CS>: ArrayLength<u8>,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+    // RetrievalResponse: CredentialResponseWithoutKeLen
+    RetrievalResponseLen<CS>: ArrayLength<u8>,
+{
+    type Error = CodecError;
+
+    fn encode(&mut self, item: M, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut frame = vec![0; Message::<CS>::max_len()];
+        let len = item.into().serialize(&mut frame)?;
+        frame.truncate(len);
+
+        self.inner.encode(Bytes::from(frame), dst)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Auditing the operations handling secrets for secret-dependent branches and
+//! memory accesses, available with the `ct-audit` feature
+//!
+//! The audit follows the approach of [ctgrind]: [`poison`] marks the bytes of
+//! a secret as undefined for Valgrind's memcheck, which then reports every
+//! branch and memory access whose address depends on them, as it would for a
+//! read of uninitialized memory. [`unpoison`] marks values computed from
+//! secrets as defined again once they may be revealed, e.g. a public key or a
+//! hash output. Outside of Valgrind both are no-ops. [`check_suite`] runs the
+//! operations handling secrets of the protocol for a [`CipherSuite`] this way:
+//!
+//! ```text
+//! RUSTFLAGS='--cfg curve25519_dalek_backend="fiat"' \
+//! CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER='valgrind --error-exitcode=1' \
+//! cargo test --release --lib --features ct-audit,curve25519 test_ct_audit
+//! ```
+//!
+//! Unlike a timing test, such as those of the `dudect` feature, the audit
+//! covers every execution path taken by the inputs, but only the code actually
+//! compiled, so it has to run with the flags and on the targets of the
+//! deployment.
+//!
+//! The arithmetic of the groups is the one of their crates:
+//!
+//! - `curve25519-dalek`, used by Ristretto255 and Curve25519, can't select its
+//!   backend with a feature. The `RUSTFLAGS` above select its `fiat` backend,
+//!   whose field arithmetic is generated and verified by [fiat-crypto], which
+//!   [`CURVE25519_FIAT_BACKEND`] reports.
+//! - The groups of the `elliptic-curve` crate, such as P-256 and P-384, use the
+//!   constant-time arithmetic of their `RustCrypto` crates, which for P-384 is
+//!   generated by fiat-crypto as well.
+//!
+//! The key stretching function is not audited, as memory-hard functions such
+//! as Argon2id access memory depending on the password by design, and neither
+//! is the choice of a new secret key, which repeats until it isn't zero.
+//!
+//! [ctgrind]: https://github.com/agl/ctgrind
+//! [fiat-crypto]: https://github.com/mit-plv/fiat-crypto
+
+#![allow(unsafe_code)]
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Output, OutputSizeUser};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+use generic_array::GenericArray;
+use rand::{CryptoRng, RngCore};
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::errors::{InternalError, ProtocolError};
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::SuiteProvider;
+use crate::provider::Provider;
+
+/// Whether `curve25519-dalek` was compiled with its `fiat` backend, see the
+/// [module documentation](self)
+pub const CURVE25519_FIAT_BACKEND: bool = cfg!(curve25519_dalek_backend = "fiat");
+
+const INFO: &[u8] = b"info";
+
+// The client requests of memcheck, see `memcheck.h` of Valgrind
+const MAKE_MEM_UNDEFINED: usize = 0x4D43_0001;
+const MAKE_MEM_DEFINED: usize = 0x4D43_0002;
+
+/// Marks the bytes of `value` as secret, so that Valgrind reports branches and
+/// memory accesses depending on them
+///
+/// This is a no-op outside of Valgrind and on architectures other than x86-64
+/// and 64-bit ARM.
+pub fn poison<T: ?Sized>(value: &T) {
+    client_request(MAKE_MEM_UNDEFINED, value);
+}
+
+/// Marks the bytes of `value` as public again, undoing [`poison`] for the
+/// values which may be revealed
+pub fn unpoison<T: ?Sized>(value: &T) {
+    client_request(MAKE_MEM_DEFINED, value);
+}
+
+/// Runs the operations handling secrets of `CS` on poisoned secrets, see the
+/// [module documentation](self)
+///
+/// This always succeeds outside of Valgrind, and under it reports the branches
+/// and memory accesses depending on the secrets.
+///
+/// # Errors
+/// [`ProtocolError::LibraryError`] if an operation of the OPRF, HKDF or HMAC
+/// fails or a MAC is verified wrongly, which doesn't happen for valid cipher
+/// suites
+pub fn check_suite<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+) -> Result<(), ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    // Key exchange group
+
+    let sk = CS::KeGroup::random_sk(rng)?;
+    let peer_pk = CS::KeGroup::public_key(CS::KeGroup::random_sk(rng)?);
+    poison(&sk);
+
+    let pk = CS::KeGroup::public_key(sk);
+    unpoison(&pk);
+    let shared_secret = CS::KeGroup::diffie_hellman(peer_pk, sk);
+    let serialized_sk = CS::KeGroup::serialize_sk(sk);
+
+    // As for the client's keypair, which Curve25519 derives by clamping the
+    // seed instead of hashing it to a scalar
+    let mut seed = GenericArray::<u8, <CS::KeGroup as KeGroup>::SkLen>::default();
+    rng.fill_bytes(&mut seed);
+    poison(&seed);
+    let derived_sk = CS::KeGroup::derive_auth_keypair_with::<CS::OprfCs>(
+        seed.clone(),
+        CS::AUTH_KEY_PAIR_INFO,
+        CS::AUTH_KEY_PAIR_DST,
+    )?;
+
+    // OPRF
+
+    let password = *b"password";
+    let blind_result = voprf::OprfClient::<CS::OprfCs>::blind(&password, rng)?;
+    let oprf_server = voprf::OprfServer::<CS::OprfCs>::new(rng)?;
+    poison(&oprf_server);
+    let evaluation_element = oprf_server.blind_evaluate(&blind_result.message);
+    unpoison(&evaluation_element);
+
+    poison(&blind_result.state);
+    poison(&password);
+    let oprf_output = blind_result
+        .state
+        .finalize(&password, &evaluation_element)?;
+
+    // HKDF and HMAC
+
+    let (prk, hkdf) = SuiteProvider::<CS>::extract(None, &[&oprf_output]);
+    let hkdf_from_prk = SuiteProvider::<CS>::from_prk(&prk)?;
+    let mut okm = Output::<OprfHash<CS>>::default();
+    SuiteProvider::<CS>::expand(&hkdf, &[INFO], &mut okm)?;
+    SuiteProvider::<CS>::expand(&hkdf_from_prk, &[INFO], &mut okm)?;
+
+    let mut mac = SuiteProvider::<CS>::new_mac(&okm)?;
+    SuiteProvider::<CS>::update_mac(&mut mac, INFO);
+    let tag = SuiteProvider::<CS>::finalize_mac(mac);
+    unpoison(&tag);
+
+    let mut forged_tag = tag.clone();
+    *forged_tag.last_mut().unwrap() ^= 1;
+    let verify = |tag: &[u8]| {
+        let mut mac = SuiteProvider::<CS>::new_mac(&okm)?;
+        SuiteProvider::<CS>::update_mac(&mut mac, INFO);
+        let verified = SuiteProvider::<CS>::verify_mac(mac, tag);
+        // The result of the verification is revealed by the protocol
+        unpoison(&verified);
+        Ok::<_, InternalError>(bool::from(verified))
+    };
+    if !verify(&tag)? || verify(&forged_tag)? {
+        return Err(ProtocolError::LibraryError(InternalError::HmacError));
+    }
+
+    // Leave no undefined bytes behind on the stack
+    unpoison(&sk);
+    unpoison(&shared_secret);
+    unpoison(&serialized_sk);
+    unpoison(&seed);
+    unpoison(&derived_sk);
+    unpoison(&oprf_server);
+    unpoison(&blind_result.state);
+    unpoison(&password);
+    unpoison(&oprf_output);
+    unpoison(&prk);
+    unpoison(&okm);
+
+    Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+fn client_request<T: ?Sized>(request: usize, value: &T) {
+    let args = [
+        request,
+        value as *const T as *const u8 as usize,
+        core::mem::size_of_val(value),
+        0,
+        0,
+        0,
+    ];
+
+    // The special instruction sequence of `valgrind.h`, which rotates `rdi`
+    // by 128 bits in total and is therefore a no-op outside of Valgrind
+    // SAFETY: the sequence only reads `args`, and restores `rdi`
+    unsafe {
+        core::arch::asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            inout("rdx") 0usize => _,
+            in("rax") args.as_ptr(),
+            inout("rdi") 0usize => _,
+            options(nostack),
+        );
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn client_request<T: ?Sized>(request: usize, value: &T) {
+    let args = [
+        request,
+        value as *const T as *const u8 as usize,
+        core::mem::size_of_val(value),
+        0,
+        0,
+        0,
+    ];
+
+    // The special instruction sequence of `valgrind.h`, which rotates `x12`
+    // by 128 bits in total and is therefore a no-op outside of Valgrind
+    // SAFETY: the sequence only reads `args`, and restores `x12`
+    unsafe {
+        core::arch::asm!(
+            "ror x12, x12, #3",
+            "ror x12, x12, #13",
+            "ror x12, x12, #51",
+            "ror x12, x12, #61",
+            "orr x10, x10, x10",
+            inout("x3") 0usize => _,
+            in("x4") args.as_ptr(),
+            inout("x12") 0usize => _,
+            options(nostack),
+        );
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn client_request<T: ?Sized>(_: usize, _: &T) {}
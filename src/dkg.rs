@@ -0,0 +1,1106 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Distributed generation and threshold use of the static key of the server,
+//! available with the `dkg` feature
+//!
+//! Whoever obtains the static key of the server can impersonate it to every
+//! client. With the distributed key generation of [FROST][FROST], a number of
+//! participants generate the key together, each obtaining a [`KeyShare`]
+//! without the key ever existing in one place, and any `threshold` of them can
+//! compute the Diffie-Hellman operations of the key exchange. Every
+//! participant, with its index from 1 to the number of participants, runs:
+//!
+//! ```ignore
+//! let parameters = Parameters::new(index, threshold, participants)?;
+//! let (secret, package) = dkg::part1::<CS, _>(&mut OsRng, parameters, context)?;
+//! // Broadcast `package` and receive the packages of all other participants
+//! let (secret, packages) = dkg::part2::<CS>(secret, &round1_packages, context)?;
+//! // Send each of `packages` to its participant and receive the packages
+//! // addressed to this one
+//! let key_share = dkg::part3::<CS>(secret, &round2_packages)?;
+//! ```
+//!
+//! The round 1 packages have to reach all participants identically, e.g.
+//! through an authenticated broadcast channel, while the round 2 packages
+//! contain secret shares and have to be sent over confidential and
+//! authenticated channels.
+//!
+//! The front-end serving the clients then holds a [`ThresholdSecretKey`]
+//! instead of a private key. It has a [`DhShareSource`] collect the
+//! [`DhShare`]s that at least `threshold` participants compute with
+//! [`KeyShare::dh_share`], and combines them whenever
+//! [`ServerLogin::start`](crate::ServerLogin::start) needs the static key. It
+//! only needs the [`PublicKeyPackage`] of the participants for that:
+//!
+//! ```ignore
+//! let key = ThresholdSecretKey::<CS, _>::new(key_share.public_key_package(), source);
+//! let server_setup = ServerSetup::<CS, _>::new_with_key(&mut OsRng, KeyPair::from_private_key(key)?)?;
+//! ```
+//!
+//! Every Diffie-Hellman share carries a proof that it was computed with the
+//! share of its participant, which [`combine_dh_shares`] checks against the
+//! [`PublicKeyPackage`]. A misbehaving participant still makes logins fail,
+//! but is reported by its index with [`DkgError::InvalidDhShare`], and doesn't
+//! learn anything about the key.
+//!
+//! The key can't be serialized, so [`ThresholdSecretKey::serialize`] is empty
+//! and [`ThresholdSecretKey::deserialize`] fails. Restore a
+//! [`ServerSetup`](crate::ServerSetup) instead with
+//! [`ServerSetup::deserialize_with_keypair`](crate::ServerSetup::deserialize_with_keypair).
+//!
+//! [FROST]: https://eprint.iacr.org/2020/852
+
+use core::convert::Infallible;
+use std::vec::Vec;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Unsigned, U0, U256};
+use generic_array::GenericArray;
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::errors::InternalError;
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::{KeGroup, ThresholdKeGroup};
+use crate::keypair::{PublicKey, SecretKey};
+
+const STR_DKG_CHALLENGE: &[u8] = b"OPAQUE-DKG-Challenge";
+const STR_DKG_DH_SHARE_CHALLENGE: &[u8] = b"OPAQUE-DKG-DhShareChallenge";
+const STR_DKG_DH_SHARE_NONCE: &[u8] = b"OPAQUE-DKG-DhShareNonce";
+
+/// An error of the distributed key generation or of a [`ThresholdSecretKey`]
+#[derive(Clone, Copy, Debug, displaydoc::Display, Eq, Hash, PartialEq)]
+pub enum DkgError<E = Infallible> {
+    /// The index or the threshold is not between 1 and the participant count
+    InvalidParameters,
+    /// The package of participant {0} is missing
+    MissingParticipant(u16),
+    /// Participant {0} is unknown or appears more than once
+    UnexpectedParticipant(u16),
+    /// The proof of knowledge of participant {0} is invalid
+    InvalidProof(u16),
+    /// The secret share of participant {0} doesn't match its commitments
+    InvalidShare(u16),
+    /// The Diffie-Hellman share of participant {0} fails its proof
+    InvalidDhShare(u16),
+    /// Fewer Diffie-Hellman shares than the threshold were collected
+    NotEnoughShares,
+    /// The result is the identity element
+    IdentityElement,
+    /// Deserializing from a byte sequence failed
+    InvalidByteSequence,
+    /// The random number generator failed
+    RandomnessError,
+    /// Error from a group operation: {0}
+    Library(InternalError),
+    /// Collecting the Diffie-Hellman shares failed: {0}
+    Source(E),
+}
+
+impl<E: std::error::Error> std::error::Error for DkgError<E> {}
+
+impl DkgError {
+    /// Convert `DkgError<Infallible>` into `DkgError<E>`
+    pub fn into_custom<E>(self) -> DkgError<E> {
+        match self {
+            Self::InvalidParameters => DkgError::InvalidParameters,
+            Self::MissingParticipant(index) => DkgError::MissingParticipant(index),
+            Self::UnexpectedParticipant(index) => DkgError::UnexpectedParticipant(index),
+            Self::InvalidProof(index) => DkgError::InvalidProof(index),
+            Self::InvalidShare(index) => DkgError::InvalidShare(index),
+            Self::InvalidDhShare(index) => DkgError::InvalidDhShare(index),
+            Self::NotEnoughShares => DkgError::NotEnoughShares,
+            Self::IdentityElement => DkgError::IdentityElement,
+            Self::InvalidByteSequence => DkgError::InvalidByteSequence,
+            Self::RandomnessError => DkgError::RandomnessError,
+            Self::Library(error) => DkgError::Library(error),
+            Self::Source(_) => unreachable!(),
+        }
+    }
+}
+
+/// The position of a participant in the distributed key generation
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Zeroize)]
+pub struct Parameters {
+    index: u16,
+    threshold: u16,
+    participants: u16,
+}
+
+impl Parameters {
+    /// Participant `index` of `participants`, any `threshold` of which can use
+    /// the key
+    ///
+    /// # Errors
+    /// [`DkgError::InvalidParameters`] if `index` or `threshold` is zero or
+    /// greater than `participants`
+    pub fn new(index: u16, threshold: u16, participants: u16) -> Result<Self, DkgError> {
+        if index == 0 || index > participants || threshold == 0 || threshold > participants {
+            return Err(DkgError::InvalidParameters);
+        }
+
+        Ok(Self {
+            index,
+            threshold,
+            participants,
+        })
+    }
+
+    /// Returns the index of the participant, starting at 1
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the number of participants needed to use the key
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// Returns the number of participants
+    pub fn participants(&self) -> u16 {
+        self.participants
+    }
+
+    // Checks that `indices` are all other participants, each exactly once
+    fn check_others(&self, indices: impl Iterator<Item = u16>) -> Result<(), DkgError> {
+        let mut seen = std::vec![false; usize::from(self.participants)];
+        seen[usize::from(self.index - 1)] = true;
+
+        for index in indices {
+            match usize::from(index)
+                .checked_sub(1)
+                .and_then(|position| seen.get_mut(position))
+            {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(DkgError::UnexpectedParticipant(index)),
+            }
+        }
+
+        match seen.iter().position(|seen| !seen) {
+            Some(position) => Err(DkgError::MissingParticipant(position as u16 + 1)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The secret state of a participant after [`part1`]
+#[derive_where(Clone, ZeroizeOnDrop)]
+pub struct Round1Secret<KG: ThresholdKeGroup> {
+    parameters: Parameters,
+    coefficients: Vec<KG::Sk>,
+    commitments: Vec<KG::Pk>,
+}
+
+/// The package a participant broadcasts to all others after [`part1`]
+#[derive_where(Clone, ZeroizeOnDrop)]
+#[derive_where(Debug; KG::Pk, KG::Sk)]
+pub struct Round1Package<KG: ThresholdKeGroup> {
+    commitments: Vec<KG::Pk>,
+    proof_commitment: KG::Pk,
+    proof_response: KG::Sk,
+}
+
+impl<KG: ThresholdKeGroup> Round1Package<KG> {
+    /// Serialization into bytes, which are the commitments to the
+    /// coefficients of the polynomial of the participant followed by the proof
+    /// of knowledge of its secret
+    pub fn serialize(&self) -> Vec<u8> {
+        self.commitments
+            .iter()
+            .chain([&self.proof_commitment])
+            .flat_map(|commitment| KG::serialize_pk(*commitment))
+            .chain(KG::serialize_sk(self.proof_response))
+            .collect()
+    }
+
+    /// Deserialization from bytes, for a key with the given `threshold`
+    pub fn deserialize(input: &[u8], threshold: u16) -> Result<Self, DkgError> {
+        let pk_len = KG::PkLen::USIZE;
+        let commitments_len = usize::from(threshold) * pk_len;
+
+        if threshold == 0 || input.len() != commitments_len + pk_len + KG::SkLen::USIZE {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        let (commitments, input) = input.split_at(commitments_len);
+        let (proof_commitment, proof_response) = input.split_at(pk_len);
+
+        Ok(Self {
+            commitments: commitments
+                .chunks(pk_len)
+                .map(KG::deserialize_pk)
+                .collect::<Result<_, _>>()
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+            proof_commitment: KG::deserialize_pk(proof_commitment)
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+            proof_response: KG::deserialize_sk(proof_response)
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+        })
+    }
+}
+
+/// The secret state of a participant after [`part2`]
+#[derive_where(Clone, ZeroizeOnDrop)]
+pub struct Round2Secret<KG: ThresholdKeGroup> {
+    parameters: Parameters,
+    share: KG::Sk,
+    commitments: Vec<(u16, Vec<KG::Pk>)>,
+}
+
+/// The secret share a participant sends to another one after [`part2`]
+#[derive_where(Clone, ZeroizeOnDrop)]
+pub struct Round2Package<KG: ThresholdKeGroup> {
+    share: KG::Sk,
+}
+
+impl<KG: ThresholdKeGroup> Round2Package<KG> {
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, KG::SkLen> {
+        KG::serialize_sk(self.share)
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, DkgError> {
+        KG::deserialize_sk(input)
+            .map(|share| Self { share })
+            .map_err(|_| DkgError::InvalidByteSequence)
+    }
+}
+
+/// The share of the static key of the server held by a participant after
+/// [`part3`]
+#[derive_where(Clone, ZeroizeOnDrop)]
+pub struct KeyShare<KG: ThresholdKeGroup> {
+    parameters: Parameters,
+    share: KG::Sk,
+    // The sum of the commitments of all participants, the first of which is
+    // the public key
+    commitments: Vec<KG::Pk>,
+}
+
+impl<KG: ThresholdKeGroup> KeyShare<KG> {
+    /// Returns the parameters of the participant holding the share
+    pub fn parameters(&self) -> Parameters {
+        self.parameters
+    }
+
+    /// Returns the public key of the server
+    pub fn public_key(&self) -> PublicKey<KG> {
+        PublicKey::from_group_type(self.commitments[0])
+    }
+
+    /// Returns the public key of the share of participant `index`, which
+    /// allows to check a share as returned by [`KeyShare::serialize`]
+    ///
+    /// # Errors
+    /// [`DkgError::UnexpectedParticipant`] if `index` is zero or greater than
+    /// the number of participants
+    pub fn verifying_share(&self, index: u16) -> Result<PublicKey<KG>, DkgError> {
+        verifying_share::<KG>(&self.commitments, self.parameters.participants, index)
+            .map(PublicKey::from_group_type)
+    }
+
+    /// Returns the public keys of the server and of the shares of all
+    /// participants
+    pub fn public_key_package(&self) -> PublicKeyPackage<KG> {
+        PublicKeyPackage {
+            threshold: self.parameters.threshold,
+            participants: self.parameters.participants,
+            commitments: self.commitments.clone(),
+        }
+    }
+
+    /// Computes the share of the Diffie-Hellman output of the static key of
+    /// the server with `pk`, together with the proof that it was computed
+    /// with this share
+    ///
+    /// # Errors
+    /// [`DkgError::Library`] if hashing the proof fails
+    pub fn dh_share<CS: CipherSuite<KeGroup = KG>>(
+        &self,
+        pk: &PublicKey<KG>,
+    ) -> Result<DhShare<KG>, DkgError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let pk = pk.to_group_type();
+        let element = KG::mul_pk(pk, self.share);
+
+        // Chaum-Pedersen proof that the element has the same discrete
+        // logarithm to `pk` as the verifying share to the generator, with the
+        // nonce derived from the share like in deterministic signatures
+        let mut share = KG::serialize_sk(self.share);
+        let nonce = KG::hash_to_scalar::<OprfHash<CS>>(
+            &[&share, &KG::serialize_pk(pk)],
+            &[STR_DKG_DH_SHARE_NONCE],
+        );
+        share.zeroize();
+        let nonce = nonce.map_err(DkgError::Library)?;
+
+        let proof_commitments = (KG::public_key(nonce), KG::mul_pk(pk, nonce));
+        let challenge = dh_share_challenge::<CS>(
+            self.parameters.index,
+            KG::public_key(self.share),
+            pk,
+            element,
+            proof_commitments,
+        )?;
+
+        Ok(DhShare {
+            index: self.parameters.index,
+            element,
+            proof_commitments,
+            proof_response: KG::add_sk(nonce, KG::mul_sk(self.share, challenge)),
+        })
+    }
+
+    /// Serialization into bytes, which are the index, the threshold and the
+    /// number of participants as two-byte big-endian integers, the secret
+    /// share and the summed commitments
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            self.parameters.index,
+            self.parameters.threshold,
+            self.parameters.participants,
+        ]
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .chain(KG::serialize_sk(self.share))
+        .chain(
+            self.commitments
+                .iter()
+                .flat_map(|commitment| KG::serialize_pk(*commitment)),
+        )
+        .collect()
+    }
+
+    /// Deserialization from bytes
+    ///
+    /// # Errors
+    /// - [`DkgError::InvalidByteSequence`] if `input` is malformed
+    /// - [`DkgError::InvalidShare`] if the share doesn't match the commitments
+    pub fn deserialize(input: &[u8]) -> Result<Self, DkgError> {
+        if input.len() < 6 {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        let (header, input) = input.split_at(6);
+        let parameters = Parameters::new(
+            u16::from_be_bytes([header[0], header[1]]),
+            u16::from_be_bytes([header[2], header[3]]),
+            u16::from_be_bytes([header[4], header[5]]),
+        )
+        .map_err(|_| DkgError::InvalidByteSequence)?;
+
+        let sk_len = KG::SkLen::USIZE;
+        let pk_len = KG::PkLen::USIZE;
+        if input.len() != sk_len + usize::from(parameters.threshold) * pk_len {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        let (share, commitments) = input.split_at(sk_len);
+        let key_share = Self {
+            parameters,
+            share: KG::deserialize_sk(share).map_err(|_| DkgError::InvalidByteSequence)?,
+            commitments: commitments
+                .chunks(pk_len)
+                .map(KG::deserialize_pk)
+                .collect::<Result<_, _>>()
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+        };
+
+        let verifying_share = key_share.verifying_share(parameters.index)?;
+        if KG::serialize_pk(KG::public_key(key_share.share)) != verifying_share.serialize() {
+            return Err(DkgError::InvalidShare(parameters.index));
+        }
+
+        Ok(key_share)
+    }
+}
+
+/// The public keys of the server and of the shares of all participants of a
+/// distributed key generation, which [`combine_dh_shares`] checks the
+/// [`DhShare`]s against
+#[derive_where(Clone)]
+#[derive_where(Debug; KG::Pk)]
+pub struct PublicKeyPackage<KG: ThresholdKeGroup> {
+    threshold: u16,
+    participants: u16,
+    // The summed commitments of all participants, the first of which is the
+    // public key
+    commitments: Vec<KG::Pk>,
+}
+
+impl<KG: ThresholdKeGroup> PublicKeyPackage<KG> {
+    /// Returns the public key of the server
+    pub fn public_key(&self) -> PublicKey<KG> {
+        PublicKey::from_group_type(self.commitments[0])
+    }
+
+    /// Returns the number of participants needed to use the key
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// Returns the number of participants
+    pub fn participants(&self) -> u16 {
+        self.participants
+    }
+
+    /// Returns the public key of the share of participant `index`
+    ///
+    /// # Errors
+    /// [`DkgError::UnexpectedParticipant`] if `index` is zero or greater than
+    /// the number of participants
+    pub fn verifying_share(&self, index: u16) -> Result<PublicKey<KG>, DkgError> {
+        verifying_share::<KG>(&self.commitments, self.participants, index)
+            .map(PublicKey::from_group_type)
+    }
+
+    /// Serialization into bytes, which are the threshold and the number of
+    /// participants as two-byte big-endian integers followed by the summed
+    /// commitments
+    pub fn serialize(&self) -> Vec<u8> {
+        [self.threshold, self.participants]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .chain(
+                self.commitments
+                    .iter()
+                    .flat_map(|commitment| KG::serialize_pk(*commitment)),
+            )
+            .collect()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, DkgError> {
+        if input.len() < 4 {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        let (header, commitments) = input.split_at(4);
+        let threshold = u16::from_be_bytes([header[0], header[1]]);
+        let participants = u16::from_be_bytes([header[2], header[3]]);
+        Parameters::new(1, threshold, participants).map_err(|_| DkgError::InvalidByteSequence)?;
+
+        let pk_len = KG::PkLen::USIZE;
+        if commitments.len() != usize::from(threshold) * pk_len {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        Ok(Self {
+            threshold,
+            participants,
+            commitments: commitments
+                .chunks(pk_len)
+                .map(KG::deserialize_pk)
+                .collect::<Result<_, _>>()
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+        })
+    }
+}
+
+/// The share of a Diffie-Hellman output computed by a participant with
+/// [`KeyShare::dh_share`]
+#[derive_where(Clone, Copy)]
+#[derive_where(Debug; KG::Pk, KG::Sk)]
+pub struct DhShare<KG: ThresholdKeGroup> {
+    index: u16,
+    element: KG::Pk,
+    proof_commitments: (KG::Pk, KG::Pk),
+    proof_response: KG::Sk,
+}
+
+impl<KG: ThresholdKeGroup> DhShare<KG> {
+    /// Returns the index of the participant that computed the share
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Serialization into bytes, which are the two-byte big-endian index of the
+    /// participant followed by the element and its proof
+    pub fn serialize(&self) -> Vec<u8> {
+        self.index
+            .to_be_bytes()
+            .into_iter()
+            .chain(KG::serialize_pk(self.element))
+            .chain(KG::serialize_pk(self.proof_commitments.0))
+            .chain(KG::serialize_pk(self.proof_commitments.1))
+            .chain(KG::serialize_sk(self.proof_response))
+            .collect()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, DkgError> {
+        let pk_len = KG::PkLen::USIZE;
+        if input.len() != 2 + 3 * pk_len + KG::SkLen::USIZE {
+            return Err(DkgError::InvalidByteSequence);
+        }
+
+        let (index, input) = input.split_at(2);
+        let (element, input) = input.split_at(pk_len);
+        let (nonce_commitment, input) = input.split_at(pk_len);
+        let (pk_commitment, proof_response) = input.split_at(pk_len);
+        let deserialize_pk =
+            |bytes| KG::deserialize_pk(bytes).map_err(|_| DkgError::InvalidByteSequence);
+
+        Ok(Self {
+            index: u16::from_be_bytes([index[0], index[1]]),
+            element: deserialize_pk(element)?,
+            proof_commitments: (
+                deserialize_pk(nonce_commitment)?,
+                deserialize_pk(pk_commitment)?,
+            ),
+            proof_response: KG::deserialize_sk(proof_response)
+                .map_err(|_| DkgError::InvalidByteSequence)?,
+        })
+    }
+}
+
+/// Collects the [`DhShare`]s of a [`ThresholdSecretKey`] from the participants
+pub trait DhShareSource<CS: CipherSuite>: Clone
+where
+    CS::KeGroup: ThresholdKeGroup,
+{
+    /// Custom error type that can be passed down to [`DkgError::Source`]
+    type Error;
+
+    /// Returns the [`DhShare`]s of the Diffie-Hellman output with `pk` of at
+    /// least the threshold of participants
+    fn dh_shares(
+        &self,
+        pk: &PublicKey<CS::KeGroup>,
+    ) -> Result<Vec<DhShare<CS::KeGroup>>, Self::Error>;
+}
+
+/// Computes the [`DhShare`]s of all [`KeyShare`]s in one place, which is only
+/// useful for testing
+impl<CS: CipherSuite> DhShareSource<CS> for Vec<KeyShare<CS::KeGroup>>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = DkgError;
+
+    fn dh_shares(
+        &self,
+        pk: &PublicKey<CS::KeGroup>,
+    ) -> Result<Vec<DhShare<CS::KeGroup>>, Self::Error> {
+        self.iter()
+            .map(|key_share| key_share.dh_share::<CS>(pk))
+            .collect()
+    }
+}
+
+/// The static key of the server shared among the participants of a
+/// distributed key generation, see the [module documentation](self)
+#[derive_where(Clone; P)]
+pub struct ThresholdSecretKey<CS: CipherSuite, P: DhShareSource<CS>>
+where
+    CS::KeGroup: ThresholdKeGroup,
+{
+    package: PublicKeyPackage<CS::KeGroup>,
+    source: P,
+}
+
+impl<CS: CipherSuite, P: DhShareSource<CS>> ThresholdSecretKey<CS, P>
+where
+    CS::KeGroup: ThresholdKeGroup,
+{
+    /// Combines the shares of the threshold of participants of `package`
+    /// collected by `source`
+    pub fn new(package: PublicKeyPackage<CS::KeGroup>, source: P) -> Self {
+        Self { package, source }
+    }
+
+    /// Returns the public keys the [`DhShare`]s are checked against
+    pub fn package(&self) -> &PublicKeyPackage<CS::KeGroup> {
+        &self.package
+    }
+
+    /// Returns the source of the [`DhShare`]s
+    pub fn source(&self) -> &P {
+        &self.source
+    }
+}
+
+impl<CS: CipherSuite, P: DhShareSource<CS>> SecretKey<CS::KeGroup> for ThresholdSecretKey<CS, P>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = DkgError<P::Error>;
+    type Len = U0;
+
+    fn diffie_hellman(
+        &self,
+        pk: PublicKey<CS::KeGroup>,
+    ) -> Result<GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>, InternalError<Self::Error>> {
+        let shares = self
+            .source
+            .dh_shares(&pk)
+            .map_err(|error| InternalError::Custom(DkgError::Source(error)))?;
+
+        combine_dh_shares::<CS>(&self.package, &pk, &shares)
+            .map_err(|error| InternalError::Custom(error.into_custom()))
+    }
+
+    fn public_key(&self) -> Result<PublicKey<CS::KeGroup>, InternalError<Self::Error>> {
+        Ok(self.package.public_key())
+    }
+
+    fn serialize(&self) -> GenericArray<u8, Self::Len> {
+        GenericArray::default()
+    }
+
+    fn deserialize(_input: &[u8]) -> Result<Self, InternalError<Self::Error>> {
+        Err(InternalError::InvalidByteSequence)
+    }
+}
+
+/// Checks the [`DhShare`]s with `pk` of at least the threshold of participants
+/// of `package` and combines them into the serialized Diffie-Hellman output of
+/// the static key of the server
+///
+/// # Errors
+/// - [`DkgError::NotEnoughShares`] if there are fewer shares than the
+///   threshold
+/// - [`DkgError::UnexpectedParticipant`] if a share has index zero, greater
+///   than the number of participants or appears more than once
+/// - [`DkgError::InvalidDhShare`] with the index of the first share whose proof
+///   fails
+/// - [`DkgError::IdentityElement`] if the output is the identity element
+pub fn combine_dh_shares<CS: CipherSuite>(
+    package: &PublicKeyPackage<CS::KeGroup>,
+    pk: &PublicKey<CS::KeGroup>,
+    shares: &[DhShare<CS::KeGroup>],
+) -> Result<GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>, DkgError>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    if shares.len() < usize::from(package.threshold) {
+        return Err(DkgError::NotEnoughShares);
+    }
+
+    for (position, share) in shares.iter().enumerate() {
+        if share.index == 0
+            || share.index > package.participants
+            || shares[..position]
+                .iter()
+                .any(|other| other.index == share.index)
+        {
+            return Err(DkgError::UnexpectedParticipant(share.index));
+        }
+    }
+
+    let pk = pk.to_group_type();
+    for share in shares {
+        let verifying_share = verifying_share::<CS::KeGroup>(
+            &package.commitments,
+            package.participants,
+            share.index,
+        )?;
+        let challenge = dh_share_challenge::<CS>(
+            share.index,
+            verifying_share,
+            pk,
+            share.element,
+            share.proof_commitments,
+        )?;
+        let expected = (
+            CS::KeGroup::add_pk(
+                share.proof_commitments.0,
+                CS::KeGroup::mul_pk(verifying_share, challenge),
+            ),
+            CS::KeGroup::add_pk(
+                share.proof_commitments.1,
+                CS::KeGroup::mul_pk(share.element, challenge),
+            ),
+        );
+
+        if bool::from(
+            CS::KeGroup::is_identity_pk(expected.0) | CS::KeGroup::is_identity_pk(expected.1),
+        ) || CS::KeGroup::serialize_pk(CS::KeGroup::public_key(share.proof_response))
+            != CS::KeGroup::serialize_pk(expected.0)
+            || CS::KeGroup::serialize_pk(CS::KeGroup::mul_pk(pk, share.proof_response))
+                != CS::KeGroup::serialize_pk(expected.1)
+        {
+            return Err(DkgError::InvalidDhShare(share.index));
+        }
+    }
+
+    let mut output = None;
+    for share in shares {
+        // The Lagrange coefficient of the participant for the interpolation at
+        // zero
+        let x = CS::KeGroup::sk_from_u16(share.index);
+        let mut numerator = CS::KeGroup::sk_from_u16(1);
+        let mut denominator = CS::KeGroup::sk_from_u16(1);
+        for other in shares.iter().filter(|other| other.index != share.index) {
+            let other_x = CS::KeGroup::sk_from_u16(other.index);
+            numerator = CS::KeGroup::mul_sk(numerator, other_x);
+            denominator = CS::KeGroup::mul_sk(denominator, CS::KeGroup::sub_sk(other_x, x));
+        }
+        let coefficient = CS::KeGroup::mul_sk(numerator, CS::KeGroup::invert_sk(denominator));
+
+        let term = CS::KeGroup::mul_pk(share.element, coefficient);
+        output = Some(match output {
+            Some(output) => CS::KeGroup::add_pk(output, term),
+            None => term,
+        });
+    }
+
+    match output {
+        Some(output) if !bool::from(CS::KeGroup::is_identity_pk(output)) => {
+            Ok(CS::KeGroup::serialize_pk(output))
+        }
+        _ => Err(DkgError::IdentityElement),
+    }
+}
+
+/// Starts the distributed key generation for the participant with
+/// `parameters`, returning its secret state and the package to broadcast to
+/// all other participants
+///
+/// All participants have to use the same `context`, which should be unique to
+/// this run of the key generation.
+///
+/// # Errors
+/// [`DkgError::Library`] if `context` is too long to be hashed
+#[allow(clippy::type_complexity)]
+pub fn part1<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    parameters: Parameters,
+    context: &[u8],
+) -> Result<(Round1Secret<CS::KeGroup>, Round1Package<CS::KeGroup>), DkgError>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let coefficients: Vec<_> = (0..parameters.threshold)
+        .map(|_| CS::KeGroup::random_sk(rng))
+        .collect::<Result<_, _>>()
+        .map_err(|_| DkgError::RandomnessError)?;
+    let commitments: Vec<_> = coefficients
+        .iter()
+        .map(|coefficient| CS::KeGroup::public_key(*coefficient))
+        .collect();
+
+    // Schnorr proof of knowledge of the secret, so that a participant can't
+    // choose its commitment depending on the ones of the others
+    let nonce = CS::KeGroup::random_sk(rng).map_err(|_| DkgError::RandomnessError)?;
+    let proof_commitment = CS::KeGroup::public_key(nonce);
+    let challenge = challenge::<CS>(parameters.index, context, commitments[0], proof_commitment)?;
+    let proof_response =
+        CS::KeGroup::add_sk(nonce, CS::KeGroup::mul_sk(coefficients[0], challenge));
+
+    let package = Round1Package {
+        commitments: commitments.clone(),
+        proof_commitment,
+        proof_response,
+    };
+
+    Ok((
+        Round1Secret {
+            parameters,
+            coefficients,
+            commitments,
+        },
+        package,
+    ))
+}
+
+/// Checks the packages broadcast by all other participants, indexed by their
+/// participant, and returns the secret state and the packages to send to
+/// each other participant
+///
+/// # Errors
+/// - [`DkgError::MissingParticipant`] or [`DkgError::UnexpectedParticipant`] if
+///   `packages` aren't from every other participant exactly once
+/// - [`DkgError::InvalidProof`] if the package of a participant is invalid
+#[allow(clippy::type_complexity)]
+pub fn part2<CS: CipherSuite>(
+    secret: Round1Secret<CS::KeGroup>,
+    packages: &[(u16, Round1Package<CS::KeGroup>)],
+    context: &[u8],
+) -> Result<
+    (
+        Round2Secret<CS::KeGroup>,
+        Vec<(u16, Round2Package<CS::KeGroup>)>,
+    ),
+    DkgError,
+>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let parameters = secret.parameters;
+    parameters.check_others(packages.iter().map(|(index, _)| *index))?;
+
+    for (index, package) in packages {
+        if package.commitments.len() != usize::from(parameters.threshold) {
+            return Err(DkgError::InvalidProof(*index));
+        }
+
+        let challenge = challenge::<CS>(
+            *index,
+            context,
+            package.commitments[0],
+            package.proof_commitment,
+        )?;
+        let expected = CS::KeGroup::add_pk(
+            package.proof_commitment,
+            CS::KeGroup::mul_pk(package.commitments[0], challenge),
+        );
+
+        if bool::from(CS::KeGroup::is_identity_pk(expected))
+            || CS::KeGroup::serialize_pk(CS::KeGroup::public_key(package.proof_response))
+                != CS::KeGroup::serialize_pk(expected)
+        {
+            return Err(DkgError::InvalidProof(*index));
+        }
+    }
+
+    let shares = packages
+        .iter()
+        .map(|(index, _)| {
+            (
+                *index,
+                Round2Package {
+                    share: evaluate_polynomial::<CS::KeGroup>(&secret.coefficients, *index),
+                },
+            )
+        })
+        .collect();
+    let commitments = [(parameters.index, secret.commitments.clone())]
+        .into_iter()
+        .chain(
+            packages
+                .iter()
+                .map(|(index, package)| (*index, package.commitments.clone())),
+        )
+        .collect();
+
+    Ok((
+        Round2Secret {
+            parameters,
+            share: evaluate_polynomial::<CS::KeGroup>(&secret.coefficients, parameters.index),
+            commitments,
+        },
+        shares,
+    ))
+}
+
+/// Checks the secret shares sent by all other participants, indexed by their
+/// participant, and returns the [`KeyShare`] of this participant
+///
+/// # Errors
+/// - [`DkgError::MissingParticipant`] or [`DkgError::UnexpectedParticipant`] if
+///   `packages` aren't from every other participant exactly once
+/// - [`DkgError::InvalidShare`] if the share of a participant doesn't match its
+///   commitments
+/// - [`DkgError::IdentityElement`] if the public key is the identity element,
+///   which only happens with invalid commitments
+pub fn part3<CS: CipherSuite>(
+    secret: Round2Secret<CS::KeGroup>,
+    packages: &[(u16, Round2Package<CS::KeGroup>)],
+) -> Result<KeyShare<CS::KeGroup>, DkgError>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let parameters = secret.parameters;
+    parameters.check_others(packages.iter().map(|(index, _)| *index))?;
+
+    let mut share = secret.share;
+    for (index, package) in packages {
+        let commitments = secret
+            .commitments
+            .iter()
+            .find(|(other, _)| other == index)
+            .map(|(_, commitments)| commitments)
+            .ok_or(DkgError::UnexpectedParticipant(*index))?;
+        let expected = evaluate_commitments::<CS::KeGroup>(commitments, parameters.index);
+
+        if bool::from(CS::KeGroup::is_identity_pk(expected))
+            || CS::KeGroup::serialize_pk(CS::KeGroup::public_key(package.share))
+                != CS::KeGroup::serialize_pk(expected)
+        {
+            return Err(DkgError::InvalidShare(*index));
+        }
+
+        share = CS::KeGroup::add_sk(share, package.share);
+    }
+
+    let mut commitments = secret.commitments[0].1.clone();
+    for (_, other) in &secret.commitments[1..] {
+        for (commitment, other) in commitments.iter_mut().zip(other) {
+            *commitment = CS::KeGroup::add_pk(*commitment, *other);
+        }
+    }
+
+    // All summed commitments are serialized with the key share, and the
+    // identity element has no serialization for every group
+    if commitments
+        .iter()
+        .any(|commitment| bool::from(CS::KeGroup::is_identity_pk(*commitment)))
+    {
+        return Err(DkgError::IdentityElement);
+    }
+
+    Ok(KeyShare {
+        parameters,
+        share,
+        commitments,
+    })
+}
+
+// The challenge of the proof of knowledge of participant `index`
+fn challenge<CS: CipherSuite>(
+    index: u16,
+    context: &[u8],
+    commitment: <CS::KeGroup as KeGroup>::Pk,
+    proof_commitment: <CS::KeGroup as KeGroup>::Pk,
+) -> Result<<CS::KeGroup as KeGroup>::Sk, DkgError>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    CS::KeGroup::hash_to_scalar::<OprfHash<CS>>(
+        &[
+            &index.to_be_bytes(),
+            &(context.len() as u64).to_be_bytes(),
+            context,
+            &CS::KeGroup::serialize_pk(commitment),
+            &CS::KeGroup::serialize_pk(proof_commitment),
+        ],
+        &[STR_DKG_CHALLENGE],
+    )
+    .map_err(DkgError::Library)
+}
+
+// The challenge of the proof of the Diffie-Hellman share `element` of
+// participant `index` with `pk`
+fn dh_share_challenge<CS: CipherSuite>(
+    index: u16,
+    verifying_share: <CS::KeGroup as KeGroup>::Pk,
+    pk: <CS::KeGroup as KeGroup>::Pk,
+    element: <CS::KeGroup as KeGroup>::Pk,
+    proof_commitments: (<CS::KeGroup as KeGroup>::Pk, <CS::KeGroup as KeGroup>::Pk),
+) -> Result<<CS::KeGroup as KeGroup>::Sk, DkgError>
+where
+    CS::KeGroup: ThresholdKeGroup,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    CS::KeGroup::hash_to_scalar::<OprfHash<CS>>(
+        &[
+            &index.to_be_bytes(),
+            &CS::KeGroup::serialize_pk(verifying_share),
+            &CS::KeGroup::serialize_pk(pk),
+            &CS::KeGroup::serialize_pk(element),
+            &CS::KeGroup::serialize_pk(proof_commitments.0),
+            &CS::KeGroup::serialize_pk(proof_commitments.1),
+        ],
+        &[STR_DKG_DH_SHARE_CHALLENGE],
+    )
+    .map_err(DkgError::Library)
+}
+
+// Returns the public key of the share of participant `index` from the summed
+// commitments
+fn verifying_share<KG: ThresholdKeGroup>(
+    commitments: &[KG::Pk],
+    participants: u16,
+    index: u16,
+) -> Result<KG::Pk, DkgError> {
+    if index == 0 || index > participants {
+        return Err(DkgError::UnexpectedParticipant(index));
+    }
+
+    let share = evaluate_commitments::<KG>(commitments, index);
+    if bool::from(KG::is_identity_pk(share)) {
+        return Err(DkgError::IdentityElement);
+    }
+
+    Ok(share)
+}
+
+// Evaluates the polynomial with `coefficients` at `index` with Horner's method
+fn evaluate_polynomial<KG: ThresholdKeGroup>(coefficients: &[KG::Sk], index: u16) -> KG::Sk {
+    let x = KG::sk_from_u16(index);
+    let (last, rest) = coefficients
+        .split_last()
+        .expect("the threshold is at least 1");
+
+    rest.iter().rev().fold(*last, |result, coefficient| {
+        KG::add_sk(KG::mul_sk(result, x), *coefficient)
+    })
+}
+
+// Evaluates the commitments to the coefficients of a polynomial at `index`,
+// which gives the public key of the evaluation of the polynomial
+fn evaluate_commitments<KG: ThresholdKeGroup>(commitments: &[KG::Pk], index: u16) -> KG::Pk {
+    let x = KG::sk_from_u16(index);
+    let (last, rest) = commitments
+        .split_last()
+        .expect("the threshold is at least 1");
+
+    rest.iter().rev().fold(*last, |result, commitment| {
+        KG::add_pk(KG::mul_pk(result, x), *commitment)
+    })
+}
@@ -0,0 +1,816 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! A single entry point for running the protocol over a byte transport
+//!
+//! A [`ProtocolDriver`] holds the state of one client or server flow. Each
+//! incoming frame is passed to [`ProtocolDriver::handle`], which determines
+//! the type of the contained [`Message`], advances the state, writes the
+//! outgoing frame, if any, into a caller-provided buffer and reports the
+//! outcome of the flow as an [`Event`]. See the
+//! [crate documentation](crate#protocol-driver) for an example.
+//!
+//! A frame consists of a single byte identifying the [`MessageType`], followed
+//! by the serialized message. Buffers of [`Message::max_len`] bytes fit every
+//! frame. All flows use the default parameters, the underlying types have to
+//! be used directly for anything else.
+
+use core::mem;
+use core::ops::Add;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::ArrayLength;
+use rand::{CryptoRng, RngCore};
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::utils::check_slice_size_atleast;
+use crate::errors::{InternalError, ProtocolError};
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, MacLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::keypair::{PrivateKey, SecretKey};
+use crate::messages::CredentialResponseWithoutKeLen;
+use crate::opaque::MaskedResponseLen;
+use crate::transparency::{self, RecordLog};
+use crate::{
+    max_message_sizes, ClientLogin, ClientLoginFinishParameters, ClientLoginFinishResult,
+    ClientRegistration, ClientRegistrationFinishParameters, ClientRegistrationFinishResult,
+    ClientRetrieval, ClientRetrievalFinishParameters, ClientRetrievalFinishResult,
+    CredentialFinalization, CredentialRequest, CredentialRequestLen, CredentialResponse,
+    CredentialResponseLen, RegistrationRequest, RegistrationResponse, RegistrationResponseLen,
+    RegistrationUpload, RegistrationUploadLen, RetrievalRequest, RetrievalResponse,
+    RetrievalResponseLen, ServerLogin, ServerLoginFinishResult, ServerLoginStartParameters,
+    ServerRegistration, ServerRetrieval, ServerSetup,
+};
+
+/// The type of a [`Message`], encoded as the first byte of a frame
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum MessageType {
+    /// [`RegistrationRequest`]
+    RegistrationRequest = 1,
+    /// [`RegistrationResponse`]
+    RegistrationResponse = 2,
+    /// [`RegistrationUpload`]
+    RegistrationUpload = 3,
+    /// [`CredentialRequest`]
+    CredentialRequest = 4,
+    /// [`CredentialResponse`]
+    CredentialResponse = 5,
+    /// [`CredentialFinalization`]
+    CredentialFinalization = 6,
+    /// [`RetrievalRequest`]
+    RetrievalRequest = 7,
+    /// [`RetrievalResponse`]
+    RetrievalResponse = 8,
+}
+
+/// Any of the messages of the protocol
+#[derive_where(Clone)]
+pub enum Message<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// A [`RegistrationRequest`]
+    RegistrationRequest(RegistrationRequest<CS>),
+    /// A [`RegistrationResponse`]
+    RegistrationResponse(RegistrationResponse<CS>),
+    /// A [`RegistrationUpload`]
+    RegistrationUpload(RegistrationUpload<CS>),
+    /// A [`CredentialRequest`]
+    CredentialRequest(CredentialRequest<CS>),
+    /// A [`CredentialResponse`]
+    CredentialResponse(CredentialResponse<CS>),
+    /// A [`CredentialFinalization`]
+    CredentialFinalization(CredentialFinalization<CS>),
+    /// A [`RetrievalRequest`]
+    RetrievalRequest(RetrievalRequest<CS>),
+    /// A [`RetrievalResponse`]
+    RetrievalResponse(RetrievalResponse<CS>),
+}
+
+/// The outcome of a flow, reported by [`ProtocolDriver::handle`] once the flow
+/// is finished
+#[derive_where(Clone)]
+pub enum Event<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The client finished registration, the outgoing frame holds the
+    /// [`RegistrationUpload`]
+    ClientRegistrationFinished(ClientRegistrationFinishResult<CS>),
+    /// The server received the [`RegistrationUpload`], and has to store the
+    /// resulting password file
+    ServerRegistrationFinished(ServerRegistration<CS>),
+    /// The client finished login, the outgoing frame holds the
+    /// [`CredentialFinalization`]
+    ClientLoginFinished(ClientLoginFinishResult<CS>),
+    /// The server authenticated the client
+    ServerLoginFinished(ServerLoginFinishResult<CS>),
+    /// The client finished retrieval
+    ClientRetrievalFinished(ClientRetrievalFinishResult<CS>),
+    /// The server answered a [`RetrievalRequest`], which it can't tell apart
+    /// from a failed retrieval
+    ServerRetrievalFinished,
+}
+
+/// The result of [`ProtocolDriver::handle`]
+#[derive_where(Clone)]
+pub struct Step<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The length of the frame written to the output buffer, which is `0` if
+    /// there is nothing to send
+    pub len: usize,
+    /// The outcome of the flow, if it is finished
+    pub event: Option<Event<CS>>,
+}
+
+/// Drives a client or server flow from incoming to outgoing frames, see the
+/// [module documentation](self)
+pub struct ProtocolDriver<
+    'a,
+    CS: CipherSuite,
+    S: SecretKey<CS::KeGroup> = PrivateKey<<CS as CipherSuite>::KeGroup>,
+> where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    state: DriverState<'a, CS, S>,
+}
+
+enum DriverState<'a, CS: CipherSuite, S: SecretKey<CS::KeGroup>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    ClientRegistration {
+        password: &'a [u8],
+        state: ClientRegistration<CS>,
+    },
+    ClientLogin {
+        password: &'a [u8],
+        state: ClientLogin<CS>,
+    },
+    ClientRetrieval {
+        password: &'a [u8],
+        state: ClientRetrieval<CS>,
+    },
+    Server {
+        server_setup: &'a ServerSetup<CS, S>,
+        credential_identifier: &'a [u8],
+        password_file: Option<ServerRegistration<CS>>,
+        record_log: Option<&'a mut dyn RecordLog>,
+    },
+    ServerLogin(ServerLogin<CS>),
+    Finished,
+}
+
+impl<CS: CipherSuite> Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Returns the type of the message
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Self::RegistrationRequest(_) => MessageType::RegistrationRequest,
+            Self::RegistrationResponse(_) => MessageType::RegistrationResponse,
+            Self::RegistrationUpload(_) => MessageType::RegistrationUpload,
+            Self::CredentialRequest(_) => MessageType::CredentialRequest,
+            Self::CredentialResponse(_) => MessageType::CredentialResponse,
+            Self::CredentialFinalization(_) => MessageType::CredentialFinalization,
+            Self::RetrievalRequest(_) => MessageType::RetrievalRequest,
+            Self::RetrievalResponse(_) => MessageType::RetrievalResponse,
+        }
+    }
+
+    /// The length of the largest frame of `CS`
+    pub fn max_len() -> usize {
+        max_message_sizes::<CS>().max() + 1
+    }
+
+    /// Writes the frame of this message into `output`, and returns its length
+    ///
+    /// # Errors
+    /// [`InternalError::SizeError`] if `output` is too small to hold the frame
+    pub fn serialize(&self, output: &mut [u8]) -> Result<usize, ProtocolError>
+    where
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+        // RetrievalResponse: CredentialResponseWithoutKeLen
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+    {
+        let message_type = self.message_type();
+
+        Ok(match self {
+            Self::RegistrationRequest(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::RegistrationResponse(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::RegistrationUpload(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::CredentialRequest(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::CredentialResponse(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::CredentialFinalization(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::RetrievalRequest(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+            Self::RetrievalResponse(message) => {
+                write_frame(message_type, &message.serialize(), output)?
+            }
+        })
+    }
+
+    /// Reads a message from its frame
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if the first byte is not a known
+    /// [`MessageType`], otherwise the errors of the message's `deserialize`
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_slice = check_slice_size_atleast(input, 1, "message_type")?;
+        let message = &checked_slice[1..];
+
+        Ok(match checked_slice[0] {
+            1 => Self::RegistrationRequest(RegistrationRequest::deserialize(message)?),
+            2 => Self::RegistrationResponse(RegistrationResponse::deserialize(message)?),
+            3 => Self::RegistrationUpload(RegistrationUpload::deserialize(message)?),
+            4 => Self::CredentialRequest(CredentialRequest::deserialize(message)?),
+            5 => Self::CredentialResponse(CredentialResponse::deserialize(message)?),
+            6 => Self::CredentialFinalization(CredentialFinalization::deserialize(message)?),
+            7 => Self::RetrievalRequest(RetrievalRequest::deserialize(message)?),
+            8 => Self::RetrievalResponse(RetrievalResponse::deserialize(message)?),
+            _ => return Err(ProtocolError::SerializationError),
+        })
+    }
+}
+
+impl<CS: CipherSuite> From<RegistrationRequest<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RegistrationRequest<CS>) -> Self {
+        Self::RegistrationRequest(message)
+    }
+}
+
+impl<CS: CipherSuite> From<RegistrationResponse<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RegistrationResponse<CS>) -> Self {
+        Self::RegistrationResponse(message)
+    }
+}
+
+impl<CS: CipherSuite> From<RegistrationUpload<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RegistrationUpload<CS>) -> Self {
+        Self::RegistrationUpload(message)
+    }
+}
+
+impl<CS: CipherSuite> From<CredentialRequest<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: CredentialRequest<CS>) -> Self {
+        Self::CredentialRequest(message)
+    }
+}
+
+impl<CS: CipherSuite> From<CredentialResponse<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: CredentialResponse<CS>) -> Self {
+        Self::CredentialResponse(message)
+    }
+}
+
+impl<CS: CipherSuite> From<CredentialFinalization<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: CredentialFinalization<CS>) -> Self {
+        Self::CredentialFinalization(message)
+    }
+}
+
+impl<CS: CipherSuite> From<RetrievalRequest<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RetrievalRequest<CS>) -> Self {
+        Self::RetrievalRequest(message)
+    }
+}
+
+impl<CS: CipherSuite> From<RetrievalResponse<CS>> for Message<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RetrievalResponse<CS>) -> Self {
+        Self::RetrievalResponse(message)
+    }
+}
+
+impl<'a, CS: CipherSuite> ProtocolDriver<'a, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Starts a client registration, writing the [`RegistrationRequest`] frame
+    /// into `output`, and returns the driver along with the frame's length
+    pub fn start_registration<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        password: &'a [u8],
+        output: &mut [u8],
+    ) -> Result<(Self, usize), ProtocolError> {
+        let result = ClientRegistration::<CS>::start(rng, password)?;
+        let len = write_frame(
+            MessageType::RegistrationRequest,
+            &result.message.serialize(),
+            output,
+        )?;
+
+        Ok((
+            Self {
+                state: DriverState::ClientRegistration {
+                    password,
+                    state: result.state,
+                },
+            },
+            len,
+        ))
+    }
+
+    /// Starts a client login, writing the [`CredentialRequest`] frame into
+    /// `output`, and returns the driver along with the frame's length
+    pub fn start_login<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        password: &'a [u8],
+        output: &mut [u8],
+    ) -> Result<(Self, usize), ProtocolError>
+    where
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+    {
+        let result = ClientLogin::<CS>::start(rng, password)?;
+        let len = write_frame(
+            MessageType::CredentialRequest,
+            &result.message.serialize(),
+            output,
+        )?;
+
+        Ok((
+            Self {
+                state: DriverState::ClientLogin {
+                    password,
+                    state: result.state,
+                },
+            },
+            len,
+        ))
+    }
+
+    /// Starts a client retrieval, writing the [`RetrievalRequest`] frame into
+    /// `output`, and returns the driver along with the frame's length
+    pub fn start_retrieval<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        password: &'a [u8],
+        output: &mut [u8],
+    ) -> Result<(Self, usize), ProtocolError> {
+        let result = ClientRetrieval::<CS>::start(rng, password)?;
+        let len = write_frame(
+            MessageType::RetrievalRequest,
+            &result.message.serialize(),
+            output,
+        )?;
+
+        Ok((
+            Self {
+                state: DriverState::ClientRetrieval {
+                    password,
+                    state: result.state,
+                },
+            },
+            len,
+        ))
+    }
+}
+
+impl<'a, CS: CipherSuite, S: SecretKey<CS::KeGroup>> ProtocolDriver<'a, CS, S>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Creates a server driver for `credential_identifier`
+    ///
+    /// `password_file` is the stored [`ServerRegistration`] of the credential,
+    /// or [`None`] if it is not registered, as in [`ServerLogin::start`]. The
+    /// driver then answers a registration, login or retrieval initiated by
+    /// the client.
+    pub fn server(
+        server_setup: &'a ServerSetup<CS, S>,
+        credential_identifier: &'a [u8],
+        password_file: Option<ServerRegistration<CS>>,
+    ) -> Self {
+        Self {
+            state: DriverState::Server {
+                server_setup,
+                credential_identifier,
+                password_file,
+                record_log: None,
+            },
+        }
+    }
+
+    /// Like [`ProtocolDriver::server`], but appends the commitment of the
+    /// password file of a finished registration to `record_log` before
+    /// reporting it, see the [`transparency`] module
+    ///
+    /// If `record_log` fails to append the entry, [`ProtocolDriver::handle`]
+    /// fails with [`ProtocolError::RecordLogError`] instead of reporting the
+    /// password file.
+    pub fn server_with_record_log(
+        server_setup: &'a ServerSetup<CS, S>,
+        credential_identifier: &'a [u8],
+        password_file: Option<ServerRegistration<CS>>,
+        record_log: &'a mut dyn RecordLog,
+    ) -> Self {
+        Self {
+            state: DriverState::Server {
+                server_setup,
+                credential_identifier,
+                password_file,
+                record_log: Some(record_log),
+            },
+        }
+    }
+
+    /// Returns `true` once the flow has reported its [`Event`] or failed
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, DriverState::Finished)
+    }
+
+    /// Processes an incoming frame, writes the outgoing frame, if any, into
+    /// `output`, and returns its length along with the [`Event`] of a finished
+    /// flow
+    ///
+    /// The driver is finished after any error, and can't be used to retry.
+    ///
+    /// # Errors
+    /// - [`ProtocolError::UnexpectedMessageError`] if the message is not
+    ///   expected in the current state
+    /// - [`InternalError::SizeError`] if `output` is too small to hold the
+    ///   outgoing frame
+    /// - the errors of the underlying protocol steps
+    pub fn handle<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<Step<CS>, ProtocolError<S::Error>>
+    where
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+        // RetrievalResponse: CredentialResponseWithoutKeLen
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+    {
+        let message = Message::<CS>::deserialize(input).map_err(|e| {
+            self.state = DriverState::Finished;
+            e.into_custom()
+        })?;
+
+        match (
+            mem::replace(&mut self.state, DriverState::Finished),
+            message,
+        ) {
+            (
+                DriverState::ClientRegistration { password, state },
+                Message::RegistrationResponse(message),
+            ) => {
+                let result = state
+                    .finish(
+                        rng,
+                        password,
+                        message,
+                        ClientRegistrationFinishParameters::default(),
+                    )
+                    .map_err(ProtocolError::into_custom)?;
+                let len = write_frame(
+                    MessageType::RegistrationUpload,
+                    &result.message.serialize(),
+                    output,
+                )?;
+
+                Ok(Step {
+                    len,
+                    event: Some(Event::ClientRegistrationFinished(result)),
+                })
+            }
+            (
+                DriverState::ClientLogin { password, state },
+                Message::CredentialResponse(message),
+            ) => {
+                let result = state
+                    .finish(password, message, ClientLoginFinishParameters::default())
+                    .map_err(ProtocolError::into_custom)?;
+                let len = write_frame(
+                    MessageType::CredentialFinalization,
+                    &result.message.serialize(),
+                    output,
+                )?;
+
+                Ok(Step {
+                    len,
+                    event: Some(Event::ClientLoginFinished(result)),
+                })
+            }
+            (
+                DriverState::ClientRetrieval { password, state },
+                Message::RetrievalResponse(message),
+            ) => {
+                let result = state
+                    .finish(
+                        password,
+                        message,
+                        ClientRetrievalFinishParameters::default(),
+                    )
+                    .map_err(ProtocolError::into_custom)?;
+
+                Ok(Step {
+                    len: 0,
+                    event: Some(Event::ClientRetrievalFinished(result)),
+                })
+            }
+            (
+                DriverState::Server {
+                    server_setup,
+                    credential_identifier,
+                    password_file,
+                    record_log,
+                },
+                Message::RegistrationRequest(message),
+            ) => {
+                let result =
+                    ServerRegistration::start(server_setup, message, credential_identifier)
+                        .map_err(ProtocolError::into_custom)?;
+                let len = write_frame(
+                    MessageType::RegistrationResponse,
+                    &result.message.serialize(),
+                    output,
+                )?;
+                self.state = DriverState::Server {
+                    server_setup,
+                    credential_identifier,
+                    password_file,
+                    record_log,
+                };
+
+                Ok(Step { len, event: None })
+            }
+            (
+                DriverState::Server {
+                    credential_identifier,
+                    record_log,
+                    ..
+                },
+                Message::RegistrationUpload(message),
+            ) => {
+                let password_file = ServerRegistration::finish(message);
+                if let Some(record_log) = record_log {
+                    transparency::append(record_log, credential_identifier, &password_file)
+                        .map_err(ProtocolError::into_custom)?;
+                }
+
+                Ok(Step {
+                    len: 0,
+                    event: Some(Event::ServerRegistrationFinished(password_file)),
+                })
+            }
+            (
+                DriverState::Server {
+                    server_setup,
+                    credential_identifier,
+                    password_file,
+                    ..
+                },
+                Message::CredentialRequest(message),
+            ) => {
+                let result = ServerLogin::start(
+                    rng,
+                    server_setup,
+                    password_file,
+                    message,
+                    credential_identifier,
+                    ServerLoginStartParameters::default(),
+                )?;
+                let len = write_frame(
+                    MessageType::CredentialResponse,
+                    &result.message.serialize(),
+                    output,
+                )?;
+                self.state = DriverState::ServerLogin(result.state);
+
+                Ok(Step { len, event: None })
+            }
+            (
+                DriverState::Server {
+                    server_setup,
+                    credential_identifier,
+                    password_file,
+                    ..
+                },
+                Message::RetrievalRequest(message),
+            ) => {
+                let result = ServerRetrieval::start(
+                    rng,
+                    server_setup,
+                    password_file,
+                    message,
+                    credential_identifier,
+                )?;
+                let len = write_frame(
+                    MessageType::RetrievalResponse,
+                    &result.message.serialize(),
+                    output,
+                )?;
+
+                Ok(Step {
+                    len,
+                    event: Some(Event::ServerRetrievalFinished),
+                })
+            }
+            (DriverState::ServerLogin(state), Message::CredentialFinalization(message)) => {
+                let result = state.finish(message).map_err(ProtocolError::into_custom)?;
+
+                Ok(Step {
+                    len: 0,
+                    event: Some(Event::ServerLoginFinished(result)),
+                })
+            }
+            _ => Err(ProtocolError::UnexpectedMessageError),
+        }
+    }
+}
+
+fn write_frame<T>(
+    message_type: MessageType,
+    message: &[u8],
+    output: &mut [u8],
+) -> Result<usize, InternalError<T>> {
+    let len = message.len() + 1;
+    let actual_len = output.len();
+    let output = output.get_mut(..len).ok_or(InternalError::SizeError {
+        name: "output",
+        len,
+        actual_len,
+    })?;
+
+    output[0] = message_type as u8;
+    output[1..].copy_from_slice(message);
+
+    Ok(len)
+}
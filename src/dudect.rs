@@ -0,0 +1,345 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Statistical timing leakage tests in the style of [dudect][CT-DUDECT],
+//! available with the `dudect` feature
+//!
+//! Each test measures an operation on inputs of two classes, a fixed and a
+//! random one, in a random order, and compares the distributions of the
+//! execution times with Welch's t-test. A t-statistic above [`THRESHOLD`]
+//! means that the execution time depends on the class, i.e. leaks secret
+//! information. [`check_suite`] runs the tests of the operations handling
+//! secrets of the protocol for a [`CipherSuite`]:
+//!
+//! ```ignore
+//! let report = opaque_ke::dudect::check_suite::<YourCipherSuite, _>(&mut OsRng, 10_000);
+//! assert!(!report.leaks(), "{report:?}");
+//! ```
+//!
+//! Timings are only meaningful in release builds on an otherwise idle machine.
+//! A test can only show the presence of a leak, never its absence, and more
+//! samples detect smaller leaks.
+//!
+//! [CT-DUDECT]: https://eprint.iacr.org/2016/1123
+
+use core::ops::Add;
+use std::time::Instant;
+use std::vec::Vec;
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
+use generic_array::{ArrayLength, GenericArray};
+use rand::{CryptoRng, RngCore};
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::envelope::{Envelope, EnvelopeLen};
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{MacLen, SuiteProvider};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::keypair::KeyPair;
+use crate::opaque::MaskedResponseLen;
+use crate::provider::Provider;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialFinalization, EnvelopeMode, Identifiers,
+    ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+/// The t-statistic above which a test is considered to have found a leak
+pub const THRESHOLD: f64 = 4.5;
+
+const PASSWORD: &[u8] = b"password";
+const CREDENTIAL_IDENTIFIER: &[u8] = b"credential identifier";
+
+// Measurements above this percentile are discarded for the cropped test, as
+// they are usually caused by interrupts rather than the operation
+const CROP_PERCENTILE: f64 = 0.9;
+
+/// The class of an input of a timing test
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Class {
+    /// The fixed input
+    Fixed,
+    /// A random input
+    Random,
+}
+
+/// The t-statistics of the tests run by [`check_suite`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Report {
+    /// Opening an envelope with a MAC that differs from the expected one only
+    /// in the last byte, against a random MAC
+    pub envelope_open: f64,
+    /// Verifying a client MAC that differs from the expected one only in the
+    /// last byte, against a random MAC
+    pub mac_verification: f64,
+    /// Answering a credential request with a real password file, against a
+    /// dummy one
+    pub login: f64,
+}
+
+impl Report {
+    /// Returns the largest t-statistic
+    pub fn max(&self) -> f64 {
+        self.envelope_open
+            .max(self.mac_verification)
+            .max(self.login)
+    }
+
+    /// Returns `true` if any t-statistic exceeds [`THRESHOLD`]
+    pub fn leaks(&self) -> bool {
+        self.max() > THRESHOLD
+    }
+}
+
+/// Measures `run` on `samples` inputs returned by `prepare` for a random
+/// [`Class`] each, and returns the t-statistic of the execution times
+///
+/// Only `run` is measured, so everything not under test, e.g. cloning a
+/// state, belongs in `prepare`.
+pub fn t_test<R, I, O>(
+    rng: &mut R,
+    samples: usize,
+    mut prepare: impl FnMut(&mut R, Class) -> I,
+    mut run: impl FnMut(&mut R, I) -> O,
+) -> f64
+where
+    R: CryptoRng + RngCore,
+{
+    let inputs: Vec<_> = (0..samples)
+        .map(|_| {
+            let class = if rng.next_u32() & 1 == 0 {
+                Class::Fixed
+            } else {
+                Class::Random
+            };
+            (class, prepare(rng, class))
+        })
+        .collect();
+
+    let mut timings = Vec::with_capacity(samples);
+    for (class, input) in inputs {
+        let start = Instant::now();
+        let output = run(rng, input);
+        let elapsed = start.elapsed();
+        // Dropping the output is not part of the measurement
+        drop(output);
+        timings.push((class, elapsed.as_nanos() as f64));
+    }
+
+    let mut sorted: Vec<_> = timings.iter().map(|(_, timing)| *timing).collect();
+    sorted.sort_by(f64::total_cmp);
+    let crop = sorted
+        .get((sorted.len() as f64 * CROP_PERCENTILE) as usize)
+        .copied()
+        .unwrap_or(f64::INFINITY);
+
+    welch(timings.iter().copied()).abs().max(
+        welch(
+            timings
+                .iter()
+                .copied()
+                .filter(|(_, timing)| *timing <= crop),
+        )
+        .abs(),
+    )
+}
+
+/// Runs the timing tests of `CS` with `samples` measurements each, see the
+/// [module documentation](self)
+pub fn check_suite<CS: CipherSuite, R: CryptoRng + RngCore>(rng: &mut R, samples: usize) -> Report
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+{
+    // Envelope
+
+    let (_, randomized_pwd_hasher) = SuiteProvider::<CS>::extract(None, &[PASSWORD]);
+    let server_keypair = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(rng).unwrap();
+    let envelope = Envelope::<CS>::seal(
+        rng,
+        &randomized_pwd_hasher,
+        server_keypair.public(),
+        Identifiers::default(),
+        &GenericArray::default(),
+        None,
+        None,
+        None,
+    )
+    .unwrap()
+    .0
+    .serialize();
+    let mac_range = NonceLen::USIZE..NonceLen::USIZE + MacLen::<CS>::USIZE;
+
+    let envelope_open = t_test(
+        rng,
+        samples,
+        |rng, class| {
+            let mut envelope = envelope.clone();
+            match class {
+                Class::Fixed => envelope[mac_range.end - 1] ^= 1,
+                Class::Random => rng.fill_bytes(&mut envelope[mac_range.clone()]),
+            }
+            Envelope::<CS>::deserialize(&envelope).unwrap()
+        },
+        |_, envelope| {
+            envelope.open(
+                &randomized_pwd_hasher,
+                server_keypair.public().clone(),
+                Identifiers::default(),
+                EnvelopeMode::Internal,
+                None,
+            )
+        },
+    );
+
+    // MAC verification
+
+    let server_setup = ServerSetup::<CS>::new(rng).unwrap();
+    let password_file = register(rng, &server_setup);
+    let client_login_start_result = ClientLogin::<CS>::start(rng, PASSWORD).unwrap();
+    let credential_request = client_login_start_result.message.clone();
+    let server_login_start_result = ServerLogin::start(
+        rng,
+        &server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+        ServerLoginStartParameters::default(),
+    )
+    .unwrap();
+    let credential_finalization = client_login_start_result
+        .state
+        .finish(
+            PASSWORD,
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )
+        .unwrap()
+        .message
+        .serialize();
+
+    let mac_verification = t_test(
+        rng,
+        samples,
+        |rng, class| {
+            let mut credential_finalization = credential_finalization.clone();
+            match class {
+                Class::Fixed => *credential_finalization.last_mut().unwrap() ^= 1,
+                Class::Random => rng.fill_bytes(&mut credential_finalization),
+            }
+            (
+                server_login_start_result.state.clone(),
+                CredentialFinalization::<CS>::deserialize(&credential_finalization).unwrap(),
+            )
+        },
+        |_, (state, credential_finalization)| state.finish(credential_finalization),
+    );
+
+    // Login
+
+    let login = t_test(
+        rng,
+        samples,
+        |_, class| {
+            let password_file = match class {
+                Class::Fixed => Some(password_file.clone()),
+                Class::Random => None,
+            };
+            (password_file, credential_request.clone())
+        },
+        |rng, (password_file, credential_request)| {
+            ServerLogin::start(
+                rng,
+                &server_setup,
+                password_file,
+                credential_request,
+                CREDENTIAL_IDENTIFIER,
+                ServerLoginStartParameters::default(),
+            )
+        },
+    );
+
+    Report {
+        envelope_open,
+        mac_verification,
+        login,
+    }
+}
+
+// Welch's t-statistic of the timings of both classes, computed with Welford's
+// online algorithm
+fn welch(timings: impl Iterator<Item = (Class, f64)>) -> f64 {
+    let mut count = [0f64; 2];
+    let mut mean = [0f64; 2];
+    let mut m2 = [0f64; 2];
+
+    for (class, timing) in timings {
+        let class = class as usize;
+        count[class] += 1.;
+        let delta = timing - mean[class];
+        mean[class] += delta / count[class];
+        m2[class] += delta * (timing - mean[class]);
+    }
+
+    if count[0] < 2. || count[1] < 2. {
+        return 0.;
+    }
+
+    let variance = [m2[0] / (count[0] - 1.), m2[1] / (count[1] - 1.)];
+    let denominator = (variance[0] / count[0] + variance[1] / count[1]).sqrt();
+
+    if denominator == 0. {
+        0.
+    } else {
+        (mean[0] - mean[1]) / denominator
+    }
+}
+
+fn register<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    server_setup: &ServerSetup<CS>,
+) -> ServerRegistration<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let client_registration_start_result = ClientRegistration::<CS>::start(rng, PASSWORD).unwrap();
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        server_setup,
+        client_registration_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+    )
+    .unwrap();
+    let client_registration_finish_result = client_registration_start_result
+        .state
+        .finish(
+            rng,
+            PASSWORD,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+        .unwrap();
+
+    ServerRegistration::finish(client_registration_finish_result.message)
+}
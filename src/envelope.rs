@@ -9,35 +9,33 @@
 use core::convert::TryFrom;
 use core::ops::Add;
 
-use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
-    Aes256Gcm, Key,
-};
 use derive_where::derive_where;
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::{Output, OutputSizeUser};
 use generic_array::sequence::Concat;
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U2, U256, U32};
+use generic_array::typenum::{
+    IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U12, U2, U256, U32,
+};
 use generic_array::{ArrayLength, GenericArray};
-use hkdf::Hkdf;
-use hmac::{Hmac, Mac};
 use rand::{CryptoRng, RngCore};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::ciphersuite::{CipherSuite, OprfHash};
 use crate::errors::utils::check_slice_size;
 use crate::errors::{InternalError, ProtocolError};
-use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::hash::{Hash, ProxyHash};
 use crate::key_exchange::group::KeGroup;
-use crate::keypair::{KeyPair, PublicKey};
+use crate::key_exchange::traits::{MacLen, SuitePrk, SuiteProvider};
+use crate::key_exchange::tripledh::truncate_mac;
+use crate::keypair::{KeyPair, PrivateKey, PublicKey, SecretKey};
+use crate::ksf::KsfParameters;
 use crate::opaque::{bytestrings_from_identifiers, Identifiers};
-use crate::serialization::{Input, MacExt};
+use crate::provider::Provider;
+use crate::serialization::Input;
+use crate::util::fill_random;
 
 // Constant string used as salt for each HKDF computation
-const STR_AUTH_KEY: [u8; 7] = *b"AuthKey";
-const STR_EXPORT_KEY: [u8; 9] = *b"ExportKey";
-const STR_WRAPPING_KEY: [u8; 11] = *b"WrappingKey";
-const STR_PRIVATE_KEY: [u8; 10] = *b"PrivateKey";
+const STR_APP_DATA_PAD: [u8; 18] = *b"ApplicationDataPad";
 type NonceLen = U32;
 
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -45,6 +43,8 @@ type NonceLen = U32;
 pub(crate) enum InnerEnvelopeMode {
     Zero = 0,
     Internal = 1,
+    External = 2,
+    Delegated = 3,
 }
 
 impl Zeroize for InnerEnvelopeMode {
@@ -58,11 +58,31 @@ impl TryFrom<u8> for InnerEnvelopeMode {
     fn try_from(x: u8) -> Result<Self, Self::Error> {
         match x {
             1 => Ok(InnerEnvelopeMode::Internal),
+            2 => Ok(InnerEnvelopeMode::External),
+            3 => Ok(InnerEnvelopeMode::Delegated),
             _ => Err(ProtocolError::SerializationError),
         }
     }
 }
 
+/// The mode in which the client's static keypair is stored in the envelope
+///
+/// The mode is not part of the serialized envelope, so the client has to
+/// specify at login the mode that was used at registration.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EnvelopeMode {
+    /// The client's keypair is derived from the randomized password, as
+    /// specified by the current OPRF-based protocol
+    #[default]
+    Internal,
+    /// The client brings its own private key, which is encrypted inside the
+    /// envelope, see [`ClientRegistrationFinishParameters::client_private_key`]
+    ///
+    /// [`ClientRegistrationFinishParameters::client_private_key`]: crate::ClientRegistrationFinishParameters::client_private_key
+    External,
+}
+
 /// This struct is an instantiation of the envelope.
 ///
 /// Note that earlier versions of this specification described an implementation
@@ -71,6 +91,11 @@ impl TryFrom<u8> for InnerEnvelopeMode {
 /// The specification update has simplified this assumption by taking an
 /// XOR-based approach without compromising on security, and to avoid the
 /// confusion around the implementation of an RKR-secure encryption.
+///
+/// The optional application data (of length [`CipherSuite::AppDataLen`]) is
+/// encrypted using the same XOR-based approach, and is covered by the envelope
+/// MAC. In [`EnvelopeMode::External`], the first `SkLen` bytes of the
+/// application data hold the client's private key instead.
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
@@ -88,7 +113,8 @@ where
 {
     pub(crate) mode: InnerEnvelopeMode,
     nonce: GenericArray<u8, NonceLen>,
-    hmac: Output<OprfHash<CS>>,
+    hmac: GenericArray<u8, MacLen<CS>>,
+    app_data: GenericArray<u8, CS::AppDataLen>,
 }
 
 // Note that this struct represents an envelope that has been "opened" with the
@@ -104,19 +130,22 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    pub(crate) client_static_keypair: KeyPair<CS::KeGroup>,
+    // `None` if the client's private key is held outside of this crate
+    pub(crate) client_static_keypair: Option<KeyPair<CS::KeGroup>>,
     pub(crate) export_key: Output<OprfHash<CS>>,
+    pub(crate) app_data: GenericArray<u8, CS::AppDataLen>,
     pub(crate) id_u: Input<'a, U2, <CS::KeGroup as KeGroup>::PkLen>,
     pub(crate) id_s: Input<'a, U2, <CS::KeGroup as KeGroup>::PkLen>,
 }
 
-pub(crate) struct OpenedInnerEnvelope<D: Hash>
+pub(crate) struct OpenedInnerEnvelope<D: Hash, L: ArrayLength<u8>>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     pub(crate) export_key: Output<D>,
+    pub(crate) app_data: GenericArray<u8, L>,
 }
 
 #[cfg(not(test))]
@@ -133,7 +162,8 @@ type SealResult<CS: CipherSuite> = (
     Output<OprfHash<CS>>,
 );
 
-pub(crate) type EnvelopeLen<CS: CipherSuite> = Sum<NonceLen, OutputSize<OprfHash<CS>>>;
+pub(crate) type EnvelopeLen<CS: CipherSuite> =
+    Sum<Sum<NonceLen, MacLen<CS>>, <CS as CipherSuite>::AppDataLen>;
 
 impl<CS: CipherSuite> Envelope<CS>
 where
@@ -144,20 +174,49 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    #[allow(clippy::type_complexity)]
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
     pub(crate) fn seal<R: RngCore + CryptoRng>(
         rng: &mut R,
-        randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+        randomized_pwd_hasher: &SuitePrk<CS>,
         server_s_pk: &PublicKey<CS::KeGroup>,
         ids: Identifiers,
+        app_data: &GenericArray<u8, CS::AppDataLen>,
+        client_private_key: Option<&PrivateKey<CS::KeGroup>>,
+        client_public_key: Option<&PublicKey<CS::KeGroup>>,
+        nonce: Option<GenericArray<u8, NonceLen>>,
     ) -> Result<SealResult<CS>, ProtocolError> {
-        let mut nonce = GenericArray::default();
-        rng.fill_bytes(&mut nonce);
+        let nonce = match nonce {
+            Some(nonce) => nonce,
+            None => {
+                let mut nonce = GenericArray::default();
+                fill_random(rng, &mut nonce)?;
+                nonce
+            }
+        };
 
-        let (mode, client_s_pk) = (
-            InnerEnvelopeMode::Internal,
-            build_inner_envelope_internal::<CS>(randomized_pwd_hasher.clone(), nonce)?,
-        );
+        let mut app_data = app_data.clone();
+        let (mode, client_s_pk) = match (client_private_key, client_public_key) {
+            // Without client authentication, the server's public key takes the
+            // place of the client's
+            (None, None) if !CS::CLIENT_AUTHENTICATION => {
+                (InnerEnvelopeMode::Internal, server_s_pk.clone())
+            }
+            (_, _) if !CS::CLIENT_AUTHENTICATION => {
+                return Err(InternalError::IncompatibleEnvelopeModeError.into())
+            }
+            (None, None) => (
+                InnerEnvelopeMode::Internal,
+                build_inner_envelope_internal::<CS>(randomized_pwd_hasher, nonce)?,
+            ),
+            (Some(client_private_key), None) => (
+                InnerEnvelopeMode::External,
+                build_inner_envelope_external::<CS>(client_private_key, &mut app_data)?,
+            ),
+            (None, Some(client_public_key)) => {
+                (InnerEnvelopeMode::Delegated, client_public_key.clone())
+            }
+            (Some(_), Some(_)) => return Err(InternalError::IncompatibleEnvelopeModeError.into()),
+        };
 
         let server_s_pk_bytes = server_s_pk.serialize();
         let (id_u, id_s) = bytestrings_from_identifiers::<CS::KeGroup>(
@@ -167,7 +226,10 @@ where
         )?;
         let aad = construct_aad(id_u.iter(), id_s.iter(), &server_s_pk_bytes);
 
-        let result = Self::seal_raw(randomized_pwd_hasher, nonce, aad, mode)?;
+        let result = Self::seal_raw(randomized_pwd_hasher, nonce, &app_data, aad, mode);
+        app_data.zeroize();
+        let result = result?;
+
         Ok((
             result.0,
             client_s_pk,
@@ -181,44 +243,51 @@ where
     /// the aad field. Note that a new nonce is sampled for each call to seal.
     #[allow(clippy::type_complexity)]
     pub(crate) fn seal_raw<'a>(
-        randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+        randomized_pwd_hasher: &SuitePrk<CS>,
         nonce: GenericArray<u8, NonceLen>,
+        app_data: &GenericArray<u8, CS::AppDataLen>,
         aad: impl Iterator<Item = &'a [u8]>,
         mode: InnerEnvelopeMode,
     ) -> Result<SealRawResult<CS>, InternalError> {
         let mut hmac_key = Output::<OprfHash<CS>>::default();
         let mut export_key = Output::<OprfHash<CS>>::default();
+        let mut sealed_app_data = GenericArray::<_, CS::AppDataLen>::default();
 
-        randomized_pwd_hasher
-            .expand_multi_info(&[&nonce, &STR_AUTH_KEY], &mut hmac_key)
-            .map_err(|_| InternalError::HkdfError)?;
-        randomized_pwd_hasher
-            .expand_multi_info(&[&nonce, &STR_EXPORT_KEY], &mut export_key)
-            .map_err(|_| InternalError::HkdfError)?;
-
-        //let mut hmac = Hmac::<OprfHash<CS>>::new_from_slice(&hmac_key)
-        let mut hmac = <Hmac<OprfHash<CS>> as KeyInit>::new_from_slice(&hmac_key)
-            .map_err(|_| InternalError::HmacError)?;
-        hmac.update(&nonce);
-        hmac.update_iter(aad);
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&nonce, CS::AUTH_KEY_LABEL],
+            &mut hmac_key,
+        )?;
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&nonce, CS::EXPORT_KEY_LABEL],
+            &mut export_key,
+        )?;
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&nonce, &STR_APP_DATA_PAD],
+            &mut sealed_app_data,
+        )?;
 
-        let hmac_bytes = hmac.finalize().into_bytes();
+        for (x1, x2) in sealed_app_data.iter_mut().zip(app_data.iter()) {
+            *x1 ^= x2
+        }
 
-        let mut wrapping_key = [0u8; 32];
-        OsRng.fill_bytes(&mut wrapping_key);
+        let mut hmac = SuiteProvider::<CS>::new_mac(&hmac_key)?;
+        SuiteProvider::<CS>::update_mac(&mut hmac, &nonce);
+        SuiteProvider::<CS>::update_mac(&mut hmac, &sealed_app_data);
+        for chunk in aad {
+            SuiteProvider::<CS>::update_mac(&mut hmac, chunk);
+        }
 
-        let key: &Key<Aes256Gcm> = Key::<Aes256Gcm>::from_slice(export_key.as_slice());
-        let cipher = Aes256Gcm::new(&key);
-        let aes_nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96-bits; unique per message
-        let ciphertext = cipher
-            .encrypt(&aes_nonce, wrapping_key.as_slice())
-            .map_err(|_| InternalError::HkdfError)?; // TODO: change
+        let hmac_bytes = truncate_mac(&SuiteProvider::<CS>::finalize_mac(hmac));
 
         Ok((
             Self {
                 mode,
                 nonce,
                 hmac: hmac_bytes,
+                app_data: sealed_app_data,
             },
             export_key,
             #[cfg(test)]
@@ -228,32 +297,55 @@ where
 
     pub(crate) fn open<'a>(
         &self,
-        randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+        randomized_pwd_hasher: &SuitePrk<CS>,
         server_s_pk: PublicKey<CS::KeGroup>,
         optional_ids: Identifiers<'a>,
+        mode: EnvelopeMode,
+        client_public_key: Option<PublicKey<CS::KeGroup>>,
     ) -> Result<OpenedEnvelope<'a, CS>, ProtocolError> {
-        let client_static_keypair = match self.mode {
-            InnerEnvelopeMode::Zero => {
+        let (client_static_keypair, client_s_pk) = match (&self.mode, mode, client_public_key) {
+            (InnerEnvelopeMode::Zero, _, _) => {
                 return Err(InternalError::IncompatibleEnvelopeModeError.into())
             }
-            InnerEnvelopeMode::Internal => {
-                recover_keys_internal::<CS>(randomized_pwd_hasher.clone(), self.nonce)?
+            (_, EnvelopeMode::Internal, None) if !CS::CLIENT_AUTHENTICATION => {
+                (None, server_s_pk.clone())
+            }
+            (_, _, _) if !CS::CLIENT_AUTHENTICATION => {
+                return Err(InternalError::IncompatibleEnvelopeModeError.into())
+            }
+            (_, _, Some(client_public_key)) => (None, client_public_key),
+            (_, EnvelopeMode::Internal, None) => {
+                let keypair = recover_keys_internal::<CS>(randomized_pwd_hasher, self.nonce)?;
+                let client_s_pk = keypair.public().clone();
+                (Some(keypair), client_s_pk)
+            }
+            (_, EnvelopeMode::External, None) => {
+                let keypair =
+                    recover_keys_external::<CS>(randomized_pwd_hasher, self.nonce, &self.app_data)?;
+                let client_s_pk = keypair.public().clone();
+                (Some(keypair), client_s_pk)
             }
         };
 
         let server_s_pk_bytes = server_s_pk.serialize();
         let (id_u, id_s) = bytestrings_from_identifiers::<CS::KeGroup>(
             optional_ids,
-            client_static_keypair.public().serialize(),
+            client_s_pk.serialize(),
             server_s_pk_bytes.clone(),
         )?;
         let aad = construct_aad(id_u.iter(), id_s.iter(), &server_s_pk_bytes);
 
-        let opened = self.open_raw(randomized_pwd_hasher, aad)?;
+        let mut opened = self.open_raw(randomized_pwd_hasher, aad)?;
+
+        // The private key must not be handed out as application data
+        if mode == EnvelopeMode::External && client_static_keypair.is_some() {
+            opened.app_data[..<CS::KeGroup as KeGroup>::SkLen::USIZE].zeroize();
+        }
 
         Ok(OpenedEnvelope {
             client_static_keypair,
             export_key: opened.export_key,
+            app_data: opened.app_data,
             id_u,
             id_s,
         })
@@ -263,54 +355,111 @@ where
     /// if the key and aad used to construct the envelope are the same.
     pub(crate) fn open_raw<'a>(
         &self,
-        randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+        randomized_pwd_hasher: &SuitePrk<CS>,
         aad: impl Iterator<Item = &'a [u8]>,
-    ) -> Result<OpenedInnerEnvelope<OprfHash<CS>>, InternalError> {
+    ) -> Result<OpenedInnerEnvelope<OprfHash<CS>, CS::AppDataLen>, InternalError> {
         let mut hmac_key = Output::<OprfHash<CS>>::default();
         let mut export_key = Output::<OprfHash<CS>>::default();
+        let mut app_data = GenericArray::<_, CS::AppDataLen>::default();
+
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&self.nonce, CS::AUTH_KEY_LABEL],
+            &mut hmac_key,
+        )?;
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&self.nonce, CS::EXPORT_KEY_LABEL],
+            &mut export_key,
+        )?;
+
+        let mut hmac = SuiteProvider::<CS>::new_mac(&hmac_key)?;
+        SuiteProvider::<CS>::update_mac(&mut hmac, &self.nonce);
+        SuiteProvider::<CS>::update_mac(&mut hmac, &self.app_data);
+        for chunk in aad {
+            SuiteProvider::<CS>::update_mac(&mut hmac, chunk);
+        }
+        if !bool::from(SuiteProvider::<CS>::verify_mac(hmac, &self.hmac)) {
+            return Err(InternalError::SealOpenHmacError);
+        }
+
+        SuiteProvider::<CS>::expand(
+            randomized_pwd_hasher,
+            &[&self.nonce, &STR_APP_DATA_PAD],
+            &mut app_data,
+        )?;
+
+        for (x1, x2) in app_data.iter_mut().zip(self.app_data.iter()) {
+            *x1 ^= x2
+        }
 
-        randomized_pwd_hasher
-            .expand(&self.nonce.concat(STR_AUTH_KEY.into()), &mut hmac_key)
-            .map_err(|_| InternalError::HkdfError)?;
-        randomized_pwd_hasher
-            .expand(&self.nonce.concat(STR_EXPORT_KEY.into()), &mut export_key)
-            .map_err(|_| InternalError::HkdfError)?;
-
-        let mut hmac = <Hmac<OprfHash<CS>> as KeyInit>::new_from_slice(&hmac_key)
-            //let mut hmac = Hmac::<OprfHash<CS>>::new_from_slice(&hmac_key)
-            .map_err(|_| InternalError::HmacError)?;
-        hmac.update(&self.nonce);
-        hmac.update_iter(aad);
-        hmac.verify(&self.hmac)
-            .map_err(|_| InternalError::SealOpenHmacError)?;
-
-        Ok(OpenedInnerEnvelope { export_key })
+        Ok(OpenedInnerEnvelope {
+            export_key,
+            app_data,
+        })
+    }
+
+    // Returns the nonce to seal the envelope with. Suites that set
+    // `ATTEST_KSF_PARAMETERS` overwrite its trailing bytes with the parameters
+    // of the key stretching function, which the MAC then covers along with the
+    // rest of the nonce.
+    pub(crate) fn nonce_with_ksf_parameters<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        nonce: Option<GenericArray<u8, NonceLen>>,
+        ksf_parameters: &KsfParameters,
+    ) -> Result<Option<GenericArray<u8, NonceLen>>, ProtocolError> {
+        if !CS::ATTEST_KSF_PARAMETERS {
+            return Ok(nonce);
+        }
+
+        let mut nonce = match nonce {
+            Some(nonce) => nonce,
+            None => {
+                let mut nonce = GenericArray::default();
+                fill_random(rng, &mut nonce)?;
+                nonce
+            }
+        };
+        nonce[NonceLen::USIZE - U12::USIZE..].copy_from_slice(&ksf_parameters.serialize());
+
+        Ok(Some(nonce))
+    }
+
+    // The parameters of the key stretching function carried in the nonce, if
+    // the suite attests them
+    pub(crate) fn ksf_parameters(&self) -> Option<KsfParameters> {
+        if !CS::ATTEST_KSF_PARAMETERS {
+            return None;
+        }
+
+        KsfParameters::deserialize(&self.nonce[NonceLen::USIZE - U12::USIZE..]).ok()
     }
 
     // Creates a dummy envelope object that serializes to the all-zeros byte string
+    #[cfg(feature = "server")]
     pub(crate) fn dummy() -> Self {
         Self {
             mode: InnerEnvelopeMode::Zero,
             nonce: GenericArray::default(),
             hmac: GenericArray::default(),
+            app_data: GenericArray::default(),
         }
     }
 
     fn hmac_key_size() -> usize {
-        OutputSize::<OprfHash<CS>>::USIZE
-    }
-
-    pub(crate) fn len() -> usize {
-        OutputSize::<OprfHash<CS>>::USIZE + NonceLen::USIZE
+        MacLen::<CS>::USIZE
     }
 
     pub(crate) fn serialize(&self) -> GenericArray<u8, EnvelopeLen<CS>>
     where
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
     {
-        self.nonce.concat(self.hmac.clone())
+        self.nonce
+            .concat(self.hmac.clone())
+            .concat(self.app_data.clone())
     }
 
     pub(crate) fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
@@ -325,16 +474,23 @@ where
             InnerEnvelopeMode::Zero => {
                 return Err(InternalError::IncompatibleEnvelopeModeError.into())
             }
-            InnerEnvelopeMode::Internal => &bytes[NonceLen::USIZE..],
+            InnerEnvelopeMode::Internal
+            | InnerEnvelopeMode::External
+            | InnerEnvelopeMode::Delegated => &bytes[NonceLen::USIZE..],
         };
 
         let hmac_key_size = Self::hmac_key_size();
-        let hmac = check_slice_size(remainder, hmac_key_size, "hmac_key_size")?;
+        let checked_remainder = check_slice_size(
+            remainder,
+            hmac_key_size + CS::AppDataLen::USIZE,
+            "hmac_key_size",
+        )?;
 
         Ok(Self {
             mode,
             nonce,
-            hmac: GenericArray::clone_from_slice(hmac),
+            hmac: GenericArray::clone_from_slice(&checked_remainder[..hmac_key_size]),
+            app_data: GenericArray::clone_from_slice(&checked_remainder[hmac_key_size..]),
         })
     }
 }
@@ -342,7 +498,7 @@ where
 // Helper functions
 
 fn build_inner_envelope_internal<CS: CipherSuite>(
-    randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+    randomized_pwd_hasher: &SuitePrk<CS>,
     nonce: GenericArray<u8, NonceLen>,
 ) -> Result<PublicKey<CS::KeGroup>, ProtocolError>
 where
@@ -354,19 +510,48 @@ where
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     let mut keypair_seed = GenericArray::<_, <CS::KeGroup as KeGroup>::SkLen>::default();
-    randomized_pwd_hasher
-        .expand(&nonce.concat(STR_PRIVATE_KEY.into()), &mut keypair_seed)
-        .map_err(|_| InternalError::HkdfError)?;
-    let client_static_keypair =
-        KeyPair::<CS::KeGroup>::from_private_key_slice(&CS::KeGroup::serialize_sk(
-            CS::KeGroup::derive_auth_keypair::<CS::OprfCs>(keypair_seed)?,
-        ))?;
+    SuiteProvider::<CS>::expand(
+        randomized_pwd_hasher,
+        &[&nonce, CS::PRIVATE_KEY_LABEL],
+        &mut keypair_seed,
+    )?;
+    let client_static_keypair = KeyPair::<CS::KeGroup>::from_private_key_slice(
+        &CS::KeGroup::serialize_sk(CS::KeGroup::derive_auth_keypair_with::<CS::OprfCs>(
+            keypair_seed,
+            CS::AUTH_KEY_PAIR_INFO,
+            CS::AUTH_KEY_PAIR_DST,
+        )?),
+    )?;
 
     Ok(client_static_keypair.public().clone())
 }
 
+fn build_inner_envelope_external<CS: CipherSuite>(
+    client_private_key: &PrivateKey<CS::KeGroup>,
+    app_data: &mut GenericArray<u8, CS::AppDataLen>,
+) -> Result<PublicKey<CS::KeGroup>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let sk_len = <CS::KeGroup as KeGroup>::SkLen::USIZE;
+    if CS::AppDataLen::USIZE < sk_len {
+        return Err(InternalError::IncompatibleEnvelopeModeError.into());
+    }
+
+    let mut client_sk_bytes = client_private_key.serialize();
+    app_data[..sk_len].copy_from_slice(&client_sk_bytes);
+    client_sk_bytes.zeroize();
+
+    Ok(client_private_key.public_key()?)
+}
+
 fn recover_keys_internal<CS: CipherSuite>(
-    randomized_pwd_hasher: Hkdf<OprfHash<CS>>,
+    randomized_pwd_hasher: &SuitePrk<CS>,
     nonce: GenericArray<u8, NonceLen>,
 ) -> Result<KeyPair<CS::KeGroup>, ProtocolError>
 where
@@ -378,17 +563,61 @@ where
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     let mut keypair_seed = GenericArray::<_, <CS::KeGroup as KeGroup>::SkLen>::default();
-    randomized_pwd_hasher
-        .expand(&nonce.concat(STR_PRIVATE_KEY.into()), &mut keypair_seed)
-        .map_err(|_| InternalError::HkdfError)?;
-    let client_static_keypair =
-        KeyPair::<CS::KeGroup>::from_private_key_slice(&CS::KeGroup::serialize_sk(
-            CS::KeGroup::derive_auth_keypair::<CS::OprfCs>(keypair_seed)?,
-        ))?;
+    SuiteProvider::<CS>::expand(
+        randomized_pwd_hasher,
+        &[&nonce, CS::PRIVATE_KEY_LABEL],
+        &mut keypair_seed,
+    )?;
+    let client_static_keypair = KeyPair::<CS::KeGroup>::from_private_key_slice(
+        &CS::KeGroup::serialize_sk(CS::KeGroup::derive_auth_keypair_with::<CS::OprfCs>(
+            keypair_seed,
+            CS::AUTH_KEY_PAIR_INFO,
+            CS::AUTH_KEY_PAIR_DST,
+        )?),
+    )?;
 
     Ok(client_static_keypair)
 }
 
+// The keys have to be recovered before the envelope MAC can be verified, as the
+// MAC covers the client's public key. A private key that fails to deserialize
+// is therefore reported like a failed MAC check.
+fn recover_keys_external<CS: CipherSuite>(
+    randomized_pwd_hasher: &SuitePrk<CS>,
+    nonce: GenericArray<u8, NonceLen>,
+    sealed_app_data: &GenericArray<u8, CS::AppDataLen>,
+) -> Result<KeyPair<CS::KeGroup>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let sk_len = <CS::KeGroup as KeGroup>::SkLen::USIZE;
+    if CS::AppDataLen::USIZE < sk_len {
+        return Err(InternalError::IncompatibleEnvelopeModeError.into());
+    }
+
+    let mut app_data = GenericArray::<_, CS::AppDataLen>::default();
+    SuiteProvider::<CS>::expand(
+        randomized_pwd_hasher,
+        &[&nonce, &STR_APP_DATA_PAD],
+        &mut app_data,
+    )?;
+
+    for (x1, x2) in app_data.iter_mut().zip(sealed_app_data.iter()) {
+        *x1 ^= x2
+    }
+
+    let client_static_keypair = KeyPair::<CS::KeGroup>::from_private_key_slice(&app_data[..sk_len])
+        .map_err(|_| InternalError::SealOpenHmacError);
+    app_data.zeroize();
+
+    Ok(client_static_keypair?)
+}
+
 fn construct_aad<'a>(
     id_u: impl Iterator<Item = &'a [u8]>,
     id_s: impl Iterator<Item = &'a [u8]>,
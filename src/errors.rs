@@ -31,6 +31,11 @@ pub enum InternalError<T = Infallible> {
         /// actual
         actual_len: usize,
     },
+    /// Computing the length of {name} overflowed.
+    SizeOverflowError {
+        /// name
+        name: &'static str,
+    },
     /// Could not decompress point.
     PointError,
     /// Size of input is empty or longer then [`u16::MAX`].
@@ -41,6 +46,8 @@ pub enum InternalError<T = Infallible> {
     HmacError,
     /// Computing the key stretching function failed
     KsfError,
+    /// Computing the key stretching function was cancelled
+    KsfCancelledError,
     /** This error occurs when the envelope seal open hmac check fails
     HMAC check in seal open failed. */
     SealOpenHmacError,
@@ -68,11 +75,16 @@ impl<T: Debug> Debug for InternalError<T> {
                 .field("len", len)
                 .field("actual_len", actual_len)
                 .finish(),
+            Self::SizeOverflowError { name } => f
+                .debug_struct("SizeOverflowError")
+                .field("name", name)
+                .finish(),
             Self::PointError => f.debug_tuple("PointError").finish(),
             Self::HashToScalar => f.debug_tuple("HashToScalar").finish(),
             Self::HkdfError => f.debug_tuple("HkdfError").finish(),
             Self::HmacError => f.debug_tuple("HmacError").finish(),
             Self::KsfError => f.debug_tuple("KsfError").finish(),
+            Self::KsfCancelledError => f.debug_tuple("KsfCancelledError").finish(),
             Self::SealOpenHmacError => f.debug_tuple("SealOpenHmacError").finish(),
             Self::IncompatibleEnvelopeModeError => {
                 f.debug_tuple("IncompatibleEnvelopeModeError").finish()
@@ -103,11 +115,13 @@ impl InternalError {
                 len,
                 actual_len,
             },
+            Self::SizeOverflowError { name } => InternalError::SizeOverflowError { name },
             Self::PointError => InternalError::PointError,
             Self::HashToScalar => InternalError::HashToScalar,
             Self::HkdfError => InternalError::HkdfError,
             Self::HmacError => InternalError::HmacError,
             Self::KsfError => InternalError::KsfError,
+            Self::KsfCancelledError => InternalError::KsfCancelledError,
             Self::SealOpenHmacError => InternalError::SealOpenHmacError,
             Self::IncompatibleEnvelopeModeError => InternalError::IncompatibleEnvelopeModeError,
             Self::OprfError(error) => InternalError::OprfError(error),
@@ -149,6 +163,57 @@ pub enum ProtocolError<T = Infallible> {
     /** Identity group element was encountered during deserialization, which is
     invalid */
     IdentityGroupElementError,
+    /** The key stretching function parameters attested by the client are
+    below the minimum required by the server */
+    KsfPolicyError,
+    /// The random number generator failed to produce randomness
+    RandomnessError,
+    /** The label passed to the exporter starts with one of the reserved
+    prefixes */
+    ReservedLabelError,
+    /// The message is not expected in the current state of the protocol
+    UnexpectedMessageError,
+    /// The credential request was seen before by the replay cache
+    ReplayError,
+    /** The throttle token is invalid, or the time before which it is
+    rejected has not passed */
+    ThrottleError,
+    /// The registration upload was rejected by the server's validation
+    RegistrationRejected,
+    /** The client login state has no creation time, or is older than the
+    maximum age given when finishing the login */
+    ExpiredStateError,
+    /** The timed client login state was modified, or is opened with a
+    different state key than it was serialized with */
+    StateAuthenticationError,
+    /// The server identity was rejected by the client's verifier
+    ServerIdentityError,
+    /** The sealed server setup could not be opened, because the passphrase is
+    wrong or the data was modified */
+    KeystoreError,
+    /** A public key was rejected by the
+    [`KeyValidationPolicy`](crate::keypair::KeyValidationPolicy) */
+    KeyValidationError,
+    /** The protocol version announced by the peer differs from
+    [`PROTOCOL_VERSION`](crate::PROTOCOL_VERSION) */
+    ProtocolVersionError,
+    /** This party announced its protocol version, but the peer didn't announce
+    one */
+    ProtocolVersionMissing,
+    /** The registration receipt was not issued for the record and credential
+    identifier by the server holding the public key */
+    ReceiptError,
+    /// The record log failed to append the commitment of a password file
+    RecordLogError,
+    /** The migration message was not authenticated with the session key of
+    the login it was sent in */
+    MigrationError,
+    /** The password is shorter than
+    [`CipherSuite::MIN_PASSWORD_LEN`](crate::CipherSuite::MIN_PASSWORD_LEN) */
+    PasswordTooShortError,
+    /** The password is longer than
+    [`CipherSuite::MAX_PASSWORD_LEN`](crate::CipherSuite::MAX_PASSWORD_LEN) */
+    PasswordTooLongError,
 }
 
 impl<T: Debug> Debug for ProtocolError<T> {
@@ -161,6 +226,25 @@ impl<T: Debug> Debug for ProtocolError<T> {
             Self::SerializationError => f.debug_tuple("SerializationError").finish(),
             Self::ReflectedValueError => f.debug_tuple("ReflectedValueError").finish(),
             Self::IdentityGroupElementError => f.debug_tuple("IdentityGroupElementError").finish(),
+            Self::KsfPolicyError => f.debug_tuple("KsfPolicyError").finish(),
+            Self::RandomnessError => f.debug_tuple("RandomnessError").finish(),
+            Self::ReservedLabelError => f.debug_tuple("ReservedLabelError").finish(),
+            Self::UnexpectedMessageError => f.debug_tuple("UnexpectedMessageError").finish(),
+            Self::ReplayError => f.debug_tuple("ReplayError").finish(),
+            Self::ThrottleError => f.debug_tuple("ThrottleError").finish(),
+            Self::RegistrationRejected => f.debug_tuple("RegistrationRejected").finish(),
+            Self::ExpiredStateError => f.debug_tuple("ExpiredStateError").finish(),
+            Self::StateAuthenticationError => f.debug_tuple("StateAuthenticationError").finish(),
+            Self::ServerIdentityError => f.debug_tuple("ServerIdentityError").finish(),
+            Self::KeystoreError => f.debug_tuple("KeystoreError").finish(),
+            Self::KeyValidationError => f.debug_tuple("KeyValidationError").finish(),
+            Self::ProtocolVersionError => f.debug_tuple("ProtocolVersionError").finish(),
+            Self::ProtocolVersionMissing => f.debug_tuple("ProtocolVersionMissing").finish(),
+            Self::ReceiptError => f.debug_tuple("ReceiptError").finish(),
+            Self::RecordLogError => f.debug_tuple("RecordLogError").finish(),
+            Self::MigrationError => f.debug_tuple("MigrationError").finish(),
+            Self::PasswordTooShortError => f.debug_tuple("PasswordTooShortError").finish(),
+            Self::PasswordTooLongError => f.debug_tuple("PasswordTooLongError").finish(),
         }
     }
 }
@@ -196,10 +280,52 @@ impl ProtocolError {
             Self::SerializationError => ProtocolError::SerializationError,
             Self::ReflectedValueError => ProtocolError::ReflectedValueError,
             Self::IdentityGroupElementError => ProtocolError::IdentityGroupElementError,
+            Self::KsfPolicyError => ProtocolError::KsfPolicyError,
+            Self::RandomnessError => ProtocolError::RandomnessError,
+            Self::ReservedLabelError => ProtocolError::ReservedLabelError,
+            Self::UnexpectedMessageError => ProtocolError::UnexpectedMessageError,
+            Self::ReplayError => ProtocolError::ReplayError,
+            Self::ThrottleError => ProtocolError::ThrottleError,
+            Self::RegistrationRejected => ProtocolError::RegistrationRejected,
+            Self::ExpiredStateError => ProtocolError::ExpiredStateError,
+            Self::StateAuthenticationError => ProtocolError::StateAuthenticationError,
+            Self::ServerIdentityError => ProtocolError::ServerIdentityError,
+            Self::KeystoreError => ProtocolError::KeystoreError,
+            Self::KeyValidationError => ProtocolError::KeyValidationError,
+            Self::ProtocolVersionError => ProtocolError::ProtocolVersionError,
+            Self::ProtocolVersionMissing => ProtocolError::ProtocolVersionMissing,
+            Self::ReceiptError => ProtocolError::ReceiptError,
+            Self::RecordLogError => ProtocolError::RecordLogError,
+            Self::MigrationError => ProtocolError::MigrationError,
+            Self::PasswordTooShortError => ProtocolError::PasswordTooShortError,
+            Self::PasswordTooLongError => ProtocolError::PasswordTooLongError,
         }
     }
 }
 
+/// The server-side cause of a failed login, reported by
+/// [`ServerLogin::finish_with_diagnostics`](crate::ServerLogin::finish_with_diagnostics)
+///
+/// This is meant for logs and metrics of the server operator only: revealing
+/// it to the client would let it tell failures apart which the protocol keeps
+/// indistinguishable.
+///
+/// Note that a client whose envelope fails to open, e.g. because of a wrong
+/// password, aborts without sending a
+/// [`CredentialFinalization`](crate::CredentialFinalization), so the server
+/// never observes that case.
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, PartialEq)]
+pub enum LoginFailure {
+    /// The credential finalization could not be deserialized: {0}
+    Deserialization(ProtocolError),
+    /** The client MAC did not verify, which happens if the client and server
+    disagree on the transcript, e.g. because of a tampered message or
+    mismatched identifiers or context */
+    ClientMacError,
+    /// Computing the key exchange failed: {0}
+    KeyExchange(ProtocolError),
+}
+
 pub(crate) mod utils {
     use super::*;
 
@@ -0,0 +1,191 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Exporting keying material from the session key, in the style of the TLS 1.3
+//! exporter
+//!
+//! An [`Exporter`] is derived from the session key of a successful login, and
+//! can then be used any number of times to derive independent keys for
+//! different purposes, each identified by a label:
+//!
+//! ```ignore
+//! let exporter = Exporter::<YourCipherSuite>::new(&client_login_finish_result.session_key)?;
+//! let mut key = [0; 32];
+//! exporter.export(b"example.com AEAD key", b"", &mut key)?;
+//! ```
+//!
+//! Labels starting with one of the [`RESERVED_LABEL_PREFIXES`] are rejected,
+//! so that keys exported by applications never collide with keys this crate
+//! may derive for its own purposes. Applications sharing a session key between
+//! several components should likewise give each component a distinct prefix.
+
+use core::convert::TryFrom;
+use core::fmt::{self, Debug};
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Digest, Output, OutputSizeUser};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U1, U256};
+use generic_array::GenericArray;
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::errors::{InternalError, ProtocolError};
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::traits::{SessionKeyLen, SuiteProvider};
+use crate::provider::Provider;
+use crate::serialization::Input;
+
+static STR_OPAQUE: &[u8] = b"OPAQUE-";
+static STR_EXPORTER_SECRET: &[u8] = b"ExporterSecret";
+
+/// Label prefixes which [`Exporter::export`] rejects
+///
+/// - `OPAQUE-` is reserved for keys derived by this crate.
+/// - `EXPORTER-` is reserved for the labels of the TLS exporter, see [RFC 5705]
+///   and [RFC 8446], so that keys exported from a session key can not be
+///   confused with keys exported from a TLS channel.
+///
+/// [RFC 5705]: https://www.rfc-editor.org/rfc/rfc5705.html
+/// [RFC 8446]: https://www.rfc-editor.org/rfc/rfc8446.html#section-7.5
+pub const RESERVED_LABEL_PREFIXES: &[&[u8]] = &[b"OPAQUE-", b"EXPORTER-"];
+
+/// Derives keying material from the session key of a login
+#[derive_where(Clone, ZeroizeOnDrop)]
+pub struct Exporter<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    exporter_secret: Output<OprfHash<CS>>,
+}
+
+impl<CS: CipherSuite> Exporter<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Derives the exporter from the `session_key` of a
+    /// [`ClientLoginFinishResult`](crate::ClientLoginFinishResult) or
+    /// [`ServerLoginFinishResult`](crate::ServerLoginFinishResult)
+    ///
+    /// # Errors
+    /// [`InternalError::HkdfError`] if the session key is shorter than the
+    /// output of the hash, see
+    /// [`SessionKeyLength`](crate::key_exchange::tripledh::SessionKeyLength)
+    pub fn new(session_key: &GenericArray<u8, SessionKeyLen<CS>>) -> Result<Self, ProtocolError> {
+        let mut exporter_secret = Output::<OprfHash<CS>>::default();
+        expand_label::<CS>(session_key, STR_EXPORTER_SECRET, &[], &mut exporter_secret)?;
+
+        Ok(Self { exporter_secret })
+    }
+
+    /// Fills `output` with keying material for `label` and `context`
+    ///
+    /// Client and server obtain the same output for the same label, context
+    /// and output length, while any change to them yields an independent
+    /// output.
+    ///
+    /// # Errors
+    /// - [`ProtocolError::ReservedLabelError`] if `label` starts with one of
+    ///   the [`RESERVED_LABEL_PREFIXES`]
+    /// - [`ProtocolError::SerializationError`] if `label` is longer than 248
+    ///   bytes
+    /// - [`InternalError::HkdfError`] if `output` is longer than 255 times the
+    ///   output size of the hash
+    pub fn export(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), ProtocolError> {
+        if RESERVED_LABEL_PREFIXES
+            .iter()
+            .any(|prefix| label.starts_with(prefix))
+        {
+            return Err(ProtocolError::ReservedLabelError);
+        }
+
+        self.export_reserved(label, context, output)
+    }
+
+    // Same as `export`, but for the labels of keys derived by this crate, which
+    // start with one of the reserved prefixes
+    pub(crate) fn export_reserved(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), ProtocolError> {
+        expand_label::<CS>(
+            &self.exporter_secret,
+            label,
+            &OprfHash::<CS>::digest(context),
+            output,
+        )
+    }
+}
+
+impl<CS: CipherSuite> Debug for Exporter<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The exporter secret is omitted, as it is secret
+        f.debug_struct("Exporter").finish_non_exhaustive()
+    }
+}
+
+// Same as the HKDF-Expand-Label of the key schedule, but with a variable output
+// length
+fn expand_label<CS: CipherSuite>(
+    secret: &[u8],
+    label: &[u8],
+    context: &[u8],
+    output: &mut [u8],
+) -> Result<(), ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let hkdf = SuiteProvider::<CS>::from_prk(secret)?;
+
+    let length_u16 = u16::try_from(output.len()).map_err(|_| InternalError::HkdfError)?;
+    let label = Input::<U1>::from_label(STR_OPAQUE, label)?;
+    let label = label.to_array_3();
+    let context = Input::<U1>::from(context)?;
+    let context = context.to_array_2();
+
+    let hkdf_label = [
+        &length_u16.to_be_bytes(),
+        label[0],
+        label[1],
+        label[2],
+        context[0],
+        context[1],
+    ];
+
+    SuiteProvider::<CS>::expand(&hkdf, &hkdf_label, output)?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Restricting cipher suites to algorithms approved by FIPS 140-3
+//!
+//! The `fips` feature provides [`is_approved`], which admits a [`CipherSuite`]
+//! built on:
+//!
+//! - the OPRF suites P256-SHA256 and P384-SHA384 of [RFC 9497] as
+//!   [`CipherSuite::OprfCs`], so the hash is SHA-256 or SHA-384,
+//! - P-256 and P-384 as [`CipherSuite::KeGroup`],
+//! - [`TripleDh`](crate::key_exchange::tripledh::TripleDh) as
+//!   [`CipherSuite::KeyExchange`], which only uses Diffie-Hellman, HKDF and
+//!   HMAC with that hash,
+//! - [`Pbkdf2`](crate::ksf::Pbkdf2) as [`CipherSuite::Ksf`].
+//!
+//! [`P256Sha256Pbkdf2`](crate::suites::P256Sha256Pbkdf2) and
+//! [`P384Sha384Pbkdf2`](crate::suites::P384Sha384Pbkdf2) are ready-made
+//! approved suites. The check is opt-in, so that enabling the feature doesn't
+//! break other suites in the same build: an application restricts itself to
+//! approved suites by evaluating [`assert_approved`] for each suite it uses,
+//! which fails its build otherwise. Custom groups, key exchanges and key
+//! stretching functions are approved through their `FIPS_APPROVED` constants.
+//!
+//! The key exchange groups implemented on top of the `elliptic-curve` crate
+//! are identified by the `voprf::CipherSuite::ID` of their OPRF suite, so only
+//! P-256 and P-384 pass. With this feature, curves without an OPRF suite, such
+//! as secp256k1, don't implement [`KeGroup`] at all.
+//!
+//! The feature selects the algorithms only and doesn't make a deployment
+//! compliant with FIPS 140-3 by itself: the arithmetic of the curves, the OPRF,
+//! the transcript hash and PBKDF2 are still computed by the `p256`, `p384`,
+//! `sha2` and `pbkdf2` crates, which are not FIPS-validated modules. The
+//! `provider::AwsLc` provider of the `aws-lc-rs` feature computes HMAC and HKDF
+//! with the validated module of AWS-LC if the application enables the `fips`
+//! feature of `aws-lc-rs`, but the other primitives remain outside of it.
+//!
+//! [RFC 9497]: https://www.rfc-editor.org/rfc/rfc9497
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::KeyExchange;
+use crate::ksf::Ksf;
+
+// The `voprf::CipherSuite::ID`s of the approved OPRF suites
+const APPROVED_OPRF_SUITES: [&str; 2] = ["P256-SHA256", "P384-SHA384"];
+
+/// Returns `true` if all primitives of `CS` are approved, see the
+/// [module documentation](self)
+///
+/// This can be evaluated in a constant, e.g. to check a suite where it is
+/// defined:
+///
+/// ```
+/// const _: () = assert!(opaque_ke::fips::is_approved::<
+///     opaque_ke::suites::P256Sha256Pbkdf2,
+/// >());
+/// ```
+pub const fn is_approved<CS: CipherSuite>() -> bool
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    is_approved_oprf_suite(<CS::OprfCs as voprf::CipherSuite>::ID)
+        && <CS::KeGroup as KeGroup>::FIPS_APPROVED
+        && <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::FIPS_APPROVED
+        && <CS::Ksf as Ksf>::FIPS_APPROVED
+}
+
+/// Fails the build if a primitive of `CS` is not approved, see
+/// [`is_approved`]
+///
+/// This is meant to be evaluated in a constant next to the definition of the
+/// suite:
+///
+/// ```
+/// # use opaque_ke::CipherSuite;
+/// struct Suite;
+///
+/// impl CipherSuite for Suite {
+///     type OprfCs = p256::NistP256;
+///     type KeGroup = p256::NistP256;
+///     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+///     type Ksf = opaque_ke::ksf::Pbkdf2;
+///     type AppDataLen = generic_array::typenum::U0;
+/// }
+///
+/// const _: () = opaque_ke::fips::assert_approved::<Suite>();
+/// ```
+///
+/// A suite with a key stretching function that is not approved fails:
+///
+/// ```compile_fail,E0080
+/// # use opaque_ke::CipherSuite;
+/// struct Suite;
+///
+/// impl CipherSuite for Suite {
+///     type OprfCs = p256::NistP256;
+///     type KeGroup = p256::NistP256;
+///     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+///     type Ksf = opaque_ke::ksf::Identity;
+///     type AppDataLen = generic_array::typenum::U0;
+/// }
+///
+/// const _: () = opaque_ke::fips::assert_approved::<Suite>();
+/// ```
+pub const fn assert_approved<CS: CipherSuite>()
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    assert!(
+        is_approved::<CS>(),
+        "invalid CipherSuite: only the approved primitives listed in `opaque_ke::fips` are allowed"
+    );
+}
+
+// Whether `id` is the `voprf::CipherSuite::ID` of an approved OPRF suite, which
+// also identifies the curves of the key exchange
+pub(crate) const fn is_approved_oprf_suite(id: &str) -> bool {
+    let mut approved = false;
+    let mut i = 0;
+
+    while i < APPROVED_OPRF_SUITES.len() {
+        approved |= bytes_eq(id.as_bytes(), APPROVED_OPRF_SUITES[i].as_bytes());
+        i += 1;
+    }
+
+    approved
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
@@ -0,0 +1,618 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Structured fuzzing entry points, available with the `fuzzing` feature
+//!
+//! The `roundtrip` entry points take arbitrary bytes as produced by a fuzzer,
+//! attempt to deserialize them as the corresponding message, and panic if a
+//! successfully deserialized message does not serialize back to the bytes it
+//! was parsed from.
+//!
+//! The `login_finish` entry points run the protocol up to a live
+//! [`ClientLogin`] or [`ServerLogin`] state, mutate the message it receives
+//! next with the fuzzer's bytes, and panic unless an altered message is
+//! rejected with the same error an honest mismatch would produce.
+//!
+//! [`corpus`] returns valid serialized messages of a cipher suite together
+//! with canonical invalid ones, to seed fuzzers and deserializer tests, and
+//! [`write_corpus`] writes them as the seed corpus of the `messages` target.
+
+use core::ops::Add;
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+use std::{format, fs, io};
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::ArrayLength;
+use rand::{CryptoRng, RngCore};
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, MacLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::CredentialResponseWithoutKeLen;
+use crate::opaque::MaskedResponseLen;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialFinalization, CredentialRequest,
+    CredentialRequestLen, CredentialResponse, CredentialResponseLen, RegistrationRequest,
+    RegistrationResponse, RegistrationResponseLen, RegistrationUpload, RegistrationUploadLen,
+    ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+const PASSWORD: &[u8] = b"password";
+const CREDENTIAL_IDENTIFIER: &[u8] = b"credential identifier";
+
+fn assert_roundtrip(data: &[u8], serialized: &[u8]) {
+    assert!(
+        data.starts_with(serialized),
+        "deserialized message does not serialize back to its input"
+    );
+}
+
+/// Fuzzes [`RegistrationRequest::deserialize`]
+pub fn fuzz_registration_request_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    if let Ok(message) = RegistrationRequest::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`RegistrationResponse::deserialize`]
+pub fn fuzz_registration_response_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // RegistrationResponse: KgPk + KePk
+    <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    RegistrationResponseLen<CS>: ArrayLength<u8>,
+{
+    if let Ok(message) = RegistrationResponse::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`RegistrationUpload::deserialize`]
+pub fn fuzz_registration_upload_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+{
+    if let Ok(message) = RegistrationUpload::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`CredentialRequest::deserialize`]
+pub fn fuzz_credential_request_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialRequest: KgPk + Ke1Message
+    <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+    CredentialRequestLen<CS>: ArrayLength<u8>,
+{
+    if let Ok(message) = CredentialRequest::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`CredentialResponse::deserialize`]
+pub fn fuzz_credential_response_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    if let Ok(message) = CredentialResponse::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`CredentialFinalization::deserialize`]
+pub fn fuzz_credential_finalization_roundtrip<CS: CipherSuite>(data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    if let Ok(message) = CredentialFinalization::<CS>::deserialize(data) {
+        assert_roundtrip(data, &message.serialize());
+    }
+}
+
+/// Fuzzes [`ClientLogin::finish`] with a mutated [`CredentialResponse`]
+///
+/// `data` is combined with XOR into the serialized credential response, so an
+/// all-zero input leaves it untouched and must lead to a successful login.
+pub fn fuzz_client_login_finish<CS: CipherSuite, R: CryptoRng + RngCore>(rng: &mut R, data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    let (client_login, credential_response, _) = live_login::<CS, R>(rng);
+
+    let mut bytes = credential_response.serialize();
+    let mutated = mutate(&mut bytes, data);
+    let Ok(credential_response) = CredentialResponse::<CS>::deserialize(&bytes) else {
+        return;
+    };
+
+    let result = client_login.finish(
+        PASSWORD,
+        credential_response,
+        ClientLoginFinishParameters::default(),
+    );
+
+    if mutated {
+        assert!(
+            matches!(
+                result,
+                Err(ProtocolError::InvalidLoginError | ProtocolError::ReflectedValueError)
+            ),
+            "mutated credential response was not rejected as an invalid login"
+        );
+    } else {
+        assert!(
+            result.is_ok(),
+            "unmodified credential response was rejected"
+        );
+    }
+}
+
+/// Fuzzes [`ServerLogin::finish`] with a mutated [`CredentialFinalization`]
+///
+/// `data` is combined with XOR into the serialized credential finalization, so
+/// an all-zero input leaves it untouched and must lead to a successful login.
+pub fn fuzz_server_login_finish<CS: CipherSuite, R: CryptoRng + RngCore>(rng: &mut R, data: &[u8])
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+{
+    let (client_login, credential_response, server_login) = live_login::<CS, R>(rng);
+    let client_login_finish_result = client_login
+        .finish(
+            PASSWORD,
+            credential_response,
+            ClientLoginFinishParameters::default(),
+        )
+        .expect("client login finish must succeed");
+
+    let mut bytes = client_login_finish_result.message.serialize();
+    let mutated = mutate(&mut bytes, data);
+    let Ok(credential_finalization) = CredentialFinalization::<CS>::deserialize(&bytes) else {
+        return;
+    };
+
+    let result = server_login.finish(credential_finalization);
+
+    if mutated {
+        assert!(
+            matches!(result, Err(ProtocolError::InvalidLoginError)),
+            "mutated credential finalization was not rejected as an invalid login"
+        );
+    } else {
+        assert!(
+            result.is_ok(),
+            "unmodified credential finalization was rejected"
+        );
+    }
+}
+
+/// The type of a message in the [`corpus`]
+///
+/// Its discriminant is the selector of the message in the `messages` fuzz
+/// target, see [`CorpusEntry::messages_input`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MessageType {
+    /// A [`RegistrationRequest`]
+    RegistrationRequest = 0,
+    /// A [`RegistrationResponse`]
+    RegistrationResponse = 1,
+    /// A [`RegistrationUpload`]
+    RegistrationUpload = 2,
+    /// A [`CredentialRequest`]
+    CredentialRequest = 3,
+    /// A [`CredentialResponse`]
+    CredentialResponse = 4,
+    /// A [`CredentialFinalization`]
+    CredentialFinalization = 5,
+}
+
+impl MessageType {
+    /// Returns the name of the message type in snake case
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::RegistrationRequest => "registration_request",
+            Self::RegistrationResponse => "registration_response",
+            Self::RegistrationUpload => "registration_upload",
+            Self::CredentialRequest => "credential_request",
+            Self::CredentialResponse => "credential_response",
+            Self::CredentialFinalization => "credential_finalization",
+        }
+    }
+}
+
+/// How a message in the [`corpus`] was derived from a valid message
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Variant {
+    /// The valid message itself
+    Valid,
+    /// The OPRF element of the message replaced with the identity element
+    IdentityElement,
+    /// The message without its last byte
+    Truncated,
+    /// The message followed by an additional byte
+    TrailingByte,
+}
+
+impl Variant {
+    /// Returns the name of the variant in snake case
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::IdentityElement => "identity_element",
+            Self::Truncated => "truncated",
+            Self::TrailingByte => "trailing_byte",
+        }
+    }
+}
+
+/// A serialized message of the [`corpus`]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CorpusEntry {
+    /// The type of the message
+    pub message_type: MessageType,
+    /// How the message was derived from a valid one, all variants except
+    /// [`Variant::Valid`] fail to deserialize
+    pub variant: Variant,
+    /// The serialized message
+    pub bytes: Vec<u8>,
+}
+
+impl CorpusEntry {
+    /// Returns the input of the `messages` fuzz target for this entry, which
+    /// is the message prefixed with the selector of its type
+    pub fn messages_input(&self) -> Vec<u8> {
+        [&[self.message_type as u8], self.bytes.as_slice()].concat()
+    }
+
+    /// Returns a file name for this entry which is unique within a corpus
+    pub fn file_name(&self) -> String {
+        format!("{}-{}", self.message_type.name(), self.variant.name())
+    }
+}
+
+/// Runs a registration and a login and returns every message sent, together
+/// with its invalid variants
+///
+/// The identity element variant is included for the messages starting with
+/// an OPRF element, i.e. all messages except [`RegistrationUpload`] and
+/// [`CredentialFinalization`].
+pub fn corpus<CS: CipherSuite, R: CryptoRng + RngCore>(rng: &mut R) -> Vec<CorpusEntry>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // RegistrationResponse: KgPk + KePk
+    <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    RegistrationResponseLen<CS>: ArrayLength<u8>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+    // CredentialRequest: KgPk + Ke1Message
+    <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+    CredentialRequestLen<CS>: ArrayLength<u8>,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    let server_setup = ServerSetup::<CS>::new(rng).expect("server setup must succeed");
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(rng, PASSWORD).expect("registration start must succeed");
+    let registration_request = client_registration_start_result.message.serialize();
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+    )
+    .expect("server registration start must succeed");
+    let registration_response = server_registration_start_result.message.serialize();
+    let client_registration_finish_result = client_registration_start_result
+        .state
+        .finish(
+            rng,
+            PASSWORD,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+        .expect("registration finish must succeed");
+    let registration_upload = client_registration_finish_result.message.serialize();
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result =
+        ClientLogin::<CS>::start(rng, PASSWORD).expect("login start must succeed");
+    let credential_request = client_login_start_result.message.serialize();
+    let server_login_start_result = ServerLogin::start(
+        rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+        ServerLoginStartParameters::default(),
+    )
+    .expect("server login start must succeed");
+    let credential_response = server_login_start_result.message.serialize();
+    let client_login_finish_result = client_login_start_result
+        .state
+        .finish(
+            PASSWORD,
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )
+        .expect("client login finish must succeed");
+    let credential_finalization = client_login_finish_result.message.serialize();
+
+    let identity = OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::identity_elem());
+    let mut corpus = Vec::new();
+
+    for (message_type, valid, starts_with_element) in [
+        (
+            MessageType::RegistrationRequest,
+            registration_request.as_slice(),
+            true,
+        ),
+        (
+            MessageType::RegistrationResponse,
+            &registration_response,
+            true,
+        ),
+        (MessageType::RegistrationUpload, &registration_upload, false),
+        (MessageType::CredentialRequest, &credential_request, true),
+        (MessageType::CredentialResponse, &credential_response, true),
+        (
+            MessageType::CredentialFinalization,
+            &credential_finalization,
+            false,
+        ),
+    ] {
+        let mut entry = |variant, bytes| {
+            corpus.push(CorpusEntry {
+                message_type,
+                variant,
+                bytes,
+            })
+        };
+
+        entry(Variant::Valid, valid.to_vec());
+        if starts_with_element {
+            entry(
+                Variant::IdentityElement,
+                [identity.as_slice(), &valid[identity.len()..]].concat(),
+            );
+        }
+        entry(Variant::Truncated, valid[..valid.len() - 1].to_vec());
+        entry(Variant::TrailingByte, [valid, &[0]].concat());
+    }
+
+    corpus
+}
+
+/// Writes the [`corpus`] to `dir` as the seed corpus of the `messages` fuzz
+/// target, with one file per entry named by [`CorpusEntry::file_name`]
+///
+/// # Errors
+/// Any error while creating `dir` or writing a file.
+pub fn write_corpus<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    dir: &Path,
+) -> io::Result<()>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // RegistrationResponse: KgPk + KePk
+    <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    RegistrationResponseLen<CS>: ArrayLength<u8>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+    // CredentialRequest: KgPk + Ke1Message
+    <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+    CredentialRequestLen<CS>: ArrayLength<u8>,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    fs::create_dir_all(dir)?;
+
+    for entry in corpus::<CS, R>(rng) {
+        fs::write(dir.join(entry.file_name()), entry.messages_input())?;
+    }
+
+    Ok(())
+}
+
+/// XORs `data` into `bytes`, returning whether `bytes` changed
+fn mutate(bytes: &mut [u8], data: &[u8]) -> bool {
+    let mut mutated = false;
+
+    for (byte, mask) in bytes.iter_mut().zip(data) {
+        *byte ^= mask;
+        mutated |= *mask != 0;
+    }
+
+    mutated
+}
+
+/// Registers [`PASSWORD`] and runs login up to the point where the client has
+/// to process the server's [`CredentialResponse`]
+fn live_login<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+) -> (ClientLogin<CS>, CredentialResponse<CS>, ServerLogin<CS>)
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+{
+    let server_setup = ServerSetup::<CS>::new(rng).expect("server setup must succeed");
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(rng, PASSWORD).expect("registration start must succeed");
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+    )
+    .expect("server registration start must succeed");
+    let client_registration_finish_result = client_registration_start_result
+        .state
+        .finish(
+            rng,
+            PASSWORD,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+        .expect("registration finish must succeed");
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result =
+        ClientLogin::<CS>::start(rng, PASSWORD).expect("login start must succeed");
+    let server_login_start_result = ServerLogin::start(
+        rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        CREDENTIAL_IDENTIFIER,
+        ServerLoginStartParameters::default(),
+    )
+    .expect("server login start must succeed");
+
+    (
+        client_login_start_result.state,
+        server_login_start_result.message,
+        server_login_start_result.state,
+    )
+}
@@ -0,0 +1,222 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Idempotent registrations
+//!
+//! A [`RegistrationToken`] identifies one registration attempt of a
+//! credential identifier, and is bound to the blinded element of its
+//! [`RegistrationRequest`]. The server issues it with
+//! [`ServerSetup::registration_token`](crate::ServerSetup::registration_token)
+//! alongside the [`RegistrationResponse`](crate::RegistrationResponse), the
+//! client echoes it and the request alongside its
+//! [`RegistrationUpload`](crate::RegistrationUpload), and the server finishes
+//! the registration with
+//! [`ServerRegistration::finish_with_token`](crate::ServerRegistration::finish_with_token),
+//! passing the token and password file stored by a previous attempt, if any.
+//! The server stores the token next to the password file.
+//!
+//! This way, an upload that is retried after a timeout is recognized as such
+//! instead of creating a conflicting record, while the upload of an older
+//! attempt which is replayed after a newer registration is rejected.
+
+use core::ops::Add;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Output, OutputSizeUser};
+use generic_array::sequence::Concat;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U2, U256, U40};
+use generic_array::{ArrayLength, GenericArray};
+use rand::{CryptoRng, RngCore};
+use voprf::Group;
+use zeroize::Zeroizing;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::errors::utils::check_slice_size;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::traits::SuiteProvider;
+use crate::key_exchange::tripledh::NonceLen;
+use crate::provider::Provider;
+use crate::serialization::Input;
+use crate::util::fill_random;
+use crate::{RegistrationRequest, ServerRegistration};
+
+static STR_REGISTRATION_TOKEN_KEY: &[u8] = b"RegistrationTokenKey";
+
+/// Length of a serialized [`RegistrationToken`]
+pub type RegistrationTokenLen<CS: CipherSuite> = Sum<U40, OutputSize<OprfHash<CS>>>;
+
+/// An authenticated identifier of a registration attempt, see the [module
+/// documentation](self)
+#[derive_where(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RegistrationToken<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    issued_at: u64,
+    nonce: GenericArray<u8, NonceLen>,
+    tag: Output<OprfHash<CS>>,
+}
+
+/// The outcome of
+/// [`ServerRegistration::finish_with_token`](crate::ServerRegistration::finish_with_token)
+#[derive_where(Clone)]
+pub enum IdempotentRegistration<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The password file of a new registration attempt, to be stored together
+    /// with its token
+    Store(ServerRegistration<CS>),
+    /// The upload was already stored by a previous try of the same attempt,
+    /// so nothing has to be written
+    AlreadyStored,
+}
+
+impl<CS: CipherSuite> RegistrationToken<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Returns the time the token was issued at
+    ///
+    /// This is only authenticated after a successful
+    /// [`ServerRegistration::finish_with_token`](crate::ServerRegistration::finish_with_token).
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, RegistrationTokenLen<CS>>
+    where
+        // RegistrationToken: (IssuedAt + Nonce) + Hash
+        U40: Add<OutputSize<OprfHash<CS>>>,
+        RegistrationTokenLen<CS>: ArrayLength<u8>,
+    {
+        GenericArray::from(self.issued_at.to_be_bytes())
+            .concat(self.nonce)
+            .concat(self.tag.clone())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_slice = check_slice_size(
+            input,
+            U40::USIZE + OutputSize::<OprfHash<CS>>::USIZE,
+            "registration_token",
+        )?;
+
+        let mut issued_at = [0; 8];
+        issued_at.copy_from_slice(&checked_slice[..8]);
+
+        Ok(Self {
+            issued_at: u64::from_be_bytes(issued_at),
+            nonce: GenericArray::clone_from_slice(&checked_slice[8..40]),
+            tag: GenericArray::clone_from_slice(&checked_slice[40..]),
+        })
+    }
+
+    pub(crate) fn new<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        oprf_seed: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+        registration_request: &RegistrationRequest<CS>,
+        issued_at: u64,
+    ) -> Result<Self, ProtocolError> {
+        let mut nonce = GenericArray::default();
+        fill_random(rng, &mut nonce)?;
+        let tag = SuiteProvider::<CS>::finalize_mac(token_mac::<CS>(
+            oprf_seed,
+            credential_identifier,
+            registration_request,
+            issued_at,
+            &nonce,
+        )?);
+
+        Ok(Self {
+            issued_at,
+            nonce,
+            tag,
+        })
+    }
+
+    pub(crate) fn verify(
+        &self,
+        oprf_seed: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+        registration_request: &RegistrationRequest<CS>,
+    ) -> Result<(), ProtocolError> {
+        let mac = token_mac::<CS>(
+            oprf_seed,
+            credential_identifier,
+            registration_request,
+            self.issued_at,
+            &self.nonce,
+        )?;
+
+        if bool::from(SuiteProvider::<CS>::verify_mac(mac, &self.tag)) {
+            Ok(())
+        } else {
+            Err(ProtocolError::RegistrationRejected)
+        }
+    }
+}
+
+// The token key is expanded from the OPRF seed with a label that can not
+// collide with the info used for OPRF keys, which always ends in "OprfKey"
+fn token_mac<CS: CipherSuite>(
+    oprf_seed: &Output<OprfHash<CS>>,
+    credential_identifier: &[u8],
+    registration_request: &RegistrationRequest<CS>,
+    issued_at: u64,
+    nonce: &[u8],
+) -> Result<<SuiteProvider<CS> as Provider<OprfHash<CS>>>::Mac, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut token_key = Zeroizing::new(Output::<OprfHash<CS>>::default());
+    SuiteProvider::<CS>::expand(
+        &SuiteProvider::<CS>::from_prk(oprf_seed)?,
+        &[STR_REGISTRATION_TOKEN_KEY],
+        &mut token_key,
+    )?;
+
+    let credential_identifier = Input::<U2>::from(credential_identifier)?;
+    let mut mac = SuiteProvider::<CS>::new_mac(&token_key)?;
+    for bytes in credential_identifier.iter() {
+        SuiteProvider::<CS>::update_mac(&mut mac, bytes);
+    }
+    SuiteProvider::<CS>::update_mac(
+        &mut mac,
+        &OprfGroup::<CS>::serialize_elem(registration_request.blinded_element.value()),
+    );
+    SuiteProvider::<CS>::update_mac(&mut mac, &issued_at.to_be_bytes());
+    SuiteProvider::<CS>::update_mac(&mut mac, nonce);
+
+    Ok(mac)
+}
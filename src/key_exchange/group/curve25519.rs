@@ -10,16 +10,26 @@
 
 use curve25519_dalek::montgomery::MontgomeryPoint;
 use curve25519_dalek::scalar::{self, Scalar};
-use curve25519_dalek::traits::Identity;
 use digest::core_api::BlockSizeUser;
 use digest::{FixedOutput, HashMarker, OutputSizeUser};
+#[cfg(feature = "pkcs8")]
+use generic_array::typenum::U34;
 use generic_array::typenum::{IsLess, IsLessOrEqual, U256, U32};
 use generic_array::GenericArray;
+#[cfg(feature = "pkcs8")]
+use pkcs8::der::asn1::OctetStringRef;
+#[cfg(feature = "pkcs8")]
+use pkcs8::der::{Decode, Encode};
+#[cfg(feature = "pkcs8")]
+use pkcs8::spki::{AlgorithmIdentifier, ObjectIdentifier};
 use rand::{CryptoRng, RngCore};
 use subtle::ConstantTimeEq;
 
 use super::KeGroup;
-use crate::errors::InternalError;
+#[cfg(feature = "pkcs8")]
+use super::Pkcs8KeGroup;
+use crate::errors::{InternalError, ProtocolError};
+use crate::util::fill_random;
 
 /// Implementation for Curve25519.
 pub struct Curve25519;
@@ -35,24 +45,43 @@ impl KeGroup for Curve25519 {
         pk.to_bytes().into()
     }
 
+    // X25519 ignores the most significant bit and reduces the u-coordinate
+    // modulo p, so only the canonical encoding is accepted. X25519 also
+    // accepts points of the twist and of any order, which the conversion to
+    // the Edwards form and the check of the subgroup reject, including the
+    // low-order points whose Diffie-Hellman output doesn't depend on the
+    // secret key, see <https://cr.yp.to/ecdh.html#validate>
     fn deserialize_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError> {
-        bytes
-            .try_into()
-            .ok()
-            .map(MontgomeryPoint)
-            .filter(|pk| pk != &MontgomeryPoint::identity())
-            .ok_or(InternalError::PointError)
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| InternalError::PointError)?;
+        let non_canonical =
+            bytes[31] == 0x7f && bytes[1..31].iter().all(|byte| *byte == 0xff) && bytes[0] >= 0xed;
+
+        if bytes[31] & 0x80 != 0 || non_canonical {
+            return Err(InternalError::PointError);
+        }
+
+        let pk = MontgomeryPoint(bytes);
+
+        // Both signs of the Edwards point are in the subgroup or neither is
+        if !pk
+            .to_edwards(0)
+            .is_some_and(|point| point.is_torsion_free())
+        {
+            return Err(InternalError::PointError);
+        }
+
+        Ok(pk)
     }
 
-    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Sk {
+    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Self::Sk, ProtocolError> {
         loop {
             // Sample 32 random bytes and then clamp, as described in https://cr.yp.to/ecdh.html
             let mut scalar_bytes = [0u8; 32];
-            rng.fill_bytes(&mut scalar_bytes);
+            fill_random(rng, &mut scalar_bytes)?;
             let scalar = scalar::clamp_integer(scalar_bytes);
 
             if scalar != Scalar::ZERO.to_bytes() {
-                break scalar;
+                break Ok(scalar);
             }
         }
     }
@@ -65,8 +94,12 @@ impl KeGroup for Curve25519 {
         unimplemented!()
     }
 
-    fn derive_auth_keypair<CS: voprf::CipherSuite>(
+    // The seed is clamped directly, so the `info` and the domain separation tag
+    // of the scalar derivation don't apply
+    fn derive_auth_keypair_with<CS: voprf::CipherSuite>(
         seed: GenericArray<u8, Self::SkLen>,
+        _info: &[u8],
+        _dst: Option<&[u8]>,
     ) -> Result<Self::Sk, InternalError>
     where
         <CS::Hash as OutputSizeUser>::OutputSize:
@@ -103,3 +136,49 @@ impl KeGroup for Curve25519 {
             .ok_or(InternalError::PointError)
     }
 }
+
+/// Keys are encoded as X25519 keys, see
+/// [RFC 8410](https://www.rfc-editor.org/rfc/rfc8410)
+#[cfg(feature = "pkcs8")]
+impl Pkcs8KeGroup for Curve25519 {
+    const ALGORITHM_IDENTIFIER: AlgorithmIdentifier<ObjectIdentifier> = AlgorithmIdentifier {
+        oid: ObjectIdentifier::new_unwrap("1.3.101.110"),
+        parameters: None,
+    };
+
+    type SpkiPkLen = U32;
+
+    // A `CurvePrivateKey`, i.e. the secret key as an octet string
+    type Pkcs8SkLen = U34;
+
+    fn serialize_spki_pk(pk: Self::Pk) -> GenericArray<u8, Self::SpkiPkLen> {
+        Self::serialize_pk(pk)
+    }
+
+    fn deserialize_spki_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError> {
+        Self::deserialize_pk(bytes)
+    }
+
+    fn serialize_pkcs8_sk(
+        sk: Self::Sk,
+    ) -> Result<GenericArray<u8, Self::Pkcs8SkLen>, InternalError> {
+        let mut output = GenericArray::default();
+        OctetStringRef::new(&sk)
+            .and_then(|sk| sk.encode_to_slice(&mut output).map(|_| ()))
+            .map_err(|_| InternalError::InvalidByteSequence)?;
+
+        Ok(output)
+    }
+
+    // Other implementations store the secret key without clamping it, which
+    // doesn't change the key as X25519 clamps it before use
+    fn deserialize_pkcs8_sk(bytes: &[u8]) -> Result<Self::Sk, InternalError> {
+        let sk = OctetStringRef::from_der(bytes).map_err(|_| InternalError::InvalidByteSequence)?;
+        let sk: [u8; 32] = sk
+            .as_bytes()
+            .try_into()
+            .map_err(|_| InternalError::InvalidByteSequence)?;
+
+        Self::deserialize_sk(&scalar::clamp_integer(sk))
+    }
+}
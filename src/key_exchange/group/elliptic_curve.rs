@@ -6,24 +6,74 @@
 // of this source tree. You may select, at your option, one of the above-listed
 // licenses.
 
+#[cfg(feature = "pkcs8")]
+use core::ops::Add;
+
 use digest::core_api::BlockSizeUser;
 use digest::{FixedOutput, HashMarker};
 use elliptic_curve::group::cofactor::CofactorGroup;
 use elliptic_curve::hash2curve::{ExpandMsgXmd, FromOkm, GroupDigest};
-use elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+#[cfg(feature = "fips")]
+use elliptic_curve::VoprfParameters;
+#[cfg(feature = "pkcs8")]
+use elliptic_curve::ALGORITHM_OID;
 use elliptic_curve::{
-    AffinePoint, Field, FieldBytesSize, Group, ProjectivePoint, PublicKey, Scalar, SecretKey,
+    AffinePoint, Field, FieldBytes, FieldBytesSize, Group, ProjectivePoint, PublicKey, Scalar,
+    SecretKey,
 };
 use generic_array::typenum::{IsLess, IsLessOrEqual, U256};
+#[cfg(feature = "pkcs8")]
+use generic_array::typenum::{Sum, U7};
+#[cfg(feature = "pkcs8")]
+use generic_array::ArrayLength;
 use generic_array::GenericArray;
+#[cfg(feature = "pkcs8")]
+use pkcs8::der::{Decode, Encode};
+#[cfg(feature = "pkcs8")]
+use pkcs8::spki::{AlgorithmIdentifier, ObjectIdentifier};
+#[cfg(feature = "pkcs8")]
+use pkcs8::AssociatedOid;
 use rand::{CryptoRng, RngCore};
+// `elliptic_curve::sec1` re-exports this, but `sec1` is also needed directly
+// for the private keys of the `pkcs8` feature
+use sec1::point::ModulusSize;
+#[cfg(feature = "pkcs8")]
+use sec1::EcPrivateKey;
+use zeroize::Zeroize;
 
 use super::KeGroup;
-use crate::errors::InternalError;
+#[cfg(feature = "pkcs8")]
+use super::Pkcs8KeGroup;
+#[cfg(feature = "dkg")]
+use super::ThresholdKeGroup;
+use crate::errors::{InternalError, ProtocolError};
+use crate::util::fill_random;
+
+/// Identifies the curves for the `fips` feature by the ID of their OPRF suite
+/// of [RFC 9497](https://www.rfc-editor.org/rfc/rfc9497), so that only the
+/// curves having one implement [`KeGroup`] with it
+#[cfg(feature = "fips")]
+pub trait CurveId {
+    /// The `voprf::CipherSuite::ID` of the curve
+    const OPRF_ID: &'static str;
+}
+
+#[cfg(feature = "fips")]
+impl<G: VoprfParameters> CurveId for G {
+    const OPRF_ID: &'static str = G::ID;
+}
+
+/// Implemented by every curve unless the `fips` feature is enabled
+#[cfg(not(feature = "fips"))]
+pub trait CurveId {}
+
+#[cfg(not(feature = "fips"))]
+impl<G> CurveId for G {}
 
 impl<G> KeGroup for G
 where
-    G: GroupDigest,
+    G: CurveId + GroupDigest,
     FieldBytesSize<Self>: ModulusSize,
     AffinePoint<Self>: FromEncodedPoint<Self> + ToEncodedPoint<Self>,
     ProjectivePoint<Self>: CofactorGroup + ToEncodedPoint<Self>,
@@ -37,18 +87,41 @@ where
 
     type SkLen = FieldBytesSize<Self>;
 
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = crate::fips::is_approved_oprf_suite(G::OPRF_ID);
+
     fn serialize_pk(pk: Self::Pk) -> GenericArray<u8, Self::PkLen> {
         GenericArray::clone_from_slice(pk.to_encoded_point(true).as_bytes())
     }
 
+    // Decoding rejects the identity, points not on the curve and coordinates
+    // outside of the field, and the cofactor of the supported curves is 1, so
+    // it remains to reject encodings other than the compressed one
     fn deserialize_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError> {
-        PublicKey::<Self>::from_sec1_bytes(bytes)
-            .map(|public_key| public_key.to_projective())
-            .map_err(|_| InternalError::PointError)
+        let pk = PublicKey::<Self>::from_sec1_bytes(bytes)
+            .map_err(|_| InternalError::PointError)?
+            .to_projective();
+
+        if pk.to_encoded_point(true).as_bytes() != bytes {
+            return Err(InternalError::PointError);
+        }
+
+        Ok(pk)
     }
 
-    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Sk {
-        *SecretKey::<Self>::random(rng).to_nonzero_scalar()
+    // Like `SecretKey::random`, but reporting a failure of the rng
+    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Self::Sk, ProtocolError> {
+        let mut bytes = FieldBytes::<Self>::default();
+
+        loop {
+            fill_random(rng, &mut bytes)?;
+
+            // Rejects zero and values not smaller than the order
+            if let Ok(sk) = SecretKey::<Self>::from_bytes(&bytes) {
+                bytes.zeroize();
+                break Ok(*sk.to_nonzero_scalar());
+            }
+        }
     }
 
     // Implements the `HashToScalar()` function from
@@ -86,7 +159,119 @@ where
     }
 
     fn deserialize_sk(bytes: &[u8]) -> Result<Self::Sk, InternalError> {
-        SecretKey::<Self>::from_slice(bytes)
+        // `SecretKey::from_slice` would also accept shorter inputs, taken as
+        // left-padded with zeros
+        GenericArray::from_exact_iter(bytes.iter().copied())
+            .and_then(|bytes| SecretKey::<Self>::from_bytes(&bytes).ok())
+            .map(|secret_key| *secret_key.to_nonzero_scalar())
+            .ok_or(InternalError::PointError)
+    }
+}
+
+#[cfg(feature = "dkg")]
+impl<G> ThresholdKeGroup for G
+where
+    G: CurveId + GroupDigest,
+    FieldBytesSize<Self>: ModulusSize,
+    AffinePoint<Self>: FromEncodedPoint<Self> + ToEncodedPoint<Self>,
+    ProjectivePoint<Self>: CofactorGroup + ToEncodedPoint<Self>,
+    Scalar<Self>: FromOkm,
+{
+    fn add_pk(a: Self::Pk, b: Self::Pk) -> Self::Pk {
+        a + b
+    }
+
+    fn mul_pk(pk: Self::Pk, scalar: Self::Sk) -> Self::Pk {
+        pk * scalar
+    }
+
+    fn is_identity_pk(pk: Self::Pk) -> subtle::Choice {
+        pk.is_identity()
+    }
+
+    fn add_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a + b
+    }
+
+    fn sub_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a - b
+    }
+
+    fn mul_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a * b
+    }
+
+    fn invert_sk(scalar: Self::Sk) -> Self::Sk {
+        scalar.invert().unwrap_or(Scalar::<Self>::ZERO)
+    }
+
+    fn sk_from_u16(value: u16) -> Self::Sk {
+        Scalar::<Self>::from(u64::from(value))
+    }
+}
+
+#[cfg(feature = "pkcs8")]
+impl<G> Pkcs8KeGroup for G
+where
+    G: AssociatedOid + CurveId + GroupDigest,
+    FieldBytesSize<Self>: ModulusSize + Add<U7>,
+    Sum<FieldBytesSize<Self>, U7>: ArrayLength<u8>,
+    AffinePoint<Self>: FromEncodedPoint<Self> + ToEncodedPoint<Self>,
+    ProjectivePoint<Self>: CofactorGroup + ToEncodedPoint<Self>,
+    Scalar<Self>: FromOkm,
+{
+    const ALGORITHM_IDENTIFIER: AlgorithmIdentifier<ObjectIdentifier> = AlgorithmIdentifier {
+        oid: ALGORITHM_OID,
+        parameters: Some(G::OID),
+    };
+
+    type SpkiPkLen = <FieldBytesSize<Self> as ModulusSize>::UncompressedPointSize;
+
+    // An `ECPrivateKey` without the optional parameters and public key, which
+    // are already covered by the algorithm identifier and the private key
+    type Pkcs8SkLen = Sum<FieldBytesSize<Self>, U7>;
+
+    fn serialize_spki_pk(pk: Self::Pk) -> GenericArray<u8, Self::SpkiPkLen> {
+        GenericArray::clone_from_slice(pk.to_encoded_point(false).as_bytes())
+    }
+
+    // RFC 5480 allows both the compressed and the uncompressed encoding
+    fn deserialize_spki_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError> {
+        PublicKey::<Self>::from_sec1_bytes(bytes)
+            .map(|public_key| public_key.to_projective())
+            .map_err(|_| InternalError::PointError)
+    }
+
+    fn serialize_pkcs8_sk(
+        sk: Self::Sk,
+    ) -> Result<GenericArray<u8, Self::Pkcs8SkLen>, InternalError> {
+        let mut sk_bytes = Self::serialize_sk(sk);
+        let mut output = GenericArray::default();
+        let result = EcPrivateKey {
+            private_key: &sk_bytes,
+            parameters: None,
+            public_key: None,
+        }
+        .encode_to_slice(&mut output)
+        .map(|_| ())
+        .map_err(|_| InternalError::InvalidByteSequence);
+        sk_bytes.zeroize();
+
+        result.map(|()| output)
+    }
+
+    fn deserialize_pkcs8_sk(bytes: &[u8]) -> Result<Self::Sk, InternalError> {
+        let ec_private_key =
+            EcPrivateKey::from_der(bytes).map_err(|_| InternalError::InvalidByteSequence)?;
+
+        if let Some(parameters) = ec_private_key.parameters {
+            if parameters.named_curve() != Some(G::OID) {
+                return Err(InternalError::InvalidByteSequence);
+            }
+        }
+
+        // Some encoders strip leading zeros of the private key
+        SecretKey::<Self>::from_slice(ec_private_key.private_key)
             .map(|secret_key| *secret_key.to_nonzero_scalar())
             .map_err(|_| InternalError::PointError)
     }
@@ -10,6 +10,7 @@
 
 #[cfg(feature = "curve25519")]
 pub mod curve25519;
+#[cfg(feature = "elliptic-curve")]
 mod elliptic_curve;
 #[cfg(feature = "ristretto255")]
 pub mod ristretto255;
@@ -19,10 +20,12 @@ use digest::{FixedOutput, HashMarker, OutputSizeUser};
 use generic_array::sequence::Concat;
 use generic_array::typenum::{IsLess, IsLessOrEqual, U256};
 use generic_array::{ArrayLength, GenericArray};
+#[cfg(feature = "pkcs8")]
+use pkcs8::spki::{AlgorithmIdentifier, ObjectIdentifier};
 use rand::{CryptoRng, RngCore};
 use zeroize::Zeroize;
 
-use crate::errors::InternalError;
+use crate::errors::{InternalError, ProtocolError};
 
 const STR_OPAQUE_DERIVE_AUTH_KEY_PAIR: [u8; 33] = *b"OPAQUE-DeriveDiffieHellmanKeyPair";
 
@@ -36,15 +39,28 @@ pub trait KeGroup {
     type Sk: Copy + Zeroize;
     /// Length of the secret key
     type SkLen: ArrayLength<u8>;
+    /// Whether the group is approved by FIPS 140-3, as checked by
+    /// [`fips::is_approved`](crate::fips::is_approved). Defaults to `false`.
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = false;
 
     /// Serializes `self`
     fn serialize_pk(pk: Self::Pk) -> GenericArray<u8, Self::PkLen>;
 
     /// Return a public key from its fixed-length bytes representation
+    ///
+    /// Implementations have to reject any input other than the canonical
+    /// encoding of a point of the prime-order subgroup which isn't the
+    /// identity, so that peers can't force a Diffie-Hellman output that
+    /// doesn't depend on the secret key, or use distinct encodings of the same
+    /// key.
     fn deserialize_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError>;
 
     /// Generate a random secret key
-    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Sk;
+    ///
+    /// # Errors
+    /// [`ProtocolError::RandomnessError`] if the `rng` fails.
+    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Self::Sk, ProtocolError>;
 
     /// Hashes a slice of pseudo-random bytes to a scalar
     ///
@@ -70,12 +86,31 @@ pub trait KeGroup {
         <CS::Hash as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<CS::Hash as BlockSizeUser>::BlockSize>,
     {
-        let info = &STR_OPAQUE_DERIVE_AUTH_KEY_PAIR;
+        Self::derive_auth_keypair_with::<CS>(seed, &STR_OPAQUE_DERIVE_AUTH_KEY_PAIR, None)
+    }
+
+    /// Like [`derive_auth_keypair`](Self::derive_auth_keypair), but with the
+    /// given `info` and, if given, domain separation tag of the
+    /// [`hash_to_scalar`](Self::hash_to_scalar) operation, see
+    /// [`CipherSuite::AUTH_KEY_PAIR_INFO`](crate::CipherSuite::AUTH_KEY_PAIR_INFO)
+    fn derive_auth_keypair_with<CS: voprf::CipherSuite>(
+        seed: GenericArray<u8, Self::SkLen>,
+        info: &[u8],
+        dst: Option<&[u8]>,
+    ) -> Result<Self::Sk, InternalError>
+    where
+        <CS::Hash as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<CS::Hash as BlockSizeUser>::BlockSize>,
+    {
         let dst_1 = GenericArray::from(STR_DERIVE_KEYPAIR)
             .concat(STR_OPRF.into())
             .concat([voprf::Mode::Oprf.to_u8()].into())
             .concat([b'-'].into());
         let dst_2 = CS::ID.as_bytes();
+        let dst: &[&[u8]] = match &dst {
+            Some(dst) => core::slice::from_ref(dst),
+            None => &[&dst_1, dst_2],
+        };
 
         let info_len = i2osp_2(info.len())
             .map_err(|_| InternalError::OprfError(voprf::Error::DeriveKeyPair))?;
@@ -86,7 +121,7 @@ pub trait KeGroup {
             // || contextString)
             let sk_s = Self::hash_to_scalar::<CS::Hash>(
                 &[&seed, &info_len, info, &counter.to_be_bytes()],
-                &[&dst_1, dst_2],
+                dst,
             )
             .map_err(|_| InternalError::OprfError(voprf::Error::DeriveKeyPair))?;
 
@@ -114,6 +149,71 @@ pub trait KeGroup {
     fn deserialize_sk(bytes: &[u8]) -> Result<Self::Sk, InternalError>;
 }
 
+/// A [`KeGroup`] whose keys can be encoded as SPKI and PKCS#8 documents,
+/// available with the `pkcs8` feature
+///
+/// Implemented for X25519 and for elliptic curves with an
+/// [`AssociatedOid`](::elliptic_curve::pkcs8::AssociatedOid). Ristretto255 has
+/// no standardized algorithm identifier.
+#[cfg(feature = "pkcs8")]
+pub trait Pkcs8KeGroup: KeGroup {
+    /// The algorithm identifier of the group's keys
+    const ALGORITHM_IDENTIFIER: AlgorithmIdentifier<ObjectIdentifier>;
+    /// Length of the public key in a `SubjectPublicKeyInfo`
+    type SpkiPkLen: ArrayLength<u8>;
+    /// Length of the secret key in a `PrivateKeyInfo`
+    type Pkcs8SkLen: ArrayLength<u8>;
+
+    /// Serializes the public key as in the `subjectPublicKey` of a
+    /// `SubjectPublicKeyInfo`
+    fn serialize_spki_pk(pk: Self::Pk) -> GenericArray<u8, Self::SpkiPkLen>;
+
+    /// Deserializes the public key from the `subjectPublicKey` of a
+    /// `SubjectPublicKeyInfo`, which may use other encodings than
+    /// [`KeGroup::deserialize_pk`] accepts
+    fn deserialize_spki_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError>;
+
+    /// Serializes the secret key as in the `privateKey` of a `PrivateKeyInfo`
+    fn serialize_pkcs8_sk(
+        sk: Self::Sk,
+    ) -> Result<GenericArray<u8, Self::Pkcs8SkLen>, InternalError>;
+
+    /// Deserializes the secret key from the `privateKey` of a `PrivateKeyInfo`
+    fn deserialize_pkcs8_sk(bytes: &[u8]) -> Result<Self::Sk, InternalError>;
+}
+
+/// A [`KeGroup`] with the arithmetic needed to share a secret key among
+/// several servers, available with the `dkg` feature
+///
+/// Implemented for Ristretto255 and for elliptic curves. X25519 only exposes
+/// the u-coordinate of a point, so its points can't be added.
+#[cfg(feature = "dkg")]
+pub trait ThresholdKeGroup: KeGroup {
+    /// Adds two public keys
+    fn add_pk(a: Self::Pk, b: Self::Pk) -> Self::Pk;
+
+    /// Multiplies a public key by a scalar
+    fn mul_pk(pk: Self::Pk, scalar: Self::Sk) -> Self::Pk;
+
+    /// Returns `true` if the public key is the identity element
+    fn is_identity_pk(pk: Self::Pk) -> subtle::Choice;
+
+    /// Adds two scalars
+    fn add_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk;
+
+    /// Subtracts `b` from `a`
+    fn sub_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk;
+
+    /// Multiplies two scalars
+    fn mul_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk;
+
+    /// Returns the multiplicative inverse of a scalar, or zero if it is zero
+    fn invert_sk(scalar: Self::Sk) -> Self::Sk;
+
+    /// Returns the scalar of an integer
+    fn sk_from_u16(value: u16) -> Self::Sk;
+}
+
 // Helper functions used to compute DeriveAuthKeyPair() (taken from the voprf
 // crate)
 
@@ -8,7 +8,6 @@
 
 //! Key Exchange group implementation for ristretto255
 
-use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
 use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::Identity;
@@ -19,9 +18,14 @@ use generic_array::GenericArray;
 use rand::{CryptoRng, RngCore};
 use subtle::ConstantTimeEq;
 use voprf::Group;
+#[cfg(not(test))]
+use zeroize::Zeroize;
 
 use super::KeGroup;
-use crate::errors::InternalError;
+#[cfg(feature = "dkg")]
+use super::ThresholdKeGroup;
+use crate::errors::{InternalError, ProtocolError};
+use crate::util::fill_random;
 
 /// Implementation for Ristretto255.
 // This is necessary because Rust lacks specialization, otherwise we could
@@ -38,6 +42,8 @@ impl KeGroup for Ristretto255 {
         pk.compress().to_bytes().into()
     }
 
+    // Decompression only accepts canonical encodings, and the group has prime
+    // order, so it remains to reject the identity
     fn deserialize_pk(bytes: &[u8]) -> Result<Self::Pk, InternalError> {
         CompressedRistretto::from_slice(bytes)
             .map_err(|_| InternalError::PointError)?
@@ -46,12 +52,17 @@ impl KeGroup for Ristretto255 {
             .ok_or(InternalError::PointError)
     }
 
-    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Sk {
+    fn random_sk<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Self::Sk, ProtocolError> {
         loop {
             let scalar = {
+                // Like `Scalar::random`, but reporting a failure of the rng
                 #[cfg(not(test))]
                 {
-                    Scalar::random(rng)
+                    let mut scalar_bytes = [0u8; 64];
+                    fill_random(rng, &mut scalar_bytes)?;
+                    let scalar = Scalar::from_bytes_mod_order_wide(&scalar_bytes);
+                    scalar_bytes.zeroize();
+                    scalar
                 }
 
                 // Tests need an exact conversion from bytes to scalar, sampling only 32 bytes
@@ -59,13 +70,13 @@ impl KeGroup for Ristretto255 {
                 #[cfg(test)]
                 {
                     let mut scalar_bytes = [0u8; 32];
-                    rng.fill_bytes(&mut scalar_bytes);
+                    fill_random(rng, &mut scalar_bytes)?;
                     Scalar::from_bytes_mod_order(scalar_bytes)
                 }
             };
 
             if scalar != Scalar::ZERO {
-                break scalar;
+                break Ok(scalar);
             }
         }
     }
@@ -86,7 +97,7 @@ impl KeGroup for Ristretto255 {
     }
 
     fn public_key(sk: Self::Sk) -> Self::Pk {
-        RISTRETTO_BASEPOINT_POINT * sk
+        RistrettoPoint::mul_base(&sk)
     }
 
     fn diffie_hellman(pk: Self::Pk, sk: Self::Sk) -> GenericArray<u8, Self::PkLen> {
@@ -107,6 +118,42 @@ impl KeGroup for Ristretto255 {
     }
 }
 
+#[cfg(feature = "dkg")]
+impl ThresholdKeGroup for Ristretto255 {
+    fn add_pk(a: Self::Pk, b: Self::Pk) -> Self::Pk {
+        a + b
+    }
+
+    fn mul_pk(pk: Self::Pk, scalar: Self::Sk) -> Self::Pk {
+        pk * scalar
+    }
+
+    fn is_identity_pk(pk: Self::Pk) -> subtle::Choice {
+        pk.ct_eq(&RistrettoPoint::identity())
+    }
+
+    fn add_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a + b
+    }
+
+    fn sub_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a - b
+    }
+
+    fn mul_sk(a: Self::Sk, b: Self::Sk) -> Self::Sk {
+        a * b
+    }
+
+    // The inverse of zero is zero
+    fn invert_sk(scalar: Self::Sk) -> Self::Sk {
+        scalar.invert()
+    }
+
+    fn sk_from_u16(value: u16) -> Self::Sk {
+        Scalar::from(value)
+    }
+}
+
 #[cfg(feature = "ristretto255-voprf")]
 impl voprf::CipherSuite for Ristretto255 {
     const ID: &'static str = voprf::Ristretto255::ID;
@@ -7,6 +7,7 @@
 // licenses.
 
 use digest::core_api::{BlockSizeUser, OutputSizeUser};
+#[cfg(any(test, feature = "key-schedule-secrets"))]
 use digest::Output;
 use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
 use generic_array::{ArrayLength, GenericArray};
@@ -17,7 +18,9 @@ use crate::ciphersuite::{CipherSuite, OprfHash};
 use crate::errors::ProtocolError;
 use crate::hash::{Hash, ProxyHash};
 use crate::key_exchange::group::KeGroup;
-use crate::keypair::{PrivateKey, PublicKey, SecretKey};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::keypair::{PublicKey, SecretKey};
+use crate::provider::Provider;
 
 pub trait KeyExchange<D: Hash, G: KeGroup>
 where
@@ -25,11 +28,26 @@ where
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// The length of the MACs, which is also used for the envelope
+    type MacLen: ArrayLength<u8>;
+    /// The length of the session key
+    type SessionKeyLen: ArrayLength<u8>;
+    /// The implementation of HMAC and HKDF, which is also used for the rest of
+    /// the protocol, see [`provider`](crate::provider)
+    type Provider: Provider<D>;
+    /// Whether the key exchange only uses primitives approved by FIPS 140-3,
+    /// as checked by [`fips::is_approved`](crate::fips::is_approved). Defaults
+    /// to `false`.
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = false;
     type KE1State: Deserialize + Serialize + ZeroizeOnDrop + Clone;
     type KE2State: Deserialize + Serialize + ZeroizeOnDrop + Clone;
     type KE1Message: Deserialize + Serialize + ZeroizeOnDrop + Clone;
     type KE2Message: Deserialize + Serialize + ZeroizeOnDrop + Clone;
     type KE3Message: Deserialize + Serialize + ZeroizeOnDrop + Clone;
+    /// The ephemeral state of the server drawn before the first message, see
+    /// [`prepare_ke2`](Self::prepare_ke2)
+    type KE2Prepared: ZeroizeOnDrop + Clone;
 
     fn generate_ke1<OprfCs: voprf::CipherSuite, R: RngCore + CryptoRng>(
         rng: &mut R,
@@ -38,6 +56,33 @@ where
         <OprfCs::Hash as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>;
 
+    /// Draws the ephemeral keys of the server and, if not given, its nonce, and
+    /// does the work of the second message that doesn't depend on the first.
+    /// `client_s_pk` is `None` if the client is not authenticated, see
+    /// [`CipherSuite::CLIENT_AUTHENTICATION`].
+    fn prepare_ke2<OprfCs: voprf::CipherSuite, R: RngCore + CryptoRng>(
+        rng: &mut R,
+        client_s_pk: Option<PublicKey<G>>,
+        server_nonce: Option<GenericArray<u8, NonceLen>>,
+    ) -> Result<Self::KE2Prepared, ProtocolError>
+    where
+        <OprfCs::Hash as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>;
+
+    /// Returns the second message for `ke1_message` with the state prepared by
+    /// [`prepare_ke2`](Self::prepare_ke2)
+    #[allow(clippy::too_many_arguments)]
+    fn complete_ke2<'a, 'b, 'c, 'd, S: SecretKey<G>>(
+        prepared: Self::KE2Prepared,
+        l1_bytes: impl Iterator<Item = &'a [u8]>,
+        l2_bytes: impl Iterator<Item = &'b [u8]>,
+        ke1_message: Self::KE1Message,
+        server_s_sk: S,
+        id_u: impl Iterator<Item = &'c [u8]>,
+        id_s: impl Iterator<Item = &'d [u8]>,
+        context: &[u8],
+    ) -> Result<GenerateKe2Result<Self, D, G>, ProtocolError<S::Error>>;
+
     #[allow(clippy::too_many_arguments)]
     fn generate_ke2<
         'a,
@@ -52,16 +97,51 @@ where
         l1_bytes: impl Iterator<Item = &'a [u8]>,
         l2_bytes: impl Iterator<Item = &'b [u8]>,
         ke1_message: Self::KE1Message,
-        client_s_pk: PublicKey<G>,
+        client_s_pk: Option<PublicKey<G>>,
         server_s_sk: S,
         id_u: impl Iterator<Item = &'c [u8]>,
         id_s: impl Iterator<Item = &'d [u8]>,
         context: &[u8],
+        server_nonce: Option<GenericArray<u8, NonceLen>>,
     ) -> Result<GenerateKe2Result<Self, D, G>, ProtocolError<S::Error>>
     where
         <OprfCs::Hash as OutputSizeUser>::OutputSize:
-            IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>;
+            IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>,
+    {
+        let prepared = Self::prepare_ke2::<OprfCs, _>(rng, client_s_pk, server_nonce)
+            .map_err(ProtocolError::into_custom)?;
+
+        Self::complete_ke2(
+            prepared,
+            l1_bytes,
+            l2_bytes,
+            ke1_message,
+            server_s_sk,
+            id_u,
+            id_s,
+            context,
+        )
+    }
+
+    /// Returns the public key in `ke2_message` that the client's static
+    /// private key is combined with, see `client_s_dh` of `generate_ke3`
+    fn client_s_dh_pk(ke2_message: &Self::KE2Message) -> PublicKey<G>;
+
+    /// Returns the ephemeral public keys of the client and the server in
+    /// `ke1_message` and `ke2_message`, in this order
+    fn ephemeral_pks(
+        ke1_message: &Self::KE1Message,
+        ke2_message: &Self::KE2Message,
+    ) -> (PublicKey<G>, PublicKey<G>);
 
+    /// Returns the ephemeral public keys of the client and the server of the
+    /// second message that `ke2_state` was produced with, in this order
+    fn ke2_state_ephemeral_pks(
+        ke2_state: &Self::KE2State,
+    ) -> Result<(PublicKey<G>, PublicKey<G>), ProtocolError>;
+
+    /// Returns the session key and the third message, where `client_s_dh` is
+    /// `None` if the client is not authenticated
     #[allow(clippy::too_many_arguments)]
     fn generate_ke3<'a, 'b, 'c, 'd>(
         l2_component: impl Iterator<Item = &'a [u8]>,
@@ -69,7 +149,7 @@ where
         ke1_state: &Self::KE1State,
         serialized_credential_request: impl Iterator<Item = &'b [u8]>,
         server_s_pk: PublicKey<G>,
-        client_s_sk: PrivateKey<G>,
+        client_s_dh: Option<GenericArray<u8, G::PkLen>>,
         id_u: impl Iterator<Item = &'c [u8]>,
         id_s: impl Iterator<Item = &'d [u8]>,
         context: &[u8],
@@ -78,7 +158,7 @@ where
     fn finish_ke(
         ke3_message: Self::KE3Message,
         ke2_state: &Self::KE2State,
-    ) -> Result<Output<D>, ProtocolError>;
+    ) -> Result<GenericArray<u8, Self::SessionKeyLen>, ProtocolError>;
 }
 
 pub trait Deserialize: Sized {
@@ -91,32 +171,43 @@ pub trait Serialize {
     fn serialize(&self) -> GenericArray<u8, Self::Len>;
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "key-schedule-secrets")))]
 pub type GenerateKe2Result<K, D, G> = (
     <K as KeyExchange<D, G>>::KE2State,
     <K as KeyExchange<D, G>>::KE2Message,
 );
-#[cfg(test)]
+#[cfg(any(test, feature = "key-schedule-secrets"))]
 pub type GenerateKe2Result<K, D, G> = (
     <K as KeyExchange<D, G>>::KE2State,
     <K as KeyExchange<D, G>>::KE2Message,
     Output<D>,
     Output<D>,
 );
-#[cfg(not(test))]
-pub type GenerateKe3Result<K, D, G> = (Output<D>, <K as KeyExchange<D, G>>::KE3Message);
-#[cfg(test)]
+#[cfg(not(any(test, feature = "key-schedule-secrets")))]
 pub type GenerateKe3Result<K, D, G> = (
-    Output<D>,
+    GenericArray<u8, <K as KeyExchange<D, G>>::SessionKeyLen>,
+    <K as KeyExchange<D, G>>::KE3Message,
+);
+#[cfg(any(test, feature = "key-schedule-secrets"))]
+pub type GenerateKe3Result<K, D, G> = (
+    GenericArray<u8, <K as KeyExchange<D, G>>::SessionKeyLen>,
     <K as KeyExchange<D, G>>::KE3Message,
     Output<D>,
     Output<D>,
 );
 
+pub type MacLen<CS: CipherSuite> =
+    <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::MacLen;
+pub type SessionKeyLen<CS: CipherSuite> =
+    <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::SessionKeyLen;
+pub(crate) type SuiteProvider<CS: CipherSuite> =
+    <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::Provider;
+pub(crate) type SuitePrk<CS: CipherSuite> = <SuiteProvider<CS> as Provider<OprfHash<CS>>>::Prk;
 pub type Ke1StateLen<CS: CipherSuite> =
     <<CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State as Serialize>::Len;
 pub type Ke1MessageLen<CS: CipherSuite> =
     <<CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1Message as Serialize>::Len;
+#[cfg(feature = "server")]
 pub type Ke2StateLen<CS: CipherSuite> =
     <<CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State as Serialize>::Len;
 pub type Ke2MessageLen<CS: CipherSuite> =
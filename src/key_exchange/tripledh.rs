@@ -8,18 +8,17 @@
 
 //! An implementation of the Triple Diffie-Hellman key exchange protocol
 use core::convert::TryFrom;
+use core::marker::PhantomData;
 use core::ops::Add;
 
 use derive_where::derive_where;
-use digest::core_api::BlockSizeUser;
+use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::{Digest, Output, OutputSizeUser};
 use generic_array::sequence::Concat;
 use generic_array::typenum::{
-    IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U1, U2, U256, U32,
+    IsGreaterOrEqual, IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, B1, U1, U16, U2, U256, U32,
 };
 use generic_array::{ArrayLength, GenericArray};
-use hkdf::{Hkdf, HkdfExtract};
-use hmac::{Hmac, Mac};
 use rand::{CryptoRng, RngCore};
 
 use crate::errors::utils::{check_slice_size, check_slice_size_atleast};
@@ -30,7 +29,9 @@ use crate::key_exchange::traits::{
     Deserialize, GenerateKe2Result, GenerateKe3Result, KeyExchange, Serialize,
 };
 use crate::keypair::{KeyPair, PrivateKey, PublicKey, SecretKey};
+use crate::provider::{Provider, RustCrypto};
 use crate::serialization::{Input, UpdateExt};
+use crate::util::fill_random;
 
 ///////////////
 // Constants //
@@ -51,7 +52,131 @@ static STR_OPAQUE: &[u8] = b"OPAQUE-";
 ////////////////////////////
 
 /// The Triple Diffie-Hellman key exchange implementation
-pub struct TripleDh;
+///
+/// `M` selects the length of the MACs of the key exchange, which
+/// [`CipherSuite`](crate::CipherSuite)s using this key exchange also use for
+/// the envelope. It defaults to [`FullMac`], as in the specification.
+///
+/// `P` selects the [`Provider`] computing the HMACs and HKDFs of the key
+/// exchange, which [`CipherSuite`](crate::CipherSuite)s using this key
+/// exchange also use for the rest of the protocol. It defaults to
+/// [`RustCrypto`].
+///
+/// `K` selects the length of the session key and the labels of the last steps
+/// of the key schedule. It defaults to [`FullSessionKey`], as in the
+/// specification.
+pub struct TripleDh<M = FullMac, P = RustCrypto, K = FullSessionKey>(PhantomData<(M, P, K)>);
+
+/// Selects the length of the MACs computed with the hash `D`
+///
+/// The length must not exceed the output length of `D`.
+pub trait MacTruncation<D: OutputSizeUser> {
+    /// The length of a MAC in bytes
+    type Len: ArrayLength<u8>;
+}
+
+/// MACs of the full output length of the hash, as in the specification
+pub struct FullMac;
+
+/// MACs truncated to their first `N` bytes, 16 by default
+///
+/// This saves the difference to the output length of the hash, e.g. 48 bytes
+/// per MAC with SHA-512, in the envelope, the [`CredentialResponse`] and the
+/// [`CredentialFinalization`], for transports where every byte counts.
+///
+/// A forged MAC is accepted with a probability of 2<sup>-8N</sup> per try, so
+/// 16 bytes leave 128 bits of security against forgeries, matching the
+/// security level of the groups with 256 bits. Online guesses are bounded
+/// much earlier by the rate limiting of logins, and the truncation has no
+/// effect on the resistance against offline dictionary attacks. Shorter MACs
+/// are rejected at compile time.
+///
+/// The MAC length changes the serialization of all messages but the requests,
+/// so clients and servers must agree on it, and password files registered
+/// with one length can't be used with another.
+///
+/// [`CredentialResponse`]: crate::CredentialResponse
+/// [`CredentialFinalization`]: crate::CredentialFinalization
+pub struct TruncatedMac<N = U16>(PhantomData<N>);
+
+impl<D: OutputSizeUser + CoreProxy> MacTruncation<D> for FullMac
+where
+    D::Core: OutputSizeUser,
+{
+    type Len = OutputSize<D>;
+}
+
+impl<D: OutputSizeUser + CoreProxy, N> MacTruncation<D> for TruncatedMac<N>
+where
+    D::Core: OutputSizeUser,
+    N: ArrayLength<u8>
+        + IsGreaterOrEqual<U16, Output = B1>
+        + IsLessOrEqual<OutputSize<D>, Output = B1>,
+{
+    type Len = N;
+}
+
+/// Selects the length of the session key derived with the hash `D`, and the
+/// labels it and the handshake secret are derived with
+///
+/// The length must be at least 16 bytes and at most 255 times the output
+/// length of `D`. The labels default to those of the specification, and are
+/// prefixed with `OPAQUE-` like all labels of the key schedule.
+///
+/// The length and the labels don't change any messages or password files, but
+/// clients and servers must agree on them, as otherwise the MACs of the key
+/// exchange fail to verify. A suite with its own labels implements this trait
+/// on a type of its own:
+///
+/// ```
+/// use digest::OutputSizeUser;
+/// use generic_array::typenum::U64;
+/// use opaque_ke::key_exchange::tripledh::SessionKeyDerivation;
+///
+/// struct AppSessionKey;
+///
+/// impl<D: OutputSizeUser> SessionKeyDerivation<D> for AppSessionKey {
+///     type Len = U64;
+///     const HANDSHAKE_SECRET_LABEL: &'static [u8] = b"AppHandshakeSecret";
+///     const SESSION_KEY_LABEL: &'static [u8] = b"AppSessionKey";
+/// }
+/// ```
+pub trait SessionKeyDerivation<D: OutputSizeUser> {
+    /// The length of the session key in bytes
+    type Len: ArrayLength<u8>;
+    /// The label of the handshake secret, from which the MAC keys are derived.
+    /// Defaults to `HandshakeSecret`.
+    const HANDSHAKE_SECRET_LABEL: &'static [u8] = STR_HANDSHAKE_SECRET;
+    /// The label of the session key. Defaults to `SessionKey`.
+    const SESSION_KEY_LABEL: &'static [u8] = STR_SESSION_KEY;
+}
+
+/// A session key of the full output length of the hash, as in the
+/// specification
+pub struct FullSessionKey;
+
+/// A session key of `N` bytes, 32 by default, derived with the labels of the
+/// specification
+///
+/// This lets suites with a longer hash, such as SHA-512, derive a 32 byte key,
+/// or suites with SHA-256 a 64 byte key, without an
+/// [`Exporter`](crate::exporter::Exporter). Keys shorter than 16 bytes are
+/// rejected at compile time.
+pub struct SessionKeyLength<N = U32>(PhantomData<N>);
+
+impl<D: OutputSizeUser + CoreProxy> SessionKeyDerivation<D> for FullSessionKey
+where
+    D::Core: OutputSizeUser,
+{
+    type Len = OutputSize<D>;
+}
+
+impl<D: OutputSizeUser, N> SessionKeyDerivation<D> for SessionKeyLength<N>
+where
+    N: ArrayLength<u8> + IsGreaterOrEqual<U16, Output = B1>,
+{
+    type Len = N;
+}
 
 /// The client state produced after the first key exchange message
 #[cfg_attr(
@@ -86,7 +211,7 @@ pub struct Ke1Message<KG: KeGroup> {
     serde(bound = "")
 )]
 #[derive_where(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, ZeroizeOnDrop)]
-pub struct Ke2State<D: Hash>
+pub struct Ke2State<D: Hash, KG: KeGroup, K: SessionKeyDerivation<D> = FullSessionKey>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -94,7 +219,23 @@ where
 {
     km3: Output<D>,
     hashed_transcript: Output<D>,
-    session_key: Output<D>,
+    session_key: GenericArray<u8, K::Len>,
+    // The ephemeral public keys of the client and the server, serialized
+    client_e_pk: GenericArray<u8, KG::PkLen>,
+    server_e_pk: GenericArray<u8, KG::PkLen>,
+}
+
+/// The ephemeral state of the server drawn before the first key exchange
+/// message
+#[derive_where(Clone, ZeroizeOnDrop)]
+#[derive_where(Debug, Eq, Hash, Ord, PartialEq, PartialOrd; KG::Pk, KG::Sk)]
+pub struct Ke2Prepared<KG: KeGroup> {
+    server_e_sk: PrivateKey<KG>,
+    server_e_pk: PublicKey<KG>,
+    server_nonce: GenericArray<u8, NonceLen>,
+    // The Diffie-Hellman of the ephemeral key with the static key of the
+    // client, if it is authenticated
+    client_s_dh: Option<GenericArray<u8, KG::PkLen>>,
 }
 
 /// The second key exchange message
@@ -105,7 +246,7 @@ where
 )]
 #[derive_where(Clone, ZeroizeOnDrop)]
 #[derive_where(Debug, Eq, Hash, Ord, PartialEq, PartialOrd; KG::Pk)]
-pub struct Ke2Message<D: Hash, KG: KeGroup>
+pub struct Ke2Message<D: Hash, KG: KeGroup, M: MacTruncation<D> = FullMac>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -113,7 +254,7 @@ where
 {
     server_nonce: GenericArray<u8, NonceLen>,
     server_e_pk: PublicKey<KG>,
-    mac: Output<D>,
+    mac: GenericArray<u8, M::Len>,
 }
 
 /// The third key exchange message
@@ -123,13 +264,13 @@ where
     serde(bound = "")
 )]
 #[derive_where(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, ZeroizeOnDrop)]
-pub struct Ke3Message<D: Hash>
+pub struct Ke3Message<D: Hash, M: MacTruncation<D> = FullMac>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    mac: Output<D>,
+    mac: GenericArray<u8, M::Len>,
 }
 
 ////////////////////////////////
@@ -137,7 +278,8 @@ where
 // ========================== //
 ////////////////////////////////
 
-impl<D: Hash, KG: KeGroup> KeyExchange<D, KG> for TripleDh
+impl<D: Hash, KG: KeGroup, M: MacTruncation<D>, P: Provider<D>, K: SessionKeyDerivation<D>>
+    KeyExchange<D, KG> for TripleDh<M, P, K>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -148,20 +290,31 @@ where
     // Ke1Message: Nonce + KePk
     NonceLen: Add<KG::PkLen>,
     Sum<NonceLen, KG::PkLen>: ArrayLength<u8>,
-    // Ke2State: (Hash + Hash) + Hash
+    // Ke2State: (((Hash + Hash) + SessionKey) + KePk) + KePk
     OutputSize<D>: Add<OutputSize<D>>,
-    Sum<OutputSize<D>, OutputSize<D>>: ArrayLength<u8> + Add<OutputSize<D>>,
-    Sum<Sum<OutputSize<D>, OutputSize<D>>, OutputSize<D>>: ArrayLength<u8>,
-    // Ke2Message: (Nonce + KePk) + Hash
+    Sum<OutputSize<D>, OutputSize<D>>: ArrayLength<u8> + Add<K::Len>,
+    Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>: ArrayLength<u8> + Add<KG::PkLen>,
+    Sum<Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>, KG::PkLen>:
+        ArrayLength<u8> + Add<KG::PkLen>,
+    Sum<Sum<Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>, KG::PkLen>, KG::PkLen>: ArrayLength<u8>,
+    // Ke2Message: (Nonce + KePk) + Mac
     NonceLen: Add<KG::PkLen>,
-    Sum<NonceLen, KG::PkLen>: ArrayLength<u8> + Add<OutputSize<D>>,
-    Sum<Sum<NonceLen, KG::PkLen>, OutputSize<D>>: ArrayLength<u8>,
+    Sum<NonceLen, KG::PkLen>: ArrayLength<u8> + Add<M::Len>,
+    Sum<Sum<NonceLen, KG::PkLen>, M::Len>: ArrayLength<u8>,
 {
+    type MacLen = M::Len;
+    type SessionKeyLen = K::Len;
+    type Provider = P;
+    // Diffie-Hellman, HKDF and HMAC with the hash of the suite, which the
+    // `fips` feature checks separately
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = true;
     type KE1State = Ke1State<KG>;
-    type KE2State = Ke2State<D>;
+    type KE2State = Ke2State<D, KG, K>;
     type KE1Message = Ke1Message<KG>;
-    type KE2Message = Ke2Message<D, KG>;
-    type KE3Message = Ke3Message<D>;
+    type KE2Message = Ke2Message<D, KG, M>;
+    type KE3Message = Ke3Message<D, M>;
+    type KE2Prepared = Ke2Prepared<KG>;
 
     fn generate_ke1<OprfCs: voprf::CipherSuite, R: RngCore + CryptoRng>(
         rng: &mut R,
@@ -170,8 +323,8 @@ where
         <OprfCs::Hash as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>,
     {
-        let client_e_kp = KeyPair::<KG>::generate_random::<OprfCs, _>(rng);
-        let client_nonce = generate_nonce::<R>(rng);
+        let client_e_kp = KeyPair::<KG>::generate_random::<OprfCs, _>(rng)?;
+        let client_nonce = generate_nonce::<R>(rng)?;
 
         let ke1_message = Ke1Message {
             client_nonce,
@@ -187,33 +340,43 @@ where
         ))
     }
 
-    #[allow(clippy::type_complexity)]
-    fn generate_ke2<
-        'a,
-        'b,
-        'c,
-        'd,
-        OprfCs: voprf::CipherSuite,
-        R: RngCore + CryptoRng,
-        S: SecretKey<KG>,
-    >(
+    fn prepare_ke2<OprfCs: voprf::CipherSuite, R: RngCore + CryptoRng>(
         rng: &mut R,
+        client_s_pk: Option<PublicKey<KG>>,
+        server_nonce: Option<GenericArray<u8, NonceLen>>,
+    ) -> Result<Self::KE2Prepared, ProtocolError>
+    where
+        <OprfCs::Hash as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>,
+    {
+        let server_e_kp = KeyPair::<KG>::generate_random::<OprfCs, _>(rng)?;
+        let server_nonce = match server_nonce {
+            Some(server_nonce) => server_nonce,
+            None => generate_nonce::<R>(rng)?,
+        };
+        let client_s_dh = client_s_pk
+            .map(|client_s_pk| server_e_kp.private().diffie_hellman(client_s_pk))
+            .transpose()?;
+
+        Ok(Ke2Prepared {
+            server_e_sk: server_e_kp.private().clone(),
+            server_e_pk: server_e_kp.public().clone(),
+            server_nonce,
+            client_s_dh,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn complete_ke2<'a, 'b, 'c, 'd, S: SecretKey<KG>>(
+        prepared: Self::KE2Prepared,
         serialized_credential_request: impl Iterator<Item = &'a [u8]>,
         l2_bytes: impl Iterator<Item = &'b [u8]>,
         ke1_message: Self::KE1Message,
-        client_s_pk: PublicKey<KG>,
         server_s_sk: S,
         id_u: impl Iterator<Item = &'c [u8]>,
         id_s: impl Iterator<Item = &'d [u8]>,
         context: &[u8],
-    ) -> Result<GenerateKe2Result<Self, D, KG>, ProtocolError<S::Error>>
-    where
-        <OprfCs::Hash as OutputSizeUser>::OutputSize:
-            IsLess<U256> + IsLessOrEqual<<OprfCs::Hash as BlockSizeUser>::BlockSize>,
-    {
-        let server_e_kp = KeyPair::<KG>::generate_random::<OprfCs, _>(rng);
-        let server_nonce = generate_nonce::<R>(rng);
-
+    ) -> Result<GenerateKe2Result<Self, D, KG>, ProtocolError<S::Error>> {
         let mut transcript_hasher = D::new()
             .chain(STR_RFC)
             .chain_iter(
@@ -225,25 +388,25 @@ where
             .chain_iter(serialized_credential_request)
             .chain_iter(id_s.into_iter())
             .chain_iter(l2_bytes)
-            .chain(server_nonce)
-            .chain(server_e_kp.public().serialize());
-
-        let result = derive_3dh_keys::<D, KG, S>(
-            TripleDhComponents {
-                pk1: ke1_message.client_e_pk.clone(),
-                sk1: server_e_kp.private().clone(),
-                pk2: ke1_message.client_e_pk.clone(),
-                sk2: server_s_sk,
-                pk3: client_s_pk,
-                sk3: server_e_kp.private().clone(),
-            },
+            .chain(prepared.server_nonce)
+            .chain(prepared.server_e_pk.serialize());
+
+        let result = derive_3dh_keys::<D, KG, P, K>(
+            [
+                prepared
+                    .server_e_sk
+                    .diffie_hellman(ke1_message.client_e_pk.clone())
+                    .map_err(InternalError::into_custom)?,
+                server_s_sk.diffie_hellman(ke1_message.client_e_pk.clone())?,
+            ],
+            prepared.client_s_dh.as_ref(),
             &transcript_hasher.clone().finalize(),
-        )?;
+        )
+        .map_err(ProtocolError::into_custom)?;
 
-        let mut mac_hasher =
-            Hmac::<D>::new_from_slice(&result.1).map_err(|_| InternalError::HmacError)?;
-        mac_hasher.update(&transcript_hasher.clone().finalize());
-        let mac = mac_hasher.finalize().into_bytes();
+        let mut mac_hasher = P::new_mac(&result.1).map_err(InternalError::into_custom)?;
+        P::update_mac(&mut mac_hasher, &transcript_hasher.clone().finalize());
+        let mac = truncate_mac(&P::finalize_mac(mac_hasher));
 
         Digest::update(&mut transcript_hasher, &mac);
 
@@ -252,19 +415,44 @@ where
                 km3: result.2,
                 hashed_transcript: transcript_hasher.finalize(),
                 session_key: result.0,
+                client_e_pk: ke1_message.client_e_pk.serialize(),
+                server_e_pk: prepared.server_e_pk.serialize(),
             },
             Ke2Message {
-                server_nonce,
-                server_e_pk: server_e_kp.public().clone(),
+                server_nonce: prepared.server_nonce,
+                server_e_pk: prepared.server_e_pk.clone(),
                 mac,
             },
-            #[cfg(test)]
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
             result.3,
-            #[cfg(test)]
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
             result.1,
         ))
     }
 
+    fn client_s_dh_pk(ke2_message: &Self::KE2Message) -> PublicKey<KG> {
+        ke2_message.server_e_pk.clone()
+    }
+
+    fn ephemeral_pks(
+        ke1_message: &Self::KE1Message,
+        ke2_message: &Self::KE2Message,
+    ) -> (PublicKey<KG>, PublicKey<KG>) {
+        (
+            ke1_message.client_e_pk.clone(),
+            ke2_message.server_e_pk.clone(),
+        )
+    }
+
+    fn ke2_state_ephemeral_pks(
+        ke2_state: &Self::KE2State,
+    ) -> Result<(PublicKey<KG>, PublicKey<KG>), ProtocolError> {
+        Ok((
+            PublicKey::deserialize(&ke2_state.client_e_pk)?,
+            PublicKey::deserialize(&ke2_state.server_e_pk)?,
+        ))
+    }
+
     #[allow(clippy::type_complexity)]
     fn generate_ke3<'a, 'b, 'c, 'd>(
         l2_component: impl Iterator<Item = &'a [u8]>,
@@ -272,7 +460,7 @@ where
         ke1_state: &Self::KE1State,
         serialized_credential_request: impl Iterator<Item = &'b [u8]>,
         server_s_pk: PublicKey<KG>,
-        client_s_sk: PrivateKey<KG>,
+        client_s_dh: Option<GenericArray<u8, KG::PkLen>>,
         id_u: impl Iterator<Item = &'c [u8]>,
         id_s: impl Iterator<Item = &'d [u8]>,
         context: &[u8],
@@ -286,40 +474,37 @@ where
             .chain_iter(l2_component)
             .chain(ke2_message.to_bytes_without_mac());
 
-        let result = derive_3dh_keys::<D, KG, PrivateKey<KG>>(
-            TripleDhComponents {
-                pk1: ke2_message.server_e_pk.clone(),
-                sk1: ke1_state.client_e_sk.clone(),
-                pk2: server_s_pk,
-                sk2: ke1_state.client_e_sk.clone(),
-                pk3: ke2_message.server_e_pk.clone(),
-                sk3: client_s_sk,
-            },
+        let result = derive_3dh_keys::<D, KG, P, K>(
+            [
+                ke1_state
+                    .client_e_sk
+                    .diffie_hellman(ke2_message.server_e_pk.clone())?,
+                ke1_state.client_e_sk.diffie_hellman(server_s_pk)?,
+            ],
+            client_s_dh.as_ref(),
             &transcript_hasher.clone().finalize(),
         )?;
 
-        let mut server_mac =
-            Hmac::<D>::new_from_slice(&result.1).map_err(|_| InternalError::HmacError)?;
-        server_mac.update(&transcript_hasher.clone().finalize());
+        let mut server_mac = P::new_mac(&result.1)?;
+        P::update_mac(&mut server_mac, &transcript_hasher.clone().finalize());
 
-        server_mac
-            .verify(&ke2_message.mac)
-            .map_err(|_| ProtocolError::InvalidLoginError)?;
+        if !bool::from(P::verify_mac(server_mac, &ke2_message.mac)) {
+            return Err(ProtocolError::InvalidLoginError);
+        }
 
         Digest::update(&mut transcript_hasher, &ke2_message.mac);
 
-        let mut client_mac =
-            Hmac::<D>::new_from_slice(&result.2).map_err(|_| InternalError::HmacError)?;
-        client_mac.update(&transcript_hasher.finalize());
+        let mut client_mac = P::new_mac(&result.2)?;
+        P::update_mac(&mut client_mac, &transcript_hasher.finalize());
 
         Ok((
             result.0,
             Ke3Message {
-                mac: client_mac.finalize().into_bytes(),
+                mac: truncate_mac(&P::finalize_mac(client_mac)),
             },
-            #[cfg(test)]
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
             result.3,
-            #[cfg(test)]
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
             result.2,
         ))
     }
@@ -327,14 +512,13 @@ where
     fn finish_ke(
         ke3_message: Self::KE3Message,
         ke2_state: &Self::KE2State,
-    ) -> Result<Output<D>, ProtocolError> {
-        let mut client_mac =
-            Hmac::<D>::new_from_slice(&ke2_state.km3).map_err(|_| InternalError::HmacError)?;
-        client_mac.update(&ke2_state.hashed_transcript);
+    ) -> Result<GenericArray<u8, K::Len>, ProtocolError> {
+        let mut client_mac = P::new_mac(&ke2_state.km3)?;
+        P::update_mac(&mut client_mac, &ke2_state.hashed_transcript);
 
-        client_mac
-            .verify(&ke3_message.mac)
-            .map_err(|_| ProtocolError::InvalidLoginError)?;
+        if !bool::from(P::verify_mac(client_mac, &ke3_message.mac)) {
+            return Err(ProtocolError::InvalidLoginError);
+        }
 
         Ok(ke2_state.session_key.clone())
     }
@@ -345,21 +529,12 @@ where
 //==================== //
 /////////////////////////
 
-// The triple of public and private components used in the 3DH computation
-struct TripleDhComponents<KG: KeGroup, S: SecretKey<KG>> {
-    pk1: PublicKey<KG>,
-    sk1: PrivateKey<KG>,
-    pk2: PublicKey<KG>,
-    sk2: S,
-    pk3: PublicKey<KG>,
-    sk3: PrivateKey<KG>,
-}
-
-// Consists of a session key, followed by two mac keys: (session_key, km2, km3)
-#[cfg(not(test))]
-type TripleDhDerivationResult<D> = (Output<D>, Output<D>, Output<D>);
-#[cfg(test)]
-type TripleDhDerivationResult<D> = (Output<D>, Output<D>, Output<D>, Output<D>);
+// Consists of a session key of length `L`, followed by two mac keys:
+// (session_key, km2, km3)
+#[cfg(not(any(test, feature = "key-schedule-secrets")))]
+type TripleDhDerivationResult<D, L> = (GenericArray<u8, L>, Output<D>, Output<D>);
+#[cfg(any(test, feature = "key-schedule-secrets"))]
+type TripleDhDerivationResult<D, L> = (GenericArray<u8, L>, Output<D>, Output<D>, Output<D>);
 
 ////////////////////////////////////////////////
 // Helper functions and Trait Implementations //
@@ -368,61 +543,51 @@ type TripleDhDerivationResult<D> = (Output<D>, Output<D>, Output<D>, Output<D>);
 
 // Helper functions
 
-// Internal function which takes the public and private components of the client
-// and server keypairs, along with some auxiliary metadata, to produce the
-// session key and two MAC keys
-fn derive_3dh_keys<D: Hash, KG: KeGroup, S: SecretKey<KG>>(
-    dh: TripleDhComponents<KG, S>,
+// Internal function which takes the three Diffie-Hellman shared secrets between
+// the client and server keypairs, along with some auxiliary metadata, to
+// produce the session key and two MAC keys with the labels of `K`. The last one
+// is left out if the client is not authenticated.
+fn derive_3dh_keys<D: Hash, KG: KeGroup, P: Provider<D>, K: SessionKeyDerivation<D>>(
+    dh: [GenericArray<u8, KG::PkLen>; 2],
+    client_s_dh: Option<&GenericArray<u8, KG::PkLen>>,
     hashed_derivation_transcript: &[u8],
-) -> Result<TripleDhDerivationResult<D>, ProtocolError<S::Error>>
+) -> Result<TripleDhDerivationResult<D, K::Len>, ProtocolError>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    let mut hkdf = HkdfExtract::<D>::new(None);
-
-    hkdf.input_ikm(
-        &dh.sk1
-            .diffie_hellman(dh.pk1)
-            .map_err(InternalError::into_custom)?,
-    );
-    hkdf.input_ikm(&dh.sk2.diffie_hellman(dh.pk2)?);
-    hkdf.input_ikm(
-        &dh.sk3
-            .diffie_hellman(dh.pk3)
-            .map_err(InternalError::into_custom)?,
-    );
-
-    let (_, extracted_ikm) = hkdf.finalize();
-    let handshake_secret = derive_secrets::<D>(
+    // Each of the three shared secrets is fed into the key derivation on its
+    // own, so they can't be combined into a single multiscalar multiplication,
+    // which would only yield their sum.
+    let (_, extracted_ikm) = match client_s_dh {
+        Some(client_s_dh) => P::extract(None, &[&dh[0], &dh[1], client_s_dh]),
+        None => P::extract(None, &[&dh[0], &dh[1]]),
+    };
+    let handshake_secret = derive_secrets::<D, P, OutputSize<D>>(
         &extracted_ikm,
-        STR_HANDSHAKE_SECRET,
+        K::HANDSHAKE_SECRET_LABEL,
         hashed_derivation_transcript,
-    )
-    .map_err(ProtocolError::into_custom)?;
-    let session_key = derive_secrets::<D>(
+    )?;
+    let session_key = derive_secrets::<D, P, K::Len>(
         &extracted_ikm,
-        STR_SESSION_KEY,
+        K::SESSION_KEY_LABEL,
         hashed_derivation_transcript,
-    )
-    .map_err(ProtocolError::into_custom)?;
+    )?;
 
-    let km2 = hkdf_expand_label::<D>(&handshake_secret, STR_SERVER_MAC, b"")
-        .map_err(ProtocolError::into_custom)?;
-    let km3 = hkdf_expand_label::<D>(&handshake_secret, STR_CLIENT_MAC, b"")
-        .map_err(ProtocolError::into_custom)?;
+    let km2 = hkdf_expand_label::<D, P>(&handshake_secret, STR_SERVER_MAC, b"")?;
+    let km3 = hkdf_expand_label::<D, P>(&handshake_secret, STR_CLIENT_MAC, b"")?;
 
     Ok((
-        GenericArray::clone_from_slice(&session_key),
+        session_key,
         GenericArray::clone_from_slice(&km2),
         GenericArray::clone_from_slice(&km3),
-        #[cfg(test)]
+        #[cfg(any(test, feature = "key-schedule-secrets"))]
         handshake_secret,
     ))
 }
 
-fn hkdf_expand_label<D: Hash>(
+fn hkdf_expand_label<D: Hash, P: Provider<D>>(
     secret: &[u8],
     label: &[u8],
     context: &[u8],
@@ -432,15 +597,15 @@ where
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    let h = Hkdf::<D>::from_prk(secret).map_err(|_| InternalError::HkdfError)?;
-    hkdf_expand_label_extracted(&h, label, context)
+    let h = P::from_prk(secret)?;
+    hkdf_expand_label_extracted::<D, P, OutputSize<D>>(&h, label, context)
 }
 
-fn hkdf_expand_label_extracted<D: Hash>(
-    hkdf: &Hkdf<D>,
+fn hkdf_expand_label_extracted<D: Hash, P: Provider<D>, L: ArrayLength<u8>>(
+    hkdf: &P::Prk,
     label: &[u8],
     context: &[u8],
-) -> Result<Output<D>, ProtocolError>
+) -> Result<GenericArray<u8, L>, ProtocolError>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -448,8 +613,7 @@ where
 {
     let mut okm = GenericArray::default();
 
-    let length_u16: u16 =
-        u16::try_from(OutputSize::<D>::USIZE).map_err(|_| ProtocolError::SerializationError)?;
+    let length_u16: u16 = u16::try_from(L::USIZE).map_err(|_| ProtocolError::SerializationError)?;
     let label = Input::<U1>::from_label(STR_OPAQUE, label)?;
     let label = label.to_array_3();
     let context = Input::<U1>::from(context)?;
@@ -464,29 +628,36 @@ where
         context[1],
     ];
 
-    hkdf.expand_multi_info(&hkdf_label, &mut okm)
-        .map_err(|_| InternalError::HkdfError)?;
+    P::expand(hkdf, &hkdf_label, &mut okm)?;
     Ok(okm)
 }
 
-fn derive_secrets<D: Hash>(
-    hkdf: &Hkdf<D>,
+fn derive_secrets<D: Hash, P: Provider<D>, L: ArrayLength<u8>>(
+    hkdf: &P::Prk,
     label: &[u8],
     hashed_derivation_transcript: &[u8],
-) -> Result<Output<D>, ProtocolError>
+) -> Result<GenericArray<u8, L>, ProtocolError>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    hkdf_expand_label_extracted::<D>(hkdf, label, hashed_derivation_transcript)
+    hkdf_expand_label_extracted::<D, P, L>(hkdf, label, hashed_derivation_transcript)
+}
+
+// Keeps the first `L` bytes of `mac`, which the bounds of `MacTruncation`
+// guarantee to be at most its length
+pub(crate) fn truncate_mac<L: ArrayLength<u8>>(mac: &[u8]) -> GenericArray<u8, L> {
+    GenericArray::clone_from_slice(&mac[..L::USIZE])
 }
 
 // Generate a random nonce up to NonceLen::USIZE bytes.
-fn generate_nonce<R: RngCore + CryptoRng>(rng: &mut R) -> GenericArray<u8, NonceLen> {
+fn generate_nonce<R: RngCore + CryptoRng>(
+    rng: &mut R,
+) -> Result<GenericArray<u8, NonceLen>, ProtocolError> {
     let mut nonce_bytes = GenericArray::default();
-    rng.fill_bytes(&mut nonce_bytes);
-    nonce_bytes
+    fill_random(rng, &mut nonce_bytes)?;
+    Ok(nonce_bytes)
 }
 
 // Serialization and deserialization implementations
@@ -496,7 +667,7 @@ impl<KG: KeGroup> Deserialize for Ke1State<KG> {
         let key_len = KG::SkLen::USIZE;
 
         let nonce_len = NonceLen::USIZE;
-        let checked_bytes = check_slice_size_atleast(bytes, key_len + nonce_len, "ke1_state")?;
+        let checked_bytes = check_slice_size(bytes, key_len + nonce_len, "ke1_state")?;
 
         Ok(Self {
             client_e_sk: PrivateKey::deserialize(&checked_bytes[..key_len])?,
@@ -549,7 +720,7 @@ where
     }
 }
 
-impl<D: Hash> Deserialize for Ke2State<D>
+impl<D: Hash, KG: KeGroup, K: SessionKeyDerivation<D>> Deserialize for Ke2State<D, KG, K>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -557,39 +728,54 @@ where
 {
     fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let hash_len = OutputSize::<D>::USIZE;
-        let checked_bytes = check_slice_size(input, 3 * hash_len, "ke2_state")?;
+        let session_key_end = 2 * hash_len + K::Len::USIZE;
+        let key_len = KG::PkLen::USIZE;
+        let checked_bytes = check_slice_size(input, session_key_end + 2 * key_len, "ke2_state")?;
 
         Ok(Self {
             km3: GenericArray::clone_from_slice(&checked_bytes[..hash_len]),
             hashed_transcript: GenericArray::clone_from_slice(
                 &checked_bytes[hash_len..2 * hash_len],
             ),
-            session_key: GenericArray::clone_from_slice(&checked_bytes[2 * hash_len..3 * hash_len]),
+            session_key: GenericArray::clone_from_slice(
+                &checked_bytes[2 * hash_len..session_key_end],
+            ),
+            client_e_pk: PublicKey::<KG>::deserialize(
+                &checked_bytes[session_key_end..session_key_end + key_len],
+            )?
+            .serialize(),
+            server_e_pk: PublicKey::<KG>::deserialize(&checked_bytes[session_key_end + key_len..])?
+                .serialize(),
         })
     }
 }
 
-impl<D: Hash> Serialize for Ke2State<D>
+impl<D: Hash, KG: KeGroup, K: SessionKeyDerivation<D>> Serialize for Ke2State<D, KG, K>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Ke2State: (Hash + Hash) + Hash
+    // Ke2State: (((Hash + Hash) + SessionKey) + KePk) + KePk
     OutputSize<D>: Add<OutputSize<D>>,
-    Sum<OutputSize<D>, OutputSize<D>>: ArrayLength<u8> + Add<OutputSize<D>>,
-    Sum<Sum<OutputSize<D>, OutputSize<D>>, OutputSize<D>>: ArrayLength<u8>,
+    Sum<OutputSize<D>, OutputSize<D>>: ArrayLength<u8> + Add<K::Len>,
+    Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>: ArrayLength<u8> + Add<KG::PkLen>,
+    Sum<Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>, KG::PkLen>:
+        ArrayLength<u8> + Add<KG::PkLen>,
+    Sum<Sum<Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>, KG::PkLen>, KG::PkLen>: ArrayLength<u8>,
 {
-    type Len = Sum<Sum<OutputSize<D>, OutputSize<D>>, OutputSize<D>>;
+    type Len = Sum<Sum<Sum<Sum<OutputSize<D>, OutputSize<D>>, K::Len>, KG::PkLen>, KG::PkLen>;
 
     fn serialize(&self) -> GenericArray<u8, Self::Len> {
         self.km3
             .clone()
             .concat(self.hashed_transcript.clone())
             .concat(self.session_key.clone())
+            .concat(self.client_e_pk.clone())
+            .concat(self.server_e_pk.clone())
     }
 }
 
-impl<KG: KeGroup, D: Hash> Deserialize for Ke2Message<D, KG>
+impl<KG: KeGroup, D: Hash, M: MacTruncation<D>> Deserialize for Ke2Message<D, KG, M>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -607,7 +793,7 @@ where
         )?;
         let checked_mac = check_slice_size(
             &unchecked_server_e_pk[key_len..],
-            OutputSize::<D>::USIZE,
+            M::Len::USIZE,
             "ke1_message mac",
         )?;
 
@@ -622,17 +808,17 @@ where
     }
 }
 
-impl<D: Hash, KG: KeGroup> Serialize for Ke2Message<D, KG>
+impl<D: Hash, KG: KeGroup, M: MacTruncation<D>> Serialize for Ke2Message<D, KG, M>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Ke2Message: (Nonce + KePk) + Hash
+    // Ke2Message: (Nonce + KePk) + Mac
     NonceLen: Add<KG::PkLen>,
-    Sum<NonceLen, KG::PkLen>: ArrayLength<u8> + Add<OutputSize<D>>,
-    Sum<Sum<NonceLen, KG::PkLen>, OutputSize<D>>: ArrayLength<u8>,
+    Sum<NonceLen, KG::PkLen>: ArrayLength<u8> + Add<M::Len>,
+    Sum<Sum<NonceLen, KG::PkLen>, M::Len>: ArrayLength<u8>,
 {
-    type Len = Sum<Sum<NonceLen, KG::PkLen>, OutputSize<D>>;
+    type Len = Sum<Sum<NonceLen, KG::PkLen>, M::Len>;
 
     fn serialize(&self) -> GenericArray<u8, Self::Len> {
         self.server_nonce
@@ -641,7 +827,7 @@ where
     }
 }
 
-impl<D: Hash, KG: KeGroup> Ke2Message<D, KG>
+impl<D: Hash, KG: KeGroup, M: MacTruncation<D>> Ke2Message<D, KG, M>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
@@ -654,14 +840,14 @@ where
     }
 }
 
-impl<D: Hash> Deserialize for Ke3Message<D>
+impl<D: Hash, M: MacTruncation<D>> Deserialize for Ke3Message<D, M>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
-        let checked_bytes = check_slice_size(bytes, OutputSize::<D>::USIZE, "ke3_message")?;
+        let checked_bytes = check_slice_size(bytes, M::Len::USIZE, "ke3_message")?;
 
         Ok(Self {
             mac: GenericArray::clone_from_slice(checked_bytes),
@@ -669,13 +855,13 @@ where
     }
 }
 
-impl<D: Hash> Serialize for Ke3Message<D>
+impl<D: Hash, M: MacTruncation<D>> Serialize for Ke3Message<D, M>
 where
     D::Core: ProxyHash,
     <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    type Len = OutputSize<D>;
+    type Len = M::Len;
 
     fn serialize(&self) -> GenericArray<u8, Self::Len> {
         self.mac.clone()
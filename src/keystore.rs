@@ -0,0 +1,227 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Passphrase-protected storage of a [`ServerSetup`]
+//!
+//! For deployments without an HSM, [`seal`] encrypts a serialized
+//! [`ServerSetup`] under a key derived from a passphrase with the key
+//! stretching function of the [`CipherSuite`], [`open`] restores it, and
+//! [`rotate`] re-encrypts it under a new passphrase:
+//!
+//! ```ignore
+//! let sealed = keystore::seal(&mut OsRng, &server_setup, passphrase, None)?;
+//! let server_setup = keystore::open::<Default, _>(&sealed, passphrase, None)?;
+//! let sealed = keystore::rotate::<Default, _, _>(&mut OsRng, &sealed, passphrase, new_passphrase, None)?;
+//! ```
+//!
+//! Every sealing draws a fresh salt as long as the output of the hash. The
+//! passphrase is extracted with the salt, stretched with the KSF, and extracted
+//! again with the salt into a key from which a pad and a MAC key are expanded.
+//! The serialized [`ServerSetup`] is masked with the pad, and the masked bytes
+//! and the salt are authenticated with an HMAC, which is checked before
+//! anything is unmasked.
+//!
+//! The passphrase is the only secret protecting the keys of the server, so it
+//! should either be random or the KSF configured with costs that make guessing
+//! it infeasible.
+
+use core::ops::Add;
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Output, OutputSizeUser};
+use generic_array::sequence::Concat;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
+use generic_array::{ArrayLength, GenericArray};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::errors::utils::check_slice_size;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::SuiteProvider;
+use crate::keypair::SecretKey;
+use crate::ksf::Ksf;
+use crate::opaque::{ServerSetup, ServerSetupLen};
+use crate::provider::Provider;
+use crate::util::fill_random;
+
+static STR_KEYSTORE_PAD: &[u8] = b"KeystorePad";
+static STR_KEYSTORE_AUTH_KEY: &[u8] = b"KeystoreAuthKey";
+
+/// Length of a sealed [`ServerSetup`] in bytes
+pub type SealedServerSetupLen<CS: CipherSuite, S: SecretKey<CS::KeGroup>> =
+    Sum<Sum<ServerSetupLen<CS, S>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>;
+
+/// Encrypts `server_setup` under `passphrase`
+///
+/// Uses the default configuration of the KSF of the cipher suite unless `ksf`
+/// is given, which has to be given to [`open`] as well.
+pub fn seal<CS: CipherSuite, S: SecretKey<CS::KeGroup>, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    server_setup: &ServerSetup<CS, S>,
+    passphrase: &[u8],
+    ksf: Option<&CS::Ksf>,
+) -> Result<GenericArray<u8, SealedServerSetupLen<CS, S>>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // ServerSetup: Hash + KeSk + KeSk
+    OutputSize<OprfHash<CS>>: Add<S::Len>,
+    Sum<OutputSize<OprfHash<CS>>, S::Len>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+    ServerSetupLen<CS, S>: ArrayLength<u8>,
+    // SealedServerSetup: (ServerSetup + Salt) + Hash
+    ServerSetupLen<CS, S>: Add<OutputSize<OprfHash<CS>>>,
+    Sum<ServerSetupLen<CS, S>, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+    SealedServerSetupLen<CS, S>: ArrayLength<u8>,
+{
+    let mut salt = Output::<OprfHash<CS>>::default();
+    fill_random(rng, &mut salt)?;
+
+    let (mut pad, mut mac) = derive_keys::<CS, ServerSetupLen<CS, S>>(&salt, passphrase, ksf)?;
+    let mut masked = server_setup.serialize();
+    masked
+        .iter_mut()
+        .zip(&pad)
+        .for_each(|(byte, pad)| *byte ^= pad);
+    pad.zeroize();
+
+    SuiteProvider::<CS>::update_mac(&mut mac, &masked);
+    SuiteProvider::<CS>::update_mac(&mut mac, &salt);
+    let tag = SuiteProvider::<CS>::finalize_mac(mac);
+
+    Ok(masked.concat(salt).concat(tag))
+}
+
+/// Decrypts a [`ServerSetup`] sealed under `passphrase`
+///
+/// Fails with [`ProtocolError::KeystoreError`] if the passphrase or the KSF
+/// configuration is wrong, or `input` was modified.
+pub fn open<CS: CipherSuite, S: SecretKey<CS::KeGroup>>(
+    input: &[u8],
+    passphrase: &[u8],
+    ksf: Option<&CS::Ksf>,
+) -> Result<ServerSetup<CS, S>, ProtocolError<S::Error>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // ServerSetup: Hash + KeSk + KeSk
+    OutputSize<OprfHash<CS>>: Add<S::Len>,
+    Sum<OutputSize<OprfHash<CS>>, S::Len>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+    ServerSetupLen<CS, S>: ArrayLength<u8>,
+{
+    let salt_len = OutputSize::<OprfHash<CS>>::USIZE;
+    let setup_len = ServerSetupLen::<CS, S>::USIZE;
+    let checked_slice = check_slice_size(
+        input,
+        setup_len + salt_len + OutputSize::<OprfHash<CS>>::USIZE,
+        "sealed_server_setup",
+    )?;
+    let masked = &checked_slice[..setup_len];
+    let salt = &checked_slice[setup_len..setup_len + salt_len];
+
+    let (mut pad, mut mac) = derive_keys::<CS, ServerSetupLen<CS, S>>(salt, passphrase, ksf)
+        .map_err(ProtocolError::into_custom)?;
+    SuiteProvider::<CS>::update_mac(&mut mac, masked);
+    SuiteProvider::<CS>::update_mac(&mut mac, salt);
+    let verified = SuiteProvider::<CS>::verify_mac(mac, &checked_slice[setup_len + salt_len..]);
+    if !bool::from(verified) {
+        pad.zeroize();
+        return Err(ProtocolError::KeystoreError);
+    }
+
+    pad.iter_mut()
+        .zip(masked)
+        .for_each(|(pad, byte)| *pad ^= byte);
+    let server_setup = ServerSetup::deserialize(&pad);
+    pad.zeroize();
+
+    server_setup
+}
+
+/// Re-encrypts a [`ServerSetup`] sealed under `passphrase` under
+/// `new_passphrase`, with a fresh salt
+#[allow(clippy::type_complexity)]
+pub fn rotate<CS: CipherSuite, S: SecretKey<CS::KeGroup>, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    input: &[u8],
+    passphrase: &[u8],
+    new_passphrase: &[u8],
+    ksf: Option<&CS::Ksf>,
+) -> Result<GenericArray<u8, SealedServerSetupLen<CS, S>>, ProtocolError<S::Error>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // ServerSetup: Hash + KeSk + KeSk
+    OutputSize<OprfHash<CS>>: Add<S::Len>,
+    Sum<OutputSize<OprfHash<CS>>, S::Len>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+    ServerSetupLen<CS, S>: ArrayLength<u8>,
+    // SealedServerSetup: (ServerSetup + Salt) + Hash
+    ServerSetupLen<CS, S>: Add<OutputSize<OprfHash<CS>>>,
+    Sum<ServerSetupLen<CS, S>, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+    SealedServerSetupLen<CS, S>: ArrayLength<u8>,
+{
+    let server_setup = open::<CS, S>(input, passphrase, ksf)?;
+    seal(rng, &server_setup, new_passphrase, ksf).map_err(ProtocolError::into_custom)
+}
+
+// Returns the pad masking the serialized setup and the MAC authenticating it
+#[allow(clippy::type_complexity)]
+fn derive_keys<CS: CipherSuite, L: ArrayLength<u8>>(
+    salt: &[u8],
+    passphrase: &[u8],
+    ksf: Option<&CS::Ksf>,
+) -> Result<
+    (
+        GenericArray<u8, L>,
+        <SuiteProvider<CS> as Provider<OprfHash<CS>>>::Mac,
+    ),
+    ProtocolError,
+>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let (mut prk, _) = SuiteProvider::<CS>::extract(Some(salt), &[passphrase]);
+    let stretched = match ksf {
+        Some(ksf) => ksf.hash(prk.clone()),
+        None => CS::Ksf::default().hash(prk.clone()),
+    };
+    prk.zeroize();
+    let mut stretched = stretched?;
+    let (_, hkdf) = SuiteProvider::<CS>::extract(Some(salt), &[&stretched]);
+    stretched.zeroize();
+
+    let mut pad = GenericArray::default();
+    SuiteProvider::<CS>::expand(&hkdf, &[STR_KEYSTORE_PAD], &mut pad)?;
+    let mut auth_key = Output::<OprfHash<CS>>::default();
+    SuiteProvider::<CS>::expand(&hkdf, &[STR_KEYSTORE_AUTH_KEY], &mut auth_key)?;
+    let mac = SuiteProvider::<CS>::new_mac(&auth_key)?;
+    auth_key.zeroize();
+
+    Ok((pad, mac))
+}
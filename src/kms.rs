@@ -0,0 +1,294 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Server keys held by a cloud KMS, available with the `kms` feature
+//!
+//! [`KmsSecretKey`] implements [`SecretKey`] for the NIST curves by deriving
+//! the Diffie-Hellman output with the key agreement of a KMS, so that the
+//! static key of the server is never exportable. It talks to the KMS through a
+//! [`KmsClient`], which deployments implement on top of the SDK they already
+//! use. For AWS KMS, whose `ECC_NIST_P256` and `ECC_NIST_P384` key specs
+//! support key agreement, that is:
+//!
+//! ```ignore
+//! #[derive(Clone)]
+//! struct AwsKms(aws_sdk_kms::Client, tokio::runtime::Handle);
+//!
+//! impl KmsClient for AwsKms {
+//!     type Error = aws_sdk_kms::Error;
+//!     type Future<'a> = BoxFuture<'a, Result<Vec<u8>, Self::Error>>;
+//!
+//!     fn public_key<'a>(&'a self, key_id: &'a str) -> Self::Future<'a> {
+//!         let request = self.0.get_public_key().key_id(key_id).send();
+//!         let task = self.1.spawn(request);
+//!         Box::pin(async move {
+//!             let output = task.await.expect("task panicked")?;
+//!             Ok(output.public_key.map(Blob::into_inner).unwrap_or_default())
+//!         })
+//!     }
+//!
+//!     fn derive_shared_secret<'a>(&'a self, key_id: &'a str, public_key: Vec<u8>) -> Self::Future<'a> {
+//!         let request = self
+//!             .0
+//!             .derive_shared_secret()
+//!             .key_id(key_id)
+//!             .key_agreement_algorithm(KeyAgreementAlgorithmSpec::Ecdh)
+//!             .public_key(Blob::new(public_key))
+//!             .send();
+//!         let task = self.1.spawn(request);
+//!         Box::pin(async move {
+//!             let output = task.await.expect("task panicked")?;
+//!             Ok(output.shared_secret.map(Blob::into_inner).unwrap_or_default())
+//!         })
+//!     }
+//! }
+//!
+//! let key = KmsSecretKey::<NistP256, _>::connect(AwsKms(client, Handle::current()), key_id).await?;
+//! let server_setup = ServerSetup::<CS, _>::new_with_key(&mut OsRng, KeyPair::from_private_key(key)?)?;
+//! ```
+//!
+//! GCP Cloud KMS offers no key agreement for elliptic curve keys, so it can't
+//! back a [`KmsSecretKey`].
+//!
+//! The key agreement of a KMS only returns the x-coordinate of the shared
+//! point, as specified for ECC CDH in NIST SP 800-56A, while the key exchange
+//! uses the whole compressed point. [`KmsSecretKey`] recovers the sign of the
+//! y-coordinate with a second derivation against the sum of the peer's public
+//! key and the generator, keeping the candidate point `P` for which `P` plus
+//! the server's public key has the x-coordinate returned by it. Every
+//! Diffie-Hellman operation therefore costs two KMS requests.
+//!
+//! [`SecretKey::diffie_hellman`] is synchronous, so [`KmsSecretKey`] drives
+//! the futures of the [`KmsClient`] to completion on the calling thread,
+//! blocking it. Servers running on Tokio should call
+//! [`ServerLogin::start`](crate::ServerLogin::start) from
+//! `tokio::task::spawn_blocking`, and have the [`KmsClient`] spawn its
+//! requests on the runtime, as above, because the calling thread doesn't run
+//! the reactor of the runtime.
+//!
+//! The key can't be serialized, so [`KmsSecretKey::serialize`] is empty and
+//! [`KmsSecretKey::deserialize`] fails. Restore a
+//! [`ServerSetup`](crate::ServerSetup) instead with
+//! [`ServerSetup::deserialize_with_keypair`](crate::ServerSetup::deserialize_with_keypair).
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll};
+use std::string::String;
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, Thread};
+use std::vec::Vec;
+
+use derive_where::derive_where;
+use elliptic_curve::group::{Curve, Group};
+use elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use elliptic_curve::{AffinePoint, CurveArithmetic, FieldBytes, FieldBytesSize, ProjectivePoint};
+use generic_array::typenum::U0;
+use generic_array::GenericArray;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroize;
+
+use crate::errors::InternalError;
+use crate::key_exchange::group::{KeGroup, Pkcs8KeGroup};
+use crate::keypair::{PublicKey, SecretKey};
+
+// Large enough for the `SubjectPublicKeyInfo` of an uncompressed P-521 point
+const SPKI_BUFFER_LEN: usize = 192;
+
+/// An error of a [`KmsSecretKey`]
+#[derive(Clone, Copy, Debug, displaydoc::Display, Eq, Hash, PartialEq)]
+pub enum KmsError<E> {
+    /// The KMS request failed: {0}
+    Client(E),
+    /// The public key returned by the KMS isn't a key of the group
+    InvalidPublicKey,
+    /// The shared secret returned by the KMS isn't an x-coordinate of the group
+    InvalidSharedSecret,
+    /// The shared point with the negated generator can't be recovered
+    IdentityElement,
+    /// Error from a group operation: {0}
+    Library(InternalError),
+}
+
+impl<E: std::error::Error> std::error::Error for KmsError<E> {}
+
+/// The requests to a KMS holding the private key of a [`KmsSecretKey`]
+pub trait KmsClient: Clone {
+    /// Custom error type that can be passed down to [`KmsError::Client`]
+    type Error;
+    /// The future returned by the requests
+    type Future<'a>: Future<Output = Result<Vec<u8>, Self::Error>>
+    where
+        Self: 'a;
+
+    /// Returns the DER-encoded `SubjectPublicKeyInfo` of the key `key_id`,
+    /// e.g. with the `GetPublicKey` operation of AWS KMS
+    fn public_key<'a>(&'a self, key_id: &'a str) -> Self::Future<'a>;
+
+    /// Returns the x-coordinate of the ECDH shared point of the key `key_id`
+    /// and the DER-encoded `SubjectPublicKeyInfo` `public_key`, e.g. with the
+    /// `DeriveSharedSecret` operation of AWS KMS
+    fn derive_shared_secret<'a>(&'a self, key_id: &'a str, public_key: Vec<u8>)
+        -> Self::Future<'a>;
+}
+
+/// A private key held by a KMS, see the [module documentation](self)
+#[derive_where(Clone; C)]
+pub struct KmsSecretKey<G: KeGroup, C: KmsClient> {
+    client: C,
+    key_id: String,
+    public_key: PublicKey<G>,
+}
+
+impl<G, C> KmsSecretKey<G, C>
+where
+    G: CurveArithmetic + Pkcs8KeGroup<Pk = ProjectivePoint<G>>,
+    FieldBytesSize<G>: ModulusSize,
+    AffinePoint<G>: FromEncodedPoint<G> + ToEncodedPoint<G>,
+    ProjectivePoint<G>: ToEncodedPoint<G>,
+    C: KmsClient,
+{
+    /// Uses the key `key_id` of `client`, whose public key is `public_key`
+    pub fn new(client: C, key_id: impl Into<String>, public_key: PublicKey<G>) -> Self {
+        Self {
+            client,
+            key_id: key_id.into(),
+            public_key,
+        }
+    }
+
+    /// Uses the key `key_id` of `client`, retrieving its public key from the
+    /// KMS
+    ///
+    /// # Errors
+    /// - [`KmsError::Client`] if the request fails
+    /// - [`KmsError::InvalidPublicKey`] if the public key isn't a key of the
+    ///   group
+    pub async fn connect(client: C, key_id: impl Into<String>) -> Result<Self, KmsError<C::Error>> {
+        let key_id = key_id.into();
+        let public_key = client.public_key(&key_id).await.map_err(KmsError::Client)?;
+        let public_key =
+            PublicKey::from_spki_der(&public_key).map_err(|_| KmsError::InvalidPublicKey)?;
+
+        Ok(Self::new(client, key_id, public_key))
+    }
+
+    /// Returns the identifier of the key in the KMS
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the client of the KMS
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    // Returns the x-coordinate of the shared point with `pk`
+    fn derive(&self, pk: ProjectivePoint<G>) -> Result<FieldBytes<G>, KmsError<C::Error>> {
+        let mut buffer = [0; SPKI_BUFFER_LEN];
+        let public_key = PublicKey::<G>::from_group_type(pk)
+            .to_spki_der(&mut buffer)
+            .map_err(KmsError::Library)?
+            .to_vec();
+        let mut shared_secret =
+            block_on(self.client.derive_shared_secret(&self.key_id, public_key))
+                .map_err(KmsError::Client)?;
+        let result = FieldBytes::<G>::from_exact_iter(shared_secret.iter().copied())
+            .ok_or(KmsError::InvalidSharedSecret);
+        shared_secret.zeroize();
+
+        result
+    }
+}
+
+impl<G, C> SecretKey<G> for KmsSecretKey<G, C>
+where
+    G: CurveArithmetic + Pkcs8KeGroup<Pk = ProjectivePoint<G>>,
+    FieldBytesSize<G>: ModulusSize,
+    AffinePoint<G>: FromEncodedPoint<G> + ToEncodedPoint<G>,
+    ProjectivePoint<G>: ToEncodedPoint<G>,
+    C: KmsClient,
+{
+    type Error = KmsError<C::Error>;
+    type Len = U0;
+
+    fn diffie_hellman(
+        &self,
+        pk: PublicKey<G>,
+    ) -> Result<GenericArray<u8, G::PkLen>, InternalError<Self::Error>> {
+        let pk = pk.to_group_type();
+        let offset = pk + ProjectivePoint::<G>::generator();
+        if bool::from(offset.is_identity()) {
+            return Err(InternalError::Custom(KmsError::IdentityElement));
+        }
+
+        let mut x = self.derive(pk).map_err(InternalError::Custom)?;
+        let mut offset_x = self.derive(offset).map_err(InternalError::Custom)?;
+
+        // The candidate with the even y-coordinate
+        let mut encoded = GenericArray::<u8, G::PkLen>::default();
+        encoded[0] = 0x02;
+        encoded[1..].copy_from_slice(&x);
+        let candidate = G::deserialize_pk(&encoded)
+            .map_err(|_| InternalError::Custom(KmsError::InvalidSharedSecret));
+        x.zeroize();
+        encoded.zeroize();
+        let candidate = candidate?;
+
+        // `candidate` plus the public key is only the identity if the shared
+        // point is its negation, because the peer's public key isn't the
+        // negated generator
+        let sum = (candidate + self.public_key.to_group_type())
+            .to_affine()
+            .to_encoded_point(true);
+        let is_shared = sum
+            .x()
+            .map_or(Choice::from(0), |sum_x| sum_x.ct_eq(&offset_x));
+        offset_x.zeroize();
+
+        let shared = ProjectivePoint::<G>::conditional_select(&-candidate, &candidate, is_shared);
+
+        Ok(G::serialize_pk(shared))
+    }
+
+    fn public_key(&self) -> Result<PublicKey<G>, InternalError<Self::Error>> {
+        Ok(self.public_key.clone())
+    }
+
+    fn serialize(&self) -> GenericArray<u8, Self::Len> {
+        GenericArray::default()
+    }
+
+    fn deserialize(_input: &[u8]) -> Result<Self, InternalError<Self::Error>> {
+        Err(InternalError::InvalidByteSequence)
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Drives `future` to completion, parking the calling thread while it's
+// pending
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => break output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
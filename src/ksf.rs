@@ -8,17 +8,191 @@
 
 //! Trait specifying a key stretching function
 
+use core::future::Future;
+
+use generic_array::sequence::Concat;
+use generic_array::typenum::{Unsigned, U12, U4};
 use generic_array::{ArrayLength, GenericArray};
 
-use crate::errors::InternalError;
+use crate::errors::utils::check_slice_size;
+use crate::errors::{InternalError, ProtocolError};
 
 /// Used for the key stretching function in OPAQUE
 pub trait Ksf: Default {
+    /// Whether the function is approved by FIPS 140-3, as checked by
+    /// [`fips::is_approved`](crate::fips::is_approved). Defaults to `false`.
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = false;
+
     /// Computes the key stretching function
     fn hash<L: ArrayLength<u8>>(
         &self,
         input: GenericArray<u8, L>,
     ) -> Result<GenericArray<u8, L>, InternalError>;
+
+    /// Returns the cost parameters of this configuration, which the client
+    /// attests to the server during registration. Defaults to all-zero costs
+    /// for functions that are not parameterized.
+    fn parameters(&self) -> KsfParameters {
+        KsfParameters::default()
+    }
+
+    /// Computes the key stretching function while reporting its progress to
+    /// `progress`, and aborting with [`InternalError::KsfCancelledError`] once
+    /// [`KsfProgress::is_cancelled`] returns `true`
+    ///
+    /// The default implementation can only check for cancellation before and
+    /// after calling [`Ksf::hash`]. Implementations which are able to run in
+    /// several steps should check it in between.
+    fn hash_with_progress<L: ArrayLength<u8>>(
+        &self,
+        input: GenericArray<u8, L>,
+        progress: &dyn KsfProgress,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        if progress.is_cancelled() {
+            return Err(InternalError::KsfCancelledError);
+        }
+        progress.report(0, 1);
+
+        let output = self.hash(input)?;
+
+        if progress.is_cancelled() {
+            return Err(InternalError::KsfCancelledError);
+        }
+        progress.report(1, 1);
+
+        Ok(output)
+    }
+}
+
+/// A key stretching function which is computed in several chunks, so that
+/// [`ClientRegistration::finish_async`](crate::ClientRegistration::finish_async)
+/// and [`ClientLogin::finish_async`](crate::ClientLogin::finish_async) can
+/// yield control between them
+///
+/// Chaining [`ChunkedKsf::hash_chunk`] over all chunks must give the same
+/// output as [`Ksf::hash`].
+pub trait ChunkedKsf: Ksf {
+    /// Returns the number of chunks, which is at least one
+    fn chunks(&self) -> u32;
+
+    /// Computes chunk `index` on the output of the previous chunk, or on the
+    /// input of the key stretching function for the first chunk
+    fn hash_chunk<L: ArrayLength<u8>>(
+        &self,
+        index: u32,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError>;
+}
+
+// Computes a chunked key stretching function, awaiting `yield_now` before each
+// chunk and reporting the completed chunks to `progress`
+pub(crate) async fn hash_cooperatively<
+    K: ChunkedKsf,
+    L: ArrayLength<u8>,
+    F: Future<Output = ()>,
+>(
+    ksf: &K,
+    input: GenericArray<u8, L>,
+    progress: Option<&dyn KsfProgress>,
+    mut yield_now: impl FnMut() -> F,
+) -> Result<GenericArray<u8, L>, InternalError> {
+    let chunks = ksf.chunks();
+    let mut output = input;
+
+    for index in 0..chunks {
+        yield_now().await;
+
+        if let Some(progress) = progress {
+            if progress.is_cancelled() {
+                return Err(InternalError::KsfCancelledError);
+            }
+            progress.report(index, chunks);
+        }
+
+        output = ksf.hash_chunk(index, output)?;
+    }
+
+    if let Some(progress) = progress {
+        if progress.is_cancelled() {
+            return Err(InternalError::KsfCancelledError);
+        }
+        progress.report(chunks, chunks);
+    }
+
+    Ok(output)
+}
+
+/// Observes the progress of a key stretching function and allows cancelling it,
+/// e.g. to keep a user interface responsive during slow password hashing
+///
+/// Both methods take `&self` so that a cancellation flag can be shared with,
+/// and set from, another thread.
+///
+/// How often cancellation is checked depends on the key stretching function.
+/// Plain `argon2::Argon2` computes all of its passes in a single call, so it
+/// can only be cancelled before and after hashing, not while it runs.
+/// `ChunkedArgon2` checks between its chunks, but its output differs from the
+/// one of plain Argon2 with more than one chunk, so it has to be chosen
+/// before registering a credential.
+pub trait KsfProgress {
+    /// Called with the number of `completed` steps out of `total`
+    fn report(&self, completed: u32, total: u32) {
+        let _ = (completed, total);
+    }
+
+    /// Returns `true` if the computation should be aborted
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Cost parameters of a key stretching function, used by servers to enforce a
+/// minimum hardening policy on registrations
+///
+/// Note that these values are attested by the client and cannot be verified by
+/// the server, so they only protect against misconfigured clients and not
+/// against malicious ones.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct KsfParameters {
+    /// Memory cost in KiB
+    pub memory_cost: u32,
+    /// Number of iterations
+    pub time_cost: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl KsfParameters {
+    /// Returns `true` if the memory and time costs are each at least as high
+    /// as those of `minimum`
+    pub fn satisfies(&self, minimum: &Self) -> bool {
+        self.memory_cost >= minimum.memory_cost && self.time_cost >= minimum.time_cost
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, U12> {
+        GenericArray::<_, U4>::from(self.memory_cost.to_be_bytes())
+            .concat(self.time_cost.to_be_bytes().into())
+            .concat(self.parallelism.to_be_bytes().into())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_slice = check_slice_size(input, U12::USIZE, "ksf_parameters")?;
+        let read = |i: usize| {
+            u32::from_be_bytes(
+                GenericArray::<_, U4>::clone_from_slice(&checked_slice[i..i + 4]).into(),
+            )
+        };
+
+        Ok(Self {
+            memory_cost: read(0),
+            time_cost: read(4),
+            parallelism: read(8),
+        })
+    }
 }
 
 /// A no-op hash which simply returns its input
@@ -34,6 +208,44 @@ impl Ksf for Identity {
     }
 }
 
+impl ChunkedKsf for Identity {
+    fn chunks(&self) -> u32 {
+        1
+    }
+
+    fn hash_chunk<L: ArrayLength<u8>>(
+        &self,
+        _: u32,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        Ok(input)
+    }
+}
+
+/// A key stretching function which performs no work, meant only for test
+/// suites and benchmarks
+///
+/// Using it in production removes all protection against offline dictionary
+/// attacks on leaked password files. It is therefore gated behind the
+/// `noop-ksf` feature, which fails to compile in release builds unless the
+/// `danger-insecure` feature is enabled too.
+#[cfg(feature = "noop-ksf")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpKsf;
+
+#[cfg(feature = "noop-ksf")]
+impl Ksf for NoOpKsf {
+    fn hash<L: ArrayLength<u8>>(
+        &self,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        Ok(input)
+    }
+}
+
+/// The `argon2` crate runs all passes in one call, so
+/// [`Ksf::hash_with_progress`] can't cancel it while it hashes, see
+/// [`KsfProgress`]
 #[cfg(feature = "argon2")]
 impl Ksf for argon2::Argon2<'_> {
     fn hash<L: ArrayLength<u8>>(
@@ -45,4 +257,327 @@ impl Ksf for argon2::Argon2<'_> {
             .map_err(|_| InternalError::KsfError)?;
         Ok(output)
     }
+
+    fn parameters(&self) -> KsfParameters {
+        let params = self.params();
+
+        KsfParameters {
+            memory_cost: params.m_cost(),
+            time_cost: params.t_cost(),
+            parallelism: params.p_cost(),
+        }
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl ChunkedKsf for argon2::Argon2<'_> {
+    fn chunks(&self) -> u32 {
+        1
+    }
+
+    fn hash_chunk<L: ArrayLength<u8>>(
+        &self,
+        _: u32,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        self.hash(input)
+    }
+}
+
+/// Argon2 split into a chain of evaluations, for clients in browsers which
+/// can't hash passwords in a Web Worker
+///
+/// Each chunk is a full evaluation of the inner Argon2 on the output of the
+/// previous one, so that
+/// [`ClientLogin::finish_async`](crate::ClientLogin::finish_async)
+/// can give control back to the event loop in between and keep the user
+/// interface responsive. The cost of the chunks adds up: four chunks with a
+/// time cost of one cost as much as a single Argon2 evaluation with a time
+/// cost of four, which is reflected in [`Ksf::parameters`].
+///
+/// With more than one chunk, the output differs from the one of the inner
+/// Argon2, so the same number of chunks must be used for registration and
+/// login. With a single chunk, it is the same as the inner Argon2.
+///
+/// The default uses the default memory cost and parallelism of Argon2id, and
+/// two chunks with a time cost of one.
+#[cfg(feature = "argon2")]
+#[derive(Clone, Debug)]
+pub struct ChunkedArgon2 {
+    argon2: argon2::Argon2<'static>,
+    chunks: u32,
+}
+
+#[cfg(feature = "argon2")]
+impl ChunkedArgon2 {
+    /// Chains `chunks` evaluations of `argon2`
+    ///
+    /// # Errors
+    /// [`InternalError::KsfError`] if `chunks` is zero.
+    pub fn new(argon2: argon2::Argon2<'static>, chunks: u32) -> Result<Self, InternalError> {
+        if chunks == 0 {
+            return Err(InternalError::KsfError);
+        }
+
+        Ok(Self { argon2, chunks })
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl Default for ChunkedArgon2 {
+    fn default() -> Self {
+        let params = argon2::Params::new(
+            argon2::Params::DEFAULT_M_COST,
+            1,
+            argon2::Params::DEFAULT_P_COST,
+            None,
+        )
+        .expect("the default Argon2 parameters are valid");
+
+        Self {
+            argon2: argon2::Argon2::new(
+                argon2::Algorithm::Argon2id,
+                argon2::Version::V0x13,
+                params,
+            ),
+            chunks: argon2::Params::DEFAULT_T_COST,
+        }
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl Ksf for ChunkedArgon2 {
+    fn hash<L: ArrayLength<u8>>(
+        &self,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        (0..self.chunks).try_fold(input, |output, index| self.hash_chunk(index, output))
+    }
+
+    fn parameters(&self) -> KsfParameters {
+        let parameters = self.argon2.parameters();
+
+        KsfParameters {
+            time_cost: parameters.time_cost.saturating_mul(self.chunks),
+            ..parameters
+        }
+    }
+
+    fn hash_with_progress<L: ArrayLength<u8>>(
+        &self,
+        input: GenericArray<u8, L>,
+        progress: &dyn KsfProgress,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        let mut output = input;
+
+        for index in 0..self.chunks {
+            if progress.is_cancelled() {
+                return Err(InternalError::KsfCancelledError);
+            }
+            progress.report(index, self.chunks);
+
+            output = self.hash_chunk(index, output)?;
+        }
+
+        if progress.is_cancelled() {
+            return Err(InternalError::KsfCancelledError);
+        }
+        progress.report(self.chunks, self.chunks);
+
+        Ok(output)
+    }
+}
+
+#[cfg(feature = "argon2")]
+impl ChunkedKsf for ChunkedArgon2 {
+    fn chunks(&self) -> u32 {
+        self.chunks
+    }
+
+    fn hash_chunk<L: ArrayLength<u8>>(
+        &self,
+        _: u32,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        self.argon2.hash(input)
+    }
+}
+
+/// PBKDF2 with HMAC-SHA-256, available with the `pbkdf2` feature
+///
+/// This is the key stretching function approved by FIPS 140-3, see
+/// [`fips`](crate::fips). As PBKDF2 isn't memory-hard, it needs a much higher
+/// iteration count than Argon2 for a comparable protection against offline
+/// dictionary attacks on leaked password files. The default of 600,000
+/// iterations follows the recommendation of OWASP. The iteration count is
+/// reported as the time cost of [`Ksf::parameters`].
+///
+/// The salt is constant, as the input is already the output of the OPRF,
+/// which is unique to the credential.
+#[cfg(feature = "pbkdf2")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pbkdf2 {
+    rounds: u32,
+}
+
+#[cfg(feature = "pbkdf2")]
+impl Pbkdf2 {
+    /// The default iteration count
+    pub const DEFAULT_ROUNDS: u32 = 600_000;
+
+    /// Uses `rounds` iterations
+    ///
+    /// # Errors
+    /// [`InternalError::KsfError`] if `rounds` is zero.
+    pub fn new(rounds: u32) -> Result<Self, InternalError> {
+        if rounds == 0 {
+            return Err(InternalError::KsfError);
+        }
+
+        Ok(Self { rounds })
+    }
+
+    /// Returns the iteration count
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+}
+
+#[cfg(feature = "pbkdf2")]
+impl Default for Pbkdf2 {
+    fn default() -> Self {
+        Self {
+            rounds: Self::DEFAULT_ROUNDS,
+        }
+    }
+}
+
+#[cfg(feature = "pbkdf2")]
+impl Ksf for Pbkdf2 {
+    #[cfg(feature = "fips")]
+    const FIPS_APPROVED: bool = true;
+
+    fn hash<L: ArrayLength<u8>>(
+        &self,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        let mut output = GenericArray::default();
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(&input, &[0; 16], self.rounds, &mut output);
+        Ok(output)
+    }
+
+    fn parameters(&self) -> KsfParameters {
+        KsfParameters {
+            memory_cost: 0,
+            time_cost: self.rounds,
+            parallelism: 1,
+        }
+    }
+}
+
+#[cfg(feature = "pbkdf2")]
+impl ChunkedKsf for Pbkdf2 {
+    fn chunks(&self) -> u32 {
+        1
+    }
+
+    fn hash_chunk<L: ArrayLength<u8>>(
+        &self,
+        _: u32,
+        input: GenericArray<u8, L>,
+    ) -> Result<GenericArray<u8, L>, InternalError> {
+        self.hash(input)
+    }
+}
+
+/// Calibrates the time cost of Argon2id on the current device, so that a
+/// single evaluation with the given memory cost (in KiB) and degree of
+/// parallelism takes approximately `target`
+///
+/// This is meant to be run once per device class, with the resulting
+/// parameters then being used for registration and login. Note that the
+/// returned parameters must stay the same for the lifetime of a registration.
+///
+/// # Errors
+/// [`InternalError::KsfError`] if the memory cost or parallelism are invalid
+/// for Argon2.
+#[cfg(all(feature = "argon2", feature = "std"))]
+pub fn calibrate_argon2(
+    target: std::time::Duration,
+    memory_cost: u32,
+    parallelism: u32,
+) -> Result<argon2::Params, InternalError> {
+    use generic_array::typenum::U64;
+
+    let params = |time_cost| {
+        argon2::Params::new(memory_cost, time_cost, parallelism, None)
+            .map_err(|_| InternalError::KsfError)
+    };
+
+    // Measure a single pass, as the cost of Argon2 grows linearly with the
+    // number of passes
+    let argon2 = argon2::Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        params(1)?,
+    );
+    let start = std::time::Instant::now();
+    argon2.hash(GenericArray::<u8, U64>::default())?;
+    let elapsed = start.elapsed().as_nanos().max(1);
+
+    let time_cost = (target.as_nanos() / elapsed).clamp(1, u32::MAX.into());
+    params(time_cost as u32)
+}
+
+/// Parses an Argon2 configuration from a PHC string, such as
+/// `$argon2id$v=19$m=65536,t=2,p=1`. A salt and hash may be present but are
+/// ignored.
+///
+/// The result can be used as the [`Ksf`] of a cipher suite, or to define a
+/// minimum policy with [`Ksf::parameters`].
+///
+/// # Errors
+/// [`InternalError::KsfError`] if the string is not a valid Argon2 PHC string.
+#[cfg(feature = "password-hash")]
+pub fn argon2_from_phc_string(phc: &str) -> Result<argon2::Argon2<'static>, InternalError> {
+    let hash = argon2::PasswordHash::new(phc).map_err(|_| InternalError::KsfError)?;
+    let algorithm =
+        argon2::Algorithm::try_from(hash.algorithm).map_err(|_| InternalError::KsfError)?;
+    let version = hash
+        .version
+        .map(argon2::Version::try_from)
+        .transpose()
+        .map_err(|_| InternalError::KsfError)?
+        .unwrap_or_default();
+    let params = argon2::Params::try_from(&argon2::PasswordHash {
+        // The output length is fixed by the protocol
+        hash: None,
+        ..hash
+    })
+    .map_err(|_| InternalError::KsfError)?;
+
+    Ok(argon2::Argon2::new(algorithm, version, params))
+}
+
+/// Formats an Argon2 configuration as a PHC string without salt and hash,
+/// such as `$argon2id$v=19$m=65536,t=2,p=1`
+///
+/// # Errors
+/// [`InternalError::KsfError`] if the parameters can not be represented as a
+/// PHC string.
+#[cfg(feature = "password-hash")]
+pub fn argon2_to_phc_string(
+    algorithm: argon2::Algorithm,
+    version: argon2::Version,
+    params: &argon2::Params,
+) -> Result<alloc::string::String, InternalError> {
+    let params = argon2::password_hash::ParamsString::try_from(params)
+        .map_err(|_| InternalError::KsfError)?;
+
+    Ok(alloc::format!(
+        "${}$v={}${}",
+        algorithm.ident(),
+        u32::from(version),
+        params
+    ))
 }
@@ -38,8 +38,12 @@
 //!     type KeGroup = opaque_ke::Ristretto255;
 //!     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //!     type Ksf = opaque_ke::ksf::Identity;
+//!     type AppDataLen = generic_array::typenum::U0;
 //! }
 //! ```
+//! Ready-made configurations can also be found in the `suites` module, which
+//! is available with the `argon2` and `ristretto255-voprf` features.
+//!
 //! See [examples/simple_login.rs](https://github.com/facebook/opaque-ke/blob/main/examples/simple_login.rs)
 //! for a working example of a simple password-based login using OPAQUE.
 //!
@@ -63,6 +67,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -70,11 +75,12 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! use rand::rngs::OsRng;
 //! use rand::RngCore;
 //! let mut rng = OsRng;
-//! let server_setup = ServerSetup::<Default>::new(&mut rng);
+//! let server_setup = ServerSetup::<Default>::new(&mut rng)?;
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //! The server must persist an instance of [`ServerSetup`] for the registration
@@ -120,6 +126,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -127,6 +134,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! use opaque_ke::ClientRegistration;
 //! use rand::rngs::OsRng;
@@ -159,6 +167,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -166,6 +175,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -175,7 +185,7 @@
 //! # )?;
 //! use opaque_ke::ServerRegistration;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! let server_registration_start_result = ServerRegistration::<Default>::start(
 //!     &server_setup,
 //!     client_registration_start_result.message,
@@ -207,6 +217,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -214,6 +225,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -222,7 +234,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
 //!     &mut client_rng,
@@ -255,6 +267,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -262,6 +275,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -270,7 +284,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
 //! let password_file = ServerRegistration::<Default>::finish(
@@ -279,6 +293,50 @@
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
+//! Servers which need to check the [`RegistrationUpload`] before storing it,
+//! e.g. that its [client public key](RegistrationUpload::client_public_key)
+//! is not registered to another account yet, can use
+//! [`ServerRegistration::finish_with_validation`] instead, which returns
+//! [`ProtocolError::RegistrationRejected`](errors::ProtocolError::RegistrationRejected)
+//! if the given check fails. Checks of the public keys alone can also be
+//! applied while deserializing, with
+//! [`RegistrationUpload::deserialize_with_policy`] on the server and
+//! [`RegistrationResponse::deserialize_with_policy`] on the client, which take
+//! a [`KeyValidationPolicy`](keypair::KeyValidationPolicy), e.g. one rejecting
+//! a client key equal to the [server's own](ServerSetup::public_key).
+//!
+//! Password files can also be stored with
+//! [`ServerRegistration::serialize_versioned`], which prefixes them with a
+//! [`RECORD_VERSION`]. [`ServerRegistration::deserialize_versioned`] reads both
+//! formats, along with layouts of the application's own through an upgrade
+//! callback, and reports whether the password file should be written back in
+//! the current format.
+//!
+//! To recognize a [`RegistrationUpload`] which is retried after a timeout,
+//! the server can issue a [`RegistrationToken`](idempotency::RegistrationToken)
+//! with [`ServerSetup::registration_token`] alongside its
+//! [`RegistrationResponse`]. The token is bound to the
+//! [`RegistrationRequest`], which the client echoes together with the token
+//! alongside its upload. [`ServerRegistration::finish_with_token`] then
+//! compares the password file finished from the upload against the token and
+//! password file stored by a previous attempt, so that a retry
+//! doesn't overwrite the record with a conflicting one and an older attempt
+//! can't be replayed over a newer one. See the [`idempotency`] module for
+//! details.
+//!
+//! As evidence against a record being replaced silently later on, the server
+//! can also sign the stored password file with
+//! [`ServerSetup::registration_receipt`]. The client keeps the
+//! [`RegistrationReceipt`](receipt::RegistrationReceipt) and verifies it
+//! against its [`RegistrationUpload`] and the
+//! [`ServerSetup::receipt_public_key`] it pinned, see the [`receipt`] module.
+//!
+//! To let auditors detect records that were substituted without a
+//! registration, [`ServerRegistration::finish_with_record_log`] appends a
+//! commitment to each new password file to a
+//! [`RecordLog`](transparency::RecordLog), e.g. a Merkle tree based
+//! transparency log, see the [`transparency`] module.
+//!
 //! ## Login
 //! The login protocol between a client and server also consists of four steps
 //! along with three messages: [`CredentialRequest`], [`CredentialResponse`],
@@ -308,6 +366,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -315,6 +374,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! use opaque_ke::ClientLogin;
@@ -323,6 +383,16 @@
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
+//! A client which persists the [`ClientLogin`] outside of memory can store it
+//! with [`ClientLogin::serialize_timed`], which records when the login was
+//! started and authenticates it with an HMAC under a state key the client
+//! holds apart from the storage. After restoring the state with
+//! [`ClientLogin::deserialize_timed`], setting
+//! [`ClientLoginFinishParameters::max_state_age`] rejects states which are
+//! older than the given age, so that leftover states are not used after the
+//! login should have been abandoned. The serialized state must still be kept
+//! confidential.
+//!
 //! ### Server Login Start
 //! In the second step of login, the server takes as input a persisted instance
 //! of [`ServerSetup`], the password file output from registration, a
@@ -345,6 +415,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -352,6 +423,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -360,7 +432,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
 //! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
@@ -416,6 +488,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -423,6 +496,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -431,7 +505,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
 //! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
@@ -472,6 +546,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -479,6 +554,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -487,7 +563,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
 //! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
@@ -535,17 +611,33 @@
 //! Upon a successful completion of the OPAQUE protocol (the client runs login
 //! with the same password used during registration), the client and server have
 //! access to a session key, which is a pseudorandomly distributed byte
-//! string (of length equal to the output size of [`voprf::CipherSuite::Hash`])
-//! which only the client and server know. Multiple login runs using the
-//! same password for the same client will produce different session keys,
-//! distributed as uniformly random strings. Thus, the session key can be used
-//! to establish a secure channel between the client and server.
+//! string (by default of length equal to the output size of
+//! [`voprf::CipherSuite::Hash`]) which only the client and server know.
+//! Multiple login runs using the same password for the same client will produce
+//! different session keys, distributed as uniformly random strings. Thus, the
+//! session key can be used to establish a secure channel between the client and
+//! server.
 //!
 //! The session key can be accessed from the `session_key` field of
 //! [`ClientLoginFinishResult`] and [`ServerLoginFinishResult`]. See the
 //! combination of [Client Login Finish](#client-login-finish) and [Server Login
 //! Finish](#server-login-finish) for example usage.
 //!
+//! Applications needing several keys, or keys of a different length, can
+//! derive them from the session key with an [`Exporter`](exporter::Exporter),
+//! which works like the keying material exporter of TLS 1.3: both sides obtain
+//! the same key for the same label and context, and distinct labels yield
+//! independent keys. Labels starting with one of the
+//! [`RESERVED_LABEL_PREFIXES`](exporter::RESERVED_LABEL_PREFIXES) are rejected.
+//!
+//! Cipher suites can instead change the length of the session key itself by
+//! using e.g. `TripleDh<FullMac, RustCrypto, SessionKeyLength<U64>>` as their
+//! [`CipherSuite::KeyExchange`], and the labels the session key and the
+//! handshake secret are derived with by implementing
+//! [`SessionKeyDerivation`](key_exchange::tripledh::SessionKeyDerivation).
+//! Neither changes the messages, but clients and servers must use the same
+//! derivation.
+//!
 //! ## Checking Server Consistency
 //!
 //! A [`ClientLoginFinishResult`] contains the `server_s_pk` field, which is
@@ -554,7 +646,9 @@
 //! of the server it engages with during the login phase. In particular, the
 //! client can check that the static public key of the server supplied during
 //! registration (with the `server_s_pk` field of
-//! [`ClientRegistrationFinishResult`]) matches this field during login.
+//! [`ClientRegistrationFinishResult`]) matches this field during login. On the
+//! server side, the same key is returned by [`ServerSetup::public_key`], so it
+//! can also be distributed to clients out of band for pinning.
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
@@ -569,6 +663,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -576,6 +671,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -584,7 +680,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! // During registration, the client obtains a ClientRegistrationFinishResult with
 //! // a server_s_pk field
@@ -667,6 +763,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -674,6 +771,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -682,7 +780,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! // During registration...
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
@@ -717,6 +815,148 @@
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
+//! Applications which only need the export key, for example to retrieve a
+//! password-protected vault, can stop after [`ClientLogin::finish`]: the client
+//! has then authenticated the server and holds the session key, and simply
+//! never sends the [`CredentialFinalization`]. In this case the server must
+//! not consider the client authenticated, as only [`ServerLogin::finish`]
+//! verifies the client.
+//!
+//! Such applications can also do without the client's static keypair by
+//! setting [`CipherSuite::CLIENT_AUTHENTICATION`] to `false`. Registration
+//! then doesn't derive a client keypair and the key exchange leaves out its
+//! Diffie-Hellman, so [`ServerLogin::finish`] only confirms that the client
+//! derived the same session key. The messages keep their sizes, but logins
+//! aren't compatible with other implementations of the specification, and the
+//! [`ClientRegistrationFinishParameters::client_private_key`],
+//! [`ClientRegistrationFinishParameters::client_public_key`] and
+//! [`ClientLogin::finish_with_secret_key`] can't be used.
+//!
+//! Note that in this mode the server learns nothing about the client: anyone
+//! knowing the server's public key can pass [`ServerLogin::finish`] without
+//! the password, even for an unregistered credential identifier. The
+//! [`ServerLoginFinishResult::client_s_pk`] is then [`None`], and
+//! [`ServerLoginFinishResult::start_migration`] and
+//! [`ServerLoginFinishResult::finish_migration`] fail with
+//! [`ProtocolError::MigrationError`](errors::ProtocolError::MigrationError).
+//!
+//! ## Application Data
+//!
+//! Clients can store a small, fixed-size secret alongside their credentials by
+//! choosing a non-zero [`CipherSuite::AppDataLen`]. The data is supplied
+//! through the `app_data` field of [`ClientRegistrationFinishParameters`],
+//! encrypted under a key derived from the password and authenticated by the
+//! envelope MAC. The server stores it without being able to read it, and the
+//! client recovers it from the `app_data` field of [`ClientLoginFinishResult`]
+//! after a successful login. Cipher suites with an `AppDataLen` of
+//! [`U0`](generic_array::typenum::U0) produce exactly the messages described in
+//! the specification.
+//!
+//! ## MAC Truncation
+//!
+//! Cipher suites can shorten the MACs of the envelope and the key exchange by
+//! using `TripleDh<TruncatedMac>` as their [`CipherSuite::KeyExchange`], which
+//! truncates every MAC to its first 16 bytes. With SHA-512, this saves 48 bytes
+//! each in the envelope and the [`CredentialFinalization`], and 96 bytes in the
+//! [`CredentialResponse`]. Shorter MACs only weaken the resistance against
+//! forgeries, and 16 bytes still leave 128 bits of security; see
+//! [`TruncatedMac`](key_exchange::tripledh::TruncatedMac) for the tradeoffs
+//! and the lower bound. The resulting messages are not part of the current
+//! specification, and clients and servers must use the same MAC length.
+//!
+//! ## External Client Keys
+//!
+//! By default the client's static keypair is derived from the password, so
+//! that the client does not need to store anything. Clients that already hold
+//! a private key, for example one generated in a secure enclave, can instead
+//! have it encrypted inside the envelope, which corresponds to the "external"
+//! envelope mode of earlier drafts of the specification. The key is supplied
+//! through the `client_private_key` field of
+//! [`ClientRegistrationFinishParameters`], and upon login the client has to set
+//! the `envelope_mode` field of [`ClientLoginFinishParameters`] to
+//! [`EnvelopeMode::External`], as the mode is not part of the serialized
+//! envelope.
+//!
+//! The encrypted key occupies the first `SkLen` bytes of the application data,
+//! so [`CipherSuite::AppDataLen`] must be at least the private key length of
+//! the [`KeGroup`](key_exchange::group::KeGroup), and only the remaining bytes
+//! are available for application data. These bytes are zeroed in the `app_data`
+//! field of [`ClientLoginFinishResult`]. The resulting messages are not part of
+//! the current specification.
+//!
+//! ## Credential Retrieval
+//!
+//! Applications which only need password-protected secret retrieval can skip
+//! the key exchange altogether with [`ClientRetrieval`] and
+//! [`ServerRetrieval`], a reduced protocol in the spirit of OPAQUE-Store. It
+//! runs only the OPRF and the envelope recovery against an ordinary password
+//! file, and yields the export key, the server's public key and the
+//! application data. No session key is established and the server does not
+//! learn whether the client knew the password, so a successful retrieval must
+//! not be treated as a login. Note that this is not part of the OPAQUE
+//! specification.
+//! ```
+//! # use opaque_ke::{
+//! #   errors::ProtocolError,
+//! #   ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ServerSetup,
+//! # };
+//! # use opaque_ke::CipherSuite;
+//! # struct Default;
+//! # #[cfg(feature = "ristretto255")]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = opaque_ke::Ristretto255;
+//! #     type KeGroup = opaque_ke::Ristretto255;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # #[cfg(not(feature = "ristretto255"))]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = p256::NistP256;
+//! #     type KeGroup = p256::NistP256;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # use rand::rngs::OsRng;
+//! # let mut client_rng = OsRng;
+//! # let client_registration_start_result = ClientRegistration::<Default>::start(
+//! #     &mut client_rng,
+//! #     b"password",
+//! # )?;
+//! # let mut server_rng = OsRng;
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let client_registration_finish_result = client_registration_start_result.state.finish(
+//! #     &mut client_rng,
+//! #     b"password",
+//! #     server_registration_start_result.message,
+//! #     ClientRegistrationFinishParameters::default()
+//! # )?;
+//! # let password_file = ServerRegistration::<Default>::finish(client_registration_finish_result.message);
+//! use opaque_ke::{ClientRetrieval, ClientRetrievalFinishParameters, ServerRetrieval};
+//!
+//! let client_retrieval_start_result = ClientRetrieval::<Default>::start(&mut client_rng, b"password")?;
+//! let server_retrieval_start_result = ServerRetrieval::start(
+//!     &mut server_rng,
+//!     &server_setup,
+//!     Some(password_file),
+//!     client_retrieval_start_result.message,
+//!     b"alice@example.com",
+//! )?;
+//! let client_retrieval_finish_result = client_retrieval_start_result.state.finish(
+//!     b"password",
+//!     server_retrieval_start_result.message,
+//!     ClientRetrievalFinishParameters::default(),
+//! )?;
+//!
+//! assert_eq!(
+//!     client_registration_finish_result.export_key,
+//!     client_retrieval_finish_result.export_key,
+//! );
+//! # Ok::<(), ProtocolError>(())
+//! ```
+//!
 //! ## Custom Identifiers
 //!
 //! Typically when applications use OPAQUE to authenticate a client to a server,
@@ -748,6 +988,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -755,6 +996,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -763,7 +1005,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
 //!     &mut client_rng,
@@ -796,6 +1038,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -803,6 +1046,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -811,7 +1055,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::new(Identifiers { client: Some(b"Alice_the_Cryptographer"), server: Some(b"Facebook") }, None))?;
 //! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
@@ -834,6 +1078,7 @@
 //!             client: Some(b"Alice_the_Cryptographer"),
 //!             server: Some(b"Facebook"),
 //!         },
+//!         ..ServerLoginStartParameters::default()
 //!     },
 //! )?;
 //! # Ok::<(), ProtocolError>(())
@@ -855,6 +1100,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -862,6 +1108,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -870,7 +1117,7 @@
 //! #     b"password",
 //! # )?;
 //! # let mut server_rng = OsRng;
-//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
+//! # let server_setup = ServerSetup::<Default>::new(&mut server_rng)?;
 //! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, b"password", server_registration_start_result.message, ClientRegistrationFinishParameters::new(Identifiers { client: Some(b"Alice_the_Cryptographer"), server: Some(b"Facebook") }, None))?;
 //! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
@@ -883,7 +1130,7 @@
 //! #     &password_file_bytes,
 //! #   )?;
 //! # let server_login_start_result =
-//! #     ServerLogin::start(&mut server_rng, &server_setup, Some(password_file), client_login_start_result.message, b"alice@example.com", ServerLoginStartParameters { context: None, identifiers: Identifiers { client: Some(b"Alice_the_Cryptographer"), server: Some(b"Facebook") } })?;
+//! #     ServerLogin::start(&mut server_rng, &server_setup, Some(password_file), client_login_start_result.message, b"alice@example.com", ServerLoginStartParameters { context: None, identifiers: Identifiers { client: Some(b"Alice_the_Cryptographer"), server: Some(b"Facebook") }, ..ServerLoginStartParameters::default() })?;
 //! let client_login_finish_result = client_login_start_result.state.finish(
 //!     b"password",
 //!     server_login_start_result.message,
@@ -905,6 +1152,20 @@
 //! Note that if only one of the client and server identifiers are present, then
 //! [Identifiers] can be used to specify them individually.
 //!
+//! The server identifier can also be a certificate, or another attestation of
+//! the server public key, to anchor the server key in an existing PKI. The
+//! client checks it with a
+//! [`ServerIdentityVerifier`](keypair::ServerIdentityVerifier) set as
+//! [`ClientLoginFinishParameters::server_identity_verifier`], which is called
+//! with the server identifier and the server public key once the envelope has
+//! authenticated both, and rejects the login with
+//! [`ProtocolError::ServerIdentityError`](errors::ProtocolError::ServerIdentityError)
+//! if it returns `false`.
+//!
+//! [`ClientLoginFinishResult::server_identity`] tells which
+//! [`ServerIdentity`] opened the envelope, so that clients of multiple
+//! servers can confirm that the login authenticated the server they intended.
+//!
 //! ## Key Exchange Context
 //!
 //! A key exchange protocol typically allows for the specifying of shared
@@ -923,6 +1184,24 @@
 //! `WithContextAndIdentifiers(context,
 //! Identifiers::ClientAndServerIdentifiers(username, server_name))`.
 //!
+//! ## Protocol Version Pinning
+//!
+//! Deployments announcing a protocol version outside of the messages, e.g. in
+//! a header of the transport, can pin it by setting the
+//! `protocol_version_announced` field of [`ServerLoginStartParameters`] and
+//! [`ClientLoginFinishParameters`] on the parties that announce
+//! [`PROTOCOL_VERSION`], and passing the version announced by the peer in
+//! their `peer_protocol_version` field. Each party fails with
+//! [`ProtocolVersionError`](errors::ProtocolError::ProtocolVersionError) as
+//! soon as the peer's version differs from the [`PROTOCOL_VERSION`] of this
+//! crate, and a party that announced its version fails with
+//! [`ProtocolVersionMissing`](errors::ProtocolError::ProtocolVersionMissing)
+//! if the peer didn't announce one, before running the key exchange.
+//! Otherwise it binds [`PROTOCOL_VERSION`] into the context of the key
+//! exchange, so that altered announcements can't make the parties run
+//! different versions without the login failing. The agreed version is
+//! returned in [`ServerLoginStartResult`] and [`ClientLoginFinishResult`].
+//!
 //! ## Dummy Server Login
 //!
 //! For applications in which the server does not wish to reveal to the client
@@ -931,7 +1210,372 @@
 //! client, which is indistinguishable from the normal credential response
 //! message that the server would return for a registered client. The dummy
 //! message is created by passing a `None` to the `password_file` parameter for
-//! [`ServerLogin::start`].
+//! [`ServerLogin::start`]. Applications which look up password files through
+//! their own code paths can instead create the fake password file with
+//! [`ServerRegistration::dummy`] and pass it in like a real one.
+//!
+//! By default, each dummy message is based on a new random fake password file.
+//! A server configured with [`ServerSetup::set_fake_record_secret`] derives
+//! the fake password file from the secret and the credential identifier
+//! instead, so that repeated login attempts for the same unregistered client
+//! are answered based on the same fake password file, also across restarts.
+//!
+//! As answering with a dummy message can take less time than looking up and
+//! answering with a real password file, the processing time of both can be
+//! padded to the same latency with a [`LatencyTarget`](timing::LatencyTarget).
+//!
+//! ## Replay Protection
+//!
+//! A client draws fresh randomness for every credential request, so a server
+//! receiving the same request twice is seeing a replay. Servers which want to
+//! reject these can call [`ServerLogin::start_with_replay_cache`] instead of
+//! [`ServerLogin::start`], which hashes the credential request and returns
+//! [`ProtocolError::ReplayError`](errors::ProtocolError::ReplayError) if the
+//! given [`ReplayCache`](replay::ReplayCache) has seen it before. With the
+//! `std` feature, [`LruReplayCache`](replay::LruReplayCache) keeps a bounded
+//! number of recently seen requests in memory; servers running several
+//! instances can implement the trait on top of shared storage instead.
+//!
+//! ## Login Throttling
+//!
+//! Servers which keep no per-account state can still enforce retry delays
+//! with a [`ThrottleToken`](throttle::ThrottleToken), which records the number
+//! of failed attempts and the time before which the next attempt is rejected.
+//! [`ServerLogin::start_with_throttle`] checks the token echoed by the client
+//! alongside its [`CredentialRequest`] and issues the next one, which the
+//! server sends as an extension alongside the [`CredentialResponse`]. As the
+//! client can leave out its token, the server must set
+//! [`ThrottleParameters::token_required`](throttle::ThrottleParameters::token_required)
+//! for accounts with recent failed attempts. The tokens are advisory only: a
+//! client can replay an older token with fewer failures until it expires after
+//! [`ThrottleParameters::lifetime`](throttle::ThrottleParameters::lifetime).
+//! See the [`throttle`] module for details.
+//!
+//! ## Login Failure Diagnostics
+//!
+//! [`ServerLogin::finish_with_diagnostics`] deserializes the credential
+//! finalization itself and reports why a login failed as a
+//! [`LoginFailure`](errors::LoginFailure) to a callback, for logging by the
+//! server operator, while always returning
+//! [`ProtocolError::InvalidLoginError`](errors::ProtocolError::InvalidLoginError),
+//! which is safe to pass on to the client.
+//!
+//! ## Per-User OPRF Keys
+//!
+//! By default, the OPRF key of each credential is derived from the OPRF seed of
+//! the [`ServerSetup`] and the credential identifier. Servers that want to
+//! rotate or escrow the keys of single accounts can instead register a
+//! credential with its own random key, obtained from
+//! [`ServerRegistration::generate_oprf_key`], through
+//! [`ServerRegistration::start_with_oprf_key`] and
+//! [`ServerRegistration::finish_with_oprf_key`]. The resulting password file
+//! stores the key, and is written with
+//! [`ServerRegistration::serialize_keyed`], which has to be protected like the
+//! [`ServerSetup`], and read back with
+//! [`ServerRegistration::deserialize_keyed`]. [`ServerLogin::start`] then uses the key of the password
+//! file instead of the OPRF seed, while dummy logins for unregistered clients
+//! keep using the OPRF seed. With `serde`, such password files are stored
+//! through `tagged_record`, as the plain serde representation of
+//! [`ServerRegistration`] has no room for the key.
+//!
+//! The `oprf_key` field of [`ServerLoginStartParameters`] instead allows
+//! mixing a pepper into the OPRF key derivation: a secret kept apart from the
+//! [`ServerSetup`], for example in an HSM or KMS.
+//! [`ServerSetup::peppered_oprf_key`] derives the key of a credential from the
+//! OPRF seed, the credential identifier and the pepper, and can be recomputed
+//! upon every registration and login instead of being stored. An attacker who
+//! obtains the credential records and the serialized [`ServerSetup`] then still
+//! lacks the pepper needed for an offline dictionary attack.
+//!
+//! Servers whose OPRF keys never leave a separate service can split
+//! [`ServerLogin::start`] with a [`CredentialResponseBuilder`]: it masks the
+//! password file first, hands out the blinded element to be evaluated by the
+//! service, possibly asynchronously, and completes the key exchange once the
+//! evaluated element is returned. The [`oprf`] module defines the interface of
+//! such a service, so that the OPRF seed never reaches the front-end servers.
+//! The [`two_server`] module goes further and splits the OPRF key of every
+//! credential between the front-end and the OPRF service, so that neither the
+//! credential records with the [`ServerSetup`] of the front-end nor the OPRF
+//! service alone suffice for an offline dictionary attack.
+//!
+//! ## Deterministic Randomness
+//!
+//! Every operation of this crate that requires randomness takes the RNG as an
+//! explicit `rng` parameter implementing [`CryptoRng`](rand::CryptoRng) and
+//! [`RngCore`](rand::RngCore), and draws from no other source. Supplying a
+//! seeded RNG to [`ServerSetup::new`] and to each protocol step therefore
+//! reproduces the exact bytes of every message, which is useful for
+//! integration tests and for comparing runs against other implementations.
+//! ```
+//! # use opaque_ke::{
+//! #   errors::ProtocolError,
+//! #   ClientRegistration, ServerSetup,
+//! # };
+//! # use opaque_ke::CipherSuite;
+//! # struct Default;
+//! # #[cfg(feature = "ristretto255")]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = opaque_ke::Ristretto255;
+//! #     type KeGroup = opaque_ke::Ristretto255;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # #[cfg(not(feature = "ristretto255"))]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = p256::NistP256;
+//! #     type KeGroup = p256::NistP256;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
+//!
+//! let run = |seed| {
+//!     let mut rng = StdRng::from_seed(seed);
+//!     let server_setup = ServerSetup::<Default>::new(&mut rng)?;
+//!     let client_registration_start_result =
+//!         ClientRegistration::<Default>::start(&mut rng, b"password")?;
+//!     Ok::<_, ProtocolError>((
+//!         server_setup.serialize(),
+//!         client_registration_start_result.message.serialize(),
+//!     ))
+//! };
+//!
+//! assert_eq!(run([0; 32])?, run([0; 32])?);
+//! # Ok::<(), ProtocolError>(())
+//! ```
+//!
+//! Note that the seeded RNG must never be used outside of testing, and that
+//! `StdRng` does not guarantee a stable output across `rand` releases, so test
+//! vectors pinned to exact bytes should use an RNG with a specified algorithm
+//! instead.
+//!
+//! Nonces and masking keys are drawn with
+//! [`RngCore::try_fill_bytes`](rand::RngCore::try_fill_bytes), so an RNG
+//! that reports a failure there, for example because a hardware or operating
+//! system source is unavailable, makes the protocol step return
+//! [`ProtocolError::RandomnessError`](errors::ProtocolError::RandomnessError)
+//! instead of panicking. This also applies to the generation of keys, as in
+//! [`ServerSetup::new`], to the OPRF seed and to the OPRF blind.
+//!
+//! Without controlling the RNG, the `envelope-nonce` feature allows specifying
+//! the nonce of the envelope through the `envelope_nonce` field of
+//! [`ClientRegistrationFinishParameters`], so that finishing a
+//! registration again after a network failure produces the same
+//! [`RegistrationUpload`] as the first attempt.
+//!
+//! ## Message Sizes
+//!
+//! The serialized length of every message is fixed by the [`CipherSuite`] and
+//! given as a `typenum` type, such as [`CredentialRequestLen`], which is also
+//! the length of the [`GenericArray`](generic_array::GenericArray) returned by
+//! `serialize`. Every message also provides it as the `const fn`
+//! `serialized_size`, and [`Sizes`] gathers the lengths of all of them, so
+//! that plain arrays can be sized for a concrete cipher suite at compile time,
+//! without spelling out any `typenum` bounds. `serialize_array` then writes a
+//! message into such an array:
+//! ```
+//! # use opaque_ke::{
+//! #   errors::ProtocolError,
+//! #   ClientLogin,
+//! # };
+//! # use opaque_ke::CipherSuite;
+//! # struct Default;
+//! # #[cfg(feature = "ristretto255")]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = opaque_ke::Ristretto255;
+//! #     type KeGroup = opaque_ke::Ristretto255;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # #[cfg(not(feature = "ristretto255"))]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = p256::NistP256;
+//! #     type KeGroup = p256::NistP256;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! use opaque_ke::{CredentialRequest, Sizes};
+//! use rand::rngs::OsRng;
+//!
+//! const CREDENTIAL_REQUEST_LEN: usize = CredentialRequest::<Default>::serialized_size();
+//!
+//! let client_login_start_result = ClientLogin::<Default>::start(&mut OsRng, b"password")?;
+//! let buffer: [u8; CREDENTIAL_REQUEST_LEN] = client_login_start_result.message.serialize_array();
+//! let message = CredentialRequest::<Default>::deserialize(&buffer)?;
+//!
+//! // A single frame size for a transport carrying all messages
+//! let frame = [0; Sizes::<Default>::MAX_MESSAGE_LEN];
+//! # Ok::<(), ProtocolError>(())
+//! ```
+//!
+//! An array of any other length fails the build:
+//! ```compile_fail,E0080
+//! # use opaque_ke::{
+//! #   errors::ProtocolError,
+//! #   ClientLogin,
+//! # };
+//! # use opaque_ke::CipherSuite;
+//! # struct Default;
+//! # #[cfg(feature = "ristretto255")]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = opaque_ke::Ristretto255;
+//! #     type KeGroup = opaque_ke::Ristretto255;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # #[cfg(not(feature = "ristretto255"))]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = p256::NistP256;
+//! #     type KeGroup = p256::NistP256;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! use opaque_ke::CredentialRequest;
+//! use rand::rngs::OsRng;
+//!
+//! const CREDENTIAL_REQUEST_LEN: usize = CredentialRequest::<Default>::serialized_size();
+//!
+//! let client_login_start_result = ClientLogin::<Default>::start(&mut OsRng, b"password")?;
+//! let buffer: [u8; CREDENTIAL_REQUEST_LEN + 1] =
+//!     client_login_start_result.message.serialize_array();
+//! # Ok::<(), ProtocolError>(())
+//! ```
+//!
+//! Internally, lengths stay `typenum` types: array lengths computed from a
+//! generic `CipherSuite` require the unstable `generic_const_exprs` feature,
+//! so only code over a concrete cipher suite can use `serialized_size` as an
+//! array length. Generic code keeps using the `typenum` types for arrays, and
+//! `serialized_size` or the constants of [`Sizes`] for everything else.
+//!
+//! At runtime, every message also reports its length through
+//! `serialized_len`, and [`max_message_sizes`] returns the lengths of all
+//! messages of a cipher suite as plain `usize` values, without requiring any
+//! of the `typenum` bounds. Network layers can use them to preallocate buffers
+//! and to reject oversized input before reading it, with
+//! [`MessageSizes::max`] as a single limit for transports carrying all
+//! messages. The `deserialize` functions themselves first check that the input
+//! has exactly the expected length, and reject it with
+//! [`InternalError::SizeError`](errors::InternalError::SizeError) otherwise,
+//! before decoding any part of it. The expected lengths are computed with
+//! [`checked_sizes`], which fails instead of overflowing for cipher suites with
+//! exotically large parameters. Deserialization never allocates.
+//!
+//! Every message also implements `TryFrom<&[u8]>` with
+//! [`ProtocolError`](errors::ProtocolError) as error, the same as its
+//! `deserialize` function, so that it fits into codecs which are generic over
+//! the decoded type, such as a `tokio_util::codec::Decoder`. In the other
+//! direction, `serialize` returns a fixed-size array which implements
+//! `AsRef<[u8]>` and can be written to the output buffer of an encoder
+//! directly. Messages don't cache their serialized form, so that they don't
+//! keep a second copy of their contents.
+//!
+//! Neither deserialization nor any other public function panics on input
+//! received from the other party or read from storage: malformed, truncated or
+//! extended encodings of every message, state, key and record are reported as
+//! a [`ProtocolError`](errors::ProtocolError), which the test suite checks
+//! for every cipher suite. Bindings over an FFI boundary and servers built with
+//! `panic = "abort"` can therefore pass untrusted bytes straight through.
+//!
+//! ## Protocol Driver
+//!
+//! Instead of calling each protocol step, transports can pass every incoming
+//! frame to a [`ProtocolDriver`](driver::ProtocolDriver), which determines the
+//! type of the message, advances the client or server state, writes the
+//! outgoing frame into a buffer and reports finished flows as an
+//! [`Event`](driver::Event). A frame is a single byte identifying the message
+//! type, followed by the serialized message. The driver uses the default
+//! parameters of each step.
+//! ```
+//! # use opaque_ke::{
+//! #   errors::ProtocolError,
+//! #   ServerSetup,
+//! # };
+//! # use opaque_ke::CipherSuite;
+//! # struct Default;
+//! # #[cfg(feature = "ristretto255")]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = opaque_ke::Ristretto255;
+//! #     type KeGroup = opaque_ke::Ristretto255;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! # #[cfg(not(feature = "ristretto255"))]
+//! # impl CipherSuite for Default {
+//! #     type OprfCs = p256::NistP256;
+//! #     type KeGroup = p256::NistP256;
+//! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+//! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! # }
+//! use opaque_ke::driver::{Event, Message, ProtocolDriver};
+//! use rand::rngs::OsRng;
+//!
+//! let mut rng = OsRng;
+//! let server_setup = ServerSetup::<Default>::new(&mut rng)?;
+//! let mut client_buffer = vec![0; Message::<Default>::max_len()];
+//! let mut server_buffer = vec![0; Message::<Default>::max_len()];
+//!
+//! // Registration
+//! let (mut client, len) =
+//!     ProtocolDriver::<Default>::start_registration(&mut rng, b"password", &mut client_buffer)?;
+//! let mut server = ProtocolDriver::server(&server_setup, b"alice@example.com", None);
+//! let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+//! let step = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer)?;
+//! let step = server.handle(&mut rng, &client_buffer[..step.len], &mut server_buffer)?;
+//! let Some(Event::ServerRegistrationFinished(password_file)) = step.event else {
+//!     unreachable!()
+//! };
+//!
+//! // Login
+//! let (mut client, len) =
+//!     ProtocolDriver::<Default>::start_login(&mut rng, b"password", &mut client_buffer)?;
+//! let mut server =
+//!     ProtocolDriver::server(&server_setup, b"alice@example.com", Some(password_file));
+//! let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+//! let step = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer)?;
+//! let Some(Event::ClientLoginFinished(client_result)) = step.event else {
+//!     unreachable!()
+//! };
+//! let step = server.handle(&mut rng, &client_buffer[..step.len], &mut server_buffer)?;
+//! let Some(Event::ServerLoginFinished(server_result)) = step.event else {
+//!     unreachable!()
+//! };
+//! assert_eq!(client_result.session_key, server_result.session_key);
+//! # Ok::<(), ProtocolError>(())
+//! ```
+//!
+//! ## Thread Safety
+//!
+//! [`ClientRegistration`], [`ServerRegistration`], [`ClientLogin`],
+//! [`ServerLogin`] and [`ServerSetup`] implement [`Clone`], [`Send`] and
+//! [`Sync`] for every cipher suite built from the groups and KSFs provided by
+//! this crate, so protocol states can be held across `.await` points and a
+//! [`ServerSetup`] can be shared between threads. [`ServerSetup`] with a custom
+//! [`SecretKey`](keypair::SecretKey) is only [`Send`] and [`Sync`] if the
+//! secret key is as well.
+//!
+//! ## Stack Usage
+//!
+//! All messages and states are held in fixed-size arrays sized by the
+//! [`CipherSuite`], while identifiers and contexts are borrowed from the
+//! caller, so no protocol step allocates on the heap, except for the KSF, e.g.
+//! the memory of `Argon2`. Callers which have to keep identifiers and contexts
+//! between protocol steps can copy them into the `heapless::Vec`-backed
+//! `HeaplessIdentifiers` and `HeaplessContext` of the `heapless` feature,
+//! instead of allocating. For budgeting RAM on embedded targets, running both
+//! the client and the server side of a complete registration and login on one
+//! thread of an optimized x86-64 build peaks at about 32 KiB of stack with
+//! [`Ristretto255`], 16 KiB with P-256, 32 KiB with P-384 and 40 KiB with
+//! P-521. Unoptimized builds need up to four times as much. These bounds are
+//! checked by the test suite, with some headroom, so regressions are caught.
 //!
 //! ## Remote Private Keys
 //!
@@ -951,6 +1595,7 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for Default {
@@ -958,6 +1603,7 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = opaque_ke::ksf::Identity;
+//! #     type AppDataLen = generic_array::typenum::U0;
 //! # }
 //! # #[derive(Debug)]
 //! # struct YourRemoteKeyError;
@@ -995,11 +1641,38 @@
 //!     }
 //! }
 //!
-//! # let remote_key = YourRemoteKey(<<Default as CipherSuite>::KeGroup>::random_sk(&mut OsRng));
+//! # let remote_key = YourRemoteKey(<<Default as CipherSuite>::KeGroup>::random_sk(&mut OsRng).unwrap());
 //! let keypair = KeyPair::from_private_key(remote_key).unwrap();
-//! let server_setup = ServerSetup::<Default, YourRemoteKey>::new_with_key(&mut OsRng, keypair);
+//! let server_setup = ServerSetup::<Default, YourRemoteKey>::new_with_key(&mut OsRng, keypair).unwrap();
 //! ```
 //!
+//! Servers whose KMS holds a seed rather than the key itself can recreate
+//! their keypair on startup with
+//! [`KeyPair::derive_from_seed`](keypair::KeyPair::derive_from_seed) and pass
+//! it to [`ServerSetup::new_with_key`], so that the private key is never
+//! persisted.
+//!
+//! With the `kms` feature, [`kms::KmsSecretKey`] is a remote key for the NIST
+//! curves, held by a cloud KMS with key agreement such as AWS KMS, which it
+//! reaches through a [`kms::KmsClient`] implemented on top of the SDK of the
+//! KMS. GCP Cloud KMS offers no key agreement for elliptic curve keys, so it
+//! can't back a [`SecretKey`](keypair::SecretKey). Deployments using it can
+//! instead keep the seed for
+//! [`KeyPair::derive_from_seed`](keypair::KeyPair::derive_from_seed) encrypted
+//! under a non-exportable KMS key, and decrypt it on startup.
+//!
+//! Clients can similarly keep their static private key in an OS keystore or a
+//! secure enclave by implementing
+//! [`ClientSecretKey`](keypair::ClientSecretKey), or
+//! [`AsyncClientSecretKey`](keypair::AsyncClientSecretKey) for keystores with
+//! an asynchronous API. The public key of such a key is supplied through the
+//! `client_public_key` field of [`ClientRegistrationFinishParameters`] in place
+//! of deriving the client's keypair from the password, and the login is
+//! finished with [`ClientLogin::finish_with_secret_key`] or
+//! [`ClientLogin::finish_with_async_secret_key`], which only ask the key for a
+//! Diffie-Hellman operation with the server's ephemeral public key after the
+//! envelope has been opened.
+//!
 //! ## Custom KSF and Parameters
 //!
 //! An application might want to use a custom KSF (Key Stretching Function)
@@ -1029,8 +1702,18 @@
 //!
 //! It is also possible to override the default derivation parameters that are
 //! used by the KSF during registration and login. This can be especially
-//! helpful if the `Ksf` trait is already implemented.
+//! helpful if the `Ksf` trait is already implemented. The parameters used
+//! during registration are reported by the client in
+//! [`ClientRegistrationFinishResult::ksf_parameters`]. Cipher suites that set
+//! [`CipherSuite::ATTEST_KSF_PARAMETERS`] also carry them in the envelope
+//! nonce of the [`RegistrationUpload`], under the envelope MAC, which allows
+//! the server to enforce a minimum policy with
+//! [`ServerRegistration::finish_with_ksf_policy`] and to read them back from
+//! the stored record later. The server can't verify that the client hashed
+//! with the parameters it attests, so the policy only guards against
+//! misconfigured clients.
 //! ```
+//! # use opaque_ke::ksf::KsfParameters;
 //! # use opaque_ke::CipherSuite;
 //! # use opaque_ke::ClientRegistration;
 //! # use opaque_ke::ClientRegistrationFinishParameters;
@@ -1048,6 +1731,8 @@
 //! #     type KeGroup = opaque_ke::Ristretto255;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = argon2::Argon2<'static>;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! #     const ATTEST_KSF_PARAMETERS: bool = true;
 //! # }
 //! # #[cfg(not(feature = "ristretto255"))]
 //! # impl CipherSuite for DefaultCipherSuite {
@@ -1055,11 +1740,13 @@
 //! #     type KeGroup = p256::NistP256;
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
 //! #     type Ksf = argon2::Argon2<'static>;
+//! #     type AppDataLen = generic_array::typenum::U0;
+//! #     const ATTEST_KSF_PARAMETERS: bool = true;
 //! # }
 //! #
 //! # let password = b"password";
 //! # let mut rng = OsRng;
-//! # let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng);
+//! # let server_setup = ServerSetup::<DefaultCipherSuite>::new(&mut rng)?;
 //! # let mut client_rng = OsRng;
 //! # let client_registration_start_result =
 //! #     ClientRegistration::<DefaultCipherSuite>::start(&mut client_rng, password)?;
@@ -1093,10 +1780,36 @@
 //!         hash_params,
 //!     )
 //!     .unwrap();
+//!
+//! // The server can reject registrations hardened below its policy
+//! let minimum = KsfParameters {
+//!     memory_cost: 65536,
+//!     time_cost: 2,
+//!     parallelism: 1,
+//! };
+//! let password_file = ServerRegistration::finish_with_ksf_policy(
+//!     client_registration_finish_result.message,
+//!     &minimum,
+//! )?;
+//! assert_eq!(
+//!     password_file.ksf_parameters(),
+//!     Some(client_registration_finish_result.ksf_parameters),
+//! );
 //! # }
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
+//! Clients which can't move the KSF to a separate thread, such as browsers
+//! without Web Workers, block their user interface while hashing the password.
+//! [`ClientRegistration::finish_async`] and [`ClientLogin::finish_async`]
+//! instead compute a [`ChunkedKsf`](ksf::ChunkedKsf) in several chunks and
+//! await a future given by the caller before each chunk, e.g. one resolving on
+//! the next `setTimeout`, so that the event loop can run in between. With the
+//! `argon2` feature, `ksf::ChunkedArgon2` chains several
+//! cheaper evaluations of Argon2 for this purpose. Since its output differs
+//! from the one of a single Argon2 evaluation, it must be used for both
+//! registration and login.
+//!
 //! # Features
 //!
 //! - The `argon2` feature, when enabled, introduces a dependency on `argon2`
@@ -1109,6 +1822,182 @@
 //!   attacks; see [the OPAQUE paper](https://eprint.iacr.org/2018/163.pdf) for
 //!   more details.
 //!
+//! - The `noop-ksf` feature provides `ksf::NoOpKsf`, a key stretching function
+//!   which performs no work, so that test suites and CI of downstream projects
+//!   do not spend their time hashing passwords. Since it removes all protection
+//!   against offline dictionary attacks, it fails to compile in release builds
+//!   unless the `danger-insecure` feature is enabled as well.
+//!
+//! - The `password-hash` feature enables the `argon2` feature and allows Argon2
+//!   configurations to be parsed from and formatted as PHC strings, such as
+//!   `$argon2id$v=19$m=65536,t=2,p=1`, through `ksf::argon2_from_phc_string`
+//!   and `ksf::argon2_to_phc_string`.
+//!
+//! - The `pbkdf2` feature introduces dependencies on `pbkdf2` and `sha2` and
+//!   provides `ksf::Pbkdf2`, PBKDF2-HMAC-SHA256 with a configurable number of
+//!   rounds, for deployments which must use a key stretching function approved
+//!   by FIPS 140-3.
+//!
+//! - The `fips` feature provides the `fips` module and the `P256Sha256Pbkdf2`
+//!   and `P384Sha384Pbkdf2` suites, and `fips::assert_approved` to make the
+//!   build fail for cipher suites built on primitives not approved by FIPS
+//!   140-3. It enables
+//!   `elliptic-curve` and `pbkdf2`. It only restricts the choice of algorithms,
+//!   which are still implemented by the `RustCrypto` crates, so it doesn't make
+//!   a deployment FIPS-compliant by itself.
+//!
+//! - The `ring` feature introduces dependencies on `ring` and `sha2` and
+//!   provides `provider::Ring`, which computes the HMACs and HKDFs of cipher
+//!   suites with SHA-256, SHA-384 or SHA-512 with `ring` instead of the `hmac`
+//!   and `hkdf` crates, when used as the provider of `TripleDh`.
+//!
+//! - The `aws-lc-rs` feature introduces dependencies on `aws-lc-rs` and `sha2`
+//!   and provides `provider::AwsLc`, which does the same with `aws-lc-rs`.
+//!
+//! - The `heapless` feature introduces a dependency on `heapless` and provides
+//!   `HeaplessIdentifiers` and `HeaplessContext`, owned identifiers and
+//!   contexts of bounded length which don't need an allocator, see [Stack
+//!   Usage](#stack-usage).
+//!
+//! - The `bench` feature introduces a dependency on `criterion` and provides
+//!   `bench::bench_suite`, which benchmarks the OPRF, the envelope, the key
+//!   exchange and full round trips of any cipher suite. The `suites` benchmark
+//!   runs it for the suites used in this crate's tests.
+//!
+//! - The `fuzzing` feature provides structured entry points in the `fuzzing`
+//!   module, which check that deserializing arbitrary bytes as a protocol
+//!   message either fails or round-trips, and that live login states reject
+//!   mutated messages, as well as a generator of valid and invalid seed
+//!   messages. The `fuzz` directory contains `cargo-fuzz` targets built on
+//!   them.
+//!
+//! - The `dudect` feature provides `dudect::check_suite`, which tests opening
+//!   envelopes, verifying MACs and answering with real and dummy password files
+//!   for timing leaks with fixed-vs-random statistical tests. It requires
+//!   `std`, and is run in CI by `cargo test --release --features dudect
+//!   test_dudect`.
+//!
+//! - The `ct-audit` feature provides `ct_audit::check_suite`, which runs the
+//!   operations of a cipher suite handling secrets on inputs marked as
+//!   undefined for Valgrind's memcheck, so that it reports any branch or memory
+//!   access depending on them. It is run in CI under Valgrind, with the
+//!   formally verified `fiat` backend of `curve25519-dalek` selected through
+//!   `RUSTFLAGS='--cfg curve25519_dalek_backend="fiat"'`, by `cargo test
+//!   --release --lib --features ct-audit test_ct_audit`.
+//!
+//! - The `test-utils` feature provides helpers in the `test_utils` module for
+//!   testing integrations of this crate, such as checking that a server does
+//!   not reveal whether a credential identifier is registered, or running
+//!   deterministic registrations and logins in-process. It introduces a
+//!   dependency on `rand_chacha`.
+//!
+//! - The `tokio-codec` feature introduces dependencies on `tokio-util` and
+//!   `bytes` and provides `codec::MessageCodec`, an `Encoder` and `Decoder` of
+//!   the protocol messages with length-delimited framing, so that a `Framed`
+//!   transport carries a whole flow. It requires `std` and `server`.
+//!
+//! - The `axum` feature introduces dependencies on `axum-core` and `http` and
+//!   provides the `web` module, whose `Opaque` extractor parses protocol
+//!   messages from request bodies and turns the messages of the server into
+//!   responses, together with the paths of the registration and login endpoints
+//!   most deployments implement. It requires `std` and `server`.
+//!
+//! - The `prost` feature introduces a dependency on `prost` and provides the
+//!   `proto` module, with the messages of the `opaque.v1` package defined in
+//!   `proto/opaque.proto` and conversions from and into the protocol messages,
+//!   for services that exchange them over gRPC. It requires `std`.
+//!
+//! - The `uniffi` feature introduces a dependency on `uniffi` and provides the
+//!   `mobile` module, Kotlin and Swift bindings of the client flows with the
+//!   `Ristretto255Sha512Argon2id` suite, which pass messages and client states
+//!   in serialized form. It requires `std` and enables `argon2` and
+//!   `ristretto255-voprf`.
+//!
+//! - The `napi` feature introduces dependencies on `napi` and `napi-derive` and
+//!   provides the `node` module, Node.js bindings of the client flows with the
+//!   `Ristretto255Sha512Argon2id` suite, which pass messages and client states
+//!   as `Buffer`s. It requires `std` and enables `argon2` and
+//!   `ristretto255-voprf`. As the bindings link against Node.js, it should only
+//!   be enabled for the `cdylib` loaded by Node.js, not for other binaries such
+//!   as the examples.
+//!
+//! - The `python` feature introduces a dependency on `pyo3` and provides the
+//!   `python` module, Python bindings of the server flows and of reading and
+//!   writing password files with the `Ristretto255Sha512Argon2id` suite, for
+//!   migration and audit scripts. It requires `std` and `server` and enables
+//!   `argon2` and `ristretto255-voprf`.
+//!
+//! - The `demo-server` feature builds the `opaque-demo-server` binary, a
+//!   reference credential server on top of `axum` and `sled` which uses dummy
+//!   responses, padded login latencies and throttle tokens, as an executable
+//!   example of the server API and a peer for interoperability tests. It
+//!   enables `axum`.
+//!
+//! - The `oprf-http` feature provides `oprf::HttpOprfEvaluator`, which
+//!   outsources the OPRF evaluation of a login to a separate service over HTTP
+//!   with `hyper`, and `oprf::evaluate_request` to answer such requests. It
+//!   requires `std` and `server` and a Tokio runtime.
+//!
+//! - The `dkg` feature provides the `dkg` module, a FROST-style distributed key
+//!   generation of the static key of the server, and `dkg::ThresholdSecretKey`,
+//!   which checks the proofs of the Diffie-Hellman shares of a threshold of
+//!   its holders and combines them during [`ServerLogin::start`]. It requires a
+//!   [`KeGroup`](key_exchange::group::KeGroup) supporting point addition, i.e.
+//!   Ristretto255 or an elliptic curve, and enables `server` and `std`.
+//!
+//! - The `randomized-password` feature adds a `randomized_pwd` field to the
+//!   results of [`ClientRegistration::finish`], [`ClientLogin::finish`] and
+//!   [`ClientRetrieval::finish`], holding the OPRF output hardened by the key
+//!   stretching function, for applications which derive their own key
+//!   hierarchies from it. Every other client secret is derived from this value,
+//!   so it must be handled with at least the same care as the password.
+//!
+//! - The `envelope-nonce` feature adds an `envelope_nonce` field to
+//!   [`ClientRegistrationFinishParameters`], making the [`RegistrationUpload`]
+//!   reproducible for a given password and server, for deterministic test
+//!   vectors and idempotent retried registrations.
+//!
+//! - The `key-schedule-secrets` feature adds the handshake secret and the MAC
+//!   key of the respective side of the 3DH key schedule to the results of
+//!   [`ClientLogin::finish`] and [`ServerLogin::start`], for applications
+//!   implementing extensions of the key exchange, such as channel binding
+//!   tokens. These are as sensitive as the session key.
+//!
+//! - The `keystore` feature provides the [`keystore`] module, which encrypts a
+//!   serialized [`ServerSetup`] under a key derived from a passphrase with the
+//!   KSF of the cipher suite, for small deployments which keep the keys of the
+//!   server on disk instead of in an HSM.
+//!
+//! - The `pkcs8` feature allows importing and exporting keys of Curve25519 and
+//!   of elliptic curves with a standardized OID, such as [`p256::NistP256`], as
+//!   DER- or PEM-encoded SPKI and PKCS#8 documents, e.g. with
+//!   `PublicKey::from_spki_der` and `PrivateKey::to_pkcs8_pem`, so that server
+//!   keys can be generated and stored with standard tooling. Encoders write
+//!   into a buffer given by the caller.
+//!
+//! - The `kms` feature provides [`kms::KmsSecretKey`], a
+//!   [`SecretKey`](keypair::SecretKey) for the NIST curves that keeps the
+//!   static key of the server in a cloud KMS, such as AWS KMS, and derives the
+//!   Diffie-Hellman outputs with its key agreement. It requires `std`.
+//!
+//! - The `pkcs11` feature provides [`pkcs11::Pkcs11SecretKey`], a
+//!   [`SecretKey`](keypair::SecretKey) for Curve25519 that keeps the static key
+//!   of the server on a PKCS#11 token, such as an HSM, and performs the
+//!   Diffie-Hellman operations there. It requires `std`.
+//!
+//! - The `server` feature, enabled by default, provides the server side of the
+//!   protocol, i.e. [`ServerSetup`], [`ServerRegistration`], [`ServerLogin`]
+//!   and [`ServerRetrieval`], together with the server-only modules such as
+//!   [`driver`], [`replay`] and [`throttle`]. Clients built without it only
+//!   contain the code of the client flow.
+//!
+//! - The `wasm-client` feature enables the `ristretto255-voprf` and `argon2`
+//!   features needed by a typical client, and is meant to be used with
+//!   `default-features = false`, so that a WebAssembly bundle contains neither
+//!   the server side nor `serde`. The `wasm-client` directory contains a
+//!   `cdylib` built with it and a size-optimized release profile, whose size is
+//!   checked against a threshold in CI by `scripts/wasm_size.sh`.
+//!
 //! - The `serde` feature, enabled by default, provides convenience functions for serializing and deserializing with [serde](https://serde.rs/).
 //!
 //! - The `ristretto255` feature enables using [`Ristretto255`] as a `KeGroup`
@@ -1118,14 +2007,34 @@
 //! - The `curve25519` feature enables Curve25519 as a `KeGroup`. To select a
 //!   specific backend see the [curve25519-dalek] documentation.
 //!
-//! - The `p256` feature enables the use of [`p256::NistP256`] as a `KeGroup`
-//!   and a `OprfCs` for `CipherSuite`.
-//!
-//! - The `bench` feature is used only for running performance benchmarks for
-//!   this implementation.
+//! - The `precomputed-tables` feature enables the precomputed basepoint tables
+//!   of `curve25519-dalek`, which speed up generating keys with
+//!   [`Ristretto255`] and Curve25519 at the cost of about 30 KiB of static data
+//!   per group.
+//!
+//!   The arithmetic backend of `curve25519-dalek` is not a crate feature,
+//!   but selected for the whole build by setting
+//!   `--cfg curve25519_dalek_backend="..."` in `RUSTFLAGS`. By default, x86-64
+//!   targets detect AVX2 and AVX-512 at runtime and use the fastest SIMD
+//!   backend available, while `"serial"` forces the portable 64-bit
+//!   implementation, and `"fiat"` uses the formally verified field arithmetic
+//!   of fiat-crypto, which is slower but easier to audit. All backends are
+//!   constant-time.
+//!
+//! - The `elliptic-curve` feature implements `KeGroup` for the curves
+//!   implementing the traits of the
+//!   [`elliptic-curve`](https://docs.rs/elliptic-curve) crate, such as
+//!   [`p256::NistP256`], `p384::NistP384` and `p521::NistP521`. These curves
+//!   are usable as `OprfCs` without it.
+//!
+//! Every group and KSF is behind its own feature, and the hash function is the
+//! one of the selected `OprfCs`, so that a client using only [`Ristretto255`],
+//! SHA-512 and Argon2 can be built with `default-features = false` and the
+//! `wasm-client` feature, without the code of any other group or KSF, nor of
+//! the server.
 //!
 //! [curve25519-dalek]:
-//!     (https://docs.rs/curve25519-dalek/4.0.0-pre.5/curve25519_dalek/index.html#backends)
+//!     (https://docs.rs/curve25519-dalek/4/curve25519_dalek/index.html#backends)
 //! [`p256::NistP256`]: https://docs.rs/p256/latest/p256/struct.NistP256.html
 
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
@@ -1134,44 +2043,137 @@
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 #![allow(type_alias_bounds)]
 
+#[cfg(all(
+    feature = "noop-ksf",
+    not(debug_assertions),
+    not(feature = "danger-insecure")
+))]
+compile_error!(
+    "The `noop-ksf` feature disables password hardening and is only meant for tests and \
+     benchmarks. Enable the `danger-insecure` feature to use it in release builds."
+);
+
+#[cfg(feature = "password-hash")]
+extern crate alloc;
 #[cfg(any(feature = "std", test))]
 extern crate std;
 
 // Error types
 pub mod errors;
 
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod ciphersuite;
+#[cfg(feature = "tokio-codec")]
+pub mod codec;
+#[cfg(feature = "ct-audit")]
+pub mod ct_audit;
+#[cfg(feature = "dkg")]
+pub mod dkg;
+#[cfg(feature = "server")]
+pub mod driver;
+#[cfg(feature = "dudect")]
+pub mod dudect;
 mod envelope;
+pub mod exporter;
+#[cfg(feature = "fips")]
+pub mod fips;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod hash;
+#[cfg(feature = "server")]
+pub mod idempotency;
 pub mod key_exchange;
 pub mod keypair;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+#[cfg(feature = "kms")]
+pub mod kms;
 pub mod ksf;
 mod messages;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+// The bindings look up the symbols of Node.js when a binary starts, which
+// fails outside of it and is reported by debug builds
+#[cfg(all(feature = "napi", not(test)))]
+pub mod node;
 mod opaque;
+#[cfg(feature = "server")]
+pub mod oprf;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+#[cfg(feature = "prost")]
+pub mod proto;
+pub mod provider;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod receipt;
+#[cfg(feature = "server")]
+pub mod replay;
 mod serialization;
+#[cfg(any(
+    all(feature = "argon2", feature = "ristretto255-voprf"),
+    feature = "fips"
+))]
+pub mod suites;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "server")]
+pub mod throttle;
+#[cfg(feature = "server")]
+pub mod timing;
+#[cfg(feature = "server")]
+pub mod transparency;
+#[cfg(feature = "server")]
+pub mod two_server;
 mod util;
+#[cfg(feature = "axum")]
+pub mod web;
 
 #[cfg(test)]
 mod tests;
 
+// Only used by the `opaque-demo-server` binary
+#[cfg(feature = "demo-server")]
+use {axum as _, sled as _, tokio as _};
+
+// The scaffolding of the `mobile` bindings has to be at the root of the crate
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!("opaque_ke");
+
 // Exports
 
 pub use ciphersuite::CipherSuite;
 pub use rand;
 
+pub use crate::envelope::EnvelopeMode;
 #[cfg(feature = "curve25519")]
 pub use crate::key_exchange::group::curve25519::Curve25519;
 #[cfg(feature = "ristretto255")]
 pub use crate::key_exchange::group::ristretto255::Ristretto255;
 pub use crate::messages::{
-    CredentialFinalization, CredentialFinalizationLen, CredentialRequest, CredentialRequestLen,
-    CredentialResponse, CredentialResponseLen, RegistrationRequest, RegistrationRequestLen,
-    RegistrationResponse, RegistrationResponseLen, RegistrationUpload, RegistrationUploadLen,
+    checked_sizes, max_message_sizes, CredentialFinalization, CredentialFinalizationLen,
+    CredentialRequest, CredentialRequestLen, CredentialResponse, CredentialResponseLen,
+    MessageSizes, RegistrationRequest, RegistrationRequestLen, RegistrationResponse,
+    RegistrationResponseLen, RegistrationUpload, RegistrationUploadLen, RetrievalRequest,
+    RetrievalRequestLen, RetrievalResponse, RetrievalResponseLen, Sizes,
 };
+#[cfg(all(feature = "serde", feature = "server"))]
+pub use crate::opaque::tagged_record;
+#[cfg(feature = "heapless")]
+pub use crate::opaque::{heapless_context, HeaplessContext, HeaplessIdentifiers};
 pub use crate::opaque::{
     ClientLogin, ClientLoginFinishParameters, ClientLoginFinishResult, ClientLoginStartResult,
-    ClientRegistration, ClientRegistrationFinishParameters, ClientRegistrationFinishResult,
-    ClientRegistrationStartResult, Identifiers, ServerLogin, ServerLoginFinishResult,
-    ServerLoginStartParameters, ServerLoginStartResult, ServerRegistration, ServerRegistrationLen,
-    ServerRegistrationStartResult, ServerSetup,
+    ClientMigrationStartResult, ClientRegistration, ClientRegistrationFinishParameters,
+    ClientRegistrationFinishResult, ClientRegistrationStartResult, ClientRetrieval,
+    ClientRetrievalFinishParameters, ClientRetrievalFinishResult, ClientRetrievalStartResult,
+    Identifiers, PreparedClientLogin, ServerIdentity, TimedClientLoginLen, PROTOCOL_VERSION,
+};
+#[cfg(feature = "server")]
+pub use crate::opaque::{
+    CredentialResponseBuilder, KeyedServerRegistrationLen, PreparedServerLogin, RecordBatch,
+    ServerLogin, ServerLoginFinishResult, ServerLoginStartParameters, ServerLoginStartResult,
+    ServerRegistration, ServerRegistrationLen, ServerRegistrationReadResult,
+    ServerRegistrationStartResult, ServerRetrieval, ServerRetrievalStartResult, ServerSetup,
+    VersionedServerRegistrationLen, KEYED_RECORD_VERSION, RECORD_VERSION,
 };
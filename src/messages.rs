@@ -8,30 +8,52 @@
 
 //! Contains the messages used for OPAQUE
 
+use core::marker::PhantomData;
 use core::ops::Add;
 
 use derive_where::derive_where;
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::{Output, OutputSizeUser};
 use generic_array::sequence::Concat;
+#[cfg(feature = "server")]
+use generic_array::typenum::U2;
 use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
 use generic_array::{ArrayLength, GenericArray};
+#[cfg(feature = "server")]
 use rand::{CryptoRng, RngCore};
 use subtle::ConstantTimeEq;
 use voprf::Group;
 
 use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
 use crate::envelope::{Envelope, EnvelopeLen};
-use crate::errors::utils::{check_slice_size, check_slice_size_atleast};
-use crate::errors::ProtocolError;
+use crate::errors::utils::check_slice_size;
+use crate::errors::{InternalError, ProtocolError};
 use crate::hash::{Hash, OutputSize, ProxyHash};
 use crate::key_exchange::group::KeGroup;
+#[cfg(feature = "server")]
+use crate::key_exchange::traits::SuiteProvider;
 use crate::key_exchange::traits::{
-    Deserialize, Ke1MessageLen, Ke2MessageLen, Ke3MessageLen, KeyExchange, Serialize,
+    Deserialize, Ke1MessageLen, Ke2MessageLen, Ke3MessageLen, KeyExchange, MacLen, Serialize,
 };
 use crate::key_exchange::tripledh::NonceLen;
-use crate::keypair::{PublicKey, SecretKey};
-use crate::opaque::{MaskedResponse, MaskedResponseLen, ServerSetup};
+#[cfg(feature = "server")]
+use crate::keypair::{KeyPair, SecretKey};
+use crate::keypair::{KeyValidationPolicy, PublicKey};
+use crate::ksf::KsfParameters;
+#[cfg(feature = "server")]
+use crate::opaque::ServerSetup;
+use crate::opaque::{MaskedResponse, MaskedResponseLen};
+#[cfg(feature = "server")]
+use crate::provider::Provider;
+#[cfg(feature = "server")]
+use crate::serialization::i2osp;
+#[cfg(feature = "server")]
+use crate::util::fill_random;
+
+#[cfg(feature = "server")]
+static STR_FAKE_MASKING_KEY: &[u8] = b"FakeMaskingKey";
+#[cfg(feature = "server")]
+static STR_FAKE_PRIVATE_KEY: &[u8] = b"FakePrivateKey";
 
 ////////////////////////////
 // High-level API Structs //
@@ -203,6 +225,52 @@ where
     pub(crate) ke3_message: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE3Message,
 }
 
+/// The message sent by the client to the server, to initiate a credential
+/// retrieval
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(bound = "")
+)]
+#[derive_where(Clone)]
+#[derive_where(Debug, Eq, Hash, Ord, PartialEq, PartialOrd; voprf::BlindedElement<CS::OprfCs>)]
+pub struct RetrievalRequest<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// blinded password information
+    pub(crate) blinded_element: voprf::BlindedElement<CS::OprfCs>,
+}
+
+/// The answer sent by the server to the user, upon reception of the
+/// credential retrieval attempt
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(bound = "")
+)]
+#[derive_where(Clone)]
+#[derive_where(Debug, Eq, Hash, PartialEq; voprf::EvaluationElement<CS::OprfCs>)]
+pub struct RetrievalResponse<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// the server's oprf output
+    pub(crate) evaluation_element: voprf::EvaluationElement<CS::OprfCs>,
+    pub(crate) masking_nonce: GenericArray<u8, NonceLen>,
+    pub(crate) masked_response: MaskedResponse<CS>,
+}
+
 ////////////////////////////////
 // High-level Implementations //
 // ========================== //
@@ -226,15 +294,38 @@ where
         self.blinded_element.clone()
     }
 
+    /// Length of the serialized message in bytes, see
+    /// [`RegistrationRequestLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::REGISTRATION_REQUEST_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, RegistrationRequestLen<CS>> {
         <OprfGroup<CS> as Group>::serialize_elem(self.blinded_element.value())
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N] {
+        into_array(self.serialize())
+    }
+
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let checked_slice = check_slice_size(input, elem_len, "registration_request_bytes")?;
+
         Ok(Self {
-            blinded_element: voprf::BlindedElement::deserialize(input)?,
+            blinded_element: voprf::BlindedElement::deserialize(checked_slice)?,
         })
     }
 }
@@ -252,6 +343,19 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Length of the serialized message in bytes, see
+    /// [`RegistrationResponseLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::REGISTRATION_RESPONSE_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, RegistrationResponseLen<CS>>
     where
@@ -263,6 +367,18 @@ where
             .concat(self.server_s_pk.serialize())
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N]
+    where
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+    {
+        into_array(self.serialize())
+    }
+
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
@@ -279,6 +395,24 @@ where
         })
     }
 
+    /// Like [`RegistrationResponse::deserialize`], but also checks the public
+    /// key of the server against `policy`
+    ///
+    /// # Errors
+    /// [`ProtocolError::KeyValidationError`] if `policy` rejects the key
+    pub fn deserialize_with_policy(
+        input: &[u8],
+        policy: &impl KeyValidationPolicy<CS::KeGroup>,
+    ) -> Result<Self, ProtocolError> {
+        let message = Self::deserialize(input)?;
+
+        if !policy.validate(&message.server_s_pk) {
+            return Err(ProtocolError::KeyValidationError);
+        }
+
+        Ok(message)
+    }
+
     #[cfg(test)]
     /// Only used for tests, where we can set the beta value to test for the
     /// reflection error case
@@ -303,11 +437,36 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Length of the serialized message in bytes, see [`RegistrationUploadLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Returns the client's static public key
+    pub fn client_public_key(&self) -> &PublicKey<CS::KeGroup> {
+        &self.client_s_pk
+    }
+
+    /// Returns the key stretching function parameters attested by the client,
+    /// or `None` if the suite does not set
+    /// [`CipherSuite::ATTEST_KSF_PARAMETERS`]
+    pub fn ksf_parameters(&self) -> Option<KsfParameters> {
+        self.envelope.ksf_parameters()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::REGISTRATION_UPLOAD_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, RegistrationUploadLen<CS>>
     where
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -321,12 +480,33 @@ where
             .concat(self.envelope.serialize())
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N]
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        into_array(self.serialize())
+    }
+
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let key_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
         let hash_len = OutputSize::<OprfHash<CS>>::USIZE;
-        let checked_slice =
-            check_slice_size_atleast(input, key_len + hash_len, "registration_upload_bytes")?;
+        let checked_slice = check_slice_size(
+            input,
+            checked_sizes::<CS>()?.registration_upload,
+            "registration_upload_bytes",
+        )?;
         let envelope = Envelope::<CS>::deserialize(&checked_slice[key_len + hash_len..])?;
         Ok(Self {
             envelope,
@@ -337,19 +517,78 @@ where
         })
     }
 
+    /// Like [`RegistrationUpload::deserialize`], but also checks the public
+    /// key of the client against `policy`
+    ///
+    /// # Errors
+    /// [`ProtocolError::KeyValidationError`] if `policy` rejects the key
+    pub fn deserialize_with_policy(
+        input: &[u8],
+        policy: &impl KeyValidationPolicy<CS::KeGroup>,
+    ) -> Result<Self, ProtocolError> {
+        let message = Self::deserialize(input)?;
+
+        if !policy.validate(&message.client_s_pk) {
+            return Err(ProtocolError::KeyValidationError);
+        }
+
+        Ok(message)
+    }
+
     // Creates a dummy instance used for faking a [CredentialResponse]
+    #[cfg(feature = "server")]
     pub(crate) fn dummy<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
         rng: &mut R,
         server_setup: &ServerSetup<CS, S>,
-    ) -> Self {
+    ) -> Result<Self, ProtocolError> {
         let mut masking_key = Output::<OprfHash<CS>>::default();
-        rng.fill_bytes(&mut masking_key);
+        fill_random(rng, &mut masking_key)?;
 
-        Self {
+        Ok(Self {
             envelope: Envelope::<CS>::dummy(),
             masking_key,
             client_s_pk: server_setup.fake_keypair.public().clone(),
-        }
+        })
+    }
+
+    // Creates a dummy instance derived from the credential identifier, see
+    // [ServerSetup::set_fake_record_secret]
+    #[cfg(feature = "server")]
+    pub(crate) fn derived_dummy(
+        fake_record_prk: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+    ) -> Result<Self, ProtocolError> {
+        let hkdf = SuiteProvider::<CS>::from_prk(fake_record_prk)?;
+        // The identifier is length-prefixed, so that it can't run into the
+        // label
+        let identifier_len = i2osp::<U2>(credential_identifier.len())?;
+
+        let mut masking_key = Output::<OprfHash<CS>>::default();
+        SuiteProvider::<CS>::expand(
+            &hkdf,
+            &[&identifier_len, credential_identifier, STR_FAKE_MASKING_KEY],
+            &mut masking_key,
+        )?;
+
+        let mut keypair_seed = GenericArray::<_, <CS::KeGroup as KeGroup>::SkLen>::default();
+        SuiteProvider::<CS>::expand(
+            &hkdf,
+            &[&identifier_len, credential_identifier, STR_FAKE_PRIVATE_KEY],
+            &mut keypair_seed,
+        )?;
+        let client_s_keypair = KeyPair::<CS::KeGroup>::from_private_key_slice(
+            &CS::KeGroup::serialize_sk(CS::KeGroup::derive_auth_keypair_with::<CS::OprfCs>(
+                keypair_seed,
+                CS::AUTH_KEY_PAIR_INFO,
+                CS::AUTH_KEY_PAIR_DST,
+            )?),
+        )?;
+
+        Ok(Self {
+            envelope: Envelope::<CS>::dummy(),
+            masking_key,
+            client_s_pk: client_s_keypair.public().clone(),
+        })
     }
 }
 
@@ -366,6 +605,18 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Length of the serialized message in bytes, see [`CredentialRequestLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::CREDENTIAL_REQUEST_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, CredentialRequestLen<CS>>
     where
@@ -377,6 +628,18 @@ where
             .concat(self.ke1_message.serialize())
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N]
+    where
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+    {
+        into_array(self.serialize())
+    }
+
     pub(crate) fn serialize_iter<'a>(
         blinded_element: &'a GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>,
         ke1_message: &'a GenericArray<u8, Ke1MessageLen<CS>>,
@@ -388,7 +651,8 @@ where
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
 
-        let checked_slice = check_slice_size_atleast(input, elem_len, "login_first_message_bytes")?;
+        let checked_slice =
+            check_slice_size(input, Self::serialized_size(), "login_first_message_bytes")?;
 
         // Check that the message is actually containing an element of the correct
         // subgroup
@@ -434,6 +698,18 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Length of the serialized message in bytes, see [`CredentialResponseLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::CREDENTIAL_RESPONSE_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, CredentialResponseLen<CS>>
     where
@@ -442,10 +718,10 @@ where
         Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
             ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
         CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
         // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
         CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
@@ -457,6 +733,28 @@ where
             .concat(self.ke2_message.serialize())
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N]
+    where
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        into_array(self.serialize())
+    }
+
     pub(crate) fn serialize_without_ke<'a>(
         beta: &'a GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>,
         masking_nonce: &'a GenericArray<u8, NonceLen>,
@@ -470,15 +768,13 @@ where
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
-        let key_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
         let nonce_len = NonceLen::USIZE;
-        let envelope_len = Envelope::<CS>::len();
-        let masked_response_len = key_len + envelope_len;
-        let ke2_message_len = Ke2MessageLen::<CS>::USIZE;
+        let sizes = checked_sizes::<CS>()?;
+        let masked_response_len = sizes.masked_response;
 
-        let checked_slice = check_slice_size_atleast(
+        let checked_slice = check_slice_size(
             input,
-            elem_len + nonce_len + masked_response_len + ke2_message_len,
+            sizes.credential_response,
             "credential_response_bytes",
         )?;
 
@@ -497,7 +793,7 @@ where
             GenericArray::clone_from_slice(&checked_slice[elem_len..elem_len + nonce_len]);
         let masked_response = MaskedResponse::deserialize(
             &checked_slice[elem_len + nonce_len..elem_len + nonce_len + masked_response_len],
-        );
+        )?;
         let ke2_message =
             <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2Message::deserialize(
                 &checked_slice[elem_len + nonce_len + masked_response_len..],
@@ -536,11 +832,31 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Length of the serialized message in bytes, see
+    /// [`CredentialFinalizationLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::CREDENTIAL_FINALIZATION_LEN
+    }
+
     /// Serialization into bytes
     pub fn serialize(&self) -> GenericArray<u8, CredentialFinalizationLen<CS>> {
         self.ke3_message.serialize()
     }
 
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N] {
+        into_array(self.serialize())
+    }
+
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let ke3_message =
@@ -550,3 +866,509 @@ where
         Ok(Self { ke3_message })
     }
 }
+
+/// Length of [`RetrievalRequest`] in bytes for serialization.
+pub type RetrievalRequestLen<CS: CipherSuite> = <OprfGroup<CS> as Group>::ElemLen;
+
+impl<CS: CipherSuite> RetrievalRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Length of the serialized message in bytes, see [`RetrievalRequestLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::RETRIEVAL_REQUEST_LEN
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, RetrievalRequestLen<CS>> {
+        <OprfGroup<CS> as Group>::serialize_elem(self.blinded_element.value())
+    }
+
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N] {
+        into_array(self.serialize())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let checked_slice = check_slice_size(input, elem_len, "retrieval_request_bytes")?;
+
+        Ok(Self {
+            blinded_element: voprf::BlindedElement::deserialize(checked_slice)?,
+        })
+    }
+}
+
+/// Length of [`RetrievalResponse`] in bytes for serialization.
+pub type RetrievalResponseLen<CS: CipherSuite> = CredentialResponseWithoutKeLen<CS>;
+
+impl<CS: CipherSuite> RetrievalResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Length of the serialized message in bytes, see [`RetrievalResponseLen`]
+    pub fn serialized_len(&self) -> usize {
+        Self::serialized_size()
+    }
+
+    /// Length of the serialized message in bytes, usable in constant
+    /// expressions, e.g. to size the array of
+    /// [`serialize_array`](Self::serialize_array)
+    pub const fn serialized_size() -> usize {
+        Sizes::<CS>::RETRIEVAL_RESPONSE_LEN
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, RetrievalResponseLen<CS>>
+    where
+        // RetrievalResponse: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        <OprfGroup<CS> as Group>::serialize_elem(self.evaluation_element.value())
+            .concat(self.masking_nonce)
+            .concat(self.masked_response.serialize())
+    }
+
+    /// Serialization into an array of
+    /// [`serialized_size`](Self::serialized_size) bytes, where a length `N`
+    /// that doesn't match fails the build
+    pub fn serialize_array<const N: usize>(&self) -> [u8; N]
+    where
+        // RetrievalResponse: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        into_array(self.serialize())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let nonce_len = NonceLen::USIZE;
+
+        let checked_slice = check_slice_size(
+            input,
+            checked_sizes::<CS>()?.retrieval_response,
+            "retrieval_response_bytes",
+        )?;
+
+        // Check that the message is actually containing an element of the correct
+        // subgroup
+        let beta_bytes = &checked_slice[..elem_len];
+        let evaluation_element = voprf::EvaluationElement::<CS::OprfCs>::deserialize(beta_bytes)?;
+
+        // Throw an error if the identity group element is encountered
+        if bool::from(<OprfGroup<CS> as Group>::identity_elem().ct_eq(&evaluation_element.value()))
+        {
+            return Err(ProtocolError::IdentityGroupElementError);
+        }
+
+        let masking_nonce =
+            GenericArray::clone_from_slice(&checked_slice[elem_len..elem_len + nonce_len]);
+        let masked_response = MaskedResponse::deserialize(&checked_slice[elem_len + nonce_len..])?;
+
+        Ok(Self {
+            evaluation_element,
+            masking_nonce,
+            masked_response,
+        })
+    }
+}
+
+// Conversions from bytes, same as `deserialize`, for generic codecs
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for RegistrationRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for RegistrationResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for RegistrationUpload<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for CredentialRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for CredentialResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for CredentialFinalization<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for RetrievalRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<&[u8]> for RetrievalResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(input: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(input)
+    }
+}
+
+/// The serialized lengths in bytes of all messages of a [`CipherSuite`], as
+/// returned by [`max_message_sizes`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MessageSizes {
+    /// Length of [`RegistrationRequest`]
+    pub registration_request: usize,
+    /// Length of [`RegistrationResponse`]
+    pub registration_response: usize,
+    /// Length of [`RegistrationUpload`], which is also the length of a
+    /// serialized [`ServerRegistration`](crate::ServerRegistration)
+    pub registration_upload: usize,
+    /// Length of [`CredentialRequest`]
+    pub credential_request: usize,
+    /// Length of [`CredentialResponse`]
+    pub credential_response: usize,
+    /// Length of [`CredentialFinalization`]
+    pub credential_finalization: usize,
+    /// Length of [`RetrievalRequest`]
+    pub retrieval_request: usize,
+    /// Length of [`RetrievalResponse`]
+    pub retrieval_response: usize,
+    /// Length of the envelope contained in a [`RegistrationUpload`]
+    pub envelope: usize,
+    /// Length of the masked response contained in a [`CredentialResponse`]
+    /// and a [`RetrievalResponse`]
+    pub masked_response: usize,
+}
+
+impl MessageSizes {
+    /// The length of the largest message, suitable as a single read limit for
+    /// a transport carrying all messages
+    pub fn max(&self) -> usize {
+        [
+            self.registration_request,
+            self.registration_response,
+            self.registration_upload,
+            self.credential_request,
+            self.credential_response,
+            self.credential_finalization,
+            self.retrieval_request,
+            self.retrieval_response,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default()
+    }
+}
+
+/// The serialized lengths in bytes of all messages of a [`CipherSuite`] as
+/// constants, e.g. for sizing buffers of a concrete cipher suite at compile
+/// time
+///
+/// These are the `USIZE` constants of the corresponding `typenum` lengths, such
+/// as [`CredentialRequestLen`], without requiring their bounds.
+pub struct Sizes<CS: CipherSuite>(PhantomData<CS>);
+
+impl<CS: CipherSuite> Sizes<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    // Envelope: (Nonce + Mac) + AppData
+    pub(crate) const ENVELOPE_LEN: usize =
+        NonceLen::USIZE + MacLen::<CS>::USIZE + CS::AppDataLen::USIZE;
+    // MaskedResponse: Envelope + KePk
+    pub(crate) const MASKED_RESPONSE_LEN: usize =
+        Self::ENVELOPE_LEN + <CS::KeGroup as KeGroup>::PkLen::USIZE;
+
+    /// Length of [`RegistrationRequest`]
+    pub const REGISTRATION_REQUEST_LEN: usize = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+    /// Length of [`RegistrationResponse`]
+    pub const REGISTRATION_RESPONSE_LEN: usize =
+        <OprfGroup<CS> as Group>::ElemLen::USIZE + <CS::KeGroup as KeGroup>::PkLen::USIZE;
+    /// Length of [`RegistrationUpload`], which is also the length of a
+    /// serialized [`ServerRegistration`](crate::ServerRegistration)
+    pub const REGISTRATION_UPLOAD_LEN: usize = <CS::KeGroup as KeGroup>::PkLen::USIZE
+        + OutputSize::<OprfHash<CS>>::USIZE
+        + Self::ENVELOPE_LEN;
+    /// Length of [`CredentialRequest`]
+    pub const CREDENTIAL_REQUEST_LEN: usize =
+        <OprfGroup<CS> as Group>::ElemLen::USIZE + Ke1MessageLen::<CS>::USIZE;
+    /// Length of [`CredentialResponse`]
+    pub const CREDENTIAL_RESPONSE_LEN: usize =
+        Self::RETRIEVAL_RESPONSE_LEN + Ke2MessageLen::<CS>::USIZE;
+    /// Length of [`CredentialFinalization`]
+    pub const CREDENTIAL_FINALIZATION_LEN: usize = Ke3MessageLen::<CS>::USIZE;
+    /// Length of [`RetrievalRequest`]
+    pub const RETRIEVAL_REQUEST_LEN: usize = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+    /// Length of [`RetrievalResponse`]
+    pub const RETRIEVAL_RESPONSE_LEN: usize =
+        <OprfGroup<CS> as Group>::ElemLen::USIZE + NonceLen::USIZE + Self::MASKED_RESPONSE_LEN;
+    /// The length of the largest message, see [`MessageSizes::max`]
+    pub const MAX_MESSAGE_LEN: usize = {
+        let lengths = [
+            Self::REGISTRATION_REQUEST_LEN,
+            Self::REGISTRATION_RESPONSE_LEN,
+            Self::REGISTRATION_UPLOAD_LEN,
+            Self::CREDENTIAL_REQUEST_LEN,
+            Self::CREDENTIAL_RESPONSE_LEN,
+            Self::CREDENTIAL_FINALIZATION_LEN,
+            Self::RETRIEVAL_REQUEST_LEN,
+            Self::RETRIEVAL_RESPONSE_LEN,
+        ];
+        let mut max = 0;
+        let mut i = 0;
+
+        while i < lengths.len() {
+            if lengths[i] > max {
+                max = lengths[i];
+            }
+            i += 1;
+        }
+
+        max
+    };
+
+    /// Returns the lengths of all messages, same as [`max_message_sizes`]
+    pub const fn messages() -> MessageSizes {
+        MessageSizes {
+            registration_request: Self::REGISTRATION_REQUEST_LEN,
+            registration_response: Self::REGISTRATION_RESPONSE_LEN,
+            registration_upload: Self::REGISTRATION_UPLOAD_LEN,
+            credential_request: Self::CREDENTIAL_REQUEST_LEN,
+            credential_response: Self::CREDENTIAL_RESPONSE_LEN,
+            credential_finalization: Self::CREDENTIAL_FINALIZATION_LEN,
+            retrieval_request: Self::RETRIEVAL_REQUEST_LEN,
+            retrieval_response: Self::RETRIEVAL_RESPONSE_LEN,
+            envelope: Self::ENVELOPE_LEN,
+            masked_response: Self::MASKED_RESPONSE_LEN,
+        }
+    }
+}
+
+/// Returns the serialized lengths of all messages of `CS`
+///
+/// As the length of every message is fixed by the [`CipherSuite`], these are
+/// also the maximum lengths a network layer has to accept. Inputs of any other
+/// length can be rejected before they are read into memory or passed to
+/// `deserialize`.
+pub fn max_message_sizes<CS: CipherSuite>() -> MessageSizes
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    Sizes::<CS>::messages()
+}
+
+/// Returns the serialized lengths of all messages of `CS` like
+/// [`max_message_sizes`], but computed with checked arithmetic
+///
+/// The deserializers slice untrusted input by these lengths, so cipher suites
+/// with parameters large enough to overflow `usize` are rejected instead of
+/// wrapping around.
+///
+/// # Errors
+/// [`InternalError::SizeOverflowError`] if any length overflows `usize`
+pub fn checked_sizes<CS: CipherSuite>() -> Result<MessageSizes, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+    let key_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
+    let hash_len = OutputSize::<OprfHash<CS>>::USIZE;
+    let mac_len = MacLen::<CS>::USIZE;
+    let nonce_len = NonceLen::USIZE;
+
+    let envelope = checked_len("envelope", &[nonce_len, mac_len, CS::AppDataLen::USIZE])?;
+    let masked_response = checked_len("masked_response", &[envelope, key_len])?;
+    let retrieval_response = checked_len(
+        "retrieval_response",
+        &[elem_len, nonce_len, masked_response],
+    )?;
+
+    Ok(MessageSizes {
+        registration_request: elem_len,
+        registration_response: checked_len("registration_response", &[elem_len, key_len])?,
+        registration_upload: checked_len("registration_upload", &[key_len, hash_len, envelope])?,
+        credential_request: checked_len(
+            "credential_request",
+            &[elem_len, Ke1MessageLen::<CS>::USIZE],
+        )?,
+        credential_response: checked_len(
+            "credential_response",
+            &[retrieval_response, Ke2MessageLen::<CS>::USIZE],
+        )?,
+        credential_finalization: Ke3MessageLen::<CS>::USIZE,
+        retrieval_request: elem_len,
+        retrieval_response,
+        envelope,
+        masked_response,
+    })
+}
+
+fn checked_len(name: &'static str, lengths: &[usize]) -> Result<usize, InternalError> {
+    lengths
+        .iter()
+        .try_fold(0usize, |sum, len| sum.checked_add(*len))
+        .ok_or(InternalError::SizeOverflowError { name })
+}
+
+/// Checks that an array of `N` bytes has the serialized length `L` of a message
+struct ArrayLenAssertion<const N: usize, L>(PhantomData<L>);
+
+impl<const N: usize, L: Unsigned> ArrayLenAssertion<N, L> {
+    const VALID: () = assert!(
+        N == L::USIZE,
+        "the length of the array doesn't match the serialized length of the message"
+    );
+}
+
+/// Copies a serialized message into an array whose length is checked when the
+/// caller is built
+fn into_array<const N: usize, L: ArrayLength<u8>>(bytes: GenericArray<u8, L>) -> [u8; N] {
+    let () = ArrayLenAssertion::<N, L>::VALID;
+
+    let mut array = [0; N];
+    array.copy_from_slice(&bytes);
+    array
+}
@@ -0,0 +1,186 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Kotlin and Swift bindings of the client side of OPAQUE through
+//! [UniFFI](https://mozilla.github.io/uniffi-rs/), available with the `uniffi`
+//! feature
+//!
+//! The bindings use the [`Ristretto255Sha512Argon2id`] cipher suite and pass
+//! messages as their serialization, so that they can be sent to any server
+//! implementation of the suite. The state of the client between the start and
+//! the finish of a flow is returned in serialized form as well, which lets an
+//! app keep it across process restarts, e.g. while waiting for the server.
+//!
+//! A library crate for the app depends on this crate with the `uniffi`
+//! feature, is built as a `cdylib` or `staticlib`, and the bindings are
+//! generated from the built library:
+//!
+//! ```text
+//! uniffi-bindgen generate --library target/release/libapp.so --language kotlin --out-dir out
+//! ```
+//!
+//! In Kotlin, a registration then reads:
+//!
+//! ```text
+//! val start = clientRegistrationStart(password)
+//! val response = server.registrationStart(start.message)
+//! val finish = clientRegistrationFinish(start.state, password, response)
+//! server.registrationFinish(finish.message)
+//! ```
+//!
+//! Randomness is taken from the operating system.
+
+use std::vec::Vec;
+
+use displaydoc::Display;
+use rand::rngs::OsRng;
+
+use crate::errors::ProtocolError;
+use crate::suites::Ristretto255Sha512Argon2id;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialResponse, RegistrationResponse,
+};
+
+/// An error of the bindings, whose message is passed to the foreign language
+#[derive(Debug, Display, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum BindingError {
+    /// The protocol failed: {0}
+    Protocol(ProtocolError),
+}
+
+impl From<ProtocolError> for BindingError {
+    fn from(error: ProtocolError) -> Self {
+        Self::Protocol(error)
+    }
+}
+
+impl std::error::Error for BindingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Protocol(error) => Some(error),
+        }
+    }
+}
+
+/// The result of [`client_registration_start`]
+#[derive(uniffi::Record)]
+pub struct RegistrationStart {
+    /// The serialized [`ClientRegistration`], to be passed to
+    /// [`client_registration_finish`]
+    pub state: Vec<u8>,
+    /// The serialized registration request, to be sent to the server
+    pub message: Vec<u8>,
+}
+
+/// The result of [`client_registration_finish`]
+#[derive(uniffi::Record)]
+pub struct RegistrationFinish {
+    /// The serialized registration upload, to be sent to the server
+    pub message: Vec<u8>,
+    /// The export key
+    pub export_key: Vec<u8>,
+    /// The static public key of the server, to be checked if it is known in
+    /// advance
+    pub server_s_pk: Vec<u8>,
+}
+
+/// The result of [`client_login_start`]
+#[derive(uniffi::Record)]
+pub struct LoginStart {
+    /// The serialized [`ClientLogin`], to be passed to
+    /// [`client_login_finish`]
+    pub state: Vec<u8>,
+    /// The serialized credential request, to be sent to the server
+    pub message: Vec<u8>,
+}
+
+/// The result of [`client_login_finish`]
+#[derive(uniffi::Record)]
+pub struct LoginFinish {
+    /// The serialized credential finalization, to be sent to the server
+    pub message: Vec<u8>,
+    /// The session key
+    pub session_key: Vec<u8>,
+    /// The export key
+    pub export_key: Vec<u8>,
+    /// The static public key of the server, to be checked if it is known in
+    /// advance
+    pub server_s_pk: Vec<u8>,
+}
+
+/// Starts a registration with `password`, see [`ClientRegistration::start`]
+#[uniffi::export]
+pub fn client_registration_start(password: Vec<u8>) -> Result<RegistrationStart, BindingError> {
+    let result = ClientRegistration::<Ristretto255Sha512Argon2id>::start(&mut OsRng, &password)?;
+
+    Ok(RegistrationStart {
+        state: result.state.serialize().to_vec(),
+        message: result.message.serialize().to_vec(),
+    })
+}
+
+/// Finishes a registration with the `state` returned by
+/// [`client_registration_start`] and the registration response of the server,
+/// see [`ClientRegistration::finish`]
+#[uniffi::export]
+pub fn client_registration_finish(
+    state: Vec<u8>,
+    password: Vec<u8>,
+    response: Vec<u8>,
+) -> Result<RegistrationFinish, BindingError> {
+    let state = ClientRegistration::<Ristretto255Sha512Argon2id>::deserialize(&state)?;
+    let response = RegistrationResponse::deserialize(&response)?;
+    let result = state.finish(
+        &mut OsRng,
+        &password,
+        response,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+
+    Ok(RegistrationFinish {
+        message: result.message.serialize().to_vec(),
+        export_key: result.export_key.to_vec(),
+        server_s_pk: result.server_s_pk.serialize().to_vec(),
+    })
+}
+
+/// Starts a login with `password`, see [`ClientLogin::start`]
+#[uniffi::export]
+pub fn client_login_start(password: Vec<u8>) -> Result<LoginStart, BindingError> {
+    let result = ClientLogin::<Ristretto255Sha512Argon2id>::start(&mut OsRng, &password)?;
+
+    Ok(LoginStart {
+        state: result.state.serialize().to_vec(),
+        message: result.message.serialize().to_vec(),
+    })
+}
+
+/// Finishes a login with the `state` returned by [`client_login_start`] and
+/// the credential response of the server, see [`ClientLogin::finish`]
+///
+/// Fails if the password is wrong or the response was not produced by the
+/// server the client registered with.
+#[uniffi::export]
+pub fn client_login_finish(
+    state: Vec<u8>,
+    password: Vec<u8>,
+    response: Vec<u8>,
+) -> Result<LoginFinish, BindingError> {
+    let state = ClientLogin::<Ristretto255Sha512Argon2id>::deserialize(&state)?;
+    let response = CredentialResponse::deserialize(&response)?;
+    let result = state.finish(&password, response, ClientLoginFinishParameters::default())?;
+
+    Ok(LoginFinish {
+        message: result.message.serialize().to_vec(),
+        session_key: result.session_key.to_vec(),
+        export_key: result.export_key.to_vec(),
+        server_s_pk: result.server_s_pk.serialize().to_vec(),
+    })
+}
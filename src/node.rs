@@ -0,0 +1,179 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Node.js bindings of the client side of OPAQUE through
+//! [napi-rs](https://napi.rs), available with the `napi` feature
+//!
+//! The bindings use the [`Ristretto255Sha512Argon2id`] cipher suite and pass
+//! messages and the state of the client between the start and the finish of a
+//! flow as `Buffer`s holding their serialization, so that Electron apps and
+//! Node backends talk to any server implementation of the suite:
+//!
+//! ```text
+//! const opaque = require('./app.node');
+//!
+//! const start = opaque.clientLoginStart(password);
+//! const response = await server.loginStart(start.message);
+//! const finish = opaque.clientLoginFinish(start.state, password, response);
+//! await server.loginFinish(finish.message);
+//! ```
+//!
+//! A library crate for the app depends on this crate with the `napi` feature
+//! and is built as a `cdylib` with `napi build`, which also generates the
+//! TypeScript definitions. Failures are thrown as JavaScript errors with the
+//! message of the [`ProtocolError`]. Randomness is taken from the operating
+//! system.
+//!
+//! The functions call into Node.js and can therefore only run inside of it.
+//! They look up the Node-API symbols in the process when it starts instead of
+//! linking against them, so that the feature doesn't break the linking of
+//! executables such as `opaque-demo-server`. Debug builds of those report the
+//! symbols they didn't find at startup.
+
+use std::string::ToString;
+
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use rand::rngs::OsRng;
+
+use crate::errors::ProtocolError;
+use crate::suites::Ristretto255Sha512Argon2id;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialResponse, RegistrationResponse,
+};
+
+/// The result of [`client_registration_start`]
+#[napi(object)]
+pub struct RegistrationStart {
+    /// The serialized [`ClientRegistration`], to be passed to
+    /// [`client_registration_finish`]
+    pub state: Buffer,
+    /// The serialized registration request, to be sent to the server
+    pub message: Buffer,
+}
+
+/// The result of [`client_registration_finish`]
+#[napi(object)]
+pub struct RegistrationFinish {
+    /// The serialized registration upload, to be sent to the server
+    pub message: Buffer,
+    /// The export key
+    pub export_key: Buffer,
+    /// The static public key of the server, to be checked if it is known in
+    /// advance
+    pub server_s_pk: Buffer,
+}
+
+/// The result of [`client_login_start`]
+#[napi(object)]
+pub struct LoginStart {
+    /// The serialized [`ClientLogin`], to be passed to
+    /// [`client_login_finish`]
+    pub state: Buffer,
+    /// The serialized credential request, to be sent to the server
+    pub message: Buffer,
+}
+
+/// The result of [`client_login_finish`]
+#[napi(object)]
+pub struct LoginFinish {
+    /// The serialized credential finalization, to be sent to the server
+    pub message: Buffer,
+    /// The session key
+    pub session_key: Buffer,
+    /// The export key
+    pub export_key: Buffer,
+    /// The static public key of the server, to be checked if it is known in
+    /// advance
+    pub server_s_pk: Buffer,
+}
+
+/// Starts a registration with `password`, see [`ClientRegistration::start`]
+#[napi]
+pub fn client_registration_start(password: Buffer) -> napi::Result<RegistrationStart> {
+    let result = ClientRegistration::<Ristretto255Sha512Argon2id>::start(&mut OsRng, &password)
+        .map_err(error)?;
+
+    Ok(RegistrationStart {
+        state: result.state.serialize().to_vec().into(),
+        message: result.message.serialize().to_vec().into(),
+    })
+}
+
+/// Finishes a registration with the `state` returned by
+/// [`client_registration_start`] and the registration response of the server,
+/// see [`ClientRegistration::finish`]
+#[napi]
+pub fn client_registration_finish(
+    state: Buffer,
+    password: Buffer,
+    response: Buffer,
+) -> napi::Result<RegistrationFinish> {
+    let result = ClientRegistration::<Ristretto255Sha512Argon2id>::deserialize(&state)
+        .and_then(|state| {
+            state.finish(
+                &mut OsRng,
+                &password,
+                RegistrationResponse::deserialize(&response)?,
+                ClientRegistrationFinishParameters::default(),
+            )
+        })
+        .map_err(error)?;
+
+    Ok(RegistrationFinish {
+        message: result.message.serialize().to_vec().into(),
+        export_key: result.export_key.to_vec().into(),
+        server_s_pk: result.server_s_pk.serialize().to_vec().into(),
+    })
+}
+
+/// Starts a login with `password`, see [`ClientLogin::start`]
+#[napi]
+pub fn client_login_start(password: Buffer) -> napi::Result<LoginStart> {
+    let result =
+        ClientLogin::<Ristretto255Sha512Argon2id>::start(&mut OsRng, &password).map_err(error)?;
+
+    Ok(LoginStart {
+        state: result.state.serialize().to_vec().into(),
+        message: result.message.serialize().to_vec().into(),
+    })
+}
+
+/// Finishes a login with the `state` returned by [`client_login_start`] and
+/// the credential response of the server, see [`ClientLogin::finish`]
+///
+/// Throws if the password is wrong or the response was not produced by the
+/// server the client registered with.
+#[napi]
+pub fn client_login_finish(
+    state: Buffer,
+    password: Buffer,
+    response: Buffer,
+) -> napi::Result<LoginFinish> {
+    let result = ClientLogin::<Ristretto255Sha512Argon2id>::deserialize(&state)
+        .and_then(|state| {
+            state.finish(
+                &password,
+                CredentialResponse::deserialize(&response)?,
+                ClientLoginFinishParameters::default(),
+            )
+        })
+        .map_err(error)?;
+
+    Ok(LoginFinish {
+        message: result.message.serialize().to_vec().into(),
+        session_key: result.session_key.to_vec().into(),
+        export_key: result.export_key.to_vec().into(),
+        server_s_pk: result.server_s_pk.serialize().to_vec().into(),
+    })
+}
+
+fn error(error: ProtocolError) -> napi::Error {
+    napi::Error::from_reason(error.to_string())
+}
@@ -8,36 +8,71 @@
 
 //! Provides the main OPAQUE API
 
+#[cfg(feature = "server")]
+use core::fmt::{self, Debug};
+use core::future::Future;
+#[cfg(feature = "server")]
+use core::marker::PhantomData;
 use core::ops::Add;
 
 use derive_where::derive_where;
 use digest::core_api::{BlockSizeUser, CoreProxy};
-use digest::{Output, OutputSizeUser};
+use digest::{Digest, Output, OutputSizeUser};
 use generic_array::sequence::Concat;
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U2, U256};
+#[cfg(feature = "server")]
+use generic_array::typenum::U1;
+#[cfg(any(feature = "server", feature = "envelope-nonce"))]
+use generic_array::typenum::U32;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U2, U256, U8, U9};
 use generic_array::{ArrayLength, GenericArray};
-use hkdf::{Hkdf, HkdfExtract};
 use rand::{CryptoRng, RngCore};
 use subtle::ConstantTimeEq;
 use voprf::Group;
+use zeroize::Zeroizing;
 
-use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
-use crate::envelope::{Envelope, EnvelopeLen};
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash, SuiteAssertions};
+use crate::envelope::{Envelope, EnvelopeLen, EnvelopeMode, OpenedEnvelope};
 use crate::errors::utils::check_slice_size;
+#[cfg(feature = "server")]
+use crate::errors::LoginFailure;
 use crate::errors::{InternalError, ProtocolError};
+use crate::exporter::Exporter;
 use crate::hash::{Hash, OutputSize, ProxyHash};
+#[cfg(feature = "server")]
+use crate::idempotency::{IdempotentRegistration, RegistrationToken};
 use crate::key_exchange::group::KeGroup;
+#[cfg(feature = "server")]
+use crate::key_exchange::traits::Ke2StateLen;
 use crate::key_exchange::traits::{
-    Deserialize, Ke1MessageLen, Ke1StateLen, Ke2StateLen, KeyExchange, Serialize,
+    Deserialize, Ke1MessageLen, Ke1StateLen, KeyExchange, MacLen, Serialize, SessionKeyLen,
+    SuitePrk, SuiteProvider,
 };
 use crate::key_exchange::tripledh::NonceLen;
-use crate::keypair::{KeyPair, PrivateKey, PublicKey, SecretKey};
-use crate::ksf::Ksf;
-use crate::messages::{CredentialRequestLen, RegistrationUploadLen};
+#[cfg(feature = "server")]
+use crate::keypair::KeyPair;
+use crate::keypair::{
+    AsyncClientSecretKey, ClientSecretKey, PrivateKey, PublicKey, SecretKey, ServerIdentityVerifier,
+};
+use crate::ksf::{hash_cooperatively, ChunkedKsf, Ksf, KsfParameters, KsfProgress};
+use crate::messages::{checked_sizes, CredentialRequestLen, RegistrationUploadLen};
+#[cfg(feature = "server")]
+use crate::oprf::OprfEvaluator;
+use crate::provider::Provider;
+#[cfg(feature = "server")]
+use crate::receipt::{ReceiptPublicKey, RegistrationReceipt};
+#[cfg(feature = "server")]
+use crate::replay::ReplayCache;
 use crate::serialization::Input;
+#[cfg(feature = "server")]
+use crate::throttle::{ThrottleParameters, ThrottleToken};
+#[cfg(feature = "server")]
+use crate::transparency::{self, RecordLog};
+#[cfg(feature = "server")]
+use crate::util::fill_random;
+use crate::util::random_scalar;
 use crate::{
     CredentialFinalization, CredentialRequest, CredentialResponse, RegistrationRequest,
-    RegistrationResponse, RegistrationUpload,
+    RegistrationResponse, RegistrationUpload, RetrievalRequest, RetrievalResponse,
 };
 
 ///////////////
@@ -46,9 +81,20 @@ use crate::{
 ///////////////
 
 const STR_CREDENTIAL_RESPONSE_PAD: &[u8; 21] = b"CredentialResponsePad";
-const STR_MASKING_KEY: &[u8; 10] = b"MaskingKey";
+#[cfg(feature = "server")]
 const STR_OPRF_KEY: &[u8; 7] = b"OprfKey";
-const STR_OPAQUE_DERIVE_KEY_PAIR: &[u8; 20] = b"OPAQUE-DeriveKeyPair";
+#[cfg(feature = "server")]
+const STR_FAKE_RECORD: &[u8; 10] = b"FakeRecord";
+#[cfg(feature = "server")]
+const STR_BATCH_MAGIC: &[u8; 14] = b"OPAQUE-Records";
+const STR_PROTOCOL_VERSION: &[u8; 22] = b"OPAQUE-ProtocolVersion";
+const STR_MIGRATION_REQUEST: &[u8; 23] = b"OPAQUE-MigrationRequest";
+const STR_MIGRATION_UPLOAD: &[u8; 22] = b"OPAQUE-MigrationUpload";
+#[cfg(feature = "server")]
+const BATCH_VERSION: u8 = 1;
+// Magic, version, record length and number of records
+#[cfg(feature = "server")]
+const BATCH_HEADER_LEN: usize = STR_BATCH_MAGIC.len() + 1 + 4 + 8;
 
 ////////////////////////////
 // High-level API Structs //
@@ -56,6 +102,7 @@ const STR_OPAQUE_DERIVE_KEY_PAIR: &[u8; 20] = b"OPAQUE-DeriveKeyPair";
 ////////////////////////////
 
 /// The state elements the server holds upon setup
+#[cfg(feature = "server")]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
@@ -80,6 +127,9 @@ pub struct ServerSetup<
     oprf_seed: Output<OprfHash<CS>>,
     keypair: KeyPair<CS::KeGroup, S>,
     pub(crate) fake_keypair: KeyPair<CS::KeGroup>,
+    // Not serialized, see `ServerSetup::set_fake_record_secret`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fake_record_prk: Option<Output<OprfHash<CS>>>,
 }
 
 /// The state elements the client holds to register itself
@@ -108,14 +158,39 @@ where
 }
 
 /// The state elements the server holds to record a registration
-#[cfg_attr(
-    feature = "serde",
-    derive(serde::Deserialize, serde::Serialize),
-    serde(bound = "")
-)]
+///
+/// With the `serde` feature, records are represented as a newtype around
+/// their [`RegistrationUpload`]. Records created by
+/// [`ServerRegistration::finish_with_oprf_key`] fail to serialize this way, as
+/// the representation has no room for their OPRF key, and have to be stored
+/// through [`tagged_record`] instead.
+#[cfg(feature = "server")]
 #[derive_where(Clone, ZeroizeOnDrop)]
 #[derive_where(Debug, Eq, Hash, Ord, PartialEq, PartialOrd; <CS::KeGroup as KeGroup>::Pk)]
-pub struct ServerRegistration<CS: CipherSuite>(pub(crate) RegistrationUpload<CS>)
+pub struct ServerRegistration<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    pub(crate) upload: RegistrationUpload<CS>,
+    // The per-user OPRF key of a record created by
+    // `ServerRegistration::finish_with_oprf_key`, or `None` if the key is
+    // derived from the OPRF seed
+    #[derive_where(skip(Debug))]
+    pub(crate) oprf_key: Option<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>>,
+}
+
+// Records without a per-user OPRF key keep the representation of the former
+// newtype `ServerRegistration(RegistrationUpload)`, so that stored records
+// continue to deserialize
+#[cfg(all(feature = "serde", feature = "server"))]
+#[derive(serde::Deserialize)]
+#[serde(bound = "", rename = "ServerRegistration")]
+struct LegacyServerRegistration<CS: CipherSuite>(RegistrationUpload<CS>)
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -124,6 +199,123 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero;
 
+#[cfg(all(feature = "serde", feature = "server"))]
+impl<'de, CS: CipherSuite> serde::Deserialize<'de> for ServerRegistration<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        LegacyServerRegistration::deserialize(deserializer)
+            .map(|LegacyServerRegistration(upload)| Self::finish(upload))
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "server"))]
+impl<CS: CipherSuite> serde::Serialize for ServerRegistration<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+
+        if self.oprf_key.is_some() {
+            return Err(S::Error::custom(
+                "a ServerRegistration with a per-user OPRF key has to be serialized with \
+                 `tagged_record`",
+            ));
+        }
+
+        serializer.serialize_newtype_struct("ServerRegistration", &self.upload)
+    }
+}
+
+/// Serde representation of a [`ServerRegistration`] which retains its per-user
+/// OPRF key, for use with `#[serde(with = "opaque_ke::tagged_record")]`
+///
+/// Records are written as a tuple of [`KEYED_RECORD_VERSION`], the
+/// [`RegistrationUpload`] and the optional OPRF key, so that records with and
+/// without a per-user OPRF key can be stored in the same field. Deserializing
+/// validates the OPRF key like [`ServerRegistration::finish_with_oprf_key`].
+/// The result contains the OPRF key of the credential, so it has to be
+/// protected like the [`ServerSetup`].
+#[cfg(all(feature = "serde", feature = "server"))]
+pub mod tagged_record {
+    use digest::core_api::{BlockSizeUser, CoreProxy};
+    use digest::OutputSizeUser;
+    use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+    use generic_array::GenericArray;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use voprf::Group;
+    use zeroize::Zeroizing;
+
+    use super::{ServerRegistration, KEYED_RECORD_VERSION};
+    use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+    use crate::hash::{Hash, ProxyHash};
+    use crate::messages::RegistrationUpload;
+
+    type OprfKey<CS> = GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>;
+
+    /// Serializes `record` together with its per-user OPRF key
+    pub fn serialize<CS: CipherSuite, S: Serializer>(
+        record: &ServerRegistration<CS>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        (KEYED_RECORD_VERSION, &record.upload, &record.oprf_key).serialize(serializer)
+    }
+
+    /// Deserializes a record written by [`serialize`]
+    pub fn deserialize<'de, CS: CipherSuite, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ServerRegistration<CS>, D::Error>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        use serde::de::Error;
+
+        let (version, upload, oprf_key) =
+            <(u8, RegistrationUpload<CS>, Option<OprfKey<CS>>)>::deserialize(deserializer)?;
+
+        if version != KEYED_RECORD_VERSION {
+            return Err(D::Error::custom("unknown ServerRegistration version"));
+        }
+
+        match oprf_key.map(Zeroizing::new) {
+            Some(oprf_key) => ServerRegistration::finish_with_oprf_key(upload, oprf_key.as_slice())
+                .map_err(D::Error::custom),
+            None => Ok(ServerRegistration::finish(upload)),
+        }
+    }
+}
+
 /// The state elements the client holds to perform a login
 #[cfg_attr(
     feature = "serde",
@@ -156,9 +348,39 @@ where
     pub(crate) oprf_client: voprf::OprfClient<CS::OprfCs>,
     pub(crate) ke1_state: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State,
     pub(crate) credential_request: CredentialRequest<CS>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) created_at: Option<u64>,
+}
+
+/// The random material of a login prepared by [`ClientLogin::prepare`] before
+/// the password is known
+///
+/// It has to be used for at most one login, by [`PreparedClientLogin::start`],
+/// which consumes it. A pool of logins prepared ahead of time is a list of
+/// these, from which each login takes one.
+#[derive_where(ZeroizeOnDrop)]
+#[derive_where(
+    Debug, Eq, PartialEq;
+    <OprfGroup<CS> as Group>::Scalar,
+    <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State,
+    <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1Message,
+)]
+pub struct PreparedClientLogin<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    blind: <OprfGroup<CS> as Group>::Scalar,
+    ke1_state: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State,
+    ke1_message: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1Message,
 }
 
 /// The state elements the server holds to record a login
+#[cfg(feature = "server")]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
@@ -173,6 +395,7 @@ where
 #[derive_where(
     Debug, Eq, Hash, PartialEq;
     <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State,
+    <CS::KeGroup as KeGroup>::Pk,
 )]
 pub struct ServerLogin<CS: CipherSuite>
 where
@@ -183,9 +406,115 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    ke2_state: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State,
+    pub(crate) ke2_state: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State,
+    pub(crate) client_s_pk: PublicKey<CS::KeGroup>,
+}
+
+/// A [`CredentialResponse`] assembled in two phases, for servers evaluating
+/// the OPRF outside of [`ServerLogin::start`]
+///
+/// [`CredentialResponseBuilder::new`] masks the password file, after which
+/// [`CredentialResponseBuilder::blinded_element`] can be evaluated elsewhere,
+/// e.g. asynchronously by an HSM service holding the OPRF keys.
+/// [`CredentialResponseBuilder::finish`] then takes the evaluated element and
+/// runs the key exchange, which returns the same result as
+/// [`ServerLogin::start`]. [`ServerLogin::start`] is equivalent to evaluating
+/// with [`CredentialResponseBuilder::evaluate`] in between.
+#[cfg(feature = "server")]
+#[derive_where(Clone)]
+pub struct CredentialResponseBuilder<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    credential_request: CredentialRequest<CS>,
+    record: MaskedRecord<'a, CS>,
+}
+
+/// A login prepared by [`ServerLogin::prepare`] before the client's
+/// [`CredentialRequest`] arrives, e.g. after a first request only naming the
+/// credential identifier
+///
+/// It holds the masked password file, the OPRF key of the credential
+/// identifier and the ephemeral keys of the key exchange, already combined
+/// with the static public key of the client. [`PreparedServerLogin::complete`]
+/// then only evaluates the OPRF and runs the rest of the key exchange, which
+/// starts with the credential request. It has to be used for at most one
+/// login, which consumes it.
+#[cfg(feature = "server")]
+pub struct PreparedServerLogin<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    record: MaskedRecord<'a, CS>,
+    oprf_server: voprf::OprfServer<CS::OprfCs>,
+    ke2_prepared: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2Prepared,
+    #[cfg(test)]
+    oprf_key: GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>,
+}
+
+// The password file masked for a login, and the parameters of the login
+#[cfg(feature = "server")]
+#[derive_where(Clone)]
+struct MaskedRecord<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    credential_identifier: &'a [u8],
+    params: ServerLoginStartParameters<'a, 'a>,
+    // The per-user OPRF key of the password file
+    oprf_key: Option<Zeroizing<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>>>,
+    versioned_context: Option<Output<OprfHash<CS>>>,
+    client_s_pk: PublicKey<CS::KeGroup>,
+    server_s_pk: PublicKey<CS::KeGroup>,
+    masking_nonce: GenericArray<u8, NonceLen>,
+    masked_response: MaskedResponse<CS>,
+}
+
+/// The state elements the client holds to retrieve its credentials without a
+/// key exchange
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(bound = "")
+)]
+#[derive_where(Clone, ZeroizeOnDrop)]
+#[derive_where(
+    Debug, Eq, Hash, PartialEq;
+    voprf::OprfClient<CS::OprfCs>,
+    voprf::BlindedElement<CS::OprfCs>,
+)]
+pub struct ClientRetrieval<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    pub(crate) oprf_client: voprf::OprfClient<CS::OprfCs>,
+    pub(crate) blinded_element: voprf::BlindedElement<CS::OprfCs>,
 }
 
+/// The server side of a credential retrieval, which holds no state
+#[cfg(feature = "server")]
+pub struct ServerRetrieval<CS: CipherSuite>(PhantomData<CS>);
+
 ////////////////////////////////
 // High-level Implementations //
 // ========================== //
@@ -194,6 +523,7 @@ where
 // Server Setup
 // ============
 
+#[cfg(feature = "server")]
 impl<CS: CipherSuite> ServerSetup<CS, PrivateKey<CS::KeGroup>>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -204,16 +534,21 @@ where
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     /// Generate a new instance of server setup
-    pub fn new<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
-        let keypair = KeyPair::generate_random::<CS::OprfCs, _>(rng);
+    ///
+    /// # Errors
+    /// [`ProtocolError::RandomnessError`] if the `rng` fails.
+    pub fn new<R: CryptoRng + RngCore>(rng: &mut R) -> Result<Self, ProtocolError> {
+        let keypair = KeyPair::generate_random::<CS::OprfCs, _>(rng)?;
         Self::new_with_key(rng, keypair)
     }
 }
 
 /// Length of [`ServerSetup`] in bytes for serialization.
+#[cfg(feature = "server")]
 pub type ServerSetupLen<CS: CipherSuite, S: SecretKey<CS::KeGroup>> =
     Sum<Sum<OutputSize<OprfHash<CS>>, S::Len>, <CS::KeGroup as KeGroup>::SkLen>;
 
+#[cfg(feature = "server")]
 impl<CS: CipherSuite, S: SecretKey<CS::KeGroup>> ServerSetup<CS, S>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -228,18 +563,24 @@ where
     /// This function should not be used to restore a previously-existing
     /// instance of [`ServerSetup`]. Instead, use [`ServerSetup::serialize`] and
     /// [`ServerSetup::deserialize`] for this purpose.
+    ///
+    /// # Errors
+    /// [`ProtocolError::RandomnessError`] if the `rng` fails.
     pub fn new_with_key<R: CryptoRng + RngCore>(
         rng: &mut R,
         keypair: KeyPair<CS::KeGroup, S>,
-    ) -> Self {
+    ) -> Result<Self, ProtocolError> {
+        let () = SuiteAssertions::<CS>::VALID;
+
         let mut oprf_seed = GenericArray::default();
-        rng.fill_bytes(&mut oprf_seed);
+        fill_random(rng, &mut oprf_seed)?;
 
-        Self {
+        Ok(Self {
             oprf_seed,
+            fake_keypair: KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(rng)?,
             keypair,
-            fake_keypair: KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(rng),
-        }
+            fake_record_prk: None,
+        })
     }
 
     /// Serialization into bytes
@@ -259,6 +600,8 @@ where
 
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError<S::Error>> {
+        let () = SuiteAssertions::<CS>::VALID;
+
         let seed_len = OutputSize::<OprfHash<CS>>::USIZE;
         let key_len = <CS::KeGroup as KeGroup>::SkLen::USIZE;
         let checked_slice = check_slice_size(input, seed_len + key_len + key_len, "server_setup")?;
@@ -268,13 +611,202 @@ where
             keypair: KeyPair::from_private_key_slice(&checked_slice[seed_len..seed_len + key_len])?,
             fake_keypair: KeyPair::from_private_key_slice(&checked_slice[seed_len + key_len..])
                 .map_err(ProtocolError::into_custom)?,
+            fake_record_prk: None,
+        })
+    }
+
+    /// Deserialization from bytes, using the given keypair instead of the
+    /// serialized private key
+    ///
+    /// This restores instances whose private key can't be deserialized on its
+    /// own, such as one held by a hardware token, from the output of
+    /// [`ServerSetup::serialize`]. The serialized private key is ignored.
+    pub fn deserialize_with_keypair(
+        input: &[u8],
+        keypair: KeyPair<CS::KeGroup, S>,
+    ) -> Result<Self, ProtocolError<S::Error>> {
+        let () = SuiteAssertions::<CS>::VALID;
+
+        let seed_len = OutputSize::<OprfHash<CS>>::USIZE;
+        let key_len = S::Len::USIZE;
+        let fake_key_len = <CS::KeGroup as KeGroup>::SkLen::USIZE;
+        let checked_slice =
+            check_slice_size(input, seed_len + key_len + fake_key_len, "server_setup")?;
+
+        Ok(Self {
+            oprf_seed: GenericArray::clone_from_slice(&checked_slice[..seed_len]),
+            keypair,
+            fake_keypair: KeyPair::from_private_key_slice(&checked_slice[seed_len + key_len..])
+                .map_err(ProtocolError::into_custom)?,
+            fake_record_prk: None,
         })
     }
 
+    /// Evaluates the OPRF on the serialized `blinded_element` with the key
+    /// derived for `credential_identifier` from the OPRF seed, and returns the
+    /// serialized evaluated element
+    ///
+    /// This is the operation of an OPRF service that
+    /// [`OprfEvaluator`](crate::oprf::OprfEvaluator) outsources the evaluation
+    /// to.
+    ///
+    /// # Errors
+    /// [`ProtocolError::LibraryError`] if `blinded_element` is not a valid
+    /// element of the group
+    pub fn evaluate_oprf(
+        &self,
+        blinded_element: &[u8],
+        credential_identifier: &[u8],
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError> {
+        let blinded_element = voprf::BlindedElement::<CS::OprfCs>::deserialize(blinded_element)?;
+        let oprf_key =
+            oprf_key_from_seed::<CS>(&self.oprf_seed, credential_identifier, CS::OPRF_KEY_INFO)?;
+        let evaluation_element = voprf::OprfServer::<CS::OprfCs>::new_with_key(&oprf_key)?
+            .blind_evaluate(&blinded_element);
+
+        Ok(OprfGroup::<CS>::serialize_elem(evaluation_element.value()))
+    }
+
+    /// Derives the OPRF key of `credential_identifier` from the OPRF seed
+    /// mixed with a separate `pepper` secret
+    ///
+    /// The result is to be supplied to
+    /// [`ServerRegistration::start_with_oprf_key`] and
+    /// [`ServerLoginStartParameters::oprf_key`]. Keeping the pepper apart from
+    /// the [`ServerSetup`], e.g. in an HSM or KMS, means that a leak of the
+    /// credential records together with the serialized [`ServerSetup`] is not
+    /// sufficient to mount an offline dictionary attack.
+    pub fn peppered_oprf_key(
+        &self,
+        credential_identifier: &[u8],
+        pepper: &[u8],
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError> {
+        peppered_oprf_key_from_seed::<CS>(
+            &self.oprf_seed,
+            credential_identifier,
+            pepper,
+            CS::OPRF_KEY_INFO,
+        )
+    }
+
+    /// Derives the fake password files of unregistered clients from `secret`
+    /// and their credential identifier, instead of from randomness
+    ///
+    /// By default, [`ServerLogin::start`] creates a new random fake password
+    /// file for every login attempt without a password file. With a fake
+    /// record secret, every attempt for the same credential identifier uses
+    /// the same fake password file, like it would for a registered client,
+    /// also across server restarts as long as the same secret is configured.
+    ///
+    /// The secret is not part of [`ServerSetup::serialize`], so it has to be
+    /// set again after [`ServerSetup::deserialize`].
+    pub fn set_fake_record_secret(&mut self, secret: &[u8]) {
+        let (prk, _) = SuiteProvider::<CS>::extract(Some(STR_FAKE_RECORD), &[secret]);
+        self.fake_record_prk = Some(prk);
+    }
+
+    /// Issues a [`RegistrationToken`] for the registration attempt of
+    /// `credential_identifier` started by `registration_request` at time
+    /// `issued_at`, see [`ServerRegistration::finish_with_token`]
+    pub fn registration_token<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        credential_identifier: &[u8],
+        registration_request: &RegistrationRequest<CS>,
+        issued_at: u64,
+    ) -> Result<RegistrationToken<CS>, ProtocolError> {
+        RegistrationToken::new(
+            rng,
+            &self.oprf_seed,
+            credential_identifier,
+            registration_request,
+            issued_at,
+        )
+    }
+
+    /// Returns the public key verifying the [`RegistrationReceipt`]s issued by
+    /// [`ServerSetup::registration_receipt`]
+    pub fn receipt_public_key(&self) -> Result<ReceiptPublicKey<CS>, ProtocolError> {
+        ReceiptPublicKey::new(&self.oprf_seed)
+    }
+
+    /// Issues a [`RegistrationReceipt`] for `password_file`, stored under
+    /// `credential_identifier` at time `issued_at`, to be returned to the
+    /// client
+    pub fn registration_receipt<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        credential_identifier: &[u8],
+        password_file: &ServerRegistration<CS>,
+        issued_at: u64,
+    ) -> Result<RegistrationReceipt<CS>, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        RegistrationReceipt::new(
+            rng,
+            &self.oprf_seed,
+            credential_identifier,
+            &password_file.upload,
+            issued_at,
+        )
+    }
+
+    /// Issues a [`ThrottleToken`] recording `failures` failed login attempts
+    /// of `credential_identifier`, which is rejected before `not_before` and
+    /// from `expires_at` on
+    pub fn throttle_token(
+        &self,
+        credential_identifier: &[u8],
+        failures: u32,
+        not_before: u64,
+        expires_at: u64,
+    ) -> Result<ThrottleToken<CS>, ProtocolError> {
+        ThrottleToken::new(
+            &self.oprf_seed,
+            credential_identifier,
+            failures,
+            not_before,
+            expires_at,
+        )
+    }
+
+    /// Checks a [`ThrottleToken`] echoed by the client for
+    /// `credential_identifier` at time `now`, returning the number of failed
+    /// login attempts it records
+    ///
+    /// # Errors
+    /// [`ProtocolError::ThrottleError`] if the token was not issued by this
+    /// [`ServerSetup`] for `credential_identifier`, or if `now` is before the
+    /// time the token is rejected until or after it expired.
+    pub fn verify_throttle_token(
+        &self,
+        credential_identifier: &[u8],
+        token: &ThrottleToken<CS>,
+        now: u64,
+    ) -> Result<u32, ProtocolError> {
+        token.verify(&self.oprf_seed, credential_identifier, now)
+    }
+
     /// Returns the keypair
     pub fn keypair(&self) -> &KeyPair<CS::KeGroup, S> {
         &self.keypair
     }
+
+    /// Returns the server's static public key, which clients receive as
+    /// `server_s_pk` in [`ClientRegistrationFinishResult`] and
+    /// [`ClientLoginFinishResult`]
+    pub fn public_key(&self) -> &PublicKey<CS::KeGroup> {
+        self.keypair.public()
+    }
 }
 
 // Registration
@@ -317,6 +849,14 @@ where
         })
     }
 
+    /// Returns the [`RegistrationRequest`] produced by [`Self::start`], e.g. to
+    /// send it again after restoring the state with [`Self::deserialize`]
+    pub fn registration_request(&self) -> RegistrationRequest<CS> {
+        RegistrationRequest {
+            blinded_element: self.blinded_element.clone(),
+        }
+    }
+
     /// Only used for testing zeroize
     #[cfg(test)]
     pub(crate) fn to_vec(&self) -> std::vec::Vec<u8> {
@@ -329,10 +869,17 @@ where
 
     /// Returns an initial "blinded" request to send to the server, as well as a
     /// [`ClientRegistration`]
+    ///
+    /// # Errors
+    /// - [`ProtocolError::PasswordTooShortError`] if `password` is shorter than
+    ///   [`CipherSuite::MIN_PASSWORD_LEN`]
+    /// - [`ProtocolError::PasswordTooLongError`] if `password` is longer than
+    ///   [`CipherSuite::MAX_PASSWORD_LEN`]
     pub fn start<R: RngCore + CryptoRng>(
         blinding_factor_rng: &mut R,
         password: &[u8],
     ) -> Result<ClientRegistrationStartResult<CS>, ProtocolError> {
+        check_password_len::<CS>(password)?;
         let blind_result = blind::<CS, _>(blinding_factor_rng, password)?;
 
         Ok(ClientRegistrationStartResult {
@@ -356,7 +903,58 @@ where
         registration_response: RegistrationResponse<CS>,
         params: ClientRegistrationFinishParameters<CS>,
     ) -> Result<ClientRegistrationFinishResult<CS>, ProtocolError> {
-        // Check for reflected value from server and halt if detected
+        self.check_registration_response(&registration_response)?;
+
+        let password_derived_key = get_password_derived_key::<CS>(
+            password,
+            self.oprf_client.clone(),
+            registration_response.evaluation_element.clone(),
+            params.ksf,
+            params.ksf_progress,
+        )?;
+
+        self.seal(rng, password_derived_key, registration_response, params)
+    }
+
+    /// Same as [`Self::finish`], but computes the key stretching function in
+    /// chunks and awaits `yield_now` before each of them
+    ///
+    /// This keeps a user interface responsive when the key stretching function
+    /// can't run in a separate thread, e.g. in a browser without Web Workers,
+    /// by returning a future from `yield_now` which lets the event loop run,
+    /// such as one resolving on the next `setTimeout`. See
+    /// [`ChunkedArgon2`](crate::ksf::ChunkedArgon2).
+    pub async fn finish_async<R: CryptoRng + RngCore, F: Future<Output = ()>>(
+        self,
+        rng: &mut R,
+        password: &[u8],
+        registration_response: RegistrationResponse<CS>,
+        params: ClientRegistrationFinishParameters<'_, '_, CS>,
+        yield_now: impl FnMut() -> F,
+    ) -> Result<ClientRegistrationFinishResult<CS>, ProtocolError>
+    where
+        CS::Ksf: ChunkedKsf,
+    {
+        self.check_registration_response(&registration_response)?;
+
+        let password_derived_key = get_password_derived_key_async::<CS, _>(
+            password,
+            self.oprf_client.clone(),
+            registration_response.evaluation_element.clone(),
+            params.ksf,
+            params.ksf_progress,
+            yield_now,
+        )
+        .await?;
+
+        self.seal(rng, password_derived_key, registration_response, params)
+    }
+
+    // Checks for a reflected value from the server
+    fn check_registration_response(
+        &self,
+        registration_response: &RegistrationResponse<CS>,
+    ) -> Result<(), ProtocolError> {
         if self
             .blinded_element
             .value()
@@ -366,24 +964,52 @@ where
             return Err(ProtocolError::ReflectedValueError);
         }
 
-        #[cfg_attr(not(test), allow(unused_variables))]
-        let (randomized_pwd, randomized_pwd_hasher) = get_password_derived_key::<CS>(
-            password,
-            self.oprf_client.clone(),
-            registration_response.evaluation_element,
-            params.ksf,
-        )?;
+        Ok(())
+    }
+
+    // Seals the envelope with the output of the key stretching function, which
+    // is shared by all ways of finishing the registration
+    fn seal<R: CryptoRng + RngCore>(
+        self,
+        rng: &mut R,
+        password_derived_key: (Output<OprfHash<CS>>, SuitePrk<CS>),
+        registration_response: RegistrationResponse<CS>,
+        params: ClientRegistrationFinishParameters<CS>,
+    ) -> Result<ClientRegistrationFinishResult<CS>, ProtocolError> {
+        let ksf_parameters = match params.ksf {
+            Some(ksf) => ksf.parameters(),
+            None => CS::Ksf::default().parameters(),
+        };
+
+        #[cfg_attr(
+            not(any(test, feature = "randomized-password")),
+            allow(unused_variables)
+        )]
+        let (randomized_pwd, randomized_pwd_hasher) = password_derived_key;
 
         let mut masking_key = Output::<OprfHash<CS>>::default();
-        randomized_pwd_hasher
-            .expand(STR_MASKING_KEY, &mut masking_key)
-            .map_err(|_| InternalError::HkdfError)?;
+        SuiteProvider::<CS>::expand(
+            &randomized_pwd_hasher,
+            &[CS::MASKING_KEY_LABEL],
+            &mut masking_key,
+        )?;
+
+        #[cfg(any(test, feature = "envelope-nonce"))]
+        let envelope_nonce = params.envelope_nonce;
+        #[cfg(not(any(test, feature = "envelope-nonce")))]
+        let envelope_nonce = None;
+        let envelope_nonce =
+            Envelope::<CS>::nonce_with_ksf_parameters(rng, envelope_nonce, &ksf_parameters)?;
 
         let result = Envelope::<CS>::seal(
             rng,
-            randomized_pwd_hasher,
+            &randomized_pwd_hasher,
             &registration_response.server_s_pk,
             params.identifiers,
+            &params.app_data,
+            params.client_private_key.as_ref(),
+            params.client_public_key.as_ref(),
+            envelope_nonce,
         )?;
 
         Ok(ClientRegistrationFinishResult {
@@ -394,19 +1020,42 @@ where
             },
             export_key: result.2,
             server_s_pk: registration_response.server_s_pk,
+            ksf_parameters,
             #[cfg(test)]
             state: self,
             #[cfg(test)]
             auth_key: result.3,
-            #[cfg(test)]
+            #[cfg(any(test, feature = "randomized-password"))]
             randomized_pwd,
         })
     }
 }
 
 /// Length of [`ServerRegistration`] in bytes for serialization.
+#[cfg(feature = "server")]
 pub type ServerRegistrationLen<CS> = RegistrationUploadLen<CS>;
 
+/// Length of [`ServerRegistration`] in bytes for
+/// [`ServerRegistration::serialize_versioned`].
+#[cfg(feature = "server")]
+pub type VersionedServerRegistrationLen<CS> = Sum<U1, ServerRegistrationLen<CS>>;
+
+/// The record version written by [`ServerRegistration::serialize_versioned`]
+#[cfg(feature = "server")]
+pub const RECORD_VERSION: u8 = 1;
+
+/// Length of [`ServerRegistration`] in bytes for
+/// [`ServerRegistration::serialize_keyed`].
+#[cfg(feature = "server")]
+pub type KeyedServerRegistrationLen<CS> =
+    Sum<VersionedServerRegistrationLen<CS>, <OprfGroup<CS> as Group>::ScalarLen>;
+
+/// The record version written by [`ServerRegistration::serialize_keyed`] for
+/// records with a per-user OPRF key
+#[cfg(feature = "server")]
+pub const KEYED_RECORD_VERSION: u8 = 2;
+
+#[cfg(feature = "server")]
 impl<CS: CipherSuite> ServerRegistration<CS>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -417,10 +1066,15 @@ where
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     /// Serialization into bytes
+    ///
+    /// This leaves out the per-user OPRF key of a record created by
+    /// [`ServerRegistration::finish_with_oprf_key`], which has to be stored
+    /// with [`ServerRegistration::serialize_keyed`] instead.
     pub fn serialize(&self) -> GenericArray<u8, ServerRegistrationLen<CS>>
     where
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -429,59 +1083,1823 @@ where
         RegistrationUploadLen<CS>: ArrayLength<u8>,
         // ServerRegistration = RegistrationUpload
     {
-        self.0.serialize()
+        self.upload.serialize()
     }
 
     /// Deserialization from bytes
+    ///
+    /// A [`ServerRegistration`] serializes to the same bytes as the
+    /// [`RegistrationUpload`] it was created from, so this also accepts the
+    /// serialized upload directly. Only input of exactly this length is
+    /// accepted, records with a per-user OPRF key have to be read with
+    /// [`ServerRegistration::deserialize_keyed`].
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
-        Ok(Self(RegistrationUpload::deserialize(input)?))
+        Ok(Self::finish(RegistrationUpload::deserialize(input)?))
     }
 
-    /// From the client's "blinded" password, returns a response to be sent back
-    /// to the client, as well as a [`ServerRegistration`]
-    pub fn start<S: SecretKey<CS::KeGroup>>(
+    /// Deserialization of a record written by
+    /// [`ServerRegistration::serialize_keyed`], including its per-user OPRF
+    /// key
+    ///
+    /// Never pass bytes received from a client to this, as the record would
+    /// carry an OPRF key of the client's choosing. Uploads are read with
+    /// [`ServerRegistration::deserialize`].
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if `input` isn't prefixed with
+    /// [`KEYED_RECORD_VERSION`], or has the wrong length
+    pub fn deserialize_keyed(input: &[u8]) -> Result<Self, ProtocolError> {
+        let record_len = RegistrationUpload::<CS>::serialized_size();
+
+        match input {
+            [KEYED_RECORD_VERSION, record @ ..]
+                if record.len() == record_len + <OprfGroup<CS> as Group>::ScalarLen::USIZE =>
+            {
+                let (upload, oprf_key) = record.split_at(record_len);
+                Self::finish_with_oprf_key(RegistrationUpload::deserialize(upload)?, oprf_key)
+            }
+            _ => Err(ProtocolError::SerializationError),
+        }
+    }
+
+    /// Serialization into bytes, prefixed with [`RECORD_VERSION`]
+    ///
+    /// Storing records in this format allows future versions of this crate to
+    /// change the record layout, with old records being upgraded as they are
+    /// read by [`ServerRegistration::deserialize_versioned`].
+    ///
+    /// Like [`ServerRegistration::serialize`], this leaves out the per-user
+    /// OPRF key of a record, see [`ServerRegistration::serialize_keyed`].
+    pub fn serialize_versioned(&self) -> GenericArray<u8, VersionedServerRegistrationLen<CS>>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // VersionedServerRegistration: Version + ServerRegistration
+        U1: Add<ServerRegistrationLen<CS>>,
+        VersionedServerRegistrationLen<CS>: ArrayLength<u8>,
+    {
+        GenericArray::from([RECORD_VERSION]).concat(self.serialize())
+    }
+
+    /// Serialization of a record created by
+    /// [`ServerRegistration::finish_with_oprf_key`] into bytes, prefixed with
+    /// [`KEYED_RECORD_VERSION`] and followed by its per-user OPRF key
+    ///
+    /// The result contains the OPRF key of the credential, so it has to be
+    /// protected like the [`ServerSetup`].
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if the record has no per-user
+    /// OPRF key.
+    pub fn serialize_keyed(
+        &self,
+    ) -> Result<GenericArray<u8, KeyedServerRegistrationLen<CS>>, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // KeyedServerRegistration: (Version + ServerRegistration) + OprfKey
+        U1: Add<ServerRegistrationLen<CS>>,
+        VersionedServerRegistrationLen<CS>:
+            ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ScalarLen>,
+        KeyedServerRegistrationLen<CS>: ArrayLength<u8>,
+    {
+        let oprf_key = self
+            .oprf_key
+            .as_ref()
+            .ok_or(ProtocolError::SerializationError)?;
+
+        Ok(GenericArray::from([KEYED_RECORD_VERSION])
+            .concat(self.serialize())
+            .concat(oprf_key.clone()))
+    }
+
+    /// Deserialization of a record stored in any known format
+    ///
+    /// Accepts the output of [`ServerRegistration::serialize_versioned`] and
+    /// [`ServerRegistration::serialize_keyed`], as well as the unversioned
+    /// output of [`ServerRegistration::serialize`].
+    /// Any other input is passed to `upgrade`, which can convert records the
+    /// application stored in a layout of its own, or return
+    /// [`ProtocolError::SerializationError`].
+    ///
+    /// If the record was not read from the current format,
+    /// [`ServerRegistrationReadResult::upgraded`] is set, and the record should
+    /// be written back with [`ServerRegistration::serialize_versioned`], e.g.
+    /// after the next successful login. This way, records are migrated as they
+    /// are used, without rewriting the whole database at once.
+    pub fn deserialize_versioned(
+        input: &[u8],
+        upgrade: impl FnOnce(&[u8]) -> Result<Self, ProtocolError>,
+    ) -> Result<ServerRegistrationReadResult<CS>, ProtocolError> {
+        let record_len = RegistrationUpload::<CS>::serialized_size();
+
+        let (record, upgraded) = match input {
+            [RECORD_VERSION, record @ ..] if record.len() == record_len => {
+                (Self::deserialize(record)?, false)
+            }
+            [KEYED_RECORD_VERSION, record @ ..]
+                if record.len() == record_len + <OprfGroup<CS> as Group>::ScalarLen::USIZE =>
+            {
+                (Self::deserialize_keyed(input)?, false)
+            }
+            record if record.len() == record_len => (Self::deserialize(record)?, true),
+            record => (upgrade(record)?, true),
+        };
+
+        Ok(ServerRegistrationReadResult { record, upgraded })
+    }
+
+    /// Checks that `input` is a valid serialized [`RegistrationUpload`] or
+    /// [`ServerRegistration`], without returning it
+    ///
+    /// This allows servers to store the bytes of a [`RegistrationUpload`] as
+    /// received, and only construct the [`ServerRegistration`] with
+    /// [`ServerRegistration::deserialize`] when it is needed at login. Like
+    /// [`ServerRegistration::deserialize`], this rejects records with a
+    /// per-user OPRF key, which a client must not be able to supply.
+    pub fn validate(input: &[u8]) -> Result<(), ProtocolError> {
+        Self::deserialize(input).map(|_| ())
+    }
+
+    /// Performs a cheap structural check of a serialized [`RegistrationUpload`]
+    /// or [`ServerRegistration`], which only verifies its length and does not
+    /// decode the client's public key
+    ///
+    /// This is meant to speed up bulk imports and migrations of large
+    /// databases. Records passing this check may still be rejected by the full
+    /// validation of [`ServerRegistration::validate`] and
+    /// [`ServerRegistration::deserialize`], which is then performed at login.
+    /// Records with a per-user OPRF key are checked with
+    /// [`ServerRegistration::validate_keyed_structure`] instead.
+    pub fn validate_structure(input: &[u8]) -> Result<(), ProtocolError> {
+        let record_len = checked_sizes::<CS>()?.registration_upload;
+        check_slice_size(input, record_len, "server_registration")?;
+
+        Ok(())
+    }
+
+    /// Performs the check of [`ServerRegistration::validate_structure`] for a
+    /// record written by [`ServerRegistration::serialize_keyed`], verifying
+    /// its version and length
+    pub fn validate_keyed_structure(input: &[u8]) -> Result<(), ProtocolError> {
+        let record_len = checked_sizes::<CS>()?.registration_upload;
+
+        match input {
+            [KEYED_RECORD_VERSION, record @ ..] => {
+                check_slice_size(
+                    record,
+                    record_len + <OprfGroup<CS> as Group>::ScalarLen::USIZE,
+                    "server_registration",
+                )?;
+            }
+            _ => return Err(ProtocolError::SerializationError),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the length of the container written by
+    /// [`ServerRegistration::serialize_batch`] for `records`
+    pub fn batch_len(records: &[(&[u8], ServerRegistration<CS>)]) -> usize {
+        BATCH_HEADER_LEN
+            + records
+                .iter()
+                .map(|(credential_identifier, record)| {
+                    2 + credential_identifier.len() + 4 + record.batch_record_len()
+                })
+                .sum::<usize>()
+            + OutputSize::<OprfHash<CS>>::USIZE
+    }
+
+    // Length of the record as written into a batch, including its version
+    fn batch_record_len(&self) -> usize {
+        let key_len = if self.oprf_key.is_some() {
+            <OprfGroup<CS> as Group>::ScalarLen::USIZE
+        } else {
+            0
+        };
+
+        1 + RegistrationUpload::<CS>::serialized_size() + key_len
+    }
+
+    /// Writes `records` together with their credential identifiers into
+    /// `output`, returning the number of bytes written
+    ///
+    /// The container starts with a magic string, a format version, the length
+    /// of a [`RegistrationUpload`] and the number of records, and ends with a
+    /// checksum over everything before it, which
+    /// [`ServerRegistration::deserialize_batch`] verifies. The checksum guards
+    /// against truncation and corruption, but as it is unkeyed it does not
+    /// protect against tampering.
+    ///
+    /// Every record is written with its length and the version byte of
+    /// [`ServerRegistration::serialize_versioned`], or of
+    /// [`ServerRegistration::serialize_keyed`] if it has a per-user OPRF key,
+    /// which is retained. A container holding such keys has to be protected
+    /// like the [`ServerSetup`].
+    ///
+    /// # Errors
+    /// - [`InternalError::SizeError`] if `output` is shorter than
+    ///   [`ServerRegistration::batch_len`]
+    /// - [`ProtocolError::SerializationError`] if a credential identifier is
+    ///   longer than [`u16::MAX`]
+    pub fn serialize_batch(
+        records: &[(&[u8], ServerRegistration<CS>)],
+        output: &mut [u8],
+    ) -> Result<usize, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let len = Self::batch_len(records);
+        if output.len() < len {
+            return Err(InternalError::SizeError {
+                name: "output",
+                len,
+                actual_len: output.len(),
+            }
+            .into());
+        }
+
+        let record_len = u32::try_from(RegistrationUpload::<CS>::serialized_size())
+            .map_err(|_| ProtocolError::SerializationError)?;
+        let mut written = 0;
+        let mut write = |bytes: &[u8]| {
+            output[written..written + bytes.len()].copy_from_slice(bytes);
+            written += bytes.len();
+        };
+
+        write(STR_BATCH_MAGIC);
+        write(&[BATCH_VERSION]);
+        write(&record_len.to_be_bytes());
+        write(&(records.len() as u64).to_be_bytes());
+
+        for (credential_identifier, record) in records {
+            let credential_identifier = Input::<U2>::from(credential_identifier)?;
+            let entry_len = u32::try_from(record.batch_record_len())
+                .map_err(|_| ProtocolError::SerializationError)?;
+            credential_identifier.iter().for_each(&mut write);
+            write(&entry_len.to_be_bytes());
+
+            match &record.oprf_key {
+                Some(oprf_key) => {
+                    write(&[KEYED_RECORD_VERSION]);
+                    write(&record.serialize());
+                    write(oprf_key);
+                }
+                None => {
+                    write(&[RECORD_VERSION]);
+                    write(&record.serialize());
+                }
+            }
+        }
+
+        let checksum = OprfHash::<CS>::digest(&output[..written]);
+        output[written..len].copy_from_slice(&checksum);
+
+        Ok(len)
+    }
+
+    /// Reads a container written by [`ServerRegistration::serialize_batch`],
+    /// returning an iterator over the credential identifiers and records
+    ///
+    /// The format version, record length and checksum of the whole container
+    /// are verified upfront, while the records themselves are deserialized one
+    /// by one as the iterator advances.
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if `input` is not a container
+    /// for records of this cipher suite, or fails the checksum
+    pub fn deserialize_batch(input: &[u8]) -> Result<RecordBatch<'_, CS>, ProtocolError> {
+        let hash_len = OutputSize::<OprfHash<CS>>::USIZE;
+        if input.len() < BATCH_HEADER_LEN + hash_len {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        let (contents, checksum) = input.split_at(input.len() - hash_len);
+        if OprfHash::<CS>::digest(contents).as_slice() != checksum {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        let (header, records) = contents.split_at(BATCH_HEADER_LEN);
+        let (magic, header) = header.split_at(STR_BATCH_MAGIC.len());
+        let mut record_len = [0; 4];
+        record_len.copy_from_slice(&header[1..5]);
+        let mut count = [0; 8];
+        count.copy_from_slice(&header[5..]);
+
+        if magic != STR_BATCH_MAGIC
+            || header[0] != BATCH_VERSION
+            || u32::from_be_bytes(record_len) as usize
+                != RegistrationUpload::<CS>::serialized_size()
+        {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        let batch = RecordBatch {
+            records,
+            remaining: usize::try_from(u64::from_be_bytes(count))
+                .map_err(|_| ProtocolError::SerializationError)?,
+            _cs: PhantomData,
+        };
+
+        // Check that the records fill the container exactly, so that iterating
+        // only fails when decoding a record
+        let mut check = batch.clone();
+        while check.remaining > 0 {
+            check.split_next()?;
+        }
+        if !check.records.is_empty() {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        Ok(batch)
+    }
+
+    /// From the client's "blinded" password, returns a response to be sent back
+    /// to the client, as well as a [`ServerRegistration`]
+    pub fn start<S: SecretKey<CS::KeGroup>>(
         server_setup: &ServerSetup<CS, S>,
         message: RegistrationRequest<CS>,
         credential_identifier: &[u8],
     ) -> Result<ServerRegistrationStartResult<CS>, ProtocolError> {
-        let oprf_key =
-            oprf_key_from_seed::<CS::OprfCs>(&server_setup.oprf_seed, credential_identifier)?;
+        let oprf_key = oprf_key_from_seed::<CS>(
+            &server_setup.oprf_seed,
+            credential_identifier,
+            CS::OPRF_KEY_INFO,
+        )?;
+
+        Self::start_with_oprf_key(server_setup, message, &oprf_key)
+    }
+
+    /// Same as [`ServerRegistration::start`], but evaluates the OPRF with a
+    /// per-user `oprf_key` instead of deriving it from the OPRF seed of the
+    /// [`ServerSetup`]
+    ///
+    /// The record is then created with
+    /// [`ServerRegistration::finish_with_oprf_key`], which stores the key in
+    /// it, so that [`ServerLogin::start`] uses it instead of the OPRF seed.
+    /// This allows rotating or escrowing the keys of single accounts. Fresh
+    /// keys can be obtained from [`ServerRegistration::generate_oprf_key`].
+    pub fn start_with_oprf_key<S: SecretKey<CS::KeGroup>>(
+        server_setup: &ServerSetup<CS, S>,
+        message: RegistrationRequest<CS>,
+        oprf_key: &[u8],
+    ) -> Result<ServerRegistrationStartResult<CS>, ProtocolError> {
+        let server = voprf::OprfServer::new_with_key(oprf_key)?;
+        let evaluation_element = server.blind_evaluate(&message.blinded_element);
+
+        Ok(ServerRegistrationStartResult {
+            message: RegistrationResponse {
+                evaluation_element,
+                server_s_pk: server_setup.keypair.public().clone(),
+            },
+            #[cfg(test)]
+            oprf_key: GenericArray::clone_from_slice(oprf_key),
+        })
+    }
+
+    /// Same as [`ServerRegistration::start`], but takes the serialized
+    /// evaluation of the blinded element of the [`RegistrationRequest`] instead
+    /// of evaluating the OPRF, e.g. as returned by an
+    /// [`OprfEvaluator`](crate::oprf::OprfEvaluator)
+    ///
+    /// # Errors
+    /// [`ProtocolError::LibraryError`] if `evaluation_element` is not a valid
+    /// element of the group or is the identity element
+    pub fn start_with_evaluation<S: SecretKey<CS::KeGroup>>(
+        server_setup: &ServerSetup<CS, S>,
+        evaluation_element: &[u8],
+    ) -> Result<ServerRegistrationStartResult<CS>, ProtocolError> {
+        let evaluation_element =
+            voprf::EvaluationElement::<CS::OprfCs>::deserialize(evaluation_element)?;
+
+        Ok(ServerRegistrationStartResult {
+            message: RegistrationResponse {
+                evaluation_element,
+                server_s_pk: server_setup.keypair.public().clone(),
+            },
+            #[cfg(test)]
+            oprf_key: GenericArray::default(),
+        })
+    }
+
+    /// Generates a random per-user OPRF key for
+    /// [`ServerRegistration::start_with_oprf_key`]
+    pub fn generate_oprf_key<R: CryptoRng + RngCore>(
+        rng: &mut R,
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError> {
+        Ok(voprf::OprfServer::<CS::OprfCs>::new(rng)?.serialize())
+    }
+
+    /// From the client's cryptographic identifiers, fully populates and returns
+    /// a [`ServerRegistration`]
+    pub fn finish(message: RegistrationUpload<CS>) -> Self {
+        Self {
+            upload: message,
+            oprf_key: None,
+        }
+    }
+
+    /// Like [`ServerRegistration::finish`], but stores the per-user
+    /// `oprf_key` that [`ServerRegistration::start_with_oprf_key`] was called
+    /// with in the record
+    ///
+    /// Such records have to be stored with
+    /// [`ServerRegistration::serialize_keyed`].
+    ///
+    /// # Errors
+    /// [`ProtocolError::LibraryError`] if `oprf_key` is not a valid OPRF key
+    pub fn finish_with_oprf_key(
+        message: RegistrationUpload<CS>,
+        oprf_key: &[u8],
+    ) -> Result<Self, ProtocolError> {
+        let oprf_key = voprf::OprfServer::<CS::OprfCs>::new_with_key(oprf_key)?.serialize();
+
+        Ok(Self {
+            upload: message,
+            oprf_key: Some(oprf_key),
+        })
+    }
+
+    /// Returns the per-user OPRF key of a record created by
+    /// [`ServerRegistration::finish_with_oprf_key`], e.g. to escrow it
+    pub fn oprf_key(&self) -> Option<&GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>> {
+        self.oprf_key.as_ref()
+    }
+
+    /// Like [`ServerRegistration::finish`], but first checks the key
+    /// stretching function parameters that the client attested in the
+    /// [`RegistrationUpload`] against the server's `minimum` policy. The
+    /// parameters are only carried by suites that set
+    /// [`CipherSuite::ATTEST_KSF_PARAMETERS`], and stay in the record for
+    /// [`ServerRegistration::ksf_parameters`].
+    ///
+    /// # Errors
+    /// [`ProtocolError::KsfPolicyError`] if the upload carries no parameters
+    /// or they do not satisfy `minimum`
+    pub fn finish_with_ksf_policy(
+        message: RegistrationUpload<CS>,
+        minimum: &KsfParameters,
+    ) -> Result<Self, ProtocolError> {
+        match message.ksf_parameters() {
+            Some(ksf_parameters) if ksf_parameters.satisfies(minimum) => Ok(Self::finish(message)),
+            _ => Err(ProtocolError::KsfPolicyError),
+        }
+    }
+
+    /// Returns the key stretching function parameters attested by the client
+    /// at registration, e.g. to find records that fall short of a raised
+    /// policy. `None` unless the suite sets
+    /// [`CipherSuite::ATTEST_KSF_PARAMETERS`].
+    pub fn ksf_parameters(&self) -> Option<KsfParameters> {
+        self.upload.ksf_parameters()
+    }
+
+    /// Like [`ServerRegistration::finish`], but first runs the server's own
+    /// `validate` checks on the [`RegistrationUpload`], e.g. that the
+    /// [client public key](RegistrationUpload::client_public_key) is not
+    /// already registered to another account
+    ///
+    /// # Errors
+    /// [`ProtocolError::RegistrationRejected`] if `validate` returns `false`
+    pub fn finish_with_validation(
+        message: RegistrationUpload<CS>,
+        validate: impl FnOnce(&RegistrationUpload<CS>) -> bool,
+    ) -> Result<Self, ProtocolError> {
+        if !validate(&message) {
+            return Err(ProtocolError::RegistrationRejected);
+        }
+
+        Ok(Self::finish(message))
+    }
+
+    /// Like [`ServerRegistration::finish`], but first appends the commitment
+    /// of the password file stored under `credential_identifier` to `log`,
+    /// see the [`transparency`] module
+    ///
+    /// # Errors
+    /// - [`ProtocolError::SerializationError`] if `credential_identifier` is
+    ///   longer than [`u16::MAX`]
+    /// - [`ProtocolError::RecordLogError`] if `log` failed to append the entry,
+    ///   in which case the password file must not be stored
+    pub fn finish_with_record_log(
+        message: RegistrationUpload<CS>,
+        credential_identifier: &[u8],
+        log: &mut impl RecordLog,
+    ) -> Result<Self, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let password_file = Self::finish(message);
+        transparency::append(log, credential_identifier, &password_file)?;
+
+        Ok(password_file)
+    }
+
+    /// Recognizes retried and replayed registrations by the
+    /// [`RegistrationToken`] the client echoed together with the
+    /// [`RegistrationRequest`] it was issued for
+    ///
+    /// `password_file` is the result of [`ServerRegistration::finish`] or
+    /// [`ServerRegistration::finish_with_oprf_key`] for the upload, and
+    /// `previous` is the token and password file stored for
+    /// `credential_identifier` by an earlier call, if any. If it has the same
+    /// token and an identical password file, the upload is a retry and
+    /// [`IdempotentRegistration::AlreadyStored`] is returned. Otherwise the
+    /// token must have been issued later than the previous one, and
+    /// `password_file` is returned in [`IdempotentRegistration::Store`], to be
+    /// stored together with `token`.
+    ///
+    /// # Errors
+    /// [`ProtocolError::RegistrationRejected`] if `token` was not issued by
+    /// `server_setup` for `credential_identifier` and `registration_request`,
+    /// was issued no later than the previous token, or is the previous token
+    /// but with a different password file
+    pub fn finish_with_token<S: SecretKey<CS::KeGroup>>(
+        server_setup: &ServerSetup<CS, S>,
+        credential_identifier: &[u8],
+        registration_request: &RegistrationRequest<CS>,
+        token: &RegistrationToken<CS>,
+        password_file: Self,
+        previous: Option<(&RegistrationToken<CS>, &ServerRegistration<CS>)>,
+    ) -> Result<IdempotentRegistration<CS>, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        token.verify(
+            &server_setup.oprf_seed,
+            credential_identifier,
+            registration_request,
+        )?;
+
+        match previous {
+            Some((previous_token, previous_record)) if previous_token == token => {
+                // The OPRF key of a keyed record has to match as well
+                if previous_record.serialize() == password_file.serialize()
+                    && previous_record.oprf_key == password_file.oprf_key
+                {
+                    Ok(IdempotentRegistration::AlreadyStored)
+                } else {
+                    Err(ProtocolError::RegistrationRejected)
+                }
+            }
+            Some((previous_token, _)) if token.issued_at() <= previous_token.issued_at() => {
+                Err(ProtocolError::RegistrationRejected)
+            }
+            _ => Ok(IdempotentRegistration::Store(password_file)),
+        }
+    }
+
+    /// Creates a fake password file for a client which is not registered
+    ///
+    /// Passing it to [`ServerLogin::start`] produces a
+    /// [`CredentialResponse`] indistinguishable from the one of a registered
+    /// client, but which no password can log in with. This is what
+    /// [`ServerLogin::start`] does when given no password file, and is meant
+    /// for applications which look up records on their own and want to
+    /// handle a missing record the same way as an existing one, see [Dummy
+    /// Server Login](crate#dummy-server-login).
+    ///
+    /// Each call uses a new random masking key, so the fake file must not be
+    /// stored, or else it would be distinguishable from a missing record in
+    /// later logins.
+    pub fn dummy<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+    ) -> Result<Self, ProtocolError> {
+        RegistrationUpload::dummy(rng, server_setup).map(Self::finish)
+    }
+
+    // Creates the fake password file used when no password file is given,
+    // derived from the credential identifier if a fake record secret is set
+    pub(crate) fn fake<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        credential_identifier: &[u8],
+    ) -> Result<Self, ProtocolError> {
+        match &server_setup.fake_record_prk {
+            Some(fake_record_prk) => {
+                RegistrationUpload::derived_dummy(fake_record_prk, credential_identifier)
+                    .map(Self::finish)
+            }
+            None => Self::dummy(rng, server_setup),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> From<RegistrationUpload<CS>> for ServerRegistration<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: RegistrationUpload<CS>) -> Self {
+        Self::finish(message)
+    }
+}
+
+/// Iterator over the credential identifiers and records of a container read
+/// by [`ServerRegistration::deserialize_batch`]
+#[cfg(feature = "server")]
+#[derive_where(Clone, Debug)]
+pub struct RecordBatch<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    records: &'a [u8],
+    remaining: usize,
+    _cs: PhantomData<CS>,
+}
+
+#[cfg(feature = "server")]
+impl<'a, CS: CipherSuite> RecordBatch<'a, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    // Splits off the next credential identifier and serialized record
+    fn split_next(&mut self) -> Result<(&'a [u8], &'a [u8]), ProtocolError> {
+        let (id_len, rest) = split(self.records, 2)?;
+        let id_len = usize::from(u16::from_be_bytes([id_len[0], id_len[1]]));
+        let (credential_identifier, rest) = split(rest, id_len)?;
+
+        let (record_len, rest) = split(rest, 4)?;
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(record_len);
+        let record_len = usize::try_from(u32::from_be_bytes(bytes))
+            .map_err(|_| ProtocolError::SerializationError)?;
+        let (record, rest) = split(rest, record_len)?;
+        self.records = rest;
+        self.remaining -= 1;
+
+        Ok((credential_identifier, record))
+    }
+}
+
+#[cfg(feature = "server")]
+impl<'a, CS: CipherSuite> Iterator for RecordBatch<'a, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Item = Result<(&'a [u8], ServerRegistration<CS>), ProtocolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        Some(
+            self.split_next()
+                .and_then(|(credential_identifier, record)| {
+                    let record = match record {
+                        [RECORD_VERSION, record @ ..] => {
+                            ServerRegistration::finish(RegistrationUpload::deserialize(record)?)
+                        }
+                        [KEYED_RECORD_VERSION, ..] => {
+                            ServerRegistration::deserialize_keyed(record)?
+                        }
+                        _ => return Err(ProtocolError::SerializationError),
+                    };
+
+                    Ok((credential_identifier, record))
+                }),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> ExactSizeIterator for RecordBatch<'_, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+}
+
+// Splits off the first `len` bytes of a batch
+#[cfg(feature = "server")]
+fn split(input: &[u8], len: usize) -> Result<(&[u8], &[u8]), ProtocolError> {
+    if input.len() < len {
+        return Err(ProtocolError::SerializationError);
+    }
+
+    Ok(input.split_at(len))
+}
+
+// Login
+// =====
+
+#[cfg(feature = "server")]
+pub(crate) type ServerLoginLen<CS: CipherSuite> =
+    Sum<Ke2StateLen<CS>, <<CS as CipherSuite>::KeGroup as KeGroup>::PkLen>;
+
+pub(crate) type ClientLoginLen<CS: CipherSuite> =
+    Sum<Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>, Ke1StateLen<CS>>;
+
+/// Length of [`ClientLogin`] in bytes for [`ClientLogin::serialize_timed`].
+pub type TimedClientLoginLen<CS: CipherSuite> =
+    Sum<Sum<U9, ClientLoginLen<CS>>, OutputSize<OprfHash<CS>>>;
+
+// Tells the output of `ClientLogin::serialize_timed` apart from the one of
+// `ClientLogin::serialize`
+const TIMED_CLIENT_LOGIN_TAG: u8 = 1;
+const STR_TIMED_CLIENT_LOGIN: &[u8; 23] = b"OPAQUE-TimedClientLogin";
+
+// Starts the HMAC of a timed client login under the state key of the client,
+// with a label so that the key can be shared with other uses
+fn timed_client_login_mac<CS: CipherSuite>(
+    state_key: &[u8],
+    timed: &[u8],
+) -> Result<<SuiteProvider<CS> as Provider<OprfHash<CS>>>::Mac, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut mac = SuiteProvider::<CS>::new_mac(state_key)?;
+    SuiteProvider::<CS>::update_mac(&mut mac, STR_TIMED_CLIENT_LOGIN);
+    SuiteProvider::<CS>::update_mac(&mut mac, timed);
+
+    Ok(mac)
+}
+
+impl<CS: CipherSuite> ClientLogin<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, ClientLoginLen<CS>>
+    where
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // ClientLogin: KgSk + CredentialRequest + Ke1State
+        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
+        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
+            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
+        ClientLoginLen<CS>: ArrayLength<u8>,
+    {
+        self.oprf_client
+            .serialize()
+            .concat(self.credential_request.serialize())
+            .concat(self.ke1_state.serialize())
+    }
+
+    /// Serialization into bytes, prefixed with a format tag and the time
+    /// `created_at` the login was started at, and followed by an HMAC of both
+    /// and the state under `state_key`
+    ///
+    /// Finishing a login whose state was restored from these bytes with
+    /// [`Self::deserialize_timed`] can be limited to a maximum age with
+    /// [`ClientLoginFinishParameters::max_state_age`], so that a state which
+    /// was left behind, e.g. in storage that wasn't cleaned up, isn't used
+    /// after the login should have been abandoned.
+    ///
+    /// `state_key` is a secret held by the client apart from the storage of
+    /// the state, e.g. in a platform keystore, so that a party which can only
+    /// modify the stored bytes can't change `created_at`. The serialized state
+    /// still has to be kept as confidential as the password itself.
+    ///
+    /// # Errors
+    /// [`InternalError::HmacError`] if `state_key` is not accepted as an HMAC
+    /// key
+    pub fn serialize_timed(
+        &self,
+        created_at: u64,
+        state_key: &[u8],
+    ) -> Result<GenericArray<u8, TimedClientLoginLen<CS>>, ProtocolError>
+    where
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // ClientLogin: KgSk + CredentialRequest + Ke1State
+        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
+        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
+            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
+        ClientLoginLen<CS>: ArrayLength<u8>,
+        // TimedClientLogin: ((Tag + CreatedAt) + ClientLogin) + Mac
+        U9: Add<ClientLoginLen<CS>>,
+        Sum<U9, ClientLoginLen<CS>>: ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+        TimedClientLoginLen<CS>: ArrayLength<u8>,
+    {
+        let timed = GenericArray::from([TIMED_CLIENT_LOGIN_TAG])
+            .concat(created_at.to_be_bytes().into())
+            .concat(self.serialize());
+        let mac = timed_client_login_mac::<CS>(state_key, &timed)?;
+
+        Ok(timed.concat(SuiteProvider::<CS>::finalize_mac(mac)))
+    }
+
+    /// Deserialization from bytes produced by [`Self::serialize`]
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        Self::deserialize_with_creation_time(input, None)
+    }
+
+    /// Deserialization from bytes produced by [`Self::serialize_timed`] with
+    /// the same `state_key`
+    ///
+    /// # Errors
+    /// - [`ProtocolError::SerializationError`] if `input` has the wrong length
+    ///   or format tag
+    /// - [`ProtocolError::StateAuthenticationError`] if `input` was modified,
+    ///   or serialized with a different `state_key`
+    pub fn deserialize_timed(input: &[u8], state_key: &[u8]) -> Result<Self, ProtocolError> {
+        let timed_len = 1
+            + U8::USIZE
+            + <OprfGroup<CS> as Group>::ScalarLen::USIZE
+            + <OprfGroup<CS> as Group>::ElemLen::USIZE
+            + Ke1MessageLen::<CS>::USIZE
+            + Ke1StateLen::<CS>::USIZE;
+
+        let checked_slice = check_slice_size(
+            input,
+            timed_len + OutputSize::<OprfHash<CS>>::USIZE,
+            "timed_client_login",
+        )?;
+        let (timed, mac) = checked_slice.split_at(timed_len);
+        if timed[0] != TIMED_CLIENT_LOGIN_TAG {
+            return Err(ProtocolError::SerializationError);
+        }
+        if !bool::from(SuiteProvider::<CS>::verify_mac(
+            timed_client_login_mac::<CS>(state_key, timed)?,
+            mac,
+        )) {
+            return Err(ProtocolError::StateAuthenticationError);
+        }
+
+        let (created_at, input) = timed[1..].split_at(U8::USIZE);
+        let mut created_at_bytes = [0; 8];
+        created_at_bytes.copy_from_slice(created_at);
+        Self::deserialize_with_creation_time(input, Some(u64::from_be_bytes(created_at_bytes)))
+    }
+
+    fn deserialize_with_creation_time(
+        input: &[u8],
+        created_at: Option<u64>,
+    ) -> Result<Self, ProtocolError> {
+        let client_len = <OprfGroup<CS> as Group>::ScalarLen::USIZE;
+        let request_len = <OprfGroup<CS> as Group>::ElemLen::USIZE + Ke1MessageLen::<CS>::USIZE;
+        let state_len = Ke1StateLen::<CS>::USIZE;
+
+        let checked_slice =
+            check_slice_size(input, client_len + request_len + state_len, "client_login")?;
+
+        let ke1_state =
+            <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State::deserialize(
+                &checked_slice[client_len + request_len..],
+            )?;
+        Ok(Self {
+            oprf_client: voprf::OprfClient::deserialize(&checked_slice[..client_len])?,
+            credential_request: CredentialRequest::deserialize(
+                &checked_slice[client_len..client_len + request_len],
+            )?,
+            ke1_state,
+            created_at,
+        })
+    }
+
+    /// Returns the [`CredentialRequest`] produced by [`Self::start`], e.g. to
+    /// send it again after restoring the state with [`Self::deserialize`]
+    pub fn credential_request(&self) -> &CredentialRequest<CS> {
+        &self.credential_request
+    }
+
+    /// Returns the time the login was started at, if the state was restored
+    /// with [`Self::deserialize_timed`]
+    pub fn created_at(&self) -> Option<u64> {
+        self.created_at
+    }
+}
+
+impl<CS: CipherSuite> ClientLogin<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Returns an initial "blinded" password request to send to the server, as
+    /// well as a [`ClientLogin`]
+    ///
+    /// # Errors
+    /// - [`ProtocolError::PasswordTooShortError`] if `password` is shorter than
+    ///   [`CipherSuite::MIN_PASSWORD_LEN`]
+    /// - [`ProtocolError::PasswordTooLongError`] if `password` is longer than
+    ///   [`CipherSuite::MAX_PASSWORD_LEN`]
+    pub fn start<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        password: &[u8],
+    ) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
+        Self::prepare(rng)?.start(password)
+    }
+
+    /// Draws the OPRF blind and the ephemeral keys of the key exchange of a
+    /// login ahead of time, e.g. while the user is typing the password
+    ///
+    /// [`PreparedClientLogin::start`] then only has to hash the password to
+    /// the OPRF group, which gives the same result as [`Self::start`] with the
+    /// same `rng`.
+    ///
+    /// # Errors
+    /// [`ProtocolError::RandomnessError`] if the `rng` fails.
+    pub fn prepare<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Result<PreparedClientLogin<CS>, ProtocolError> {
+        let () = SuiteAssertions::<CS>::VALID;
+
+        let blind = random_blind::<CS, _>(rng)?;
+        let (ke1_state, ke1_message) = CS::KeyExchange::generate_ke1::<CS::OprfCs, _>(rng)?;
+
+        Ok(PreparedClientLogin {
+            blind,
+            ke1_state,
+            ke1_message,
+        })
+    }
+
+    /// "Unblinds" the server's answer and returns the opened assets from the
+    /// server
+    pub fn finish(
+        self,
+        password: &[u8],
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<CS>,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let opened =
+            self.open_credential_response(password, &credential_response, &params, None)?;
+        let client_s_dh = opened.client_s_dh(&credential_response)?;
+
+        self.finish_ke(credential_response, opened, client_s_dh, &params)
+    }
+
+    /// Same as [`Self::finish`], but computes the key stretching function in
+    /// chunks and awaits `yield_now` before each of them, see
+    /// [`ClientRegistration::finish_async`]
+    pub async fn finish_async<F: Future<Output = ()>>(
+        self,
+        password: &[u8],
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<'_, '_, '_, CS>,
+        yield_now: impl FnMut() -> F,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError>
+    where
+        CS::Ksf: ChunkedKsf,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        self.check_credential_response(&credential_response, &params)?;
+
+        let password_derived_key = get_password_derived_key_async::<CS, _>(
+            password,
+            self.oprf_client.clone(),
+            credential_response.evaluation_element.clone(),
+            params.ksf,
+            params.ksf_progress,
+            yield_now,
+        )
+        .await?;
+
+        let opened =
+            self.open_envelope(password_derived_key, &credential_response, &params, None)?;
+        let client_s_dh = opened.client_s_dh(&credential_response)?;
+
+        self.finish_ke(credential_response, opened, client_s_dh, &params)
+    }
+
+    /// Same as [`Self::finish`], but delegates the client's Diffie-Hellman
+    /// operation to a private key held outside of this crate
+    ///
+    /// The envelope must have been sealed with the public key of
+    /// `client_secret_key`, see
+    /// [`ClientRegistrationFinishParameters::client_public_key`], and
+    /// [`ClientLoginFinishParameters::envelope_mode`] is ignored.
+    pub fn finish_with_secret_key<S: ClientSecretKey<CS::KeGroup>>(
+        self,
+        client_secret_key: &S,
+        password: &[u8],
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<CS>,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let opened = self
+            .open_credential_response(
+                password,
+                &credential_response,
+                &params,
+                Some(client_secret_key.public_key()?),
+            )
+            .map_err(ProtocolError::into_custom)?;
+        let client_s_dh = client_secret_key.diffie_hellman(CS::KeyExchange::client_s_dh_pk(
+            &credential_response.ke2_message,
+        ))?;
+
+        self.finish_ke(credential_response, opened, Some(client_s_dh), &params)
+            .map_err(ProtocolError::into_custom)
+    }
+
+    /// Same as [`Self::finish_with_secret_key`], but for a private key whose
+    /// Diffie-Hellman operation is asynchronous
+    pub async fn finish_with_async_secret_key<S: AsyncClientSecretKey<CS::KeGroup>>(
+        self,
+        client_secret_key: &S,
+        password: &[u8],
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<'_, '_, '_, CS>,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let opened = self
+            .open_credential_response(
+                password,
+                &credential_response,
+                &params,
+                Some(client_secret_key.public_key()?),
+            )
+            .map_err(ProtocolError::into_custom)?;
+        let client_s_dh = client_secret_key
+            .diffie_hellman(CS::KeyExchange::client_s_dh_pk(
+                &credential_response.ke2_message,
+            ))
+            .await?;
+
+        self.finish_ke(credential_response, opened, Some(client_s_dh), &params)
+            .map_err(ProtocolError::into_custom)
+    }
+
+    // Recovers the server's public key and opens the envelope, which is shared
+    // by all ways of finishing the login. The client's public key is given if
+    // its private key is held outside of this crate.
+    fn open_credential_response<'i>(
+        &self,
+        password: &[u8],
+        credential_response: &CredentialResponse<CS>,
+        params: &ClientLoginFinishParameters<'_, 'i, '_, CS>,
+        client_public_key: Option<PublicKey<CS::KeGroup>>,
+    ) -> Result<OpenedCredentialResponse<'i, CS>, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        self.check_credential_response(credential_response, params)?;
+
+        let password_derived_key = get_password_derived_key::<CS>(
+            password,
+            self.oprf_client.clone(),
+            credential_response.evaluation_element.clone(),
+            params.ksf,
+            params.ksf_progress,
+        )?;
+
+        self.open_envelope(
+            password_derived_key,
+            credential_response,
+            params,
+            client_public_key,
+        )
+    }
+
+    // Checks the parameters and the credential response before the key
+    // stretching function is computed
+    fn check_credential_response(
+        &self,
+        credential_response: &CredentialResponse<CS>,
+        params: &ClientLoginFinishParameters<CS>,
+    ) -> Result<(), ProtocolError> {
+        check_protocol_version(
+            params.protocol_version_announced,
+            params.peer_protocol_version,
+        )?;
+
+        if let Some((now, max_age)) = params.max_state_age {
+            match self.created_at {
+                Some(created_at) if now.saturating_sub(created_at) <= max_age => (),
+                _ => return Err(ProtocolError::ExpiredStateError),
+            }
+        }
+
+        // Check if beta value from server is equal to alpha value from client
+        if self
+            .credential_request
+            .blinded_element
+            .value()
+            .ct_eq(&credential_response.evaluation_element.value())
+            .into()
+        {
+            return Err(ProtocolError::ReflectedValueError);
+        }
+
+        Ok(())
+    }
+
+    // Unmasks the credential response and opens the envelope with the output of
+    // the key stretching function
+    fn open_envelope<'i>(
+        &self,
+        password_derived_key: (Output<OprfHash<CS>>, SuitePrk<CS>),
+        credential_response: &CredentialResponse<CS>,
+        params: &ClientLoginFinishParameters<'_, 'i, '_, CS>,
+        client_public_key: Option<PublicKey<CS::KeGroup>>,
+    ) -> Result<OpenedCredentialResponse<'i, CS>, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        #[cfg_attr(
+            not(any(test, feature = "randomized-password")),
+            allow(unused_variables)
+        )]
+        let (randomized_pwd, randomized_pwd_hasher) = password_derived_key;
+
+        let mut masking_key = Output::<OprfHash<CS>>::default();
+        SuiteProvider::<CS>::expand(
+            &randomized_pwd_hasher,
+            &[CS::MASKING_KEY_LABEL],
+            &mut masking_key,
+        )?;
+
+        let (server_s_pk, envelope) = credential_response
+            .masked_response
+            .unmask(&masking_key, &credential_response.masking_nonce)
+            .map_err(|e| match e {
+                ProtocolError::SerializationError => ProtocolError::InvalidLoginError,
+                err => err,
+            })?;
+
+        let opened_envelope = envelope
+            .open(
+                &randomized_pwd_hasher,
+                server_s_pk.clone(),
+                params.identifiers,
+                params.envelope_mode,
+                client_public_key,
+            )
+            .map_err(|e| match e {
+                ProtocolError::LibraryError(InternalError::SealOpenHmacError) => {
+                    ProtocolError::InvalidLoginError
+                }
+                err => err,
+            })?;
+
+        // The envelope authenticated the server public key and identity, so the
+        // verifier sees the ones given at registration
+        if let Some(verifier) = params.server_identity_verifier {
+            let server_identity = server_s_pk.serialize();
+            let server_identity = params.identifiers.server.unwrap_or(&server_identity);
+
+            if !verifier.verify(server_identity, &server_s_pk) {
+                return Err(ProtocolError::ServerIdentityError);
+            }
+        }
+
+        Ok(OpenedCredentialResponse {
+            envelope: opened_envelope,
+            server_s_pk,
+            #[cfg(any(test, feature = "randomized-password"))]
+            randomized_pwd,
+        })
+    }
+
+    // Completes the key exchange given the client's static Diffie-Hellman
+    // shared secret, which is `None` if the client is not authenticated
+    fn finish_ke(
+        self,
+        credential_response: CredentialResponse<CS>,
+        opened: OpenedCredentialResponse<'_, CS>,
+        client_s_dh: Option<GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>>,
+        params: &ClientLoginFinishParameters<CS>,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let opened_envelope = opened.envelope;
+        let server_s_pk = opened.server_s_pk;
+
+        let beta = OprfGroup::<CS>::serialize_elem(credential_response.evaluation_element.value());
+        let credential_response_component = CredentialResponse::<CS>::serialize_without_ke(
+            &beta,
+            &credential_response.masking_nonce,
+            &credential_response.masked_response,
+        );
+
+        let blinded_element =
+            OprfGroup::<CS>::serialize_elem(self.credential_request.blinded_element.value());
+        let ke1_message = self.credential_request.ke1_message.serialize();
+        let serialized_credential_request =
+            CredentialRequest::<CS>::serialize_iter(&blinded_element, &ke1_message);
+
+        let (client_e_pk, server_e_pk) = CS::KeyExchange::ephemeral_pks(
+            &self.credential_request.ke1_message,
+            &credential_response.ke2_message,
+        );
+        let result = CS::KeyExchange::generate_ke3(
+            credential_response_component,
+            credential_response.ke2_message,
+            &self.ke1_state,
+            serialized_credential_request,
+            server_s_pk.clone(),
+            client_s_dh,
+            opened_envelope.id_u.iter(),
+            opened_envelope.id_s.iter(),
+            versioned_context::<CS>(
+                params.context,
+                params.protocol_version_announced,
+                params.peer_protocol_version,
+            )?
+            .as_deref()
+            .or(params.context)
+            .unwrap_or_default(),
+        )?;
+
+        Ok(ClientLoginFinishResult {
+            message: CredentialFinalization {
+                ke3_message: result.1,
+            },
+            session_key: result.0,
+            export_key: opened_envelope.export_key,
+            server_s_pk,
+            client_e_pk,
+            server_e_pk,
+            app_data: opened_envelope.app_data,
+            server_identity: if params.identifiers.server.is_some() {
+                ServerIdentity::Supplied
+            } else {
+                ServerIdentity::PublicKey
+            },
+            protocol_version: params
+                .protocol_version_announced
+                .then_some(PROTOCOL_VERSION),
+            #[cfg(any(test, feature = "randomized-password"))]
+            randomized_pwd: opened.randomized_pwd,
+            #[cfg(test)]
+            state: self,
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
+            handshake_secret: result.2,
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
+            client_mac_key: result.3,
+        })
+    }
+}
+
+impl<CS: CipherSuite> PreparedClientLogin<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Blinds `password` with the prepared material, and returns the initial
+    /// password request to send to the server, as well as a [`ClientLogin`]
+    ///
+    /// # Errors
+    /// The same as for [`ClientLogin::start`]
+    pub fn start(self, password: &[u8]) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
+        check_password_len::<CS>(password)?;
+        let blind_result =
+            voprf::OprfClient::<CS::OprfCs>::deterministic_blind_unchecked(password, self.blind)?;
+
+        let credential_request = CredentialRequest {
+            blinded_element: blind_result.message,
+            ke1_message: self.ke1_message.clone(),
+        };
+
+        Ok(ClientLoginStartResult {
+            message: credential_request.clone(),
+            state: ClientLogin {
+                oprf_client: blind_result.state,
+                ke1_state: self.ke1_state.clone(),
+                credential_request,
+                created_at: None,
+            },
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> ServerLogin<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, ServerLoginLen<CS>>
+    where
+        // ServerLogin: Ke2State + KePk
+        Ke2StateLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        ServerLoginLen<CS>: ArrayLength<u8>,
+    {
+        self.ke2_state
+            .serialize()
+            .concat(self.client_s_pk.serialize())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let state_len = Ke2StateLen::<CS>::USIZE;
+        let key_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
+        let checked_slice = check_slice_size(input, state_len + key_len, "server_login")?;
+
+        Ok(Self {
+            ke2_state:
+                <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State::deserialize(
+                    &checked_slice[..state_len],
+                )?,
+            client_s_pk: PublicKey::deserialize(&checked_slice[state_len..])?,
+        })
+    }
+
+    /// From the client's "blinded" password, returns a challenge to be sent
+    /// back to the client, as well as a [`ServerLogin`]
+    ///
+    /// If [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled, the key exchange
+    /// leaves out the client's static key of `password_file`, see
+    /// [`ServerLogin::finish`].
+    pub fn start<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        credential_request: CredentialRequest<CS>,
+        credential_identifier: &[u8],
+        params: ServerLoginStartParameters,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        Self::prepare(
+            rng,
+            server_setup,
+            password_file,
+            credential_identifier,
+            params,
+        )?
+        .complete(server_setup, credential_request)
+    }
+
+    /// Does the work of [`ServerLogin::start`] that doesn't depend on the
+    /// client's [`CredentialRequest`] ahead of time, see
+    /// [`PreparedServerLogin`]
+    ///
+    /// [`PreparedServerLogin::complete`] then gives the same result as
+    /// [`ServerLogin::start`] with the same `rng`.
+    pub fn prepare<'a, R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        credential_identifier: &'a [u8],
+        params: ServerLoginStartParameters<'a, 'a>,
+    ) -> Result<PreparedServerLogin<'a, CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let record = MaskedRecord::new(
+            rng,
+            server_setup,
+            password_file,
+            credential_identifier,
+            params,
+        )?;
+        let oprf_key = record
+            .oprf_key(server_setup)
+            .map_err(ProtocolError::into_custom)?;
+        let oprf_server = voprf::OprfServer::new_with_key(&oprf_key)
+            .map_err(|e| ProtocolError::into_custom(e.into()))?;
+        let ke2_prepared = CS::KeyExchange::prepare_ke2::<CS::OprfCs, _>(
+            rng,
+            CS::CLIENT_AUTHENTICATION.then(|| record.client_s_pk.clone()),
+            record.params.server_nonce,
+        )
+        .map_err(ProtocolError::into_custom)?;
+
+        Ok(PreparedServerLogin {
+            record,
+            oprf_server,
+            ke2_prepared,
+            #[cfg(test)]
+            oprf_key,
+        })
+    }
+
+    /// Same as [`ServerLogin::start`], but first consults `replay_cache` with
+    /// a hash of `credential_request`
+    ///
+    /// # Errors
+    /// [`ProtocolError::ReplayError`] if `replay_cache` has seen the same
+    /// credential request before.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_with_replay_cache<
+        R: RngCore + CryptoRng,
+        S: SecretKey<CS::KeGroup>,
+        C: ReplayCache + ?Sized,
+    >(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        credential_request: CredentialRequest<CS>,
+        credential_identifier: &[u8],
+        params: ServerLoginStartParameters,
+        replay_cache: &mut C,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let blinded_element =
+            OprfGroup::<CS>::serialize_elem(credential_request.blinded_element.value());
+        let ke1_message = credential_request.ke1_message.serialize();
+        let mut hasher = OprfHash::<CS>::new();
+        CredentialRequest::<CS>::serialize_iter(&blinded_element, &ke1_message)
+            .for_each(|bytes| hasher.update(bytes));
+
+        if replay_cache.check_and_insert(&hasher.finalize()) {
+            return Err(ProtocolError::ReplayError);
+        }
+
+        Self::start(
+            rng,
+            server_setup,
+            password_file,
+            credential_request,
+            credential_identifier,
+            params,
+        )
+    }
+
+    /// Same as [`ServerLogin::start`], but first checks the [`ThrottleToken`]
+    /// echoed by the client with [`ServerSetup::verify_throttle_token`], and
+    /// returns the token to send alongside the [`CredentialResponse`]
+    ///
+    /// The returned token pessimistically records one more failed attempt, is
+    /// rejected until [`ThrottleParameters::backoff`] of that many failures
+    /// has passed and expires [`ThrottleParameters::lifetime`] later. A client
+    /// that logs in successfully drops it. See the [`throttle`](crate::throttle)
+    /// module for the limits of this stateless throttling.
+    ///
+    /// # Errors
+    /// [`ProtocolError::ThrottleError`] if the client echoed no token but
+    /// [`ThrottleParameters::token_required`] is set, or if the token is
+    /// rejected by [`ServerSetup::verify_throttle_token`].
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn start_with_throttle<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        credential_request: CredentialRequest<CS>,
+        credential_identifier: &[u8],
+        params: ServerLoginStartParameters,
+        throttle: ThrottleParameters<CS>,
+    ) -> Result<(ServerLoginStartResult<CS>, ThrottleToken<CS>), ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let failures = match throttle.token {
+            Some(token) => server_setup
+                .verify_throttle_token(credential_identifier, token, throttle.now)
+                .map_err(ProtocolError::into_custom)?,
+            None if throttle.token_required => return Err(ProtocolError::ThrottleError),
+            None => 0,
+        };
+        let failures = failures.saturating_add(1);
+        let not_before = throttle.now.saturating_add((throttle.backoff)(failures));
+        let token = server_setup
+            .throttle_token(
+                credential_identifier,
+                failures,
+                not_before,
+                not_before.saturating_add(throttle.lifetime),
+            )
+            .map_err(ProtocolError::into_custom)?;
+
+        let result = Self::start(
+            rng,
+            server_setup,
+            password_file,
+            credential_request,
+            credential_identifier,
+            params,
+        )?;
 
-        let server = voprf::OprfServer::new_with_key(&oprf_key)?;
-        let evaluation_element = server.blind_evaluate(&message.blinded_element);
+        Ok((result, token))
+    }
 
-        Ok(ServerRegistrationStartResult {
-            message: RegistrationResponse {
-                evaluation_element,
-                server_s_pk: server_setup.keypair.public().clone(),
-            },
+    /// From the client's second and final message, check the client's
+    /// authentication and produce a message transport, along with the client
+    /// static public key that was authenticated
+    ///
+    /// If [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled, this only checks
+    /// that the client derived the same session key, which any client knowing
+    /// the server's public key can do without the password, also against a
+    /// dummy password file. The login then doesn't authenticate the client,
+    /// and [`ServerLoginFinishResult::client_s_pk`] is [`None`].
+    pub fn finish(
+        self,
+        message: CredentialFinalization<CS>,
+    ) -> Result<ServerLoginFinishResult<CS>, ProtocolError> {
+        let session_key = <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::finish_ke(
+            message.ke3_message,
+            &self.ke2_state,
+        )?;
+        let (client_e_pk, server_e_pk) = CS::KeyExchange::ke2_state_ephemeral_pks(&self.ke2_state)?;
+
+        Ok(ServerLoginFinishResult {
+            session_key,
+            client_s_pk: CS::CLIENT_AUTHENTICATION.then(|| self.client_s_pk.clone()),
+            client_e_pk,
+            server_e_pk,
             #[cfg(test)]
-            oprf_key,
+            state: self,
         })
     }
 
-    /// From the client's cryptographic identifiers, fully populates and returns
-    /// a [`ServerRegistration`]
-    pub fn finish(message: RegistrationUpload<CS>) -> Self {
-        Self(message)
+    /// Same as [`ServerLogin::finish`], but takes the serialized credential
+    /// finalization and reports the cause of a failure to `on_failure`
+    ///
+    /// Every failure is returned as [`ProtocolError::InvalidLoginError`], so
+    /// that the error can be passed on to the client without revealing which
+    /// check failed, while `on_failure` can e.g. log the [`LoginFailure`].
+    pub fn finish_with_diagnostics(
+        self,
+        input: &[u8],
+        on_failure: impl FnOnce(LoginFailure),
+    ) -> Result<ServerLoginFinishResult<CS>, ProtocolError> {
+        let message = match CredentialFinalization::deserialize(input) {
+            Ok(message) => message,
+            Err(error) => {
+                on_failure(LoginFailure::Deserialization(error));
+                return Err(ProtocolError::InvalidLoginError);
+            }
+        };
+
+        self.finish(message).map_err(|error| {
+            on_failure(match error {
+                ProtocolError::InvalidLoginError => LoginFailure::ClientMacError,
+                error => LoginFailure::KeyExchange(error),
+            });
+            ProtocolError::InvalidLoginError
+        })
     }
+}
+
+// Retrieval
+// =========
 
-    // Creates a dummy instance used for faking a [CredentialResponse]
-    pub(crate) fn dummy<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+pub(crate) type ClientRetrievalLen<CS: CipherSuite> =
+    Sum<<OprfGroup<CS> as Group>::ScalarLen, <OprfGroup<CS> as Group>::ElemLen>;
+
+#[cfg(feature = "server")]
+impl<'a, CS: CipherSuite> CredentialResponseBuilder<'a, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Masks `password_file`, or a fake record if it is `None`, for
+    /// `credential_request`, taking the same arguments as
+    /// [`ServerLogin::start`]
+    ///
+    /// # Errors
+    /// - [`ProtocolError::ProtocolVersionError`] if the
+    ///   [`peer_protocol_version`](ServerLoginStartParameters::peer_protocol_version)
+    ///   of `params` differs from [`PROTOCOL_VERSION`]
+    /// - [`ProtocolError::ProtocolVersionMissing`] if `params` has
+    ///   [`protocol_version_announced`](ServerLoginStartParameters::protocol_version_announced)
+    ///   set, but no `peer_protocol_version`
+    pub fn new<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
         rng: &mut R,
         server_setup: &ServerSetup<CS, S>,
-    ) -> Self {
-        Self(RegistrationUpload::dummy(rng, server_setup))
+        password_file: Option<ServerRegistration<CS>>,
+        credential_request: CredentialRequest<CS>,
+        credential_identifier: &'a [u8],
+        params: ServerLoginStartParameters<'a, 'a>,
+    ) -> Result<Self, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        Ok(Self {
+            credential_request,
+            record: MaskedRecord::new(
+                rng,
+                server_setup,
+                password_file,
+                credential_identifier,
+                params,
+            )?,
+        })
     }
-}
 
-// Login
-// =====
+    /// Returns the serialized blinded element of the credential request, which
+    /// is the input of the OPRF evaluation
+    pub fn blinded_element(&self) -> GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen> {
+        OprfGroup::<CS>::serialize_elem(self.credential_request.blinded_element.value())
+    }
 
-pub(crate) type ClientLoginLen<CS: CipherSuite> =
-    Sum<Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>, Ke1StateLen<CS>>;
+    /// Evaluates the OPRF in process, with the per-user OPRF key of the
+    /// password file, the [`oprf_key`](ServerLoginStartParameters::oprf_key)
+    /// of the parameters or else the key derived from the OPRF seed of
+    /// `server_setup`, and returns the serialized evaluated element
+    pub fn evaluate<S: SecretKey<CS::KeGroup>>(
+        &self,
+        server_setup: &ServerSetup<CS, S>,
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError> {
+        let oprf_key = self.record.oprf_key(server_setup)?;
+        let server = voprf::OprfServer::new_with_key(&oprf_key)?;
+        let evaluation_element = server.blind_evaluate(&self.credential_request.blinded_element);
 
-impl<CS: CipherSuite> ClientLogin<CS>
+        Ok(OprfGroup::<CS>::serialize_elem(evaluation_element.value()))
+    }
+
+    /// Outsources the OPRF evaluation to `evaluator`, and returns the
+    /// serialized evaluated element
+    ///
+    /// Neither the per-user OPRF key of the password file nor the
+    /// [`oprf_key`](ServerLoginStartParameters::oprf_key) of the parameters is
+    /// used, as the evaluator holds the keys.
+    pub async fn evaluate_with<E: OprfEvaluator<CS>>(
+        &self,
+        evaluator: &E,
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError<E::Error>> {
+        let blinded_element = self.blinded_element();
+
+        evaluator
+            .evaluate(&blinded_element, self.record.credential_identifier)
+            .await
+    }
+
+    /// Takes the serialized evaluation of
+    /// [`blinded_element`](Self::blinded_element) and runs the key exchange
+    ///
+    /// `server_setup` must be the same as passed to
+    /// [`CredentialResponseBuilder::new`]. As with [`ServerLogin::start`], the
+    /// client's static key is left out if
+    /// [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled.
+    ///
+    /// # Errors
+    /// [`ProtocolError::LibraryError`] if `evaluation_element` is not a valid
+    /// element of the group or is the identity element
+    pub fn finish<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        self,
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        evaluation_element: &[u8],
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let evaluation_element =
+            voprf::EvaluationElement::<CS::OprfCs>::deserialize(evaluation_element)
+                .map_err(|e| ProtocolError::into_custom(e.into()))?;
+        let ke2_prepared = CS::KeyExchange::prepare_ke2::<CS::OprfCs, _>(
+            rng,
+            CS::CLIENT_AUTHENTICATION.then(|| self.record.client_s_pk.clone()),
+            self.record.params.server_nonce,
+        )
+        .map_err(ProtocolError::into_custom)?;
+
+        self.record.complete(
+            ke2_prepared,
+            server_setup,
+            self.credential_request,
+            evaluation_element,
+        )
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> PreparedServerLogin<'_, CS>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -490,47 +2908,202 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    /// Serialization into bytes
-    pub fn serialize(&self) -> GenericArray<u8, ClientLoginLen<CS>>
+    /// Evaluates the OPRF for `credential_request` and runs the key exchange,
+    /// returning a challenge to be sent back to the client, as well as a
+    /// [`ServerLogin`]
+    ///
+    /// `server_setup` must be the same as passed to [`ServerLogin::prepare`].
+    pub fn complete<S: SecretKey<CS::KeGroup>>(
+        self,
+        server_setup: &ServerSetup<CS, S>,
+        credential_request: CredentialRequest<CS>,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
     where
-        // CredentialRequest: KgPk + Ke1Message
-        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
-        CredentialRequestLen<CS>: ArrayLength<u8>,
-        // ClientLogin: KgSk + CredentialRequest + Ke1State
-        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
-        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
-            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
-        ClientLoginLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        self.oprf_client
-            .serialize()
-            .concat(self.credential_request.serialize())
-            .concat(self.ke1_state.serialize())
+        let evaluation_element = self
+            .oprf_server
+            .blind_evaluate(&credential_request.blinded_element);
+
+        let result = self.record.complete(
+            self.ke2_prepared,
+            server_setup,
+            credential_request,
+            evaluation_element,
+        )?;
+        #[cfg(test)]
+        let result = ServerLoginStartResult {
+            oprf_key: self.oprf_key,
+            ..result
+        };
+
+        Ok(result)
     }
+}
 
-    /// Deserialization from bytes
-    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
-        let client_len = <OprfGroup<CS> as Group>::ScalarLen::USIZE;
-        let request_len = <OprfGroup<CS> as Group>::ElemLen::USIZE + Ke1MessageLen::<CS>::USIZE;
-        let state_len = Ke1StateLen::<CS>::USIZE;
-        let checked_slice =
-            check_slice_size(input, client_len + request_len + state_len, "client_login")?;
+#[cfg(feature = "server")]
+impl<'a, CS: CipherSuite> MaskedRecord<'a, CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn new<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        credential_identifier: &'a [u8],
+        params: ServerLoginStartParameters<'a, 'a>,
+    ) -> Result<Self, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let versioned_context = versioned_context::<CS>(
+            params.context,
+            params.protocol_version_announced,
+            params.peer_protocol_version,
+        )
+        .map_err(ProtocolError::into_custom)?;
+        let record = match password_file {
+            Some(x) => x,
+            None => ServerRegistration::fake(rng, server_setup, credential_identifier)
+                .map_err(ProtocolError::into_custom)?,
+        };
+
+        let client_s_pk = record.upload.client_s_pk.clone();
+        let server_s_pk = server_setup.keypair.private().public_key()?;
+
+        let masking_nonce = match params.masking_nonce {
+            Some(masking_nonce) => masking_nonce,
+            None => {
+                let mut masking_nonce = GenericArray::<_, NonceLen>::default();
+                fill_random(rng, &mut masking_nonce).map_err(ProtocolError::into_custom)?;
+                masking_nonce
+            }
+        };
+
+        let masked_response = MaskedResponse::mask(
+            &record.upload.masking_key,
+            masking_nonce.as_slice(),
+            &server_s_pk,
+            &record.upload.envelope,
+        )
+        .map_err(ProtocolError::into_custom)?;
 
-        let ke1_state =
-            <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE1State::deserialize(
-                &checked_slice[client_len + request_len..],
-            )?;
         Ok(Self {
-            oprf_client: voprf::OprfClient::deserialize(&checked_slice[..client_len])?,
-            credential_request: CredentialRequest::deserialize(
-                &checked_slice[client_len..client_len + request_len],
-            )?,
-            ke1_state,
+            credential_identifier,
+            params,
+            oprf_key: record.oprf_key.clone().map(Zeroizing::new),
+            versioned_context,
+            client_s_pk,
+            server_s_pk,
+            masking_nonce,
+            masked_response,
+        })
+    }
+
+    fn oprf_key<S: SecretKey<CS::KeGroup>>(
+        &self,
+        server_setup: &ServerSetup<CS, S>,
+    ) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError> {
+        select_oprf_key::<CS>(
+            self.oprf_key.as_deref(),
+            self.params.oprf_key,
+            &server_setup.oprf_seed,
+            self.credential_identifier,
+        )
+    }
+
+    fn complete<S: SecretKey<CS::KeGroup>>(
+        self,
+        ke2_prepared: <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2Prepared,
+        server_setup: &ServerSetup<CS, S>,
+        credential_request: CredentialRequest<CS>,
+        evaluation_element: voprf::EvaluationElement<CS::OprfCs>,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let context = self
+            .versioned_context
+            .as_deref()
+            .or(self.params.context)
+            .unwrap_or_default();
+
+        let (id_u, id_s) = bytestrings_from_identifiers::<CS::KeGroup>(
+            self.params.identifiers,
+            self.client_s_pk.serialize(),
+            self.server_s_pk.serialize(),
+        )
+        .map_err(ProtocolError::into_custom)?;
+
+        let blinded_element =
+            OprfGroup::<CS>::serialize_elem(credential_request.blinded_element.value());
+        let ke1_message = credential_request.ke1_message.serialize();
+        let credential_request_bytes =
+            CredentialRequest::<CS>::serialize_iter(&blinded_element, &ke1_message);
+
+        let beta = OprfGroup::<CS>::serialize_elem(evaluation_element.value());
+        let credential_response_component = CredentialResponse::<CS>::serialize_without_ke(
+            &beta,
+            &self.masking_nonce,
+            &self.masked_response,
+        );
+
+        let result = CS::KeyExchange::complete_ke2(
+            ke2_prepared,
+            credential_request_bytes,
+            credential_response_component,
+            credential_request.ke1_message.clone(),
+            server_setup.keypair.private().clone(),
+            id_u.iter(),
+            id_s.iter(),
+            context,
+        )?;
+
+        let credential_response = CredentialResponse {
+            evaluation_element,
+            masking_nonce: self.masking_nonce,
+            masked_response: self.masked_response,
+            ke2_message: result.1,
+        };
+
+        Ok(ServerLoginStartResult {
+            message: credential_response,
+            state: ServerLogin {
+                ke2_state: result.0,
+                client_s_pk: self.client_s_pk,
+            },
+            protocol_version: self
+                .params
+                .protocol_version_announced
+                .then_some(PROTOCOL_VERSION),
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
+            handshake_secret: result.2,
+            #[cfg(any(test, feature = "key-schedule-secrets"))]
+            server_mac_key: result.3,
+            #[cfg(test)]
+            oprf_key: GenericArray::default(),
         })
     }
 }
 
-impl<CS: CipherSuite> ClientLogin<CS>
+impl<CS: CipherSuite> ClientRetrieval<CS>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -539,26 +3112,60 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, ClientRetrievalLen<CS>>
+    where
+        // ClientRetrieval: KgSk + KgPk
+        <OprfGroup<CS> as Group>::ScalarLen: Add<<OprfGroup<CS> as Group>::ElemLen>,
+        ClientRetrievalLen<CS>: ArrayLength<u8>,
+    {
+        self.oprf_client
+            .serialize()
+            .concat(self.blinded_element.serialize())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let client_len = <OprfGroup<CS> as Group>::ScalarLen::USIZE;
+        let element_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let checked_slice = check_slice_size(input, client_len + element_len, "client_retrieval")?;
+
+        Ok(Self {
+            oprf_client: voprf::OprfClient::deserialize(&checked_slice[..client_len])?,
+            blinded_element: voprf::BlindedElement::deserialize(&checked_slice[client_len..])?,
+        })
+    }
+
+    /// Returns the [`RetrievalRequest`] produced by [`Self::start`], e.g. to
+    /// send it again after restoring the state with [`Self::deserialize`]
+    pub fn retrieval_request(&self) -> RetrievalRequest<CS> {
+        RetrievalRequest {
+            blinded_element: self.blinded_element.clone(),
+        }
+    }
+
     /// Returns an initial "blinded" password request to send to the server, as
-    /// well as a [`ClientLogin`]
+    /// well as a [`ClientRetrieval`]
+    ///
+    /// # Errors
+    /// - [`ProtocolError::PasswordTooShortError`] if `password` is shorter than
+    ///   [`CipherSuite::MIN_PASSWORD_LEN`]
+    /// - [`ProtocolError::PasswordTooLongError`] if `password` is longer than
+    ///   [`CipherSuite::MAX_PASSWORD_LEN`]
     pub fn start<R: RngCore + CryptoRng>(
         rng: &mut R,
         password: &[u8],
-    ) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
+    ) -> Result<ClientRetrievalStartResult<CS>, ProtocolError> {
+        check_password_len::<CS>(password)?;
         let blind_result = blind::<CS, _>(rng, password)?;
-        let (ke1_state, ke1_message) = CS::KeyExchange::generate_ke1::<CS::OprfCs, _>(rng)?;
-
-        let credential_request = CredentialRequest {
-            blinded_element: blind_result.message,
-            ke1_message,
-        };
 
-        Ok(ClientLoginStartResult {
-            message: credential_request.clone(),
+        Ok(ClientRetrievalStartResult {
+            message: RetrievalRequest {
+                blinded_element: blind_result.message.clone(),
+            },
             state: Self {
                 oprf_client: blind_result.state,
-                ke1_state,
-                credential_request,
+                blinded_element: blind_result.message,
             },
         })
     }
@@ -568,54 +3175,60 @@ where
     pub fn finish(
         self,
         password: &[u8],
-        credential_response: CredentialResponse<CS>,
-        params: ClientLoginFinishParameters<CS>,
-    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError>
+        retrieval_response: RetrievalResponse<CS>,
+        params: ClientRetrievalFinishParameters<CS>,
+    ) -> Result<ClientRetrievalFinishResult<CS>, ProtocolError>
     where
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
     {
         // Check if beta value from server is equal to alpha value from client
         if self
-            .credential_request
             .blinded_element
             .value()
-            .ct_eq(&credential_response.evaluation_element.value())
+            .ct_eq(&retrieval_response.evaluation_element.value())
             .into()
         {
             return Err(ProtocolError::ReflectedValueError);
         }
 
-        let (_, randomized_pwd_hasher) = get_password_derived_key::<CS>(
+        #[cfg_attr(
+            not(any(test, feature = "randomized-password")),
+            allow(unused_variables)
+        )]
+        let (randomized_pwd, randomized_pwd_hasher) = get_password_derived_key::<CS>(
             password,
             self.oprf_client.clone(),
-            credential_response.evaluation_element.clone(),
+            retrieval_response.evaluation_element,
             params.ksf,
+            params.ksf_progress,
         )?;
 
         let mut masking_key = Output::<OprfHash<CS>>::default();
-        randomized_pwd_hasher
-            .expand(STR_MASKING_KEY, &mut masking_key)
-            .map_err(|_| InternalError::HkdfError)?;
+        SuiteProvider::<CS>::expand(
+            &randomized_pwd_hasher,
+            &[CS::MASKING_KEY_LABEL],
+            &mut masking_key,
+        )?;
 
-        let (server_s_pk, envelope) = unmask_response::<CS>(
-            &masking_key,
-            &credential_response.masking_nonce,
-            &credential_response.masked_response,
-        )
-        .map_err(|e| match e {
-            ProtocolError::SerializationError => ProtocolError::InvalidLoginError,
-            err => err,
-        })?;
+        let (server_s_pk, envelope) = retrieval_response
+            .masked_response
+            .unmask(&masking_key, &retrieval_response.masking_nonce)
+            .map_err(|e| match e {
+                ProtocolError::SerializationError => ProtocolError::InvalidLoginError,
+                err => err,
+            })?;
 
         let opened_envelope = envelope
             .open(
-                randomized_pwd_hasher,
+                &randomized_pwd_hasher,
                 server_s_pk.clone(),
                 params.identifiers,
+                params.envelope_mode,
+                None,
             )
             .map_err(|e| match e {
                 ProtocolError::LibraryError(InternalError::SealOpenHmacError) => {
@@ -624,49 +3237,18 @@ where
                 err => err,
             })?;
 
-        let beta = OprfGroup::<CS>::serialize_elem(credential_response.evaluation_element.value());
-        let credential_response_component = CredentialResponse::<CS>::serialize_without_ke(
-            &beta,
-            &credential_response.masking_nonce,
-            &credential_response.masked_response,
-        );
-
-        let blinded_element =
-            OprfGroup::<CS>::serialize_elem(self.credential_request.blinded_element.value());
-        let ke1_message = self.credential_request.ke1_message.serialize();
-        let serialized_credential_request =
-            CredentialRequest::<CS>::serialize_iter(&blinded_element, &ke1_message);
-
-        let result = CS::KeyExchange::generate_ke3(
-            credential_response_component,
-            credential_response.ke2_message,
-            &self.ke1_state,
-            serialized_credential_request,
-            server_s_pk.clone(),
-            opened_envelope.client_static_keypair.private().clone(),
-            opened_envelope.id_u.iter(),
-            opened_envelope.id_s.iter(),
-            params.context.unwrap_or(&[]),
-        )?;
-
-        Ok(ClientLoginFinishResult {
-            message: CredentialFinalization {
-                ke3_message: result.1,
-            },
-            session_key: result.0,
+        Ok(ClientRetrievalFinishResult {
             export_key: opened_envelope.export_key,
             server_s_pk,
-            #[cfg(test)]
-            state: self,
-            #[cfg(test)]
-            handshake_secret: result.2,
-            #[cfg(test)]
-            client_mac_key: result.3,
+            app_data: opened_envelope.app_data,
+            #[cfg(any(test, feature = "randomized-password"))]
+            randomized_pwd,
         })
     }
 }
 
-impl<CS: CipherSuite> ServerLogin<CS>
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> ServerRetrieval<CS>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -675,136 +3257,357 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    /// Serialization into bytes
-    pub fn serialize(&self) -> GenericArray<u8, Ke2StateLen<CS>> {
-        self.ke2_state.serialize()
+    /// From the client's "blinded" password, returns the masked envelope to be
+    /// sent back to the client
+    ///
+    /// As in [`ServerLogin::start`], passing [`None`] as `password_file` for an
+    /// unregistered credential identifier returns an indistinguishable dummy
+    /// response, and the per-user OPRF key of a password file created by
+    /// [`ServerRegistration::finish_with_oprf_key`] is used instead of the OPRF
+    /// seed.
+    pub fn start<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        retrieval_request: RetrievalRequest<CS>,
+        credential_identifier: &[u8],
+    ) -> Result<ServerRetrievalStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        Self::start_inner(
+            rng,
+            server_setup,
+            password_file,
+            retrieval_request,
+            credential_identifier,
+            None,
+        )
     }
 
-    /// Deserialization from bytes
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
-        Ok(Self {
-            ke2_state:
-                <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State::deserialize(
-                    bytes,
-                )?,
-        })
+    /// Same as [`ServerRetrieval::start`], but evaluates the OPRF with the
+    /// `oprf_key` the credential was registered with, like
+    /// [`ServerLoginStartParameters::oprf_key`]
+    ///
+    /// This is meant for keys which are recomputed rather than stored, like
+    /// the ones of [`ServerSetup::peppered_oprf_key`]. The key of a password
+    /// file created by [`ServerRegistration::finish_with_oprf_key`] still takes
+    /// precedence.
+    pub fn start_with_oprf_key<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS, S>,
+        password_file: Option<ServerRegistration<CS>>,
+        retrieval_request: RetrievalRequest<CS>,
+        credential_identifier: &[u8],
+        oprf_key: &[u8],
+    ) -> Result<ServerRetrievalStartResult<CS>, ProtocolError<S::Error>>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        Self::start_inner(
+            rng,
+            server_setup,
+            password_file,
+            retrieval_request,
+            credential_identifier,
+            Some(oprf_key),
+        )
     }
 
-    /// From the client's "blinded" password, returns a challenge to be sent
-    /// back to the client, as well as a [`ServerLogin`]
-    pub fn start<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
+    fn start_inner<R: RngCore + CryptoRng, S: SecretKey<CS::KeGroup>>(
         rng: &mut R,
         server_setup: &ServerSetup<CS, S>,
         password_file: Option<ServerRegistration<CS>>,
-        credential_request: CredentialRequest<CS>,
+        retrieval_request: RetrievalRequest<CS>,
         credential_identifier: &[u8],
-        ServerLoginStartParameters {
-            context,
-            identifiers,
-        }: ServerLoginStartParameters,
-    ) -> Result<ServerLoginStartResult<CS>, ProtocolError<S::Error>>
+        oprf_key: Option<&[u8]>,
+    ) -> Result<ServerRetrievalStartResult<CS>, ProtocolError<S::Error>>
     where
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
     {
         let record = match password_file {
             Some(x) => x,
-            None => ServerRegistration::dummy(rng, server_setup),
+            None => ServerRegistration::fake(rng, server_setup, credential_identifier)
+                .map_err(ProtocolError::into_custom)?,
         };
 
-        let client_s_pk = record.0.client_s_pk.clone();
-        let context = context.unwrap_or(&[]);
-        let server_s_sk = server_setup.keypair.private();
-        let server_s_pk = server_s_sk.public_key()?;
+        let server_s_pk = server_setup.keypair.private().public_key()?;
 
         let mut masking_nonce = GenericArray::<_, NonceLen>::default();
-        rng.fill_bytes(&mut masking_nonce);
+        fill_random(rng, &mut masking_nonce).map_err(ProtocolError::into_custom)?;
 
-        let masked_response = mask_response(
-            &record.0.masking_key,
+        let masked_response = MaskedResponse::mask(
+            &record.upload.masking_key,
             masking_nonce.as_slice(),
             &server_s_pk,
-            &record.0.envelope,
-        )
-        .map_err(ProtocolError::into_custom)?;
-
-        let (id_u, id_s) = bytestrings_from_identifiers::<CS::KeGroup>(
-            identifiers,
-            client_s_pk.serialize(),
-            server_s_pk.serialize(),
+            &record.upload.envelope,
         )
         .map_err(ProtocolError::into_custom)?;
 
-        let blinded_element =
-            OprfGroup::<CS>::serialize_elem(credential_request.blinded_element.value());
-        let ke1_message = credential_request.ke1_message.serialize();
-        let credential_request_bytes =
-            CredentialRequest::<CS>::serialize_iter(&blinded_element, &ke1_message);
-
-        let oprf_key =
-            oprf_key_from_seed::<CS::OprfCs>(&server_setup.oprf_seed, credential_identifier)
-                .map_err(ProtocolError::into_custom)?;
+        let oprf_key = Zeroizing::new(
+            select_oprf_key::<CS>(
+                record.oprf_key.as_ref(),
+                oprf_key,
+                &server_setup.oprf_seed,
+                credential_identifier,
+            )
+            .map_err(ProtocolError::into_custom)?,
+        );
         let server = voprf::OprfServer::new_with_key(&oprf_key)
             .map_err(|e| ProtocolError::into_custom(e.into()))?;
-        let evaluation_element = server.blind_evaluate(&credential_request.blinded_element);
+        let evaluation_element = server.blind_evaluate(&retrieval_request.blinded_element);
 
-        let beta = OprfGroup::<CS>::serialize_elem(evaluation_element.value());
-        let credential_response_component =
-            CredentialResponse::<CS>::serialize_without_ke(&beta, &masking_nonce, &masked_response);
+        Ok(ServerRetrievalStartResult {
+            message: RetrievalResponse {
+                evaluation_element,
+                masking_nonce,
+                masked_response,
+            },
+        })
+    }
+}
 
-        let result = CS::KeyExchange::generate_ke2::<CS::OprfCs, _, _>(
-            rng,
-            credential_request_bytes,
-            credential_response_component,
-            credential_request.ke1_message.clone(),
-            client_s_pk,
-            server_s_sk.clone(),
-            id_u.iter(),
-            id_s.iter(),
-            context,
-        )?;
+// Migration
+// =========
 
-        let credential_response = CredentialResponse {
-            evaluation_element,
-            masking_nonce,
-            masked_response,
-            ke2_message: result.1,
-        };
+impl<CS: CipherSuite> ClientLoginFinishResult<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Starts a fresh registration under the cipher suite `NCS` after a
+    /// successful login, in order to migrate the credential record to `NCS`
+    /// without a password reset
+    ///
+    /// The [`RegistrationRequest`] is authenticated with
+    /// [`ClientMigrationStartResult::mac`], under a key derived from the
+    /// session key of this login, and both are sent to the server, which
+    /// continues with [`ServerLoginFinishResult::start_migration`]. The
+    /// registration is then finished as usual with [`ClientRegistration::finish`],
+    /// and the [`RegistrationUpload`] is sent along with the MAC of
+    /// [`ClientLoginFinishResult::finish_migration`].
+    pub fn start_migration<NCS: CipherSuite, R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        password: &[u8],
+    ) -> Result<ClientMigrationStartResult<CS, NCS>, ProtocolError>
+    where
+        <OprfHash<NCS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<NCS> as BlockSizeUser>::BlockSize>,
+        OprfHash<NCS>: Hash,
+        <OprfHash<NCS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let ClientRegistrationStartResult { message, state } =
+            ClientRegistration::<NCS>::start(rng, password)?;
+        let mac = migration_mac::<CS>(
+            &self.session_key,
+            STR_MIGRATION_REQUEST,
+            &message.serialize(),
+        )?;
 
-        Ok(ServerLoginStartResult {
-            message: credential_response,
-            state: Self {
-                ke2_state: result.0,
-            },
-            #[cfg(test)]
-            handshake_secret: result.2,
-            #[cfg(test)]
-            server_mac_key: result.3,
-            #[cfg(test)]
-            oprf_key: GenericArray::clone_from_slice(&oprf_key),
+        Ok(ClientMigrationStartResult {
+            message,
+            mac: SuiteProvider::<CS>::finalize_mac(mac),
+            state,
         })
     }
 
-    /// From the client's second and final message, check the client's
-    /// authentication and produce a message transport
-    pub fn finish(
-        self,
-        message: CredentialFinalization<CS>,
-    ) -> Result<ServerLoginFinishResult<CS>, ProtocolError> {
-        let session_key = <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::finish_ke(
-            message.ke3_message,
-            &self.ke2_state,
+    /// Returns the MAC to send along with the [`RegistrationUpload`] of a
+    /// migration started with [`ClientLoginFinishResult::start_migration`],
+    /// which the server checks in [`ServerLoginFinishResult::finish_migration`]
+    pub fn finish_migration<NCS: CipherSuite>(
+        &self,
+        message: &RegistrationUpload<NCS>,
+    ) -> Result<Output<OprfHash<CS>>, ProtocolError>
+    where
+        <OprfHash<NCS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<NCS> as BlockSizeUser>::BlockSize>,
+        OprfHash<NCS>: Hash,
+        <OprfHash<NCS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<NCS>>,
+        Sum<NonceLen, MacLen<NCS>>: ArrayLength<u8> + Add<NCS::AppDataLen>,
+        EnvelopeLen<NCS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <NCS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<NCS>>>,
+        Sum<<NCS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<NCS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<NCS>>,
+        RegistrationUploadLen<NCS>: ArrayLength<u8>,
+    {
+        let mac = migration_mac::<CS>(
+            &self.session_key,
+            STR_MIGRATION_UPLOAD,
+            &message.serialize(),
         )?;
 
-        Ok(ServerLoginFinishResult {
-            session_key,
-            #[cfg(test)]
-            state: self,
-        })
+        Ok(SuiteProvider::<CS>::finalize_mac(mac))
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> ServerLoginFinishResult<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Answers a registration request under the cipher suite `NCS` that the
+    /// client started with [`ClientLoginFinishResult::start_migration`]
+    ///
+    /// # Errors
+    /// [`ProtocolError::MigrationError`] if `mac` is not the
+    /// [`ClientMigrationStartResult::mac`] of `message` for the session key of
+    /// this login, or if [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled for
+    /// `CS`, as the login then doesn't prove knowledge of the password.
+    pub fn start_migration<NCS: CipherSuite, S: SecretKey<NCS::KeGroup>>(
+        &self,
+        server_setup: &ServerSetup<NCS, S>,
+        message: RegistrationRequest<NCS>,
+        mac: &[u8],
+        credential_identifier: &[u8],
+    ) -> Result<ServerRegistrationStartResult<NCS>, ProtocolError>
+    where
+        <OprfHash<NCS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<NCS> as BlockSizeUser>::BlockSize>,
+        OprfHash<NCS>: Hash,
+        <OprfHash<NCS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        if !CS::CLIENT_AUTHENTICATION {
+            return Err(ProtocolError::MigrationError);
+        }
+
+        verify_migration_mac::<CS>(
+            &self.session_key,
+            STR_MIGRATION_REQUEST,
+            &message.serialize(),
+            mac,
+        )?;
+
+        ServerRegistration::<NCS>::start(server_setup, message, credential_identifier)
+    }
+
+    /// Finishes a migration with the [`RegistrationUpload`] of the client and
+    /// the MAC of [`ClientLoginFinishResult::finish_migration`]
+    ///
+    /// The resulting [`ServerRegistration`] should replace the previous
+    /// record.
+    ///
+    /// # Errors
+    /// [`ProtocolError::MigrationError`] if `mac` is not the MAC of `message`
+    /// for the session key of this login, or if
+    /// [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled for `CS`.
+    pub fn finish_migration<NCS: CipherSuite>(
+        &self,
+        message: RegistrationUpload<NCS>,
+        mac: &[u8],
+    ) -> Result<ServerRegistration<NCS>, ProtocolError>
+    where
+        <OprfHash<NCS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<NCS> as BlockSizeUser>::BlockSize>,
+        OprfHash<NCS>: Hash,
+        <OprfHash<NCS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<NCS>>,
+        Sum<NonceLen, MacLen<NCS>>: ArrayLength<u8> + Add<NCS::AppDataLen>,
+        EnvelopeLen<NCS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <NCS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<NCS>>>,
+        Sum<<NCS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<NCS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<NCS>>,
+        RegistrationUploadLen<NCS>: ArrayLength<u8>,
+    {
+        if !CS::CLIENT_AUTHENTICATION {
+            return Err(ProtocolError::MigrationError);
+        }
+
+        verify_migration_mac::<CS>(
+            &self.session_key,
+            STR_MIGRATION_UPLOAD,
+            &message.serialize(),
+            mac,
+        )?;
+
+        Ok(ServerRegistration::finish(message))
+    }
+}
+
+// Starts the MAC of a registration message of a migration, under a key
+// exported from the session key of the login with a reserved label per message,
+// so that the messages can't be sent outside of that login
+fn migration_mac<CS: CipherSuite>(
+    session_key: &GenericArray<u8, SessionKeyLen<CS>>,
+    label: &[u8],
+    message: &[u8],
+) -> Result<<SuiteProvider<CS> as Provider<OprfHash<CS>>>::Mac, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut key = Zeroizing::new(Output::<OprfHash<CS>>::default());
+    Exporter::<CS>::new(session_key)?.export_reserved(label, &[], &mut key)?;
+    let mut mac = SuiteProvider::<CS>::new_mac(&key)?;
+    SuiteProvider::<CS>::update_mac(&mut mac, message);
+
+    Ok(mac)
+}
+
+#[cfg(feature = "server")]
+fn verify_migration_mac<CS: CipherSuite>(
+    session_key: &GenericArray<u8, SessionKeyLen<CS>>,
+    label: &[u8],
+    message: &[u8],
+    mac: &[u8],
+) -> Result<(), ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    // `verify_mac` accepts truncated tags, which a migration never sends
+    if mac.len() != OutputSize::<OprfHash<CS>>::USIZE
+        || !bool::from(SuiteProvider::<CS>::verify_mac(
+            migration_mac::<CS>(session_key, label, message)?,
+            mac,
+        ))
+    {
+        return Err(ProtocolError::MigrationError);
     }
+
+    Ok(())
 }
 
 /////////////////////////
@@ -821,6 +3624,99 @@ pub struct Identifiers<'a> {
     pub server: Option<&'a [u8]>,
 }
 
+/// Identifiers of at most `N` bytes each, owned in a [`heapless::Vec`]
+///
+/// Unlike [`Identifiers`], which borrows them, these can be kept next to the
+/// [`ClientRegistration`] or [`ClientLogin`] between protocol steps without an
+/// allocator, e.g. on microcontrollers.
+#[cfg(feature = "heapless")]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct HeaplessIdentifiers<const N: usize> {
+    /// Client identifier
+    pub client: Option<heapless::Vec<u8, N>>,
+    /// Server identifier
+    pub server: Option<heapless::Vec<u8, N>>,
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> HeaplessIdentifiers<N> {
+    /// Copies the borrowed `identifiers`
+    ///
+    /// # Errors
+    /// [`InternalError::SizeError`] if an identifier is longer than `N` bytes
+    pub fn new(identifiers: Identifiers<'_>) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            client: identifiers
+                .client
+                .map(|client| heapless_copy(client, "client_identifier"))
+                .transpose()?,
+            server: identifiers
+                .server
+                .map(|server| heapless_copy(server, "server_identifier"))
+                .transpose()?,
+        })
+    }
+
+    /// Borrows the identifiers, e.g. for
+    /// [`ClientLoginFinishParameters::identifiers`]
+    pub fn as_identifiers(&self) -> Identifiers<'_> {
+        Identifiers {
+            client: self.client.as_deref(),
+            server: self.server.as_deref(),
+        }
+    }
+}
+
+/// A context of at most `N` bytes, owned in a [`heapless::Vec`], see
+/// [`ClientLoginFinishParameters::context`]
+#[cfg(feature = "heapless")]
+pub type HeaplessContext<const N: usize> = heapless::Vec<u8, N>;
+
+/// Copies `context` into a [`HeaplessContext`]
+///
+/// # Errors
+/// [`InternalError::SizeError`] if `context` is longer than `N` bytes
+#[cfg(feature = "heapless")]
+pub fn heapless_context<const N: usize>(
+    context: &[u8],
+) -> Result<HeaplessContext<N>, ProtocolError> {
+    heapless_copy(context, "context")
+}
+
+#[cfg(feature = "heapless")]
+fn heapless_copy<const N: usize>(
+    input: &[u8],
+    name: &'static str,
+) -> Result<heapless::Vec<u8, N>, ProtocolError> {
+    heapless::Vec::from_slice(input).map_err(|()| {
+        InternalError::SizeError {
+            name,
+            len: N,
+            actual_len: input.len(),
+        }
+        .into()
+    })
+}
+
+/// The server identity that was used to open the envelope during login
+///
+/// Both are authenticated by the envelope, which fails to open unless the
+/// server identity is the one given during registration.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ServerIdentity {
+    /// The server identifier supplied in [`Identifiers::server`]
+    Supplied,
+    /// The serialized server public key, as no server identifier was supplied
+    PublicKey,
+}
+
+/// The protocol version run by this crate, which parties announce to each
+/// other outside of the messages, e.g. in a header of the transport
+///
+/// See [`ClientLoginFinishParameters::peer_protocol_version`] and
+/// [`ServerLoginStartParameters::peer_protocol_version`].
+pub const PROTOCOL_VERSION: u16 = 1;
+
 /// Optional parameters for client registration finish
 #[derive_where(Clone, Default)]
 pub struct ClientRegistrationFinishParameters<'i, 'h, CS: CipherSuite>
@@ -836,6 +3732,42 @@ where
     pub identifiers: Identifiers<'i>,
     /// Specifying a configuration for the key stretching function
     pub ksf: Option<&'h CS::Ksf>,
+    /// Observes the progress of the key stretching function and allows
+    /// cancelling it. Plain Argon2 can only be cancelled before and after
+    /// hashing, see [`KsfProgress`].
+    pub ksf_progress: Option<&'h dyn KsfProgress>,
+    /// Application data to be sealed inside the envelope, which is recovered
+    /// in [`ClientLoginFinishResult::app_data`] upon login
+    pub app_data: GenericArray<u8, CS::AppDataLen>,
+    /// An existing private key of the client to be encrypted inside the
+    /// envelope, instead of deriving the client's keypair from the password
+    ///
+    /// This selects [`EnvelopeMode::External`], which must then also be
+    /// specified upon login. The key takes up the first `SkLen` bytes of the
+    /// application data, which are overwritten in
+    /// [`app_data`](Self::app_data), so [`CipherSuite::AppDataLen`] must be at
+    /// least `SkLen`.
+    pub client_private_key: Option<PrivateKey<CS::KeGroup>>,
+    /// The public key of a private key of the client that is held outside of
+    /// this crate, such as in a secure enclave, instead of deriving the
+    /// client's keypair from the password
+    ///
+    /// Only the public key is bound to the envelope, and the login has to be
+    /// finished with [`ClientLogin::finish_with_secret_key`] or
+    /// [`ClientLogin::finish_with_async_secret_key`]. This can't be combined
+    /// with [`client_private_key`](Self::client_private_key).
+    pub client_public_key: Option<PublicKey<CS::KeGroup>>,
+    /// Specifying the nonce of the envelope, instead of drawing it from the RNG
+    /// passed to [`ClientRegistration::finish`], available with the
+    /// `envelope-nonce` feature
+    ///
+    /// Finishing the registration again with the same password, server and
+    /// nonce produces the same [`RegistrationUpload`], so that a retried
+    /// registration can't leave two different records behind. The nonce must
+    /// otherwise be uniformly random, and must not be used for registrations
+    /// with a different password or server.
+    #[cfg(any(test, feature = "envelope-nonce"))]
+    pub envelope_nonce: Option<GenericArray<u8, U32>>,
 }
 
 impl<'i, 'h, CS: CipherSuite> ClientRegistrationFinishParameters<'i, 'h, CS>
@@ -849,10 +3781,47 @@ where
 {
     /// Create a new [`ClientRegistrationFinishParameters`]
     pub fn new(identifiers: Identifiers<'i>, ksf: Option<&'h CS::Ksf>) -> Self {
-        Self { identifiers, ksf }
+        Self {
+            identifiers,
+            ksf,
+            ksf_progress: None,
+            app_data: GenericArray::default(),
+            client_private_key: None,
+            client_public_key: None,
+            #[cfg(any(test, feature = "envelope-nonce"))]
+            envelope_nonce: None,
+        }
     }
 }
 
+/// Contains the fields that are returned by
+/// [`ClientLoginFinishResult::start_migration`]
+#[derive_where(Clone)]
+pub struct ClientMigrationStartResult<CS: CipherSuite, NCS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    <OprfHash<NCS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<NCS> as BlockSizeUser>::BlockSize>,
+    OprfHash<NCS>: Hash,
+    <OprfHash<NCS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<NCS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The registration request message to be sent to the server
+    pub message: RegistrationRequest<NCS>,
+    /// The MAC of [`Self::message`] to be sent to the server, under a key
+    /// derived from the session key of the login
+    pub mac: Output<OprfHash<CS>>,
+    /// The client state that must be persisted in order to complete
+    /// registration
+    pub state: ClientRegistration<NCS>,
+}
+
 /// Contains the fields that are returned by a client registration start
 #[derive_where(Clone)]
 pub struct ClientRegistrationStartResult<CS: CipherSuite>
@@ -888,20 +3857,53 @@ where
     pub export_key: Output<OprfHash<CS>>,
     /// The server's static public key
     pub server_s_pk: PublicKey<CS::KeGroup>,
-    /// Instance of the ClientRegistration, only used in tests for checking
+    /// The parameters of the key stretching function used during
+    /// registration, which suites that set
+    /// [`CipherSuite::ATTEST_KSF_PARAMETERS`] also carry in the `message`
+    pub ksf_parameters: KsfParameters,
+    /// Instance of the [`ClientRegistration`], only used in tests for checking
     /// zeroize
     #[cfg(test)]
     pub state: ClientRegistration<CS>,
-    /// AuthKey, only used in tests
+    /// `AuthKey`, only used in tests
     #[cfg(test)]
     pub auth_key: Output<OprfHash<CS>>,
-    /// Password derived key, only used in tests
-    #[cfg(test)]
+    /// The randomized password, i.e. the OPRF output hardened by the key
+    /// stretching function, available with the `randomized-password` feature
+    ///
+    /// **Warning:** every other client secret, including the envelope keys and
+    /// the export key, is derived from this value, so whoever learns it can
+    /// authenticate as the client. It must never leave the client, and should
+    /// only be used as the input keying material of a KDF with a distinct
+    /// label. Prefer the export key whenever it is sufficient.
+    #[cfg(any(test, feature = "randomized-password"))]
     pub randomized_pwd: Output<OprfHash<CS>>,
 }
 
+/// Contains the fields that are returned by
+/// [`ServerRegistration::deserialize_versioned`]
+#[cfg(feature = "server")]
+#[derive_where(Clone)]
+#[derive_where(Debug; <CS::KeGroup as KeGroup>::Pk)]
+pub struct ServerRegistrationReadResult<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The deserialized record
+    pub record: ServerRegistration<CS>,
+    /// Whether the record was stored in an older format, and should be written
+    /// back with [`ServerRegistration::serialize_versioned`]
+    pub upgraded: bool,
+}
+
 /// Contains the fields that are returned by a server registration start. Note
 /// that there is no state output in this step
+#[cfg(feature = "server")]
 #[derive_where(Clone)]
 pub struct ServerRegistrationStartResult<CS: CipherSuite>
 where
@@ -954,6 +3956,38 @@ where
     pub identifiers: Identifiers<'i>,
     /// Specifying a configuration for the key stretching hash
     pub ksf: Option<&'h CS::Ksf>,
+    /// Observes the progress of the key stretching hash and allows cancelling
+    /// it. Plain Argon2 can only be cancelled before and after hashing, see
+    /// [`KsfProgress`].
+    pub ksf_progress: Option<&'h dyn KsfProgress>,
+    /// The mode in which the envelope was sealed during registration
+    pub envelope_mode: EnvelopeMode,
+    /// The current time and the maximum age of the [`ClientLogin`], in the
+    /// same unit as the time given to [`ClientLogin::serialize_timed`]
+    ///
+    /// If set, finishing fails with [`ProtocolError::ExpiredStateError`] if
+    /// the state is older, or has no creation time because it was not
+    /// restored with [`ClientLogin::deserialize_timed`].
+    pub max_state_age: Option<(u64, u64)>,
+    /// Verifies the server identity, e.g. a certificate given as
+    /// [`Identifiers::server`], against the server public key after the
+    /// envelope was opened
+    ///
+    /// If set, finishing fails with [`ProtocolError::ServerIdentityError`] if
+    /// the verifier rejects the server identity.
+    pub server_identity_verifier: Option<&'h dyn ServerIdentityVerifier<CS::KeGroup>>,
+    /// The protocol version announced by the server
+    ///
+    /// If set, finishing fails with [`ProtocolError::ProtocolVersionError`] if
+    /// it differs from [`PROTOCOL_VERSION`].
+    pub peer_protocol_version: Option<u16>,
+    /// Whether the client announced [`PROTOCOL_VERSION`] to the server
+    ///
+    /// If set, finishing fails with [`ProtocolError::ProtocolVersionMissing`]
+    /// if `peer_protocol_version` isn't set, and otherwise
+    /// [`PROTOCOL_VERSION`] is bound into the transcript, so that the login
+    /// fails if the announcements were altered.
+    pub protocol_version_announced: bool,
 }
 
 impl<'c, 'i, 'h, CS: CipherSuite> ClientLoginFinishParameters<'c, 'i, 'h, CS>
@@ -975,6 +4009,12 @@ where
             context,
             identifiers,
             ksf,
+            ksf_progress: None,
+            envelope_mode: EnvelopeMode::Internal,
+            max_state_age: None,
+            server_identity_verifier: None,
+            peer_protocol_version: None,
+            protocol_version_announced: false,
         }
     }
 }
@@ -992,27 +4032,66 @@ where
 {
     /// The message to send to the server to complete the protocol
     pub message: CredentialFinalization<CS>,
-    /// The session key
-    pub session_key: Output<OprfHash<CS>>,
+    /// The session key, of the length selected by the
+    /// [`KeyExchange`](CipherSuite::KeyExchange)
+    pub session_key: GenericArray<u8, SessionKeyLen<CS>>,
     /// The client-side export key
     pub export_key: Output<OprfHash<CS>>,
     /// The server's static public key
     pub server_s_pk: PublicKey<CS::KeGroup>,
-    /// Instance of the ClientLogin, only used in tests for checking zeroize
+    /// The client's ephemeral public key of the key exchange
+    ///
+    /// The ephemeral public keys aren't secret, as they are sent in the clear,
+    /// and are the same on both sides of a login, e.g. for binding the login
+    /// into an audit log.
+    pub client_e_pk: PublicKey<CS::KeGroup>,
+    /// The server's ephemeral public key of the key exchange
+    pub server_e_pk: PublicKey<CS::KeGroup>,
+    /// The application data that was sealed inside the envelope during
+    /// registration, see [`ClientRegistrationFinishParameters::app_data`]
+    pub app_data: GenericArray<u8, CS::AppDataLen>,
+    /// The server identity used to open the envelope, i.e. the supplied server
+    /// identifier or the server public key in [`Self::server_s_pk`]
+    pub server_identity: ServerIdentity,
+    /// [`PROTOCOL_VERSION`], if it was bound into the transcript because of
+    /// [`ClientLoginFinishParameters::protocol_version_announced`]
+    pub protocol_version: Option<u16>,
+    /// The randomized password, i.e. the OPRF output hardened by the key
+    /// stretching function, available with the `randomized-password` feature
+    ///
+    /// **Warning:** every other client secret, including the envelope keys and
+    /// the export key, is derived from this value, so whoever learns it can
+    /// authenticate as the client. It must never leave the client, and should
+    /// only be used as the input keying material of a KDF with a distinct
+    /// label. Prefer the export key whenever it is sufficient.
+    #[cfg(any(test, feature = "randomized-password"))]
+    pub randomized_pwd: Output<OprfHash<CS>>,
+    /// Instance of the [`ClientLogin`], only used in tests for checking zeroize
     #[cfg(test)]
     pub state: ClientLogin<CS>,
-    /// Handshake secret, only used in tests
-    #[cfg(test)]
+    /// The handshake secret of the key schedule, available with the
+    /// `key-schedule-secrets` feature
+    ///
+    /// **Warning:** this value is only meant for implementing extensions of the
+    /// key exchange, and must never be used as a session key or sent over the
+    /// wire. Prefer [`ClientLoginFinishResult::session_key`] or the export key
+    /// whenever they are sufficient.
+    #[cfg(any(test, feature = "key-schedule-secrets"))]
     pub handshake_secret: Output<OprfHash<CS>>,
-    /// Client MAC key, only used in tests
-    #[cfg(test)]
+    /// The client MAC key of the key schedule, authenticating
+    /// [`CredentialFinalization`], available with the `key-schedule-secrets`
+    /// feature
+    ///
+    /// The same warning as for `handshake_secret` applies.
+    #[cfg(any(test, feature = "key-schedule-secrets"))]
     pub client_mac_key: Output<OprfHash<CS>>,
 }
 
 /// Contains the fields that are returned by a server login finish
+#[cfg(feature = "server")]
 #[derive_where(Clone)]
-#[cfg_attr(not(test), derive_where(Debug))]
-#[cfg_attr(test, derive_where(Debug; ServerLogin<CS>))]
+#[cfg_attr(not(test), derive_where(Debug; <CS::KeGroup as KeGroup>::Pk))]
+#[cfg_attr(test, derive_where(Debug; <CS::KeGroup as KeGroup>::Pk, ServerLogin<CS>))]
 pub struct ServerLoginFinishResult<CS: CipherSuite>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1022,31 +4101,103 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    /// The session key between client and server
-    pub session_key: Output<OprfHash<CS>>,
-    /// Instance of the ClientRegistration, only used in tests for checking
+    /// The session key between client and server, of the length selected by
+    /// the [`KeyExchange`](CipherSuite::KeyExchange)
+    pub session_key: GenericArray<u8, SessionKeyLen<CS>>,
+    /// The client's static public key, taken from the password file and
+    /// authenticated by the client's final message
+    ///
+    /// Unless a client identifier was set in
+    /// [`ServerLoginStartParameters::identifiers`], its serialization is also
+    /// the authenticated client identity. For a dummy password file, login
+    /// never succeeds, so this key is never returned.
+    ///
+    /// This is [`None`] if [`CipherSuite::CLIENT_AUTHENTICATION`] is disabled:
+    /// the client's final message then doesn't prove knowledge of the
+    /// password, and [`ServerLogin::finish`] also succeeds for a dummy
+    /// password file.
+    pub client_s_pk: Option<PublicKey<CS::KeGroup>>,
+    /// The client's ephemeral public key of the key exchange, see
+    /// [`ClientLoginFinishResult::client_e_pk`]
+    pub client_e_pk: PublicKey<CS::KeGroup>,
+    /// The server's ephemeral public key of the key exchange
+    pub server_e_pk: PublicKey<CS::KeGroup>,
+    /// Instance of the [`ClientRegistration`], only used in tests for checking
     /// zeroize
     #[cfg(test)]
     pub state: ServerLogin<CS>,
 }
 
 /// Optional parameters for server login start
-#[derive(Clone, Debug, Default)]
+#[cfg(feature = "server")]
+#[derive(Clone, Default)]
 pub struct ServerLoginStartParameters<'c, 'i> {
     /// Specifying a context field that the client must agree on
     pub context: Option<&'c [u8]>,
     /// Specifying a user identifier and server identifier that will be matched
     /// against the client
     pub identifiers: Identifiers<'i>,
+    /// Specifying the masking nonce of the credential response, instead of
+    /// drawing it from the RNG passed to [`ServerLogin::start`]
+    ///
+    /// Nonces must be uniformly random and never reused, e.g. drawn from a DRBG
+    /// seeded inside an HSM.
+    pub masking_nonce: Option<GenericArray<u8, U32>>,
+    /// Specifying the server nonce of the key exchange, instead of drawing it
+    /// from the RNG passed to [`ServerLogin::start`]
+    ///
+    /// The same requirements as for `masking_nonce` apply.
+    pub server_nonce: Option<GenericArray<u8, U32>>,
+    /// Specifying the OPRF key the credential was registered with by
+    /// [`ServerRegistration::start_with_oprf_key`], instead of deriving it from
+    /// the OPRF seed of the [`ServerSetup`]
+    ///
+    /// This is meant for keys which can be recomputed but are not stored, like
+    /// the ones of [`ServerSetup::peppered_oprf_key`]. A password file created
+    /// by [`ServerRegistration::finish_with_oprf_key`] carries its own key,
+    /// which takes precedence over this one.
+    pub oprf_key: Option<&'c [u8]>,
+    /// The protocol version announced by the client
+    ///
+    /// If set, starting fails with [`ProtocolError::ProtocolVersionError`] if
+    /// it differs from [`PROTOCOL_VERSION`].
+    pub peer_protocol_version: Option<u16>,
+    /// Whether the server announced [`PROTOCOL_VERSION`] to the client
+    ///
+    /// If set, starting fails with [`ProtocolError::ProtocolVersionMissing`]
+    /// if `peer_protocol_version` isn't set, and otherwise
+    /// [`PROTOCOL_VERSION`] is bound into the transcript, so that the login
+    /// fails if the announcements were altered.
+    pub protocol_version_announced: bool,
+}
+
+#[cfg(feature = "server")]
+impl Debug for ServerLoginStartParameters<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The OPRF key is omitted, as it is secret
+        f.debug_struct("ServerLoginStartParameters")
+            .field("context", &self.context)
+            .field("identifiers", &self.identifiers)
+            .field("masking_nonce", &self.masking_nonce)
+            .field("server_nonce", &self.server_nonce)
+            .field("peer_protocol_version", &self.peer_protocol_version)
+            .field(
+                "protocol_version_announced",
+                &self.protocol_version_announced,
+            )
+            .finish_non_exhaustive()
+    }
 }
 
 /// Contains the fields that are returned by a server login start
+#[cfg(feature = "server")]
 #[derive_where(Clone)]
 #[derive_where(
     Debug;
     voprf::EvaluationElement<CS::OprfCs>,
     <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2Message,
     <CS::KeyExchange as KeyExchange<OprfHash<CS>, CS::KeGroup>>::KE2State,
+    <CS::KeGroup as KeGroup>::Pk,
 )]
 pub struct ServerLoginStartResult<CS: CipherSuite>
 where
@@ -1061,17 +4212,121 @@ where
     pub message: CredentialResponse<CS>,
     /// The state that the server must keep in order to finish the protocl
     pub state: ServerLogin<CS>,
-    /// Handshake secret, only used in tests
-    #[cfg(test)]
+    /// [`PROTOCOL_VERSION`], if it was bound into the transcript because of
+    /// [`ServerLoginStartParameters::protocol_version_announced`]
+    ///
+    /// It isn't part of the state, but [`ServerLogin::finish`] only succeeds
+    /// if the client bound the same version into the transcript.
+    pub protocol_version: Option<u16>,
+    /// The handshake secret of the key schedule, available with the
+    /// `key-schedule-secrets` feature
+    ///
+    /// **Warning:** this value is only meant for implementing extensions of the
+    /// key exchange, and must never be used as a session key or sent over the
+    /// wire.
+    #[cfg(any(test, feature = "key-schedule-secrets"))]
     pub handshake_secret: Output<OprfHash<CS>>,
-    /// Server MAC key, only used in tests
-    #[cfg(test)]
+    /// The server MAC key of the key schedule, authenticating
+    /// [`CredentialResponse`], available with the `key-schedule-secrets`
+    /// feature
+    ///
+    /// The same warning as for `handshake_secret` applies.
+    #[cfg(any(test, feature = "key-schedule-secrets"))]
     pub server_mac_key: Output<OprfHash<CS>>,
     /// OPRF key, only used in tests
     #[cfg(test)]
     pub oprf_key: GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>,
 }
 
+/// Contains the fields that are returned by a client retrieval start
+#[derive_where(Clone)]
+pub struct ClientRetrievalStartResult<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The message to send to the server to begin the retrieval
+    pub message: RetrievalRequest<CS>,
+    /// The state that the client must keep in order to complete the retrieval
+    pub state: ClientRetrieval<CS>,
+}
+
+/// Optional parameters for client retrieval finish
+#[derive_where(Clone, Default)]
+pub struct ClientRetrievalFinishParameters<'i, 'h, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Specifying a user identifier and server identifier that were used to
+    /// seal the envelope during registration
+    pub identifiers: Identifiers<'i>,
+    /// Specifying a configuration for the key stretching hash
+    pub ksf: Option<&'h CS::Ksf>,
+    /// Observes the progress of the key stretching hash and allows cancelling
+    /// it. Plain Argon2 can only be cancelled before and after hashing, see
+    /// [`KsfProgress`].
+    pub ksf_progress: Option<&'h dyn KsfProgress>,
+    /// The mode in which the envelope was sealed during registration
+    pub envelope_mode: EnvelopeMode,
+}
+
+/// Contains the fields that are returned by a client retrieval finish
+#[derive_where(Clone)]
+pub struct ClientRetrievalFinishResult<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The client-side export key
+    pub export_key: Output<OprfHash<CS>>,
+    /// The server's static public key
+    pub server_s_pk: PublicKey<CS::KeGroup>,
+    /// The application data that was sealed inside the envelope during
+    /// registration, see [`ClientRegistrationFinishParameters::app_data`]
+    pub app_data: GenericArray<u8, CS::AppDataLen>,
+    /// The randomized password, i.e. the OPRF output hardened by the key
+    /// stretching function, available with the `randomized-password` feature
+    ///
+    /// **Warning:** every other client secret, including the envelope keys and
+    /// the export key, is derived from this value, so whoever learns it can
+    /// authenticate as the client. It must never leave the client, and should
+    /// only be used as the input keying material of a KDF with a distinct
+    /// label. Prefer the export key whenever it is sufficient.
+    #[cfg(any(test, feature = "randomized-password"))]
+    pub randomized_pwd: Output<OprfHash<CS>>,
+}
+
+/// Contains the fields that are returned by a server retrieval start. Note
+/// that there is no state output in this step
+#[cfg(feature = "server")]
+#[derive_where(Clone)]
+#[derive_where(Debug; voprf::EvaluationElement<CS::OprfCs>)]
+pub struct ServerRetrievalStartResult<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The message to send back to the client
+    pub message: RetrievalResponse<CS>,
+}
+
 ////////////////////////////////////////////////
 // Helper functions and Trait Implementations //
 // ========================================== //
@@ -1084,7 +4339,8 @@ fn get_password_derived_key<CS: CipherSuite>(
     oprf_client: voprf::OprfClient<CS::OprfCs>,
     evaluation_element: voprf::EvaluationElement<CS::OprfCs>,
     ksf: Option<&CS::Ksf>,
-) -> Result<(Output<OprfHash<CS>>, Hkdf<OprfHash<CS>>), ProtocolError>
+    ksf_progress: Option<&dyn KsfProgress>,
+) -> Result<(Output<OprfHash<CS>>, SuitePrk<CS>), ProtocolError>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1095,47 +4351,183 @@ where
 {
     let oprf_output = oprf_client.finalize(input, &evaluation_element)?;
 
-    let hardened_output = if let Some(ksf) = ksf {
-        ksf.hash(oprf_output.clone())
-    } else {
-        CS::Ksf::default().hash(oprf_output.clone())
+    let default_ksf;
+    let ksf = match ksf {
+        Some(ksf) => ksf,
+        None => {
+            default_ksf = CS::Ksf::default();
+            &default_ksf
+        }
+    };
+    let hardened_output = match ksf_progress {
+        Some(progress) => ksf.hash_with_progress(oprf_output.clone(), progress),
+        None => ksf.hash(oprf_output.clone()),
     }
     .map_err(ProtocolError::from)?;
 
-    let mut hkdf = HkdfExtract::<OprfHash<CS>>::new(None);
-    hkdf.input_ikm(&oprf_output);
-    hkdf.input_ikm(&hardened_output);
-    Ok(hkdf.finalize())
+    Ok(extract_password_derived_key::<CS>(
+        &oprf_output,
+        &hardened_output,
+    ))
+}
+
+// Same as `get_password_derived_key`, but computes the key stretching function
+// in chunks and awaits `yield_now` before each of them
+async fn get_password_derived_key_async<CS: CipherSuite, F: Future<Output = ()>>(
+    input: &[u8],
+    oprf_client: voprf::OprfClient<CS::OprfCs>,
+    evaluation_element: voprf::EvaluationElement<CS::OprfCs>,
+    ksf: Option<&CS::Ksf>,
+    ksf_progress: Option<&dyn KsfProgress>,
+    yield_now: impl FnMut() -> F,
+) -> Result<(Output<OprfHash<CS>>, SuitePrk<CS>), ProtocolError>
+where
+    CS::Ksf: ChunkedKsf,
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let oprf_output = oprf_client.finalize(input, &evaluation_element)?;
+
+    let default_ksf;
+    let ksf = match ksf {
+        Some(ksf) => ksf,
+        None => {
+            default_ksf = CS::Ksf::default();
+            &default_ksf
+        }
+    };
+    let hardened_output =
+        hash_cooperatively(ksf, oprf_output.clone(), ksf_progress, yield_now).await?;
+
+    Ok(extract_password_derived_key::<CS>(
+        &oprf_output,
+        &hardened_output,
+    ))
+}
+
+fn extract_password_derived_key<CS: CipherSuite>(
+    oprf_output: &Output<OprfHash<CS>>,
+    hardened_output: &Output<OprfHash<CS>>,
+) -> (Output<OprfHash<CS>>, SuitePrk<CS>)
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    SuiteProvider::<CS>::extract(None, &[oprf_output, hardened_output])
+}
+
+#[cfg(feature = "server")]
+fn oprf_key_from_seed<CS: CipherSuite>(
+    oprf_seed: &Output<OprfHash<CS>>,
+    credential_identifier: &[u8],
+    info: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let hkdf = SuiteProvider::<CS>::from_prk(oprf_seed)?;
+    oprf_key_from_hkdf::<CS>(hkdf, credential_identifier, info)
 }
 
-fn oprf_key_from_seed<CS: voprf::CipherSuite>(
-    oprf_seed: &Output<CS::Hash>,
+// Selects the OPRF key of a credential: the per-user key stored in its password
+// file takes precedence over the key passed by the caller, and the key derived
+// from the OPRF seed is only used if neither is present
+#[cfg(feature = "server")]
+fn select_oprf_key<CS: CipherSuite>(
+    record_key: Option<&GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>>,
+    parameter_key: Option<&[u8]>,
+    oprf_seed: &Output<OprfHash<CS>>,
     credential_identifier: &[u8],
-) -> Result<GenericArray<u8, <CS::Group as Group>::ScalarLen>, ProtocolError>
-where
-    <CS::Hash as OutputSizeUser>::OutputSize:
-        IsLess<U256> + IsLessOrEqual<<CS::Hash as BlockSizeUser>::BlockSize>,
-    CS::Hash: Hash,
-    <CS::Hash as CoreProxy>::Core: ProxyHash,
-    <<CS::Hash as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
-    Le<<<CS::Hash as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-{
-    let mut ikm = GenericArray::<_, <CS::Group as Group>::ScalarLen>::default();
-    Hkdf::<CS::Hash>::from_prk(oprf_seed)
-        .ok()
-        .and_then(|hkdf| {
-            hkdf.expand_multi_info(&[credential_identifier, STR_OPRF_KEY], &mut ikm)
-                .ok()
-        })
-        .ok_or(InternalError::HkdfError)?;
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    match (record_key, parameter_key) {
+        (Some(oprf_key), _) => Ok(oprf_key.clone()),
+        (None, Some(oprf_key)) => check_slice_size(
+            oprf_key,
+            <OprfGroup<CS> as Group>::ScalarLen::USIZE,
+            "oprf_key",
+        )
+        .map(GenericArray::clone_from_slice)
+        .map_err(Into::into),
+        (None, None) => {
+            oprf_key_from_seed::<CS>(oprf_seed, credential_identifier, CS::OPRF_KEY_INFO)
+        }
+    }
+}
+
+// The pepper is used as the HKDF-Extract salt over the OPRF seed, so that both
+// are required to recompute the OPRF key
+#[cfg(feature = "server")]
+fn peppered_oprf_key_from_seed<CS: CipherSuite>(
+    oprf_seed: &Output<OprfHash<CS>>,
+    credential_identifier: &[u8],
+    pepper: &[u8],
+    info: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let (_, hkdf) = SuiteProvider::<CS>::extract(Some(pepper), &[oprf_seed]);
+    oprf_key_from_hkdf::<CS>(hkdf, credential_identifier, info)
+}
+
+#[cfg(feature = "server")]
+fn oprf_key_from_hkdf<CS: CipherSuite>(
+    hkdf: SuitePrk<CS>,
+    credential_identifier: &[u8],
+    info: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut ikm = GenericArray::<_, <OprfGroup<CS> as Group>::ScalarLen>::default();
+    SuiteProvider::<CS>::expand(&hkdf, &[credential_identifier, STR_OPRF_KEY], &mut ikm)?;
 
-    Ok(CS::Group::serialize_scalar(voprf::derive_key::<CS>(
+    Ok(OprfGroup::<CS>::serialize_scalar(voprf::derive_key::<
+        CS::OprfCs,
+    >(
         ikm.as_slice(),
-        &GenericArray::from(*STR_OPAQUE_DERIVE_KEY_PAIR),
+        info,
         voprf::Mode::Oprf,
     )?))
 }
 
+/// The public key of the server and the envelope of the client, masked with
+/// the masking key of the client as `server_s_pk || envelope`
+///
+/// The envelope is split into the lengths of its nonce, MAC and application
+/// data, which avoids requiring the bounds of [`EnvelopeLen`] on every message
+/// containing a masked response.
 #[cfg_attr(
     feature = "serde",
     derive(serde::Deserialize, serde::Serialize),
@@ -1152,13 +4544,14 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    pub(crate) nonce: GenericArray<u8, NonceLen>,
-    pub(crate) hash: Output<OprfHash<CS>>,
-    pub(crate) pk: GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>,
+    server_s_pk: GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>,
+    envelope_nonce: GenericArray<u8, NonceLen>,
+    envelope_hmac: GenericArray<u8, MacLen<CS>>,
+    envelope_app_data: GenericArray<u8, CS::AppDataLen>,
 }
 
 pub(crate) type MaskedResponseLen<CS: CipherSuite> =
-    Sum<Sum<NonceLen, OutputSize<OprfHash<CS>>>, <CS::KeGroup as KeGroup>::PkLen>;
+    Sum<EnvelopeLen<CS>, <CS::KeGroup as KeGroup>::PkLen>;
 
 impl<CS: CipherSuite> MaskedResponse<CS>
 where
@@ -1171,38 +4564,155 @@ where
 {
     pub(crate) fn serialize(&self) -> GenericArray<u8, MaskedResponseLen<CS>>
     where
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        self.nonce.concat(self.hash.clone()).concat(self.pk.clone())
+        let mut bytes = GenericArray::<_, MaskedResponseLen<CS>>::default();
+
+        for (byte, masked) in bytes.iter_mut().zip(self.iter().flatten()) {
+            *byte = *masked;
+        }
+
+        bytes
     }
 
-    pub(crate) fn deserialize(bytes: &[u8]) -> Self {
-        let nonce = NonceLen::USIZE;
-        let hash = nonce + OutputSize::<OprfHash<CS>>::USIZE;
-        let pk = hash + <CS::KeGroup as KeGroup>::PkLen::USIZE;
+    /// Splits `bytes` into the masked public key and envelope, after checking
+    /// that it has exactly the length of a masked response
+    pub(crate) fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_bytes = check_slice_size(
+            bytes,
+            checked_sizes::<CS>()?.masked_response,
+            "masked_response",
+        )?;
+        let (server_s_pk, envelope) =
+            checked_bytes.split_at(<CS::KeGroup as KeGroup>::PkLen::USIZE);
+        let (envelope_nonce, envelope) = envelope.split_at(NonceLen::USIZE);
+        let (envelope_hmac, envelope_app_data) = envelope.split_at(MacLen::<CS>::USIZE);
 
-        Self {
-            nonce: GenericArray::clone_from_slice(&bytes[..nonce]),
-            hash: GenericArray::clone_from_slice(&bytes[nonce..hash]),
-            pk: GenericArray::clone_from_slice(&bytes[hash..pk]),
-        }
+        Ok(Self {
+            server_s_pk: GenericArray::clone_from_slice(server_s_pk),
+            envelope_nonce: GenericArray::clone_from_slice(envelope_nonce),
+            envelope_hmac: GenericArray::clone_from_slice(envelope_hmac),
+            envelope_app_data: GenericArray::clone_from_slice(envelope_app_data),
+        })
     }
 
     pub(crate) fn iter(&self) -> impl Iterator<Item = &[u8]> {
-        [self.nonce.as_slice(), &self.hash, &self.pk].into_iter()
+        [
+            self.server_s_pk.as_slice(),
+            &self.envelope_nonce,
+            &self.envelope_hmac,
+            &self.envelope_app_data,
+        ]
+        .into_iter()
+    }
+
+    /// Masks the public key of the server and the envelope of the client with
+    /// the pad derived from `masking_key` and `masking_nonce`
+    #[cfg(feature = "server")]
+    pub(crate) fn mask(
+        masking_key: &[u8],
+        masking_nonce: &[u8],
+        server_s_pk: &PublicKey<CS::KeGroup>,
+        envelope: &Envelope<CS>,
+    ) -> Result<Self, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut xor_pad = Self::pad(masking_key, masking_nonce)?;
+
+        for (x1, x2) in xor_pad.iter_mut().zip(
+            server_s_pk
+                .serialize()
+                .as_slice()
+                .iter()
+                .chain(envelope.serialize().iter()),
+        ) {
+            *x1 ^= x2
+        }
+
+        Self::deserialize(&xor_pad)
+    }
+
+    /// Removes the mask derived from `masking_key` and `masking_nonce`, and
+    /// returns the public key of the server and the envelope of the client
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if the unmasked public key is
+    /// invalid, which happens when the masking key is wrong
+    pub(crate) fn unmask(
+        &self,
+        masking_key: &[u8],
+        masking_nonce: &[u8],
+    ) -> Result<(PublicKey<CS::KeGroup>, Envelope<CS>), ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut xor_pad = Self::pad(masking_key, masking_nonce)?;
+
+        for (x1, x2) in xor_pad.iter_mut().zip(self.iter().flatten()) {
+            *x1 ^= x2
+        }
+
+        let (server_s_pk, envelope) = xor_pad.split_at(self.server_s_pk.len());
+        let server_s_pk =
+            PublicKey::deserialize(server_s_pk).map_err(|_| ProtocolError::SerializationError)?;
+        let envelope = Envelope::deserialize(envelope)?;
+
+        Ok((server_s_pk, envelope))
+    }
+
+    fn pad(
+        masking_key: &[u8],
+        masking_nonce: &[u8],
+    ) -> Result<GenericArray<u8, MaskedResponseLen<CS>>, ProtocolError>
+    where
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut xor_pad = GenericArray::<_, MaskedResponseLen<CS>>::default();
+
+        SuiteProvider::<CS>::expand(
+            &SuiteProvider::<CS>::from_prk(masking_key)?,
+            &[masking_nonce, STR_CREDENTIAL_RESPONSE_PAD],
+            &mut xor_pad,
+        )?;
+
+        Ok(xor_pad)
     }
 }
 
-fn mask_response<CS: CipherSuite>(
-    masking_key: &[u8],
-    masking_nonce: &[u8],
-    server_s_pk: &PublicKey<CS::KeGroup>,
-    envelope: &Envelope<CS>,
-) -> Result<MaskedResponse<CS>, ProtocolError>
+// The assets of a credential response that has been opened by the client
+struct OpenedCredentialResponse<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    envelope: OpenedEnvelope<'a, CS>,
+    server_s_pk: PublicKey<CS::KeGroup>,
+    #[cfg(any(test, feature = "randomized-password"))]
+    randomized_pwd: Output<OprfHash<CS>>,
+}
+
+impl<CS: CipherSuite> OpenedCredentialResponse<'_, CS>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1210,36 +4720,53 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
-    MaskedResponseLen<CS>: ArrayLength<u8>,
 {
-    let mut xor_pad = GenericArray::<_, MaskedResponseLen<CS>>::default();
+    // The Diffie-Hellman of the client's static private key recovered from the
+    // envelope, or `None` if the suite doesn't authenticate the client
+    #[allow(clippy::type_complexity)]
+    fn client_s_dh(
+        &self,
+        credential_response: &CredentialResponse<CS>,
+    ) -> Result<Option<GenericArray<u8, <CS::KeGroup as KeGroup>::PkLen>>, ProtocolError> {
+        if !CS::CLIENT_AUTHENTICATION {
+            return Ok(None);
+        }
 
-    Hkdf::<OprfHash<CS>>::from_prk(masking_key)
-        .map_err(|_| InternalError::HkdfError)?
-        .expand_multi_info(&[masking_nonce, STR_CREDENTIAL_RESPONSE_PAD], &mut xor_pad)
-        .map_err(|_| InternalError::HkdfError)?;
+        let client_s_dh = self
+            .envelope
+            .client_static_keypair
+            .as_ref()
+            .ok_or(InternalError::IncompatibleEnvelopeModeError)?
+            .private()
+            .diffie_hellman(CS::KeyExchange::client_s_dh_pk(
+                &credential_response.ke2_message,
+            ))?;
 
-    for (x1, x2) in xor_pad.iter_mut().zip(
-        server_s_pk
-            .serialize()
-            .as_slice()
-            .iter()
-            .chain(envelope.serialize().iter()),
-    ) {
-        *x1 ^= x2
+        Ok(Some(client_s_dh))
     }
+}
 
-    Ok(MaskedResponse::deserialize(&xor_pad))
+// Checks the protocol version announced by the peer, which has to be given if
+// this party announced its own one
+fn check_protocol_version(announced: bool, peer_version: Option<u16>) -> Result<(), ProtocolError> {
+    match (announced, peer_version) {
+        (_, Some(version)) if version != PROTOCOL_VERSION => {
+            Err(ProtocolError::ProtocolVersionError)
+        }
+        (true, None) => Err(ProtocolError::ProtocolVersionMissing),
+        _ => Ok(()),
+    }
 }
 
-fn unmask_response<CS: CipherSuite>(
-    masking_key: &[u8],
-    masking_nonce: &[u8],
-    masked_response: &MaskedResponse<CS>,
-) -> Result<(PublicKey<CS::KeGroup>, Envelope<CS>), ProtocolError>
+// Checks the protocol version announced by the peer and, if this party
+// announced its own one, binds `PROTOCOL_VERSION` into the context of the key
+// exchange, or returns `None` so that the context is used as is and logins stay
+// compatible with other implementations
+fn versioned_context<CS: CipherSuite>(
+    context: Option<&[u8]>,
+    announced: bool,
+    peer_version: Option<u16>,
+) -> Result<Option<Output<OprfHash<CS>>>, ProtocolError>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1247,28 +4774,21 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
-    MaskedResponseLen<CS>: ArrayLength<u8>,
 {
-    let mut xor_pad = GenericArray::<_, MaskedResponseLen<CS>>::default();
-
-    Hkdf::<OprfHash<CS>>::from_prk(masking_key)
-        .map_err(|_| InternalError::HkdfError)?
-        .expand_multi_info(&[masking_nonce, STR_CREDENTIAL_RESPONSE_PAD], &mut xor_pad)
-        .map_err(|_| InternalError::HkdfError)?;
-
-    for (x1, x2) in xor_pad.iter_mut().zip(masked_response.iter().flatten()) {
-        *x1 ^= x2
+    check_protocol_version(announced, peer_version)?;
+    if !announced {
+        return Ok(None);
     }
 
-    let key_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
-    let server_s_pk = PublicKey::deserialize(&xor_pad[..key_len])
-        .map_err(|_| ProtocolError::SerializationError)?;
-    let envelope = Envelope::deserialize(&xor_pad[key_len..])?;
+    let context = Input::<U2>::from(context.unwrap_or_default())?;
+    let mut hasher = OprfHash::<CS>::new()
+        .chain_update(STR_PROTOCOL_VERSION)
+        .chain_update(PROTOCOL_VERSION.to_be_bytes());
+    for bytes in context.iter() {
+        hasher.update(bytes);
+    }
 
-    Ok((server_s_pk, envelope))
+    Ok(Some(hasher.finalize()))
 }
 
 #[allow(clippy::type_complexity)]
@@ -1291,13 +4811,32 @@ pub(crate) fn bytestrings_from_identifiers<KG: KeGroup>(
     Ok((client_identity, server_identity))
 }
 
+// Checks `password` against the bounds of `CS`
+fn check_password_len<CS: CipherSuite>(password: &[u8]) -> Result<(), ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    if password.len() < CS::MIN_PASSWORD_LEN {
+        Err(ProtocolError::PasswordTooShortError)
+    } else if password.len() > CS::MAX_PASSWORD_LEN {
+        Err(ProtocolError::PasswordTooLongError)
+    } else {
+        Ok(())
+    }
+}
+
 /// Internal function for computing the blind result by calling the voprf
 /// library. Note that for tests, we use the deterministic blinding in order to
 /// be able to set the blinding factor directly from the passed-in rng.
 fn blind<CS: CipherSuite, R: RngCore + CryptoRng>(
     rng: &mut R,
     password: &[u8],
-) -> Result<voprf::OprfClientBlindResult<CS::OprfCs>, voprf::Error>
+) -> Result<voprf::OprfClientBlindResult<CS::OprfCs>, ProtocolError>
 where
     <OprfHash<CS> as OutputSizeUser>::OutputSize:
         IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1306,20 +4845,27 @@ where
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
-    #[cfg(not(test))]
-    let result = voprf::OprfClient::blind(password, rng)?;
+    let () = SuiteAssertions::<CS>::VALID;
 
-    #[cfg(test)]
-    let result = {
-        let mut blind_bytes = GenericArray::<_, <OprfGroup<CS> as Group>::ScalarLen>::default();
-        let blind = loop {
-            rng.fill_bytes(&mut blind_bytes);
-            if let Ok(scalar) = <OprfGroup<CS> as Group>::deserialize_scalar(&blind_bytes) {
-                break scalar;
-            }
-        };
-        voprf::OprfClient::deterministic_blind_unchecked(password, blind)?
-    };
+    let blind = random_blind::<CS, _>(rng)?;
+
+    Ok(voprf::OprfClient::deterministic_blind_unchecked(
+        password, blind,
+    )?)
+}
 
-    Ok(result)
+// The blind `voprf::OprfClient::blind` would draw from `rng`, but reporting a
+// failure of `rng`
+fn random_blind<CS: CipherSuite, R: RngCore + CryptoRng>(
+    rng: &mut R,
+) -> Result<<OprfGroup<CS> as Group>::Scalar, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    random_scalar::<OprfGroup<CS>, _>(rng)
 }
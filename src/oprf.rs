@@ -0,0 +1,374 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Outsourcing the OPRF evaluation of a login to a separate service
+//!
+//! An [`OprfEvaluator`] evaluates the OPRF on the blinded element of a
+//! credential request with the key of its credential identifier. Front-end
+//! servers that hand the evaluation of a [`CredentialResponseBuilder`] to an
+//! evaluator never hold the OPRF seed, so that a compromised front-end can't
+//! mount an offline dictionary attack on the credential records:
+//!
+//! ```ignore
+//! let builder = CredentialResponseBuilder::new(&mut OsRng, &server_setup, password_file, credential_request, credential_identifier, params)?;
+//! let evaluation_element = builder.evaluate_with(&evaluator).await?;
+//! let server_login_start_result = builder.finish(&mut OsRng, &server_setup, &evaluation_element)?;
+//! ```
+//!
+//! The [`ServerSetup`] of the front-end then only needs the key pair of the
+//! server, while the OPRF service holds a [`ServerSetup`] with the OPRF seed
+//! and runs [`ServerSetup::evaluate_oprf`]. A [`ServerSetup`] is also an
+//! evaluator itself, which evaluates in process. Requests to an OPRF service
+//! are serialized as an [`EvaluationRequest`].
+//!
+//! With the `oprf-http` feature, [`HttpOprfEvaluator`] sends the evaluation to
+//! an OPRF service over HTTP, which answers requests with
+//! [`evaluate_request`].
+//!
+//! [`CredentialResponseBuilder`]: crate::CredentialResponseBuilder
+
+use core::convert::Infallible;
+use core::future::{ready, Future, Ready};
+#[cfg(feature = "oprf-http")]
+use core::pin::Pin;
+#[cfg(feature = "oprf-http")]
+use std::boxed::Box;
+#[cfg(feature = "oprf-http")]
+use std::vec::Vec;
+
+#[cfg(feature = "oprf-http")]
+use bytes::Bytes;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+#[cfg(feature = "oprf-http")]
+use generic_array::typenum::Unsigned;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+use generic_array::GenericArray;
+#[cfg(feature = "oprf-http")]
+use http::header::{CONTENT_TYPE, HOST};
+#[cfg(feature = "oprf-http")]
+use http::{Request, StatusCode, Uri};
+#[cfg(feature = "oprf-http")]
+use http_body_util::{BodyExt, Full};
+#[cfg(feature = "oprf-http")]
+use hyper_util::rt::TokioIo;
+#[cfg(feature = "oprf-http")]
+use tokio::net::TcpStream;
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+#[cfg(feature = "oprf-http")]
+use crate::errors::utils::check_slice_size;
+#[cfg(feature = "oprf-http")]
+use crate::errors::InternalError;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, ProxyHash};
+use crate::keypair::SecretKey;
+use crate::ServerSetup;
+
+/// Evaluates the OPRF of a login, see the [module documentation](self)
+pub trait OprfEvaluator<CS: CipherSuite> {
+    /// Custom error type that can be passed down to `InternalError::Custom`
+    type Error;
+    /// The future returned by [`evaluate`](Self::evaluate)
+    type EvaluateFuture<'a>: Future<
+        Output = Result<
+            GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>,
+            ProtocolError<Self::Error>,
+        >,
+    >
+    where
+        Self: 'a;
+
+    /// Evaluates the OPRF on the serialized `blinded_element` with the key of
+    /// `credential_identifier`, and returns the serialized evaluated element
+    fn evaluate<'a>(
+        &'a self,
+        blinded_element: &'a [u8],
+        credential_identifier: &'a [u8],
+    ) -> Self::EvaluateFuture<'a>;
+}
+
+impl<CS: CipherSuite, S: SecretKey<CS::KeGroup>> OprfEvaluator<CS> for ServerSetup<CS, S>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = Infallible;
+    type EvaluateFuture<'a>
+        = Ready<
+        Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError<Self::Error>>,
+    >
+    where
+        Self: 'a;
+
+    fn evaluate<'a>(
+        &'a self,
+        blinded_element: &'a [u8],
+        credential_identifier: &'a [u8],
+    ) -> Self::EvaluateFuture<'a> {
+        ready(self.evaluate_oprf(blinded_element, credential_identifier))
+    }
+}
+
+/// The request of an evaluation sent to an OPRF service
+///
+/// It is serialized as the two-byte big-endian length of the credential
+/// identifier, the credential identifier and the serialized blinded element.
+/// The service responds with the serialized evaluated element.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EvaluationRequest<'a> {
+    credential_identifier_len: [u8; 2],
+    credential_identifier: &'a [u8],
+    blinded_element: &'a [u8],
+}
+
+impl<'a> EvaluationRequest<'a> {
+    /// Requests the evaluation of the serialized `blinded_element` with the key
+    /// of `credential_identifier`
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if `credential_identifier` is
+    /// longer than [`u16::MAX`]
+    pub fn new(
+        blinded_element: &'a [u8],
+        credential_identifier: &'a [u8],
+    ) -> Result<Self, ProtocolError> {
+        let len = u16::try_from(credential_identifier.len())
+            .map_err(|_| ProtocolError::SerializationError)?;
+
+        Ok(Self {
+            credential_identifier_len: len.to_be_bytes(),
+            credential_identifier,
+            blinded_element,
+        })
+    }
+
+    /// Returns the serialized blinded element
+    pub fn blinded_element(&self) -> &'a [u8] {
+        self.blinded_element
+    }
+
+    /// Returns the credential identifier
+    pub fn credential_identifier(&self) -> &'a [u8] {
+        self.credential_identifier
+    }
+
+    /// Returns the length of the serialized request
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        2 + self.credential_identifier.len() + self.blinded_element.len()
+    }
+
+    /// Returns the serialized request in chunks
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        [
+            self.credential_identifier_len.as_slice(),
+            self.credential_identifier,
+            self.blinded_element,
+        ]
+        .into_iter()
+    }
+
+    /// Deserializes a request, borrowing the credential identifier and the
+    /// blinded element from `input`
+    ///
+    /// The blinded element isn't validated, which is left to the evaluation.
+    ///
+    /// # Errors
+    /// [`ProtocolError::SerializationError`] if `input` is shorter than the
+    /// length of the credential identifier it announces
+    pub fn deserialize(input: &'a [u8]) -> Result<Self, ProtocolError> {
+        if input.len() < 2 {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        let (len, input) = input.split_at(2);
+        let credential_identifier_len = [len[0], len[1]];
+        let len = usize::from(u16::from_be_bytes(credential_identifier_len));
+
+        if input.len() < len {
+            return Err(ProtocolError::SerializationError);
+        }
+
+        let (credential_identifier, blinded_element) = input.split_at(len);
+
+        Ok(Self {
+            credential_identifier_len,
+            credential_identifier,
+            blinded_element,
+        })
+    }
+}
+
+/// Returns the body of a request to an OPRF service, which is the serialized
+/// [`EvaluationRequest`], available with the `oprf-http` feature
+///
+/// # Errors
+/// [`ProtocolError::SerializationError`] if `credential_identifier` is longer
+/// than [`u16::MAX`]
+#[cfg(feature = "oprf-http")]
+pub fn serialize_request(
+    blinded_element: &[u8],
+    credential_identifier: &[u8],
+) -> Result<Vec<u8>, ProtocolError> {
+    Ok(
+        EvaluationRequest::new(blinded_element, credential_identifier)?
+            .iter()
+            .collect::<Vec<_>>()
+            .concat(),
+    )
+}
+
+/// Answers a request written by [`serialize_request`] with
+/// [`ServerSetup::evaluate_oprf`], returning the body of the response,
+/// available with the `oprf-http` feature
+///
+/// # Errors
+/// - [`ProtocolError::SerializationError`] if `body` is shorter than the length
+///   of the credential identifier it announces
+/// - [`ProtocolError::LibraryError`] if the blinded element is invalid
+#[cfg(feature = "oprf-http")]
+pub fn evaluate_request<CS: CipherSuite, S: SecretKey<CS::KeGroup>>(
+    server_setup: &ServerSetup<CS, S>,
+    body: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let request = EvaluationRequest::deserialize(body)?;
+    server_setup.evaluate_oprf(request.blinded_element(), request.credential_identifier())
+}
+
+/// An error of [`HttpOprfEvaluator`], available with the `oprf-http` feature
+#[cfg(feature = "oprf-http")]
+#[derive(Debug, displaydoc::Display)]
+pub enum HttpOprfError {
+    /// The URI of the OPRF service has no host
+    InvalidUri,
+    /// Connecting to the OPRF service failed: {0}
+    Io(std::io::Error),
+    /// The request to the OPRF service failed: {0}
+    Http(hyper::Error),
+    /// The OPRF service responded with status {0}
+    Status(StatusCode),
+}
+
+#[cfg(feature = "oprf-http")]
+impl std::error::Error for HttpOprfError {}
+
+/// An [`OprfEvaluator`] sending each evaluation to an OPRF service over
+/// HTTP/1.1, available with the `oprf-http` feature
+///
+/// The evaluation is sent as a `POST` request to the URI, with the output of
+/// [`serialize_request`] as body, and the service responds with the serialized
+/// evaluated element, e.g. as returned by [`evaluate_request`].
+///
+/// Every evaluation opens a new connection, which is driven by a task spawned
+/// on the current Tokio runtime. Only plain HTTP is supported, so the
+/// connection to the service has to be protected otherwise, e.g. by a
+/// private network or a sidecar terminating TLS.
+#[cfg(feature = "oprf-http")]
+#[derive(Clone, Debug)]
+pub struct HttpOprfEvaluator {
+    uri: Uri,
+}
+
+#[cfg(feature = "oprf-http")]
+impl HttpOprfEvaluator {
+    /// Sends evaluations to the OPRF service at `uri`
+    pub fn new(uri: Uri) -> Self {
+        Self { uri }
+    }
+
+    /// Returns the URI of the OPRF service
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    async fn post(&self, body: Vec<u8>) -> Result<Bytes, HttpOprfError> {
+        let authority = self.uri.authority().ok_or(HttpOprfError::InvalidUri)?;
+        let stream = TcpStream::connect((authority.host(), authority.port_u16().unwrap_or(80)))
+            .await
+            .map_err(HttpOprfError::Io)?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(HttpOprfError::Http)?;
+        tokio::spawn(connection);
+
+        let path = self
+            .uri
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str());
+        let request = Request::post(path)
+            .header(HOST, authority.as_str())
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|_| HttpOprfError::InvalidUri)?;
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(HttpOprfError::Http)?;
+
+        if response.status() != StatusCode::OK {
+            return Err(HttpOprfError::Status(response.status()));
+        }
+
+        Ok(response
+            .into_body()
+            .collect()
+            .await
+            .map_err(HttpOprfError::Http)?
+            .to_bytes())
+    }
+}
+
+#[cfg(feature = "oprf-http")]
+impl<CS: CipherSuite> OprfEvaluator<CS> for HttpOprfEvaluator {
+    type Error = HttpOprfError;
+    type EvaluateFuture<'a> = Pin<
+        Box<
+            dyn Future<
+                    Output = Result<
+                        GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>,
+                        ProtocolError<Self::Error>,
+                    >,
+                > + Send
+                + 'a,
+        >,
+    >;
+
+    fn evaluate<'a>(
+        &'a self,
+        blinded_element: &'a [u8],
+        credential_identifier: &'a [u8],
+    ) -> Self::EvaluateFuture<'a> {
+        Box::pin(async move {
+            let body = serialize_request(blinded_element, credential_identifier)
+                .map_err(ProtocolError::into_custom)?;
+            let response = self.post(body).await.map_err(InternalError::Custom)?;
+            let evaluation_element = check_slice_size(
+                &response,
+                <OprfGroup<CS> as Group>::ElemLen::USIZE,
+                "evaluation_element",
+            )
+            .map_err(|e| ProtocolError::into_custom(e.into()))?;
+
+            Ok(GenericArray::clone_from_slice(evaluation_element))
+        })
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Server keys held by a PKCS#11 token
+//!
+//! [`Pkcs11SecretKey`] implements [`SecretKey`] for Curve25519 by deriving the
+//! Diffie-Hellman output on the token with `CKM_ECDH1_DERIVE`, so that the
+//! static key of the server never leaves a hardware security module such as
+//! a Luna or `YubiHSM` 2:
+//!
+//! ```ignore
+//! let session = Arc::new(Mutex::new(session));
+//! let key = Pkcs11SecretKey::new(session, private_key_handle, public_key_handle)?;
+//! let server_setup = ServerSetup::<Default, _>::new_with_key(&mut OsRng, KeyPair::from_private_key(key)?)?;
+//! ```
+//!
+//! Tokens only return the x-coordinate of the shared point for Weierstrass
+//! curves, while the key exchange of the NIST curves requires the whole
+//! compressed point, so only Curve25519 is supported, for which the
+//! u-coordinate is the complete output.
+//!
+//! The key can't be serialized, so [`Pkcs11SecretKey::serialize`] is empty and
+//! [`Pkcs11SecretKey::deserialize`] fails. Restore a
+//! [`ServerSetup`](crate::ServerSetup) instead with
+//! [`ServerSetup::deserialize_with_keypair`](crate::ServerSetup::deserialize_with_keypair).
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use cryptoki::error::Error;
+use cryptoki::mechanism::elliptic_curve::{EcKdf, Ecdh1DeriveParams};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::Session;
+use generic_array::typenum::U0;
+use generic_array::GenericArray;
+use zeroize::Zeroize;
+
+use crate::errors::InternalError;
+use crate::key_exchange::group::KeGroup;
+use crate::keypair::{PublicKey, SecretKey};
+use crate::Curve25519;
+
+/// A Curve25519 private key held by a PKCS#11 token, see the [module
+/// documentation](self)
+#[derive(Clone)]
+pub struct Pkcs11SecretKey {
+    session: Arc<Mutex<Session>>,
+    private_key: ObjectHandle,
+    public_key: PublicKey<Curve25519>,
+}
+
+impl Pkcs11SecretKey {
+    /// Uses the `CKK_EC_MONTGOMERY` key pair with the given handles
+    ///
+    /// The public key is read from the `CKA_EC_POINT` attribute of
+    /// `public_key`, either DER-encoded as an OCTET STRING or raw.
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        private_key: ObjectHandle,
+        public_key: ObjectHandle,
+    ) -> Result<Self, Error> {
+        let attributes = session
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_attributes(public_key, &[AttributeType::EcPoint])?;
+        let point = attributes
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                Attribute::EcPoint(point) => Some(point),
+                _ => None,
+            })
+            .ok_or(Error::NotSupported)?;
+        let point = match point.as_slice() {
+            [0x04, 0x20, point @ ..] if point.len() == 32 => point,
+            point => point,
+        };
+        let public_key = PublicKey::deserialize(point).map_err(|_| Error::InvalidValue)?;
+
+        Ok(Self {
+            session,
+            private_key,
+            public_key,
+        })
+    }
+
+    /// Returns the handle of the private key
+    pub fn handle(&self) -> ObjectHandle {
+        self.private_key
+    }
+}
+
+impl SecretKey<Curve25519> for Pkcs11SecretKey {
+    type Error = Error;
+    type Len = U0;
+
+    fn diffie_hellman(
+        &self,
+        pk: PublicKey<Curve25519>,
+    ) -> Result<GenericArray<u8, <Curve25519 as KeGroup>::PkLen>, InternalError<Self::Error>> {
+        let pk = pk.serialize();
+        let mechanism = Mechanism::Ecdh1Derive(Ecdh1DeriveParams::new(EcKdf::null(), &pk));
+        // The shared secret is derived into a session object that has to be
+        // readable, and is destroyed right after
+        let template = [
+            Attribute::Class(ObjectClass::SECRET_KEY),
+            Attribute::KeyType(KeyType::GENERIC_SECRET),
+            Attribute::ValueLen(32.into()),
+            Attribute::Token(false),
+            Attribute::Sensitive(false),
+            Attribute::Extractable(true),
+        ];
+
+        let session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+        let shared = session
+            .derive_key(&mechanism, self.private_key, &template)
+            .map_err(InternalError::Custom)?;
+        let attributes = session.get_attributes(shared, &[AttributeType::Value]);
+        let destroyed = session.destroy_object(shared);
+
+        let mut value = attributes
+            .map_err(InternalError::Custom)?
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Value(value) => Some(value),
+                _ => None,
+            })
+            .unwrap_or_default();
+        destroyed.map_err(InternalError::Custom)?;
+
+        let result = GenericArray::from_exact_iter(value.iter().copied())
+            .ok_or(InternalError::Custom(Error::InvalidValue));
+        value.zeroize();
+
+        result
+    }
+
+    fn public_key(&self) -> Result<PublicKey<Curve25519>, InternalError<Self::Error>> {
+        Ok(self.public_key.clone())
+    }
+
+    fn serialize(&self) -> GenericArray<u8, Self::Len> {
+        GenericArray::default()
+    }
+
+    fn deserialize(_input: &[u8]) -> Result<Self, InternalError<Self::Error>> {
+        Err(InternalError::Custom(Error::NotSupported))
+    }
+}
@@ -0,0 +1,498 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Protocol Buffers representations of the protocol messages, available with
+//! the `prost` feature
+//!
+//! The messages of this module are the [`prost`] types of the `opaque.v1`
+//! package defined in `proto/opaque.proto`, which ships with the crate, so that
+//! services written in other languages can generate their own types from the
+//! same schema. Instead of a single opaque byte field, every message has a
+//! field for each of its components.
+//!
+//! Protocol messages convert into their representation with [`From`], and
+//! back with [`TryFrom`], which fails if a field doesn't have the length
+//! required by the cipher suite:
+//!
+//! ```ignore
+//! let request = proto::CredentialRequest::from(&client_login_start_result.message);
+//! let response = client.login_start(request).await?.into_inner();
+//! let credential_response = CredentialResponse::<DefaultCipherSuite>::try_from(response)?;
+//! ```
+//!
+//! The cipher suite isn't part of the messages and has to be agreed on by both
+//! sides, e.g. by versioning the service.
+
+use core::ops::Add;
+use std::vec::Vec;
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
+use generic_array::ArrayLength;
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::utils::check_slice_size;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, Ke3MessageLen, MacLen, Serialize};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::{self, Sizes};
+
+/// The first message of the registration, sent by the client
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegistrationRequest {
+    /// The blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub blinded_message: Vec<u8>,
+}
+
+/// The answer of the server to a [`RegistrationRequest`]
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegistrationResponse {
+    /// The evaluated blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub evaluated_message: Vec<u8>,
+    /// The static public key of the server
+    #[prost(bytes = "vec", tag = "2")]
+    pub server_public_key: Vec<u8>,
+}
+
+/// The last message of the registration, sent by the client and stored by the
+/// server as the password file
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegistrationUpload {
+    /// The static public key of the client
+    #[prost(bytes = "vec", tag = "1")]
+    pub client_public_key: Vec<u8>,
+    /// The key used by the server to mask the credential response
+    #[prost(bytes = "vec", tag = "2")]
+    pub masking_key: Vec<u8>,
+    /// The envelope of the client
+    #[prost(bytes = "vec", tag = "3")]
+    pub envelope: Vec<u8>,
+}
+
+/// The first message of the login, sent by the client
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CredentialRequest {
+    /// The blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub blinded_message: Vec<u8>,
+    /// The first message of the key exchange
+    #[prost(bytes = "vec", tag = "2")]
+    pub ke1: Vec<u8>,
+}
+
+/// The answer of the server to a [`CredentialRequest`]
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CredentialResponse {
+    /// The evaluated blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub evaluated_message: Vec<u8>,
+    /// The nonce used to mask the response
+    #[prost(bytes = "vec", tag = "2")]
+    pub masking_nonce: Vec<u8>,
+    /// The masked envelope and server public key
+    #[prost(bytes = "vec", tag = "3")]
+    pub masked_response: Vec<u8>,
+    /// The second message of the key exchange
+    #[prost(bytes = "vec", tag = "4")]
+    pub ke2: Vec<u8>,
+}
+
+/// The last message of the login, sent by the client
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CredentialFinalization {
+    /// The third message of the key exchange
+    #[prost(bytes = "vec", tag = "1")]
+    pub ke3: Vec<u8>,
+}
+
+/// The first message of a credential retrieval without key exchange, sent by
+/// the client
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetrievalRequest {
+    /// The blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub blinded_message: Vec<u8>,
+}
+
+/// The answer of the server to a [`RetrievalRequest`]
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RetrievalResponse {
+    /// The evaluated blinded password
+    #[prost(bytes = "vec", tag = "1")]
+    pub evaluated_message: Vec<u8>,
+    /// The nonce used to mask the response
+    #[prost(bytes = "vec", tag = "2")]
+    pub masking_nonce: Vec<u8>,
+    /// The masked envelope and server public key
+    #[prost(bytes = "vec", tag = "3")]
+    pub masked_response: Vec<u8>,
+}
+
+// Checks the length of every field and concatenates them into the
+// serialization of the protocol message
+fn concat(fields: &[(&[u8], usize, &'static str)]) -> Result<Vec<u8>, ProtocolError> {
+    let mut bytes = Vec::with_capacity(fields.iter().map(|(_, len, _)| len).sum());
+
+    for (field, len, name) in fields {
+        bytes.extend_from_slice(check_slice_size(field, *len, name)?);
+    }
+
+    Ok(bytes)
+}
+
+impl<CS: CipherSuite> From<&messages::RegistrationRequest<CS>> for RegistrationRequest
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::RegistrationRequest<CS>) -> Self {
+        Self {
+            blinded_message: message.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<RegistrationRequest> for messages::RegistrationRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: RegistrationRequest) -> Result<Self, Self::Error> {
+        Self::deserialize(&message.blinded_message)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::RegistrationResponse<CS>> for RegistrationResponse
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::RegistrationResponse<CS>) -> Self {
+        Self {
+            evaluated_message: OprfGroup::<CS>::serialize_elem(message.evaluation_element.value())
+                .to_vec(),
+            server_public_key: message.server_s_pk.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<RegistrationResponse> for messages::RegistrationResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: RegistrationResponse) -> Result<Self, Self::Error> {
+        Self::deserialize(&concat(&[
+            (
+                &message.evaluated_message,
+                <OprfGroup<CS> as Group>::ElemLen::USIZE,
+                "evaluated_message",
+            ),
+            (
+                &message.server_public_key,
+                <CS::KeGroup as KeGroup>::PkLen::USIZE,
+                "server_public_key",
+            ),
+        ])?)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::RegistrationUpload<CS>> for RegistrationUpload
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+{
+    fn from(message: &messages::RegistrationUpload<CS>) -> Self {
+        Self {
+            client_public_key: message.client_s_pk.serialize().to_vec(),
+            masking_key: message.masking_key.to_vec(),
+            envelope: message.envelope.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<RegistrationUpload> for messages::RegistrationUpload<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: RegistrationUpload) -> Result<Self, Self::Error> {
+        Self::deserialize(&concat(&[
+            (
+                &message.client_public_key,
+                <CS::KeGroup as KeGroup>::PkLen::USIZE,
+                "client_public_key",
+            ),
+            (
+                &message.masking_key,
+                OutputSize::<OprfHash<CS>>::USIZE,
+                "masking_key",
+            ),
+            (&message.envelope, Sizes::<CS>::ENVELOPE_LEN, "envelope"),
+        ])?)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::CredentialRequest<CS>> for CredentialRequest
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::CredentialRequest<CS>) -> Self {
+        Self {
+            blinded_message: OprfGroup::<CS>::serialize_elem(message.blinded_element.value())
+                .to_vec(),
+            ke1: message.ke1_message.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<CredentialRequest> for messages::CredentialRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: CredentialRequest) -> Result<Self, Self::Error> {
+        Self::deserialize(&concat(&[
+            (
+                &message.blinded_message,
+                <OprfGroup<CS> as Group>::ElemLen::USIZE,
+                "blinded_message",
+            ),
+            (&message.ke1, Ke1MessageLen::<CS>::USIZE, "ke1"),
+        ])?)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::CredentialResponse<CS>> for CredentialResponse
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::CredentialResponse<CS>) -> Self {
+        Self {
+            evaluated_message: OprfGroup::<CS>::serialize_elem(message.evaluation_element.value())
+                .to_vec(),
+            masking_nonce: message.masking_nonce.to_vec(),
+            masked_response: message.masked_response.iter().flatten().copied().collect(),
+            ke2: message.ke2_message.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<CredentialResponse> for messages::CredentialResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: CredentialResponse) -> Result<Self, Self::Error> {
+        Self::deserialize(&concat(&[
+            (
+                &message.evaluated_message,
+                <OprfGroup<CS> as Group>::ElemLen::USIZE,
+                "evaluated_message",
+            ),
+            (&message.masking_nonce, NonceLen::USIZE, "masking_nonce"),
+            (
+                &message.masked_response,
+                masked_response_len::<CS>(),
+                "masked_response",
+            ),
+            (&message.ke2, Ke2MessageLen::<CS>::USIZE, "ke2"),
+        ])?)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::CredentialFinalization<CS>> for CredentialFinalization
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::CredentialFinalization<CS>) -> Self {
+        Self {
+            ke3: message.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<CredentialFinalization> for messages::CredentialFinalization<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: CredentialFinalization) -> Result<Self, Self::Error> {
+        Self::deserialize(check_slice_size(
+            &message.ke3,
+            Ke3MessageLen::<CS>::USIZE,
+            "ke3",
+        )?)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::RetrievalRequest<CS>> for RetrievalRequest
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::RetrievalRequest<CS>) -> Self {
+        Self {
+            blinded_message: message.serialize().to_vec(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<RetrievalRequest> for messages::RetrievalRequest<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: RetrievalRequest) -> Result<Self, Self::Error> {
+        Self::deserialize(&message.blinded_message)
+    }
+}
+
+impl<CS: CipherSuite> From<&messages::RetrievalResponse<CS>> for RetrievalResponse
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    fn from(message: &messages::RetrievalResponse<CS>) -> Self {
+        Self {
+            evaluated_message: OprfGroup::<CS>::serialize_elem(message.evaluation_element.value())
+                .to_vec(),
+            masking_nonce: message.masking_nonce.to_vec(),
+            masked_response: message.masked_response.iter().flatten().copied().collect(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> TryFrom<RetrievalResponse> for messages::RetrievalResponse<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Error = ProtocolError;
+
+    fn try_from(message: RetrievalResponse) -> Result<Self, Self::Error> {
+        Self::deserialize(&concat(&[
+            (
+                &message.evaluated_message,
+                <OprfGroup<CS> as Group>::ElemLen::USIZE,
+                "evaluated_message",
+            ),
+            (&message.masking_nonce, NonceLen::USIZE, "masking_nonce"),
+            (
+                &message.masked_response,
+                masked_response_len::<CS>(),
+                "masked_response",
+            ),
+        ])?)
+    }
+}
+
+// MaskedResponse: Envelope + KePk
+fn masked_response_len<CS: CipherSuite>() -> usize
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    Sizes::<CS>::ENVELOPE_LEN + <CS::KeGroup as KeGroup>::PkLen::USIZE
+}
@@ -0,0 +1,360 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Choosing the implementation of HMAC and HKDF
+//!
+//! A [`Provider`] computes every HMAC and HKDF of the protocol: the key
+//! schedule and MACs of the key exchange, the envelope, the masking of the
+//! credential response and the derivation of the OPRF keys. The provider of a
+//! [`CipherSuite`](crate::CipherSuite) is the one of its
+//! [`KeyExchange`](crate::CipherSuite::KeyExchange), which for
+//! [`TripleDh`](crate::key_exchange::tripledh::TripleDh) is its second
+//! parameter:
+//!
+//! - [`RustCrypto`], the default, uses the `hmac` and `hkdf` crates and
+//!   supports any hash of the OPRF suite.
+//! - `Ring`, available with the `ring` feature, uses the `ring` crate and
+//!   supports SHA-256, SHA-384 and SHA-512, see `RingHash`.
+//! - `AwsLc`, available with the `aws-lc-rs` feature, uses the `aws-lc-rs`
+//!   crate and supports SHA-256, SHA-384 and SHA-512, see `AwsLcHash`.
+//!
+//! ```ignore
+//! impl CipherSuite for P256Ring {
+//!     type OprfCs = p256::NistP256;
+//!     type KeGroup = p256::NistP256;
+//!     type KeyExchange = TripleDh<FullMac, Ring>;
+//!     type Ksf = Argon2<'static>;
+//!     type AppDataLen = U0;
+//! }
+//! ```
+//!
+//! All providers compute the same values, so clients and servers can use
+//! different ones. The transcript hash of the key exchange, the OPRF and the
+//! key stretching function still use the hash of the OPRF suite directly, as
+//! do the extensions of the crate outside of the protocol, i.e. the
+//! [`exporter`](crate::exporter), [`receipt`](crate::receipt)s, registration
+//! tokens, throttle tokens and the keystore.
+
+use digest::core_api::BlockSizeUser;
+use digest::Output;
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+use generic_array::typenum::Unsigned;
+use generic_array::typenum::{IsLess, Le, NonZero, U256};
+use hkdf::{Hkdf, HkdfExtract};
+use hmac::{Hmac, Mac};
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::errors::InternalError;
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+use crate::hash::OutputSize;
+use crate::hash::{Hash, ProxyHash};
+
+/// An implementation of HMAC and HKDF with the hash `D`, see the [module
+/// documentation](self)
+pub trait Provider<D: Hash>
+where
+    D::Core: ProxyHash,
+    <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// A pseudorandom key of HKDF, ready to be expanded
+    type Prk;
+    /// An HMAC computation in progress
+    type Mac;
+
+    /// HKDF-Extract of the concatenation of `ikm`, returning the pseudorandom
+    /// key both as bytes and ready to be expanded
+    fn extract(salt: Option<&[u8]>, ikm: &[&[u8]]) -> (Output<D>, Self::Prk);
+
+    /// Loads the pseudorandom key `prk` of HKDF
+    ///
+    /// # Errors
+    /// [`InternalError::HkdfError`] if `prk` is shorter than the output of `D`
+    fn from_prk(prk: &[u8]) -> Result<Self::Prk, InternalError>;
+
+    /// HKDF-Expand of `prk` with the concatenation of `info`, filling `okm`
+    ///
+    /// # Errors
+    /// [`InternalError::HkdfError`] if `okm` is longer than 255 times the
+    /// output of `D`
+    fn expand(prk: &Self::Prk, info: &[&[u8]], okm: &mut [u8]) -> Result<(), InternalError>;
+
+    /// Starts an HMAC with `key`
+    ///
+    /// # Errors
+    /// [`InternalError::HmacError`] if `key` is not accepted
+    fn new_mac(key: &[u8]) -> Result<Self::Mac, InternalError>;
+
+    /// Feeds `data` into `mac`
+    fn update_mac(mac: &mut Self::Mac, data: &[u8]);
+
+    /// Returns the HMAC of everything fed into `mac`
+    fn finalize_mac(mac: Self::Mac) -> Output<D>;
+
+    /// Compares `tag` with the first bytes of the HMAC of `mac` in constant
+    /// time, an empty `tag` or one longer than the HMAC never matches
+    fn verify_mac(mac: Self::Mac, tag: &[u8]) -> Choice {
+        let expected = Self::finalize_mac(mac);
+
+        if tag.is_empty() || tag.len() > expected.len() {
+            return Choice::from(0);
+        }
+
+        expected[..tag.len()].ct_eq(tag)
+    }
+}
+
+/// The [`Provider`] of the `hmac` and `hkdf` crates
+pub struct RustCrypto;
+
+impl<D: Hash> Provider<D> for RustCrypto
+where
+    D::Core: ProxyHash,
+    <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Prk = Hkdf<D>;
+    type Mac = Hmac<D>;
+
+    fn extract(salt: Option<&[u8]>, ikm: &[&[u8]]) -> (Output<D>, Self::Prk) {
+        let mut hkdf = HkdfExtract::<D>::new(salt);
+        for chunk in ikm {
+            hkdf.input_ikm(chunk);
+        }
+        hkdf.finalize()
+    }
+
+    fn from_prk(prk: &[u8]) -> Result<Self::Prk, InternalError> {
+        Hkdf::from_prk(prk).map_err(|_| InternalError::HkdfError)
+    }
+
+    fn expand(prk: &Self::Prk, info: &[&[u8]], okm: &mut [u8]) -> Result<(), InternalError> {
+        prk.expand_multi_info(info, okm)
+            .map_err(|_| InternalError::HkdfError)
+    }
+
+    fn new_mac(key: &[u8]) -> Result<Self::Mac, InternalError> {
+        Hmac::new_from_slice(key).map_err(|_| InternalError::HmacError)
+    }
+
+    fn update_mac(mac: &mut Self::Mac, data: &[u8]) {
+        mac.update(data);
+    }
+
+    fn finalize_mac(mac: Self::Mac) -> Output<D> {
+        mac.finalize().into_bytes()
+    }
+}
+
+/// The [`Provider`] of the `ring` crate, available with the `ring` feature
+///
+/// It supports the hashes implementing [`RingHash`].
+#[cfg(feature = "ring")]
+pub struct Ring;
+
+/// A hash of the `sha2` crate which [`Ring`] computes with the same algorithm
+/// of the `ring` crate, available with the `ring` feature
+#[cfg(feature = "ring")]
+pub trait RingHash {
+    /// The HKDF algorithm of `ring` with this hash, which must have the same
+    /// output length
+    fn hkdf_algorithm() -> ring::hkdf::Algorithm;
+}
+
+#[cfg(feature = "ring")]
+impl RingHash for sha2::Sha256 {
+    fn hkdf_algorithm() -> ring::hkdf::Algorithm {
+        ring::hkdf::HKDF_SHA256
+    }
+}
+
+#[cfg(feature = "ring")]
+impl RingHash for sha2::Sha384 {
+    fn hkdf_algorithm() -> ring::hkdf::Algorithm {
+        ring::hkdf::HKDF_SHA384
+    }
+}
+
+#[cfg(feature = "ring")]
+impl RingHash for sha2::Sha512 {
+    fn hkdf_algorithm() -> ring::hkdf::Algorithm {
+        ring::hkdf::HKDF_SHA512
+    }
+}
+
+// The length of the output of HKDF-Expand for `ring` and `aws-lc-rs`
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+struct OkmLen(usize);
+
+#[cfg(feature = "ring")]
+impl ring::hkdf::KeyType for OkmLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+#[cfg(feature = "ring")]
+impl<D: Hash + RingHash> Provider<D> for Ring
+where
+    D::Core: ProxyHash,
+    <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Prk = ring::hkdf::Prk;
+    type Mac = ring::hmac::Context;
+
+    fn extract(salt: Option<&[u8]>, ikm: &[&[u8]]) -> (Output<D>, Self::Prk) {
+        // HKDF-Extract is the HMAC of the input keyed with the salt, and HMAC
+        // pads a missing salt with zeros, like HKDF
+        let salt = ring::hmac::Key::new(
+            D::hkdf_algorithm().hmac_algorithm(),
+            salt.unwrap_or_default(),
+        );
+        let mut mac = ring::hmac::Context::with_key(&salt);
+        for chunk in ikm {
+            mac.update(chunk);
+        }
+        let prk = Output::<D>::clone_from_slice(mac.sign().as_ref());
+        let hkdf = ring::hkdf::Prk::new_less_safe(D::hkdf_algorithm(), &prk);
+
+        (prk, hkdf)
+    }
+
+    fn from_prk(prk: &[u8]) -> Result<Self::Prk, InternalError> {
+        if prk.len() < OutputSize::<D>::USIZE {
+            return Err(InternalError::HkdfError);
+        }
+
+        Ok(ring::hkdf::Prk::new_less_safe(D::hkdf_algorithm(), prk))
+    }
+
+    fn expand(prk: &Self::Prk, info: &[&[u8]], okm: &mut [u8]) -> Result<(), InternalError> {
+        prk.expand(info, OkmLen(okm.len()))
+            .and_then(|expanded| expanded.fill(okm))
+            .map_err(|_| InternalError::HkdfError)
+    }
+
+    fn new_mac(key: &[u8]) -> Result<Self::Mac, InternalError> {
+        Ok(ring::hmac::Context::with_key(&ring::hmac::Key::new(
+            D::hkdf_algorithm().hmac_algorithm(),
+            key,
+        )))
+    }
+
+    fn update_mac(mac: &mut Self::Mac, data: &[u8]) {
+        mac.update(data);
+    }
+
+    fn finalize_mac(mac: Self::Mac) -> Output<D> {
+        Output::<D>::clone_from_slice(mac.sign().as_ref())
+    }
+}
+
+/// The [`Provider`] of the `aws-lc-rs` crate, available with the `aws-lc-rs`
+/// feature
+///
+/// It supports the hashes implementing [`AwsLcHash`]. With the `fips` feature
+/// of `aws-lc-rs`, HMAC and HKDF are computed by its FIPS-validated module.
+#[cfg(feature = "aws-lc-rs")]
+pub struct AwsLc;
+
+/// A hash of the `sha2` crate which [`AwsLc`] computes with the same algorithm
+/// of the `aws-lc-rs` crate, available with the `aws-lc-rs` feature
+#[cfg(feature = "aws-lc-rs")]
+pub trait AwsLcHash {
+    /// The HKDF algorithm of `aws-lc-rs` with this hash, which must have the
+    /// same output length
+    fn hkdf_algorithm() -> aws_lc_rs::hkdf::Algorithm;
+}
+
+#[cfg(feature = "aws-lc-rs")]
+impl AwsLcHash for sha2::Sha256 {
+    fn hkdf_algorithm() -> aws_lc_rs::hkdf::Algorithm {
+        aws_lc_rs::hkdf::HKDF_SHA256
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+impl AwsLcHash for sha2::Sha384 {
+    fn hkdf_algorithm() -> aws_lc_rs::hkdf::Algorithm {
+        aws_lc_rs::hkdf::HKDF_SHA384
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+impl AwsLcHash for sha2::Sha512 {
+    fn hkdf_algorithm() -> aws_lc_rs::hkdf::Algorithm {
+        aws_lc_rs::hkdf::HKDF_SHA512
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+impl aws_lc_rs::hkdf::KeyType for OkmLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+impl<D: Hash + AwsLcHash> Provider<D> for AwsLc
+where
+    D::Core: ProxyHash,
+    <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    type Prk = aws_lc_rs::hkdf::Prk;
+    type Mac = aws_lc_rs::hmac::Context;
+
+    fn extract(salt: Option<&[u8]>, ikm: &[&[u8]]) -> (Output<D>, Self::Prk) {
+        // As for `Ring`, HKDF-Extract is computed as the HMAC of the input
+        // keyed with the salt, which also yields the bytes of the key
+        let salt = aws_lc_rs::hmac::Key::new(
+            D::hkdf_algorithm().hmac_algorithm(),
+            salt.unwrap_or_default(),
+        );
+        let mut mac = aws_lc_rs::hmac::Context::with_key(&salt);
+        for chunk in ikm {
+            mac.update(chunk);
+        }
+        let prk = Output::<D>::clone_from_slice(mac.sign().as_ref());
+        let hkdf = aws_lc_rs::hkdf::Prk::new_less_safe(D::hkdf_algorithm(), &prk);
+
+        (prk, hkdf)
+    }
+
+    fn from_prk(prk: &[u8]) -> Result<Self::Prk, InternalError> {
+        if prk.len() < OutputSize::<D>::USIZE {
+            return Err(InternalError::HkdfError);
+        }
+
+        Ok(aws_lc_rs::hkdf::Prk::new_less_safe(
+            D::hkdf_algorithm(),
+            prk,
+        ))
+    }
+
+    fn expand(prk: &Self::Prk, info: &[&[u8]], okm: &mut [u8]) -> Result<(), InternalError> {
+        prk.expand(info, OkmLen(okm.len()))
+            .and_then(|expanded| expanded.fill(okm))
+            .map_err(|_| InternalError::HkdfError)
+    }
+
+    fn new_mac(key: &[u8]) -> Result<Self::Mac, InternalError> {
+        Ok(aws_lc_rs::hmac::Context::with_key(
+            &aws_lc_rs::hmac::Key::new(D::hkdf_algorithm().hmac_algorithm(), key),
+        ))
+    }
+
+    fn update_mac(mac: &mut Self::Mac, data: &[u8]) {
+        mac.update(data);
+    }
+
+    fn finalize_mac(mac: Self::Mac) -> Output<D> {
+        Output::<D>::clone_from_slice(mac.sign().as_ref())
+    }
+}
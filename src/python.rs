@@ -0,0 +1,223 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Python bindings of the server side of OPAQUE through
+//! [PyO3](https://pyo3.rs), available with the `python` feature
+//!
+//! The bindings use the [`Ristretto255Sha512Argon2id`] cipher suite and are
+//! meant for tooling around a production deployment, such as scripts which
+//! migrate or audit a database of password files, or answer logins in tests.
+//! Messages and states are passed as `bytes` holding their serialization, and
+//! password files are read with `ServerRegistration.deserialize`:
+//!
+//! ```text
+//! import opaque
+//!
+//! setup = opaque.ServerSetup.deserialize(stored_setup)
+//! for credential_identifier, record in database:
+//!     password_file = opaque.ServerRegistration.deserialize(record)
+//!     print(credential_identifier, password_file.client_public_key().hex())
+//! ```
+//!
+//! A crate building the extension module depends on this crate with the
+//! `python` feature and on `pyo3` with the `extension-module` feature, and
+//! adds the bindings to its module with [`register`]:
+//!
+//! ```ignore
+//! #[pymodule]
+//! fn opaque(module: &Bound<'_, PyModule>) -> PyResult<()> {
+//!     opaque_ke::python::register(module)
+//! }
+//! ```
+//!
+//! Failures are raised as `OpaqueError` with the message of the
+//! [`ProtocolError`]. Randomness is taken from the operating system.
+
+use std::string::ToString;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::rngs::OsRng;
+
+use crate::errors::ProtocolError;
+use crate::suites::Ristretto255Sha512Argon2id;
+use crate::{
+    CredentialFinalization, CredentialRequest, RegistrationRequest, RegistrationUpload,
+    ServerLogin, ServerLoginStartParameters,
+};
+
+type Suite = Ristretto255Sha512Argon2id;
+
+create_exception!(
+    opaque,
+    OpaqueError,
+    PyException,
+    "Raised if the protocol fails"
+);
+
+fn error(error: ProtocolError) -> PyErr {
+    OpaqueError::new_err(error.to_string())
+}
+
+/// The keys of the server, see [`crate::ServerSetup`]
+#[pyclass]
+pub struct ServerSetup(crate::ServerSetup<Suite>);
+
+#[pymethods]
+impl ServerSetup {
+    /// Generates a new setup
+    #[new]
+    fn new() -> PyResult<Self> {
+        crate::ServerSetup::new(&mut OsRng).map(Self).map_err(error)
+    }
+
+    /// Deserializes a setup returned by `serialize`
+    #[staticmethod]
+    fn deserialize(input: &[u8]) -> PyResult<Self> {
+        crate::ServerSetup::deserialize(input)
+            .map(Self)
+            .map_err(error)
+    }
+
+    /// Serializes the setup, which contains the private keys of the server
+    fn serialize<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.serialize())
+    }
+
+    /// Returns the static public key of the server
+    fn public_key<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.public_key().serialize())
+    }
+}
+
+/// A password file, see [`crate::ServerRegistration`]
+#[pyclass]
+pub struct ServerRegistration(crate::ServerRegistration<Suite>);
+
+#[pymethods]
+impl ServerRegistration {
+    /// Deserializes a password file in any known format, see
+    /// [`crate::ServerRegistration::deserialize_versioned`]
+    #[staticmethod]
+    fn deserialize(input: &[u8]) -> PyResult<Self> {
+        crate::ServerRegistration::deserialize_versioned(input, |_| {
+            Err(ProtocolError::SerializationError)
+        })
+        .map(|result| Self(result.record))
+        .map_err(error)
+    }
+
+    /// Returns `True` if `input` is a valid unversioned password file, see
+    /// [`crate::ServerRegistration::validate`]
+    #[staticmethod]
+    fn validate(input: &[u8]) -> bool {
+        crate::ServerRegistration::<Suite>::validate(input).is_ok()
+    }
+
+    /// Serializes the password file without a version
+    fn serialize<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.serialize())
+    }
+
+    /// Serializes the password file prefixed with its version
+    fn serialize_versioned<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.serialize_versioned())
+    }
+
+    /// Returns the static public key of the client
+    fn client_public_key<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.upload.client_public_key().serialize())
+    }
+}
+
+/// Answers a serialized registration request, returning the serialized
+/// registration response
+#[pyfunction]
+fn server_registration_start<'py>(
+    py: Python<'py>,
+    server_setup: &ServerSetup,
+    request: &[u8],
+    credential_identifier: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let request = RegistrationRequest::deserialize(request).map_err(error)?;
+    let result = crate::ServerRegistration::start(&server_setup.0, request, credential_identifier)
+        .map_err(error)?;
+
+    Ok(PyBytes::new(py, &result.message.serialize()))
+}
+
+/// Creates the password file from a serialized registration upload
+#[pyfunction]
+fn server_registration_finish(upload: &[u8]) -> PyResult<ServerRegistration> {
+    let upload = RegistrationUpload::deserialize(upload).map_err(error)?;
+
+    Ok(ServerRegistration(crate::ServerRegistration::finish(
+        upload,
+    )))
+}
+
+/// Answers a serialized credential request with the password file, or a dummy
+/// response if it is `None`, returning the serialized state of the server and
+/// the serialized credential response
+#[pyfunction]
+#[pyo3(signature = (server_setup, password_file, request, credential_identifier))]
+fn server_login_start<'py>(
+    py: Python<'py>,
+    server_setup: &ServerSetup,
+    password_file: Option<&ServerRegistration>,
+    request: &[u8],
+    credential_identifier: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let request = CredentialRequest::deserialize(request).map_err(error)?;
+    let result = ServerLogin::start(
+        &mut OsRng,
+        &server_setup.0,
+        password_file.map(|password_file| password_file.0.clone()),
+        request,
+        credential_identifier,
+        ServerLoginStartParameters::default(),
+    )
+    .map_err(error)?;
+
+    Ok((
+        PyBytes::new(py, &result.state.serialize()),
+        PyBytes::new(py, &result.message.serialize()),
+    ))
+}
+
+/// Finishes a login with the state returned by `server_login_start` and the
+/// serialized credential finalization, returning the session key
+///
+/// Raises `OpaqueError` if the client failed to authenticate.
+#[pyfunction]
+fn server_login_finish<'py>(
+    py: Python<'py>,
+    state: &[u8],
+    finalization: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let state = ServerLogin::<Suite>::deserialize(state).map_err(error)?;
+    let finalization = CredentialFinalization::deserialize(finalization).map_err(error)?;
+    let result = state.finish(finalization).map_err(error)?;
+
+    Ok(PyBytes::new(py, &result.session_key))
+}
+
+/// Adds the classes and functions of the bindings to `module`
+pub fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("OpaqueError", module.py().get_type::<OpaqueError>())?;
+    module.add_class::<ServerSetup>()?;
+    module.add_class::<ServerRegistration>()?;
+    module.add_function(wrap_pyfunction!(server_registration_start, module)?)?;
+    module.add_function(wrap_pyfunction!(server_registration_finish, module)?)?;
+    module.add_function(wrap_pyfunction!(server_login_start, module)?)?;
+    module.add_function(wrap_pyfunction!(server_login_finish, module)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,387 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Auditable registration receipts
+//!
+//! A [`RegistrationReceipt`] is a signature of the server over the hash of a
+//! [`RegistrationUpload`] stored for a credential identifier, and the time it
+//! was stored at. The server issues it with
+//! [`ServerSetup::registration_receipt`](crate::ServerSetup::registration_receipt)
+//! after storing the password file, and the client keeps it together with the
+//! upload it sent. Later, e.g. when the server is suspected to have replaced a
+//! record, [`RegistrationReceipt::verify`] shows whether the server accepted
+//! that upload for the credential identifier:
+//!
+//! ```ignore
+//! // Server, after storing the password file
+//! let receipt = server_setup.registration_receipt(&mut OsRng, credential_identifier, &password_file, now)?;
+//!
+//! // Client, with the public key it pinned earlier
+//! receipt.verify(&receipt_public_key, credential_identifier, &client_registration_finish_result.message)?;
+//! ```
+//!
+//! Receipts are Schnorr signatures in the group of the OPRF. The signing key
+//! is derived from the OPRF seed of the [`ServerSetup`](crate::ServerSetup), so
+//! the [`ReceiptPublicKey`] returned by
+//! [`ServerSetup::receipt_public_key`](crate::ServerSetup::receipt_public_key)
+//! stays the same for as long as the [`ServerSetup`](crate::ServerSetup) does,
+//! and should be distributed to clients out of band.
+
+use core::ops::Add;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Digest, Output, OutputSizeUser};
+use generic_array::sequence::Concat;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256, U8};
+use generic_array::{ArrayLength, GenericArray};
+#[cfg(feature = "server")]
+use rand::{CryptoRng, RngCore};
+use subtle::ConstantTimeEq;
+use voprf::Group;
+#[cfg(feature = "server")]
+use zeroize::Zeroizing;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::utils::check_slice_size;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::MacLen;
+#[cfg(feature = "server")]
+use crate::key_exchange::traits::SuiteProvider;
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::RegistrationUploadLen;
+#[cfg(feature = "server")]
+use crate::provider::Provider;
+#[cfg(feature = "server")]
+use crate::util::random_scalar;
+use crate::RegistrationUpload;
+
+#[cfg(feature = "server")]
+static STR_RECEIPT_KEY: &[u8] = b"RegistrationReceiptKey";
+static STR_RECEIPT_CHALLENGE: &[u8] = b"OPAQUE-RegistrationReceipt";
+
+/// Length of a serialized [`ReceiptPublicKey`]
+pub type ReceiptPublicKeyLen<CS: CipherSuite> = <OprfGroup<CS> as Group>::ElemLen;
+
+/// Length of a serialized [`RegistrationReceipt`]
+pub type RegistrationReceiptLen<CS: CipherSuite> = Sum<
+    Sum<Sum<U8, OutputSize<OprfHash<CS>>>, <OprfGroup<CS> as Group>::ElemLen>,
+    <OprfGroup<CS> as Group>::ScalarLen,
+>;
+
+/// The public key verifying [`RegistrationReceipt`]s, see the [module
+/// documentation](self)
+#[derive_where(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ReceiptPublicKey<CS: CipherSuite>(GenericArray<u8, ReceiptPublicKeyLen<CS>>)
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero;
+
+/// A signature of the server over a stored registration record, see the
+/// [module documentation](self)
+#[derive_where(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RegistrationReceipt<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    issued_at: u64,
+    record_hash: Output<OprfHash<CS>>,
+    commitment: GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>,
+    response: GenericArray<u8, <OprfGroup<CS> as Group>::ScalarLen>,
+}
+
+impl<CS: CipherSuite> ReceiptPublicKey<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, ReceiptPublicKeyLen<CS>> {
+        self.0.clone()
+    }
+
+    /// Deserialization from bytes
+    ///
+    /// # Errors
+    /// [`ProtocolError::LibraryError`] if `input` is not a valid element of the
+    /// group
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let element = OprfGroup::<CS>::deserialize_elem(input)?;
+
+        Ok(Self(OprfGroup::<CS>::serialize_elem(element)))
+    }
+}
+
+impl<CS: CipherSuite> RegistrationReceipt<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Returns the time the record was stored at, as given by the server
+    ///
+    /// This is only authenticated after a successful
+    /// [`RegistrationReceipt::verify`].
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Returns the hash of the serialized record the receipt was issued for
+    pub fn record_hash(&self) -> &Output<OprfHash<CS>> {
+        &self.record_hash
+    }
+
+    /// Verifies the receipt with `public_key`, for `record` stored under
+    /// `credential_identifier`
+    ///
+    /// # Errors
+    /// [`ProtocolError::ReceiptError`] if `record` is not the record the
+    /// receipt was issued for, or if the receipt was not issued for
+    /// `credential_identifier` by the server holding `public_key`
+    pub fn verify(
+        &self,
+        public_key: &ReceiptPublicKey<CS>,
+        credential_identifier: &[u8],
+        record: &RegistrationUpload<CS>,
+    ) -> Result<(), ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        if !bool::from(hash_record::<CS>(record).ct_eq(&self.record_hash)) {
+            return Err(ProtocolError::ReceiptError);
+        }
+
+        let public_key = OprfGroup::<CS>::deserialize_elem(&public_key.0)?;
+        let commitment = OprfGroup::<CS>::deserialize_elem(&self.commitment)
+            .map_err(|_| ProtocolError::ReceiptError)?;
+        let response = OprfGroup::<CS>::deserialize_scalar(&self.response)
+            .map_err(|_| ProtocolError::ReceiptError)?;
+        let challenge = challenge::<CS>(
+            &self.commitment,
+            &OprfGroup::<CS>::serialize_elem(public_key),
+            credential_identifier,
+            &self.record_hash,
+            self.issued_at,
+        )?;
+
+        let expected = commitment + &(public_key * &challenge);
+        if bool::from(
+            OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::base_elem() * &response)
+                .ct_eq(&OprfGroup::<CS>::serialize_elem(expected)),
+        ) {
+            Ok(())
+        } else {
+            Err(ProtocolError::ReceiptError)
+        }
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, RegistrationReceiptLen<CS>>
+    where
+        // RegistrationReceipt: ((IssuedAt + Hash) + Elem) + Scalar
+        U8: Add<OutputSize<OprfHash<CS>>>,
+        Sum<U8, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ElemLen>,
+        Sum<Sum<U8, OutputSize<OprfHash<CS>>>, <OprfGroup<CS> as Group>::ElemLen>:
+            ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ScalarLen>,
+        RegistrationReceiptLen<CS>: ArrayLength<u8>,
+    {
+        GenericArray::from(self.issued_at.to_be_bytes())
+            .concat(self.record_hash.clone())
+            .concat(self.commitment.clone())
+            .concat(self.response.clone())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let hash_len = OutputSize::<OprfHash<CS>>::USIZE;
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let checked_slice = check_slice_size(
+            input,
+            U8::USIZE + hash_len + elem_len + <OprfGroup<CS> as Group>::ScalarLen::USIZE,
+            "registration_receipt",
+        )?;
+
+        let mut issued_at = [0; 8];
+        issued_at.copy_from_slice(&checked_slice[..8]);
+        let (record_hash, rest) = checked_slice[8..].split_at(hash_len);
+        let (commitment, response) = rest.split_at(elem_len);
+
+        Ok(Self {
+            issued_at: u64::from_be_bytes(issued_at),
+            record_hash: GenericArray::clone_from_slice(record_hash),
+            commitment: GenericArray::clone_from_slice(commitment),
+            response: GenericArray::clone_from_slice(response),
+        })
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn new<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        oprf_seed: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+        record: &RegistrationUpload<CS>,
+        issued_at: u64,
+    ) -> Result<Self, ProtocolError>
+    where
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let signing_key = signing_key::<CS>(oprf_seed)?;
+        let public_key =
+            OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::base_elem() * &signing_key);
+        let record_hash = hash_record::<CS>(record);
+
+        let nonce = random_scalar::<OprfGroup<CS>, _>(rng)?;
+        let commitment = OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::base_elem() * &nonce);
+        let challenge = challenge::<CS>(
+            &commitment,
+            &public_key,
+            credential_identifier,
+            &record_hash,
+            issued_at,
+        )?;
+
+        Ok(Self {
+            issued_at,
+            record_hash,
+            commitment,
+            response: OprfGroup::<CS>::serialize_scalar(nonce + &(challenge * &signing_key)),
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+impl<CS: CipherSuite> ReceiptPublicKey<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    pub(crate) fn new(oprf_seed: &Output<OprfHash<CS>>) -> Result<Self, ProtocolError> {
+        let signing_key = signing_key::<CS>(oprf_seed)?;
+
+        Ok(Self(OprfGroup::<CS>::serialize_elem(
+            OprfGroup::<CS>::base_elem() * &signing_key,
+        )))
+    }
+}
+
+// The signing key is expanded from the OPRF seed with a label that can not
+// collide with the info used for OPRF keys, which always ends in "OprfKey"
+#[cfg(feature = "server")]
+fn signing_key<CS: CipherSuite>(
+    oprf_seed: &Output<OprfHash<CS>>,
+) -> Result<<OprfGroup<CS> as Group>::Scalar, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut key_material = Zeroizing::new(Output::<OprfHash<CS>>::default());
+    SuiteProvider::<CS>::expand(
+        &SuiteProvider::<CS>::from_prk(oprf_seed)?,
+        &[STR_RECEIPT_KEY],
+        &mut key_material,
+    )?;
+
+    Ok(OprfGroup::<CS>::hash_to_scalar::<OprfHash<CS>>(
+        &[key_material.as_slice()],
+        &[STR_RECEIPT_KEY],
+    )?)
+}
+
+fn hash_record<CS: CipherSuite>(record: &RegistrationUpload<CS>) -> Output<OprfHash<CS>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+{
+    OprfHash::<CS>::digest(record.serialize())
+}
+
+// The challenge binds the signature to the public key, the credential
+// identifier, the record and the time it was stored at
+fn challenge<CS: CipherSuite>(
+    commitment: &[u8],
+    public_key: &[u8],
+    credential_identifier: &[u8],
+    record_hash: &[u8],
+    issued_at: u64,
+) -> Result<<OprfGroup<CS> as Group>::Scalar, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    Ok(OprfGroup::<CS>::hash_to_scalar::<OprfHash<CS>>(
+        &[
+            commitment,
+            public_key,
+            &(credential_identifier.len() as u64).to_be_bytes(),
+            credential_identifier,
+            record_hash,
+            &issued_at.to_be_bytes(),
+        ],
+        &[STR_RECEIPT_CHALLENGE],
+    )?)
+}
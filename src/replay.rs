@@ -0,0 +1,104 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Rejecting replayed credential requests
+//!
+//! [`ServerLogin::start_with_replay_cache`](crate::ServerLogin::start_with_replay_cache)
+//! hashes each incoming [`CredentialRequest`](crate::CredentialRequest) and
+//! consults a [`ReplayCache`] with it before answering. A client draws fresh
+//! randomness for every request, so a request seen before is a replay.
+
+#[cfg(any(feature = "std", test))]
+use std::collections::{HashMap, VecDeque};
+#[cfg(any(feature = "std", test))]
+use std::vec::Vec;
+
+/// Remembers the hashes of credential requests seen by the server
+pub trait ReplayCache {
+    /// Returns `true` if `request_hash` was inserted before, and otherwise
+    /// inserts it and returns `false`
+    ///
+    /// Implementations backed by storage which can fail should return `true`
+    /// on failure, so that requests are rejected rather than accepted
+    /// unchecked.
+    fn check_and_insert(&mut self, request_hash: &[u8]) -> bool;
+}
+
+/// An in-memory [`ReplayCache`] holding the `capacity` most recently used
+/// hashes, available with the `std` feature
+///
+/// Replays are detected for as long as the hash of the original request has
+/// not been evicted, so the capacity should cover the number of requests a
+/// server receives within the period replays have to be rejected for.
+#[cfg(any(feature = "std", test))]
+#[derive(Clone, Debug)]
+pub struct LruReplayCache {
+    capacity: usize,
+    counter: u64,
+    // Maps each hash to the counter value of its last use
+    entries: HashMap<Vec<u8>, u64>,
+    // Hashes in order of use, including outdated uses of hashes that were used
+    // again later
+    order: VecDeque<(Vec<u8>, u64)>,
+}
+
+#[cfg(any(feature = "std", test))]
+impl LruReplayCache {
+    /// Creates an empty cache holding up to `capacity` hashes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counter: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of hashes held
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no hashes are held
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn touch(&mut self, request_hash: &[u8]) {
+        self.counter += 1;
+        self.entries.insert(request_hash.to_vec(), self.counter);
+        self.order.push_back((request_hash.to_vec(), self.counter));
+
+        while self.entries.len() > self.capacity {
+            let Some((hash, used)) = self.order.pop_front() else {
+                break;
+            };
+
+            if self.entries.get(&hash) == Some(&used) {
+                self.entries.remove(&hash);
+            }
+        }
+
+        // Drop outdated uses, so they don't accumulate for hashes which are
+        // replayed repeatedly
+        if self.order.len() > 2 * self.capacity.max(1) {
+            let entries = &self.entries;
+            self.order
+                .retain(|(hash, used)| entries.get(hash) == Some(used));
+        }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl ReplayCache for LruReplayCache {
+    fn check_and_insert(&mut self, request_hash: &[u8]) -> bool {
+        let seen = self.entries.contains_key(request_hash);
+        self.touch(request_hash);
+        seen
+    }
+}
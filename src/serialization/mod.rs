@@ -11,7 +11,6 @@ use core::marker::PhantomData;
 use digest::Update;
 use generic_array::typenum::{U0, U2};
 use generic_array::{ArrayLength, GenericArray};
-use hmac::Mac;
 
 use crate::errors::ProtocolError;
 
@@ -142,18 +141,6 @@ impl<T: Update> UpdateExt for T {
     }
 }
 
-pub(crate) trait MacExt {
-    fn update_iter<'a>(&mut self, iter: impl Iterator<Item = &'a [u8]>);
-}
-
-impl<T: Mac> MacExt for T {
-    fn update_iter<'a>(&mut self, iter: impl Iterator<Item = &'a [u8]>) {
-        for bytes in iter {
-            self.update(bytes);
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests;
 
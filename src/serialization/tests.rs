@@ -12,8 +12,8 @@ use std::vec::Vec;
 
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::{Output, OutputSizeUser};
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
-use generic_array::ArrayLength;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U0, U256};
+use generic_array::{ArrayLength, GenericArray};
 use proptest::collection::vec;
 use proptest::prelude::*;
 use rand::rngs::OsRng;
@@ -26,12 +26,14 @@ use crate::errors::*;
 use crate::hash::{Hash, OutputSize, ProxyHash};
 use crate::key_exchange::group::KeGroup;
 use crate::key_exchange::traits::{
-    Deserialize, Ke1MessageLen, Ke1StateLen, Ke2MessageLen, KeyExchange, Serialize,
+    Deserialize, Ke1MessageLen, Ke1StateLen, Ke2MessageLen, KeyExchange, MacLen, Serialize,
+    SuiteProvider,
 };
 use crate::key_exchange::tripledh::{NonceLen, TripleDh};
 use crate::keypair::{KeyPair, SecretKey};
 use crate::messages::CredentialResponseWithoutKeLen;
 use crate::opaque::{ClientLoginLen, ClientRegistrationLen, MaskedResponseLen};
+use crate::provider::Provider;
 use crate::serialization::{i2osp, os2ip};
 use crate::*;
 
@@ -44,6 +46,7 @@ impl CipherSuite for Ristretto255 {
     type KeGroup = crate::Ristretto255;
     type KeyExchange = TripleDh;
     type Ksf = crate::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 struct P256;
@@ -53,6 +56,7 @@ impl CipherSuite for P256 {
     type KeGroup = ::p256::NistP256;
     type KeyExchange = TripleDh;
     type Ksf = crate::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 struct P384;
@@ -62,6 +66,7 @@ impl CipherSuite for P384 {
     type KeGroup = ::p384::NistP384;
     type KeyExchange = TripleDh;
     type Ksf = crate::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 struct P521;
@@ -71,6 +76,7 @@ impl CipherSuite for P521 {
     type KeGroup = ::p521::NistP521;
     type KeyExchange = TripleDh;
     type Ksf = crate::ksf::Identity;
+    type AppDataLen = U0;
 }
 
 fn random_point<CS: CipherSuite>() -> <CS::KeGroup as KeGroup>::Pk
@@ -83,7 +89,7 @@ where
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
 {
     let mut rng = OsRng;
-    let sk = CS::KeGroup::random_sk(&mut rng);
+    let sk = CS::KeGroup::random_sk(&mut rng).unwrap();
     CS::KeGroup::public_key(sk)
 }
 
@@ -139,8 +145,9 @@ fn server_registration_roundtrip() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -163,7 +170,7 @@ fn server_registration_roundtrip() -> Result<(), ProtocolError> {
         // length-MAC_SIZE hmac
         mock_envelope_bytes.extend_from_slice(&Output::<OprfHash<CS>>::default());
 
-        let mock_client_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let mock_client_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         // serialization order: oprf_key, public key, envelope
         let mut bytes = Vec::<u8>::new();
         bytes.extend_from_slice(&mock_client_kp.public().serialize());
@@ -245,7 +252,7 @@ fn registration_response_roundtrip() -> Result<(), ProtocolError> {
         let pt = random_point::<CS>();
         let beta_bytes = CS::KeGroup::serialize_pk(pt);
         let mut rng = OsRng;
-        let skp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let skp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let pubkey_bytes = skp.public().serialize();
 
         let mut input = Vec::new();
@@ -291,8 +298,9 @@ fn registration_upload_roundtrip() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -301,7 +309,7 @@ fn registration_upload_roundtrip() -> Result<(), ProtocolError> {
         RegistrationUploadLen<CS>: ArrayLength<u8>,
     {
         let mut rng = OsRng;
-        let skp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let skp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let pubkey_bytes = skp.public().serialize();
 
         let mut key = [0u8; 32];
@@ -312,11 +320,12 @@ fn registration_upload_roundtrip() -> Result<(), ProtocolError> {
         let mut masking_key = Output::<OprfHash<CS>>::default();
         rng.fill_bytes(&mut masking_key);
 
-        let randomized_pwd_hasher = hkdf::Hkdf::new(None, &key);
+        let (_, randomized_pwd_hasher) = SuiteProvider::<CS>::extract(None, &[&key]);
 
         let (envelope, _, _) = Envelope::<CS>::seal_raw(
-            randomized_pwd_hasher,
+            &randomized_pwd_hasher,
             nonce.into(),
+            &GenericArray::default(),
             [pubkey_bytes.as_slice()].into_iter(),
             InnerEnvelopeMode::Internal,
         )
@@ -362,7 +371,7 @@ fn credential_request_roundtrip() -> Result<(), ProtocolError> {
         let alpha = random_point::<CS>();
         let alpha_bytes = CS::KeGroup::serialize_pk(alpha);
 
-        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let mut client_nonce = [0u8; NonceLen::USIZE];
         rng.fill_bytes(&mut client_nonce);
 
@@ -418,10 +427,10 @@ fn credential_response_roundtrip() -> Result<(), ProtocolError> {
         Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
             ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
         CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
         // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
         CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
@@ -436,10 +445,10 @@ fn credential_response_roundtrip() -> Result<(), ProtocolError> {
         rng.fill_bytes(&mut masking_nonce);
 
         let mut masked_response =
-            vec![0u8; <OprfGroup<CS> as Group>::ElemLen::USIZE + Envelope::<CS>::len()];
+            vec![0u8; <OprfGroup<CS> as Group>::ElemLen::USIZE + Sizes::<CS>::ENVELOPE_LEN];
         rng.fill_bytes(&mut masked_response);
 
-        let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let mut mac = Output::<OprfHash<CS>>::default();
         rng.fill_bytes(&mut mac);
         let mut server_nonce = [0u8; NonceLen::USIZE];
@@ -548,7 +557,7 @@ fn client_login_roundtrip() -> Result<(), ProtocolError> {
         let pw = b"hunter2";
         let mut rng = OsRng;
 
-        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let mut client_nonce = [0; NonceLen::USIZE];
         rng.fill_bytes(&mut client_nonce);
 
@@ -608,7 +617,7 @@ fn ke1_message_roundtrip() -> Result<(), ProtocolError> {
     {
         let mut rng = OsRng;
 
-        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let mut client_nonce = vec![0u8; NonceLen::USIZE];
         rng.fill_bytes(&mut client_nonce);
 
@@ -649,7 +658,7 @@ fn ke2_message_roundtrip() -> Result<(), ProtocolError> {
     {
         let mut rng = OsRng;
 
-        let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+        let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
         let mut mac = Output::<OprfHash<CS>>::default();
         rng.fill_bytes(&mut mac);
         let mut server_nonce = vec![0u8; NonceLen::USIZE];
@@ -745,6 +754,110 @@ macro_rules! test {
         mod $mod {
             use super::*;
 
+            /// Runs the protocol with an RNG seeded from `seed`, returning the
+            /// serialization of every message in order
+            fn protocol_messages(seed: [u8; 32]) -> Result<[Vec<u8>; 6], ProtocolError> {
+                use rand::rngs::StdRng;
+                use rand::SeedableRng;
+
+                let mut rng = StdRng::from_seed(seed);
+                let mut password = [0; 32];
+                rng.fill_bytes(&mut password);
+                let server_setup = ServerSetup::<$CS>::new(&mut rng)?;
+                let client_registration_start_result =
+                    ClientRegistration::<$CS>::start(&mut rng, &password)?;
+                let registration_request = client_registration_start_result.message.serialize();
+                let server_registration_start_result = ServerRegistration::<$CS>::start(
+                    &server_setup,
+                    client_registration_start_result.message,
+                    &seed,
+                )?;
+                let registration_response = server_registration_start_result.message.serialize();
+                let client_registration_finish_result = client_registration_start_result.state.finish(
+                    &mut rng,
+                    &password,
+                    server_registration_start_result.message,
+                    ClientRegistrationFinishParameters::default(),
+                )?;
+                let registration_upload = client_registration_finish_result.message.serialize();
+                let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+                let client_login_start_result = ClientLogin::<$CS>::start(&mut rng, &password)?;
+                let credential_request = client_login_start_result.message.serialize();
+                let server_login_start_result = ServerLogin::<$CS>::start(
+                    &mut rng,
+                    &server_setup,
+                    Some(password_file),
+                    client_login_start_result.message,
+                    &seed,
+                    ServerLoginStartParameters::default(),
+                )?;
+                let credential_response = server_login_start_result.message.serialize();
+                let client_login_finish_result = client_login_start_result.state.finish(
+                    &password,
+                    server_login_start_result.message,
+                    ClientLoginFinishParameters::default(),
+                )?;
+                let credential_finalization = client_login_finish_result.message.serialize();
+
+                Ok([
+                    registration_request.to_vec(),
+                    registration_response.to_vec(),
+                    registration_upload.to_vec(),
+                    credential_request.to_vec(),
+                    credential_response.to_vec(),
+                    credential_finalization.to_vec(),
+                ])
+            }
+
+            /// Deserializes `bytes` as the message at `index` of
+            /// [`protocol_messages`], returning its serialization
+            fn roundtrip(index: usize, bytes: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+                Ok(match index {
+                    0 => RegistrationRequest::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    1 => RegistrationResponse::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    2 => RegistrationUpload::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    3 => CredentialRequest::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    4 => CredentialResponse::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    5 => CredentialFinalization::<$CS>::deserialize(bytes)?.serialize().to_vec(),
+                    _ => unreachable!("unexpected message index"),
+                })
+            }
+
+            proptest! {
+                #![proptest_config(ProptestConfig::with_cases(8))]
+
+                #[test]
+                fn test_roundtrip_protocol_messages(seed in any::<[u8; 32]>()) {
+                    for (index, message) in protocol_messages(seed).unwrap().iter().enumerate() {
+                        prop_assert_eq!(&roundtrip(index, message).unwrap(), message);
+                    }
+                }
+
+                #[test]
+                fn test_length_boundaries_protocol_messages(seed in any::<[u8; 32]>()) {
+                    for (index, message) in protocol_messages(seed).unwrap().iter().enumerate() {
+                        prop_assert!(roundtrip(index, &message[..message.len() - 1]).is_err());
+                        prop_assert!(roundtrip(index, &[message.as_slice(), &[0]].concat()).is_err());
+                    }
+                }
+
+                #[test]
+                fn test_identity_rejection_protocol_messages(seed in any::<[u8; 32]>()) {
+                    let identity = <OprfGroup<$CS> as Group>::serialize_elem(
+                        <OprfGroup<$CS> as Group>::identity_elem(),
+                    );
+                    let messages = protocol_messages(seed).unwrap();
+
+                    // The OPRF element leads every message but the last two
+                    // registration and login messages
+                    for index in [0, 1, 3, 4] {
+                        let mut message = messages[index].clone();
+                        message[..identity.len()].copy_from_slice(&identity);
+                        prop_assert!(roundtrip(index, &message).is_err());
+                    }
+                }
+            }
+
             proptest! {
                 #[test]
                 fn test_nocrash_registration_request(bytes in vec(any::<u8>(), 0..200)) {
@@ -0,0 +1,106 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Ready-made [`CipherSuite`] configurations
+//!
+//! All suites use [`TripleDh`] with MACs of the full hash length for the key
+//! exchange and the envelope layout of the specification. The Argon2id suites
+//! use the default parameters of the `argon2` crate, which can be overridden
+//! per registration and login through the `ksf` field of
+//! [`ClientRegistrationFinishParameters`] and [`ClientLoginFinishParameters`].
+//! The PBKDF2 suites of the `fips` feature, approved by FIPS 140-3, default to
+//! [`Pbkdf2::DEFAULT_ROUNDS`] iterations.
+//!
+//! [`Pbkdf2::DEFAULT_ROUNDS`]: crate::ksf::Pbkdf2::DEFAULT_ROUNDS
+//! [`ClientRegistrationFinishParameters`]: crate::ClientRegistrationFinishParameters
+//! [`ClientLoginFinishParameters`]: crate::ClientLoginFinishParameters
+
+use generic_array::typenum::U0;
+
+use crate::ciphersuite::CipherSuite;
+use crate::key_exchange::tripledh::TripleDh;
+
+/// Ristretto255 with SHA-512 for both the OPRF and the key exchange, and
+/// Argon2id as the key stretching function
+#[cfg(all(feature = "argon2", feature = "ristretto255-voprf"))]
+pub struct Ristretto255Sha512Argon2id;
+
+#[cfg(all(feature = "argon2", feature = "ristretto255-voprf"))]
+impl CipherSuite for Ristretto255Sha512Argon2id {
+    type OprfCs = crate::Ristretto255;
+    type KeGroup = crate::Ristretto255;
+    type KeyExchange = TripleDh;
+    type Ksf = argon2::Argon2<'static>;
+    type AppDataLen = U0;
+}
+
+/// Ristretto255 with SHA-512 for the OPRF, Curve25519 for the key exchange,
+/// and Argon2id as the key stretching function
+#[cfg(all(
+    feature = "argon2",
+    feature = "curve25519",
+    feature = "ristretto255-voprf"
+))]
+pub struct Ristretto255Curve25519Sha512Argon2id;
+
+#[cfg(all(
+    feature = "argon2",
+    feature = "curve25519",
+    feature = "ristretto255-voprf"
+))]
+impl CipherSuite for Ristretto255Curve25519Sha512Argon2id {
+    type OprfCs = crate::Ristretto255;
+    type KeGroup = crate::Curve25519;
+    type KeyExchange = TripleDh;
+    type Ksf = argon2::Argon2<'static>;
+    type AppDataLen = U0;
+}
+
+/// The recommended configuration of the specification, which may change to
+/// track future recommendations
+///
+/// Note that changing the cipher suite invalidates existing registrations, so
+/// applications which persist password files should refer to a concrete suite
+/// instead.
+#[cfg(all(feature = "argon2", feature = "ristretto255-voprf"))]
+pub type Default = Ristretto255Sha512Argon2id;
+
+/// P-256 with SHA-256 for both the OPRF and the key exchange, and
+/// PBKDF2-HMAC-SHA-256 as the key stretching function, available with the
+/// `fips` feature
+#[cfg(feature = "fips")]
+pub struct P256Sha256Pbkdf2;
+
+#[cfg(feature = "fips")]
+impl CipherSuite for P256Sha256Pbkdf2 {
+    type OprfCs = p256::NistP256;
+    type KeGroup = p256::NistP256;
+    type KeyExchange = TripleDh;
+    type Ksf = crate::ksf::Pbkdf2;
+    type AppDataLen = U0;
+}
+
+/// P-384 with SHA-384 for both the OPRF and the key exchange, and
+/// PBKDF2-HMAC-SHA-256 as the key stretching function, available with the
+/// `fips` feature
+#[cfg(feature = "fips")]
+pub struct P384Sha384Pbkdf2;
+
+#[cfg(feature = "fips")]
+impl CipherSuite for P384Sha384Pbkdf2 {
+    type OprfCs = p384::NistP384;
+    type KeGroup = p384::NistP384;
+    type KeyExchange = TripleDh;
+    type Ksf = crate::ksf::Pbkdf2;
+    type AppDataLen = U0;
+}
+
+#[cfg(feature = "fips")]
+const _: () = crate::fips::assert_approved::<P256Sha256Pbkdf2>();
+#[cfg(feature = "fips")]
+const _: () = crate::fips::assert_approved::<P384Sha384Pbkdf2>();
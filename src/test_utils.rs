@@ -0,0 +1,281 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Helpers for testing integrations of this crate, available with the
+//! `test-utils` feature
+//!
+//! [`simulate_registration`] and [`simulate_login`] run both sides of a flow
+//! in-process and return every message and key, so that storage and transport
+//! layers can be regression-tested against known-good protocol runs. Both are
+//! deterministic: all randomness is drawn from a `ChaCha20Rng` seeded with the
+//! given seed, so the same seed, setup and inputs always produce the same
+//! transcript.
+
+use core::ops::Add;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Output, OutputSizeUser};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::{ArrayLength, GenericArray};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke2MessageLen, MacLen, SessionKeyLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::keypair::PublicKey;
+use crate::messages::CredentialResponseWithoutKeLen;
+use crate::opaque::MaskedResponseLen;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialFinalization, CredentialRequest,
+    CredentialResponse, CredentialResponseLen, RegistrationRequest, RegistrationResponse,
+    RegistrationUpload, ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+/// Drives a login against a registered and an unregistered credential
+/// identifier through `respond`, and panics if the two credential responses
+/// can be told apart by a client
+///
+/// `respond` must perform the server's lookup and [`ServerLogin::start`] for
+/// the given credential identifier, exactly as the server under test does.
+/// `password` must not be the password registered for
+/// `registered_identifier`, as a client without knowledge of the password must
+/// not be able to distinguish both cases.
+///
+/// The responses are checked to have the same length, to deserialize
+/// successfully, and to fail client login with the same error.
+///
+/// [`ServerLogin::start`]: crate::ServerLogin::start
+pub fn assert_enumeration_resistance<CS: CipherSuite, R: CryptoRng + RngCore>(
+    rng: &mut R,
+    mut respond: impl FnMut(CredentialRequest<CS>, &[u8]) -> CredentialResponse<CS>,
+    registered_identifier: &[u8],
+    unregistered_identifier: &[u8],
+    password: &[u8],
+) where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    let mut login = |credential_identifier: &[u8]| {
+        let client_login_start_result =
+            ClientLogin::<CS>::start(rng, password).expect("client login start must succeed");
+        let response = respond(client_login_start_result.message, credential_identifier);
+
+        let bytes = response.serialize();
+        let response = CredentialResponse::<CS>::deserialize(&bytes)
+            .expect("credential response must deserialize");
+        let result = client_login_start_result.state.finish(
+            password,
+            response,
+            ClientLoginFinishParameters::default(),
+        );
+
+        (bytes.len(), result.err())
+    };
+
+    let (registered_len, registered_error) = login(registered_identifier);
+    let (unregistered_len, unregistered_error) = login(unregistered_identifier);
+
+    assert_eq!(
+        registered_len, unregistered_len,
+        "credential responses differ in length"
+    );
+    assert!(
+        matches!(registered_error, Some(ProtocolError::InvalidLoginError)),
+        "login against the registered identifier must fail with `InvalidLoginError`, got \
+         {registered_error:?}"
+    );
+    assert_eq!(
+        registered_error, unregistered_error,
+        "client login fails differently for registered and unregistered identifiers"
+    );
+}
+
+/// All messages and keys of a registration run by [`simulate_registration`]
+#[derive_where(Clone)]
+pub struct RegistrationTranscript<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The message sent by the client to start the registration
+    pub registration_request: RegistrationRequest<CS>,
+    /// The answer of the server
+    pub registration_response: RegistrationResponse<CS>,
+    /// The message sent by the client to finish the registration
+    pub registration_upload: RegistrationUpload<CS>,
+    /// The password file stored by the server
+    pub password_file: ServerRegistration<CS>,
+    /// The export key of the client
+    pub export_key: Output<OprfHash<CS>>,
+    /// The static public key of the server, as received by the client
+    pub server_s_pk: PublicKey<CS::KeGroup>,
+}
+
+/// All messages and keys of a login run by [`simulate_login`]
+#[derive_where(Clone)]
+pub struct LoginTranscript<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The message sent by the client to start the login
+    pub credential_request: CredentialRequest<CS>,
+    /// The answer of the server
+    pub credential_response: CredentialResponse<CS>,
+    /// The message sent by the client to finish the login
+    pub credential_finalization: CredentialFinalization<CS>,
+    /// The session key, which is the same for client and server
+    pub session_key: GenericArray<u8, SessionKeyLen<CS>>,
+    /// The export key of the client
+    pub export_key: Output<OprfHash<CS>>,
+    /// The static public key of the server, as received by the client
+    pub server_s_pk: PublicKey<CS::KeGroup>,
+    /// The static public key of the client, as authenticated by the server,
+    /// see [`ServerLoginFinishResult::client_s_pk`]
+    pub client_s_pk: Option<PublicKey<CS::KeGroup>>,
+}
+
+/// Runs a registration of `password` for `credential_identifier` with
+/// `server_setup`, drawing all randomness from `seed`
+///
+/// # Errors
+/// Any error returned by one of the steps of the registration.
+pub fn simulate_registration<CS: CipherSuite>(
+    seed: [u8; 32],
+    server_setup: &ServerSetup<CS>,
+    password: &[u8],
+    credential_identifier: &[u8],
+) -> Result<RegistrationTranscript<CS>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut rng = ChaCha20Rng::from_seed(seed);
+
+    let client_registration_start_result = ClientRegistration::<CS>::start(&mut rng, password)?;
+    let registration_request = client_registration_start_result.message;
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        server_setup,
+        registration_request.clone(),
+        credential_identifier,
+    )?;
+    let registration_response = server_registration_start_result.message;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        password,
+        registration_response.clone(),
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let registration_upload = client_registration_finish_result.message;
+
+    Ok(RegistrationTranscript {
+        password_file: ServerRegistration::finish(registration_upload.clone()),
+        registration_request,
+        registration_response,
+        registration_upload,
+        export_key: client_registration_finish_result.export_key,
+        server_s_pk: client_registration_finish_result.server_s_pk,
+    })
+}
+
+/// Runs a login with `password` for `credential_identifier` against
+/// `password_file` and `server_setup`, drawing all randomness from `seed`
+///
+/// # Errors
+/// Any error returned by one of the steps of the login, in particular
+/// [`ProtocolError::InvalidLoginError`] if `password` doesn't match
+/// `password_file`.
+pub fn simulate_login<CS: CipherSuite>(
+    seed: [u8; 32],
+    server_setup: &ServerSetup<CS>,
+    password_file: ServerRegistration<CS>,
+    password: &[u8],
+    credential_identifier: &[u8],
+) -> Result<LoginTranscript<CS>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+{
+    let mut rng = ChaCha20Rng::from_seed(seed);
+
+    let client_login_start_result = ClientLogin::<CS>::start(&mut rng, password)?;
+    let credential_request = client_login_start_result.message;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        server_setup,
+        Some(password_file),
+        credential_request.clone(),
+        credential_identifier,
+        ServerLoginStartParameters::default(),
+    )?;
+    let credential_response = server_login_start_result.message;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        password,
+        credential_response.clone(),
+        ClientLoginFinishParameters::default(),
+    )?;
+    let credential_finalization = client_login_finish_result.message;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(credential_finalization.clone())?;
+
+    Ok(LoginTranscript {
+        credential_request,
+        credential_response,
+        credential_finalization,
+        session_key: server_login_finish_result.session_key,
+        export_key: client_login_finish_result.export_key,
+        server_s_pk: client_login_finish_result.server_s_pk,
+        client_s_pk: server_login_finish_result.client_s_pk,
+    })
+}
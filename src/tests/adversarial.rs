@@ -0,0 +1,369 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Adversarial tests, which inject reflected, identity and duplicated values at
+//! every message boundary of the protocol and check the errors they raise
+//!
+//! Every test works on the wire encoding of the messages, as an active attacker
+//! would: a message is serialized, a field of it is overwritten, and the result
+//! is deserialized and handed to the other party.
+
+use core::ops::Add;
+use std::vec::Vec;
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
+use generic_array::ArrayLength;
+use rand::rngs::OsRng;
+use voprf::Group;
+
+use super::full_test::*;
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::{InternalError, ProtocolError};
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, MacLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::{
+    CredentialRequestLen, CredentialResponseLen, CredentialResponseWithoutKeLen,
+    RegistrationResponseLen, RegistrationUploadLen,
+};
+use crate::opaque::MaskedResponseLen;
+use crate::replay::LruReplayCache;
+use crate::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialFinalization, CredentialRequest,
+    CredentialResponse, RegistrationRequest, RegistrationResponse, RegistrationUpload, ServerLogin,
+    ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+static PASSWORD: &[u8] = b"password";
+static CREDENTIAL_IDENTIFIER: &[u8] = b"credential_identifier";
+
+// Returns `message` with the bytes at `offset` replaced by `value`
+fn inject(message: &[u8], offset: usize, value: &[u8]) -> Vec<u8> {
+    let mut message = message.to_vec();
+    message[offset..offset + value.len()].copy_from_slice(value);
+    message
+}
+
+fn identity_elem<CS: CipherSuite>() -> Vec<u8> {
+    OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::identity_elem()).to_vec()
+}
+
+// All-zero bytes where a key exchange public key is expected, which is the
+// identity or not a valid encoding in every group
+fn zero_pk<CS: CipherSuite>() -> Vec<u8> {
+    vec![0; <CS::KeGroup as KeGroup>::PkLen::USIZE]
+}
+
+fn is_oprf_error(result: Result<impl Sized, ProtocolError>) -> bool {
+    matches!(
+        result,
+        Err(ProtocolError::LibraryError(InternalError::OprfError(_)))
+    )
+}
+
+fn is_point_error(result: Result<impl Sized, ProtocolError>) -> bool {
+    matches!(
+        result,
+        Err(ProtocolError::LibraryError(InternalError::PointError))
+    )
+}
+
+#[test]
+fn test_adversarial_registration() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
+        // RegistrationRequest: identity blinded element
+        let client_registration_start_result = ClientRegistration::<CS>::start(&mut rng, PASSWORD)?;
+        let registration_request = client_registration_start_result.message.serialize();
+        assert!(is_oprf_error(RegistrationRequest::<CS>::deserialize(
+            &identity_elem::<CS>()
+        )));
+
+        let registration_response = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            CREDENTIAL_IDENTIFIER,
+        )?
+        .message
+        .serialize();
+
+        // RegistrationResponse: identity evaluation element
+        assert!(is_oprf_error(RegistrationResponse::<CS>::deserialize(
+            &inject(&registration_response, 0, &identity_elem::<CS>())
+        )));
+
+        // RegistrationResponse: identity server public key
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        assert!(is_point_error(RegistrationResponse::<CS>::deserialize(
+            &inject(&registration_response, elem_len, &zero_pk::<CS>())
+        )));
+
+        // RegistrationResponse: blinded element reflected as the evaluation
+        // element
+        let reflected = RegistrationResponse::deserialize(&inject(
+            &registration_response,
+            0,
+            &registration_request,
+        ))?;
+        assert!(matches!(
+            client_registration_start_result.state.clone().finish(
+                &mut rng,
+                PASSWORD,
+                reflected,
+                ClientRegistrationFinishParameters::default(),
+            ),
+            Err(ProtocolError::ReflectedValueError)
+        ));
+
+        // RegistrationUpload: identity client public key
+        let registration_upload = client_registration_start_result
+            .state
+            .finish(
+                &mut rng,
+                PASSWORD,
+                RegistrationResponse::deserialize(&registration_response)?,
+                ClientRegistrationFinishParameters::default(),
+            )?
+            .message
+            .serialize();
+        assert!(is_point_error(RegistrationUpload::<CS>::deserialize(
+            &inject(&registration_upload, 0, &zero_pk::<CS>())
+        )));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_adversarial_login() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result = ClientRegistration::<CS>::start(&mut rng, PASSWORD)?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            CREDENTIAL_IDENTIFIER,
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            PASSWORD,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let server_login_start = |credential_request| {
+            ServerLogin::start(
+                &mut OsRng,
+                &server_setup,
+                Some(password_file.clone()),
+                credential_request,
+                CREDENTIAL_IDENTIFIER,
+                ServerLoginStartParameters::default(),
+            )
+        };
+
+        let elem_len = <OprfGroup<CS> as Group>::ElemLen::USIZE;
+        let nonce_len = NonceLen::USIZE;
+        let pk_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
+        let ke2_offset = CredentialResponseWithoutKeLen::<CS>::USIZE;
+
+        let client_login_start_result = ClientLogin::<CS>::start(&mut rng, PASSWORD)?;
+        let credential_request = client_login_start_result.message.serialize();
+        let client_e_pk = &credential_request[elem_len + nonce_len..];
+
+        // CredentialRequest: identity blinded element
+        assert!(is_oprf_error(CredentialRequest::<CS>::deserialize(
+            &inject(&credential_request, 0, &identity_elem::<CS>())
+        )));
+
+        // CredentialRequest: identity client ephemeral public key
+        assert!(is_point_error(CredentialRequest::<CS>::deserialize(
+            &inject(&credential_request, elem_len + nonce_len, &zero_pk::<CS>())
+        )));
+
+        let server_login_start_result =
+            server_login_start(CredentialRequest::deserialize(&credential_request)?)?;
+        let credential_response = server_login_start_result.message.serialize();
+        let login_finish = |credential_response: &[u8]| {
+            client_login_start_result.state.clone().finish(
+                PASSWORD,
+                CredentialResponse::deserialize(credential_response)?,
+                ClientLoginFinishParameters::default(),
+            )
+        };
+
+        // CredentialResponse: identity evaluation element
+        assert!(is_oprf_error(CredentialResponse::<CS>::deserialize(
+            &inject(&credential_response, 0, &identity_elem::<CS>())
+        )));
+
+        // CredentialResponse: identity server ephemeral public key
+        assert!(is_point_error(CredentialResponse::<CS>::deserialize(
+            &inject(
+                &credential_response,
+                ke2_offset + nonce_len,
+                &zero_pk::<CS>()
+            )
+        )));
+
+        // CredentialResponse: blinded element reflected as the evaluation element
+        assert!(matches!(
+            login_finish(&inject(
+                &credential_response,
+                0,
+                &credential_request[..elem_len]
+            )),
+            Err(ProtocolError::ReflectedValueError)
+        ));
+
+        // CredentialResponse: client ephemeral public key reflected as the
+        // server ephemeral public key
+        assert!(matches!(
+            login_finish(&inject(
+                &credential_response,
+                ke2_offset + nonce_len,
+                client_e_pk
+            )),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        // CredentialResponse: duplicated from another login
+        let other_credential_response =
+            server_login_start(ClientLogin::<CS>::start(&mut rng, PASSWORD)?.message)?
+                .message
+                .serialize();
+        assert!(matches!(
+            login_finish(&other_credential_response),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        // CredentialFinalization: server MAC reflected as the client MAC
+        let server_mac = &credential_response[ke2_offset + nonce_len + pk_len..];
+        assert!(matches!(
+            server_login_start_result
+                .state
+                .clone()
+                .finish(CredentialFinalization::deserialize(server_mac)?),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        // CredentialFinalization: duplicated from another login
+        let other_client_login_start_result = ClientLogin::<CS>::start(&mut rng, PASSWORD)?;
+        let other_credential_finalization = other_client_login_start_result
+            .state
+            .finish(
+                PASSWORD,
+                server_login_start(other_client_login_start_result.message)?.message,
+                ClientLoginFinishParameters::default(),
+            )?
+            .message
+            .serialize();
+        assert!(matches!(
+            server_login_start_result
+                .state
+                .clone()
+                .finish(CredentialFinalization::deserialize(
+                    &other_credential_finalization
+                )?),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        // The untampered messages still complete the login
+        let client_login_finish_result = login_finish(&credential_response)?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(client_login_finish_result.session_key),
+            hex::encode(server_login_finish_result.session_key)
+        );
+
+        // CredentialRequest: duplicated while the replay cache remembers it
+        let mut replay_cache = LruReplayCache::new(1);
+        let mut server_login_start_with_replay_cache = |credential_request| {
+            ServerLogin::start_with_replay_cache(
+                &mut OsRng,
+                &server_setup,
+                Some(password_file.clone()),
+                credential_request,
+                CREDENTIAL_IDENTIFIER,
+                ServerLoginStartParameters::default(),
+                &mut replay_cache,
+            )
+        };
+        server_login_start_with_replay_cache(CredentialRequest::deserialize(&credential_request)?)?;
+        assert!(matches!(
+            server_login_start_with_replay_cache(CredentialRequest::deserialize(
+                &credential_request
+            )?),
+            Err(ProtocolError::ReplayError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
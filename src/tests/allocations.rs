@@ -0,0 +1,423 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Checks that serialization and deserialization of messages and states, as
+//! well as the client side of the protocol, do not allocate
+
+use core::cell::Cell;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::thread_local;
+
+use generic_array::typenum::U0;
+use rand::rngs::OsRng;
+
+use crate::errors::ProtocolError;
+use crate::key_exchange::tripledh::TripleDh;
+use crate::ksf::Identity;
+use crate::*;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations `f` performs on the current thread
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let after = ALLOCATIONS.with(Cell::get);
+    drop(result);
+
+    after - before
+}
+
+struct P256;
+
+impl CipherSuite for P256 {
+    type OprfCs = p256::NistP256;
+    type KeGroup = p256::NistP256;
+    type KeyExchange = TripleDh;
+    type Ksf = Identity;
+    type AppDataLen = U0;
+}
+
+#[test]
+fn test_serialization_does_not_allocate() -> Result<(), ProtocolError> {
+    let password = b"password";
+    let credential_identifier = b"credential identifier";
+    let mut rng = OsRng;
+
+    // Ensure that allocations are actually counted
+    assert_eq!(allocations(|| std::vec![0_u8]), 1);
+
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    assert_eq!(allocations(|| server_setup.serialize()), 0);
+
+    let client_registration_start_result = ClientRegistration::<P256>::start(&mut rng, password)?;
+    assert_eq!(
+        allocations(|| client_registration_start_result.message.serialize()),
+        0
+    );
+    assert_eq!(
+        allocations(|| client_registration_start_result.state.serialize()),
+        0
+    );
+
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
+    assert_eq!(
+        allocations(|| server_registration_start_result.message.serialize()),
+        0
+    );
+
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        password,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    assert_eq!(
+        allocations(|| client_registration_finish_result.message.serialize()),
+        0
+    );
+
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+    assert_eq!(allocations(|| password_file.serialize()), 0);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, password)?;
+    assert_eq!(
+        allocations(|| client_login_start_result.message.serialize()),
+        0
+    );
+    assert_eq!(
+        allocations(|| client_login_start_result.state.serialize()),
+        0
+    );
+
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        credential_identifier,
+        ServerLoginStartParameters::default(),
+    )?;
+    assert_eq!(
+        allocations(|| server_login_start_result.message.serialize()),
+        0
+    );
+    assert_eq!(
+        allocations(|| server_login_start_result.state.serialize()),
+        0
+    );
+
+    let credential_response = server_login_start_result.message.serialize();
+    assert_eq!(
+        allocations(|| CredentialResponse::<P256>::deserialize(&credential_response)),
+        0
+    );
+
+    let mut client_login_finish_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_login_finish_result = Some(client_login_start_result.state.finish(
+                password,
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            ))
+        }),
+        0
+    );
+    let client_login_finish_result = client_login_finish_result.unwrap()?;
+    assert_eq!(
+        allocations(|| client_login_finish_result.message.serialize()),
+        0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_deserialization_does_not_allocate() -> Result<(), ProtocolError> {
+    let password = b"password";
+    let credential_identifier = b"credential identifier";
+    let mut rng = OsRng;
+
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let client_registration_start_result = ClientRegistration::<P256>::start(&mut rng, password)?;
+    let registration_request = client_registration_start_result.message.serialize();
+    let client_registration = client_registration_start_result.state.serialize();
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
+    let registration_response = server_registration_start_result.message.serialize();
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        password,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let registration_upload = client_registration_finish_result.message.serialize();
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let server_registration = password_file.serialize();
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, password)?;
+    let credential_request = client_login_start_result.message.serialize();
+    let client_login = client_login_start_result.state.serialize();
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        credential_identifier,
+        ServerLoginStartParameters::default(),
+    )?;
+    let server_login = server_login_start_result.state.serialize();
+    let client_login_finish_result = client_login_start_result.state.finish(
+        password,
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let credential_finalization = client_login_finish_result.message.serialize();
+
+    assert_eq!(
+        allocations(|| RegistrationRequest::<P256>::deserialize(&registration_request)),
+        0
+    );
+    assert_eq!(
+        allocations(|| ClientRegistration::<P256>::deserialize(&client_registration)),
+        0
+    );
+    assert_eq!(
+        allocations(|| RegistrationResponse::<P256>::deserialize(&registration_response)),
+        0
+    );
+    assert_eq!(
+        allocations(|| RegistrationUpload::<P256>::deserialize(&registration_upload)),
+        0
+    );
+    assert_eq!(
+        allocations(|| ServerRegistration::<P256>::deserialize(&server_registration)),
+        0
+    );
+    assert_eq!(
+        allocations(|| CredentialRequest::<P256>::deserialize(&credential_request)),
+        0
+    );
+    assert_eq!(
+        allocations(|| ClientLogin::<P256>::deserialize(&client_login)),
+        0
+    );
+    assert_eq!(
+        allocations(|| ServerLogin::<P256>::deserialize(&server_login)),
+        0
+    );
+    assert_eq!(
+        allocations(|| CredentialFinalization::<P256>::deserialize(&credential_finalization)),
+        0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_client_flow_does_not_allocate() -> Result<(), ProtocolError> {
+    let password = b"password";
+    let credential_identifier = b"credential identifier";
+    let context = b"context";
+    let identifiers = Identifiers {
+        client: Some(b"client"),
+        server: Some(b"server"),
+    };
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+
+    let mut client_registration_start_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_registration_start_result =
+                Some(ClientRegistration::<P256>::start(&mut rng, password))
+        }),
+        0
+    );
+    let client_registration_start_result = client_registration_start_result.unwrap()?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
+
+    let mut client_registration_finish_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_registration_finish_result = Some(client_registration_start_result.state.finish(
+                &mut rng,
+                password,
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::new(identifiers, None),
+            ))
+        }),
+        0
+    );
+    let client_registration_finish_result = client_registration_finish_result.unwrap()?;
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let mut client_login_start_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_login_start_result = Some(ClientLogin::<P256>::start(&mut rng, password))
+        }),
+        0
+    );
+    let client_login_start_result = client_login_start_result.unwrap()?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        credential_identifier,
+        ServerLoginStartParameters {
+            context: Some(context),
+            identifiers,
+            ..Default::default()
+        },
+    )?;
+
+    let mut client_login_finish_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_login_finish_result = Some(client_login_start_result.state.finish(
+                password,
+                server_login_start_result.message,
+                ClientLoginFinishParameters::new(Some(context), identifiers, None),
+            ))
+        }),
+        0
+    );
+    let client_login_finish_result = client_login_finish_result.unwrap()?;
+    server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn test_heapless_client_flow_does_not_allocate() -> Result<(), ProtocolError> {
+    let password = b"password";
+    let credential_identifier = b"credential identifier";
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+
+    // Owned copies, as kept by a client between protocol steps
+    let mut owned = None;
+    assert_eq!(
+        allocations(|| {
+            owned = Some((
+                heapless_context::<16>(b"context"),
+                HeaplessIdentifiers::<16>::new(Identifiers {
+                    client: Some(b"client"),
+                    server: Some(b"server"),
+                }),
+            ))
+        }),
+        0
+    );
+    let (context, identifiers) = owned.unwrap();
+    let (context, identifiers) = (context?, identifiers?);
+
+    let client_registration_start_result = ClientRegistration::<P256>::start(&mut rng, password)?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        password,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::new(identifiers.as_identifiers(), None),
+    )?;
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, password)?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        credential_identifier,
+        ServerLoginStartParameters {
+            context: Some(b"context"),
+            identifiers: Identifiers {
+                client: Some(b"client"),
+                server: Some(b"server"),
+            },
+            ..Default::default()
+        },
+    )?;
+
+    let mut client_login_finish_result = None;
+    assert_eq!(
+        allocations(|| {
+            client_login_finish_result = Some(client_login_start_result.state.finish(
+                password,
+                server_login_start_result.message,
+                ClientLoginFinishParameters::new(
+                    Some(&context),
+                    identifiers.as_identifiers(),
+                    None,
+                ),
+            ))
+        }),
+        0
+    );
+    let client_login_finish_result = client_login_finish_result.unwrap()?;
+    server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    // Longer inputs are rejected instead of truncated
+    assert!(matches!(
+        heapless_context::<4>(b"context"),
+        Err(ProtocolError::LibraryError(
+            crate::errors::InternalError::SizeError {
+                name: "context",
+                len: 4,
+                actual_len: 7,
+            }
+        ))
+    ));
+    assert!(HeaplessIdentifiers::<4>::new(Identifiers {
+        client: None,
+        server: Some(b"server"),
+    })
+    .is_err());
+
+    Ok(())
+}
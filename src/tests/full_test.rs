@@ -15,8 +15,8 @@ use std::{format, println, ptr, vec};
 
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::{Output, OutputSizeUser};
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U256};
-use generic_array::ArrayLength;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U0, U256};
+use generic_array::{ArrayLength, GenericArray};
 use rand::rngs::OsRng;
 use serde_json::Value;
 use subtle::ConstantTimeEq;
@@ -27,23 +27,27 @@ use crate::envelope::EnvelopeLen;
 use crate::errors::*;
 use crate::hash::{Hash, OutputSize, ProxyHash};
 use crate::key_exchange::group::KeGroup;
-use crate::key_exchange::traits::{Ke1MessageLen, Ke1StateLen, Ke2MessageLen};
+use crate::key_exchange::traits::{
+    Ke1MessageLen, Ke1StateLen, Ke2MessageLen, Ke2StateLen, MacLen, Serialize, SessionKeyLen,
+};
 use crate::key_exchange::tripledh::{NonceLen, TripleDh};
-use crate::keypair::SecretKey;
-use crate::ksf::Identity;
+use crate::keypair::{
+    AsyncClientSecretKey, ClientSecretKey, KeyPair, PrivateKey, PublicKey, SecretKey,
+};
+use crate::ksf::{Identity, Ksf, KsfParameters};
 use crate::messages::{
     CredentialRequestLen, CredentialResponseLen, CredentialResponseWithoutKeLen,
     RegistrationResponseLen, RegistrationUploadLen,
 };
 use crate::opaque::*;
-use crate::tests::mock_rng::CycleRng;
+use crate::tests::mock_rng::{CycleRng, FailingRng};
 use crate::*;
 
 // Tests
 // =====
 
 #[cfg(feature = "ristretto255")]
-struct Ristretto255;
+pub(super) struct Ristretto255;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for Ristretto255 {
@@ -51,10 +55,11 @@ impl CipherSuite for Ristretto255 {
     type KeGroup = crate::Ristretto255;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct Ristretto255P256;
+pub(super) struct Ristretto255P256;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for Ristretto255P256 {
@@ -62,10 +67,11 @@ impl CipherSuite for Ristretto255P256 {
     type KeGroup = crate::Ristretto255;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct Ristretto255P384;
+pub(super) struct Ristretto255P384;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for Ristretto255P384 {
@@ -73,10 +79,11 @@ impl CipherSuite for Ristretto255P384 {
     type KeGroup = crate::Ristretto255;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct Ristretto255P521;
+pub(super) struct Ristretto255P521;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for Ristretto255P521 {
@@ -84,37 +91,41 @@ impl CipherSuite for Ristretto255P521 {
     type KeGroup = crate::Ristretto255;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P256;
+pub(super) struct P256;
 
 impl CipherSuite for P256 {
     type OprfCs = p256::NistP256;
     type KeGroup = p256::NistP256;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P256P384;
+pub(super) struct P256P384;
 
 impl CipherSuite for P256P384 {
     type OprfCs = p384::NistP384;
     type KeGroup = p256::NistP256;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P256P521;
+pub(super) struct P256P521;
 
 impl CipherSuite for P256P521 {
     type OprfCs = p521::NistP521;
     type KeGroup = p256::NistP256;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct P256Ristretto255;
+pub(super) struct P256Ristretto255;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for P256Ristretto255 {
@@ -122,37 +133,41 @@ impl CipherSuite for P256Ristretto255 {
     type KeGroup = p256::NistP256;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P384;
+pub(super) struct P384;
 
 impl CipherSuite for P384 {
     type OprfCs = p384::NistP384;
     type KeGroup = p384::NistP384;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P384P256;
+pub(super) struct P384P256;
 
 impl CipherSuite for P384P256 {
     type OprfCs = p256::NistP256;
     type KeGroup = p384::NistP384;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P384P521;
+pub(super) struct P384P521;
 
 impl CipherSuite for P384P521 {
     type OprfCs = p521::NistP521;
     type KeGroup = p384::NistP384;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct P384Ristretto255;
+pub(super) struct P384Ristretto255;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for P384Ristretto255 {
@@ -160,37 +175,41 @@ impl CipherSuite for P384Ristretto255 {
     type KeGroup = p384::NistP384;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P521;
+pub(super) struct P521;
 
 impl CipherSuite for P521 {
     type OprfCs = p521::NistP521;
     type KeGroup = p521::NistP521;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P521P256;
+pub(super) struct P521P256;
 
 impl CipherSuite for P521P256 {
     type OprfCs = p256::NistP256;
     type KeGroup = p521::NistP521;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
-struct P521P384;
+pub(super) struct P521P384;
 
 impl CipherSuite for P521P384 {
     type OprfCs = p384::NistP384;
     type KeGroup = p521::NistP521;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "ristretto255")]
-struct P521Ristretto255;
+pub(super) struct P521Ristretto255;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for P521Ristretto255 {
@@ -198,10 +217,11 @@ impl CipherSuite for P521Ristretto255 {
     type KeGroup = p521::NistP521;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(all(feature = "curve25519", feature = "ristretto255"))]
-struct Curve25519Ristretto255;
+pub(super) struct Curve25519Ristretto255;
 
 #[cfg(all(feature = "curve25519", feature = "ristretto255"))]
 impl CipherSuite for Curve25519Ristretto255 {
@@ -209,10 +229,11 @@ impl CipherSuite for Curve25519Ristretto255 {
     type KeGroup = crate::Curve25519;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "curve25519")]
-struct Curve25519P256;
+pub(super) struct Curve25519P256;
 
 #[cfg(feature = "curve25519")]
 impl CipherSuite for Curve25519P256 {
@@ -220,10 +241,11 @@ impl CipherSuite for Curve25519P256 {
     type KeGroup = crate::Curve25519;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "curve25519")]
-struct Curve25519P384;
+pub(super) struct Curve25519P384;
 
 #[cfg(feature = "curve25519")]
 impl CipherSuite for Curve25519P384 {
@@ -231,10 +253,11 @@ impl CipherSuite for Curve25519P384 {
     type KeGroup = crate::Curve25519;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 #[cfg(feature = "curve25519")]
-struct Curve25519P521;
+pub(super) struct Curve25519P521;
 
 #[cfg(feature = "curve25519")]
 impl CipherSuite for Curve25519P521 {
@@ -242,6 +265,7 @@ impl CipherSuite for Curve25519P521 {
     type KeGroup = crate::Curve25519;
     type KeyExchange = TripleDh;
     type Ksf = Identity;
+    type AppDataLen = U0;
 }
 
 pub struct TestVectorParameters {
@@ -320,6 +344,8 @@ macro_rules! run_all {
     };
 }
 
+pub(super) use run_all;
+
 fn decode(values: &Value, key: &str) -> Option<Vec<u8>> {
     values[key].as_str().and_then(|s| hex::decode(s).ok())
 }
@@ -558,8 +584,9 @@ where
     // RegistrationResponse: KgPk + KePk
     <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
     RegistrationResponseLen<CS>: ArrayLength<u8>,
-    // Envelope: Nonce + Hash
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
     EnvelopeLen<CS>: ArrayLength<u8>,
     // RegistrationUpload: (KePk + Hash) + Envelope
     <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -578,9 +605,10 @@ where
     Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
         ArrayLength<u8> + Add<Ke1StateLen<CS>>,
     ClientLoginLen<CS>: ArrayLength<u8>,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
     // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
     <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
@@ -593,6 +621,9 @@ where
     // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
     CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
     CredentialResponseLen<CS>: ArrayLength<u8>,
+    // ServerLogin: Ke2State + KePk
+    Ke2StateLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    ServerLoginLen<CS>: ArrayLength<u8>,
 {
     use rand::RngCore;
 
@@ -601,11 +632,11 @@ where
     let mut rng = OsRng;
 
     // Inputs
-    let server_s_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
-    let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
-    let client_s_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
-    let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
-    let fake_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng);
+    let server_s_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
+    let server_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
+    let client_s_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
+    let client_e_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
+    let fake_kp = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?;
     let credential_identifier = b"credIdentifier";
     let id_u = b"idU";
     let id_s = b"idS";
@@ -717,6 +748,7 @@ where
                 client: Some(id_u),
                 server: Some(id_s),
             },
+            ..Default::default()
         },
     )
     .unwrap();
@@ -1081,8 +1113,9 @@ fn test_registration_upload() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -1138,8 +1171,9 @@ fn test_password_file() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
         EnvelopeLen<CS>: ArrayLength<u8>,
         // RegistrationUpload: (KePk + Hash) + Envelope
         <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -1155,9 +1189,43 @@ fn test_password_file() -> Result<(), ProtocolError> {
         )?);
 
         assert_eq!(
-            hex::encode(parameters.password_file),
+            hex::encode(&parameters.password_file),
+            hex::encode(password_file.serialize())
+        );
+
+        // The upload bytes can be stored and used as password file directly
+        ServerRegistration::<CS>::validate(&parameters.registration_upload)?;
+        let password_file: ServerRegistration<CS> =
+            RegistrationUpload::<CS>::deserialize(&parameters.registration_upload)?.into();
+        assert_eq!(
+            hex::encode(&parameters.password_file),
             hex::encode(password_file.serialize())
         );
+        assert_eq!(
+            hex::encode(&parameters.password_file),
+            hex::encode(
+                ServerRegistration::<CS>::deserialize(&parameters.registration_upload)?.serialize()
+            )
+        );
+        assert!(ServerRegistration::<CS>::validate(
+            &parameters.registration_upload[..parameters.registration_upload.len() - 1]
+        )
+        .is_err());
+
+        // The structural check only looks at the length
+        ServerRegistration::<CS>::validate_structure(&parameters.password_file)?;
+        ServerRegistration::<CS>::validate_structure(&vec![0xff; parameters.password_file.len()])?;
+        for len in [
+            0,
+            parameters.password_file.len() - 1,
+            parameters.password_file.len() + 1,
+        ] {
+            assert!(matches!(
+                ServerRegistration::<CS>::validate_structure(&vec![0; len]),
+                Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
+            ));
+        }
+
         Ok(())
     }
 
@@ -1222,10 +1290,10 @@ fn test_credential_response() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
         // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
         <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
@@ -1235,6 +1303,9 @@ fn test_credential_response() -> Result<(), ProtocolError> {
         // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
         CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
         CredentialResponseLen<CS>: ArrayLength<u8>,
+        // ServerLogin: Ke2State + KePk
+        Ke2StateLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        ServerLoginLen<CS>: ArrayLength<u8>,
     {
         let parameters = populate_test_vectors(&serde_json::from_str(test_vector).unwrap());
 
@@ -1267,6 +1338,7 @@ fn test_credential_response() -> Result<(), ProtocolError> {
                     client: Some(&parameters.id_u),
                     server: Some(&parameters.id_s),
                 },
+                ..Default::default()
             },
         )?;
         assert_eq!(
@@ -1295,10 +1367,10 @@ fn test_credential_finalization() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
     {
         let parameters = populate_test_vectors(&serde_json::from_str(test_vector).unwrap());
@@ -1385,15 +1457,16 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
 {
     let credential_identifier = b"credentialIdentifier";
     let mut client_rng = OsRng;
     let mut server_rng = OsRng;
-    let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+    let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
     let client_registration_start_result =
         ClientRegistration::<CS>::start(&mut client_rng, registration_password)?;
     let server_registration_start_result = ServerRegistration::<CS>::start(
@@ -1409,10 +1482,13 @@ where
     )?;
     let p_file = ServerRegistration::finish(client_registration_finish_result.message);
     let client_login_start_result = ClientLogin::<CS>::start(&mut client_rng, login_password)?;
+    // Ke1Message: Nonce + KePk
+    let client_e_pk =
+        client_login_start_result.message.ke1_message.serialize()[NonceLen::USIZE..].to_vec();
     let server_login_start_result = ServerLogin::<CS>::start(
         &mut server_rng,
         &server_setup,
-        Some(p_file),
+        Some(p_file.clone()),
         client_login_start_result.message,
         credential_identifier,
         ServerLoginStartParameters::default(),
@@ -1438,6 +1514,38 @@ where
             hex::encode(client_registration_finish_result.export_key),
             hex::encode(client_login_finish_result.export_key)
         );
+        assert_eq!(
+            hex::encode(client_registration_finish_result.randomized_pwd),
+            hex::encode(client_login_finish_result.randomized_pwd)
+        );
+        assert_eq!(
+            client_registration_finish_result.server_s_pk.serialize(),
+            server_setup.public_key().serialize()
+        );
+        assert_eq!(
+            client_login_finish_result.server_s_pk.serialize(),
+            server_setup.public_key().serialize()
+        );
+        assert_eq!(
+            server_login_finish_result.client_s_pk.unwrap().serialize(),
+            p_file.upload.client_s_pk.serialize()
+        );
+        assert_eq!(
+            client_login_finish_result.client_e_pk.serialize().to_vec(),
+            client_e_pk
+        );
+        assert_eq!(
+            server_login_finish_result.client_e_pk.serialize().to_vec(),
+            client_e_pk
+        );
+        assert_eq!(
+            client_login_finish_result.server_e_pk.serialize(),
+            server_login_finish_result.server_e_pk.serialize()
+        );
+        assert_ne!(
+            client_login_finish_result.server_e_pk.serialize().to_vec(),
+            client_e_pk
+        );
     } else {
         assert!(matches!(
             client_login_result,
@@ -1460,11 +1568,9 @@ fn test_complete_flow_fail() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-// Zeroize tests
-
 #[test]
-fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
-    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+fn test_stack_usage() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(test_vector: &'static str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1472,18 +1578,29 @@ fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let mut client_rng = OsRng;
-        let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-
-        let mut state = client_registration_start_result.state;
-        unsafe { ptr::drop_in_place(&mut state) };
-        for byte in state.to_vec() {
-            assert_eq!(byte, 0);
-        }
-
-        Ok(())
+        // Both sides of a complete registration and login, on a thread with a
+        // stack far smaller than the default, which aborts the test run when
+        // exceeded. Unoptimized builds need considerably more stack.
+        const STACK_SIZE: usize = if cfg!(debug_assertions) {
+            192 * 1024
+        } else {
+            64 * 1024
+        };
+
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                test_complete_flow::<CS>(test_vector, b"good password", b"good password")
+            })
+            .unwrap()
+            .join()
+            .unwrap()
     }
 
     run_all!(inner);
@@ -1492,7 +1609,9 @@ fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
 }
 
 #[test]
-fn test_zeroize_client_registration_finish() -> Result<(), ProtocolError> {
+fn test_auto_traits() -> Result<(), ProtocolError> {
+    fn assert_traits<T: Clone + Send + Sync>() {}
+
     fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1501,41 +1620,51 @@ fn test_zeroize_client_registration_finish() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Checked for every concrete suite `run_all!` instantiates this with.
+        ClientRegistration<CS>: Send + Sync,
+        ServerRegistration<CS>: Send + Sync,
+        ClientLogin<CS>: Send + Sync,
+        ServerLogin<CS>: Send + Sync,
+        ServerSetup<CS>: Send + Sync,
+        ClientRetrieval<CS>: Send + Sync,
     {
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
-        let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-        let server_registration_start_result = ServerRegistration::<CS>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-        )?;
-        let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
-            STR_PASSWORD.as_bytes(),
-            server_registration_start_result.message,
-            ClientRegistrationFinishParameters::default(),
-        )?;
-
-        let mut state = client_registration_finish_result.state;
-        unsafe { ptr::drop_in_place(&mut state) };
-        for byte in state.to_vec() {
-            assert_eq!(byte, 0);
-        }
+        assert_traits::<ClientRegistration<CS>>();
+        assert_traits::<ServerRegistration<CS>>();
+        assert_traits::<ClientLogin<CS>>();
+        assert_traits::<ServerLogin<CS>>();
+        assert_traits::<ServerSetup<CS>>();
+        assert_traits::<ClientRetrieval<CS>>();
 
         Ok(())
     }
 
     run_all!(inner);
 
+    #[cfg(all(feature = "argon2", feature = "ristretto255"))]
+    {
+        struct Argon2Ristretto255;
+
+        impl CipherSuite for Argon2Ristretto255 {
+            type OprfCs = crate::Ristretto255;
+            type KeGroup = crate::Ristretto255;
+            type KeyExchange = TripleDh;
+            type Ksf = argon2::Argon2<'static>;
+            type AppDataLen = U0;
+        }
+
+        inner::<Argon2Ristretto255>("")?;
+    }
+
     Ok(())
 }
 
+#[cfg(feature = "ristretto255")]
 #[test]
-fn test_zeroize_server_registration_finish() -> Result<(), ProtocolError> {
-    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+fn test_deterministic_rng() -> Result<(), ProtocolError> {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn run<CS: CipherSuite>(seed: [u8; 32]) -> Result<Vec<Vec<u8>>, ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1543,52 +1672,53 @@ fn test_zeroize_server_registration_finish() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // Envelope: Nonce + Hash
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        EnvelopeLen<CS>: ArrayLength<u8>,
-        // RegistrationUpload: (KePk + Hash) + Envelope
-        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
-        RegistrationUploadLen<CS>: ArrayLength<u8>,
-        // ServerRegistration = RegistrationUpload
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+        let mut rng = StdRng::from_seed(seed);
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
         let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
         let server_registration_start_result = ServerRegistration::<CS>::start(
             &server_setup,
             client_registration_start_result.message,
             STR_CREDENTIAL_IDENTIFIER.as_bytes(),
         )?;
         let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
+            &mut rng,
             STR_PASSWORD.as_bytes(),
             server_registration_start_result.message,
             ClientRegistrationFinishParameters::default(),
         )?;
         let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::<CS>::start(
+            &mut rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
 
-        let mut state = p_file;
-        util::drop_manually(&mut state);
-        util::test_zeroized(&mut state.0.envelope.mode);
-        util::test_zeroized(&mut state.0.masking_key);
-
-        Ok(())
+        Ok(vec![
+            server_setup.public_key().serialize().to_vec(),
+            client_registration_finish_result.export_key.to_vec(),
+            client_login_finish_result.message.serialize().to_vec(),
+            client_login_finish_result.session_key.to_vec(),
+        ])
     }
 
-    run_all!(inner);
-
-    Ok(())
-}
-
-#[test]
-fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
-    fn inner<CS: CipherSuite<KeyExchange = TripleDh>>(
-        _test_vector: &str,
-    ) -> Result<(), ProtocolError>
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1596,37 +1726,14 @@ fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // CredentialRequest: KgPk + Ke1Message
-        <OprfGroup<CS> as Group>::ElemLen: Add<Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>>,
-        CredentialRequestLen<CS>: ArrayLength<u8>,
-        // Ke1State: KeSk + Nonce
-        <CS::KeGroup as KeGroup>::SkLen: Add<NonceLen>,
-        Sum<<CS::KeGroup as KeGroup>::SkLen, NonceLen>: ArrayLength<u8>,
-        // Ke1Message: Nonce + KePk
-        NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
-        Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>: ArrayLength<u8>,
-        // Ke2State: (Hash + Hash) + Hash
-        OutputSize<OprfHash<CS>>: Add<OutputSize<OprfHash<CS>>>,
-        Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
-        Sum<Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8>,
-        // Ke2Message: (Nonce + KePk) + Hash
-        NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
-        Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>:
-            ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
-        Sum<Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let mut client_rng = OsRng;
-        let client_login_start_result =
-            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-
-        let mut state = client_login_start_result.state;
-        util::drop_manually(&mut state);
-        util::test_zeroized(&mut state.oprf_client);
-        util::test_zeroized(&mut state.ke1_state);
-        util::test_zeroized(&mut state.credential_request.ke1_message.client_nonce);
+        assert_eq!(run::<CS>([0; 32])?, run::<CS>([0; 32])?);
+        assert_ne!(run::<CS>([0; 32])?, run::<CS>([1; 32])?);
 
         Ok(())
     }
@@ -1636,9 +1743,99 @@ fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
     Ok(())
 }
 
+#[cfg(feature = "ristretto255")]
 #[test]
-fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
-    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+fn test_app_data() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U32;
+
+    struct AppDataCipherSuite;
+
+    impl CipherSuite for AppDataCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U32;
+    }
+
+    let app_data = GenericArray::from([0x5a; 32]);
+
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<AppDataCipherSuite>::new(&mut server_rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<AppDataCipherSuite>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<AppDataCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            app_data,
+            ..Default::default()
+        },
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    // The stored envelope must not contain the application data in the clear
+    let p_file_bytes = p_file.serialize();
+    assert!(!p_file_bytes
+        .windows(app_data.len())
+        .any(|window| window == app_data.as_slice()));
+
+    for (login_password, success) in [
+        (STR_PASSWORD.as_bytes(), true),
+        (b"bad password".as_slice(), false),
+    ] {
+        let client_login_start_result =
+            ClientLogin::<AppDataCipherSuite>::start(&mut client_rng, login_password)?;
+        let server_login_start_result = ServerLogin::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file.clone()),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_result = client_login_start_result.state.finish(
+            login_password,
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        );
+
+        if success {
+            assert_eq!(client_login_result?.app_data, app_data);
+        } else {
+            assert!(matches!(
+                client_login_result,
+                Err(ProtocolError::InvalidLoginError)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "ristretto255")]
+#[test]
+fn test_truncated_mac() -> Result<(), ProtocolError> {
+    use crate::key_exchange::tripledh::TruncatedMac;
+
+    struct TruncatedMacCipherSuite;
+
+    impl CipherSuite for TruncatedMacCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh<TruncatedMac>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    fn sizes<CS: CipherSuite>() -> Result<[usize; 4], ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
             IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
@@ -1646,57 +1843,7018 @@ fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
-        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
-        let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-        let server_registration_start_result = ServerRegistration::<CS>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-        )?;
-        let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
-            STR_PASSWORD.as_bytes(),
-            server_registration_start_result.message,
-            ClientRegistrationFinishParameters::default(),
-        )?;
-        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let sizes = checked_sizes::<CS>()?;
+
+        Ok([
+            sizes.envelope,
+            sizes.registration_upload,
+            sizes.credential_response,
+            sizes.credential_finalization,
+        ])
+    }
+
+    // Every MAC is cut from the 64 bytes of SHA-512 to 16 bytes
+    let full = sizes::<Ristretto255>()?;
+    let truncated = sizes::<TruncatedMacCipherSuite>()?;
+    assert_eq!(full[0] - truncated[0], 48);
+    assert_eq!(full[1] - truncated[1], 48);
+    assert_eq!(full[2] - truncated[2], 96);
+    assert_eq!(full[3] - truncated[3], 48);
+
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<TruncatedMacCipherSuite>::new(&mut server_rng)?;
+    let client_registration_start_result = ClientRegistration::<TruncatedMacCipherSuite>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_registration_start_result = ServerRegistration::<TruncatedMacCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let registration_upload = client_registration_finish_result.message.serialize();
+    assert_eq!(registration_upload.len(), truncated[1]);
+    let p_file = ServerRegistration::finish(RegistrationUpload::deserialize(&registration_upload)?);
+
+    for (login_password, success) in [
+        (STR_PASSWORD.as_bytes(), true),
+        (b"bad password".as_slice(), false),
+    ] {
         let client_login_start_result =
-            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-        let server_login_start_result = ServerLogin::<CS>::start(
+            ClientLogin::<TruncatedMacCipherSuite>::start(&mut client_rng, login_password)?;
+        let server_login_start_result = ServerLogin::start(
             &mut server_rng,
             &server_setup,
-            Some(p_file),
+            Some(p_file.clone()),
             client_login_start_result.message,
             STR_CREDENTIAL_IDENTIFIER.as_bytes(),
             ServerLoginStartParameters::default(),
         )?;
+        let credential_response = server_login_start_result.message.serialize();
+        assert_eq!(credential_response.len(), truncated[2]);
+        let client_login_result = client_login_start_result.state.finish(
+            login_password,
+            CredentialResponse::deserialize(&credential_response)?,
+            ClientLoginFinishParameters::default(),
+        );
 
-        let mut state = server_login_start_result.state;
-        unsafe { ptr::drop_in_place(&mut state) };
-        for byte in state.serialize() {
-            assert_eq!(byte, 0);
+        if !success {
+            assert!(matches!(
+                client_login_result,
+                Err(ProtocolError::InvalidLoginError)
+            ));
+            continue;
         }
 
-        Ok(())
+        let client_login_finish_result = client_login_result?;
+        let credential_finalization = client_login_finish_result.message.serialize();
+        assert_eq!(credential_finalization.len(), truncated[3]);
+        let server_login_finish_result =
+            server_login_start_result
+                .state
+                .finish(CredentialFinalization::deserialize(
+                    &credential_finalization,
+                )?)?;
+        assert_eq!(
+            client_login_finish_result.session_key,
+            server_login_finish_result.session_key
+        );
     }
 
-    run_all!(inner);
-
     Ok(())
 }
 
+#[cfg(feature = "ristretto255")]
 #[test]
-fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
-    fn inner<CS: CipherSuite<KeyExchange = TripleDh>>(
+fn test_external_envelope_mode() -> Result<(), ProtocolError> {
+    use generic_array::typenum::{U16, U48};
+
+    struct ExternalCipherSuite;
+
+    impl CipherSuite for ExternalCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U48;
+    }
+
+    struct ShortAppDataCipherSuite;
+
+    impl CipherSuite for ShortAppDataCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U16;
+    }
+
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let client_keypair =
+        KeyPair::<crate::Ristretto255>::generate_random::<crate::Ristretto255, _>(&mut client_rng)?;
+    let app_data = GenericArray::from([0x5a; 48]);
+
+    // The application data of the suite must have room for the private key
+    let server_setup = ServerSetup::<ShortAppDataCipherSuite>::new(&mut server_rng)?;
+    let client_registration_start_result = ClientRegistration::<ShortAppDataCipherSuite>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_registration_start_result = ServerRegistration::<ShortAppDataCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    assert!(matches!(
+        client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters {
+                client_private_key: Some(client_keypair.private().clone()),
+                ..Default::default()
+            },
+        ),
+        Err(ProtocolError::LibraryError(
+            InternalError::IncompatibleEnvelopeModeError
+        ))
+    ));
+
+    let server_setup = ServerSetup::<ExternalCipherSuite>::new(&mut server_rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<ExternalCipherSuite>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<ExternalCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            app_data,
+            client_private_key: Some(client_keypair.private().clone()),
+            ..Default::default()
+        },
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    assert_eq!(
+        p_file.upload.client_s_pk.serialize(),
+        client_keypair.public().serialize()
+    );
+    // The stored envelope must not contain the private key in the clear
+    let client_sk_bytes = client_keypair.private().serialize();
+    assert!(!p_file
+        .serialize()
+        .windows(client_sk_bytes.len())
+        .any(|window| window == client_sk_bytes.as_slice()));
+
+    for (login_password, envelope_mode, success) in [
+        (STR_PASSWORD.as_bytes(), EnvelopeMode::External, true),
+        (STR_PASSWORD.as_bytes(), EnvelopeMode::Internal, false),
+        (b"bad password".as_slice(), EnvelopeMode::External, false),
+    ] {
+        let client_login_start_result =
+            ClientLogin::<ExternalCipherSuite>::start(&mut client_rng, login_password)?;
+        let server_login_start_result = ServerLogin::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file.clone()),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_result = client_login_start_result.state.finish(
+            login_password,
+            server_login_start_result.message,
+            ClientLoginFinishParameters {
+                envelope_mode,
+                ..Default::default()
+            },
+        );
+
+        if success {
+            let client_login_finish_result = client_login_result?;
+            // The private key is not returned as application data
+            assert_eq!(client_login_finish_result.app_data[..32], [0; 32]);
+            assert_eq!(client_login_finish_result.app_data[32..], app_data[32..]);
+
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+            assert_eq!(
+                hex::encode(server_login_finish_result.session_key),
+                hex::encode(client_login_finish_result.session_key)
+            );
+        } else {
+            assert!(matches!(
+                client_login_result,
+                Err(ProtocolError::InvalidLoginError)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_per_user_oprf_key() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U1;
+
+    use crate::{KeyedServerRegistrationLen, VersionedServerRegistrationLen, KEYED_RECORD_VERSION};
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // KeyedServerRegistration: (Version + ServerRegistration) + OprfKey
+        U1: Add<ServerRegistrationLen<CS>>,
+        VersionedServerRegistrationLen<CS>:
+            ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ScalarLen>,
+        KeyedServerRegistrationLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start_with_oprf_key(
+            &server_setup,
+            client_registration_start_result.message,
+            &oprf_key,
+        )?;
+        assert_eq!(server_registration_start_result.oprf_key, oprf_key);
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let keyed_file = ServerRegistration::finish_with_oprf_key(
+            client_registration_finish_result.message.clone(),
+            &oprf_key,
+        )?;
+        assert_eq!(
+            keyed_file.oprf_key().map(|key| key.as_slice()),
+            Some(oprf_key.as_slice())
+        );
+
+        // The key survives serialization
+        let serialized = keyed_file.serialize_keyed()?;
+        assert_eq!(serialized[0], KEYED_RECORD_VERSION);
+        ServerRegistration::<CS>::validate_keyed_structure(&serialized)?;
+        let keyed_file = ServerRegistration::<CS>::deserialize_keyed(&serialized)?;
+        assert_eq!(
+            keyed_file.oprf_key().map(|key| key.as_slice()),
+            Some(oprf_key.as_slice())
+        );
+        let read_result = ServerRegistration::<CS>::deserialize_versioned(&serialized, |_| {
+            Err(ProtocolError::SerializationError)
+        })?;
+        assert!(!read_result.upgraded);
+        assert_eq!(read_result.record.oprf_key(), keyed_file.oprf_key());
+        assert!(
+            ServerRegistration::<CS>::deserialize_keyed(&serialized[..serialized.len() - 1])
+                .is_err()
+        );
+
+        // A client can't pass off an upload with an OPRF key of its choosing as
+        // a keyed record
+        let mut keyed_upload = vec![KEYED_RECORD_VERSION];
+        keyed_upload.extend_from_slice(&client_registration_finish_result.message.serialize());
+        keyed_upload.extend_from_slice(&ServerRegistration::<CS>::generate_oprf_key(&mut rng)?);
+        assert!(ServerRegistration::<CS>::validate(&keyed_upload).is_err());
+        assert!(ServerRegistration::<CS>::validate_structure(&keyed_upload).is_err());
+        assert!(ServerRegistration::<CS>::deserialize(&keyed_upload).is_err());
+        assert!(ServerRegistration::<CS>::deserialize_keyed(
+            &client_registration_finish_result.message.serialize()
+        )
+        .is_err());
+
+        let plain_file = ServerRegistration::finish(client_registration_finish_result.message);
+        assert!(plain_file.oprf_key().is_none());
+        assert!(matches!(
+            plain_file.serialize_keyed(),
+            Err(ProtocolError::SerializationError)
+        ));
+
+        for (password_file, oprf_key, success) in [
+            // The keyed record is used without any parameter
+            (&keyed_file, None, true),
+            // A record without the key needs it in the parameters
+            (&plain_file, Some(oprf_key.as_slice()), true),
+            (&plain_file, None, false),
+        ] {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters {
+                    oprf_key,
+                    ..Default::default()
+                },
+            )?;
+            let client_login_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            );
+
+            if success {
+                let client_login_finish_result = client_login_result?;
+                let server_login_finish_result = server_login_start_result
+                    .state
+                    .finish(client_login_finish_result.message)?;
+                assert_eq!(
+                    hex::encode(server_login_finish_result.session_key),
+                    hex::encode(client_login_finish_result.session_key)
+                );
+            } else {
+                // The OPRF seed of the server setup does not yield the same key
+                assert!(matches!(
+                    client_login_result,
+                    Err(ProtocolError::InvalidLoginError)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_server_registration_serde() -> Result<(), ProtocolError> {
+    // The definition of `ServerRegistration` before it could carry an OPRF key
+    #[derive(serde::Serialize)]
+    #[serde(bound = "", rename = "ServerRegistration")]
+    struct BaselineServerRegistration<CS: CipherSuite>(RegistrationUpload<CS>)
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero;
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    #[serde(bound = "")]
+    struct Stored<CS: CipherSuite>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        #[serde(with = "crate::tagged_record")]
+        record: ServerRegistration<CS>,
+    }
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start_with_oprf_key(
+            &server_setup,
+            client_registration_start_result.message,
+            &oprf_key,
+        )?;
+        let upload = client_registration_start_result
+            .state
+            .finish(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+            )?
+            .message;
+
+        // Records stored before keep deserializing, and are still written the
+        // same way
+        let baseline = BaselineServerRegistration(upload.clone());
+        let baseline_bincode = bincode::serialize(&baseline).unwrap();
+        let baseline_json = serde_json::to_string(&baseline).unwrap();
+        let record: ServerRegistration<CS> = bincode::deserialize(&baseline_bincode).unwrap();
+        assert!(record.oprf_key().is_none());
+        assert_eq!(bincode::serialize(&record).unwrap(), baseline_bincode);
+        let record: ServerRegistration<CS> = serde_json::from_str(&baseline_json).unwrap();
+        assert!(record.oprf_key().is_none());
+        assert_eq!(serde_json::to_string(&record).unwrap(), baseline_json);
+
+        // The key of a keyed record can't be dropped silently
+        let keyed = ServerRegistration::finish_with_oprf_key(upload, &oprf_key)?;
+        assert_eq!(
+            keyed.oprf_key().map(|key| key.as_slice()),
+            Some(oprf_key.as_slice())
+        );
+        assert!(bincode::serialize(&keyed).is_err());
+        assert!(serde_json::to_string(&keyed).is_err());
+
+        for record in [keyed, record] {
+            let stored = Stored { record };
+            let serialized = bincode::serialize(&stored).unwrap();
+            let restored: Stored<CS> = bincode::deserialize(&serialized).unwrap();
+            assert_eq!(bincode::serialize(&restored).unwrap(), serialized);
+            assert_eq!(restored.record.oprf_key(), stored.record.oprf_key());
+            let serialized = serde_json::to_string(&stored).unwrap();
+            let restored: Stored<CS> = serde_json::from_str(&serialized).unwrap();
+            assert_eq!(serde_json::to_string(&restored).unwrap(), serialized);
+            assert_eq!(restored.record.oprf_key(), stored.record.oprf_key());
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_protocol_version() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        const CONTEXT: &[u8] = b"context";
+        const OTHER_VERSION: u16 = PROTOCOL_VERSION + 1;
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        // The versions are the ones received from the peer, while the flags
+        // tell whether a party announced its own version
+        let mut login = |server_version: Option<u16>,
+                         client_version: Option<u16>,
+                         server_announced: bool,
+                         client_announced: bool|
+         -> Result<(), ProtocolError> {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters {
+                    context: Some(CONTEXT),
+                    peer_protocol_version: client_version,
+                    protocol_version_announced: server_announced,
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(
+                server_login_start_result.protocol_version,
+                server_announced.then_some(PROTOCOL_VERSION)
+            );
+            let client_login_finish_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters {
+                    context: Some(CONTEXT),
+                    peer_protocol_version: server_version,
+                    protocol_version_announced: client_announced,
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(
+                client_login_finish_result.protocol_version,
+                client_announced.then_some(PROTOCOL_VERSION)
+            );
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+            assert_eq!(
+                hex::encode(server_login_finish_result.session_key),
+                hex::encode(client_login_finish_result.session_key)
+            );
+
+            Ok(())
+        };
+
+        login(None, None, false, false)?;
+        login(Some(PROTOCOL_VERSION), Some(PROTOCOL_VERSION), true, true)?;
+
+        // A different announced version is reported before running the key
+        // exchange
+        assert!(matches!(
+            login(Some(OTHER_VERSION), Some(PROTOCOL_VERSION), true, true),
+            Err(ProtocolError::ProtocolVersionError)
+        ));
+        assert!(matches!(
+            login(Some(PROTOCOL_VERSION), Some(OTHER_VERSION), true, true),
+            Err(ProtocolError::ProtocolVersionError)
+        ));
+
+        // A party that announced its version but didn't receive the peer's,
+        // because only it is configured to announce or because the
+        // announcement was dropped, fails before running the key exchange
+        assert!(matches!(
+            login(Some(PROTOCOL_VERSION), None, true, false),
+            Err(ProtocolError::ProtocolVersionMissing)
+        ));
+        assert!(matches!(
+            login(None, Some(PROTOCOL_VERSION), false, true),
+            Err(ProtocolError::ProtocolVersionMissing)
+        ));
+        assert!(matches!(
+            login(None, Some(PROTOCOL_VERSION), true, true),
+            Err(ProtocolError::ProtocolVersionMissing)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_credential_response_builder() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let oprf_key = server_registration_start_result.oprf_key;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        // The OPRF is evaluated outside of the builder, as by a separate service
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let builder = CredentialResponseBuilder::new(
+            &mut rng,
+            &server_setup,
+            Some(password_file.clone()),
+            client_login_start_result.message.clone(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let blinded_element =
+            voprf::BlindedElement::<CS::OprfCs>::deserialize(&builder.blinded_element())?;
+        let evaluation_element = voprf::OprfServer::<CS::OprfCs>::new_with_key(&oprf_key)?
+            .blind_evaluate(&blinded_element)
+            .serialize();
+        assert_eq!(builder.evaluate(&server_setup)?, evaluation_element);
+        assert_eq!(
+            block_on(builder.evaluate_with(&server_setup))?,
+            evaluation_element
+        );
+
+        let identity = OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::identity_elem());
+        assert!(builder
+            .clone()
+            .finish(&mut rng, &server_setup, &identity)
+            .is_err());
+        assert!(builder
+            .clone()
+            .finish(&mut rng, &server_setup, &evaluation_element[1..])
+            .is_err());
+
+        let server_login_start_result =
+            builder.finish(&mut rng, &server_setup, &evaluation_element)?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(server_login_finish_result.session_key),
+            hex::encode(client_login_finish_result.session_key)
+        );
+
+        // An evaluation with the wrong OPRF key fails like a wrong password
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let builder = CredentialResponseBuilder::new(
+            &mut rng,
+            &server_setup,
+            Some(password_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let evaluation_element = builder.evaluate(&ServerSetup::<CS>::new(&mut rng)?)?;
+        let server_login_start_result =
+            builder.finish(&mut rng, &server_setup, &evaluation_element)?;
+        assert!(matches!(
+            client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            ),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_server_login_prepare() -> Result<(), ProtocolError> {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        // Preparing a login draws the same randomness as starting it
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let started = ServerLogin::start(
+            &mut StdRng::from_seed([0; 32]),
+            &server_setup,
+            Some(password_file.clone()),
+            client_login_start_result.message.clone(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let completed = ServerLogin::prepare(
+            &mut StdRng::from_seed([0; 32]),
+            &server_setup,
+            Some(password_file.clone()),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?
+        .complete(&server_setup, client_login_start_result.message)?;
+        assert_eq!(
+            hex::encode(started.handshake_secret),
+            hex::encode(completed.handshake_secret)
+        );
+        assert_eq!(completed.oprf_key, started.oprf_key);
+
+        let mut login = |password_file: Option<ServerRegistration<CS>>| {
+            // The server prepares the login before the credential request arrives
+            let prepared = ServerLogin::prepare(
+                &mut rng,
+                &server_setup,
+                password_file,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result =
+                prepared.complete(&server_setup, client_login_start_result.message)?;
+            let client_login_finish_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            )?;
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+            assert_eq!(
+                hex::encode(server_login_finish_result.session_key),
+                hex::encode(client_login_finish_result.session_key)
+            );
+
+            Ok::<_, ProtocolError>(())
+        };
+
+        login(Some(password_file))?;
+        assert!(matches!(login(None), Err(ProtocolError::InvalidLoginError)));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_two_server() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        use crate::oprf::EvaluationRequest;
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let oprf_server = ServerSetup::<CS>::new(&mut rng)?;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let evaluation_element = block_on(two_server::evaluate(
+            &server_setup,
+            &oprf_server,
+            &client_registration_start_result.message.serialize(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ))?;
+        let server_registration_start_result =
+            ServerRegistration::<CS>::start_with_evaluation(&server_setup, &evaluation_element)?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let builder = CredentialResponseBuilder::new(
+            &mut rng,
+            &server_setup,
+            Some(password_file.clone()),
+            client_login_start_result.message.clone(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+
+        // The request to the OPRF server survives serialization
+        let blinded_element = builder.blinded_element();
+        let request =
+            EvaluationRequest::new(&blinded_element, STR_CREDENTIAL_IDENTIFIER.as_bytes())?;
+        let serialized = request.iter().collect::<Vec<_>>().concat();
+        assert_eq!(serialized.len(), request.len());
+        assert_eq!(EvaluationRequest::deserialize(&serialized)?, request);
+        assert!(matches!(
+            EvaluationRequest::deserialize(&serialized[..1]),
+            Err(ProtocolError::SerializationError)
+        ));
+        assert!(matches!(
+            EvaluationRequest::deserialize(&serialized[..1 + STR_CREDENTIAL_IDENTIFIER.len()]),
+            Err(ProtocolError::SerializationError)
+        ));
+
+        let evaluation_element = block_on(two_server::evaluate(
+            &server_setup,
+            &oprf_server,
+            request.blinded_element(),
+            request.credential_identifier(),
+        ))?;
+        let server_login_start_result =
+            builder.finish(&mut rng, &server_setup, &evaluation_element)?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(server_login_finish_result.session_key),
+            hex::encode(client_login_finish_result.session_key)
+        );
+
+        // The key of the front-end alone doesn't evaluate the OPRF of the
+        // credential
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        assert!(matches!(
+            client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            ),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        let identity = OprfGroup::<CS>::serialize_elem(OprfGroup::<CS>::identity_elem());
+        assert!(two_server::combine_evaluations::<CS>(&evaluation_element, &identity).is_err());
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "oprf-http")]
+#[test]
+fn test_http_oprf_evaluator() -> Result<(), ProtocolError> {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use crate::oprf::{evaluate_request, HttpOprfError, HttpOprfEvaluator, OprfEvaluator};
+
+    let mut rng = OsRng;
+    // The OPRF service holds its own setup, of which the front-end only needs
+    // the key pair
+    let oprf_service_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let server_setup = oprf_service_setup.clone();
+
+    // A minimal OPRF service answering a single request per connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+
+            let body = loop {
+                let len = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..len]);
+
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let headers = std::str::from_utf8(&request[..end]).unwrap().to_lowercase();
+                    let content_length: usize = headers
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+
+                    if request.len() >= end + 4 + content_length {
+                        break request[end + 4..end + 4 + content_length].to_vec();
+                    }
+                }
+            };
+
+            let (status, body) = match evaluate_request(&oprf_service_setup, &body) {
+                Ok(evaluation_element) => ("200 OK", evaluation_element.to_vec()),
+                Err(_) => ("400 Bad Request", Vec::new()),
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let evaluator = HttpOprfEvaluator::new(format!("http://{addr}/evaluate").parse().unwrap());
+
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let builder = CredentialResponseBuilder::new(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let evaluation_element = runtime.block_on(builder.evaluate_with(&evaluator)).unwrap();
+    assert_eq!(evaluation_element, builder.evaluate(&server_setup)?);
+
+    let server_login_start_result = builder.finish(&mut rng, &server_setup, &evaluation_element)?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    // Errors of the service are passed on
+    let result = runtime.block_on(<HttpOprfEvaluator as OprfEvaluator<P256>>::evaluate(
+        &evaluator,
+        &[0; 3],
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    ));
+    assert!(matches!(
+        result,
+        Err(ProtocolError::LibraryError(InternalError::Custom(
+            HttpOprfError::Status(status)
+        ))) if status.as_u16() == 400
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_pepper() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        const PEPPER: &[u8] = b"pepper";
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let oprf_key =
+            server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), PEPPER)?;
+
+        // The pepper changes the key derived from the OPRF seed
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let unpeppered_oprf_key = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?
+        .oprf_key;
+        assert_ne!(oprf_key, unpeppered_oprf_key);
+        assert_ne!(
+            oprf_key,
+            server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), b"other")?
+        );
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start_with_oprf_key(
+            &server_setup,
+            client_registration_start_result.message,
+            &oprf_key,
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        for (pepper, success) in [
+            (Some(PEPPER), true),
+            (Some(b"other".as_slice()), false),
+            (None, false),
+        ] {
+            let oprf_key = pepper
+                .map(|pepper| {
+                    server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), pepper)
+                })
+                .transpose()?;
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters {
+                    oprf_key: oprf_key.as_deref(),
+                    ..Default::default()
+                },
+            )?;
+            let client_login_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            );
+
+            if success {
+                let client_login_finish_result = client_login_result?;
+                let server_login_finish_result = server_login_start_result
+                    .state
+                    .finish(client_login_finish_result.message)?;
+                assert_eq!(
+                    hex::encode(server_login_finish_result.session_key),
+                    hex::encode(client_login_finish_result.session_key)
+                );
+            } else {
+                assert!(matches!(
+                    client_login_result,
+                    Err(ProtocolError::InvalidLoginError)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+// A client private key held outside of this crate
+struct EnclaveKey<KG: KeGroup>(PrivateKey<KG>);
+
+impl<KG: KeGroup> ClientSecretKey<KG> for EnclaveKey<KG> {
+    type Error = core::convert::Infallible;
+
+    fn diffie_hellman(
+        &self,
+        pk: PublicKey<KG>,
+    ) -> Result<GenericArray<u8, KG::PkLen>, InternalError<Self::Error>> {
+        SecretKey::diffie_hellman(&self.0, pk)
+    }
+
+    fn public_key(&self) -> Result<PublicKey<KG>, InternalError<Self::Error>> {
+        SecretKey::public_key(&self.0)
+    }
+}
+
+impl<KG: KeGroup> AsyncClientSecretKey<KG> for EnclaveKey<KG> {
+    type Error = core::convert::Infallible;
+    type DiffieHellmanFuture<'a>
+        = core::future::Ready<Result<GenericArray<u8, KG::PkLen>, InternalError<Self::Error>>>
+    where
+        Self: 'a;
+
+    fn diffie_hellman(&self, pk: PublicKey<KG>) -> Self::DiffieHellmanFuture<'_> {
+        core::future::ready(SecretKey::diffie_hellman(&self.0, pk))
+    }
+
+    fn public_key(&self) -> Result<PublicKey<KG>, InternalError<Self::Error>> {
+        SecretKey::public_key(&self.0)
+    }
+}
+
+#[cfg(feature = "dkg")]
+#[test]
+fn test_dkg() -> Result<(), ProtocolError> {
+    use crate::dkg::{
+        self, DhShare, DkgError, KeyShare, Parameters, PublicKeyPackage, ThresholdSecretKey,
+    };
+    use crate::key_exchange::group::ThresholdKeGroup;
+
+    fn inner<CS: CipherSuite>() -> Result<(), ProtocolError>
+    where
+        CS::KeGroup: ThresholdKeGroup,
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        const CONTEXT: &[u8] = b"test dkg";
+        let mut rng = OsRng;
+
+        assert_eq!(Parameters::new(0, 2, 3), Err(DkgError::InvalidParameters));
+        assert_eq!(Parameters::new(1, 4, 3), Err(DkgError::InvalidParameters));
+
+        let mut secrets = Vec::new();
+        let mut round1_packages = Vec::new();
+        for index in 1..=3 {
+            let parameters = Parameters::new(index, 2, 3).unwrap();
+            let (secret, package) = dkg::part1::<CS, _>(&mut rng, parameters, CONTEXT).unwrap();
+            let package =
+                dkg::Round1Package::deserialize(&package.serialize(), parameters.threshold())
+                    .unwrap();
+            secrets.push(secret);
+            round1_packages.push((index, package));
+        }
+
+        // A package bound to another context fails the proof of knowledge
+        let others = || round1_packages[1..].to_vec();
+        assert!(matches!(
+            dkg::part2::<CS>(secrets[0].clone(), &others(), b"other context"),
+            Err(DkgError::InvalidProof(2))
+        ));
+        assert!(matches!(
+            dkg::part2::<CS>(secrets[0].clone(), &others()[..1], CONTEXT),
+            Err(DkgError::MissingParticipant(3))
+        ));
+        assert!(matches!(
+            dkg::part2::<CS>(secrets[0].clone(), &round1_packages, CONTEXT),
+            Err(DkgError::UnexpectedParticipant(1))
+        ));
+
+        let mut round2_secrets = Vec::new();
+        let mut round2_packages = Vec::new();
+        for (secret, (index, _)) in secrets.into_iter().zip(&round1_packages) {
+            let packages: Vec<_> = round1_packages
+                .iter()
+                .filter(|(other, _)| other != index)
+                .cloned()
+                .collect();
+            let (secret, packages) = dkg::part2::<CS>(secret, &packages, CONTEXT).unwrap();
+            round2_secrets.push(secret);
+            round2_packages.extend(
+                packages
+                    .into_iter()
+                    .map(|(to, package)| (*index, to, package)),
+            );
+        }
+
+        let mut key_shares = Vec::new();
+        for (secret, index) in round2_secrets.into_iter().zip(1..) {
+            let packages: Vec<_> = round2_packages
+                .iter()
+                .filter(|(_, to, _)| *to == index)
+                .map(|(from, _, package)| {
+                    (
+                        *from,
+                        dkg::Round2Package::deserialize(&package.serialize()).unwrap(),
+                    )
+                })
+                .collect();
+
+            // A share that doesn't match the commitments of its sender
+            let mut invalid = packages.clone();
+            invalid.swap(0, 1);
+            invalid[0].0 = packages[0].0;
+            invalid[1].0 = packages[1].0;
+            assert!(matches!(
+                dkg::part3::<CS>(secret.clone(), &invalid),
+                Err(DkgError::InvalidShare(_))
+            ));
+
+            let key_share = dkg::part3::<CS>(secret, &packages).unwrap();
+            let serialized = key_share.serialize();
+            assert_eq!(
+                KeyShare::<CS::KeGroup>::deserialize(&serialized)
+                    .unwrap()
+                    .serialize(),
+                serialized
+            );
+            key_shares.push(key_share);
+        }
+
+        // All participants agree on the public key and on the public keys of
+        // the shares, which deserialization checks the share against
+        let public_key = key_shares[0].public_key();
+        for key_share in &key_shares {
+            assert_eq!(key_share.public_key().serialize(), public_key.serialize());
+            for index in 1..=3 {
+                assert_eq!(
+                    key_share.verifying_share(index).unwrap().serialize(),
+                    key_shares[0].verifying_share(index).unwrap().serialize()
+                );
+            }
+        }
+        let mut serialized = key_shares[0].serialize();
+        serialized[6..].copy_from_slice(&key_shares[1].serialize()[6..]);
+        assert!(matches!(
+            KeyShare::<CS::KeGroup>::deserialize(&serialized),
+            Err(DkgError::InvalidShare(1))
+        ));
+
+        let package = key_shares[0].public_key_package();
+        assert_eq!(
+            PublicKeyPackage::<CS::KeGroup>::deserialize(&package.serialize())
+                .unwrap()
+                .serialize(),
+            package.serialize()
+        );
+        assert_eq!(package.public_key().serialize(), public_key.serialize());
+        assert_eq!(
+            package.verifying_share(3).unwrap().serialize(),
+            key_shares[2].verifying_share(3).unwrap().serialize()
+        );
+
+        // A Diffie-Hellman share which isn't computed with the share of its
+        // participant is reported with its index
+        let pk = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?
+            .public()
+            .clone();
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| {
+                DhShare::deserialize(&key_share.dh_share::<CS>(&pk).unwrap().serialize()).unwrap()
+            })
+            .collect();
+        let output = dkg::combine_dh_shares::<CS>(&package, &pk, &shares[..2]).unwrap();
+        assert_eq!(
+            dkg::combine_dh_shares::<CS>(&package, &pk, &shares[1..]).unwrap(),
+            output
+        );
+        let mut invalid = shares[1].serialize();
+        invalid[..2].copy_from_slice(&3u16.to_be_bytes());
+        let invalid = DhShare::deserialize(&invalid).unwrap();
+        assert_eq!(
+            dkg::combine_dh_shares::<CS>(&package, &pk, &[shares[0], invalid]),
+            Err(DkgError::InvalidDhShare(3))
+        );
+        let mut invalid = shares[1].serialize();
+        let element_len = <CS::KeGroup as KeGroup>::PkLen::USIZE;
+        invalid[2..2 + element_len].copy_from_slice(&shares[2].serialize()[2..2 + element_len]);
+        let invalid = DhShare::deserialize(&invalid).unwrap();
+        assert_eq!(
+            dkg::combine_dh_shares::<CS>(&package, &pk, &[shares[0], invalid]),
+            Err(DkgError::InvalidDhShare(2))
+        );
+
+        // Any two of the three participants can answer logins
+        let key = ThresholdSecretKey::<CS, _>::new(package.clone(), key_shares[1..].to_vec());
+        let server_setup = ServerSetup::<CS, _>::new_with_key(
+            &mut rng,
+            KeyPair::from_private_key(key).map_err(|_| ProtocolError::SerializationError)?,
+        )?;
+        assert_eq!(
+            server_setup.public_key().serialize(),
+            public_key.serialize()
+        );
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file.clone()),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )
+        .map_err(|_| ProtocolError::SerializationError)?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(server_login_finish_result.session_key),
+            hex::encode(client_login_finish_result.session_key)
+        );
+
+        // A single participant can't
+        let key = ThresholdSecretKey::<CS, _>::new(package, key_shares[..1].to_vec());
+        let server_setup = ServerSetup::<CS, _>::new_with_key(
+            &mut rng,
+            KeyPair::from_private_key(key).map_err(|_| ProtocolError::SerializationError)?,
+        )?;
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        assert!(matches!(
+            ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            ),
+            Err(ProtocolError::LibraryError(InternalError::Custom(
+                DkgError::NotEnoughShares
+            )))
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "ristretto255")]
+    inner::<Ristretto255>()?;
+    inner::<P256>()?;
+
+    Ok(())
+}
+
+// Polls `future` to completion without an async runtime
+fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn noop_raw_waker() -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+    static VTABLE: RawWakerVTable =
+        RawWakerVTable::new(|_| noop_raw_waker(), |_| {}, |_| {}, |_| {});
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn test_client_secret_key() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let client_key = EnclaveKey(
+            KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut rng)?
+                .private()
+                .clone(),
+        );
+        let client_s_pk = ClientSecretKey::public_key(&client_key)?;
+
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters {
+                client_public_key: Some(client_s_pk.clone()),
+                ..Default::default()
+            },
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+        assert_eq!(
+            password_file.upload.client_s_pk.serialize(),
+            client_s_pk.serialize()
+        );
+
+        for variant in 0..3 {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            let state = client_login_start_result.state;
+            let message = server_login_start_result.message;
+            let client_login_finish_result = match variant {
+                0 => state.finish_with_secret_key(
+                    &client_key,
+                    STR_PASSWORD.as_bytes(),
+                    message,
+                    ClientLoginFinishParameters::default(),
+                )?,
+                1 => block_on(state.finish_with_async_secret_key(
+                    &client_key,
+                    STR_PASSWORD.as_bytes(),
+                    message,
+                    ClientLoginFinishParameters::default(),
+                ))?,
+                _ => {
+                    // The envelope is not bound to a key derived from the password
+                    assert!(matches!(
+                        state.finish(
+                            STR_PASSWORD.as_bytes(),
+                            message,
+                            ClientLoginFinishParameters::default(),
+                        ),
+                        Err(ProtocolError::InvalidLoginError)
+                    ));
+                    continue;
+                }
+            };
+
+            assert_eq!(
+                client_login_finish_result.export_key,
+                client_registration_finish_result.export_key
+            );
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+            assert_eq!(
+                hex::encode(server_login_finish_result.session_key),
+                hex::encode(client_login_finish_result.session_key)
+            );
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "kms")]
+#[test]
+fn test_kms() -> Result<(), ProtocolError> {
+    use core::future::{ready, Ready};
+
+    use crate::kms::{KmsClient, KmsError, KmsSecretKey};
+
+    type KG = p256::NistP256;
+
+    // Answers like AWS KMS for the key "key", from a private key in memory
+    #[derive(Clone)]
+    struct MockKms {
+        private_key: PrivateKey<KG>,
+        truncate: bool,
+    }
+
+    impl KmsClient for MockKms {
+        type Error = &'static str;
+        type Future<'a> = Ready<Result<Vec<u8>, Self::Error>>;
+
+        fn public_key<'a>(&'a self, key_id: &'a str) -> Self::Future<'a> {
+            let mut buffer = [0; 128];
+            ready(match key_id {
+                "key" => Ok(SecretKey::<KG>::public_key(&self.private_key)
+                    .unwrap()
+                    .to_spki_der(&mut buffer)
+                    .unwrap()
+                    .to_vec()),
+                _ => Err("unknown key"),
+            })
+        }
+
+        fn derive_shared_secret<'a>(
+            &'a self,
+            key_id: &'a str,
+            public_key: Vec<u8>,
+        ) -> Self::Future<'a> {
+            if key_id != "key" {
+                return ready(Err("unknown key"));
+            }
+            let public_key = PublicKey::<KG>::from_spki_der(&public_key).unwrap();
+            let shared = self.private_key.diffie_hellman(public_key).unwrap();
+            let end = if self.truncate { 16 } else { shared.len() };
+
+            ready(Ok(shared[1..end].to_vec()))
+        }
+    }
+
+    let mut rng = OsRng;
+    let private_key = PrivateKey::<KG>::deserialize(&KG::serialize_sk(KG::random_sk(&mut rng)?))?;
+    let client = MockKms {
+        private_key: private_key.clone(),
+        truncate: false,
+    };
+
+    assert_eq!(
+        block_on(KmsSecretKey::<KG, _>::connect(client.clone(), "other")).err(),
+        Some(KmsError::Client("unknown key"))
+    );
+    let key = block_on(KmsSecretKey::<KG, _>::connect(client.clone(), "key")).unwrap();
+    assert_eq!(key.key_id(), "key");
+    assert_eq!(
+        SecretKey::<KG>::public_key(&key).unwrap().serialize(),
+        SecretKey::<KG>::public_key(&private_key)
+            .unwrap()
+            .serialize()
+    );
+
+    // Both signs of the y-coordinate of the shared point are recovered
+    for _ in 0..32 {
+        let peer = PublicKey::<KG>::from_group_type(KG::public_key(KG::random_sk(&mut rng)?));
+        assert_eq!(
+            key.diffie_hellman(peer.clone()).unwrap(),
+            private_key.diffie_hellman(peer).unwrap()
+        );
+    }
+
+    let negated_generator = PublicKey::<KG>::from_group_type(KG::public_key(-p256::Scalar::ONE));
+    assert!(matches!(
+        key.diffie_hellman(negated_generator),
+        Err(InternalError::Custom(KmsError::IdentityElement))
+    ));
+    let peer = PublicKey::<KG>::from_group_type(KG::public_key(KG::random_sk(&mut rng)?));
+    let truncated = KmsSecretKey::new(
+        MockKms {
+            private_key: private_key.clone(),
+            truncate: true,
+        },
+        "key",
+        SecretKey::<KG>::public_key(&private_key).unwrap(),
+    );
+    assert!(matches!(
+        truncated.diffie_hellman(peer.clone()),
+        Err(InternalError::Custom(KmsError::InvalidSharedSecret))
+    ));
+    let unknown = KmsSecretKey::new(
+        client,
+        "other",
+        SecretKey::<KG>::public_key(&private_key).unwrap(),
+    );
+    assert!(matches!(
+        unknown.diffie_hellman(peer),
+        Err(InternalError::Custom(KmsError::Client("unknown key")))
+    ));
+
+    // The key answers logins
+    let server_setup = ServerSetup::<P256, _>::new_with_key(
+        &mut rng,
+        KeyPair::from_private_key(key).map_err(|_| ProtocolError::SerializationError)?,
+    )?;
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )
+    .map_err(|_| ProtocolError::SerializationError)?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+    assert_eq!(
+        hex::encode(server_login_finish_result.session_key),
+        hex::encode(client_login_finish_result.session_key)
+    );
+
+    Ok(())
+}
+
+#[cfg(all(feature = "noop-ksf", feature = "ristretto255"))]
+#[test]
+fn test_noop_ksf() -> Result<(), ProtocolError> {
+    struct NoOpKsfCipherSuite;
+
+    impl CipherSuite for NoOpKsfCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh;
+        type Ksf = crate::ksf::NoOpKsf;
+        type AppDataLen = U0;
+    }
+
+    test_complete_flow::<NoOpKsfCipherSuite>("", b"good password", b"good password")?;
+    test_complete_flow::<NoOpKsfCipherSuite>("", b"good password", b"bad password")?;
+
+    Ok(())
+}
+
+#[cfg(feature = "fips")]
+#[test]
+fn test_fips() -> Result<(), ProtocolError> {
+    use crate::ksf::Pbkdf2;
+    use crate::suites::{P256Sha256Pbkdf2, P384Sha384Pbkdf2};
+
+    assert!(crate::fips::is_approved::<P256Sha256Pbkdf2>());
+    assert!(crate::fips::is_approved::<P384Sha384Pbkdf2>());
+    const _: () = crate::fips::assert_approved::<P256Sha256Pbkdf2>();
+    // `Identity` is not an approved key stretching function
+    assert!(!crate::fips::is_approved::<P256>());
+    assert!(!crate::fips::is_approved::<P256P384>());
+    #[cfg(feature = "ristretto255")]
+    assert!(!crate::fips::is_approved::<Ristretto255>());
+    // The curves are identified by their OPRF suite
+    const _: () = assert!(<p256::NistP256 as KeGroup>::FIPS_APPROVED);
+    const _: () = assert!(<p384::NistP384 as KeGroup>::FIPS_APPROVED);
+    const _: () = assert!(!<p521::NistP521 as KeGroup>::FIPS_APPROVED);
+
+    assert!(matches!(Pbkdf2::new(0), Err(InternalError::KsfError)));
+
+    // Fewer rounds than the default keep the test fast
+    let pbkdf2 = Pbkdf2::new(1000)?;
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256Sha256Pbkdf2>::new(&mut rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<P256Sha256Pbkdf2>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256Sha256Pbkdf2>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            ksf: Some(&pbkdf2),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        client_registration_finish_result.ksf_parameters.time_cost,
+        1000
+    );
+
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let client_login_start_result =
+        ClientLogin::<P256Sha256Pbkdf2>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters {
+            ksf: Some(&pbkdf2),
+            ..Default::default()
+        },
+    )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    assert_eq!(
+        hex::encode(client_registration_finish_result.export_key),
+        hex::encode(client_login_finish_result.export_key)
+    );
+    assert_eq!(
+        hex::encode(client_login_finish_result.session_key),
+        hex::encode(server_login_finish_result.session_key)
+    );
+
+    Ok(())
+}
+
+// Checks that the HKDFs and HMACs of `P` match those of `RustCrypto`
+#[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+fn compare_provider<D: Hash, P: crate::provider::Provider<D>>() -> Result<(), ProtocolError>
+where
+    P::Mac: Clone,
+    D::Core: ProxyHash,
+    <D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    use crate::provider::{Provider, RustCrypto};
+
+    for salt in [None, Some(b"salt".as_slice())] {
+        let (prk, rust_crypto_prk) =
+            <RustCrypto as Provider<D>>::extract(salt, &[b"input", b"keying material"]);
+        let (other_prk, other_hkdf) = P::extract(salt, &[b"input", b"keying material"]);
+        assert_eq!(prk, other_prk);
+
+        let mut okm = [0; 100];
+        let mut other_okm = [0; 100];
+        <RustCrypto as Provider<D>>::expand(&rust_crypto_prk, &[b"some", b"info"], &mut okm)?;
+        P::expand(&other_hkdf, &[b"some", b"info"], &mut other_okm)?;
+        assert_eq!(okm, other_okm);
+
+        let other_hkdf = P::from_prk(&prk)?;
+        P::expand(&other_hkdf, &[b"some", b"info"], &mut other_okm)?;
+        assert_eq!(okm, other_okm);
+    }
+
+    let mut too_long = vec![0; 256 * OutputSize::<D>::USIZE];
+    assert!(matches!(
+        P::expand(&P::from_prk(&Output::<D>::default())?, &[], &mut too_long,),
+        Err(InternalError::HkdfError)
+    ));
+    assert!(matches!(
+        P::from_prk(&[0; 16]),
+        Err(InternalError::HkdfError)
+    ));
+
+    let mut rust_crypto_mac = <RustCrypto as Provider<D>>::new_mac(b"key")?;
+    let mut other_mac = P::new_mac(b"key")?;
+    for chunk in [b"authenticated".as_slice(), b"message"] {
+        <RustCrypto as Provider<D>>::update_mac(&mut rust_crypto_mac, chunk);
+        P::update_mac(&mut other_mac, chunk);
+    }
+    let tag = <RustCrypto as Provider<D>>::finalize_mac(rust_crypto_mac);
+    assert_eq!(tag, P::finalize_mac(other_mac.clone()));
+
+    let mut wrong_tag = tag.clone();
+    wrong_tag[0] ^= 1;
+    let mut too_long = tag.to_vec();
+    too_long.push(0);
+    for (candidate, valid) in [
+        (tag.as_slice(), true),
+        (&tag[..16], true),
+        (&wrong_tag[..16], false),
+        (&[], false),
+        (too_long.as_slice(), false),
+    ] {
+        assert_eq!(
+            bool::from(P::verify_mac(other_mac.clone(), candidate)),
+            valid
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "ring")]
+#[test]
+fn test_ring_provider() -> Result<(), ProtocolError> {
+    use crate::key_exchange::tripledh::FullMac;
+    use crate::provider::Ring;
+
+    compare_provider::<sha2::Sha256, Ring>()?;
+    compare_provider::<sha2::Sha384, Ring>()?;
+    compare_provider::<sha2::Sha512, Ring>()?;
+
+    struct RingCipherSuite;
+
+    impl CipherSuite for RingCipherSuite {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh<FullMac, Ring>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    test_complete_flow::<RingCipherSuite>("", b"good password", b"good password")?;
+    test_complete_flow::<RingCipherSuite>("", b"good password", b"bad password")?;
+
+    // Both providers compute the same values, so a record registered with one
+    // can be used to log in with the other
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let server_setup = ServerSetup::<RingCipherSuite>::deserialize(&server_setup.serialize())?;
+    let p_file = ServerRegistration::<RingCipherSuite>::deserialize(&p_file.serialize())?;
+    let client_login_start_result =
+        ClientLogin::<RingCipherSuite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    assert_eq!(
+        hex::encode(client_registration_finish_result.export_key),
+        hex::encode(client_login_finish_result.export_key)
+    );
+    assert_eq!(
+        hex::encode(client_login_finish_result.session_key),
+        hex::encode(server_login_finish_result.session_key)
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "aws-lc-rs")]
+#[test]
+fn test_aws_lc_provider() -> Result<(), ProtocolError> {
+    use crate::key_exchange::tripledh::FullMac;
+    use crate::provider::AwsLc;
+
+    compare_provider::<sha2::Sha256, AwsLc>()?;
+    compare_provider::<sha2::Sha384, AwsLc>()?;
+    compare_provider::<sha2::Sha512, AwsLc>()?;
+
+    struct AwsLcCipherSuite;
+
+    impl CipherSuite for AwsLcCipherSuite {
+        type OprfCs = p384::NistP384;
+        type KeGroup = p384::NistP384;
+        type KeyExchange = TripleDh<FullMac, AwsLc>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    test_complete_flow::<AwsLcCipherSuite>("", b"good password", b"good password")?;
+    test_complete_flow::<AwsLcCipherSuite>("", b"good password", b"bad password")?;
+
+    Ok(())
+}
+
+#[cfg(feature = "ristretto255")]
+#[test]
+fn test_custom_labels() -> Result<(), ProtocolError> {
+    struct CustomLabelsCipherSuite;
+
+    impl CipherSuite for CustomLabelsCipherSuite {
+        type OprfCs = crate::Ristretto255;
+        type KeGroup = crate::Ristretto255;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+
+        const MASKING_KEY_LABEL: &'static [u8] = b"CustomMaskingKey";
+        const AUTH_KEY_LABEL: &'static [u8] = b"CustomAuthKey";
+        const EXPORT_KEY_LABEL: &'static [u8] = b"CustomExportKey";
+        const PRIVATE_KEY_LABEL: &'static [u8] = b"CustomPrivateKey";
+    }
+
+    test_complete_flow::<CustomLabelsCipherSuite>("", b"good password", b"good password")?;
+    test_complete_flow::<CustomLabelsCipherSuite>("", b"good password", b"bad password")?;
+
+    // A record registered with custom labels must not be usable by a cipher
+    // suite with the default labels
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<CustomLabelsCipherSuite>::new(&mut server_rng)?;
+    let client_registration_start_result = ClientRegistration::<CustomLabelsCipherSuite>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_registration_start_result = ServerRegistration::<CustomLabelsCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let server_setup = ServerSetup::<Ristretto255>::deserialize(&server_setup.serialize())?;
+    let p_file = ServerRegistration::<Ristretto255>::deserialize(&p_file.serialize())?;
+    let client_login_start_result =
+        ClientLogin::<Ristretto255>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    );
+    assert!(matches!(
+        client_login_result,
+        Err(ProtocolError::InvalidLoginError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_derivation_tags() -> Result<(), ProtocolError> {
+    struct CustomOprfCs;
+
+    impl voprf::CipherSuite for CustomOprfCs {
+        const ID: &'static str = "Custom-P256-SHA256";
+
+        type Group = <p256::NistP256 as voprf::CipherSuite>::Group;
+        type Hash = <p256::NistP256 as voprf::CipherSuite>::Hash;
+    }
+
+    struct CustomTagsCipherSuite;
+
+    impl CipherSuite for CustomTagsCipherSuite {
+        type OprfCs = CustomOprfCs;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+
+        const OPRF_KEY_INFO: &'static [u8] = b"Custom-DeriveKeyPair";
+        const AUTH_KEY_PAIR_INFO: &'static [u8] = b"Custom-DeriveDiffieHellmanKeyPair";
+        const AUTH_KEY_PAIR_DST: Option<&'static [u8]> = Some(b"Custom-DeriveKeyPair-P256");
+    }
+
+    test_complete_flow::<CustomTagsCipherSuite>("", b"good password", b"good password")?;
+    test_complete_flow::<CustomTagsCipherSuite>("", b"good password", b"bad password")?;
+
+    // The same seeds derive different keys than with the default tags
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<CustomTagsCipherSuite>::new(&mut rng)?;
+    let default_server_setup = ServerSetup::<P256>::deserialize(&server_setup.serialize())?;
+    assert_ne!(
+        hex::encode(server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), b"")?),
+        hex::encode(
+            default_server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), b"")?
+        )
+    );
+    assert_ne!(
+        hex::encode(
+            KeyPair::derive_from_seed::<CustomTagsCipherSuite>(&[0; 32], b"info")?
+                .public()
+                .serialize()
+        ),
+        hex::encode(
+            KeyPair::derive_from_seed::<P256>(&[0; 32], b"info")?
+                .public()
+                .serialize()
+        )
+    );
+
+    // A record registered with custom tags must not be usable by a cipher suite
+    // with the default tags
+    let client_registration_start_result =
+        ClientRegistration::<CustomTagsCipherSuite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<CustomTagsCipherSuite>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::<P256>::deserialize(
+        &ServerRegistration::finish(client_registration_finish_result.message).serialize(),
+    )?;
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &default_server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    );
+    assert!(matches!(
+        client_login_result,
+        Err(ProtocolError::InvalidLoginError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_session_key_derivation() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U64;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::key_exchange::tripledh::{FullMac, SessionKeyDerivation, SessionKeyLength};
+    use crate::provider::RustCrypto;
+
+    struct LongSessionKey;
+
+    impl CipherSuite for LongSessionKey {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh<FullMac, RustCrypto, SessionKeyLength<U64>>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    struct CustomLabels<const HANDSHAKE: bool>;
+
+    impl<D: OutputSizeUser, const HANDSHAKE: bool> SessionKeyDerivation<D> for CustomLabels<HANDSHAKE> {
+        type Len = U64;
+        const HANDSHAKE_SECRET_LABEL: &'static [u8] = if HANDSHAKE {
+            b"CustomHandshakeSecret"
+        } else {
+            b"HandshakeSecret"
+        };
+        const SESSION_KEY_LABEL: &'static [u8] = b"CustomSessionKey";
+    }
+
+    struct CustomSessionKeyLabel;
+
+    impl CipherSuite for CustomSessionKeyLabel {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh<FullMac, RustCrypto, CustomLabels<false>>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    struct CustomHandshakeLabel;
+
+    impl CipherSuite for CustomHandshakeLabel {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh<FullMac, RustCrypto, CustomLabels<true>>;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+    }
+
+    // Registers and logs in with the same seed, returning the session keys of
+    // the client and the server and the export key
+    fn login<CS: CipherSuite>() -> Result<[Vec<u8>; 3], ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = StdRng::from_seed([0; 32]);
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+
+        Ok([
+            client_login_finish_result.session_key.to_vec(),
+            server_login_finish_result.session_key.to_vec(),
+            client_login_finish_result.export_key.to_vec(),
+        ])
+    }
+
+    test_complete_flow::<LongSessionKey>("", b"good password", b"good password")?;
+    test_complete_flow::<LongSessionKey>("", b"good password", b"bad password")?;
+
+    // The length only changes the session key, which SHA-256 expands to 64
+    // bytes
+    let [client_session_key, server_session_key, export_key] = login::<P256>()?;
+    let [long_client_session_key, long_server_session_key, long_export_key] =
+        login::<LongSessionKey>()?;
+    assert_eq!(long_client_session_key.len(), 64);
+    assert_eq!(long_client_session_key, long_server_session_key);
+    assert_ne!(
+        &long_client_session_key[..32],
+        client_session_key.as_slice()
+    );
+    assert_eq!(client_session_key, server_session_key);
+    assert_eq!(export_key, long_export_key);
+
+    // A custom session key label yields a different session key
+    let [custom_client_session_key, custom_server_session_key, custom_export_key] =
+        login::<CustomSessionKeyLabel>()?;
+    assert_eq!(custom_client_session_key, custom_server_session_key);
+    assert_ne!(custom_client_session_key, long_client_session_key);
+    assert_eq!(custom_export_key, export_key);
+
+    // A custom handshake secret label yields different MAC keys, so a login
+    // with a server using the default label fails
+    let [custom_client_session_key, custom_server_session_key, _] =
+        login::<CustomHandshakeLabel>()?;
+    assert_eq!(custom_client_session_key, custom_server_session_key);
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    let client_login_start_result =
+        ClientLogin::<CustomHandshakeLabel>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(p_file),
+        CredentialRequest::deserialize(&client_login_start_result.message.serialize())?,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        CredentialResponse::deserialize(&server_login_start_result.message.serialize())?,
+        ClientLoginFinishParameters::default(),
+    );
+    assert!(matches!(
+        client_login_result,
+        Err(ProtocolError::InvalidLoginError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_password_length() -> Result<(), ProtocolError> {
+    struct BoundedPasswords;
+
+    impl CipherSuite for BoundedPasswords {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+
+        const MIN_PASSWORD_LEN: usize = 8;
+        const MAX_PASSWORD_LEN: usize = 16;
+    }
+
+    fn check<CS: CipherSuite>(password: &[u8]) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut rng = OsRng;
+        let registration = ClientRegistration::<CS>::start(&mut rng, password).map(|_| ());
+        let login = ClientLogin::<CS>::start(&mut rng, password).map(|_| ());
+        let prepared_login = ClientLogin::<CS>::prepare(&mut rng)?
+            .start(password)
+            .map(|_| ());
+        let retrieval = ClientRetrieval::<CS>::start(&mut rng, password).map(|_| ());
+
+        assert_eq!(registration, login);
+        assert_eq!(registration, prepared_login);
+        assert_eq!(registration, retrieval);
+
+        registration
+    }
+
+    // The default bounds reject empty passwords and those longer than 1024 bytes
+    assert_eq!(
+        check::<P256>(b""),
+        Err(ProtocolError::PasswordTooShortError)
+    );
+    check::<P256>(b"p")?;
+    check::<P256>(&[b'p'; 1024])?;
+    assert_eq!(
+        check::<P256>(&[b'p'; 1025]),
+        Err(ProtocolError::PasswordTooLongError)
+    );
+
+    assert_eq!(
+        check::<BoundedPasswords>(&[b'p'; 7]),
+        Err(ProtocolError::PasswordTooShortError)
+    );
+    check::<BoundedPasswords>(&[b'p'; 8])?;
+    check::<BoundedPasswords>(&[b'p'; 16])?;
+    assert_eq!(
+        check::<BoundedPasswords>(&[b'p'; 17]),
+        Err(ProtocolError::PasswordTooLongError)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_server_authentication_only() -> Result<(), ProtocolError> {
+    struct ServerAuthOnly;
+
+    impl CipherSuite for ServerAuthOnly {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh;
+        type Ksf = Identity;
+        type AppDataLen = U0;
+
+        const CLIENT_AUTHENTICATION: bool = false;
+    }
+
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<ServerAuthOnly>::new(&mut server_rng)?;
+
+    let register = |params: ClientRegistrationFinishParameters<ServerAuthOnly>| {
+        let client_registration_start_result =
+            ClientRegistration::<ServerAuthOnly>::start(&mut OsRng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<ServerAuthOnly>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        client_registration_start_result.state.finish(
+            &mut OsRng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            params,
+        )
+    };
+
+    // There is no client key to seal
+    let client_keypair =
+        KeyPair::<p256::NistP256>::generate_random::<p256::NistP256, _>(&mut client_rng)?;
+    assert!(matches!(
+        register(ClientRegistrationFinishParameters {
+            client_public_key: Some(client_keypair.public().clone()),
+            ..Default::default()
+        }),
+        Err(ProtocolError::LibraryError(
+            InternalError::IncompatibleEnvelopeModeError
+        ))
+    ));
+
+    let client_registration_finish_result =
+        register(ClientRegistrationFinishParameters::default())?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message.clone());
+    assert_eq!(p_file.upload.client_s_pk, *server_setup.public_key());
+
+    let login_start = |client_rng: &mut OsRng, server_rng: &mut OsRng| {
+        let client_login_start_result =
+            ClientLogin::<ServerAuthOnly>::start(client_rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            server_rng,
+            &server_setup,
+            Some(p_file.clone()),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        Ok::<_, ProtocolError>((client_login_start_result.state, server_login_start_result))
+    };
+
+    // The client authenticates the server and both derive the same keys
+    let (client_login, server_login_start_result) = login_start(&mut client_rng, &mut server_rng)?;
+    let client_login_finish_result = client_login.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    assert_eq!(
+        client_login_finish_result.export_key,
+        client_registration_finish_result.export_key
+    );
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+    assert_eq!(
+        server_login_finish_result.session_key,
+        client_login_finish_result.session_key
+    );
+
+    let (client_login, server_login_start_result) = login_start(&mut client_rng, &mut server_rng)?;
+    assert!(matches!(
+        client_login.finish(
+            b"wrong password",
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        ),
+        Err(ProtocolError::InvalidLoginError)
+    ));
+
+    let (client_login, server_login_start_result) = login_start(&mut client_rng, &mut server_rng)?;
+    assert!(matches!(
+        client_login.finish_with_secret_key(
+            &EnclaveKey(client_keypair.private().clone()),
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        ),
+        Err(ProtocolError::LibraryError(
+            InternalError::IncompatibleEnvelopeModeError
+        ))
+    ));
+
+    // A client without the password only needs the server's public key to
+    // produce a final message the server accepts
+    let (client_login, server_login_start_result) = login_start(&mut client_rng, &mut server_rng)?;
+    let credential_response = server_login_start_result.message;
+    let beta =
+        OprfGroup::<ServerAuthOnly>::serialize_elem(credential_response.evaluation_element.value());
+    let blinded_element = OprfGroup::<ServerAuthOnly>::serialize_elem(
+        client_login.credential_request.blinded_element.value(),
+    );
+    let ke1_message = client_login.credential_request.ke1_message.serialize();
+    let (id_u, id_s) = bytestrings_from_identifiers::<p256::NistP256>(
+        Identifiers::default(),
+        server_setup.public_key().serialize(),
+        server_setup.public_key().serialize(),
+    )?;
+    let (session_key, ke3_message, ..) =
+        <TripleDh as crate::key_exchange::traits::KeyExchange<_, p256::NistP256>>::generate_ke3(
+            CredentialResponse::<ServerAuthOnly>::serialize_without_ke(
+                &beta,
+                &credential_response.masking_nonce,
+                &credential_response.masked_response,
+            ),
+            credential_response.ke2_message,
+            &client_login.ke1_state,
+            CredentialRequest::<ServerAuthOnly>::serialize_iter(&blinded_element, &ke1_message),
+            server_setup.public_key().clone(),
+            None,
+            id_u.iter(),
+            id_s.iter(),
+            &[],
+        )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(CredentialFinalization { ke3_message })?;
+    assert_eq!(server_login_finish_result.session_key, session_key);
+    assert!(server_login_finish_result.client_s_pk.is_none());
+
+    // Such a login is no proof of the password and can't replace the record
+    let client_registration_start_result =
+        ClientRegistration::<ServerAuthOnly>::start(&mut client_rng, b"new password")?;
+    assert!(matches!(
+        server_login_finish_result.start_migration(
+            &server_setup,
+            client_registration_start_result.message,
+            &[],
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ),
+        Err(ProtocolError::MigrationError)
+    ));
+    assert!(matches!(
+        server_login_finish_result
+            .finish_migration::<ServerAuthOnly>(client_registration_finish_result.message, &[],),
+        Err(ProtocolError::MigrationError)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "ristretto255")]
+#[test]
+fn test_migration() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let old_server_setup = ServerSetup::<P256>::new(&mut server_rng)?;
+    let new_server_setup = ServerSetup::<Ristretto255>::new(&mut server_rng)?;
+
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &old_server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+    // Login with the old cipher suite and migrate the record
+    let client_login_start_result =
+        ClientLogin::<P256>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &old_server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message.clone())?;
+
+    let client_migration_start_result = client_login_finish_result
+        .start_migration::<Ristretto255, _>(&mut client_rng, STR_PASSWORD.as_bytes())?;
+
+    // The migration messages are only accepted with the MAC of this login
+    assert!(matches!(
+        server_login_finish_result.start_migration(
+            &new_server_setup,
+            client_migration_start_result.message.clone(),
+            &[0; 32],
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ),
+        Err(ProtocolError::MigrationError)
+    ));
+    assert!(matches!(
+        server_login_finish_result.start_migration(
+            &new_server_setup,
+            client_migration_start_result.message.clone(),
+            &client_migration_start_result.mac[..16],
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ),
+        Err(ProtocolError::MigrationError)
+    ));
+    let server_migration_start_result = server_login_finish_result.start_migration(
+        &new_server_setup,
+        client_migration_start_result.message,
+        &client_migration_start_result.mac,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_migration_finish_result = client_migration_start_result.state.finish(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+        server_migration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let upload_mac =
+        client_login_finish_result.finish_migration(&client_migration_finish_result.message)?;
+    // The MAC of the request doesn't authenticate the upload
+    assert!(matches!(
+        server_login_finish_result.finish_migration(
+            client_migration_finish_result.message.clone(),
+            &client_migration_start_result.mac,
+        ),
+        Err(ProtocolError::MigrationError)
+    ));
+    let p_file = server_login_finish_result
+        .finish_migration(client_migration_finish_result.message.clone(), &upload_mac)?;
+
+    // Login with the new cipher suite
+    let client_login_start_result =
+        ClientLogin::<Ristretto255>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &new_server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(client_login_finish_result.message)?;
+
+    assert_eq!(
+        hex::encode(server_login_finish_result.session_key),
+        hex::encode(client_login_finish_result.session_key)
+    );
+    assert_eq!(
+        hex::encode(client_migration_finish_result.export_key),
+        hex::encode(client_login_finish_result.export_key)
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_enumeration_resistance() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        crate::test_utils::assert_enumeration_resistance::<CS, _>(
+            &mut client_rng,
+            |credential_request, credential_identifier| {
+                let password_file = (credential_identifier == STR_CREDENTIAL_IDENTIFIER.as_bytes())
+                    .then(|| p_file.clone());
+                ServerLogin::start(
+                    &mut server_rng,
+                    &server_setup,
+                    password_file,
+                    credential_request,
+                    credential_identifier,
+                    ServerLoginStartParameters::default(),
+                )
+                .unwrap()
+                .message
+            },
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            b"unregistered",
+            b"wrong password",
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_simulation() -> Result<(), ProtocolError> {
+    use crate::test_utils::{
+        simulate_login, simulate_registration, LoginTranscript, RegistrationTranscript,
+    };
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let server_setup = ServerSetup::<CS>::new(&mut OsRng)?;
+        let simulate = |seed| {
+            let registration = simulate_registration(
+                seed,
+                &server_setup,
+                STR_PASSWORD.as_bytes(),
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            let login = simulate_login(
+                seed,
+                &server_setup,
+                registration.password_file.clone(),
+                STR_PASSWORD.as_bytes(),
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            Ok::<_, ProtocolError>((registration, login))
+        };
+        let transcript =
+            |(registration, login): &(RegistrationTranscript<CS>, LoginTranscript<CS>)| {
+                [
+                    registration.registration_request.serialize().to_vec(),
+                    registration.registration_response.serialize().to_vec(),
+                    registration.registration_upload.serialize().to_vec(),
+                    registration.password_file.serialize().to_vec(),
+                    registration.export_key.to_vec(),
+                    login.credential_request.serialize().to_vec(),
+                    login.credential_response.serialize().to_vec(),
+                    login.credential_finalization.serialize().to_vec(),
+                    login.session_key.to_vec(),
+                    login.export_key.to_vec(),
+                ]
+            };
+
+        // The same seed reproduces the same run
+        let run = simulate([0; 32])?;
+        assert_eq!(transcript(&run), transcript(&simulate([0; 32])?));
+        assert_ne!(transcript(&run), transcript(&simulate([1; 32])?));
+
+        let (registration, login) = run;
+        assert_eq!(registration.export_key, login.export_key);
+        assert_eq!(
+            registration.server_s_pk.serialize(),
+            server_setup.public_key().serialize()
+        );
+        assert_eq!(
+            login.server_s_pk.serialize(),
+            registration.server_s_pk.serialize()
+        );
+        assert_eq!(
+            login.client_s_pk.as_ref().unwrap().serialize(),
+            registration
+                .registration_upload
+                .client_public_key()
+                .serialize()
+        );
+
+        assert!(matches!(
+            simulate_login(
+                [0; 32],
+                &server_setup,
+                registration.password_file,
+                b"wrong password",
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "fuzzing")]
+#[test]
+fn test_fuzzing() -> Result<(), ProtocolError> {
+    use rand::RngCore;
+
+    use crate::fuzzing::*;
+
+    type FuzzTarget = fn(&[u8]);
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let registration_request = client_registration_start_result.message.serialize();
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let registration_response = server_registration_start_result.message.serialize();
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let registration_upload = client_registration_finish_result.message.serialize();
+    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let credential_request = client_login_start_result.message.serialize();
+    let server_login_start_result = ServerLogin::<P256>::start(
+        &mut rng,
+        &server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let credential_response = server_login_start_result.message.serialize();
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let credential_finalization = client_login_finish_result.message.serialize();
+
+    let targets: [(FuzzTarget, &[u8]); 6] = [
+        (
+            fuzz_registration_request_roundtrip::<P256>,
+            &registration_request,
+        ),
+        (
+            fuzz_registration_response_roundtrip::<P256>,
+            &registration_response,
+        ),
+        (
+            fuzz_registration_upload_roundtrip::<P256>,
+            &registration_upload,
+        ),
+        (
+            fuzz_credential_request_roundtrip::<P256>,
+            &credential_request,
+        ),
+        (
+            fuzz_credential_response_roundtrip::<P256>,
+            &credential_response,
+        ),
+        (
+            fuzz_credential_finalization_roundtrip::<P256>,
+            &credential_finalization,
+        ),
+    ];
+
+    for (target, message) in targets {
+        target(message);
+
+        for len in 0..message.len() {
+            target(&message[..len]);
+        }
+
+        let mut extended = message.to_vec();
+        extended.push(0);
+        target(&extended);
+
+        let mut random = vec![0; message.len()];
+        rng.fill_bytes(&mut random);
+        target(&random);
+    }
+
+    let len = CredentialResponseLen::<P256>::USIZE;
+    for data in [vec![], vec![0; len], vec![0xff; len], vec![1]] {
+        fuzz_client_login_finish::<P256, _>(&mut rng, &data);
+        fuzz_server_login_finish::<P256, _>(&mut rng, &data);
+    }
+    for index in [0, len / 2, len - 1] {
+        let mut data = vec![0; len];
+        data[index] = 1;
+        fuzz_client_login_finish::<P256, _>(&mut rng, &data);
+        fuzz_server_login_finish::<P256, _>(&mut rng, &data);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "fuzzing")]
+#[test]
+fn test_fuzzing_corpus() -> Result<(), ProtocolError> {
+    use crate::fuzzing::*;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let corpus = corpus::<CS, _>(&mut OsRng);
+        assert_eq!(corpus.len(), 22);
+
+        for entry in &corpus {
+            let bytes = entry.bytes.as_slice();
+            let (deserialized, target): (_, fn(&[u8])) = match entry.message_type {
+                MessageType::RegistrationRequest => (
+                    RegistrationRequest::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_registration_request_roundtrip::<CS>,
+                ),
+                MessageType::RegistrationResponse => (
+                    RegistrationResponse::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_registration_response_roundtrip::<CS>,
+                ),
+                MessageType::RegistrationUpload => (
+                    RegistrationUpload::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_registration_upload_roundtrip::<CS>,
+                ),
+                MessageType::CredentialRequest => (
+                    CredentialRequest::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_credential_request_roundtrip::<CS>,
+                ),
+                MessageType::CredentialResponse => (
+                    CredentialResponse::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_credential_response_roundtrip::<CS>,
+                ),
+                MessageType::CredentialFinalization => (
+                    CredentialFinalization::<CS>::deserialize(bytes).is_ok(),
+                    fuzz_credential_finalization_roundtrip::<CS>,
+                ),
+            };
+
+            assert_eq!(
+                deserialized,
+                entry.variant == Variant::Valid,
+                "{}",
+                entry.file_name()
+            );
+            target(bytes);
+            assert_eq!(entry.messages_input()[0], entry.message_type as u8);
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "opaque-ke-corpus-{}",
+            rand::RngCore::next_u64(&mut OsRng)
+        ));
+        write_corpus::<CS, _>(&mut OsRng, &dir).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), corpus.len());
+        std::fs::remove_dir_all(dir).unwrap();
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "dudect")]
+#[test]
+fn test_dudect() {
+    use crate::dudect::check_suite;
+
+    let report = check_suite::<P256, _>(&mut OsRng, 10_000);
+    assert!(!report.leaks(), "{report:?}");
+}
+
+#[cfg(feature = "ct-audit")]
+#[test]
+fn test_ct_audit() -> Result<(), ProtocolError> {
+    use crate::ct_audit::check_suite;
+
+    // The backend is selected by `RUSTFLAGS`, not by a feature
+    #[cfg(any(feature = "ristretto255", feature = "curve25519"))]
+    #[allow(clippy::assertions_on_constants)]
+    {
+        assert!(
+            crate::ct_audit::CURVE25519_FIAT_BACKEND,
+            "run with RUSTFLAGS='--cfg curve25519_dalek_backend=\"fiat\"'"
+        );
+    }
+
+    check_suite::<P256, _>(&mut OsRng)?;
+    check_suite::<P256P384, _>(&mut OsRng)?;
+    #[cfg(feature = "ristretto255")]
+    check_suite::<Ristretto255, _>(&mut OsRng)?;
+    #[cfg(feature = "curve25519")]
+    check_suite::<Curve25519P256, _>(&mut OsRng)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio-codec")]
+#[test]
+fn test_tokio_codec() -> Result<(), ProtocolError> {
+    use bytes::{BufMut, BytesMut};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::codec::{CodecError, Message, MessageCodec};
+
+    let mut rng = OsRng;
+    let mut codec = MessageCodec::<P256>::new();
+    let mut buffer = BytesMut::new();
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let credential_request = client_login_start_result.message;
+
+    // A message is decoded once its frame is complete
+    codec
+        .encode(credential_request.clone(), &mut buffer)
+        .unwrap();
+    codec
+        .encode(
+            Message::CredentialRequest(credential_request.clone()),
+            &mut buffer,
+        )
+        .unwrap();
+    assert_eq!(
+        buffer.len(),
+        2 * (4 + 1 + CredentialRequestLen::<P256>::USIZE)
+    );
+    let mut partial = buffer.split_to(buffer.len() / 2 - 1);
+    assert!(codec.decode(&mut partial).unwrap().is_none());
+    partial.unsplit(buffer);
+    let mut buffer = partial;
+
+    for _ in 0..2 {
+        match codec.decode(&mut buffer).unwrap() {
+            Some(Message::CredentialRequest(message)) => {
+                assert_eq!(message.serialize(), credential_request.serialize())
+            }
+            _ => panic!("expected a credential request"),
+        }
+    }
+    assert!(buffer.is_empty());
+    assert!(codec.decode(&mut buffer).unwrap().is_none());
+
+    // Frames which are too long are rejected before they are buffered
+    buffer.put_u32(Message::<P256>::max_len() as u32 + 1);
+    assert!(matches!(codec.decode(&mut buffer), Err(CodecError::Io(_))));
+
+    // And frames with an unknown message type are rejected
+    let mut buffer = BytesMut::new();
+    buffer.put_u32(1);
+    buffer.put_u8(0);
+    assert!(matches!(
+        codec.decode(&mut buffer),
+        Err(CodecError::Protocol(ProtocolError::SerializationError))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "axum")]
+#[test]
+fn test_axum() -> Result<(), ProtocolError> {
+    use axum_core::body::Body;
+    use axum_core::extract::{FromRequest, Request};
+    use axum_core::response::IntoResponse;
+    use bytes::Bytes;
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+
+    use crate::web::{Opaque, OpaqueRejection, CONTENT_TYPE_OPAQUE};
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let body = client_login_start_result.message.serialize().to_vec();
+
+    let Opaque(credential_request) = block_on(Opaque::<CredentialRequest<P256>>::from_request(
+        Request::new(Body::from(body.clone())),
+        &(),
+    ))
+    .unwrap();
+    assert_eq!(credential_request.serialize().to_vec(), body);
+
+    // Bodies of the wrong length are rejected
+    let rejection = block_on(Opaque::<CredentialRequest<P256>>::from_request(
+        Request::new(Body::from(body[1..].to_vec())),
+        &(),
+    ))
+    .unwrap_err();
+    assert!(matches!(rejection, OpaqueRejection::Message(_)));
+    assert_eq!(rejection.into_response().status(), StatusCode::BAD_REQUEST);
+
+    // The response carries the serialized message
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        None,
+        credential_request,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let response = Opaque(server_login_start_result.message.clone()).into_response();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], CONTENT_TYPE_OPAQUE);
+    let body = block_on(Bytes::from_request(Request::new(response.into_body()), &())).unwrap();
+    assert_eq!(
+        body.to_vec(),
+        server_login_start_result.message.serialize().to_vec()
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "prost")]
+#[test]
+fn test_prost() -> Result<(), ProtocolError> {
+    use prost::Message;
+
+    use crate::proto;
+
+    // Sends the message over the wire and converts it back
+    fn roundtrip<M, P>(message: &M) -> Result<M, ProtocolError>
+    where
+        P: for<'a> From<&'a M> + Message + Default,
+        M: TryFrom<P, Error = ProtocolError>,
+    {
+        let bytes = P::from(message).encode_to_vec();
+        M::try_from(P::decode(bytes.as_slice()).unwrap())
+    }
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let message =
+        roundtrip::<_, proto::RegistrationRequest>(&client_registration_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        client_registration_start_result.message.serialize()
+    );
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let message =
+        roundtrip::<_, proto::RegistrationResponse>(&server_registration_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        server_registration_start_result.message.serialize()
+    );
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let message =
+        roundtrip::<_, proto::RegistrationUpload>(&client_registration_finish_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        client_registration_finish_result.message.serialize()
+    );
+    let password_file = ServerRegistration::finish(message);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let message = roundtrip::<_, proto::CredentialRequest>(&client_login_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        client_login_start_result.message.serialize()
+    );
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file.clone()),
+        message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let message = roundtrip::<_, proto::CredentialResponse>(&server_login_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        server_login_start_result.message.serialize()
+    );
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let message =
+        roundtrip::<_, proto::CredentialFinalization>(&client_login_finish_result.message)?;
+    let server_login_finish_result = server_login_start_result.state.finish(message)?;
+    assert_eq!(
+        client_login_finish_result.session_key,
+        server_login_finish_result.session_key
+    );
+
+    let client_retrieval_start_result =
+        ClientRetrieval::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let message = roundtrip::<_, proto::RetrievalRequest>(&client_retrieval_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        client_retrieval_start_result.message.serialize()
+    );
+    let server_retrieval_start_result = ServerRetrieval::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let message = roundtrip::<_, proto::RetrievalResponse>(&server_retrieval_start_result.message)?;
+    assert_eq!(
+        message.serialize(),
+        server_retrieval_start_result.message.serialize()
+    );
+
+    // Fields of the wrong length are rejected, even if the message as a whole
+    // has the right length
+    let mut message = proto::CredentialResponse::from(&server_login_start_result.message);
+    message
+        .ke2
+        .insert(0, message.masked_response.pop().unwrap());
+    assert!(matches!(
+        CredentialResponse::<P256>::try_from(message),
+        Err(ProtocolError::LibraryError(InternalError::SizeError {
+            name: "masked_response",
+            ..
+        }))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "uniffi")]
+#[test]
+fn test_uniffi() -> Result<(), ProtocolError> {
+    use crate::mobile::{
+        client_login_finish, client_login_start, client_registration_finish,
+        client_registration_start, BindingError,
+    };
+    use crate::suites::Ristretto255Sha512Argon2id;
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<Ristretto255Sha512Argon2id>::new(&mut rng)?;
+    let password = STR_PASSWORD.as_bytes().to_vec();
+
+    let registration_start = client_registration_start(password.clone()).unwrap();
+    let server_registration_start_result = ServerRegistration::<Ristretto255Sha512Argon2id>::start(
+        &server_setup,
+        RegistrationRequest::deserialize(&registration_start.message)?,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let registration_finish = client_registration_finish(
+        registration_start.state,
+        password.clone(),
+        server_registration_start_result
+            .message
+            .serialize()
+            .to_vec(),
+    )
+    .unwrap();
+    assert_eq!(
+        registration_finish.server_s_pk,
+        server_setup.keypair().public().serialize().to_vec()
+    );
+    let password_file = ServerRegistration::finish(RegistrationUpload::deserialize(
+        &registration_finish.message,
+    )?);
+
+    let login = |password: Vec<u8>| {
+        let login_start = client_login_start(password.clone()).unwrap();
+        let server_login_start_result = ServerLogin::start(
+            &mut OsRng,
+            &server_setup,
+            Some(password_file.clone()),
+            CredentialRequest::deserialize(&login_start.message)?,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let login_finish = client_login_finish(
+            login_start.state,
+            password,
+            server_login_start_result.message.serialize().to_vec(),
+        );
+        Ok::<_, ProtocolError>((login_finish, server_login_start_result.state))
+    };
+
+    let (login_finish, server_login) = login(password)?;
+    let login_finish = login_finish.unwrap();
+    let server_login_finish_result =
+        server_login.finish(CredentialFinalization::deserialize(&login_finish.message)?)?;
+    assert_eq!(
+        login_finish.session_key,
+        server_login_finish_result.session_key.to_vec()
+    );
+    assert_eq!(login_finish.export_key, registration_finish.export_key);
+
+    // A wrong password fails on the client
+    assert!(matches!(
+        login(b"wrong password".to_vec())?.0,
+        Err(BindingError::Protocol(ProtocolError::InvalidLoginError))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn test_python() -> Result<(), ProtocolError> {
+    use pyo3::prelude::*;
+    use pyo3::types::{PyBytes, PyModule};
+
+    use crate::suites::Ristretto255Sha512Argon2id;
+
+    type Suite = Ristretto255Sha512Argon2id;
+
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let module = PyModule::new(py, "opaque").unwrap();
+        crate::python::register(&module).unwrap();
+        let call = |name: &str, args: Vec<PyObject>| {
+            module
+                .getattr(name)
+                .unwrap()
+                .call1(pyo3::types::PyTuple::new(py, args).unwrap())
+        };
+        let bytes = |bytes: &[u8]| PyBytes::new(py, bytes).into_any().unbind();
+
+        let server_setup = module.getattr("ServerSetup").unwrap().call0().unwrap();
+        let server_setup = module
+            .getattr("ServerSetup")
+            .unwrap()
+            .call_method1(
+                "deserialize",
+                (server_setup.call_method0("serialize").unwrap(),),
+            )
+            .unwrap();
+
+        let mut rng = OsRng;
+        let client_registration_start_result =
+            ClientRegistration::<Suite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let response = call(
+            "server_registration_start",
+            vec![
+                server_setup.clone().unbind(),
+                bytes(&client_registration_start_result.message.serialize()),
+                bytes(STR_CREDENTIAL_IDENTIFIER.as_bytes()),
+            ],
+        )
+        .unwrap();
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            RegistrationResponse::deserialize(response.extract().unwrap())?,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let upload = client_registration_finish_result.message.serialize();
+        let password_file = call("server_registration_finish", vec![bytes(&upload)]).unwrap();
+
+        // Records are read in both formats
+        let record: Vec<u8> = password_file
+            .call_method0("serialize_versioned")
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert_eq!(record[1..], upload[..]);
+        let password_file = module
+            .getattr("ServerRegistration")
+            .unwrap()
+            .call_method1("deserialize", (bytes(&record),))
+            .unwrap();
+        let client_public_key: Vec<u8> = password_file
+            .call_method0("client_public_key")
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert_eq!(
+            client_public_key,
+            client_registration_finish_result
+                .message
+                .client_public_key()
+                .serialize()
+                .to_vec()
+        );
+        assert!(module
+            .getattr("ServerRegistration")
+            .unwrap()
+            .call_method1("deserialize", (bytes(&record[2..]),))
+            .unwrap_err()
+            .is_instance_of::<crate::python::OpaqueError>(py));
+
+        let client_login_start_result =
+            ClientLogin::<Suite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let (state, response): (Vec<u8>, Vec<u8>) = call(
+            "server_login_start",
+            vec![
+                server_setup.unbind(),
+                password_file.unbind(),
+                bytes(&client_login_start_result.message.serialize()),
+                bytes(STR_CREDENTIAL_IDENTIFIER.as_bytes()),
+            ],
+        )
+        .unwrap()
+        .extract()
+        .unwrap();
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            CredentialResponse::deserialize(&response)?,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let session_key: Vec<u8> = call(
+            "server_login_finish",
+            vec![
+                bytes(&state),
+                bytes(&client_login_finish_result.message.serialize()),
+            ],
+        )
+        .unwrap()
+        .extract()
+        .unwrap();
+        assert_eq!(session_key, client_login_finish_result.session_key.to_vec());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_ksf_policy() -> Result<(), ProtocolError> {
+    #[derive(Default)]
+    struct CostlyKsf;
+
+    impl Ksf for CostlyKsf {
+        fn hash<L: ArrayLength<u8>>(
+            &self,
+            input: GenericArray<u8, L>,
+        ) -> Result<GenericArray<u8, L>, InternalError> {
+            Ok(input)
+        }
+
+        fn parameters(&self) -> KsfParameters {
+            KsfParameters {
+                memory_cost: 64 * 1024,
+                time_cost: 3,
+                parallelism: 1,
+            }
+        }
+    }
+
+    struct AttestedKsf;
+
+    impl CipherSuite for AttestedKsf {
+        type OprfCs = p256::NistP256;
+        type KeGroup = p256::NistP256;
+        type KeyExchange = TripleDh;
+        type Ksf = CostlyKsf;
+        type AppDataLen = U0;
+
+        const ATTEST_KSF_PARAMETERS: bool = true;
+    }
+
+    fn register<CS: CipherSuite>(
+        server_setup: &ServerSetup<CS>,
+    ) -> Result<ClientRegistrationFinishResult<CS>, ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut rng = OsRng;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+    }
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let server_setup = ServerSetup::<CS>::new(&mut OsRng)?;
+        let client_registration_finish_result = register(&server_setup)?;
+
+        // Suites that don't attest their parameters can't satisfy any policy
+        assert_eq!(
+            client_registration_finish_result.message.ksf_parameters(),
+            None
+        );
+        assert!(matches!(
+            ServerRegistration::finish_with_ksf_policy(
+                client_registration_finish_result.message,
+                &KsfParameters::default(),
+            ),
+            Err(ProtocolError::KsfPolicyError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<AttestedKsf>::new(&mut rng)?;
+    let client_registration_finish_result = register(&server_setup)?;
+    let ksf_parameters =
+        KsfParameters::deserialize(&client_registration_finish_result.ksf_parameters.serialize())?;
+    assert_eq!(ksf_parameters, CostlyKsf.parameters());
+    assert_eq!(
+        client_registration_finish_result.message.ksf_parameters(),
+        Some(ksf_parameters)
+    );
+
+    let raised = KsfParameters {
+        time_cost: 4,
+        ..ksf_parameters
+    };
+    assert!(matches!(
+        ServerRegistration::finish_with_ksf_policy(
+            client_registration_finish_result.message.clone(),
+            &raised,
+        ),
+        Err(ProtocolError::KsfPolicyError)
+    ));
+
+    // The parameters are stored in the record
+    let password_file = ServerRegistration::finish_with_ksf_policy(
+        client_registration_finish_result.message.clone(),
+        &ksf_parameters,
+    )?;
+    let password_file = ServerRegistration::<AttestedKsf>::deserialize(&password_file.serialize())?;
+    assert_eq!(password_file.ksf_parameters(), Some(ksf_parameters));
+    assert!(!password_file.ksf_parameters().unwrap().satisfies(&raised));
+
+    let login = |password_file: ServerRegistration<AttestedKsf>| {
+        let client_login_start_result =
+            ClientLogin::<AttestedKsf>::start(&mut OsRng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut OsRng,
+            &server_setup,
+            Some(password_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        client_login_start_result
+            .state
+            .finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            )
+            .map(|_| ())
+    };
+    login(password_file)?;
+
+    // The envelope MAC covers the parameters, so raising them breaks the login
+    let mut upload = client_registration_finish_result.message.serialize();
+    let envelope_start = <<AttestedKsf as CipherSuite>::KeGroup as KeGroup>::PkLen::USIZE
+        + OutputSize::<OprfHash<AttestedKsf>>::USIZE;
+    let time_cost = envelope_start + 32 - 8;
+    upload[time_cost + 3] += 1;
+    let tampered = ServerRegistration::<AttestedKsf>::deserialize(&upload)?;
+    assert!(tampered.ksf_parameters().unwrap().satisfies(&raised));
+    assert!(matches!(
+        login(tampered),
+        Err(ProtocolError::InvalidLoginError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_envelope_nonce() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
+        let mut register = |envelope_nonce| -> Result<_, ProtocolError> {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = ServerRegistration::<CS>::start(
+                &server_setup,
+                client_registration_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            let client_registration_finish_result = client_registration_start_result.state.finish(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters {
+                    envelope_nonce,
+                    ..Default::default()
+                },
+            )?;
+
+            Ok((
+                hex::encode(client_registration_finish_result.message.serialize()),
+                hex::encode(client_registration_finish_result.export_key),
+            ))
+        };
+
+        // Retrying with the same nonce produces the same upload, despite a new
+        // blind
+        let nonce = GenericArray::from([1; 32]);
+        let first = register(Some(nonce))?;
+        assert_eq!(first, register(Some(nonce))?);
+        assert_ne!(first, register(Some(GenericArray::from([2; 32])))?);
+        assert_ne!(first, register(None)?);
+        assert_ne!(register(None)?, register(None)?);
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_registration_validation() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+
+        // Rejects client public keys which are registered already
+        let mut registered_keys = Vec::new();
+        let mut finish = |message: RegistrationUpload<CS>| {
+            ServerRegistration::finish_with_validation(message, |upload| {
+                let key = upload.client_public_key().serialize().to_vec();
+                if registered_keys.contains(&key) {
+                    return false;
+                }
+                registered_keys.push(key);
+                true
+            })
+        };
+
+        let password_file = finish(client_registration_finish_result.message.clone())?;
+        assert_eq!(
+            hex::encode(password_file.serialize()),
+            hex::encode(client_registration_finish_result.message.serialize())
+        );
+        assert!(matches!(
+            finish(client_registration_finish_result.message),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_record_log() -> Result<(), ProtocolError> {
+    use crate::driver::{Event, Message, ProtocolDriver};
+    use digest::Digest;
+
+    use crate::transparency::{record_commitment, MemoryRecordLog};
+    use crate::RECORD_VERSION;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+        // RetrievalResponse: CredentialResponseWithoutKeLen
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let credential_identifier = STR_CREDENTIAL_IDENTIFIER.as_bytes();
+        let register = |rng: &mut OsRng| -> Result<RegistrationUpload<CS>, ProtocolError> {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = ServerRegistration::<CS>::start(
+                &server_setup,
+                client_registration_start_result.message,
+                credential_identifier,
+            )?;
+            Ok(client_registration_start_result
+                .state
+                .finish(
+                    rng,
+                    STR_PASSWORD.as_bytes(),
+                    server_registration_start_result.message,
+                    ClientRegistrationFinishParameters::default(),
+                )?
+                .message)
+        };
+
+        // Every new password file is appended, in order
+        let mut log = MemoryRecordLog::new();
+        let password_file = ServerRegistration::finish_with_record_log(
+            register(&mut rng)?,
+            credential_identifier,
+            &mut log,
+        )?;
+        let newer_password_file = ServerRegistration::finish_with_record_log(
+            register(&mut rng)?,
+            credential_identifier,
+            &mut log,
+        )?;
+        assert_eq!(log.len(), 2);
+        for ((identifier, commitment), password_file) in log
+            .entries()
+            .iter()
+            .zip([&password_file, &newer_password_file])
+        {
+            assert_eq!(identifier, credential_identifier);
+            assert_eq!(
+                commitment.as_slice(),
+                record_commitment(credential_identifier, password_file)?.as_slice()
+            );
+        }
+
+        // The commitment is bound to the credential identifier
+        assert_ne!(
+            record_commitment(credential_identifier, &password_file)?,
+            record_commitment(b"other", &password_file)?
+        );
+
+        // The commitment is bound to the version and per-user OPRF key of the
+        // stored password file
+        let upload = register(&mut rng)?;
+        let password_file = ServerRegistration::<CS>::finish(upload.clone());
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let keyed_password_file =
+            ServerRegistration::<CS>::finish_with_oprf_key(upload.clone(), &oprf_key)?;
+        let other_oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let other_keyed_password_file =
+            ServerRegistration::<CS>::finish_with_oprf_key(upload, &other_oprf_key)?;
+        let commitment = record_commitment(credential_identifier, &password_file)?;
+        let keyed_commitment = record_commitment(credential_identifier, &keyed_password_file)?;
+        assert_ne!(commitment, keyed_commitment);
+        assert_ne!(
+            keyed_commitment,
+            record_commitment(credential_identifier, &other_keyed_password_file)?
+        );
+        let mut hasher = OprfHash::<CS>::new_with_prefix(b"OPAQUE-RecordCommitment");
+        hasher.update((credential_identifier.len() as u16).to_be_bytes());
+        hasher.update(credential_identifier);
+        hasher.update([RECORD_VERSION]);
+        hasher.update(password_file.serialize());
+        assert_eq!(commitment, hasher.finalize());
+
+        // A failing log rejects the password file
+        assert!(matches!(
+            ServerRegistration::finish_with_record_log(
+                register(&mut rng)?,
+                credential_identifier,
+                &mut |_: &[u8], _: &[u8]| false
+            ),
+            Err(ProtocolError::RecordLogError)
+        ));
+
+        // The driver appends the password file it reports
+        let mut client_buffer = vec![0; Message::<CS>::max_len()];
+        let mut server_buffer = vec![0; Message::<CS>::max_len()];
+        let mut log = MemoryRecordLog::new();
+        let (mut client, len) = ProtocolDriver::<CS>::start_registration(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &mut client_buffer,
+        )?;
+        let mut server = ProtocolDriver::server_with_record_log(
+            &server_setup,
+            credential_identifier,
+            None,
+            &mut log,
+        );
+        let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+        let step = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer)?;
+        let step = server.handle(&mut rng, &client_buffer[..step.len], &mut server_buffer)?;
+        let Some(Event::ServerRegistrationFinished(password_file)) = step.event else {
+            panic!("expected the server registration to finish")
+        };
+        drop(server);
+        assert_eq!(log.len(), 1);
+        assert_eq!(
+            log.entries()[0].1.as_slice(),
+            record_commitment(credential_identifier, &password_file)?.as_slice()
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_registration_token() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U40;
+
+    use crate::idempotency::{IdempotentRegistration, RegistrationToken, RegistrationTokenLen};
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // RegistrationToken: (IssuedAt + Nonce) + Hash
+        U40: Add<OutputSize<OprfHash<CS>>>,
+        RegistrationTokenLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let start = |rng: &mut OsRng| -> Result<_, ProtocolError> {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            Ok((
+                client_registration_start_result.message.clone(),
+                client_registration_start_result,
+            ))
+        };
+        let finish = |rng: &mut OsRng,
+                      client_registration_start_result: ClientRegistrationStartResult<CS>|
+         -> Result<RegistrationUpload<CS>, ProtocolError> {
+            let server_registration_start_result = ServerRegistration::<CS>::start(
+                &server_setup,
+                client_registration_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            Ok(client_registration_start_result
+                .state
+                .finish(
+                    rng,
+                    STR_PASSWORD.as_bytes(),
+                    server_registration_start_result.message,
+                    ClientRegistrationFinishParameters::default(),
+                )?
+                .message)
+        };
+
+        let (request, client_registration_start_result) = start(&mut rng)?;
+        let token = server_setup.registration_token(
+            &mut rng,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            &request,
+            1,
+        )?;
+        let token = RegistrationToken::<CS>::deserialize(&token.serialize())?;
+        assert_eq!(token.issued_at(), 1);
+        let upload = finish(&mut rng, client_registration_start_result)?;
+        let finish_with_token =
+            |request: &RegistrationRequest<CS>,
+             token: &RegistrationToken<CS>,
+             password_file: ServerRegistration<CS>,
+             previous: Option<(&RegistrationToken<CS>, &ServerRegistration<CS>)>| {
+                ServerRegistration::finish_with_token(
+                    &server_setup,
+                    STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                    request,
+                    token,
+                    password_file,
+                    previous,
+                )
+            };
+
+        // The first attempt is stored
+        let password_file = match finish_with_token(
+            &request,
+            &token,
+            ServerRegistration::finish(upload.clone()),
+            None,
+        )? {
+            IdempotentRegistration::Store(password_file) => password_file,
+            IdempotentRegistration::AlreadyStored => panic!("first attempt not stored"),
+        };
+        assert_eq!(
+            hex::encode(password_file.serialize()),
+            hex::encode(upload.serialize())
+        );
+
+        // Retrying the same upload stores nothing
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish(upload.clone()),
+                Some((&token, &password_file)),
+            ),
+            Ok(IdempotentRegistration::AlreadyStored)
+        ));
+
+        // A different upload under the same token conflicts
+        let (_, client_registration_start_result) = start(&mut rng)?;
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish(finish(&mut rng, client_registration_start_result)?),
+                Some((&token, &password_file)),
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        // The token only finishes the registration it was issued for
+        let (other_request, _) = start(&mut rng)?;
+        assert!(matches!(
+            finish_with_token(
+                &other_request,
+                &token,
+                ServerRegistration::finish(upload.clone()),
+                None,
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        // A newer attempt replaces the record, after which the older one is a
+        // replay
+        let (newer_request, client_registration_start_result) = start(&mut rng)?;
+        let newer_token = server_setup.registration_token(
+            &mut rng,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            &newer_request,
+            2,
+        )?;
+        let newer_upload = finish(&mut rng, client_registration_start_result)?;
+        let newer_password_file = match finish_with_token(
+            &newer_request,
+            &newer_token,
+            ServerRegistration::finish(newer_upload),
+            Some((&token, &password_file)),
+        )? {
+            IdempotentRegistration::Store(password_file) => password_file,
+            IdempotentRegistration::AlreadyStored => panic!("newer attempt not stored"),
+        };
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish(upload.clone()),
+                Some((&newer_token, &newer_password_file)),
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        // Keyed records are stored with their OPRF key, and a retry has to
+        // carry the same one
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let keyed_file = match finish_with_token(
+            &request,
+            &token,
+            ServerRegistration::finish_with_oprf_key(upload.clone(), &oprf_key)?,
+            None,
+        )? {
+            IdempotentRegistration::Store(password_file) => password_file,
+            IdempotentRegistration::AlreadyStored => panic!("keyed attempt not stored"),
+        };
+        assert_eq!(keyed_file.oprf_key(), Some(&oprf_key));
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish_with_oprf_key(upload.clone(), &oprf_key)?,
+                Some((&token, &keyed_file)),
+            ),
+            Ok(IdempotentRegistration::AlreadyStored)
+        ));
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish_with_oprf_key(
+                    upload.clone(),
+                    &ServerRegistration::<CS>::generate_oprf_key(&mut rng)?,
+                )?,
+                Some((&token, &keyed_file)),
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+        assert!(matches!(
+            finish_with_token(
+                &request,
+                &token,
+                ServerRegistration::finish(upload.clone()),
+                Some((&token, &keyed_file)),
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        // Tokens are bound to the server setup and credential identifier
+        assert!(matches!(
+            ServerRegistration::finish_with_token(
+                &server_setup,
+                b"other",
+                &request,
+                &token,
+                ServerRegistration::finish(upload.clone()),
+                None,
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+        assert!(matches!(
+            ServerRegistration::finish_with_token(
+                &ServerSetup::<CS>::new(&mut rng)?,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                &request,
+                &token,
+                ServerRegistration::finish(upload),
+                None,
+            ),
+            Err(ProtocolError::RegistrationRejected)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_registration_receipt() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U8;
+
+    use crate::receipt::{ReceiptPublicKey, RegistrationReceipt, RegistrationReceiptLen};
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // RegistrationReceipt: ((IssuedAt + Hash) + Elem) + Scalar
+        U8: Add<OutputSize<OprfHash<CS>>>,
+        Sum<U8, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ElemLen>,
+        Sum<Sum<U8, OutputSize<OprfHash<CS>>>, <OprfGroup<CS> as Group>::ElemLen>:
+            ArrayLength<u8> + Add<<OprfGroup<CS> as Group>::ScalarLen>,
+        RegistrationReceiptLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let register = |rng: &mut OsRng| -> Result<RegistrationUpload<CS>, ProtocolError> {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = ServerRegistration::<CS>::start(
+                &server_setup,
+                client_registration_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            Ok(client_registration_start_result
+                .state
+                .finish(
+                    rng,
+                    STR_PASSWORD.as_bytes(),
+                    server_registration_start_result.message,
+                    ClientRegistrationFinishParameters::default(),
+                )?
+                .message)
+        };
+
+        let upload = register(&mut rng)?;
+        let password_file = ServerRegistration::finish(upload.clone());
+        let receipt = server_setup.registration_receipt(
+            &mut rng,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            &password_file,
+            1,
+        )?;
+        let receipt = RegistrationReceipt::<CS>::deserialize(&receipt.serialize())?;
+        assert_eq!(receipt.issued_at(), 1);
+        let public_key =
+            ReceiptPublicKey::<CS>::deserialize(&server_setup.receipt_public_key()?.serialize())?;
+        assert_eq!(public_key, server_setup.receipt_public_key()?);
+
+        receipt.verify(&public_key, STR_CREDENTIAL_IDENTIFIER.as_bytes(), &upload)?;
+
+        // A substituted record, another credential identifier or another server
+        // fail verification
+        assert!(matches!(
+            receipt.verify(
+                &public_key,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                &register(&mut rng)?
+            ),
+            Err(ProtocolError::ReceiptError)
+        ));
+        assert!(matches!(
+            receipt.verify(&public_key, b"other", &upload),
+            Err(ProtocolError::ReceiptError)
+        ));
+        assert!(matches!(
+            receipt.verify(
+                &ServerSetup::<CS>::new(&mut rng)?.receipt_public_key()?,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                &upload
+            ),
+            Err(ProtocolError::ReceiptError)
+        ));
+
+        // Modifying the time the record was stored at invalidates the receipt
+        let mut serialized = receipt.serialize();
+        serialized[7] ^= 1;
+        assert!(matches!(
+            RegistrationReceipt::<CS>::deserialize(&serialized)?.verify(
+                &public_key,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                &upload
+            ),
+            Err(ProtocolError::ReceiptError)
+        ));
+        assert!(matches!(
+            RegistrationReceipt::<CS>::deserialize(&serialized[1..]),
+            Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_client_state_requests() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // ClientRegistration: KgSk + KgPk
+        <OprfGroup<CS> as Group>::ScalarLen: Add<<OprfGroup<CS> as Group>::ElemLen>,
+        ClientRegistrationLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // ClientLogin: KgSk + CredentialRequest + Ke1State
+        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
+        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
+            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
+        ClientLoginLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let state = ClientRegistration::<CS>::deserialize(
+            &client_registration_start_result.state.serialize(),
+        )?;
+        assert_eq!(
+            hex::encode(state.registration_request().serialize()),
+            hex::encode(client_registration_start_result.message.serialize())
+        );
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let state = ClientLogin::<CS>::deserialize(&client_login_start_result.state.serialize())?;
+        assert_eq!(
+            hex::encode(state.credential_request().serialize()),
+            hex::encode(client_login_start_result.message.serialize())
+        );
+
+        let client_retrieval_start_result =
+            ClientRetrieval::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let state =
+            ClientRetrieval::<CS>::deserialize(&client_retrieval_start_result.state.serialize())?;
+        assert_eq!(
+            hex::encode(state.retrieval_request().serialize()),
+            hex::encode(client_retrieval_start_result.message.serialize())
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_client_login_state_age() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U9;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // ClientLogin: KgSk + CredentialRequest + Ke1State
+        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
+        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
+            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
+        ClientLoginLen<CS>: ArrayLength<u8>,
+        // TimedClientLogin: ((Tag + CreatedAt) + ClientLogin) + Mac
+        U9: Add<ClientLoginLen<CS>>,
+        Sum<U9, ClientLoginLen<CS>>: ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+        TimedClientLoginLen<CS>: ArrayLength<u8>,
+    {
+        const STATE_KEY: &[u8] = b"state key";
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let mut login = |created_at: Option<u64>, max_state_age| -> Result<(), ProtocolError> {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let state = match created_at {
+                Some(created_at) => ClientLogin::<CS>::deserialize_timed(
+                    &client_login_start_result
+                        .state
+                        .serialize_timed(created_at, STATE_KEY)?,
+                    STATE_KEY,
+                )?,
+                None => {
+                    ClientLogin::<CS>::deserialize(&client_login_start_result.state.serialize())?
+                }
+            };
+            assert_eq!(state.created_at(), created_at);
+
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            state
+                .finish(
+                    STR_PASSWORD.as_bytes(),
+                    server_login_start_result.message,
+                    ClientLoginFinishParameters {
+                        max_state_age,
+                        ..Default::default()
+                    },
+                )
+                .map(|_| ())
+        };
+
+        login(None, None)?;
+        login(Some(100), None)?;
+        login(Some(100), Some((160, 60)))?;
+        assert!(matches!(
+            login(Some(100), Some((161, 60))),
+            Err(ProtocolError::ExpiredStateError)
+        ));
+        assert!(matches!(
+            login(None, Some((160, 60))),
+            Err(ProtocolError::ExpiredStateError)
+        ));
+
+        let timed = ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?
+            .state
+            .serialize_timed(100, STATE_KEY)?;
+
+        // The format tag is checked, and the timed format is only accepted
+        // with its state key
+        let mut modified = timed.clone();
+        modified[0] ^= 0xff;
+        assert!(matches!(
+            ClientLogin::<CS>::deserialize_timed(&modified, STATE_KEY),
+            Err(ProtocolError::SerializationError)
+        ));
+        assert!(ClientLogin::<CS>::deserialize(&timed).is_err());
+        assert!(matches!(
+            ClientLogin::<CS>::deserialize_timed(&timed, b"other state key"),
+            Err(ProtocolError::StateAuthenticationError)
+        ));
+
+        // The creation time can't be changed without the state key
+        let mut modified = timed.clone();
+        modified[8] ^= 0xff;
+        assert!(matches!(
+            ClientLogin::<CS>::deserialize_timed(&modified, STATE_KEY),
+            Err(ProtocolError::StateAuthenticationError)
+        ));
+        let mut modified = timed;
+        let last = modified.len() - 1;
+        modified[last] ^= 0xff;
+        assert!(matches!(
+            ClientLogin::<CS>::deserialize_timed(&modified, STATE_KEY),
+            Err(ProtocolError::StateAuthenticationError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_client_login_prepare() -> Result<(), ProtocolError> {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // ClientLogin: KgSk + CredentialRequest + Ke1State
+        <OprfGroup<CS> as Group>::ScalarLen: Add<CredentialRequestLen<CS>>,
+        Sum<<OprfGroup<CS> as Group>::ScalarLen, CredentialRequestLen<CS>>:
+            ArrayLength<u8> + Add<Ke1StateLen<CS>>,
+        ClientLoginLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+
+        // Preparing a login draws the same randomness as starting it
+        let started = ClientLogin::<CS>::start(&mut StdRng::from_seed([0; 32]), b"password")?;
+        let prepared = ClientLogin::<CS>::prepare(&mut StdRng::from_seed([0; 32]))?;
+        let prepared_start_result = prepared.start(b"password")?;
+        assert_eq!(
+            started.message.serialize(),
+            prepared_start_result.message.serialize()
+        );
+        assert_eq!(
+            started.state.serialize(),
+            prepared_start_result.state.serialize()
+        );
+
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        // Each login takes its own prepared randomness from a pool
+        let mut pool = (0..2)
+            .map(|_| ClientLogin::<CS>::prepare(&mut rng))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut login = |password: &[u8]| -> Result<_, ProtocolError> {
+            let client_login_start_result = pool.pop().unwrap().start(password)?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            let client_login_finish_result = client_login_start_result.state.finish(
+                password,
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            )?;
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+            assert_eq!(
+                client_login_finish_result.session_key,
+                server_login_finish_result.session_key
+            );
+
+            Ok(client_login_finish_result.export_key)
+        };
+
+        assert_eq!(
+            login(STR_PASSWORD.as_bytes())?,
+            client_registration_finish_result.export_key
+        );
+        assert!(matches!(
+            login(b"wrong password"),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_server_identity_verifier() -> Result<(), ProtocolError> {
+    use crate::keypair::ServerIdentityVerifier;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        const CERTIFICATE: &[u8] = b"certificate";
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let server_public_key = server_setup.public_key().serialize();
+
+        let register = |rng: &mut OsRng, server: Option<&[u8]>| {
+            let identifiers = Identifiers {
+                client: None,
+                server,
+            };
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = ServerRegistration::<CS>::start(
+                &server_setup,
+                client_registration_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            let client_registration_finish_result = client_registration_start_result.state.finish(
+                rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::new(identifiers, None),
+            )?;
+            Ok::<_, ProtocolError>(ServerRegistration::finish(
+                client_registration_finish_result.message,
+            ))
+        };
+        let login = |rng: &mut OsRng,
+                     password_file: ServerRegistration<CS>,
+                     server: Option<&[u8]>,
+                     verifier: &dyn ServerIdentityVerifier<CS::KeGroup>| {
+            let identifiers = Identifiers {
+                client: None,
+                server,
+            };
+            let client_login_start_result = ClientLogin::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                rng,
+                &server_setup,
+                Some(password_file),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters {
+                    identifiers,
+                    ..Default::default()
+                },
+            )?;
+            client_login_start_result
+                .state
+                .finish(
+                    STR_PASSWORD.as_bytes(),
+                    server_login_start_result.message,
+                    ClientLoginFinishParameters {
+                        identifiers,
+                        server_identity_verifier: Some(verifier),
+                        ..Default::default()
+                    },
+                )
+                .map(|result| result.server_identity)
+        };
+
+        let verifier = |identity: &[u8], key: &PublicKey<CS::KeGroup>| {
+            identity == CERTIFICATE && key.serialize() == server_public_key
+        };
+        let password_file = register(&mut rng, Some(CERTIFICATE))?;
+        assert_eq!(
+            login(
+                &mut rng,
+                password_file.clone(),
+                Some(CERTIFICATE),
+                &verifier,
+            )?,
+            ServerIdentity::Supplied
+        );
+        assert!(matches!(
+            login(
+                &mut rng,
+                password_file,
+                Some(CERTIFICATE),
+                &|_: &[u8], _: &_| false
+            ),
+            Err(ProtocolError::ServerIdentityError)
+        ));
+
+        // Without a server identifier the verifier sees the server public key
+        let password_file = register(&mut rng, None)?;
+        let verifier = |identity: &[u8], key: &PublicKey<CS::KeGroup>| {
+            identity == server_public_key.as_slice() && key.serialize() == server_public_key
+        };
+        assert_eq!(
+            login(&mut rng, password_file, None, &verifier)?,
+            ServerIdentity::PublicKey
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_keypair_derive_from_seed() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let seed = [1; 32];
+        let keypair = KeyPair::derive_from_seed::<CS>(&seed, b"info")?;
+        let server_setup = ServerSetup::<CS>::new_with_key(&mut OsRng, keypair.clone())?;
+        assert_eq!(
+            hex::encode(server_setup.public_key().serialize()),
+            hex::encode(keypair.public().serialize())
+        );
+
+        // Deterministic, but independent for different seeds and infos
+        let serialize = |keypair: KeyPair<CS::KeGroup>| hex::encode(keypair.private().serialize());
+        assert_eq!(
+            serialize(KeyPair::derive_from_seed::<CS>(&seed, b"info")?),
+            serialize(keypair.clone())
+        );
+        assert_ne!(
+            serialize(KeyPair::derive_from_seed::<CS>(&seed, b"other info")?),
+            serialize(keypair.clone())
+        );
+        assert_ne!(
+            serialize(KeyPair::derive_from_seed::<CS>(&[2; 32], b"info")?),
+            serialize(keypair)
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_server_setup_deserialize_with_keypair() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // ServerSetup: Hash + KeSk + KeSk
+        OutputSize<OprfHash<CS>>: Add<<CS::KeGroup as KeGroup>::SkLen>,
+        Sum<OutputSize<OprfHash<CS>>, <CS::KeGroup as KeGroup>::SkLen>:
+            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+        ServerSetupLen<CS, PrivateKey<CS::KeGroup>>: ArrayLength<u8>,
+    {
+        let server_setup = ServerSetup::<CS>::new(&mut OsRng)?;
+        let serialized = server_setup.serialize();
+
+        let restored = ServerSetup::<CS>::deserialize_with_keypair(
+            &serialized,
+            server_setup.keypair().clone(),
+        )?;
+        assert_eq!(hex::encode(restored.serialize()), hex::encode(&serialized));
+
+        // The serialized private key is ignored
+        let keypair = KeyPair::<CS::KeGroup>::generate_random::<CS::OprfCs, _>(&mut OsRng)?;
+        let restored = ServerSetup::<CS>::deserialize_with_keypair(&serialized, keypair.clone())?;
+        assert_eq!(
+            hex::encode(restored.public_key().serialize()),
+            hex::encode(keypair.public().serialize())
+        );
+
+        assert!(matches!(
+            ServerSetup::<CS>::deserialize_with_keypair(&serialized[1..], keypair),
+            Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "keystore")]
+#[test]
+fn test_keystore() -> Result<(), ProtocolError> {
+    use crate::keystore;
+
+    // Generic over the key, as bounds on the length of a `PrivateKey` can't be
+    // normalized here
+    fn inner<CS: CipherSuite, S: SecretKey<CS::KeGroup>>(
+        server_setup: ServerSetup<CS, S>,
+    ) -> Result<(), ProtocolError<S::Error>>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // ServerSetup: Hash + KeSk + KeSk
+        OutputSize<OprfHash<CS>>: Add<S::Len>,
+        Sum<OutputSize<OprfHash<CS>>, S::Len>:
+            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+        ServerSetupLen<CS, S>: ArrayLength<u8>,
+        // SealedServerSetup: (ServerSetup + Salt) + Hash
+        ServerSetupLen<CS, S>: Add<OutputSize<OprfHash<CS>>>,
+        Sum<ServerSetupLen<CS, S>, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+        keystore::SealedServerSetupLen<CS, S>: ArrayLength<u8>,
+    {
+        const PASSPHRASE: &[u8] = b"passphrase";
+        const NEW_PASSPHRASE: &[u8] = b"new passphrase";
+
+        let sealed = keystore::seal(&mut OsRng, &server_setup, PASSPHRASE, None)
+            .map_err(ProtocolError::into_custom)?;
+        let opened = keystore::open::<CS, S>(&sealed, PASSPHRASE, None)?;
+        assert_eq!(
+            hex::encode(opened.serialize()),
+            hex::encode(server_setup.serialize())
+        );
+
+        // Salted with fresh randomness
+        assert_ne!(
+            hex::encode(&sealed),
+            hex::encode(
+                keystore::seal(&mut OsRng, &server_setup, PASSPHRASE, None)
+                    .map_err(ProtocolError::into_custom)?
+            )
+        );
+
+        assert!(matches!(
+            keystore::open::<CS, S>(&sealed, NEW_PASSPHRASE, None),
+            Err(ProtocolError::KeystoreError)
+        ));
+        for index in [0, sealed.len() - 64, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 1;
+            assert!(matches!(
+                keystore::open::<CS, S>(&tampered, PASSPHRASE, None),
+                Err(ProtocolError::KeystoreError)
+            ));
+        }
+        assert!(matches!(
+            keystore::open::<CS, S>(&sealed[1..], PASSPHRASE, None),
+            Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
+        ));
+
+        let rotated =
+            keystore::rotate::<CS, S, _>(&mut OsRng, &sealed, PASSPHRASE, NEW_PASSPHRASE, None)?;
+        assert!(matches!(
+            keystore::open::<CS, S>(&rotated, PASSPHRASE, None),
+            Err(ProtocolError::KeystoreError)
+        ));
+        let opened = keystore::open::<CS, S>(&rotated, NEW_PASSPHRASE, None)?;
+        assert_eq!(
+            hex::encode(opened.serialize()),
+            hex::encode(server_setup.serialize())
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "ristretto255")]
+    inner(ServerSetup::<Ristretto255>::new(&mut OsRng)?)?;
+    inner(ServerSetup::<P256>::new(&mut OsRng)?)?;
+    inner(ServerSetup::<P384>::new(&mut OsRng)?)?;
+    inner(ServerSetup::<P521>::new(&mut OsRng)?)?;
+    #[cfg(feature = "curve25519")]
+    inner(ServerSetup::<Curve25519P256>::new(&mut OsRng)?)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_key_validation_policy() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let other_server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let registration_response = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?
+        .message
+        .serialize();
+
+        // The client pins the public key of the server
+        let pinned = |public_key: &PublicKey<CS::KeGroup>| {
+            public_key.serialize() == server_setup.public_key().serialize()
+        };
+        let registration_response =
+            RegistrationResponse::<CS>::deserialize_with_policy(&registration_response, &pinned)?;
+        let other_registration_response = ServerRegistration::<CS>::start(
+            &other_server_setup,
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?
+        .message
+        .serialize();
+        assert!(matches!(
+            RegistrationResponse::<CS>::deserialize_with_policy(
+                &other_registration_response,
+                &pinned
+            ),
+            Err(ProtocolError::KeyValidationError)
+        ));
+
+        let registration_upload = client_registration_start_result
+            .state
+            .finish(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                registration_response,
+                ClientRegistrationFinishParameters::default(),
+            )?
+            .message
+            .serialize();
+
+        // The server rejects its own public key as the client's
+        let distinct = |public_key: &PublicKey<CS::KeGroup>| {
+            public_key.serialize() != server_setup.public_key().serialize()
+        };
+        RegistrationUpload::<CS>::deserialize_with_policy(&registration_upload, &distinct)?;
+        let mut reflected_upload = registration_upload.to_vec();
+        reflected_upload[..<CS::KeGroup as KeGroup>::PkLen::USIZE]
+            .copy_from_slice(&server_setup.public_key().serialize());
+        assert!(matches!(
+            RegistrationUpload::<CS>::deserialize_with_policy(&reflected_upload, &distinct),
+            Err(ProtocolError::KeyValidationError)
+        ));
+        RegistrationUpload::<CS>::deserialize(&reflected_upload)?;
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_record_batch() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let mut records = Vec::new();
+        for credential_identifier in [b"".as_slice(), b"alice", b"bob", b"carol"] {
+            let keyed = credential_identifier == b"carol";
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = if keyed {
+                ServerRegistration::<CS>::start_with_oprf_key(
+                    &server_setup,
+                    client_registration_start_result.message,
+                    &oprf_key,
+                )?
+            } else {
+                ServerRegistration::<CS>::start(
+                    &server_setup,
+                    client_registration_start_result.message,
+                    credential_identifier,
+                )?
+            };
+            let client_registration_finish_result = client_registration_start_result.state.finish(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+            )?;
+            let record = if keyed {
+                ServerRegistration::finish_with_oprf_key(
+                    client_registration_finish_result.message,
+                    &oprf_key,
+                )?
+            } else {
+                ServerRegistration::finish(client_registration_finish_result.message)
+            };
+            records.push((credential_identifier, record));
+        }
+
+        let mut output = vec![0; ServerRegistration::batch_len(&records)];
+        assert_eq!(
+            ServerRegistration::serialize_batch(&records, &mut output)?,
+            output.len()
+        );
+
+        let batch = ServerRegistration::<CS>::deserialize_batch(&output)?;
+        assert_eq!(batch.len(), records.len());
+        for (result, (credential_identifier, record)) in batch.zip(&records) {
+            let (batch_identifier, batch_record) = result?;
+            assert_eq!(batch_identifier, *credential_identifier);
+            assert_eq!(
+                hex::encode(batch_record.serialize()),
+                hex::encode(record.serialize())
+            );
+            assert_eq!(batch_record.oprf_key(), record.oprf_key());
+
+            // Imported records, including the keyed one, can still log in
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(batch_record),
+                client_login_start_result.message,
+                batch_identifier,
+                ServerLoginStartParameters::default(),
+            )?;
+            let client_login_finish_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            )?;
+            server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+        }
+
+        // Corruption and truncation fail the checksum
+        for index in [0, output.len() / 2, output.len() - 1] {
+            let mut corrupted = output.clone();
+            corrupted[index] ^= 1;
+            assert!(matches!(
+                ServerRegistration::<CS>::deserialize_batch(&corrupted),
+                Err(ProtocolError::SerializationError)
+            ));
+        }
+        assert!(matches!(
+            ServerRegistration::<CS>::deserialize_batch(&output[..output.len() - 1]),
+            Err(ProtocolError::SerializationError)
+        ));
+
+        // Output buffers which are too small are rejected
+        assert!(matches!(
+            ServerRegistration::serialize_batch(&records, &mut output[1..]),
+            Err(ProtocolError::LibraryError(InternalError::SizeError {
+                name: "output",
+                ..
+            }))
+        ));
+
+        let mut output = vec![0; ServerRegistration::<CS>::batch_len(&[])];
+        ServerRegistration::<CS>::serialize_batch(&[], &mut output)?;
+        assert_eq!(
+            ServerRegistration::<CS>::deserialize_batch(&output)?.count(),
+            0
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_versioned_record() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U1;
+
+    use crate::{VersionedServerRegistrationLen, RECORD_VERSION};
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // VersionedServerRegistration: Version + ServerRegistration
+        U1: Add<ServerRegistrationLen<CS>>,
+        VersionedServerRegistrationLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let serialized = hex::encode(password_file.serialize());
+
+        let versioned = password_file.serialize_versioned();
+        assert_eq!(versioned[0], RECORD_VERSION);
+        assert_eq!(hex::encode(&versioned[1..]), serialized);
+
+        let no_upgrade = |_: &[u8]| Err(ProtocolError::SerializationError);
+
+        let result = ServerRegistration::<CS>::deserialize_versioned(&versioned, no_upgrade)?;
+        assert!(!result.upgraded);
+        assert_eq!(hex::encode(result.record.serialize()), serialized);
+
+        // Unversioned records are read as well
+        let result = ServerRegistration::<CS>::deserialize_versioned(
+            &password_file.serialize(),
+            no_upgrade,
+        )?;
+        assert!(result.upgraded);
+        assert_eq!(hex::encode(result.record.serialize()), serialized);
+
+        // Layouts of the application are passed to the upgrade callback
+        let mut legacy = b"legacy".to_vec();
+        legacy.extend_from_slice(&password_file.serialize());
+        let result = ServerRegistration::<CS>::deserialize_versioned(&legacy, |input| {
+            ServerRegistration::deserialize(input.strip_prefix(b"legacy").unwrap())
+        })?;
+        assert!(result.upgraded);
+        assert_eq!(hex::encode(result.record.serialize()), serialized);
+
+        // Unknown versions are not mistaken for the current one
+        let mut unknown = versioned.to_vec();
+        unknown[0] = RECORD_VERSION + 1;
+        assert!(matches!(
+            ServerRegistration::<CS>::deserialize_versioned(&unknown, no_upgrade),
+            Err(ProtocolError::SerializationError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "argon2", feature = "ristretto255-voprf"))]
+#[test]
+fn test_suites() -> Result<(), ProtocolError> {
+    test_complete_flow::<crate::suites::Default>("", b"good password", b"good password")?;
+    test_complete_flow::<crate::suites::Default>("", b"good password", b"bad password")?;
+    #[cfg(feature = "curve25519")]
+    test_complete_flow::<crate::suites::Ristretto255Curve25519Sha512Argon2id>(
+        "",
+        b"good password",
+        b"good password",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_ksf_progress() -> Result<(), ProtocolError> {
+    use core::cell::Cell;
+
+    use crate::ksf::KsfProgress;
+
+    #[derive(Default)]
+    struct Progress {
+        reports: Cell<u32>,
+        cancelled: bool,
+    }
+
+    impl KsfProgress for Progress {
+        fn report(&self, completed: u32, total: u32) {
+            assert!(completed <= total);
+            self.reports.set(self.reports.get() + 1);
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.cancelled
+        }
+    }
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let progress = Progress::default();
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters {
+                ksf_progress: Some(&progress),
+                ..Default::default()
+            },
+        )?;
+        assert_ne!(progress.reports.get(), 0);
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let progress = Progress {
+            cancelled: true,
+            ..Default::default()
+        };
+        let client_login_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters {
+                ksf_progress: Some(&progress),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            client_login_result,
+            Err(ProtocolError::LibraryError(
+                InternalError::KsfCancelledError
+            ))
+        ));
+        assert_eq!(progress.reports.get(), 0);
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_finish_async() -> Result<(), ProtocolError> {
+    use core::cell::Cell;
+
+    use crate::ksf::ChunkedKsf;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        CS::Ksf: ChunkedKsf,
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let yields = Cell::new(0);
+        let yield_now = || {
+            yields.set(yields.get() + 1);
+            core::future::ready(())
+        };
+
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result =
+            block_on(client_registration_start_result.state.finish_async(
+                &mut client_rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+                yield_now,
+            ))?;
+        assert_eq!(yields.get(), 1);
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        // The asynchronous and synchronous logins open the same envelope
+        for asynchronous in [false, true] {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut server_rng,
+                &server_setup,
+                Some(p_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            let state = client_login_start_result.state;
+            let message = server_login_start_result.message;
+            let client_login_finish_result = if asynchronous {
+                block_on(state.finish_async(
+                    STR_PASSWORD.as_bytes(),
+                    message,
+                    ClientLoginFinishParameters::default(),
+                    yield_now,
+                ))?
+            } else {
+                state.finish(
+                    STR_PASSWORD.as_bytes(),
+                    message,
+                    ClientLoginFinishParameters::default(),
+                )?
+            };
+            let server_login_finish_result = server_login_start_result
+                .state
+                .finish(client_login_finish_result.message)?;
+
+            assert_eq!(
+                hex::encode(&client_registration_finish_result.export_key),
+                hex::encode(client_login_finish_result.export_key)
+            );
+            assert_eq!(
+                hex::encode(server_login_finish_result.session_key),
+                hex::encode(client_login_finish_result.session_key)
+            );
+        }
+        assert_eq!(yields.get(), 2);
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[cfg(feature = "argon2")]
+#[test]
+fn test_chunked_argon2() -> Result<(), ProtocolError> {
+    use core::cell::Cell;
+
+    use generic_array::typenum::U64;
+
+    use crate::ksf::{ChunkedArgon2, Ksf};
+
+    let argon2 = argon2::Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2::Params::new(8, 1, 1, None).unwrap(),
+    );
+    let input = GenericArray::<u8, U64>::default();
+
+    // A single chunk is the inner Argon2, more chunks chain it
+    assert_eq!(
+        ChunkedArgon2::new(argon2.clone(), 1)?.hash(input)?,
+        argon2.hash(input)?
+    );
+    let chunked_argon2 = ChunkedArgon2::new(argon2.clone(), 3)?;
+    assert_eq!(
+        chunked_argon2.hash(input)?,
+        argon2.hash(argon2.hash(argon2.hash(input)?)?)?
+    );
+    assert_eq!(chunked_argon2.parameters().time_cost, 3);
+    assert!(matches!(
+        ChunkedArgon2::new(argon2, 0),
+        Err(InternalError::KsfError)
+    ));
+
+    #[cfg(feature = "ristretto255")]
+    {
+        struct ChunkedSuite;
+
+        impl CipherSuite for ChunkedSuite {
+            type OprfCs = crate::Ristretto255;
+            type KeGroup = crate::Ristretto255;
+            type KeyExchange = TripleDh;
+            type Ksf = ChunkedArgon2;
+            type AppDataLen = U0;
+        }
+
+        let yields = Cell::new(0);
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<ChunkedSuite>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<ChunkedSuite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<ChunkedSuite>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result =
+            block_on(client_registration_start_result.state.finish_async(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters {
+                    ksf: Some(&chunked_argon2),
+                    ..Default::default()
+                },
+                || {
+                    yields.set(yields.get() + 1);
+                    core::future::ready(())
+                },
+            ))?;
+        assert_eq!(yields.get(), 3);
+        assert_eq!(
+            client_registration_finish_result.ksf_parameters.time_cost,
+            3
+        );
+
+        // Logging in synchronously chains the same chunks
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let client_login_start_result =
+            ClientLogin::<ChunkedSuite>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters {
+                ksf: Some(&chunked_argon2),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            hex::encode(client_registration_finish_result.export_key),
+            hex::encode(client_login_finish_result.export_key)
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "argon2", feature = "std"))]
+#[test]
+fn test_calibrate_argon2() -> Result<(), ProtocolError> {
+    use std::time::Duration;
+
+    let params = crate::ksf::calibrate_argon2(Duration::from_millis(50), 1024, 1)?;
+    assert_eq!(params.m_cost(), 1024);
+    assert_eq!(params.p_cost(), 1);
+    assert!(params.t_cost() >= 1);
+
+    // A target below the cost of a single pass still yields a valid time cost
+    let params = crate::ksf::calibrate_argon2(Duration::ZERO, 1024, 1)?;
+    assert_eq!(params.t_cost(), 1);
+
+    assert!(matches!(
+        crate::ksf::calibrate_argon2(Duration::from_millis(50), 1024, 0),
+        Err(InternalError::KsfError)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "password-hash")]
+#[test]
+fn test_argon2_phc_string() -> Result<(), ProtocolError> {
+    use crate::ksf::{argon2_from_phc_string, argon2_to_phc_string, Ksf};
+
+    let phc = "$argon2id$v=19$m=65536,t=2,p=1";
+    let argon2 = argon2_from_phc_string(phc)?;
+    assert_eq!(
+        argon2.parameters(),
+        KsfParameters {
+            memory_cost: 65536,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    );
+    assert_eq!(
+        argon2_to_phc_string(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            argon2.params()
+        )?,
+        phc
+    );
+
+    // Salt and hash are ignored
+    let argon2 =
+        argon2_from_phc_string("$argon2i$v=19$m=4096,t=3,p=1$c2FsdHNhbHQ$dGhpc2lzYWhhc2hvdXRwdXQ")?;
+    assert_eq!(argon2.params().m_cost(), 4096);
+    assert_eq!(argon2.params().t_cost(), 3);
+
+    for invalid in ["", "$scrypt$ln=15,r=8,p=1", "$argon2id$v=19$m=abc"] {
+        assert!(matches!(
+            argon2_from_phc_string(invalid),
+            Err(InternalError::KsfError)
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_retrieval() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // ClientRetrieval: KgSk + KgPk
+        <OprfGroup<CS> as Group>::ScalarLen: Add<<OprfGroup<CS> as Group>::ElemLen>,
+        ClientRetrievalLen<CS>: ArrayLength<u8>,
+        // RetrievalResponse: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let retrieve = |rng: &mut OsRng,
+                        password: &[u8],
+                        password_file: Option<ServerRegistration<CS>>,
+                        oprf_key: Option<&[u8]>|
+         -> Result<ClientRetrievalFinishResult<CS>, ProtocolError> {
+            let client_retrieval_start_result = ClientRetrieval::<CS>::start(rng, password)?;
+            let request =
+                RetrievalRequest::deserialize(&client_retrieval_start_result.message.serialize())?;
+            let server_retrieval_start_result = match oprf_key {
+                Some(oprf_key) => ServerRetrieval::start_with_oprf_key(
+                    rng,
+                    &server_setup,
+                    password_file,
+                    request,
+                    STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                    oprf_key,
+                )?,
+                None => ServerRetrieval::start(
+                    rng,
+                    &server_setup,
+                    password_file,
+                    request,
+                    STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                )?,
+            };
+            let response =
+                RetrievalResponse::deserialize(&server_retrieval_start_result.message.serialize())?;
+            let state = ClientRetrieval::<CS>::deserialize(
+                &client_retrieval_start_result.state.serialize(),
+            )?;
+            state.finish(
+                password,
+                response,
+                ClientRetrievalFinishParameters::default(),
+            )
+        };
+
+        let client_retrieval_finish_result = retrieve(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            Some(password_file.clone()),
+            None,
+        )?;
+        assert_eq!(
+            hex::encode(client_registration_finish_result.export_key),
+            hex::encode(client_retrieval_finish_result.export_key)
+        );
+        assert_eq!(
+            hex::encode(client_registration_finish_result.randomized_pwd),
+            hex::encode(client_retrieval_finish_result.randomized_pwd)
+        );
+        assert_eq!(
+            client_retrieval_finish_result.server_s_pk.serialize(),
+            server_setup.public_key().serialize()
+        );
+
+        assert!(matches!(
+            retrieve(&mut rng, b"wrong password", Some(password_file), None),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+        assert!(matches!(
+            retrieve(&mut rng, STR_PASSWORD.as_bytes(), None, None),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        // Register with an OPRF key that is not derived from the OPRF seed
+        let register = |rng: &mut OsRng,
+                        oprf_key: &[u8]|
+         -> Result<
+            (ClientRegistrationFinishResult<CS>, RegistrationUpload<CS>),
+            ProtocolError,
+        > {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(rng, STR_PASSWORD.as_bytes())?;
+            let server_registration_start_result = ServerRegistration::<CS>::start_with_oprf_key(
+                &server_setup,
+                client_registration_start_result.message,
+                oprf_key,
+            )?;
+            let client_registration_finish_result = client_registration_start_result.state.finish(
+                rng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+            )?;
+            let upload = client_registration_finish_result.message.clone();
+            Ok((client_registration_finish_result, upload))
+        };
+
+        // The key stored in a keyed record is used, even over a key passed by
+        // the caller
+        let oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        let (client_registration_finish_result, upload) = register(&mut rng, &oprf_key)?;
+        let password_file = ServerRegistration::finish_with_oprf_key(upload, &oprf_key)?;
+        let other_oprf_key = ServerRegistration::<CS>::generate_oprf_key(&mut rng)?;
+        for parameter_key in [None, Some(other_oprf_key.as_slice())] {
+            let client_retrieval_finish_result = retrieve(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+                Some(password_file.clone()),
+                parameter_key,
+            )?;
+            assert_eq!(
+                hex::encode(&client_registration_finish_result.export_key),
+                hex::encode(client_retrieval_finish_result.export_key)
+            );
+        }
+
+        // A recomputed key is passed by the caller for a plain record
+        let oprf_key =
+            server_setup.peppered_oprf_key(STR_CREDENTIAL_IDENTIFIER.as_bytes(), b"pepper")?;
+        let (client_registration_finish_result, upload) = register(&mut rng, &oprf_key)?;
+        let password_file = ServerRegistration::finish(upload);
+        let client_retrieval_finish_result = retrieve(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            Some(password_file.clone()),
+            Some(&oprf_key),
+        )?;
+        assert_eq!(
+            hex::encode(client_registration_finish_result.export_key),
+            hex::encode(client_retrieval_finish_result.export_key)
+        );
+        assert!(matches!(
+            retrieve(&mut rng, STR_PASSWORD.as_bytes(), Some(password_file), None),
+            Err(ProtocolError::InvalidLoginError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_message_sizes() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let sizes = max_message_sizes::<CS>();
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let message = &client_registration_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.registration_request);
+        assert_eq!(
+            message.serialized_len(),
+            RegistrationRequestLen::<CS>::USIZE
+        );
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let message = &server_registration_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.registration_response);
+        assert_eq!(
+            message.serialized_len(),
+            RegistrationResponseLen::<CS>::USIZE
+        );
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let message = &client_registration_finish_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.registration_upload);
+        assert_eq!(message.serialized_len(), RegistrationUploadLen::<CS>::USIZE);
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let message = &client_login_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.credential_request);
+        assert_eq!(message.serialized_len(), CredentialRequestLen::<CS>::USIZE);
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file.clone()),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let message = &server_login_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.credential_response);
+        assert_eq!(message.serialized_len(), CredentialResponseLen::<CS>::USIZE);
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let message = &client_login_finish_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.credential_finalization);
+        assert_eq!(
+            message.serialized_len(),
+            CredentialFinalizationLen::<CS>::USIZE
+        );
+
+        let client_retrieval_start_result =
+            ClientRetrieval::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let message = &client_retrieval_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.retrieval_request);
+        let server_retrieval_start_result = ServerRetrieval::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file),
+            client_retrieval_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let message = &server_retrieval_start_result.message;
+        assert_eq!(message.serialize().len(), message.serialized_len());
+        assert_eq!(message.serialized_len(), sizes.retrieval_response);
+
+        assert_eq!(
+            sizes.max(),
+            sizes.credential_response.max(sizes.registration_upload)
+        );
+
+        // The constants agree with the `typenum` lengths
+        assert_eq!(Sizes::<CS>::messages(), sizes);
+        assert_eq!(checked_sizes::<CS>()?, sizes);
+        assert_eq!(sizes.envelope, EnvelopeLen::<CS>::USIZE);
+        assert_eq!(sizes.masked_response, MaskedResponseLen::<CS>::USIZE);
+        assert_eq!(Sizes::<CS>::MAX_MESSAGE_LEN, sizes.max());
+        assert_eq!(
+            Sizes::<CS>::REGISTRATION_REQUEST_LEN,
+            RegistrationRequestLen::<CS>::USIZE
+        );
+        assert_eq!(
+            Sizes::<CS>::REGISTRATION_RESPONSE_LEN,
+            RegistrationResponseLen::<CS>::USIZE
+        );
+        assert_eq!(
+            Sizes::<CS>::REGISTRATION_UPLOAD_LEN,
+            RegistrationUploadLen::<CS>::USIZE
+        );
+        assert_eq!(
+            Sizes::<CS>::CREDENTIAL_REQUEST_LEN,
+            CredentialRequestLen::<CS>::USIZE
+        );
+        assert_eq!(
+            Sizes::<CS>::CREDENTIAL_RESPONSE_LEN,
+            CredentialResponseLen::<CS>::USIZE
+        );
+        assert_eq!(
+            Sizes::<CS>::CREDENTIAL_FINALIZATION_LEN,
+            CredentialFinalizationLen::<CS>::USIZE
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_serialize_array() -> Result<(), ProtocolError> {
+    // Array lengths can be computed from a concrete cipher suite
+    const REGISTRATION_REQUEST_LEN: usize = RegistrationRequest::<P256>::serialized_size();
+    const REGISTRATION_RESPONSE_LEN: usize = RegistrationResponse::<P256>::serialized_size();
+    const REGISTRATION_UPLOAD_LEN: usize = RegistrationUpload::<P256>::serialized_size();
+    const CREDENTIAL_REQUEST_LEN: usize = CredentialRequest::<P256>::serialized_size();
+    const CREDENTIAL_RESPONSE_LEN: usize = CredentialResponse::<P256>::serialized_size();
+    const CREDENTIAL_FINALIZATION_LEN: usize = CredentialFinalization::<P256>::serialized_size();
+
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+
+    let client_registration_start_result =
+        ClientRegistration::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let bytes: [u8; REGISTRATION_REQUEST_LEN] =
+        client_registration_start_result.message.serialize_array();
+    assert_eq!(
+        bytes.as_slice(),
+        client_registration_start_result
+            .message
+            .serialize()
+            .as_slice()
+    );
+    let server_registration_start_result = ServerRegistration::<P256>::start(
+        &server_setup,
+        RegistrationRequest::deserialize(&bytes)?,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let bytes: [u8; REGISTRATION_RESPONSE_LEN] =
+        server_registration_start_result.message.serialize_array();
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        STR_PASSWORD.as_bytes(),
+        RegistrationResponse::deserialize(&bytes)?,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let bytes: [u8; REGISTRATION_UPLOAD_LEN] =
+        client_registration_finish_result.message.serialize_array();
+    let password_file = ServerRegistration::finish(RegistrationUpload::deserialize(&bytes)?);
+
+    let client_login_start_result = ClientLogin::<P256>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let bytes: [u8; CREDENTIAL_REQUEST_LEN] = client_login_start_result.message.serialize_array();
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        CredentialRequest::deserialize(&bytes)?,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let bytes: [u8; CREDENTIAL_RESPONSE_LEN] = server_login_start_result.message.serialize_array();
+    let client_login_finish_result = client_login_start_result.state.finish(
+        STR_PASSWORD.as_bytes(),
+        CredentialResponse::deserialize(&bytes)?,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let bytes: [u8; CREDENTIAL_FINALIZATION_LEN] =
+        client_login_finish_result.message.serialize_array();
+    let server_login_finish_result = server_login_start_result
+        .state
+        .finish(CredentialFinalization::deserialize(&bytes)?)?;
+
+    assert_eq!(
+        hex::encode(server_login_finish_result.session_key),
+        hex::encode(client_login_finish_result.session_key)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_length() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let parameters = populate_test_vectors(&serde_json::from_str(test_vector).unwrap());
+        let sizes = max_message_sizes::<CS>();
+
+        // Inputs shorter or longer than the fixed length are rejected upfront
+        fn check<T>(
+            bytes: &[u8],
+            len: usize,
+            deserialize: impl Fn(&[u8]) -> Result<T, ProtocolError>,
+        ) {
+            assert_eq!(bytes.len(), len);
+            assert!(deserialize(bytes).is_ok());
+
+            for input in [&bytes[..len - 1], &[bytes, &[0]].concat()] {
+                assert!(matches!(
+                    deserialize(input),
+                    Err(ProtocolError::LibraryError(InternalError::SizeError {
+                        len: expected_len,
+                        actual_len,
+                        ..
+                    })) if expected_len == len && actual_len == input.len()
+                ));
+            }
+        }
+
+        check(
+            &parameters.registration_request,
+            sizes.registration_request,
+            RegistrationRequest::<CS>::deserialize,
+        );
+        check(
+            &parameters.registration_response,
+            sizes.registration_response,
+            RegistrationResponse::<CS>::deserialize,
+        );
+        check(
+            &parameters.registration_upload,
+            sizes.registration_upload,
+            RegistrationUpload::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_request,
+            sizes.credential_request,
+            CredentialRequest::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_response,
+            sizes.credential_response,
+            CredentialResponse::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_finalization,
+            sizes.credential_finalization,
+            CredentialFinalization::<CS>::deserialize,
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_try_from_bytes() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        EnvelopeLen<CS>: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+    {
+        let parameters = populate_test_vectors(&serde_json::from_str(test_vector).unwrap());
+
+        // Decodes like a codec which is generic over the message type
+        fn check<T>(bytes: &[u8], serialize: impl Fn(T) -> Vec<u8>)
+        where
+            T: for<'a> TryFrom<&'a [u8], Error = ProtocolError>,
+        {
+            assert_eq!(serialize(T::try_from(bytes).unwrap()), bytes);
+            assert!(T::try_from(&bytes[1..]).is_err());
+        }
+
+        check(
+            &parameters.registration_request,
+            |message: RegistrationRequest<CS>| message.serialize().to_vec(),
+        );
+        check(
+            &parameters.registration_response,
+            |message: RegistrationResponse<CS>| message.serialize().to_vec(),
+        );
+        check(
+            &parameters.registration_upload,
+            |message: RegistrationUpload<CS>| message.serialize().to_vec(),
+        );
+        check(
+            &parameters.credential_request,
+            |message: CredentialRequest<CS>| message.serialize().to_vec(),
+        );
+        check(
+            &parameters.credential_response,
+            |message: CredentialResponse<CS>| message.serialize().to_vec(),
+        );
+        check(
+            &parameters.credential_finalization,
+            |message: CredentialFinalization<CS>| message.serialize().to_vec(),
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_no_panic() -> Result<(), ProtocolError> {
+    use digest::Digest;
+
+    use crate::driver::Message;
+    use crate::RECORD_VERSION;
+
+    fn inner<CS: CipherSuite>(test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+    {
+        let parameters = populate_test_vectors(&serde_json::from_str(test_vector).unwrap());
+
+        // Feeds `deserialize` with inputs of every length up to one byte more
+        // than `valid`, filled with constant bytes, and with every byte of
+        // `valid` inverted, none of which may panic. Truncated and extended
+        // encodings have to be rejected.
+        fn check<T, E>(valid: &[u8], deserialize: impl Fn(&[u8]) -> Result<T, E>) {
+            assert!(deserialize(valid).is_ok());
+
+            for len in 0..=valid.len() + 1 {
+                for byte in [0x00, 0x01, 0xff] {
+                    let _ = deserialize(&vec![byte; len]);
+                }
+            }
+
+            for len in 0..valid.len() {
+                assert!(deserialize(&valid[..len]).is_err());
+            }
+            assert!(deserialize(&[valid, &[0]].concat()).is_err());
+
+            for index in 0..valid.len() {
+                let mut input = valid.to_vec();
+                input[index] ^= 0xff;
+                let _ = deserialize(&input);
+            }
+        }
+
+        check(
+            &parameters.registration_request,
+            RegistrationRequest::<CS>::deserialize,
+        );
+        check(
+            &parameters.registration_response,
+            RegistrationResponse::<CS>::deserialize,
+        );
+        check(
+            &parameters.registration_upload,
+            RegistrationUpload::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_request,
+            CredentialRequest::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_response,
+            CredentialResponse::<CS>::deserialize,
+        );
+        check(
+            &parameters.credential_finalization,
+            CredentialFinalization::<CS>::deserialize,
+        );
+        check(
+            &parameters.client_registration_state,
+            ClientRegistration::<CS>::deserialize,
+        );
+        check(
+            &parameters.registration_upload,
+            ServerRegistration::<CS>::deserialize,
+        );
+        check(
+            &parameters.client_login_state,
+            ClientLogin::<CS>::deserialize,
+        );
+        check(
+            &parameters.server_login_state,
+            ServerLogin::<CS>::deserialize,
+        );
+        check(
+            &[
+                parameters.oprf_seed.as_slice(),
+                &parameters.server_s_sk,
+                &parameters.fake_sk,
+            ]
+            .concat(),
+            ServerSetup::<CS>::deserialize,
+        );
+        check(
+            &parameters.server_s_pk,
+            PublicKey::<CS::KeGroup>::deserialize,
+        );
+        check(
+            &parameters.server_s_sk,
+            KeyPair::<CS::KeGroup>::from_private_key_slice,
+        );
+        check(&[0; 12], KsfParameters::deserialize);
+        check(
+            &[&[RECORD_VERSION], parameters.registration_upload.as_slice()].concat(),
+            |input| {
+                ServerRegistration::<CS>::deserialize_versioned(input, |_| {
+                    Err(ProtocolError::SerializationError)
+                })
+            },
+        );
+        for (message_type, message) in [
+            (1, &parameters.registration_request),
+            (2, &parameters.registration_response),
+            (3, &parameters.registration_upload),
+            (4, &parameters.credential_request),
+            (5, &parameters.credential_response),
+            (6, &parameters.credential_finalization),
+        ] {
+            check(
+                &[&[message_type], message.as_slice()].concat(),
+                Message::<CS>::deserialize,
+            );
+        }
+
+        // The checksum of a batch is recomputed over the modified contents, so
+        // that parsing the header and the records is reached
+        let record = ServerRegistration::<CS>::deserialize(&parameters.registration_upload)?;
+        let records = [(parameters.credential_identifier.as_slice(), record)];
+        let mut batch = vec![0; ServerRegistration::batch_len(&records)];
+        ServerRegistration::serialize_batch(&records, &mut batch)?;
+        let contents = &batch[..batch.len() - OutputSize::<OprfHash<CS>>::USIZE];
+        check(contents, |contents| {
+            let batch = [contents, &OprfHash::<CS>::digest(contents)].concat();
+            ServerRegistration::<CS>::deserialize_batch(&batch)?
+                .collect::<Result<Vec<_>, _>>()
+                .map(|_| ())
+        });
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_protocol_driver() -> Result<(), ProtocolError> {
+    use crate::driver::{Event, Message, MessageType, ProtocolDriver};
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // RegistrationResponse: KgPk + KePk
+        <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        RegistrationResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Ke1MessageLen<CS>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+        <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+        Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>:
+            ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+        CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+        CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+        CredentialResponseLen<CS>: ArrayLength<u8>,
+        // RetrievalResponse: CredentialResponseWithoutKeLen
+        RetrievalResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let mut client_buffer = vec![0; Message::<CS>::max_len()];
+        let mut server_buffer = vec![0; Message::<CS>::max_len()];
+        let credential_identifier = STR_CREDENTIAL_IDENTIFIER.as_bytes();
+
+        // Registration
+        let (mut client, len) = ProtocolDriver::<CS>::start_registration(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &mut client_buffer,
+        )?;
+        assert_eq!(client_buffer[0], MessageType::RegistrationRequest as u8);
+        let mut server = ProtocolDriver::server(&server_setup, credential_identifier, None);
+        let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+        assert!(step.event.is_none());
+        assert!(!server.is_finished());
+        let step = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer)?;
+        assert!(client.is_finished());
+        let Some(Event::ClientRegistrationFinished(client_registration_finish_result)) = step.event
+        else {
+            panic!("expected the client registration to finish")
+        };
+        let step = server.handle(&mut rng, &client_buffer[..step.len], &mut server_buffer)?;
+        assert!(server.is_finished());
+        assert_eq!(step.len, 0);
+        let Some(Event::ServerRegistrationFinished(password_file)) = step.event else {
+            panic!("expected the server registration to finish")
+        };
+
+        // Login
+        for (password, success) in [
+            (STR_PASSWORD.as_bytes(), true),
+            (b"wrong password".as_slice(), false),
+        ] {
+            let (mut client, len) =
+                ProtocolDriver::<CS>::start_login(&mut rng, password, &mut client_buffer)?;
+            let mut server = ProtocolDriver::server(
+                &server_setup,
+                credential_identifier,
+                Some(password_file.clone()),
+            );
+            let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+            let result = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer);
+            assert!(client.is_finished());
+
+            if !success {
+                assert!(matches!(result, Err(ProtocolError::InvalidLoginError)));
+                continue;
+            }
+
+            let step = result?;
+            let Some(Event::ClientLoginFinished(client_login_finish_result)) = step.event else {
+                panic!("expected the client login to finish")
+            };
+            let step = server.handle(&mut rng, &client_buffer[..step.len], &mut server_buffer)?;
+            let Some(Event::ServerLoginFinished(server_login_finish_result)) = step.event else {
+                panic!("expected the server login to finish")
+            };
+            assert_eq!(
+                hex::encode(client_login_finish_result.session_key),
+                hex::encode(server_login_finish_result.session_key)
+            );
+            assert_eq!(
+                hex::encode(&client_registration_finish_result.export_key),
+                hex::encode(client_login_finish_result.export_key)
+            );
+        }
+
+        // Retrieval
+        let (mut client, len) = ProtocolDriver::<CS>::start_retrieval(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &mut client_buffer,
+        )?;
+        let mut server = ProtocolDriver::server(
+            &server_setup,
+            credential_identifier,
+            Some(password_file.clone()),
+        );
+        let step = server.handle(&mut rng, &client_buffer[..len], &mut server_buffer)?;
+        assert!(matches!(step.event, Some(Event::ServerRetrievalFinished)));
+        let step = client.handle(&mut rng, &server_buffer[..step.len], &mut client_buffer)?;
+        let Some(Event::ClientRetrievalFinished(client_retrieval_finish_result)) = step.event
+        else {
+            panic!("expected the client retrieval to finish")
+        };
+        assert_eq!(
+            hex::encode(&client_registration_finish_result.export_key),
+            hex::encode(client_retrieval_finish_result.export_key)
+        );
+
+        // Messages not expected in the current state abort the flow
+        let (mut client, len) = ProtocolDriver::<CS>::start_login(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &mut client_buffer,
+        )?;
+        assert!(matches!(
+            client.handle(&mut rng, &client_buffer[..len], &mut server_buffer),
+            Err(ProtocolError::UnexpectedMessageError)
+        ));
+        assert!(client.is_finished());
+        let mut server = ProtocolDriver::server(&server_setup, credential_identifier, None);
+        client_buffer[0] = 0;
+        assert!(matches!(
+            server.handle(&mut rng, &client_buffer[..len], &mut server_buffer),
+            Err(ProtocolError::SerializationError)
+        ));
+
+        // The output buffer must fit the outgoing frame
+        let mut server = ProtocolDriver::server(&server_setup, credential_identifier, None);
+        let (_, len) = ProtocolDriver::<CS>::start_login(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &mut client_buffer,
+        )?;
+        assert!(matches!(
+            server.handle(&mut rng, &client_buffer[..len], &mut server_buffer[..1]),
+            Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_server_login_start_nonces() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let masking_nonce = GenericArray::from([1; 32]);
+        let server_nonce = GenericArray::from([2; 32]);
+
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            Some(password_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters {
+                masking_nonce: Some(masking_nonce),
+                server_nonce: Some(server_nonce),
+                ..Default::default()
+            },
+        )?;
+
+        let message = &server_login_start_result.message;
+        assert_eq!(message.masking_nonce, masking_nonce);
+        assert_eq!(
+            message.ke2_message.serialize()[..NonceLen::USIZE],
+            server_nonce[..]
+        );
+
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(server_login_finish_result.session_key),
+            hex::encode(client_login_finish_result.session_key)
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_failing_rng() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut rng = OsRng;
+        assert!(matches!(
+            CS::KeGroup::random_sk(&mut FailingRng),
+            Err(ProtocolError::RandomnessError)
+        ));
+        assert!(matches!(
+            ServerSetup::<CS>::new(&mut FailingRng),
+            Err(ProtocolError::RandomnessError)
+        ));
+        assert!(matches!(
+            ServerSetup::<CS>::new_with_key(
+                &mut FailingRng,
+                KeyPair::generate_random::<CS::OprfCs, _>(&mut rng)?
+            ),
+            Err(ProtocolError::RandomnessError)
+        ));
+
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message.clone(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+
+        assert!(matches!(
+            client_registration_start_result.state.finish(
+                &mut FailingRng,
+                STR_PASSWORD.as_bytes(),
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+            ),
+            Err(ProtocolError::RandomnessError)
+        ));
+        assert!(matches!(
+            ClientRegistration::<CS>::start(&mut FailingRng, STR_PASSWORD.as_bytes()),
+            Err(ProtocolError::RandomnessError)
+        ));
+        assert!(matches!(
+            ClientLogin::<CS>::prepare(&mut FailingRng),
+            Err(ProtocolError::RandomnessError)
+        ));
+        assert!(matches!(
+            ClientLogin::<CS>::start(&mut FailingRng, STR_PASSWORD.as_bytes()),
+            Err(ProtocolError::RandomnessError)
+        ));
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        assert!(matches!(
+            ServerLogin::start(
+                &mut FailingRng,
+                &server_setup,
+                None,
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            ),
+            Err(ProtocolError::RandomnessError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+// Zeroize tests
+
+#[test]
+fn test_exporter() -> Result<(), ProtocolError> {
+    use rand::RngCore;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut session_key = GenericArray::<u8, SessionKeyLen<CS>>::default();
+        OsRng.fill_bytes(&mut session_key);
+        let exporter = exporter::Exporter::<CS>::new(&session_key)?;
+
+        let export = |exporter: &exporter::Exporter<CS>, label: &[u8], context: &[u8], len| {
+            let mut output = vec![0; len];
+            exporter
+                .export(label, context, &mut output)
+                .map(|()| output)
+        };
+
+        // The same inputs yield the same output, also across exporters
+        let output = export(&exporter, b"label", b"context", 32)?;
+        assert_eq!(output, export(&exporter, b"label", b"context", 32)?);
+        assert_eq!(
+            output,
+            export(
+                &exporter::Exporter::<CS>::new(&session_key)?,
+                b"label",
+                b"context",
+                32
+            )?
+        );
+        assert_ne!(&output[..], &session_key[..32]);
+
+        // Any other input yields a different output
+        assert_ne!(output, export(&exporter, b"other", b"context", 32)?);
+        assert_ne!(output, export(&exporter, b"label", b"other", 32)?);
+        assert_ne!(output[..16], export(&exporter, b"label", b"context", 16)?);
+
+        for prefix in exporter::RESERVED_LABEL_PREFIXES {
+            let label = [*prefix, b"label"].concat();
+            assert_eq!(
+                export(&exporter, &label, b"context", 32),
+                Err(ProtocolError::ReservedLabelError)
+            );
+        }
+        assert_eq!(
+            export(&exporter, &[0; 249], b"context", 32),
+            Err(ProtocolError::SerializationError)
+        );
+        assert_eq!(
+            export(
+                &exporter,
+                b"label",
+                b"context",
+                255 * OutputSize::<OprfHash<CS>>::USIZE + 1
+            ),
+            Err(ProtocolError::LibraryError(InternalError::HkdfError))
+        );
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut client_rng = OsRng;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+
+        let mut state = client_registration_start_result.state;
+        unsafe { ptr::drop_in_place(&mut state) };
+        for byte in state.to_vec() {
+            assert_eq!(byte, 0);
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_registration_finish() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+
+        let mut state = client_registration_finish_result.state;
+        unsafe { ptr::drop_in_place(&mut state) };
+        for byte in state.to_vec() {
+            assert_eq!(byte, 0);
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_server_registration_finish() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // Envelope: (Nonce + Mac) + AppData
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // ServerRegistration = RegistrationUpload
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let mut state = p_file;
+        util::drop_manually(&mut state);
+        util::test_zeroized(&mut state.upload.envelope.mode);
+        util::test_zeroized(&mut state.upload.masking_key);
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite<KeyExchange = TripleDh>>(
+        _test_vector: &str,
+    ) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // CredentialRequest: KgPk + Ke1Message
+        <OprfGroup<CS> as Group>::ElemLen: Add<Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>>,
+        CredentialRequestLen<CS>: ArrayLength<u8>,
+        // Ke1State: KeSk + Nonce
+        <CS::KeGroup as KeGroup>::SkLen: Add<NonceLen>,
+        Sum<<CS::KeGroup as KeGroup>::SkLen, NonceLen>: ArrayLength<u8>,
+        // Ke1Message: Nonce + KePk
+        NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>: ArrayLength<u8>,
+        // Ke2State: (((Hash + Hash) + Hash) + KePk) + KePk
+        OutputSize<OprfHash<CS>>: Add<OutputSize<OprfHash<CS>>>,
+        Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+        Sum<Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<
+            Sum<Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>,
+            <CS::KeGroup as KeGroup>::PkLen,
+        >: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<
+            Sum<
+                Sum<
+                    Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>,
+                    OutputSize<OprfHash<CS>>,
+                >,
+                <CS::KeGroup as KeGroup>::PkLen,
+            >,
+            <CS::KeGroup as KeGroup>::PkLen,
+        >: ArrayLength<u8>,
+        // Ke2Message: (Nonce + KePk) + Hash
+        NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>:
+            ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
+        Sum<Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8>,
+    {
+        let mut client_rng = OsRng;
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+
+        let mut state = client_login_start_result.state;
+        util::drop_manually(&mut state);
+        util::test_zeroized(&mut state.oprf_client);
+        util::test_zeroized(&mut state.ke1_state);
+        util::test_zeroized(&mut state.credential_request.ke1_message.client_nonce);
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::<CS>::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+
+        let mut state = server_login_start_result.state;
+        unsafe { ptr::drop_in_place(&mut state) };
+        for byte in state.ke2_state.serialize() {
+            assert_eq!(byte, 0);
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite<KeyExchange = TripleDh>>(
         _test_vector: &str,
     ) -> Result<(), ProtocolError>
     where
@@ -1706,11 +8864,15 @@ fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
+        // MaskedResponse: ((Nonce + Hash) + AppData) + KePk
         NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
+        Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        Sum<Sum<NonceLen, OutputSize<OprfHash<CS>>>, CS::AppDataLen>:
             ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
-        MaskedResponseLen<CS>: ArrayLength<u8>,
+        Sum<
+            Sum<Sum<NonceLen, OutputSize<OprfHash<CS>>>, CS::AppDataLen>,
+            <CS::KeGroup as KeGroup>::PkLen,
+        >: ArrayLength<u8>,
         // CredentialRequest: KgPk + Ke1Message
         <OprfGroup<CS> as Group>::ElemLen: Add<Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>>,
         CredentialRequestLen<CS>: ArrayLength<u8>,
@@ -1720,12 +8882,26 @@ fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
         // Ke1Message: Nonce + KePk
         NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
         Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>: ArrayLength<u8>,
-        // Ke2State: (Hash + Hash) + Hash
+        // Ke2State: (((Hash + Hash) + Hash) + KePk) + KePk
         OutputSize<OprfHash<CS>>: Add<OutputSize<OprfHash<CS>>>,
         Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>:
             ArrayLength<u8> + Add<OutputSize<OprfHash<CS>>>,
         Sum<Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8>,
+            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<
+            Sum<Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>, OutputSize<OprfHash<CS>>>,
+            <CS::KeGroup as KeGroup>::PkLen,
+        >: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        Sum<
+            Sum<
+                Sum<
+                    Sum<OutputSize<OprfHash<CS>>, OutputSize<OprfHash<CS>>>,
+                    OutputSize<OprfHash<CS>>,
+                >,
+                <CS::KeGroup as KeGroup>::PkLen,
+            >,
+            <CS::KeGroup as KeGroup>::PkLen,
+        >: ArrayLength<u8>,
         // Ke2Message: (Nonce + KePk) + Hash
         NonceLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
         Sum<NonceLen, <CS::KeGroup as KeGroup>::PkLen>:
@@ -1735,7 +8911,7 @@ fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
     {
         let mut client_rng = OsRng;
         let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
         let client_registration_start_result =
             ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
         let server_registration_start_result = ServerRegistration::<CS>::start(
@@ -1781,7 +8957,245 @@ fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
 }
 
 #[test]
-fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
+fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::<CS>::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
+        let client_login_finish_result = client_login_start_result.state.finish(
+            STR_PASSWORD.as_bytes(),
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )?;
+        let server_login_finish_result = server_login_start_result
+            .state
+            .finish(client_login_finish_result.message)?;
+
+        let mut state = server_login_finish_result.state;
+        unsafe { ptr::drop_in_place(&mut state) };
+        for byte in state.ke2_state.serialize() {
+            assert_eq!(byte, 0);
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_scalar_always_nonzero() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        // Start out with a bunch of zeros to force resampling of scalar
+        let mut client_registration_rng = CycleRng::new([vec![0u8; 128], vec![1u8; 128]].concat());
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_registration_rng, STR_PASSWORD.as_bytes())?;
+
+        assert!(!bool::from(
+            OprfGroup::<CS>::identity_elem().ct_eq(
+                &client_registration_start_result
+                    .message
+                    .get_blinded_element_for_testing()
+                    .value(),
+            )
+        ));
+
+        // Start out with a bunch of zeros to force resampling of scalar
+        let mut client_login_rng = CycleRng::new([vec![0u8; 128], vec![1u8; 128]].concat());
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_login_rng, STR_PASSWORD.as_bytes())?;
+
+        assert!(!bool::from(
+            OprfGroup::<CS>::identity_elem().ct_eq(
+                &client_login_start_result
+                    .message
+                    .get_blinded_element_for_testing()
+                    .value(),
+            )
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_reflected_value_error_registration() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    {
+        let credential_identifier = b"credentialIdentifier";
+        let password = b"password";
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, password)?;
+        let alpha = client_registration_start_result
+            .message
+            .get_blinded_element_for_testing()
+            .value();
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            credential_identifier,
+        )?;
+
+        let reflected_registration_response = server_registration_start_result
+            .message
+            .set_evaluation_element_for_testing(alpha);
+
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            password,
+            reflected_registration_response,
+            ClientRegistrationFinishParameters::default(),
+        );
+
+        assert!(matches!(
+            client_registration_finish_result,
+            Err(ProtocolError::ReflectedValueError)
+        ));
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_reflected_value_error_login() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let credential_identifier = b"credentialIdentifier";
+        let password = b"password";
+        let mut client_rng = OsRng;
+        let mut server_rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut server_rng)?;
+        let client_registration_start_result =
+            ClientRegistration::<CS>::start(&mut client_rng, password)?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &server_setup,
+            client_registration_start_result.message,
+            credential_identifier,
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut client_rng,
+            password,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+        let client_login_start_result = ClientLogin::<CS>::start(&mut client_rng, password)?;
+        let alpha = client_login_start_result
+            .message
+            .get_blinded_element_for_testing()
+            .value();
+        let server_login_start_result = ServerLogin::<CS>::start(
+            &mut server_rng,
+            &server_setup,
+            Some(p_file),
+            client_login_start_result.message,
+            credential_identifier,
+            ServerLoginStartParameters::default(),
+        )?;
+
+        let reflected_credential_response = server_login_start_result
+            .message
+            .set_evaluation_element_for_testing(alpha);
+
+        let client_login_result = client_login_start_result.state.finish(
+            password,
+            reflected_credential_response,
+            ClientLoginFinishParameters::default(),
+        );
+
+        assert!(matches!(
+            client_login_result,
+            Err(ProtocolError::ReflectedValueError)
+        ));
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_cache() -> Result<(), ProtocolError> {
+    use crate::replay::{LruReplayCache, ReplayCache};
+
     fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1790,40 +9204,46 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
         let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
         let server_registration_start_result = ServerRegistration::<CS>::start(
             &server_setup,
             client_registration_start_result.message,
             STR_CREDENTIAL_IDENTIFIER.as_bytes(),
         )?;
         let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
+            &mut rng,
             STR_PASSWORD.as_bytes(),
             server_registration_start_result.message,
             ClientRegistrationFinishParameters::default(),
         )?;
-        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
-        let client_login_start_result =
-            ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
-        let server_login_start_result = ServerLogin::<CS>::start(
-            &mut server_rng,
-            &server_setup,
-            Some(p_file),
-            client_login_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-            ServerLoginStartParameters::default(),
-        )?;
-        let client_login_finish_result = client_login_start_result.state.finish(
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let mut replay_cache = LruReplayCache::new(1);
+        let mut server_login_start = |credential_request| {
+            ServerLogin::start_with_replay_cache(
+                &mut OsRng,
+                &server_setup,
+                Some(password_file.clone()),
+                credential_request,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+                &mut replay_cache,
+            )
+        };
+
+        let first = ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = server_login_start(first.message.clone())?;
+        let client_login_finish_result = first.state.finish(
             STR_PASSWORD.as_bytes(),
             server_login_start_result.message,
             ClientLoginFinishParameters::default(),
@@ -1831,23 +9251,54 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
         let server_login_finish_result = server_login_start_result
             .state
             .finish(client_login_finish_result.message)?;
+        assert_eq!(
+            hex::encode(client_login_finish_result.session_key),
+            hex::encode(server_login_finish_result.session_key)
+        );
 
-        let mut state = server_login_finish_result.state;
-        unsafe { ptr::drop_in_place(&mut state) };
-        for byte in state.serialize() {
-            assert_eq!(byte, 0);
-        }
+        // Replaying the credential request is rejected
+        assert!(matches!(
+            server_login_start(first.message.clone()),
+            Err(ProtocolError::ReplayError)
+        ));
+
+        // Once evicted by a newer request, the replay is no longer detected
+        let second = ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        server_login_start(second.message)?;
+        server_login_start(first.message)?;
 
         Ok(())
     }
 
     run_all!(inner);
 
+    // Detecting a replay counts as a use of the hash
+    let mut replay_cache = LruReplayCache::new(2);
+    assert!(replay_cache.is_empty());
+    assert!(!replay_cache.check_and_insert(b"a"));
+    assert!(!replay_cache.check_and_insert(b"b"));
+    assert!(replay_cache.check_and_insert(b"a"));
+    assert!(!replay_cache.check_and_insert(b"c"));
+    assert_eq!(replay_cache.len(), 2);
+    assert!(replay_cache.check_and_insert(b"a"));
+    assert!(!replay_cache.check_and_insert(b"b"));
+
+    for _ in 0..10 {
+        assert!(replay_cache.check_and_insert(b"b"));
+    }
+    assert!(!replay_cache.check_and_insert(b"c"));
+    assert!(replay_cache.check_and_insert(b"b"));
+    assert_eq!(replay_cache.len(), 2);
+
     Ok(())
 }
 
 #[test]
-fn test_scalar_always_nonzero() -> Result<(), ProtocolError> {
+fn test_throttle_token() -> Result<(), ProtocolError> {
+    use generic_array::typenum::U20;
+
+    use crate::throttle::{ThrottleParameters, ThrottleToken, ThrottleTokenLen};
+
     fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1856,33 +9307,79 @@ fn test_scalar_always_nonzero() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // ThrottleToken: (Failures + NotBefore + ExpiresAt) + Hash
+        U20: Add<OutputSize<OprfHash<CS>>>,
+        ThrottleTokenLen<CS>: ArrayLength<u8>,
     {
-        // Start out with a bunch of zeros to force resampling of scalar
-        let mut client_registration_rng = CycleRng::new([vec![0u8; 128], vec![1u8; 128]].concat());
-        let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_registration_rng, STR_PASSWORD.as_bytes())?;
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let credential_identifier = STR_CREDENTIAL_IDENTIFIER.as_bytes();
 
-        assert!(!bool::from(
-            OprfGroup::<CS>::identity_elem().ct_eq(
-                &client_registration_start_result
-                    .message
-                    .get_blinded_element_for_testing()
-                    .value(),
-            )
+        let token = server_setup.throttle_token(credential_identifier, 3, 100, 1000)?;
+        assert_eq!(token.failures(), 3);
+        assert_eq!(token.not_before(), 100);
+        assert_eq!(token.expires_at(), 1000);
+
+        let token = ThrottleToken::<CS>::deserialize(&token.serialize())?;
+        assert_eq!(
+            server_setup.verify_throttle_token(credential_identifier, &token, 100)?,
+            3
+        );
+        assert_eq!(
+            server_setup.verify_throttle_token(credential_identifier, &token, 999)?,
+            3
+        );
+
+        // Too early or expired
+        assert!(matches!(
+            server_setup.verify_throttle_token(credential_identifier, &token, 99),
+            Err(ProtocolError::ThrottleError)
+        ));
+        assert!(matches!(
+            server_setup.verify_throttle_token(credential_identifier, &token, 1000),
+            Err(ProtocolError::ThrottleError)
         ));
 
-        // Start out with a bunch of zeros to force resampling of scalar
-        let mut client_login_rng = CycleRng::new([vec![0u8; 128], vec![1u8; 128]].concat());
-        let client_login_start_result =
-            ClientLogin::<CS>::start(&mut client_login_rng, STR_PASSWORD.as_bytes())?;
+        // Issued for a different credential identifier or server
+        assert!(matches!(
+            server_setup.verify_throttle_token(b"other", &token, 100),
+            Err(ProtocolError::ThrottleError)
+        ));
+        assert!(matches!(
+            ServerSetup::<CS>::new(&mut rng)?.verify_throttle_token(
+                credential_identifier,
+                &token,
+                100
+            ),
+            Err(ProtocolError::ThrottleError)
+        ));
 
-        assert!(!bool::from(
-            OprfGroup::<CS>::identity_elem().ct_eq(
-                &client_login_start_result
-                    .message
-                    .get_blinded_element_for_testing()
-                    .value(),
-            )
+        // Tampering with the recorded failures or times
+        let mut bytes = token.serialize();
+        bytes[3] = 0;
+        let tampered = ThrottleToken::<CS>::deserialize(&bytes)?;
+        assert!(matches!(
+            server_setup.verify_throttle_token(credential_identifier, &tampered, 100),
+            Err(ProtocolError::ThrottleError)
+        ));
+        let mut bytes = token.serialize();
+        bytes[11] = 0;
+        let tampered = ThrottleToken::<CS>::deserialize(&bytes)?;
+        assert!(matches!(
+            server_setup.verify_throttle_token(credential_identifier, &tampered, 0),
+            Err(ProtocolError::ThrottleError)
+        ));
+        let mut bytes = token.serialize();
+        bytes[18] = 0xff;
+        let tampered = ThrottleToken::<CS>::deserialize(&bytes)?;
+        assert!(matches!(
+            server_setup.verify_throttle_token(credential_identifier, &tampered, 1000),
+            Err(ProtocolError::ThrottleError)
+        ));
+
+        assert!(matches!(
+            ThrottleToken::<CS>::deserialize(&bytes[1..]),
+            Err(ProtocolError::LibraryError(InternalError::SizeError { .. }))
         ));
 
         Ok(())
@@ -1890,11 +9387,71 @@ fn test_scalar_always_nonzero() -> Result<(), ProtocolError> {
 
     run_all!(inner);
 
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<P256>::new(&mut rng)?;
+    let start = |token: Option<&ThrottleToken<P256>>, token_required: bool, now: u64| {
+        let client_login_start_result =
+            ClientLogin::<P256>::start(&mut OsRng, STR_PASSWORD.as_bytes())?;
+        ServerLogin::start_with_throttle(
+            &mut OsRng,
+            &server_setup,
+            None,
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+            ThrottleParameters {
+                token,
+                token_required,
+                now,
+                backoff: |failures| 10 * u64::from(failures),
+                lifetime: 100,
+            },
+        )
+        .map(|(_, token)| token)
+    };
+
+    // The first attempt needs no token, and each one counts as a failure
+    let first_token = start(None, false, 0)?;
+    assert_eq!(
+        (
+            first_token.failures(),
+            first_token.not_before(),
+            first_token.expires_at()
+        ),
+        (1, 10, 110)
+    );
+    assert!(matches!(
+        start(Some(&first_token), false, 9),
+        Err(ProtocolError::ThrottleError)
+    ));
+    let token = start(Some(&first_token), false, 10)?;
+    assert_eq!(
+        (token.failures(), token.not_before(), token.expires_at()),
+        (2, 30, 130)
+    );
+
+    // An older token can be replayed until it expires
+    assert_eq!(start(Some(&first_token), false, 109)?.failures(), 2);
+    assert!(matches!(
+        start(Some(&first_token), false, 110),
+        Err(ProtocolError::ThrottleError)
+    ));
+
+    // Leaving out the token is only possible while the server doesn't require it
+    assert!(matches!(
+        start(None, true, 30),
+        Err(ProtocolError::ThrottleError)
+    ));
+    let token = start(Some(&token), true, 30)?;
+    assert_eq!(token.failures(), 3);
+
     Ok(())
 }
 
 #[test]
-fn test_reflected_value_error_registration() -> Result<(), ProtocolError> {
+fn test_login_failure_diagnostics() -> Result<(), ProtocolError> {
+    use crate::errors::LoginFailure;
+
     fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1903,38 +9460,73 @@ fn test_reflected_value_error_registration() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
     {
-        let credential_identifier = b"credentialIdentifier";
-        let password = b"password";
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+
         let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, password)?;
-        let alpha = client_registration_start_result
-            .message
-            .get_blinded_element_for_testing()
-            .value();
+            ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
         let server_registration_start_result = ServerRegistration::<CS>::start(
             &server_setup,
             client_registration_start_result.message,
-            credential_identifier,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
         )?;
-
-        let reflected_registration_response = server_registration_start_result
-            .message
-            .set_evaluation_element_for_testing(alpha);
-
         let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
-            password,
-            reflected_registration_response,
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            server_registration_start_result.message,
             ClientRegistrationFinishParameters::default(),
-        );
+        )?;
+        let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+
+        let mut login = |tamper: fn(&mut Vec<u8>)| -> Result<_, ProtocolError> {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let server_login_start_result = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file.clone()),
+                client_login_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )?;
+            let client_login_finish_result = client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            )?;
 
+            let mut message = client_login_finish_result.message.serialize().to_vec();
+            tamper(&mut message);
+            let mut failure = None;
+            let result = server_login_start_result
+                .state
+                .finish_with_diagnostics(&message, |cause| failure = Some(cause));
+
+            Ok((result.map(|_| ()), failure))
+        };
+
+        assert!(matches!(login(|_| ())?, (Ok(()), None)));
         assert!(matches!(
-            client_registration_finish_result,
-            Err(ProtocolError::ReflectedValueError)
+            login(|message| message[0] ^= 1)?,
+            (
+                Err(ProtocolError::InvalidLoginError),
+                Some(LoginFailure::ClientMacError)
+            )
+        ));
+        assert!(matches!(
+            login(|message| {
+                message.pop();
+            })?,
+            (
+                Err(ProtocolError::InvalidLoginError),
+                Some(LoginFailure::Deserialization(_))
+            )
         ));
 
         Ok(())
@@ -1946,7 +9538,7 @@ fn test_reflected_value_error_registration() -> Result<(), ProtocolError> {
 }
 
 #[test]
-fn test_reflected_value_error_login() -> Result<(), ProtocolError> {
+fn test_dummy_server_registration() -> Result<(), ProtocolError> {
     fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
     where
         <OprfHash<CS> as OutputSizeUser>::OutputSize:
@@ -1955,59 +9547,200 @@ fn test_reflected_value_error_login() -> Result<(), ProtocolError> {
         <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
         <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
         Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-        // MaskedResponse: (Nonce + Hash) + KePk
-        NonceLen: Add<OutputSize<OprfHash<CS>>>,
-        Sum<NonceLen, OutputSize<OprfHash<CS>>>:
-            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
         MaskedResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
     {
-        let credential_identifier = b"credentialIdentifier";
-        let password = b"password";
-        let mut client_rng = OsRng;
-        let mut server_rng = OsRng;
-        let server_setup = ServerSetup::<CS>::new(&mut server_rng);
-        let client_registration_start_result =
-            ClientRegistration::<CS>::start(&mut client_rng, password)?;
-        let server_registration_start_result = ServerRegistration::<CS>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            credential_identifier,
-        )?;
-        let client_registration_finish_result = client_registration_start_result.state.finish(
-            &mut client_rng,
-            password,
-            server_registration_start_result.message,
-            ClientRegistrationFinishParameters::default(),
-        )?;
-        let p_file = ServerRegistration::finish(client_registration_finish_result.message);
-        let client_login_start_result = ClientLogin::<CS>::start(&mut client_rng, password)?;
-        let alpha = client_login_start_result
-            .message
-            .get_blinded_element_for_testing()
-            .value();
-        let server_login_start_result = ServerLogin::<CS>::start(
-            &mut server_rng,
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let password_file = ServerRegistration::dummy(&mut rng, &server_setup)?;
+
+        // Fake password files differ, so that they are not recognizable
+        assert_ne!(
+            hex::encode(password_file.serialize()),
+            hex::encode(ServerRegistration::dummy(&mut rng, &server_setup)?.serialize())
+        );
+
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
             &server_setup,
-            Some(p_file),
+            Some(password_file),
             client_login_start_result.message,
-            credential_identifier,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
             ServerLoginStartParameters::default(),
         )?;
+        assert!(matches!(
+            client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            ),
+            Err(ProtocolError::InvalidLoginError)
+        ));
 
-        let reflected_credential_response = server_login_start_result
-            .message
-            .set_evaluation_element_for_testing(alpha);
+        Ok(())
+    }
 
-        let client_login_result = client_login_start_result.state.finish(
-            password,
-            reflected_credential_response,
-            ClientLoginFinishParameters::default(),
+    run_all!(inner);
+
+    Ok(())
+}
+
+#[test]
+fn test_latency_target() -> Result<(), ProtocolError> {
+    use std::time::{Duration, Instant};
+
+    use crate::timing::LatencyTarget;
+
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+    {
+        let target = LatencyTarget::new(Duration::from_millis(20));
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let password_file = ServerRegistration::dummy(&mut rng, &server_setup)?;
+
+        // Both the real and the dummy path take at least the target
+        for password_file in [Some(password_file), None] {
+            let client_login_start_result =
+                ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+            let start = Instant::now();
+            let timed = target.run(|| {
+                ServerLogin::start(
+                    &mut rng,
+                    &server_setup,
+                    password_file,
+                    client_login_start_result.message,
+                    STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                    ServerLoginStartParameters::default(),
+                )
+            });
+            let total = start.elapsed();
+            timed.value?;
+            assert!(total >= target.target());
+            assert!(timed.elapsed <= total);
+        }
+
+        Ok(())
+    }
+
+    run_all!(inner);
+
+    // Exceeding the target isn't padded further
+    let target = LatencyTarget::new(Duration::from_millis(1));
+    assert_eq!(target.remaining(Duration::from_secs(1)), Duration::ZERO);
+    let timed = target.run(|| std::thread::sleep(Duration::from_millis(5)));
+    assert!(timed.elapsed >= Duration::from_millis(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_fake_record_secret() -> Result<(), ProtocolError> {
+    fn inner<CS: CipherSuite>(_test_vector: &str) -> Result<(), ProtocolError>
+    where
+        <OprfHash<CS> as OutputSizeUser>::OutputSize:
+            IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+        OprfHash<CS>: Hash,
+        <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+        <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+        Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+        // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+        NonceLen: Add<MacLen<CS>>,
+        Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+        EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+        MaskedResponseLen<CS>: ArrayLength<u8>,
+        // RegistrationUpload: (KePk + Hash) + Envelope
+        <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+        Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+            ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+        RegistrationUploadLen<CS>: ArrayLength<u8>,
+        // ServerSetup: Hash + KeSk + KeSk
+        OutputSize<OprfHash<CS>>: Add<<CS::KeGroup as KeGroup>::SkLen>,
+        Sum<OutputSize<OprfHash<CS>>, <CS::KeGroup as KeGroup>::SkLen>:
+            ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::SkLen>,
+        ServerSetupLen<CS, PrivateKey<CS::KeGroup>>: ArrayLength<u8>,
+    {
+        const SECRET: &[u8] = b"fake record secret";
+
+        let mut rng = OsRng;
+        let mut server_setup = ServerSetup::<CS>::new(&mut rng)?;
+        let fake = |server_setup: &ServerSetup<CS>, credential_identifier: &[u8]| {
+            ServerRegistration::fake(&mut OsRng, server_setup, credential_identifier)
+                .map(|password_file| hex::encode(password_file.serialize()))
+        };
+
+        // Random by default
+        assert_ne!(
+            fake(&server_setup, STR_CREDENTIAL_IDENTIFIER.as_bytes())?,
+            fake(&server_setup, STR_CREDENTIAL_IDENTIFIER.as_bytes())?
+        );
+
+        server_setup.set_fake_record_secret(SECRET);
+        let password_file = fake(&server_setup, STR_CREDENTIAL_IDENTIFIER.as_bytes())?;
+        assert_eq!(
+            password_file,
+            fake(&server_setup, STR_CREDENTIAL_IDENTIFIER.as_bytes())?
         );
+        assert_ne!(password_file, fake(&server_setup, b"other")?);
 
+        // The secret has to be set again after a restart
+        let mut restarted = ServerSetup::<CS>::deserialize(&server_setup.serialize())?;
+        assert_ne!(
+            password_file,
+            fake(&restarted, STR_CREDENTIAL_IDENTIFIER.as_bytes())?
+        );
+        restarted.set_fake_record_secret(SECRET);
+        assert_eq!(
+            password_file,
+            fake(&restarted, STR_CREDENTIAL_IDENTIFIER.as_bytes())?
+        );
+        restarted.set_fake_record_secret(b"other");
+        assert_ne!(
+            password_file,
+            fake(&restarted, STR_CREDENTIAL_IDENTIFIER.as_bytes())?
+        );
+
+        // Logins against the fake password file fail as usual
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        let server_login_start_result = ServerLogin::start(
+            &mut rng,
+            &server_setup,
+            None,
+            client_login_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )?;
         assert!(matches!(
-            client_login_result,
-            Err(ProtocolError::ReflectedValueError)
+            client_login_start_result.state.finish(
+                STR_PASSWORD.as_bytes(),
+                server_login_start_result.message,
+                ClientLoginFinishParameters::default(),
+            ),
+            Err(ProtocolError::InvalidLoginError)
         ));
+
         Ok(())
     }
 
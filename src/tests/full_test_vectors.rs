@@ -40,7 +40,7 @@ pub static TEST_VECTOR_RISTRETTO255: &str = r#"
     "credential_finalization": "eab00a0d24f05c0bdab1c026411a72c6bf6a778e69abf9d0c4b32d92447e316d42694a46fb8f2fdc7f28f06a3c67feb1dc0812cb3dce467dc5f63a61267c565a",
     "client_registration_state": "fec9b267bdc3978d9ee7c9e4a92d1ef5116b106385913c4fe8f75c27112f6c0d06b58b47baefacb7bdd30b41922f569bc3fe1b3dfbf03aad0b71a5accd7d791e",
     "client_login_state": "fec9b267bdc3978d9ee7c9e4a92d1ef5116b106385913c4fe8f75c27112f6c0d06b58b47baefacb7bdd30b41922f569bc3fe1b3dfbf03aad0b71a5accd7d791e78ad0a1bc925acae5d28ad271f240761f194d3deaa8b9aeb75223efb91b04d5afc2038b7bd7f19aaf27a81e6393414df49d27e4afa1a3a990476bf201aa21f42730e2239b2ecad7639f03b3a6a82126096a197d481a8fc2bb6106754294e0d0578ad0a1bc925acae5d28ad271f240761f194d3deaa8b9aeb75223efb91b04d5a",
-    "server_login_state": "589e137bd0be129798d0a1caf09f768d44877a63a0d7fbe9943c6403e9458c75d0e211d4b65847e44fb61e108b1d24261876891e6eb7ec1b53727be721f7b398f5d5b8c0fdeda829a545ce50509dbc1d08d85cdc3d02c72a24c2cdab0a68084ba6cc9784abaa87a46125321c1320b2465658daf0759948c14e4fa019fb4d2859b195a99f00c34f4723db8a2238966e205513bb961629feb7c5bfdbd49ac0234fd0c0307b7b06aee055b95534ff4267d3bde43b65c025d73da564b51702fcf395",
+    "server_login_state": "589e137bd0be129798d0a1caf09f768d44877a63a0d7fbe9943c6403e9458c75d0e211d4b65847e44fb61e108b1d24261876891e6eb7ec1b53727be721f7b398f5d5b8c0fdeda829a545ce50509dbc1d08d85cdc3d02c72a24c2cdab0a68084ba6cc9784abaa87a46125321c1320b2465658daf0759948c14e4fa019fb4d2859b195a99f00c34f4723db8a2238966e205513bb961629feb7c5bfdbd49ac0234fd0c0307b7b06aee055b95534ff4267d3bde43b65c025d73da564b51702fcf395fc2038b7bd7f19aaf27a81e6393414df49d27e4afa1a3a990476bf201aa21f42a0b3cd246332623f2fbd86b2213a88959f1242ad68183c977dc937425a2bea129ab9a041b38a44b59dd992ae553bb0bcddecc23685b7339d46260aacbb6f8625",
     "password_file": "9ab9a041b38a44b59dd992ae553bb0bcddecc23685b7339d46260aacbb6f862571f75f437f4d28022298d467f5b19f7a2fd417f27710b8e0f136c94a4b06173764492bdcfd42957a1842da57e1de46e9aab09e2b4481c9e51555bcd2ba7b659d59eeeca55a98beaf8a20bbbcf32c342b66159168aead7afd976d1705b06081098e41574ff6315f8b799bdd178c5fb2544b7d2429065fdd4906f596048fdc91a72c49902ce0eabf2524c423a084847d62a9d795eb6410611babd167a9c34241fd",
     "export_key": "1142ad5ec15e2b3e22345c5f076e147abe77f387a8f414b14b2efd4b471bd7dddecbcfc88688b935cde04c3a19fed3b5adfdcf61b8f40a6d478db75539dcaaa7",
     "session_key": "b195a99f00c34f4723db8a2238966e205513bb961629feb7c5bfdbd49ac0234fd0c0307b7b06aee055b95534ff4267d3bde43b65c025d73da564b51702fcf395"
@@ -77,7 +77,7 @@ pub static TEST_VECTOR_RISTRETTO255_P256: &str = r#"
     "credential_finalization": "1d56e939a755545cdbc2ccc96b1158d748088f929363ba9a24f2d42239fcb2dc",
     "client_registration_state": "b30684b4107d4569c6c807ffc4f1bc5f938279c0805e379b21384900fc3aadf903d19a2fe940efb6ead9a934b8bd70b5975f7cf6abf6c2d04ea62d4b3119755749",
     "client_login_state": "b30684b4107d4569c6c807ffc4f1bc5f938279c0805e379b21384900fc3aadf903d19a2fe940efb6ead9a934b8bd70b5975f7cf6abf6c2d04ea62d4b3119755749b7d4f2cc1ea339a41064a8bcfe5c37442b28adc362fcfa4d312456a1d22c4e35e4e7f2de4c2b83b1b2362fc43d0582c5dd8d7368da1c9944d4a999a3dbe87d191d6071b2740380791c98dae3c49c8e441b72bb1cf328a0bd16c8a7222bef1d04b7d4f2cc1ea339a41064a8bcfe5c37442b28adc362fcfa4d312456a1d22c4e35",
-    "server_login_state": "cb7bf243b2854fd4760f0a0141801eea0efa824611e4c44395cbc8006ff763af9fd56d36d849f3ff72dd841027c5d7f145a5fc4061b9c0ad92a0c8394ea5dbaaf8ef744dfeecf39f8a244075e9d71f6ac9dafb5f656ee4d67ff7fb64696b0c39",
+    "server_login_state": "cb7bf243b2854fd4760f0a0141801eea0efa824611e4c44395cbc8006ff763af9fd56d36d849f3ff72dd841027c5d7f145a5fc4061b9c0ad92a0c8394ea5dbaaf8ef744dfeecf39f8a244075e9d71f6ac9dafb5f656ee4d67ff7fb64696b0c39e4e7f2de4c2b83b1b2362fc43d0582c5dd8d7368da1c9944d4a999a3dbe87d19f0cbeb7ea1fce5905956d98f86d33397f674e79c10431cfd4e249838cdea0f4b84191d318db400be4247a1d2a8d5561cc858ad1ec1a445fd7f0b01498b52112c",
     "password_file": "84191d318db400be4247a1d2a8d5561cc858ad1ec1a445fd7f0b01498b52112cb3254a4d18e9bd9c093ad574e90ffdb674d7e29cb05cda39b4932195823ebafeff3f3c9a3038cbe239dbabb1826da03defbb9b301c81c7ad1bce3f40f151040141b6c365a26dee6daebff3e1d8ce63f1347843a1bc97a884a8aa96a815aa219e",
     "export_key": "01e5f9608070591c9bb42cd4643e995997ac0ee6499c13aaae3e78c1cf5c841a",
     "session_key": "f8ef744dfeecf39f8a244075e9d71f6ac9dafb5f656ee4d67ff7fb64696b0c39"
@@ -114,7 +114,7 @@ pub static TEST_VECTOR_RISTRETTO255_P384: &str = r#"
     "credential_finalization": "8ff72d0422c66561c41dd1db84572e4ff3717a2a8320f9429d47c749a68d8c004d98ccc7f08f189229daf6be560bcdb0",
     "client_registration_state": "0e8d4db10256fa6d5124e8b8b61473d986dd15dfe474cb3e907435770216b1c772018259c3811dab9814b7ad8cbc640702008b74d36dbcf42ae30b2b81cbf3270fdf3466035d372768063b483919624fcf0575b11e10a09c1258ee1f6699b8e248",
     "client_login_state": "0e8d4db10256fa6d5124e8b8b61473d986dd15dfe474cb3e907435770216b1c772018259c3811dab9814b7ad8cbc640702008b74d36dbcf42ae30b2b81cbf3270fdf3466035d372768063b483919624fcf0575b11e10a09c1258ee1f6699b8e2484af04cff80bb07e8b860f84776f375617fd2e6fdf2674d2d57d72c40db6ab28a88249adde60c64609198939aec00a04db1c4c763ba3b6aac685299a62856d01fdba6162c67a28ecf5729cb7b409a13ea6c6f55529c7959f63df4b9a82af9b6084af04cff80bb07e8b860f84776f375617fd2e6fdf2674d2d57d72c40db6ab28a",
-    "server_login_state": "e6ede2b0985648321469cea821b37b772f710764980dec580cca6c95be2554fc050489570af42a09ea267364908f667b495cc6e4cdba5ab80f836d41a5e95493b0683fac921a9e51c5f147c24a84ac68f69ac7f82de8737038a5de953180a44f0e988d7ac920e06b5a5fb50eb771e00d86f2baf971f12479725ba6178a592e64192f87fc461bea24d1252d7312d95156",
+    "server_login_state": "e6ede2b0985648321469cea821b37b772f710764980dec580cca6c95be2554fc050489570af42a09ea267364908f667b495cc6e4cdba5ab80f836d41a5e95493b0683fac921a9e51c5f147c24a84ac68f69ac7f82de8737038a5de953180a44f0e988d7ac920e06b5a5fb50eb771e00d86f2baf971f12479725ba6178a592e64192f87fc461bea24d1252d7312d9515688249adde60c64609198939aec00a04db1c4c763ba3b6aac685299a62856d01f9070566733b012d41f9361fb193f717d5e63d7f06f65694db41d667c6a60196a4e327a4644ec8e788a79a356ac2b2ac33ed0b58381e8f8ecdad6525a3fa3217e",
     "password_file": "4e327a4644ec8e788a79a356ac2b2ac33ed0b58381e8f8ecdad6525a3fa3217e74ab5243e4fa108d9cee8d36585277ac1afdb2b3dc94a9709e7f04a503edb0cd22b0554e2a23a5d0459896126b6547b319a17366501cc0bcb8e3dba2881376e55aadc77e930af57735d767a2097ce3035f8d44f9b75e8d8d796dab779d70f94ee9878e124df2e5519f4c53c885dc8210bf5e243bce7728f8fd866885f033af5c",
     "export_key": "e2e9e6487c30be6a174f08eb98892c744ac9fadba6f6c06b9a20ac786dc9d3cda147d983bd899368e4ee8dab58dd9224",
     "session_key": "0e988d7ac920e06b5a5fb50eb771e00d86f2baf971f12479725ba6178a592e64192f87fc461bea24d1252d7312d95156"
@@ -151,7 +151,7 @@ pub static TEST_VECTOR_RISTRETTO255_P521: &str = r#"
     "credential_finalization": "c7eb54ddad68262df5e7f818420242e288c07b122fed4087fd8d7f8b322b84d23055b1076abcc04bfa8a1c05925658a3734da293a040aacb40d4f216dbad7314",
     "client_registration_state": "0024ff84c5cddd63a9f0150c96ee0efc82a6c4a73595abbc13dc8c5a6ab903bcf42511bfc36c9d769d8e930ef06e417611c562671c3d3fec69882106ec6af39604790201ac0524b745f9fa6fbe64378d50a5a018b770bf0c1e36ecfc986e4626ea0cae567ebc2bf4617fc35b2ecdd090c2c5f82cc090b37d7954ae68b2a64ba22f2f70ea74",
     "client_login_state": "0024ff84c5cddd63a9f0150c96ee0efc82a6c4a73595abbc13dc8c5a6ab903bcf42511bfc36c9d769d8e930ef06e417611c562671c3d3fec69882106ec6af39604790201ac0524b745f9fa6fbe64378d50a5a018b770bf0c1e36ecfc986e4626ea0cae567ebc2bf4617fc35b2ecdd090c2c5f82cc090b37d7954ae68b2a64ba22f2f70ea7415ac5399d93cb6303d7056d9337362ff827b340520e26a2a52bfe389b33d8d480e3ccee2d2f1e8f1f9259bfc7ccc852f7b2616a7aa1fffb21bf3f9d2f6ba224cc7be2071b9453853b75b32e304299d67a70b129708f11b669fdd9fba7d8a0f0515ac5399d93cb6303d7056d9337362ff827b340520e26a2a52bfe389b33d8d48",
-    "server_login_state": "3caf57acb5a211f501373bb98be0505fc32a4d9bfe8ad267fd48c901e4fc2f3720f828b4ae6e37dcdfbb6dedd05589a0891b19c0bd79de388f628cd22c7bc43e0af400cb43bb7050a0426e81089e1f737600634abd12ddce87e03dadbe0345cf34263dee165ecacd3f8c029afbf8f6a141768625aabeb8a908f0f978504c2b462c6dc38db363e4e4db41d7b508bc99e65baf5f3386d71ce861ba74d7225a11cce072b50e7ec34f0dcf54ae2dd66204a10759b6e9208bfb66142f915a07ce0b60",
+    "server_login_state": "3caf57acb5a211f501373bb98be0505fc32a4d9bfe8ad267fd48c901e4fc2f3720f828b4ae6e37dcdfbb6dedd05589a0891b19c0bd79de388f628cd22c7bc43e0af400cb43bb7050a0426e81089e1f737600634abd12ddce87e03dadbe0345cf34263dee165ecacd3f8c029afbf8f6a141768625aabeb8a908f0f978504c2b462c6dc38db363e4e4db41d7b508bc99e65baf5f3386d71ce861ba74d7225a11cce072b50e7ec34f0dcf54ae2dd66204a10759b6e9208bfb66142f915a07ce0b600e3ccee2d2f1e8f1f9259bfc7ccc852f7b2616a7aa1fffb21bf3f9d2f6ba224c489de9178b9b7de17ce7cb77cbc75c1f9857a2b311a67aa54839b9a6b84a2628c62dcfb02541bb505684ff2e61aacd89d4c42cd2e4d9ad4efebb490558458c19",
     "password_file": "c62dcfb02541bb505684ff2e61aacd89d4c42cd2e4d9ad4efebb490558458c19f352d32d1734d5c38d1195eada99ebe0953f7f917d6db06707c97bc838e801beef14f27aa9cdf68b12026d4b4bac5af375bc65f87145d0c60219b0aa378bf1a2d2d649c25452e3c7e01938bffda81f72d3bbc9dc7dd17f90bc339cdbef27320c2a3c1005353f18687f4eabaedc723caa826e57587d2a65d8f0eabce9b4aa70a2740c24963efe7d8317cb2db4b2d2a8911fe374018bd6ece1681aab7e696a4042",
     "export_key": "538bef8ee8da2ad63a7fa73e3c53dd42d002271a33ec19de5481c60bcfa68d41bcb709ab42b4ab68fd1eb75f762805db973453d7657b672ae2b2956def43c1bc",
     "session_key": "2c6dc38db363e4e4db41d7b508bc99e65baf5f3386d71ce861ba74d7225a11cce072b50e7ec34f0dcf54ae2dd66204a10759b6e9208bfb66142f915a07ce0b60"
@@ -187,7 +187,7 @@ pub static TEST_VECTOR_P256: &str = r#"
     "credential_finalization": "554b8419fd438c5492d0c0ac141a6ccc729d1862c9bd2845973c82d24931affe",
     "client_registration_state": "8084b4564f290f9a2ade8105afb2604ca4d4545605693c8028b19502bf8c9da602b159cf62b8f4c7324c0af0afd2c71c4347e1f6806cb4a93c99d7dac2eee8ca57",
     "client_login_state": "8084b4564f290f9a2ade8105afb2604ca4d4545605693c8028b19502bf8c9da602b159cf62b8f4c7324c0af0afd2c71c4347e1f6806cb4a93c99d7dac2eee8ca57a96d561ef8a4d1a122fb37c0baf698d5670e18b6fd40d6d8779865d4332fafe00220d8a0be3f983a1d60218949f8c7cfcfab0e85f475aa3366b9d5b85dcddc0f791caa915e5d150060adff98bdbca083a0c66131c312b2d98b12c0b18314715f30a96d561ef8a4d1a122fb37c0baf698d5670e18b6fd40d6d8779865d4332fafe0",
-    "server_login_state": "bf73f13d58b0c2391bc5b8efcc04a73461dc5a76e80d4a6a6e975fae897886a1c380b68db4924f437f561fbeef19d34cdcbf8a6a6eff0746eaf85078441cbeae423e136b3acad115bf24254e10eb1daa85587f3a0f80300b20bb2ac0b5f93854",
+    "server_login_state": "bf73f13d58b0c2391bc5b8efcc04a73461dc5a76e80d4a6a6e975fae897886a1c380b68db4924f437f561fbeef19d34cdcbf8a6a6eff0746eaf85078441cbeae423e136b3acad115bf24254e10eb1daa85587f3a0f80300b20bb2ac0b5f938540220d8a0be3f983a1d60218949f8c7cfcfab0e85f475aa3366b9d5b85dcddc0f7903f2451ce6c763e633182274aeca243426609bad2bf08a7df1d51a3a0253b6d3b103193588c902bcfedb82542268570364c966be2d0fad7a5c088ea41caa2610391a",
     "password_file": "03193588c902bcfedb82542268570364c966be2d0fad7a5c088ea41caa2610391a90f2ae1679620495ba742e75d54c16ed437ae382054907665b1f6a3ff2b9e0502b1cea37dcce617193b1e481512613d0ae657d9f1dd0bbabf3bca43dd3b2b9b5c7fd1251cec1ef5bd658d6d771f23b3b4645f0a6a9cebb233ab630af4982b636",
     "export_key": "cc448b499a4d621f1fc57d63a190170c65b2661f8bc8378ad0d9874612c891e3",
     "session_key": "423e136b3acad115bf24254e10eb1daa85587f3a0f80300b20bb2ac0b5f93854"
@@ -223,7 +223,7 @@ pub static TEST_VECTOR_P256_P384: &str = r#"
     "credential_finalization": "eb1d6fcb2b418c6f44226f930ada292b160a3fafd910c5126234f59daf1572cbdf3bc350c344c58b71f5a70ecc0b6f8d",
     "client_registration_state": "b9d8dd9458caf0bea43656f70afb32b33e831c8b56ec7b78334cbd0263b7f44f483ca2037b19cbecfb91453ad301732102bafb4ec30e7f957f9021726929b29323ac3a9125c494025b6ddc929ca8e3f524e89cadd5517d7c807af4a3facfe340ac",
     "client_login_state": "b9d8dd9458caf0bea43656f70afb32b33e831c8b56ec7b78334cbd0263b7f44f483ca2037b19cbecfb91453ad301732102bafb4ec30e7f957f9021726929b29323ac3a9125c494025b6ddc929ca8e3f524e89cadd5517d7c807af4a3facfe340aca041203a967e5602373ea635716ef2c46ce29bd1c4084af073b891453100f874031980cf56588b0bf278d6ff657fbc4dc852904fe557a4e4c42704410b0cf7774fd8214e0a6f5986e398ebb3c46a1832d4fb13f32722a002b5683c4da1c73c1a3ea041203a967e5602373ea635716ef2c46ce29bd1c4084af073b891453100f874",
-    "server_login_state": "27a700043ac66429c63ca7aa53949571eec1108629b8eff4ff626b9da00765e8e6d1aa8ecc2d4f97820f5cfa36400fd8bddca7a4de01994e85d81758875d73e3f7377de84091838e8a6b5f24920208a5286a512cffc3464bc33118682c4c621c5ab35e96454a2aeef38702deb953f371be3389b7a347fa4d66db54205b2ecec0a8913c362909b583c0d359e379bab006",
+    "server_login_state": "27a700043ac66429c63ca7aa53949571eec1108629b8eff4ff626b9da00765e8e6d1aa8ecc2d4f97820f5cfa36400fd8bddca7a4de01994e85d81758875d73e3f7377de84091838e8a6b5f24920208a5286a512cffc3464bc33118682c4c621c5ab35e96454a2aeef38702deb953f371be3389b7a347fa4d66db54205b2ecec0a8913c362909b583c0d359e379bab006031980cf56588b0bf278d6ff657fbc4dc852904fe557a4e4c42704410b0cf7774f035ed7d7a3a824e138ee7b31f20668c186602eb72d2bca3f532e6f13f1821b746203acf1912b03a4af014e1071499a2132ddf201031c07b09db7586f301cbe879cf8",
     "password_file": "03acf1912b03a4af014e1071499a2132ddf201031c07b09db7586f301cbe879cf813d90d135613a946bc796c11b0d0b80573410f384c1523feff06509d4f1a1fb5ef4afd5c8ec01d1ba1c302317009480e45c55050c92b1b8a6450b7389407e119c0916b967edbd5ef04b98763c36fe2d2fc077770d06cdc08c46017928bb1f249b691a1ac7324059523c8744aa8d606e79d75f7aa58143e2ad093bc905ddde075",
     "export_key": "7f875d311ef6af7230cd5ab772119e8d54917ae52c89ed3d66e8ca61ed28e7bd37931acafcc81427279b2e2b77654d39",
     "session_key": "5ab35e96454a2aeef38702deb953f371be3389b7a347fa4d66db54205b2ecec0a8913c362909b583c0d359e379bab006"
@@ -259,7 +259,7 @@ pub static TEST_VECTOR_P256_P521: &str = r#"
     "credential_finalization": "aee8f7607a84a4d2aa5fb4e56a7f991203f66af4d12f05478703b3c6cef4fa8dbcedb313c69c1112bec6b7edbd2efb9559c0a720d4af066a4b8597d832423eab",
     "client_registration_state": "01ec03df3e28a196897c2fddaa1c0e5995cb230d48a469a591512a1f059c08724492d91b00aef931fa97f0bf7b091d7e7f0cefbca891aada132eda220cf665645052030149c75915fc8a8ac27e1892b830c333757106b8ebbc5b0a30864583611c9761ef170049e8646a7f50eaeb2177104a996e4c97a5e7c2578e5981217564ee4431ddb1",
     "client_login_state": "01ec03df3e28a196897c2fddaa1c0e5995cb230d48a469a591512a1f059c08724492d91b00aef931fa97f0bf7b091d7e7f0cefbca891aada132eda220cf665645052030149c75915fc8a8ac27e1892b830c333757106b8ebbc5b0a30864583611c9761ef170049e8646a7f50eaeb2177104a996e4c97a5e7c2578e5981217564ee4431ddb1040e46dd0d1000739133e9a615dda26c8d2cd112cb4ad1f00f59b8982543bbaf03b12441c880972b16d541731ea52a7f35e6072448a6e0445c8287f82ffda3faf7c273133b44bd2f29a1546ade5a7dc3a6a622fb47eccaa5e13ef57bcd3a20d034040e46dd0d1000739133e9a615dda26c8d2cd112cb4ad1f00f59b8982543bbaf",
-    "server_login_state": "585ba0a46a81f792750d15daeb41626f68acef1431b62ac680ff3099db271b57cf30e91db995cf295e5bf02a8b7e6050bc1c5335b2111b2b9a1f8ede802f7efbdc293ad1489ae8d6aa03e4bcc59259a72cad57af857be5c7f382561b4d67dff85356233d659147565fc952ef99039a16d915c63f90ac3f700e3d554c939bf1b5641a965de4348f57e1c53c0927a58bf719c6f78471e416fda8212812d9a0c019a3744d4ff8ff0e53d7fa7d39943208d9e30effc4cdd331959389b995fa86b177",
+    "server_login_state": "585ba0a46a81f792750d15daeb41626f68acef1431b62ac680ff3099db271b57cf30e91db995cf295e5bf02a8b7e6050bc1c5335b2111b2b9a1f8ede802f7efbdc293ad1489ae8d6aa03e4bcc59259a72cad57af857be5c7f382561b4d67dff85356233d659147565fc952ef99039a16d915c63f90ac3f700e3d554c939bf1b5641a965de4348f57e1c53c0927a58bf719c6f78471e416fda8212812d9a0c019a3744d4ff8ff0e53d7fa7d39943208d9e30effc4cdd331959389b995fa86b17703b12441c880972b16d541731ea52a7f35e6072448a6e0445c8287f82ffda3faf70206d79e5dd7f3b9e77e8747024384347a9fe72b5bbb7359e108f5abf96ee726f202c30b220271ac9445557b1b2493de5e1092454d46f9473006f3a10713d197cbe7",
     "password_file": "02c30b220271ac9445557b1b2493de5e1092454d46f9473006f3a10713d197cbe774423797c2e81a709c1e801ed9057597630d116ca27c07e430710bb09764db082b3a85dc31e5ab8c572379b73ad40a26702410f78d08588b968a819fddca89418bb560746fd3abbb99abd55da73150aae164ef3486b4dee052b899f06755de98f6b38328d008ce05f66cf636134b5e30270ffdf344f59b8a9c14cc4eda6151441c42cfceec71233445863ffc23d797d33f954c38dee728635c3814318ab37f98",
     "export_key": "8ca70931fce3e4238b8daa0479e14108f250534a009da9bffa06d86d59c03ca416ff2c83e579a81c1b1a4c6d382c0319bbf0ff9c1e147d007df68fd3716d652e",
     "session_key": "641a965de4348f57e1c53c0927a58bf719c6f78471e416fda8212812d9a0c019a3744d4ff8ff0e53d7fa7d39943208d9e30effc4cdd331959389b995fa86b177"
@@ -296,7 +296,7 @@ pub static TEST_VECTOR_P256_RISTRETTO255: &str = r#"
     "credential_finalization": "f36f0a0afbf8c11164d35954c101f268909cf0ce7e1616bb9f6b8a4594562c87860d455a926a894cd4d580d6afc6693d00e4e8971db5272c5ca20567e49edd77",
     "client_registration_state": "3c85d9bd4df7caa7ab504656dc19e2f53eb6b847e00e1bceaf00780f589941069cace2ee452a7cc0399c4ef0a631945e1452eb5179060593befbf773501fdd0e",
     "client_login_state": "3c85d9bd4df7caa7ab504656dc19e2f53eb6b847e00e1bceaf00780f589941069cace2ee452a7cc0399c4ef0a631945e1452eb5179060593befbf773501fdd0ef8f27e9fa18019fceb5cfbda000f5e973cb7db8c955b4cbf2f549c935b6fc26c0208cd4a2392a5209096e1847c98aa74d75fb2f7912bc947e098c03f6472b40f1a5b2d3abfd386b53645b6372ca91ebf4bcfab139646e1ccacb4c8853467708e10f8f27e9fa18019fceb5cfbda000f5e973cb7db8c955b4cbf2f549c935b6fc26c",
-    "server_login_state": "588af0acb3e8a3c01dcf092574a42af600e873af562837f698f7db257b4de0248adaa8f1616bf868817396cacb6dc7aebee7301982afbf124fa871580f0e6ccad782a49b66c676013562f5ce02b15dc4b77b2bd6ec2fca173fb1d58fa7b359aed976c29a53fa0609ea12e60134a7d938584f467f891500ace2e3263c2b93d1c653ab94cbefac77f220e58915a7b4ef56255761a302815f4c5a8d4f4361f8ac8b616c73602a755e380cff5f9060615cb9a2c80df6d9d8ddeba8c973f4e2cae549",
+    "server_login_state": "588af0acb3e8a3c01dcf092574a42af600e873af562837f698f7db257b4de0248adaa8f1616bf868817396cacb6dc7aebee7301982afbf124fa871580f0e6ccad782a49b66c676013562f5ce02b15dc4b77b2bd6ec2fca173fb1d58fa7b359aed976c29a53fa0609ea12e60134a7d938584f467f891500ace2e3263c2b93d1c653ab94cbefac77f220e58915a7b4ef56255761a302815f4c5a8d4f4361f8ac8b616c73602a755e380cff5f9060615cb9a2c80df6d9d8ddeba8c973f4e2cae5490208cd4a2392a5209096e1847c98aa74d75fb2f7912bc947e098c03f6472b40f1a02ed5af74303cc02051400f12dd70f4e65cc7f1f16d302780a325f4df2c0eb13c5034548b2d9fdd7f1cb67a2d5d236ab1b9df3500c337746c526b150b56cfe3d6d61",
     "password_file": "034548b2d9fdd7f1cb67a2d5d236ab1b9df3500c337746c526b150b56cfe3d6d61c2a07366823e4f02cba7e2aeca0c216f3e1f8f1c8ae4e6ddefe3f897daabd43c48894cadb44242f198a437c72927992ba150e138013f6d1bb2b03890ea9af31bcc4340c5dfae39e73a4e051b4ef6ee9317995726293c7c1f8c5d97c56887e21839c328c3ac431ccedc9a8081465da5ad0dc419e396fe589047ffaa00cea0621ceb9824029cb338ebb7dd619f1afcea11d96a1fa3972ad6c8135a160d4d5472e3",
     "export_key": "87120b54a6eb8fee9fa4b2459d1d34e1db01bee10f3716a47fedb9f683f241a0782ba6c715b0b62986787a46a52fa54e009e5ae697deb725e3797ad62eaf5c03",
     "session_key": "53ab94cbefac77f220e58915a7b4ef56255761a302815f4c5a8d4f4361f8ac8b616c73602a755e380cff5f9060615cb9a2c80df6d9d8ddeba8c973f4e2cae549"
@@ -332,7 +332,7 @@ pub static TEST_VECTOR_P384: &str = r#"
     "credential_finalization": "70b1618194b315fa860fb4f8b1269f1aa0aa7fcc61fc5c871be5481043fb926221faf53320bc50b59048beb4a6eb2d16",
     "client_registration_state": "14051146ed8b782bcec67b902c39a4deaf1c7505c3445ba3ddafd5e8b25f044e4d9d5cfdfc224027472c487fb8b0331b026f48f8591864dc85e976825a9dd842c7af248426010618e625b7e133681e97297fc28d6a3882eec687ab3d80a436abc6",
     "client_login_state": "14051146ed8b782bcec67b902c39a4deaf1c7505c3445ba3ddafd5e8b25f044e4d9d5cfdfc224027472c487fb8b0331b026f48f8591864dc85e976825a9dd842c7af248426010618e625b7e133681e97297fc28d6a3882eec687ab3d80a436abc6811120f15836d3caff504f3957a5f7424297dc3aefa19cb2fa017088362b55a60320d9935c0e8135b508ea75f72085dedd2e756ab8765c26f8969f0dac0ab6a7c5aefb2c879f26d6177cbc8e1a5c915aacb7bebc8c569c8872421541d13abaffaa76f1243e2a3ee96af42238e6917e71df01c51b48fa036eed2a3ff6a5dad6fe87811120f15836d3caff504f3957a5f7424297dc3aefa19cb2fa017088362b55a6",
-    "server_login_state": "f3aa5959d422531460661ef47f20aec52ea0a0dafdf328df11d90c435696dde0de0e586e1c3af22de0b2c53756e0b1b68ea64f740b789b82ac9e14176210c06b830bd57938cf81727171892463b4f11c1543a7893de405c0508bea2a72e69b6d0a84dd1ac4f23d000fe71a40f119bcdb416fab1fba8e1ad4d968fa2e80232d95f56848df4a0420294c30b02df7e5ce44",
+    "server_login_state": "f3aa5959d422531460661ef47f20aec52ea0a0dafdf328df11d90c435696dde0de0e586e1c3af22de0b2c53756e0b1b68ea64f740b789b82ac9e14176210c06b830bd57938cf81727171892463b4f11c1543a7893de405c0508bea2a72e69b6d0a84dd1ac4f23d000fe71a40f119bcdb416fab1fba8e1ad4d968fa2e80232d95f56848df4a0420294c30b02df7e5ce440320d9935c0e8135b508ea75f72085dedd2e756ab8765c26f8969f0dac0ab6a7c5aefb2c879f26d6177cbc8e1a5c915aac0235c701ca67b34b7d538f16ed96a480acdbfd8b716fb4e37c1a7459baff2d5dfdc5c899776396994119f83e9cbbfb914303403427023536efbdbbccb035f343462a90cb7e02ac7f27ed6ca8030b4af35fb8e4a4025c38dd556a1305c21b92abf104",
     "password_file": "03403427023536efbdbbccb035f343462a90cb7e02ac7f27ed6ca8030b4af35fb8e4a4025c38dd556a1305c21b92abf1043fdc29cd29d6fd43ac741b66ddca9b0097c4563a2ee087f66324754bf5974e5ecdd3028d45b3367bb98ba1e3e44d53a8b1ad256d64c77833840d0c576d39b3fe250f4e7fbfd3cb63451b7a8bfabf8c598ff0fd15685256f792398ca143e282611819c873e62bb847a0f3fd23f2e6b5ea0ae8af3def5f4313030200cc3b1f335f",
     "export_key": "939a56d04995e2dd7abcdbf8f8a0282587727eb9949f501364187d5b73f6f77436a316189f6ca249a27bcede28c5c61c",
     "session_key": "0a84dd1ac4f23d000fe71a40f119bcdb416fab1fba8e1ad4d968fa2e80232d95f56848df4a0420294c30b02df7e5ce44"
@@ -368,7 +368,7 @@ pub static TEST_VECTOR_P384_P256: &str = r#"
     "credential_finalization": "a1fa731ff420416939b4e7e970a9cc94dd1fed0c148ba3dd8edec61ecc7e5093",
     "client_registration_state": "a4944aa53d7d3735351d97cee1c2cb1070d1386894efc2937503625bc7835f0502c24cfafadd53197ee094597e500f4bed1d82cf13866cedda372b426dfe9af019",
     "client_login_state": "a4944aa53d7d3735351d97cee1c2cb1070d1386894efc2937503625bc7835f0502c24cfafadd53197ee094597e500f4bed1d82cf13866cedda372b426dfe9af0198c5512a1a0d00b6a771fc2aee228e27f06c29c2baf4d56c5b3240d54e5ef531203060096f01d3455ef36a1987a62b43437c1f615d93fccb9c6f351b8f068a94f77eb1959dfa28d44940dc4e5c05aa99125dccb508dda7516532b2901c94119b0f24369710dfc774828fd37497cc1fd28fb56af32e8aa7fb9cc8015a08f4272a2418c5512a1a0d00b6a771fc2aee228e27f06c29c2baf4d56c5b3240d54e5ef5312",
-    "server_login_state": "c254d6df8ae484e092983d8120986dae4f4a019c36d9ea4b9380addd7caac6e0fa06d5b3e3f2717b11ccdc26c4ab3b3958fd5f6d74951193c375d5b2a8b603fe56bd2671579b7e13ef0bbceec21fb4d84ffc479a7aacb1cf2b22b58f078a729c",
+    "server_login_state": "c254d6df8ae484e092983d8120986dae4f4a019c36d9ea4b9380addd7caac6e0fa06d5b3e3f2717b11ccdc26c4ab3b3958fd5f6d74951193c375d5b2a8b603fe56bd2671579b7e13ef0bbceec21fb4d84ffc479a7aacb1cf2b22b58f078a729c03060096f01d3455ef36a1987a62b43437c1f615d93fccb9c6f351b8f068a94f77eb1959dfa28d44940dc4e5c05aa991250212226eda8d14dc16f80ff996127c6c2ffbbe2c175b758c86465655a0b1b8378df6e5d711996b2699e9b5e216c3bd8ce30244fcc63ecf09c89edfd93924984b600dff0ab11190a70de55a6417b46276b594c6d64a2f279db6345f92a024ac17ca6f",
     "password_file": "0244fcc63ecf09c89edfd93924984b600dff0ab11190a70de55a6417b46276b594c6d64a2f279db6345f92a024ac17ca6f08684194ed2ba879165dd8b4f22e251bba0f6f0239462337a3e044d1f1f0793bf5773140577e8ac202bade3a2abb6f82843fc734c517091ebeeafd89dd25707f476cd7053e72117c4cbe614a68d214f3d0b61a1c0534e104ddffea1e7fce1f32",
     "export_key": "2b7ea0e43e09aaf2162bac86036d9a81ad7cfe53547a5be615e35bbacfca63dc",
     "session_key": "56bd2671579b7e13ef0bbceec21fb4d84ffc479a7aacb1cf2b22b58f078a729c"
@@ -404,7 +404,7 @@ pub static TEST_VECTOR_P384_P521: &str = r#"
     "credential_finalization": "9505c71ed5827eec8bc8a5ff79446d0fafb0ca55fa5da5c68a66bd82dd5958f01e200306b0d8265e75068a6e5cec6da4681d986a015f27d770908d4306c84aa2",
     "client_registration_state": "00820bd8ba8c3a7c62bcbc9fb97a71d793fb0c9f0b0d7008f647492f9c0e943642156fd19020eaea773d8c3d9ef678359f181bee2d407254873def7bfa1063e0cd9a02013645a85ce6311a4dacacc6f0893ad5eec63725dc0f99e8d80451aff04bbf8e7fdff3be3ff118cdfc476b33d11897178c68f8f95d525822625f9844403db7838eef",
     "client_login_state": "00820bd8ba8c3a7c62bcbc9fb97a71d793fb0c9f0b0d7008f647492f9c0e943642156fd19020eaea773d8c3d9ef678359f181bee2d407254873def7bfa1063e0cd9a02013645a85ce6311a4dacacc6f0893ad5eec63725dc0f99e8d80451aff04bbf8e7fdff3be3ff118cdfc476b33d11897178c68f8f95d525822625f9844403db7838eef7c951e078f852dba7d0b18e3d3da97bf0e986a4c145df53b630653725d2b425902f072b105b96a20f3309e7bc3d5f982567544ab460190e6176f2d455de13886bc7bd15bc2030a77f7c1bdb7b67a90b9784e2ba41ac03c4c6a66b963060ec87a35b45bbbb772e3907c2213c14fe5149c17c2fb37f722b20f626669771056eb71917c951e078f852dba7d0b18e3d3da97bf0e986a4c145df53b630653725d2b4259",
-    "server_login_state": "6f609696f70ecfec6285b21417d5bec4de5579b498d71f32dec2771f75aded3d8f32d88f5fcbda0b1428608df443f60ce07dbc2871350cca7f278fb04fc22e4cca973589b356690803a22dcf7fd85bbcc9af226fc9d209af985c2f622e5c0156d884de6dfa9ee0476e1861755af7d18b1861023a436c861c4d26b164adb7d55eba086fba54ba8b3a63c9d7ef5e06b0f990c28f11af4b4d3b62ab99be6bd80ff82f4f3aa791fe062de4595b4373fd7256ad117837e243de9394bf48ed68bcd113",
+    "server_login_state": "6f609696f70ecfec6285b21417d5bec4de5579b498d71f32dec2771f75aded3d8f32d88f5fcbda0b1428608df443f60ce07dbc2871350cca7f278fb04fc22e4cca973589b356690803a22dcf7fd85bbcc9af226fc9d209af985c2f622e5c0156d884de6dfa9ee0476e1861755af7d18b1861023a436c861c4d26b164adb7d55eba086fba54ba8b3a63c9d7ef5e06b0f990c28f11af4b4d3b62ab99be6bd80ff82f4f3aa791fe062de4595b4373fd7256ad117837e243de9394bf48ed68bcd11302f072b105b96a20f3309e7bc3d5f982567544ab460190e6176f2d455de13886bc7bd15bc2030a77f7c1bdb7b67a90b97802c5f2406a316c1b783100b61d8ca76123a23c36bf21e3d421ac60fea1ff7d9479bc2948be77e6af4048260f4038ab878202db7c3c6626803154fdd89113efa552ad77925c507b0a453e596ce1299ba5f0eda55f45203d77a17190a2b928f752a840",
     "password_file": "02db7c3c6626803154fdd89113efa552ad77925c507b0a453e596ce1299ba5f0eda55f45203d77a17190a2b928f752a840757cdaa6468e270aa0a2a2e919918d439a18f13c1ce30bd250900401f6bc74665c1609bf7f0e0449698e7f4fb0f368c5b2c712b3046612ab1bcf5e010f068929fc0c112a63173a7810e89d86a9f66e1e60b4f176973b50678e6fcb45d344a19255fa2e7725cb45d1c696a493549b7dea87c270a1eeb88e2d4829561d49e5b90a8e4c3126bad32c712082a1bd19eaf1f1fc5bb3530cd4060ec7c7f0c45f78ee13",
     "export_key": "5d1f5949dd36d4810cfe3be8df2c5523cf34e1ca9e6e517a50a600bd5ded8dbc38ed9f84eee1c1766325b22b78768045a61920ccadf0123d8244ada47b528713",
     "session_key": "ba086fba54ba8b3a63c9d7ef5e06b0f990c28f11af4b4d3b62ab99be6bd80ff82f4f3aa791fe062de4595b4373fd7256ad117837e243de9394bf48ed68bcd113"
@@ -441,7 +441,7 @@ pub static TEST_VECTOR_P384_RISTRETTO255: &str = r#"
     "credential_finalization": "88045925ed64b518ca45b6b3af35820b9babec206cd89edc4958471af45f58a0d840a6f28b9e64fffcda7e8c02ff8f05628b35c545955672744bc35af1974a1e",
     "client_registration_state": "7561121511345f82a5197d0745076b1cf6e809495aecbeec71e6da4f25f42109465cf52e606bd0d47be00055c1c557b7ce1b7db023aa4fde92b1721b6d110264",
     "client_login_state": "7561121511345f82a5197d0745076b1cf6e809495aecbeec71e6da4f25f42109465cf52e606bd0d47be00055c1c557b7ce1b7db023aa4fde92b1721b6d110264fdcf1dcf5ce229482e57f2ce0bcb8ce4d78d8e4ea4cf4385a4f9787b576a50af026f89c7150c89bdffffaf8570316d362492bd102d391ede0837940f60e202b67dcaf6b6ffbafbe13a8b1a95f19b571be2d3006541d54b4c75e80a7425ca8c84f37ec04349e294a4ffdb310891954dce0f6209e4eaa799fa811bd4e959a501be40fdcf1dcf5ce229482e57f2ce0bcb8ce4d78d8e4ea4cf4385a4f9787b576a50af",
-    "server_login_state": "55c65cb0075961368cfb05d424392545df4cec3f5eb58f4821b6f0484777d43a63ce723c1502fcf95aac3ae8a2075d3af529a4e6921c431752ceb5db6ed146dba521b6cebaeafbd3c2602946fd6955b80402319de194977b71c1395ed5a6dee7fe0de82b650f41f2518f233b824c58af43dc36d331b1940f1fe7105190416c9d4caf4e5ceafcfa7c18d74bff2f7cb66585dd493c64dc10e808bfa5c8f7342ee8d56d1d59b7ebc881be5e828ad61f1162ec10a34d8920d97e92b05bee36b3dce4",
+    "server_login_state": "55c65cb0075961368cfb05d424392545df4cec3f5eb58f4821b6f0484777d43a63ce723c1502fcf95aac3ae8a2075d3af529a4e6921c431752ceb5db6ed146dba521b6cebaeafbd3c2602946fd6955b80402319de194977b71c1395ed5a6dee7fe0de82b650f41f2518f233b824c58af43dc36d331b1940f1fe7105190416c9d4caf4e5ceafcfa7c18d74bff2f7cb66585dd493c64dc10e808bfa5c8f7342ee8d56d1d59b7ebc881be5e828ad61f1162ec10a34d8920d97e92b05bee36b3dce4026f89c7150c89bdffffaf8570316d362492bd102d391ede0837940f60e202b67dcaf6b6ffbafbe13a8b1a95f19b571be203e0150240a2b4ea5326e4ad0d33d3052a3d7fc95e9e9db836b1922033d18046f570cc3c37663605d88b99138403fe0af503a578c65fe4d802b4fcd05ffa5e965c7e8b7d9c0dfec03aad39c75922f9431729548f7a6d5703edb635ee228f426778e2",
     "password_file": "03a578c65fe4d802b4fcd05ffa5e965c7e8b7d9c0dfec03aad39c75922f9431729548f7a6d5703edb635ee228f426778e2f9637fffd924422be36b88bf11e193f47a6a8131ebd8e644479d321844f15a10ab29752f5557ad03c71dd0c9804cf52522e94462641eed27698773a5d45aa1c8f217994652e109e6e6d69a498e5d79abe1eec3d9bd3dba2fc9b027344a35382be1236ff53058ee8b1bff087fad3c510d0440dea4b649087a11080958199561b50d99415fff92b67b701f1b904d84a7545981efee1fc7fa67896fc74c38041717",
     "export_key": "60c276cb9c768c94d3f37d839e01022daaf34b12a62eeed5c45577e2365a67428235c236b267efc7bb1e29d45adbfbac768b8402c53d304e99c1331b4bba5055",
     "session_key": "4caf4e5ceafcfa7c18d74bff2f7cb66585dd493c64dc10e808bfa5c8f7342ee8d56d1d59b7ebc881be5e828ad61f1162ec10a34d8920d97e92b05bee36b3dce4"
@@ -477,7 +477,7 @@ pub static TEST_VECTOR_P521: &str = r#"
     "credential_finalization": "5d46d71394d579936497fab61312fb35559d4ee016f3b4dc64d53f106902ed3af7dd6ecb163e1c07ed2350edc0b7a0196c43084e65544e60697b5c94f0c60131",
     "client_registration_state": "01d53d0742dea86cf835400c5f63e6944dcd5bef624954eaed6bc01e938dd80b4bf6c380bbd8b4fbec55395383f1febfccc48bda5d32c786e2ba1f476bc694da26820300bdcb719e1151f90f6c4c112db037dd019871509fb97a2fe80d0c454be9b6a6c92b8b0c17ec259336772de66d9c2bcdd6b367c184656f35382ac57f8126184e517e",
     "client_login_state": "01d53d0742dea86cf835400c5f63e6944dcd5bef624954eaed6bc01e938dd80b4bf6c380bbd8b4fbec55395383f1febfccc48bda5d32c786e2ba1f476bc694da26820300bdcb719e1151f90f6c4c112db037dd019871509fb97a2fe80d0c454be9b6a6c92b8b0c17ec259336772de66d9c2bcdd6b367c184656f35382ac57f8126184e517ef291c492b0493d3ea43f6d675e6675abddae2b2c519631b06662866c6ba645af020085e98bf7fcaee49362bc264ba627d799cdc549d5a151ba72f6369b2105a3dcf2fd1e06c453012685c207a2ee4aaf67a69c3df678ae373e0849a1ae1a3ecd0f0d6a000f16ba3799e6a70d957b8f1f43e65dcd8ad03b55486569e708deddf2e649bfce5addc58e91f1e74b13978dc7d7d311b44cbb83f5a179c85a81c028a91b2b6f4595f291c492b0493d3ea43f6d675e6675abddae2b2c519631b06662866c6ba645af",
-    "server_login_state": "d6286ff49733a4370a58d3d312f70aa693d1a50ab789b25b9daac211d6546f49dc109848d78ff27c8505cd214d491ae4e461dd8e5da9c73ac22e2300daa9b0136490c1e83b765a6940a3f58a7179d4270c5aec733f4587235ebb81e1eec5abbc6627b4124e56fae739d86537116ee09b4029a41b5aa668f18c95aa77b423971a0d88ae45515bfcae0eb67fa549af728f6b3868fce6567f75735bc13206cfbdeb71038fe9eee43772c108649fd9f744c4c7b1c98300247aa5b5889509a6aa0991",
+    "server_login_state": "d6286ff49733a4370a58d3d312f70aa693d1a50ab789b25b9daac211d6546f49dc109848d78ff27c8505cd214d491ae4e461dd8e5da9c73ac22e2300daa9b0136490c1e83b765a6940a3f58a7179d4270c5aec733f4587235ebb81e1eec5abbc6627b4124e56fae739d86537116ee09b4029a41b5aa668f18c95aa77b423971a0d88ae45515bfcae0eb67fa549af728f6b3868fce6567f75735bc13206cfbdeb71038fe9eee43772c108649fd9f744c4c7b1c98300247aa5b5889509a6aa0991020085e98bf7fcaee49362bc264ba627d799cdc549d5a151ba72f6369b2105a3dcf2fd1e06c453012685c207a2ee4aaf67a69c3df678ae373e0849a1ae1a3ecd0f0d6a03007363ac908a029b36a27e79c67d36f9805a96d67123f83dd1b71e50c5baa5cda6072a394f6de7adc2854da53e8d55e49724937b93ab979b4c5946a870a73a9d16740201aada3f53fb6031afff983dbc2d87e41746f19cb1b2483cfbe5f9e05c42cdccbecfbb1c471f5608ab53b7b99b787697fc7ca54948444800468d18f58fb764371f9e",
     "password_file": "0201aada3f53fb6031afff983dbc2d87e41746f19cb1b2483cfbe5f9e05c42cdccbecfbb1c471f5608ab53b7b99b787697fc7ca54948444800468d18f58fb764371f9e1cad97fe383169e0cedea4d7a792988d9a212f909d79f4502d49a0b1bc7dafde0f2cedafced5400cb23eb151c5590e4f3af04318973beb03070b1bb428eb80f10159d841bafbcd84b891668fd25ad0cfe3d4a6fd7c9dc4cc24845dcbffe4bd1f2ff6938bb5e57ace14c61a0873a810244e5e2391d1f2a0bd5451f738532d8948caab74c358dee6057b9de2b13a0fcf43567a52ff836d9b5c4af48c1e6170ff69",
     "export_key": "b321ee27fca6499d3a395ab10ad1428b59940ca154d24c5ff6106cd0029fc9bb3df11f9aeb49b82b875f59e5354ca5861250d3745b53f4d21d85bafbc6cbe9d2",
     "session_key": "0d88ae45515bfcae0eb67fa549af728f6b3868fce6567f75735bc13206cfbdeb71038fe9eee43772c108649fd9f744c4c7b1c98300247aa5b5889509a6aa0991"
@@ -513,7 +513,7 @@ pub static TEST_VECTOR_P521_P256: &str = r#"
     "credential_finalization": "0a6edabbf67a6c45d4e6bd456915bf9c842f70c4cbd0f05d60c5073c46a00ed2",
     "client_registration_state": "b7a45bb40fdcbc48dab6175b7afdd255e239d04ba1c03da41619e6cca75ab675030f8ca3c03824a1e09dcd43cf3b6c13474f3d8c9d2b271c91fad320cb0968b378",
     "client_login_state": "b7a45bb40fdcbc48dab6175b7afdd255e239d04ba1c03da41619e6cca75ab675030f8ca3c03824a1e09dcd43cf3b6c13474f3d8c9d2b271c91fad320cb0968b37889e40a5c268ff8238209a2541f9a4a58cf8d1f61f30c49ecd36426448fbcb7050200cbee16fa009cb4cbb6768af98b7cfec57d8abd5c0a48ceb96e3346436926f465180b9f99dc9c0fc5356344ea741bf3449a8964645d1113c7266e95f7034529eaa3009f150be53d7b352923df1ff345a8e32af621fb547e0ee8fecd97c984188e214d70384dd1bc409ae0f56bf04bb70f59f18dfaf1caa322159eb1904a48eb5758bf4b89e40a5c268ff8238209a2541f9a4a58cf8d1f61f30c49ecd36426448fbcb705",
-    "server_login_state": "03bb0135a05f6894c25550c25de788b3a80f305d4b4f67935f143d9a9778d7a9e2743c32915424143c3233fe5c05baba3179bafb3d7ce77e94b405d4745151ed7bfccb7d3de5e5b25b5eb654d60b660ff6800da8d2737fdae8a8ba422d5b4239",
+    "server_login_state": "03bb0135a05f6894c25550c25de788b3a80f305d4b4f67935f143d9a9778d7a9e2743c32915424143c3233fe5c05baba3179bafb3d7ce77e94b405d4745151ed7bfccb7d3de5e5b25b5eb654d60b660ff6800da8d2737fdae8a8ba422d5b42390200cbee16fa009cb4cbb6768af98b7cfec57d8abd5c0a48ceb96e3346436926f465180b9f99dc9c0fc5356344ea741bf3449a8964645d1113c7266e95f7034529eaa30200c3eee50b586317c123b0e84625a77f3149f145ca56562153984495a0edbc1d7e9425f43d67793ddd667e26ab1fb81d0bee13fce15cf21d7bf3e0b552a37f0fd3a10300990dfdeddb00e2d64e90ea895f7d8b911a545206d7902c3f5e5e017b544d2e2bac701af1e587f46f2161cc34b3ce56a50f408c46ee21fb20b8013851189e34b566",
     "password_file": "0300990dfdeddb00e2d64e90ea895f7d8b911a545206d7902c3f5e5e017b544d2e2bac701af1e587f46f2161cc34b3ce56a50f408c46ee21fb20b8013851189e34b56653242f342b085450a088a4b4e09207684fe2674a61c7ea0c87c0f9266960094800a5e78b13c731614b14c348ee1ae085948f0de613071915b6c9e2e54bc27da68bd82df25787d11501e25973ec94a7a2aef7bfc8fa861dd2413e48a5774ea67e",
     "export_key": "3d307f7ceb83ffa41fbff4a411dfe1ee89771fd3c9862bffc3c29a47afdfa092",
     "session_key": "7bfccb7d3de5e5b25b5eb654d60b660ff6800da8d2737fdae8a8ba422d5b4239"
@@ -549,7 +549,7 @@ pub static TEST_VECTOR_P521_P384: &str = r#"
     "credential_finalization": "74d55fed076ca357fc42d6d810490be30cf1f66c6aace86f1eb19a9f02448b12076b8330d1342678caa63357eb1666a4",
     "client_registration_state": "28f753c3e4f129ceb6a1343f03bcb5b942ae2d5a856d5ef9b105ed1e6c28a7165caf822920d9b6fad903cd9ce911988102a1d37e5168c0bc187cb43b7f17ad1cd5dbbcb34c9a7f545d96125c4cdc61ca4771acc8184daf4a9103d44f53de6b647c",
     "client_login_state": "28f753c3e4f129ceb6a1343f03bcb5b942ae2d5a856d5ef9b105ed1e6c28a7165caf822920d9b6fad903cd9ce911988102a1d37e5168c0bc187cb43b7f17ad1cd5dbbcb34c9a7f545d96125c4cdc61ca4771acc8184daf4a9103d44f53de6b647cd5884faa28baca9f5cdcf15738869edf02f31a954f5c4581dcb065291e503c630300cc10de7db87e0a652ba9db5d051699ad978be3a1f66b86367d37985001b45c80a8c411fcf333d0e7fdfc3d5f72a4843d2ae13d48c660d2540ac0cff8f0899f9277013f46e72bda9671fa3fa9fefad740729f510a01f1895767ae3621b6d1bbee551860f587f7b4dde887dcaa6ed45ebadb2d89e4deb6620352af7015b1182644e4f5cad5884faa28baca9f5cdcf15738869edf02f31a954f5c4581dcb065291e503c63",
-    "server_login_state": "274c683c4bd0427b1e90fa56f2366247fc8b8f6874fe2e268f0fb27ead7f884bbc6fc74fdfbd6bc63e70210ea3287550b4289494ead368a4a39bcdd2f4299abad33278f31c19fdbe42d5154067a2c5436f8e3d559037d547e567c654bb625640fdb6cd4a235e4e7e131dadcf9db64b567392c58bd01704963f4ce99c48c5d6a03816cbff976738d612309b582cc3a516",
+    "server_login_state": "274c683c4bd0427b1e90fa56f2366247fc8b8f6874fe2e268f0fb27ead7f884bbc6fc74fdfbd6bc63e70210ea3287550b4289494ead368a4a39bcdd2f4299abad33278f31c19fdbe42d5154067a2c5436f8e3d559037d547e567c654bb625640fdb6cd4a235e4e7e131dadcf9db64b567392c58bd01704963f4ce99c48c5d6a03816cbff976738d612309b582cc3a5160300cc10de7db87e0a652ba9db5d051699ad978be3a1f66b86367d37985001b45c80a8c411fcf333d0e7fdfc3d5f72a4843d2ae13d48c660d2540ac0cff8f0899f92770300131f6bd571cace2f3e5f7f9d30a74718f5b85a9419df3d9396cdb7f8c988c09d92bb2427411cf8742b362f001c75fdef73c1c90c9f09635dd757bdb1f5b47b86060201d7a3f69092e2fe5ae4980aaf396a9792b67ae32a7f1bb2805473f1e58ae8a5e39774b1fa602ace55fc11b5a729342cd72717835b15307ff3044d97b4fa0f831736",
     "password_file": "0201d7a3f69092e2fe5ae4980aaf396a9792b67ae32a7f1bb2805473f1e58ae8a5e39774b1fa602ace55fc11b5a729342cd72717835b15307ff3044d97b4fa0f831736c66b123f324599018950cbcf0e5bb6b6d7c240757e0c7cd943d8634ded57d33a9cfe4667ca889b0de6949c56fc290a950184d9f698348306ddd6da32d5952ed19fc986b26927abd5c33956d9c496520991d3a01faaf8200b9c5ae6975b3fc980c7b05c06cd832be6b45bac4e5c82af6ac68129af18e4c7b0f2b611c5e921cc26",
     "export_key": "3161d922d75a098464ae080a1a969452a31679caef02011f25a5497aaed7ba687ca7067744e2e32a4a98438c0db6d3eb",
     "session_key": "fdb6cd4a235e4e7e131dadcf9db64b567392c58bd01704963f4ce99c48c5d6a03816cbff976738d612309b582cc3a516"
@@ -586,7 +586,7 @@ pub static TEST_VECTOR_P521_RISTRETTO255: &str = r#"
     "credential_finalization": "7ecee6ec700bf5d3ab1d6d12d712c26ed5991e262411947904d1dcd74072cc7594273b3bbcf5281156693092d2712bc82a6c09aacdd88a1e50a3671af295b9ce",
     "client_registration_state": "7f6822f06ba40f0089575925d1996a39a20e7c4f550291bf351eabdabf77930812c9d1648e724bbdde4c2717a42a00992e5c825895adc26976f792cc469f4f40",
     "client_login_state": "7f6822f06ba40f0089575925d1996a39a20e7c4f550291bf351eabdabf77930812c9d1648e724bbdde4c2717a42a00992e5c825895adc26976f792cc469f4f404b1fbc0d307e77109344f2ee9702eca6cd47139a519044421850b3a5a744b80f02016deb800dc30cd6e1b443ed0a97c1bca14c66c1b2eb3e0179a9775c3e00705780d4f8b6b3e5b68cd3e104247b0bdd80078761527c3b08e42b92c957eb94da99168c0062824eee3e9829988af023518bf839df7c9a503796228e2c8d9bb678a1e5fb6602b52c3c46505ea29d03065e44d2ebf8fef95b1ef5e30fac62530bb22c4184c2334b1fbc0d307e77109344f2ee9702eca6cd47139a519044421850b3a5a744b80f",
-    "server_login_state": "016b3a6312c6d960da61e6feb1714fdfdbf778e51134d895a22ba02cf971af8b26fd9774f0ed6099d4d6eab227dc75e69bee9ad8e6d1709e7ad0ad0b34b8673596f1d5334d0f4d219ba92ea2e7960dd4739a3983a90ec7901a0788a16ffe0a448f519a8c90d3daa5b725ca1969cf589ef672a409a8250150bcf041f039a439198b6e3941373e46d39da3eaf963049bc17ad26e7dbdd5c7c1359967cb997b9d1bf01795d00b20e6b1690712c6c36858a71f2f2fab06dac0ceb2d0f47dceb7afc0",
+    "server_login_state": "016b3a6312c6d960da61e6feb1714fdfdbf778e51134d895a22ba02cf971af8b26fd9774f0ed6099d4d6eab227dc75e69bee9ad8e6d1709e7ad0ad0b34b8673596f1d5334d0f4d219ba92ea2e7960dd4739a3983a90ec7901a0788a16ffe0a448f519a8c90d3daa5b725ca1969cf589ef672a409a8250150bcf041f039a439198b6e3941373e46d39da3eaf963049bc17ad26e7dbdd5c7c1359967cb997b9d1bf01795d00b20e6b1690712c6c36858a71f2f2fab06dac0ceb2d0f47dceb7afc002016deb800dc30cd6e1b443ed0a97c1bca14c66c1b2eb3e0179a9775c3e00705780d4f8b6b3e5b68cd3e104247b0bdd80078761527c3b08e42b92c957eb94da99168c0200c5820422e722f17f153b27b47f06767feabe9f08970bbfa173c032698df288ebb1b5b1552492706cf653b0cc709166f7d2a99e03ea6ebbe2bc50236e1f977ca74d0300f34695787b243746fe6880493c8b424aa5a392b3a25e06bc4c5bb7fe7ab94945ac302c142f7802960de91ac23da80edbce2ad472800d4344f4d5ff7c1a892917aa",
     "password_file": "0300f34695787b243746fe6880493c8b424aa5a392b3a25e06bc4c5bb7fe7ab94945ac302c142f7802960de91ac23da80edbce2ad472800d4344f4d5ff7c1a892917aac28b7f50411ab9bc221eae90a7d0892e48554b57c8cf0ce5d4f93b3a9c802e50b1dbd67084bfcdca076dc7dfc87dd0fd0820acb001f4a46ce11fd3b01cc082fe0155153fa6146292c7d715b3f9a4df097177d523acc855570c70d2b0b1da308080e5c782c971e615a2daad2feab765690e90c412ec6c11808cfc278689b362f7269d2c50413ac5e84e1623cdaf5ce252f69eea03744dc935cc4a9ec1ced75caf",
     "export_key": "6411891269a792b0a955d3d44bbdaeb04610ab8aa3ef67b193eadc83d8bf03ae3dd862e658860897654eb13089e594a27b98aa0cecb9e2dc7a96435e7c7c8dc3",
     "session_key": "8b6e3941373e46d39da3eaf963049bc17ad26e7dbdd5c7c1359967cb997b9d1bf01795d00b20e6b1690712c6c36858a71f2f2fab06dac0ceb2d0f47dceb7afc0"
@@ -623,7 +623,7 @@ pub static TEST_VECTOR_CURVE25519_P256: &str = r#"
     "credential_finalization": "165313d2ea22ecbf5bea4c51403e23c5499ce0fcfd310e82e18548af6d01bc79",
     "client_registration_state": "9dd01eb98a98117adbf61e6b47bb792f37946dd650119bedad41d707aaa6cedc0370df57e0dd97bf7b63d857e2a78643a2859e6181eabb7f172869ef8dec1e8855",
     "client_login_state": "9dd01eb98a98117adbf61e6b47bb792f37946dd650119bedad41d707aaa6cedc0370df57e0dd97bf7b63d857e2a78643a2859e6181eabb7f172869ef8dec1e885528c3b59d6ca159f449ef24ace855da639816cfc3e8eb831936819b985e097a66409d5c3e32b2b2179191bca50fabaf6298deb4d4563765aeff680c3c2f5c8c15900dbf928953244d76f93d00915eaae392764a52d5958893514309356c32cd5728c3b59d6ca159f449ef24ace855da639816cfc3e8eb831936819b985e097a66",
-    "server_login_state": "d3a292fd12038a20628b0e1c795a7fa62aca3ffc5161252c05bcee2d2c9f3c57fbd539b9e16092b76099587f21a5d6f53edf0d3965658dbd55e80f60a1271de6bae6d3a6c1ea344ae1447f450e4a7054fcc8469c536ad82478e246cf1f64f618",
+    "server_login_state": "d3a292fd12038a20628b0e1c795a7fa62aca3ffc5161252c05bcee2d2c9f3c57fbd539b9e16092b76099587f21a5d6f53edf0d3965658dbd55e80f60a1271de6bae6d3a6c1ea344ae1447f450e4a7054fcc8469c536ad82478e246cf1f64f618409d5c3e32b2b2179191bca50fabaf6298deb4d4563765aeff680c3c2f5c8c15210c150aa648ca52dca1e24a346363c24390f040a2d470847a93e5e27fb24157398af8860b5b97dfe3e535b84adee179d07dc6e54300d277b570eb5f9402f10d",
     "password_file": "398af8860b5b97dfe3e535b84adee179d07dc6e54300d277b570eb5f9402f10dabd9ed4ebbdc3086c316d8abb6e6a6b733042932f8e898fb06bb740d807c7efb90965c6cb443523e451872764a3abb1dfd4ed9369e33b66c5e3c91afbf2e27575593168bd4ef4413ca76e44b6abcc704f72351d51e23e433dd326ebe3dde0856",
     "export_key": "f1ad9cda4afaa1247a368201f3fa97ad22895f6ffaf80ab6a27fc7eb32567c85",
     "session_key": "bae6d3a6c1ea344ae1447f450e4a7054fcc8469c536ad82478e246cf1f64f618"
@@ -660,7 +660,7 @@ pub static TEST_VECTOR_CURVE25519_P384: &str = r#"
     "credential_finalization": "470c26069718f2654ae15fc6f8da8a562e1b2e5f1f7de41af868f8543e8e918e99b389787483fc167f12538ea1afb57f",
     "client_registration_state": "e5f905e484b233c70aa996ef816e0404e375e3a0e6b91d3017f7dcbe5b8ad31931548f02ceb851e93b9d487ce0f256e60274c87f3d7b5d97d0ecfac4b4effe4423d7d67dd877ba9639a6d850f226cbc6925ef891418365426b7047be915aa78394",
     "client_login_state": "e5f905e484b233c70aa996ef816e0404e375e3a0e6b91d3017f7dcbe5b8ad31931548f02ceb851e93b9d487ce0f256e60274c87f3d7b5d97d0ecfac4b4effe4423d7d67dd877ba9639a6d850f226cbc6925ef891418365426b7047be915aa783949f35b5b7d1c604139a2d9aa24d6108d9d54ab4414a3dc7a30ab0c966b11c4e9ad54762285880bd21c3471c90eee165d53c1504ac4b142d470f30c416a2aea073c868e0a5e0db07e903eab983d2806ea6b45cec0d30fbc6e9f6ca4859fa9226619f35b5b7d1c604139a2d9aa24d6108d9d54ab4414a3dc7a30ab0c966b11c4e9a",
-    "server_login_state": "32de46ce527efc9e1e93c8d87152bf08f333c97ed7b9a7330fb343fe0508d3ce7f9d2e0f6097c997fe5da8b4068c05f1221500906d5743ebb53fd38f705addbc50fca346b892f66f86bd83054a407855c1e46b92f85d01a090ab73bcbe2195058a0c2c13312d03de4861a067c79fd2c2343e6c5c43c946a6e5b1ddd38f6b9693f290e6f86e3121f7dd3c67b4d1f8baa1",
+    "server_login_state": "32de46ce527efc9e1e93c8d87152bf08f333c97ed7b9a7330fb343fe0508d3ce7f9d2e0f6097c997fe5da8b4068c05f1221500906d5743ebb53fd38f705addbc50fca346b892f66f86bd83054a407855c1e46b92f85d01a090ab73bcbe2195058a0c2c13312d03de4861a067c79fd2c2343e6c5c43c946a6e5b1ddd38f6b9693f290e6f86e3121f7dd3c67b4d1f8baa1d54762285880bd21c3471c90eee165d53c1504ac4b142d470f30c416a2aea073d051bc53f5b2b408cad4949923869e613a0680e98f5d17b13fb3cb7dcee0271ca640dd4e580e445339b7197941249f0da676480ed219a471a165fd6724a06871",
     "password_file": "a640dd4e580e445339b7197941249f0da676480ed219a471a165fd6724a06871338ab60d552b9695e7830612c0dbdc63350a15ce5e07449f2d073429e30e502cd554c17594d293239ce1e1519d5844eb18cdf990fba0921839754c6cf59b4130faec08a6b5bae26d26ec14ca1a312468efa19c73a471983de466090dc7f59797b13756916d6ec7c671f1d130b219d9a4270a8f6292145becb0093c6609442068",
     "export_key": "024c1f4b9304679986790e08c90f4d29b7c7326eca259b6fedf49041eb52a45d1aa3d5f4ca1971e5c3603ae364a25f28",
     "session_key": "8a0c2c13312d03de4861a067c79fd2c2343e6c5c43c946a6e5b1ddd38f6b9693f290e6f86e3121f7dd3c67b4d1f8baa1"
@@ -697,7 +697,7 @@ pub static TEST_VECTOR_CURVE25519_P521: &str = r#"
     "credential_finalization": "7324f1b61c676ff9fd864fbef35ebe16e1a4f58a06616f605eda73519e406d263b302050511af364af90b64869fa68c511b82cddddb80234f1b373ac536a3510",
     "client_registration_state": "003746c5d4296be07e02180c09ded1b02818899d95467c4c31036f885a250c4355a7df93a6fa1a80f7d7d5f7305219d7dff2ff1feffd90ad30528fe5b4efac77b78c0300652dbedb61b4db66d29cbf2503e421b3c3a016b076342dcbe5eab869098a01eb6a58406310c2bcda53d560987356242a02629c628edfab14e5fca8529e84b5612c",
     "client_login_state": "003746c5d4296be07e02180c09ded1b02818899d95467c4c31036f885a250c4355a7df93a6fa1a80f7d7d5f7305219d7dff2ff1feffd90ad30528fe5b4efac77b78c0300652dbedb61b4db66d29cbf2503e421b3c3a016b076342dcbe5eab869098a01eb6a58406310c2bcda53d560987356242a02629c628edfab14e5fca8529e84b5612ca8ea6cc47d48ce1e98fd2ab2f5274811fdaa52463674ae68c5e240bc21c7ab1d76da0d06a99c1a4b5a597a21c07160233f97e29b32b5e16ad4f14f4abbd4c61ab0d9e818f3509807010df9e4ac2814e638bb59ff393c98bc884aa5e9df47a368a8ea6cc47d48ce1e98fd2ab2f5274811fdaa52463674ae68c5e240bc21c7ab1d",
-    "server_login_state": "8abbc16af5df318a202c2cc55356b3db9761e4644c05e72d163d8c978dc2f9ac6cfc31f200de3c088bec9d6bc499497d25e3758ff70518a0e5a187114a75859f334c41d8e05df279a19ad0c9388c92dd4c2cb772baac7c2750bd65c2b1ac9309552b6c75197a15b92e74971355956d5b1b1535092db9cec0aa2a574b5aaa88d5c4a6f68e2e3687e267bb57fed54a2b23ba3348b8abebc93c80c3e079b6f633cd6f71126228cf3604436a8686c931827a743291c0cac1dbf81eb9d7a29e88964f",
+    "server_login_state": "8abbc16af5df318a202c2cc55356b3db9761e4644c05e72d163d8c978dc2f9ac6cfc31f200de3c088bec9d6bc499497d25e3758ff70518a0e5a187114a75859f334c41d8e05df279a19ad0c9388c92dd4c2cb772baac7c2750bd65c2b1ac9309552b6c75197a15b92e74971355956d5b1b1535092db9cec0aa2a574b5aaa88d5c4a6f68e2e3687e267bb57fed54a2b23ba3348b8abebc93c80c3e079b6f633cd6f71126228cf3604436a8686c931827a743291c0cac1dbf81eb9d7a29e88964f76da0d06a99c1a4b5a597a21c07160233f97e29b32b5e16ad4f14f4abbd4c61af80d4c62109145d82e85c47ba81214779ab0d36705b21b7e578457b1d677800f3f4769c1d1587c567787ce00e9aab9860c3819b8bb96acc0a5b83cc2d5f06031",
     "password_file": "3f4769c1d1587c567787ce00e9aab9860c3819b8bb96acc0a5b83cc2d5f060316cd6fa876c77cf0b601e3249e7ac7ce55e1cc44a931a254d36dac17f5270a546c36cf74746e2233441f0c3bb203d3c8143d24bf5970b72ff90f5363c7017bbdf704d6fa7bbb63b397bbea0d0854a80bb522b5849de62f38d0f6c2e34cd84ac4a7ff4234a988335eeb6cecddb6c0a388cc81636f5392c0b2870ebf18ad56d77aaba65ca5df9e12ca0fa968d838bea7f5413b6f2c1399a66a60d479eb23dea025b",
     "export_key": "d8d600a733bedf7f32326f2b66fadff76984f5fbf82b4139c81669ede8fc526f301baebf48989cba1e6e6b854ac5e2aba1a6d2862fa0e3b368f0229079b6b7fc",
     "session_key": "c4a6f68e2e3687e267bb57fed54a2b23ba3348b8abebc93c80c3e079b6f633cd6f71126228cf3604436a8686c931827a743291c0cac1dbf81eb9d7a29e88964f"
@@ -734,7 +734,7 @@ pub static TEST_VECTOR_CURVE25519_RISTRETTO255: &str = r#"
     "credential_finalization": "f73a56dc67de8926e62426e849419dea16eb4570e70279726a57197359de88daa3655806bead9edd7b1a6fc633c1a1fa79b13f34b5778d389520600fe82be3e5",
     "client_registration_state": "7259abefb88fdd0a92b62f6d6fd71d532b452a5be0d60f77c5ef9466c17aff08805c06c9e2a64a579da1679c28066b0d311828ba65d30c7abda1a82ffb241728",
     "client_login_state": "7259abefb88fdd0a92b62f6d6fd71d532b452a5be0d60f77c5ef9466c17aff08805c06c9e2a64a579da1679c28066b0d311828ba65d30c7abda1a82ffb241728bf8c182b38731d6c3b2fd539021ba373786b340a715a87ba97d7a88db8f62162742b4b8f679f5e69a4d4cf2170f0e31cb965e8e36252b9556208af5a539c872d40441167dc35503b0973ea25d7bdf3d3ae7a84f0a5886806ed559015b6814d56bf8c182b38731d6c3b2fd539021ba373786b340a715a87ba97d7a88db8f62162",
-    "server_login_state": "10f9d97b73c7db86a9980a9ac1d8833fd4847de708667e377ac22f3df3f90884806311d5a684272ce5e8a4f8594e780c541736531b8c181ec62ed58f7b22ec743d776583defe4983252e39e2bd6fcba7da876acae118e8f928a9d9b3088f5ea0721f7cbc356779bb69a76e2edbc6c4df599433e0922eb2129fdbeee6eceada4506b58894846059a824b5abe8afd4ab401e9609435bd4eeaf5aafb2a2a0b5bf91cbfa109fadd91446a97425f14f60f85fc4716f1b30ae57fd6a02cc0fb3f37a31",
+    "server_login_state": "10f9d97b73c7db86a9980a9ac1d8833fd4847de708667e377ac22f3df3f90884806311d5a684272ce5e8a4f8594e780c541736531b8c181ec62ed58f7b22ec743d776583defe4983252e39e2bd6fcba7da876acae118e8f928a9d9b3088f5ea0721f7cbc356779bb69a76e2edbc6c4df599433e0922eb2129fdbeee6eceada4506b58894846059a824b5abe8afd4ab401e9609435bd4eeaf5aafb2a2a0b5bf91cbfa109fadd91446a97425f14f60f85fc4716f1b30ae57fd6a02cc0fb3f37a31742b4b8f679f5e69a4d4cf2170f0e31cb965e8e36252b9556208af5a539c872dbe87af61ff7b7f4b706f9e9bbadbc48a1f4daaf57b975f6c0f5894bdba808255a962d1ad70eb06beb7302638150a13d22bf7ec4fa5ebcd55da7d24d9257a7f62",
     "password_file": "a962d1ad70eb06beb7302638150a13d22bf7ec4fa5ebcd55da7d24d9257a7f6221d6a85f394f9e3bcf3abddd2401d7b47c639bd5a42a00dbd5a1bf37837701b408964cf5ca70981a2ee7c913caa3931992e0778841a78231cb7cda4d887a117088413ce81f01a5760d1d8ce3e9e74cdca4da73bd58f42787d834f48d83cf906db03db448f3cbb78f49fd18522097e2b5c21d2b8a028e6fde90b2cd1d22103e96d16386ab6be752dff69783915c89028956aa5caee824479cdc401d898daae9e4",
     "export_key": "2553ad41a7a260563a59ce672923b3de1f2826503261c1c2e98cdd28a165a63927d3d9fcd1a27d128c46f4353f46915b6064e2001b0d1fbd3448430d955038fa",
     "session_key": "06b58894846059a824b5abe8afd4ab401e9609435bd4eeaf5aafb2a2a0b5bf91cbfa109fadd91446a97425f14f60f85fc4716f1b30ae57fd6a02cc0fb3f37a31"
@@ -64,3 +64,33 @@ impl RngCore for CycleRng {
 
 // This is meant for testing only
 impl CryptoRng for CycleRng {}
+
+/// An implementation of `RngCore` whose `try_fill_bytes` always fails, while
+/// `fill_bytes` still draws from [`OsRng`](rand::rngs::OsRng), for testing how
+/// RNG failures are reported.
+#[derive(Clone, Copy, Debug)]
+pub struct FailingRng;
+
+impl RngCore for FailingRng {
+    fn next_u32(&mut self) -> u32 {
+        rand::rngs::OsRng.next_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand::rngs::OsRng.next_u64()
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand::rngs::OsRng.fill_bytes(dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), Error> {
+        Err(Error::new("failing RNG"))
+    }
+}
+
+// This is meant for testing only
+impl CryptoRng for FailingRng {}
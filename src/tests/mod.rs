@@ -6,10 +6,13 @@
 // of this source tree. You may select, at your option, one of the above-listed
 // licenses.
 
+mod adversarial;
+mod allocations;
 mod full_test;
 #[rustfmt::skip]
 mod full_test_vectors;
 pub mod mock_rng;
 mod opaque_vectors;
+#[allow(clippy::explicit_counter_loop)]
 mod parser;
 mod test_opaque_vectors;
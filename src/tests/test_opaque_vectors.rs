@@ -12,7 +12,7 @@ use std::vec::Vec;
 
 use digest::core_api::{BlockSizeUser, CoreProxy};
 use digest::OutputSizeUser;
-use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U0, U256};
 use generic_array::{ArrayLength, GenericArray};
 use json::JsonValue;
 use rand::rngs::OsRng;
@@ -24,7 +24,7 @@ use crate::envelope::EnvelopeLen;
 use crate::errors::*;
 use crate::hash::{Hash, OutputSize, ProxyHash};
 use crate::key_exchange::group::KeGroup;
-use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen};
+use crate::key_exchange::traits::{Ke1MessageLen, Ke2MessageLen, MacLen};
 use crate::key_exchange::tripledh::{NonceLen, TripleDh};
 use crate::ksf::Identity;
 use crate::messages::{
@@ -108,7 +108,9 @@ where
         dummy_private_key: {
             match decode(values, "client_private_key") {
                 Some(value) => value,
-                None => CS::KeGroup::serialize_sk(CS::KeGroup::random_sk(&mut OsRng)).to_vec(),
+                None => {
+                    CS::KeGroup::serialize_sk(CS::KeGroup::random_sk(&mut OsRng).unwrap()).to_vec()
+                }
             }
         },
         dummy_masking_key: {
@@ -164,8 +166,9 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Envelope: Nonce + Hash
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
     EnvelopeLen<CS>: ArrayLength<u8>,
     // RegistrationUpload: (KePk + Hash) + Envelope
     <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -209,6 +212,7 @@ fn tests() -> Result<(), ProtocolError> {
             type KeGroup = crate::Ristretto255;
             type KeyExchange = TripleDh;
             type Ksf = Identity;
+            type AppDataLen = U0;
         }
 
         let ristretto_real_tvs = json_to_test_vectors!(
@@ -248,6 +252,7 @@ fn tests() -> Result<(), ProtocolError> {
             type KeGroup = crate::Curve25519;
             type KeyExchange = TripleDh;
             type Ksf = Identity;
+            type AppDataLen = U0;
         }
 
         let ristretto_real_tvs = json_to_test_vectors!(
@@ -285,6 +290,7 @@ fn tests() -> Result<(), ProtocolError> {
         type KeGroup = p256::NistP256;
         type KeyExchange = TripleDh;
         type Ksf = Identity;
+        type AppDataLen = U0;
     }
 
     let p256_real_tvs = json_to_test_vectors!(
@@ -394,8 +400,9 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Envelope: Nonce + Hash
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
     EnvelopeLen<CS>: ArrayLength<u8>,
     // RegistrationUpload: (KePk + Hash) + Envelope
     <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -481,8 +488,9 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Envelope: Nonce + Hash
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
     EnvelopeLen<CS>: ArrayLength<u8>,
     // RegistrationUpload: (KePk + Hash) + Envelope
     <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -490,17 +498,19 @@ where
         ArrayLength<u8> + Add<EnvelopeLen<CS>>,
     RegistrationUploadLen<CS>: ArrayLength<u8>,
     // ServerRegistration = RegistrationUpload
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
     // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
     <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
     Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
     CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
     // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
     CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
@@ -539,6 +549,7 @@ where
                     client: parameters.client_identity.as_deref(),
                     server: parameters.server_identity.as_deref(),
                 },
+                ..Default::default()
             },
         )?;
         assert_eq!(
@@ -569,9 +580,10 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
 {
     for parameters in tvs {
@@ -632,8 +644,9 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // Envelope: Nonce + Hash
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
     EnvelopeLen<CS>: ArrayLength<u8>,
     // RegistrationUpload: (KePk + Hash) + Envelope
     <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
@@ -641,9 +654,10 @@ where
         ArrayLength<u8> + Add<EnvelopeLen<CS>>,
     RegistrationUploadLen<CS>: ArrayLength<u8>,
     // ServerRegistration = RegistrationUpload
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
 {
     for parameters in tvs {
@@ -679,6 +693,7 @@ where
                     client: parameters.client_identity.as_deref(),
                     server: parameters.server_identity.as_deref(),
                 },
+                ..Default::default()
             },
         )?;
 
@@ -704,9 +719,10 @@ where
     <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
     <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
     Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
-    // MaskedResponse: (Nonce + Hash) + KePk
-    NonceLen: Add<OutputSize<OprfHash<CS>>>,
-    Sum<NonceLen, OutputSize<OprfHash<CS>>>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
     MaskedResponseLen<CS>: ArrayLength<u8>,
     // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
     <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
@@ -747,6 +763,7 @@ where
                     client: parameters.client_identity.as_deref(),
                     server: parameters.server_identity.as_deref(),
                 },
+                ..Default::default()
             },
         )?;
         assert_eq!(
@@ -0,0 +1,279 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Stateless throttling of login attempts
+//!
+//! A [`ThrottleToken`] records the number of failed login attempts of a
+//! credential identifier, the time before which the next attempt is rejected
+//! and the time from which the token itself is rejected, authenticated with a
+//! key derived from the
+//! [`ServerSetup`](crate::ServerSetup). The
+//! server sends it to the client as an extension alongside the
+//! [`CredentialResponse`](crate::CredentialResponse), and the client echoes it
+//! alongside its next [`CredentialRequest`](crate::CredentialRequest), so that
+//! the server can enforce per-account retry delays without storing anything.
+//! [`ServerLogin::start_with_throttle`](crate::ServerLogin::start_with_throttle)
+//! checks the echoed token and issues the next one:
+//!
+//! ```ignore
+//! let (server_login_start_result, token) = ServerLogin::start_with_throttle(
+//!     &mut rng,
+//!     &server_setup,
+//!     password_file,
+//!     credential_request,
+//!     credential_identifier,
+//!     ServerLoginStartParameters::default(),
+//!     ThrottleParameters {
+//!         token: echoed_token.as_ref(),
+//!         token_required: recently_failed,
+//!         now,
+//!         backoff: |failures| 1 << failures.min(10),
+//!         lifetime: 3600,
+//!     },
+//! )?;
+//! // Send `token` alongside `server_login_start_result.message`
+//! ```
+//!
+//! Times are given by the caller, in seconds since an arbitrary epoch shared by
+//! all servers using the same [`ServerSetup`](crate::ServerSetup). A client can
+//! always leave out its token to skip the delay, so the server must require
+//! one with [`ThrottleParameters::token_required`] for accounts with recent
+//! failed attempts, which it has to track by other means, e.g. a coarse
+//! per-account flag, or combine the tokens with coarser rate limiting.
+//!
+//! The tokens are advisory only. Nothing ties a token to the latest attempt, so
+//! a client that kept an older token, e.g. the one of its first failed attempt,
+//! can replay it instead of the latest one and only waits for the delay of
+//! that older token. [`ThrottleParameters::lifetime`] bounds how long a token
+//! can be replayed. Servers that need the retry delays to be enforced must
+//! store the number of failed attempts with the account instead.
+
+use core::ops::Add;
+
+use derive_where::derive_where;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Output, OutputSizeUser};
+use generic_array::sequence::Concat;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, Unsigned, U2, U20, U256};
+use generic_array::{ArrayLength, GenericArray};
+use zeroize::Zeroizing;
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::errors::utils::check_slice_size;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::traits::SuiteProvider;
+use crate::provider::Provider;
+use crate::serialization::Input;
+
+static STR_THROTTLE_KEY: &[u8] = b"ThrottleKey";
+
+/// Length of a serialized [`ThrottleToken`]
+pub type ThrottleTokenLen<CS: CipherSuite> = Sum<U20, OutputSize<OprfHash<CS>>>;
+
+/// An authenticated record of failed login attempts, see the [module
+/// documentation](self)
+///
+/// Created with
+/// [`ServerSetup::throttle_token`](crate::ServerSetup::throttle_token)
+/// and checked with
+/// [`ServerSetup::verify_throttle_token`](crate::ServerSetup::verify_throttle_token).
+#[derive_where(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ThrottleToken<CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    failures: u32,
+    not_before: u64,
+    expires_at: u64,
+    tag: Output<OprfHash<CS>>,
+}
+
+impl<CS: CipherSuite> ThrottleToken<CS>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// Returns the number of failed attempts the token was issued for
+    ///
+    /// This is only authenticated after a successful
+    /// [`ServerSetup::verify_throttle_token`](crate::ServerSetup::verify_throttle_token).
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns the time before which the token is rejected
+    ///
+    /// This is only authenticated after a successful
+    /// [`ServerSetup::verify_throttle_token`](crate::ServerSetup::verify_throttle_token).
+    pub fn not_before(&self) -> u64 {
+        self.not_before
+    }
+
+    /// Returns the time from which the token is rejected
+    ///
+    /// This is only authenticated after a successful
+    /// [`ServerSetup::verify_throttle_token`](crate::ServerSetup::verify_throttle_token).
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> GenericArray<u8, ThrottleTokenLen<CS>>
+    where
+        // ThrottleToken: (Failures + NotBefore + ExpiresAt) + Hash
+        U20: Add<OutputSize<OprfHash<CS>>>,
+        ThrottleTokenLen<CS>: ArrayLength<u8>,
+    {
+        GenericArray::from(self.failures.to_be_bytes())
+            .concat(GenericArray::from(self.not_before.to_be_bytes()))
+            .concat(GenericArray::from(self.expires_at.to_be_bytes()))
+            .concat(self.tag.clone())
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_slice = check_slice_size(
+            input,
+            U20::USIZE + OutputSize::<OprfHash<CS>>::USIZE,
+            "throttle_token",
+        )?;
+
+        let mut failures = [0; 4];
+        failures.copy_from_slice(&checked_slice[..4]);
+        let mut not_before = [0; 8];
+        not_before.copy_from_slice(&checked_slice[4..12]);
+        let mut expires_at = [0; 8];
+        expires_at.copy_from_slice(&checked_slice[12..20]);
+
+        Ok(Self {
+            failures: u32::from_be_bytes(failures),
+            not_before: u64::from_be_bytes(not_before),
+            expires_at: u64::from_be_bytes(expires_at),
+            tag: GenericArray::clone_from_slice(&checked_slice[20..]),
+        })
+    }
+
+    pub(crate) fn new(
+        oprf_seed: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+        failures: u32,
+        not_before: u64,
+        expires_at: u64,
+    ) -> Result<Self, ProtocolError> {
+        let tag = SuiteProvider::<CS>::finalize_mac(throttle_mac::<CS>(
+            oprf_seed,
+            credential_identifier,
+            failures,
+            not_before,
+            expires_at,
+        )?);
+
+        Ok(Self {
+            failures,
+            not_before,
+            expires_at,
+            tag,
+        })
+    }
+
+    pub(crate) fn verify(
+        &self,
+        oprf_seed: &Output<OprfHash<CS>>,
+        credential_identifier: &[u8],
+        now: u64,
+    ) -> Result<u32, ProtocolError> {
+        let mac = throttle_mac::<CS>(
+            oprf_seed,
+            credential_identifier,
+            self.failures,
+            self.not_before,
+            self.expires_at,
+        )?;
+
+        if !bool::from(SuiteProvider::<CS>::verify_mac(mac, &self.tag))
+            || now < self.not_before
+            || now >= self.expires_at
+        {
+            return Err(ProtocolError::ThrottleError);
+        }
+
+        Ok(self.failures)
+    }
+}
+
+/// Parameters of
+/// [`ServerLogin::start_with_throttle`](crate::ServerLogin::start_with_throttle)
+#[derive_where(Clone, Copy, Debug)]
+pub struct ThrottleParameters<'a, CS: CipherSuite>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    /// The token echoed by the client, if any
+    pub token: Option<&'a ThrottleToken<CS>>,
+    /// Whether to reject the attempt if the client didn't echo a token
+    pub token_required: bool,
+    /// The current time
+    pub now: u64,
+    /// Returns the delay before the next attempt after the given number of
+    /// failed attempts
+    pub backoff: fn(u32) -> u64,
+    /// How long the issued token is accepted once its delay has passed, which
+    /// bounds how long an older token can be replayed
+    pub lifetime: u64,
+}
+
+// The throttle key is expanded from the OPRF seed with a label that can not
+// collide with the info used for OPRF keys, which always ends in "OprfKey"
+fn throttle_mac<CS: CipherSuite>(
+    oprf_seed: &Output<OprfHash<CS>>,
+    credential_identifier: &[u8],
+    failures: u32,
+    not_before: u64,
+    expires_at: u64,
+) -> Result<<SuiteProvider<CS> as Provider<OprfHash<CS>>>::Mac, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let mut throttle_key = Zeroizing::new(Output::<OprfHash<CS>>::default());
+    SuiteProvider::<CS>::expand(
+        &SuiteProvider::<CS>::from_prk(oprf_seed)?,
+        &[STR_THROTTLE_KEY],
+        &mut throttle_key,
+    )?;
+
+    let credential_identifier = Input::<U2>::from(credential_identifier)?;
+    let mut mac = SuiteProvider::<CS>::new_mac(&throttle_key)?;
+    for bytes in credential_identifier.iter() {
+        SuiteProvider::<CS>::update_mac(&mut mac, bytes);
+    }
+    SuiteProvider::<CS>::update_mac(&mut mac, &failures.to_be_bytes());
+    SuiteProvider::<CS>::update_mac(&mut mac, &not_before.to_be_bytes());
+    SuiteProvider::<CS>::update_mac(&mut mac, &expires_at.to_be_bytes());
+
+    Ok(mac)
+}
@@ -0,0 +1,98 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Equalizing the processing time of the server
+//!
+//! A [dummy](crate::ServerRegistration::dummy) credential response is
+//! indistinguishable from a real one by its content, but the server takes a
+//! different code path to produce it, e.g. skipping the lookup of the password
+//! file, so the time it takes to answer can still reveal whether a client is
+//! registered. A [`LatencyTarget`] pads the processing time of both paths to
+//! the same configurable latency:
+//!
+//! ```ignore
+//! let target = LatencyTarget::new(Duration::from_millis(50));
+//! let timed = target.run(|| {
+//!     let password_file = database.lookup(credential_identifier);
+//!     ServerLogin::start(&mut OsRng, &server_setup, password_file, request, credential_identifier, params)
+//! });
+//! if timed.elapsed > target.target() {
+//!     // The target is too low to hide the difference
+//! }
+//! ```
+//!
+//! [`LatencyTarget::run`] blocks the thread while padding and is available with
+//! the `std` feature. Asynchronous servers should instead measure the elapsed
+//! time themselves and wait for [`LatencyTarget::remaining`] with the timer of
+//! their runtime, so that other tasks can run in the meantime.
+//!
+//! The target should be chosen above the slowest processing time of both
+//! paths under load, including the lookup of the password file. Padding only
+//! hides differences in the time until the response is sent, not in the load
+//! of the server.
+
+use core::time::Duration;
+#[cfg(any(feature = "std", test))]
+use std::time::Instant;
+
+/// A latency that the processing time of the server is padded to, see the
+/// [module documentation](self)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LatencyTarget {
+    target: Duration,
+}
+
+impl LatencyTarget {
+    /// Pads the processing time to `target`
+    pub const fn new(target: Duration) -> Self {
+        Self { target }
+    }
+
+    /// Returns the latency that the processing time is padded to
+    pub const fn target(&self) -> Duration {
+        self.target
+    }
+
+    /// Returns how long to wait after processing for `elapsed` in order to
+    /// reach the target, which is zero if the target was exceeded
+    pub fn remaining(&self, elapsed: Duration) -> Duration {
+        self.target.saturating_sub(elapsed)
+    }
+
+    /// Runs `f` and sleeps until the target is reached, available with the
+    /// `std` feature
+    #[cfg(any(feature = "std", test))]
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> Timed<T> {
+        let start = Instant::now();
+        let value = f();
+        let elapsed = start.elapsed();
+
+        // Sleeping can end early on some platforms, so sleep until the target
+        // is reached as measured by the monotonic clock
+        let mut remaining = self.remaining(elapsed);
+        while !remaining.is_zero() {
+            std::thread::sleep(remaining);
+            remaining = self.remaining(start.elapsed());
+        }
+
+        Timed { value, elapsed }
+    }
+}
+
+/// The result of [`LatencyTarget::run`]
+#[cfg(any(feature = "std", test))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Timed<T> {
+    /// The value returned by the function
+    pub value: T,
+    /// The processing time of the function before padding
+    ///
+    /// If it exceeds the target, the processing time wasn't hidden and the
+    /// target should be raised.
+    pub elapsed: Duration,
+}
@@ -0,0 +1,211 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Appending credential record changes to a transparency log
+//!
+//! A [`RecordLog`] receives a commitment to each password file the server is
+//! about to store, together with its credential identifier. It is meant to
+//! be backed by an append-only Merkle tree, e.g. a [Certificate
+//! Transparency][RFC6962]-style log, so that auditors and clients can check
+//! that the record of a credential identifier was only ever replaced through a
+//! registration, and the server can't substitute records unnoticed.
+//!
+//! The log is called by
+//! [`ServerRegistration::finish_with_record_log`](crate::ServerRegistration::finish_with_record_log)
+//! and by a [`ProtocolDriver`](crate::driver::ProtocolDriver) created with
+//! [`ProtocolDriver::server_with_record_log`](crate::driver::ProtocolDriver::server_with_record_log).
+//! Other flows, e.g. the
+//! [`IdempotentRegistration::Store`](crate::idempotency::IdempotentRegistration::Store)
+//! outcome of a registration with a token, can call [`append`] before storing
+//! the password file. Upgrading the stored format with
+//! [`ServerRegistration::deserialize_versioned`](crate::ServerRegistration::deserialize_versioned)
+//! doesn't change the commitment, so it needs no entry.
+//!
+//! The commitment is computed by [`record_commitment`], which anyone holding
+//! the password file, such as an auditor, can recompute.
+//!
+//! [RFC6962]: https://www.rfc-editor.org/rfc/rfc6962
+
+use core::ops::Add;
+#[cfg(any(feature = "std", test))]
+use std::vec::Vec;
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::{Digest, Output, OutputSizeUser};
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U2, U256};
+use generic_array::ArrayLength;
+
+use crate::ciphersuite::{CipherSuite, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, OutputSize, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::MacLen;
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::RegistrationUploadLen;
+use crate::serialization::Input;
+use crate::{ServerRegistration, KEYED_RECORD_VERSION, RECORD_VERSION};
+
+static STR_RECORD_COMMITMENT: &[u8] = b"OPAQUE-RecordCommitment";
+
+/// Receives the commitments of password files before they are stored, see the
+/// [module documentation](self)
+///
+/// Implemented for closures taking the same arguments as
+/// [`append`](Self::append).
+pub trait RecordLog {
+    /// Appends `commitment` to the log as the new record of
+    /// `credential_identifier`, and returns `true` on success
+    ///
+    /// If `false` is returned, the flow fails with
+    /// [`ProtocolError::RecordLogError`], and the password file must not be
+    /// stored, as auditors would consider it substituted.
+    fn append(&mut self, credential_identifier: &[u8], commitment: &[u8]) -> bool;
+}
+
+impl<F: FnMut(&[u8], &[u8]) -> bool> RecordLog for F {
+    fn append(&mut self, credential_identifier: &[u8], commitment: &[u8]) -> bool {
+        self(credential_identifier, commitment)
+    }
+}
+
+/// An in-memory [`RecordLog`] keeping every entry in order, available with the
+/// `std` feature
+///
+/// This is meant for tests and for collecting the entries before writing them
+/// to a log of the application's own.
+#[cfg(any(feature = "std", test))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryRecordLog {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[cfg(any(feature = "std", test))]
+impl MemoryRecordLog {
+    /// Creates an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the credential identifiers and commitments appended so far,
+    /// oldest first
+    pub fn entries(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.entries
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing was appended
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl RecordLog for MemoryRecordLog {
+    fn append(&mut self, credential_identifier: &[u8], commitment: &[u8]) -> bool {
+        self.entries
+            .push((credential_identifier.to_vec(), commitment.to_vec()));
+        true
+    }
+}
+
+/// Returns the commitment to `password_file` stored under
+/// `credential_identifier`, the hash of both with a domain separation label
+///
+/// The password file is hashed in its
+/// [`ServerRegistration::serialize_versioned`] form, or in its
+/// [`ServerRegistration::serialize_keyed`] form if it has a per-user OPRF key,
+/// so that replacing only that key changes the commitment too.
+///
+/// # Errors
+/// [`ProtocolError::SerializationError`] if `credential_identifier` is longer
+/// than [`u16::MAX`]
+pub fn record_commitment<CS: CipherSuite>(
+    credential_identifier: &[u8],
+    password_file: &ServerRegistration<CS>,
+) -> Result<Output<OprfHash<CS>>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+{
+    let credential_identifier = Input::<U2>::from(credential_identifier)?;
+    let mut hasher = OprfHash::<CS>::new_with_prefix(STR_RECORD_COMMITMENT);
+    for chunk in credential_identifier.iter() {
+        hasher.update(chunk);
+    }
+    // The same bytes as `serialize_keyed` or `serialize_versioned`, without
+    // requiring their length bounds
+    match &password_file.oprf_key {
+        Some(oprf_key) => {
+            hasher.update([KEYED_RECORD_VERSION]);
+            hasher.update(password_file.serialize());
+            hasher.update(oprf_key.as_slice());
+        }
+        None => {
+            hasher.update([RECORD_VERSION]);
+            hasher.update(password_file.serialize());
+        }
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Appends the [`record_commitment`] of `password_file` stored under
+/// `credential_identifier` to `log`
+///
+/// # Errors
+/// - [`ProtocolError::SerializationError`] if `credential_identifier` is longer
+///   than [`u16::MAX`]
+/// - [`ProtocolError::RecordLogError`] if `log` failed to append the entry
+pub fn append<CS: CipherSuite, L: RecordLog + ?Sized>(
+    log: &mut L,
+    credential_identifier: &[u8],
+    password_file: &ServerRegistration<CS>,
+) -> Result<(), ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // Envelope: (Nonce + Mac) + AppData
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8>,
+    // RegistrationUpload: (KePk + Hash) + Envelope
+    <CS::KeGroup as KeGroup>::PkLen: Add<OutputSize<OprfHash<CS>>>,
+    Sum<<CS::KeGroup as KeGroup>::PkLen, OutputSize<OprfHash<CS>>>:
+        ArrayLength<u8> + Add<EnvelopeLen<CS>>,
+    RegistrationUploadLen<CS>: ArrayLength<u8>,
+{
+    let commitment = record_commitment(credential_identifier, password_file)?;
+
+    if log.append(credential_identifier, &commitment) {
+        Ok(())
+    } else {
+        Err(ProtocolError::RecordLogError)
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Splitting the server across two services, neither of which can mount an
+//! offline dictionary attack on its own
+//!
+//! An offline dictionary attack on a credential record requires the OPRF key
+//! of the credential. In the two-server mode, that key is the sum of the keys
+//! derived for the credential identifier from the OPRF seeds of two
+//! [`ServerSetup`]s, each held by a different service:
+//!
+//! - The front-end holds its [`ServerSetup`], including the key pair of the
+//!   server, and the credential records with their envelopes and masking keys.
+//!   It talks to the clients and masks the credential responses.
+//! - The OPRF server holds its own [`ServerSetup`], of which only the OPRF seed
+//!   is used, and never sees a credential record. It evaluates the blinded
+//!   elements forwarded by the front-end, e.g. with `oprf::evaluate_request` of
+//!   the `oprf-http` feature.
+//!
+//! The front-end sends [`EvaluationRequest`](crate::oprf::EvaluationRequest)s
+//! to the OPRF server through an [`OprfEvaluator`], and [`evaluate`] adds the
+//! evaluation of the OPRF server to its own. Clients are unaffected, as the
+//! combined evaluation is an evaluation with the summed key:
+//!
+//! ```ignore
+//! // Registration
+//! let evaluation_element = two_server::evaluate(&server_setup, &oprf_server, &registration_request.serialize(), credential_identifier).await?;
+//! let server_registration_start_result = ServerRegistration::start_with_evaluation(&server_setup, &evaluation_element)?;
+//!
+//! // Login
+//! let builder = CredentialResponseBuilder::new(&mut OsRng, &server_setup, password_file, credential_request, credential_identifier, params)?;
+//! let evaluation_element = two_server::evaluate(&server_setup, &oprf_server, &builder.blinded_element(), credential_identifier).await?;
+//! let server_login_start_result = builder.finish(&mut OsRng, &server_setup, &evaluation_element)?;
+//! ```
+//!
+//! [`ServerRegistration::start`](crate::ServerRegistration::start) and
+//! [`ServerLogin::start`](crate::ServerLogin::start) only evaluate with the
+//! key of the front-end, so credentials registered in the two-server mode
+//! can't be used with them and vice versa. The OPRF server still answers every
+//! request on its own, so it should only accept requests from the front-end
+//! and rate-limit them like login attempts.
+
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, U256};
+use generic_array::GenericArray;
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, ProxyHash};
+use crate::keypair::SecretKey;
+use crate::oprf::OprfEvaluator;
+use crate::ServerSetup;
+
+/// Evaluates the OPRF on the serialized `blinded_element` with the key of
+/// `credential_identifier` of both the front-end, holding `server_setup`, and
+/// `oprf_server`, and returns the serialized combined evaluation
+///
+/// # Errors
+/// [`ProtocolError::LibraryError`] if `blinded_element` or the evaluation of
+/// `oprf_server` is not a valid element of the group
+pub async fn evaluate<CS: CipherSuite, S: SecretKey<CS::KeGroup>, E: OprfEvaluator<CS>>(
+    server_setup: &ServerSetup<CS, S>,
+    oprf_server: &E,
+    blinded_element: &[u8],
+    credential_identifier: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError<E::Error>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let first = server_setup
+        .evaluate_oprf(blinded_element, credential_identifier)
+        .map_err(ProtocolError::into_custom)?;
+    let second = oprf_server
+        .evaluate(blinded_element, credential_identifier)
+        .await?;
+
+    combine_evaluations::<CS>(&first, &second).map_err(ProtocolError::into_custom)
+}
+
+/// Adds two serialized evaluations of the same blinded element, which gives
+/// the evaluation with the sum of both keys
+///
+/// # Errors
+/// [`ProtocolError::LibraryError`] if an evaluation is not a valid element of
+/// the group or is the identity element
+pub fn combine_evaluations<CS: CipherSuite>(
+    first: &[u8],
+    second: &[u8],
+) -> Result<GenericArray<u8, <OprfGroup<CS> as Group>::ElemLen>, ProtocolError>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+{
+    let first = OprfGroup::<CS>::deserialize_elem(first)?;
+    let second = OprfGroup::<CS>::deserialize_elem(second)?;
+
+    Ok(OprfGroup::<CS>::serialize_elem(first + &second))
+}
@@ -8,6 +8,39 @@
 
 //! Utility functions.
 
+use generic_array::GenericArray;
+use rand::RngCore;
+use voprf::Group;
+use zeroize::Zeroize;
+
+use crate::errors::ProtocolError;
+
+/// Fills `dest` from `rng`, reporting a failure of the RNG as
+/// [`ProtocolError::RandomnessError`] instead of panicking.
+pub(crate) fn fill_random<R: RngCore>(rng: &mut R, dest: &mut [u8]) -> Result<(), ProtocolError> {
+    rng.try_fill_bytes(dest)
+        .map_err(|_| ProtocolError::RandomnessError)
+}
+
+/// Samples a random non-zero scalar of the OPRF group `G` like
+/// [`Group::random_scalar`], but through [`fill_random`].
+///
+/// Rejecting the byte strings which aren't a canonical encoding keeps the
+/// distribution uniform, and also makes the scalar depend exactly on the bytes
+/// drawn from `rng`, which the test vectors rely on.
+pub(crate) fn random_scalar<G: Group, R: RngCore>(rng: &mut R) -> Result<G::Scalar, ProtocolError> {
+    let mut bytes = GenericArray::<_, G::ScalarLen>::default();
+
+    loop {
+        fill_random(rng, &mut bytes)?;
+
+        if let Ok(scalar) = G::deserialize_scalar(&bytes) {
+            bytes.zeroize();
+            break Ok(scalar);
+        }
+    }
+}
+
 #[cfg(test)]
 pub(crate) fn test_zeroize_on_drop<T: Sized>(value: &mut T) {
     drop_manually(value);
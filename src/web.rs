@@ -0,0 +1,199 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! Extractors and responders for serving OPAQUE over HTTP with axum, available
+//! with the `axum` feature
+//!
+//! [`Opaque`] extracts any protocol message from the raw bytes of a request
+//! body, and turns the messages sent by the server into responses with the
+//! serialized message as body. Most deployments implement the same endpoints,
+//! which are named in [`paths`]:
+//!
+//! ```ignore
+//! async fn login_start(
+//!     State(server): State<Server>,
+//!     Opaque(credential_request): Opaque<CredentialRequest<DefaultCipherSuite>>,
+//! ) -> Result<Opaque<CredentialResponse<DefaultCipherSuite>>, StatusCode> {
+//!     let server_login_start_result = ServerLogin::start(/* ... */, credential_request, /* ... */)
+//!         .map_err(|_| StatusCode::BAD_REQUEST)?;
+//!     // Keep `server_login_start_result.state` until the login is finished
+//!     Ok(Opaque(server_login_start_result.message))
+//! }
+//!
+//! let app = Router::new()
+//!     .route(paths::REGISTRATION_START, post(registration_start))
+//!     .route(paths::REGISTRATION_FINISH, post(registration_finish))
+//!     .route(paths::LOGIN_START, post(login_start))
+//!     .route(paths::LOGIN_FINISH, post(login_finish));
+//! ```
+//!
+//! Bodies are read up to the limit configured for the router, e.g. with
+//! `DefaultBodyLimit`, and rejected with `400 Bad Request` unless they have
+//! the exact length of the message.
+
+use core::ops::Add;
+use std::vec::Vec;
+
+use axum_core::extract::rejection::BytesRejection;
+use axum_core::extract::{FromRequest, Request};
+use axum_core::response::{IntoResponse, Response};
+use bytes::Bytes;
+use digest::core_api::{BlockSizeUser, CoreProxy};
+use digest::OutputSizeUser;
+use generic_array::typenum::{IsLess, IsLessOrEqual, Le, NonZero, Sum, U256};
+use generic_array::ArrayLength;
+use http::header::CONTENT_TYPE;
+use http::{HeaderValue, StatusCode};
+use voprf::Group;
+
+use crate::ciphersuite::{CipherSuite, OprfGroup, OprfHash};
+use crate::envelope::EnvelopeLen;
+use crate::errors::ProtocolError;
+use crate::hash::{Hash, ProxyHash};
+use crate::key_exchange::group::KeGroup;
+use crate::key_exchange::traits::{Ke2MessageLen, MacLen};
+use crate::key_exchange::tripledh::NonceLen;
+use crate::messages::CredentialResponseWithoutKeLen;
+use crate::opaque::MaskedResponseLen;
+use crate::{
+    CredentialResponse, CredentialResponseLen, RegistrationResponse, RegistrationResponseLen,
+    RetrievalResponse, RetrievalResponseLen,
+};
+
+/// The media type of request and response bodies
+pub const CONTENT_TYPE_OPAQUE: &str = "application/octet-stream";
+
+/// The paths of the endpoints implemented by most deployments, each of which
+/// accepts a `POST` request with the message of the client as body
+pub mod paths {
+    /// Receives a [`RegistrationRequest`](crate::RegistrationRequest) and
+    /// returns a [`RegistrationResponse`](crate::RegistrationResponse)
+    pub const REGISTRATION_START: &str = "/opaque/registration/start";
+    /// Receives a [`RegistrationUpload`](crate::RegistrationUpload)
+    pub const REGISTRATION_FINISH: &str = "/opaque/registration/finish";
+    /// Receives a [`CredentialRequest`](crate::CredentialRequest) and returns
+    /// a [`CredentialResponse`](crate::CredentialResponse)
+    pub const LOGIN_START: &str = "/opaque/login/start";
+    /// Receives a [`CredentialFinalization`](crate::CredentialFinalization)
+    pub const LOGIN_FINISH: &str = "/opaque/login/finish";
+}
+
+/// A protocol message in the body of a request or response
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Opaque<M>(pub M);
+
+/// The rejection of the [`Opaque`] extractor
+#[derive(Debug)]
+pub enum OpaqueRejection {
+    /// The body could not be read
+    Body(BytesRejection),
+    /// The body does not contain a valid message
+    Message(ProtocolError),
+}
+
+impl IntoResponse for OpaqueRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Body(rejection) => rejection.into_response(),
+            Self::Message(_) => (StatusCode::BAD_REQUEST, "Invalid OPAQUE message").into_response(),
+        }
+    }
+}
+
+impl<S: Send + Sync, M> FromRequest<S> for Opaque<M>
+where
+    M: for<'a> TryFrom<&'a [u8], Error = ProtocolError>,
+{
+    type Rejection = OpaqueRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(OpaqueRejection::Body)?;
+
+        M::try_from(&body)
+            .map(Self)
+            .map_err(OpaqueRejection::Message)
+    }
+}
+
+impl<CS: CipherSuite> IntoResponse for Opaque<RegistrationResponse<CS>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // RegistrationResponse: KgPk + KePk
+    <OprfGroup<CS> as Group>::ElemLen: Add<<CS::KeGroup as KeGroup>::PkLen>,
+    RegistrationResponseLen<CS>: ArrayLength<u8>,
+{
+    fn into_response(self) -> Response {
+        respond(self.0.serialize().to_vec())
+    }
+}
+
+impl<CS: CipherSuite> IntoResponse for Opaque<CredentialResponse<CS>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // CredentialResponse: CredentialResponseWithoutKeLen + Ke2Message
+    CredentialResponseWithoutKeLen<CS>: Add<Ke2MessageLen<CS>>,
+    CredentialResponseLen<CS>: ArrayLength<u8>,
+{
+    fn into_response(self) -> Response {
+        respond(self.0.serialize().to_vec())
+    }
+}
+
+impl<CS: CipherSuite> IntoResponse for Opaque<RetrievalResponse<CS>>
+where
+    <OprfHash<CS> as OutputSizeUser>::OutputSize:
+        IsLess<U256> + IsLessOrEqual<<OprfHash<CS> as BlockSizeUser>::BlockSize>,
+    OprfHash<CS>: Hash,
+    <OprfHash<CS> as CoreProxy>::Core: ProxyHash,
+    <<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+    Le<<<OprfHash<CS> as CoreProxy>::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+    // CredentialResponseWithoutKeLen: (KgPk + Nonce) + MaskedResponse
+    <OprfGroup<CS> as Group>::ElemLen: Add<NonceLen>,
+    Sum<<OprfGroup<CS> as Group>::ElemLen, NonceLen>: ArrayLength<u8> + Add<MaskedResponseLen<CS>>,
+    CredentialResponseWithoutKeLen<CS>: ArrayLength<u8>,
+    // MaskedResponse: ((Nonce + Mac) + AppData) + KePk
+    NonceLen: Add<MacLen<CS>>,
+    Sum<NonceLen, MacLen<CS>>: ArrayLength<u8> + Add<CS::AppDataLen>,
+    EnvelopeLen<CS>: ArrayLength<u8> + Add<<CS::KeGroup as KeGroup>::PkLen>,
+    MaskedResponseLen<CS>: ArrayLength<u8>,
+    // RetrievalResponse: CredentialResponseWithoutKeLen
+    RetrievalResponseLen<CS>: ArrayLength<u8>,
+{
+    fn into_response(self) -> Response {
+        respond(self.0.serialize().to_vec())
+    }
+}
+
+fn respond(body: Vec<u8>) -> Response {
+    (
+        [(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_OPAQUE))],
+        body,
+    )
+        .into_response()
+}
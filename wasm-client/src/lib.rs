@@ -0,0 +1,228 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed
+// licenses.
+
+//! A WebAssembly module exporting the client side of OPAQUE, built with the
+//! `wasm-client` feature
+//!
+//! Its size is checked by `scripts/wasm_size.sh`, so that server code paths or
+//! large dependencies pulled into the client build are caught. Every function
+//! takes its inputs as pointer and length pairs, writes its output to
+//! `out_ptr` and returns the number of bytes written, or a negative number on
+//! failure. Randomness is derived from a 32-byte seed supplied by the caller,
+//! which should be taken from `crypto.getRandomValues()`.
+
+use std::slice;
+
+use argon2::Argon2;
+use generic_array::typenum::U0;
+use opaque_ke::{
+    CipherSuite, ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialResponse, RegistrationResponse,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+struct DefaultCipherSuite;
+
+impl CipherSuite for DefaultCipherSuite {
+    type OprfCs = opaque_ke::Ristretto255;
+    type KeGroup = opaque_ke::Ristretto255;
+    type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+
+    type Ksf = Argon2<'static>;
+    type AppDataLen = U0;
+}
+
+const ERROR_PROTOCOL: isize = -1;
+const ERROR_BUFFER_TOO_SMALL: isize = -2;
+
+/// Allocates a buffer of `len` bytes to pass inputs and outputs
+#[no_mangle]
+pub extern "C" fn opaque_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// Frees a buffer returned by [`opaque_alloc`]
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`opaque_alloc`] with the same `len`.
+#[no_mangle]
+pub unsafe extern "C" fn opaque_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Starts a registration, writing the client state followed by the
+/// registration request
+///
+/// # Safety
+///
+/// All pointers must be valid for their lengths, `seed_ptr` for 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn client_registration_start(
+    password_ptr: *const u8,
+    password_len: usize,
+    seed_ptr: *const u8,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> isize {
+    let mut rng = rng(seed_ptr);
+    let password = input(password_ptr, password_len);
+
+    match ClientRegistration::<DefaultCipherSuite>::start(&mut rng, password) {
+        Ok(result) => output(
+            &[&result.state.serialize(), &result.message.serialize()],
+            out_ptr,
+            out_len,
+        ),
+        Err(_) => ERROR_PROTOCOL,
+    }
+}
+
+/// Finishes a registration, writing the registration upload followed by the
+/// export key
+///
+/// # Safety
+///
+/// All pointers must be valid for their lengths, `seed_ptr` for 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn client_registration_finish(
+    state_ptr: *const u8,
+    state_len: usize,
+    password_ptr: *const u8,
+    password_len: usize,
+    response_ptr: *const u8,
+    response_len: usize,
+    seed_ptr: *const u8,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> isize {
+    let mut rng = rng(seed_ptr);
+    let result = ClientRegistration::<DefaultCipherSuite>::deserialize(input(state_ptr, state_len))
+        .and_then(|state| {
+            let response = RegistrationResponse::deserialize(input(response_ptr, response_len))?;
+            state.finish(
+                &mut rng,
+                input(password_ptr, password_len),
+                response,
+                ClientRegistrationFinishParameters::default(),
+            )
+        });
+
+    match result {
+        Ok(result) => output(
+            &[&result.message.serialize(), &result.export_key],
+            out_ptr,
+            out_len,
+        ),
+        Err(_) => ERROR_PROTOCOL,
+    }
+}
+
+/// Starts a login, writing the client state followed by the credential
+/// request
+///
+/// # Safety
+///
+/// All pointers must be valid for their lengths, `seed_ptr` for 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn client_login_start(
+    password_ptr: *const u8,
+    password_len: usize,
+    seed_ptr: *const u8,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> isize {
+    let mut rng = rng(seed_ptr);
+    let password = input(password_ptr, password_len);
+
+    match ClientLogin::<DefaultCipherSuite>::start(&mut rng, password) {
+        Ok(result) => output(
+            &[&result.state.serialize(), &result.message.serialize()],
+            out_ptr,
+            out_len,
+        ),
+        Err(_) => ERROR_PROTOCOL,
+    }
+}
+
+/// Finishes a login, writing the credential finalization followed by the
+/// session key and the export key
+///
+/// # Safety
+///
+/// All pointers must be valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn client_login_finish(
+    state_ptr: *const u8,
+    state_len: usize,
+    password_ptr: *const u8,
+    password_len: usize,
+    response_ptr: *const u8,
+    response_len: usize,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> isize {
+    let result = ClientLogin::<DefaultCipherSuite>::deserialize(input(state_ptr, state_len))
+        .and_then(|state| {
+            let response = CredentialResponse::deserialize(input(response_ptr, response_len))?;
+            state.finish(
+                input(password_ptr, password_len),
+                response,
+                ClientLoginFinishParameters::default(),
+            )
+        });
+
+    match result {
+        Ok(result) => output(
+            &[
+                &result.message.serialize(),
+                &result.session_key,
+                &result.export_key,
+            ],
+            out_ptr,
+            out_len,
+        ),
+        Err(_) => ERROR_PROTOCOL,
+    }
+}
+
+unsafe fn input<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+unsafe fn rng(seed_ptr: *const u8) -> StdRng {
+    let mut seed = [0; 32];
+    seed.copy_from_slice(input(seed_ptr, 32));
+    StdRng::from_seed(seed)
+}
+
+unsafe fn output(parts: &[&[u8]], out_ptr: *mut u8, out_len: usize) -> isize {
+    let len = parts.iter().map(|part| part.len()).sum();
+
+    if len > out_len {
+        return ERROR_BUFFER_TOO_SMALL;
+    }
+
+    let out = slice::from_raw_parts_mut(out_ptr, len);
+    let mut offset = 0;
+
+    for part in parts {
+        out[offset..offset + part.len()].copy_from_slice(part);
+        offset += part.len();
+    }
+
+    len as isize
+}